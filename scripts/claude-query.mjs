@@ -112,6 +112,7 @@ async function main() {
       cwd,
       maxTurns: config.maxTurns,
       permissionMode: config.permissionMode,
+      ...(config.model && { model: config.model }),
       systemPrompt: { type: 'preset', preset: 'claude_code' },
       ...(resumeSessionId && { resume: resumeSessionId })
     };
@@ -125,6 +126,15 @@ async function main() {
     const baseTools = ['Read', 'Write', 'Edit', 'Bash', 'Glob', 'Grep',
                        'WebSearch', 'WebFetch', 'Task', 'TodoWrite', 'Skill'];
     options.allowedTools = config.allowedTools || baseTools;
+    if (config.disallowedTools?.length > 0) {
+      options.disallowedTools = config.disallowedTools;
+    }
+
+    // Quick questions ask for no session file; SDKs without the option still write one,
+    // which mensa deletes once the query finishes
+    if (config.persistSession === false) {
+      options.persistSession = false;
+    }
 
     // Only add mcpServers if there are any
     if (Object.keys(mcpServers).length > 0) {
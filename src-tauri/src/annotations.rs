@@ -0,0 +1,476 @@
+// mensa - Annotations Module
+// Finds TODO/FIXME-style comments across a workspace and turns a selection of them into a prompt
+
+use crate::cancel::{self, ReadError};
+use crate::{workspace, AppState};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+const DEFAULT_KINDS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+
+/// Files larger than this are skipped (generated and vendored code, data dumps)
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Annotations collected at most; the scan stops there
+const MAX_ANNOTATIONS: usize = 20_000;
+
+/// Files scanned between `code-annotations-batch` events
+const BATCH_FILES: usize = 500;
+
+/// A cached scan older than this is redone
+const SCAN_TTL: Duration = Duration::from_secs(120);
+
+/// Leading bytes checked for NUL to skip binary files
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// One annotation comment
+#[derive(Debug, Clone, Serialize, serde::Deserialize)]
+pub struct CodeAnnotation {
+    /// Workspace-relative, `/`-separated
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    pub kind: String,
+    /// From `TODO(author)`
+    pub author: Option<String>,
+    pub text: String,
+}
+
+/// A file's annotations, in line order
+#[derive(Debug, Clone, Serialize)]
+pub struct FileAnnotations {
+    pub path: String,
+    pub annotations: Vec<CodeAnnotation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnotationScan {
+    pub files: Vec<FileAnnotations>,
+    /// Annotation count per kind
+    pub totals: BTreeMap<String, usize>,
+    pub total: usize,
+    pub files_scanned: usize,
+    /// The scan stopped at MAX_ANNOTATIONS
+    pub truncated: bool,
+    /// Served from the cache; no batches were emitted
+    pub cached: bool,
+}
+
+struct CachedScan {
+    files: Vec<FileAnnotations>,
+    files_scanned: usize,
+    truncated: bool,
+    scanned_at: Instant,
+}
+
+/// Workspace root and the sorted kinds scanned for
+type ScanKey = (PathBuf, Vec<String>);
+
+/// Complete scans per workspace root and kind set, dropped when the workspace's files change
+#[derive(Default)]
+pub struct AnnotationCache {
+    scans: Mutex<HashMap<ScanKey, Arc<CachedScan>>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Matches a kind right after a comment marker (`//`, `#`, `--`, `;`, `/*`, a leading `*`,
+/// `<!--`, `%`, `REM`), with an optional `(author)` and the rest of the line as text
+fn build_pattern(kinds: &[String]) -> Result<Regex, String> {
+    let alternatives: Vec<String> = kinds.iter().map(|k| regex::escape(k)).collect();
+    Regex::new(&format!(
+        r"(?://+!?|#+|--+|;+|/\*+!?|^\s*\*+|<!--|%+|\bREM\b)\s*@?({})\b(?:\(([^)]*)\))?[:!]?\s*(.*)$",
+        alternatives.join("|")
+    ))
+    .map_err(|e| format!("Invalid annotation kinds: {}", e))
+}
+
+fn build_globs(globs: &[String]) -> Result<Option<GlobSet>, String> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in globs {
+        let glob = Glob::new(pattern).map_err(|e| format!("Invalid include glob '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| format!("Failed to compile include globs: {}", e))
+}
+
+/// Annotations on one line of source
+fn parse_line(pattern: &Regex, path: &str, line_number: usize, line: &str) -> Option<CodeAnnotation> {
+    let captures = pattern.captures(line)?;
+    let text = captures.get(3).map_or("", |m| m.as_str());
+    let text = text.trim().trim_end_matches("*/").trim_end_matches("-->").trim();
+    Some(CodeAnnotation {
+        path: path.to_string(),
+        line: line_number,
+        kind: captures[1].to_string(),
+        author: captures
+            .get(2)
+            .map(|m| m.as_str().trim().to_string())
+            .filter(|a| !a.is_empty()),
+        text: text.to_string(),
+    })
+}
+
+/// Annotations of a file, or None when it's too large, unreadable or binary
+fn scan_file(pattern: &Regex, root: &Path, path: &Path) -> Option<FileAnnotations> {
+    let relative = path.strip_prefix(root).ok()?.to_string_lossy().replace('\\', "/");
+    let mut file = std::fs::File::open(path).ok()?;
+    if file.metadata().ok()?.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).ok()?;
+    if bytes[..bytes.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    let content = String::from_utf8_lossy(&bytes);
+    let annotations: Vec<CodeAnnotation> = content
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| parse_line(pattern, &relative, i + 1, line))
+        .collect();
+    (!annotations.is_empty()).then_some(FileAnnotations {
+        path: relative,
+        annotations,
+    })
+}
+
+fn is_included(globs: Option<&GlobSet>, path: &str) -> bool {
+    globs.is_none_or(|globs| globs.is_match(path))
+}
+
+/// Walk the workspace (gitignore-aware) and scan every file, emitting what was found in
+/// batches. None once cancelled.
+fn scan_workspace(
+    app: &tauri::AppHandle,
+    root: &Path,
+    pattern: &Regex,
+    globs: Option<&GlobSet>,
+    token: &cancel::CancellationToken,
+    operation_id: Option<&str>,
+) -> Option<CachedScan> {
+    let walker = ignore::WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    let mut files = Vec::new();
+    let mut batch: Vec<FileAnnotations> = Vec::new();
+    let emit_batch = |batch: &mut Vec<FileAnnotations>| {
+        if !batch.is_empty() {
+            let _ = app.emit("code-annotations-batch", serde_json::json!({
+                "working_dir": root.to_string_lossy(),
+                "operation_id": operation_id,
+                "files": batch
+            }));
+            batch.clear();
+        }
+    };
+    let mut files_scanned = 0;
+    let mut count = 0;
+    let mut truncated = false;
+    for entry in walker.flatten() {
+        if token.is_cancelled() {
+            return None;
+        }
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        files_scanned += 1;
+        if let Some(found) = scan_file(pattern, root, entry.path()) {
+            count += found.annotations.len();
+            if is_included(globs, &found.path) {
+                batch.push(found.clone());
+            }
+            files.push(found);
+        }
+        if files_scanned % BATCH_FILES == 0 {
+            emit_batch(&mut batch);
+        }
+        if count >= MAX_ANNOTATIONS {
+            truncated = true;
+            break;
+        }
+    }
+    emit_batch(&mut batch);
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Some(CachedScan {
+        files,
+        files_scanned,
+        truncated,
+        scanned_at: Instant::now(),
+    })
+}
+
+fn result_from(scan: &CachedScan, globs: Option<&GlobSet>, cached: bool) -> AnnotationScan {
+    let files: Vec<FileAnnotations> = scan
+        .files
+        .iter()
+        .filter(|f| is_included(globs, &f.path))
+        .cloned()
+        .collect();
+    let mut totals = BTreeMap::new();
+    for annotation in files.iter().flat_map(|f| &f.annotations) {
+        *totals.entry(annotation.kind.clone()).or_insert(0) += 1;
+    }
+    AnnotationScan {
+        total: totals.values().sum(),
+        totals,
+        files,
+        files_scanned: scan.files_scanned,
+        truncated: scan.truncated,
+        cached,
+    }
+}
+
+/// Drop cached scans of workspaces containing any of these files
+pub fn mark_dirty(app: &tauri::AppHandle, files: &HashSet<PathBuf>) {
+    let state = app.state::<AppState>();
+    let mut scans = state.annotations.scans.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    scans.retain(|(root, _), _| !files.iter().any(|f| f.starts_with(root)));
+}
+
+/// A markdown block listing the annotations by file, ready to send as a prompt
+fn format_prompt(entries: &[CodeAnnotation]) -> String {
+    let mut by_file: BTreeMap<&str, Vec<&CodeAnnotation>> = BTreeMap::new();
+    for entry in entries {
+        by_file.entry(entry.path.as_str()).or_default().push(entry);
+    }
+    let mut prompt = format!(
+        "Address the following {} code annotation{}. Resolve each one and remove the comment once it's done.\n",
+        entries.len(),
+        if entries.len() == 1 { "" } else { "s" }
+    );
+    for (path, mut annotations) in by_file {
+        annotations.sort_by_key(|a| a.line);
+        prompt.push_str(&format!("\n## {}\n", path));
+        for annotation in annotations {
+            let author = annotation
+                .author
+                .as_ref()
+                .map(|a| format!(" ({})", a))
+                .unwrap_or_default();
+            prompt.push_str(&format!("- Line {}: {}{}", annotation.line, annotation.kind, author));
+            if !annotation.text.is_empty() {
+                prompt.push_str(&format!(": {}", annotation.text));
+            }
+            prompt.push('\n');
+        }
+    }
+    prompt
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List annotation comments (TODO, FIXME, HACK, XXX by default) in the workspace, grouped by
+/// file with totals per kind. Ignored, binary and files over 1 MiB are skipped; `include_globs`
+/// narrows the paths returned. Results found so far arrive as `code-annotations-batch` events
+/// while a fresh scan runs; with an `operation_id` it can be stopped by `cancel_operation`.
+#[tauri::command]
+pub async fn scan_code_annotations(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+    kinds: Option<Vec<String>>,
+    include_globs: Option<Vec<String>>,
+    operation_id: Option<String>,
+) -> Result<AnnotationScan, ReadError> {
+    let root = workspace::canonical_dir(&working_dir)?;
+    let mut kinds: Vec<String> = kinds
+        .filter(|k| !k.is_empty())
+        .unwrap_or_else(|| DEFAULT_KINDS.iter().map(|k| k.to_string()).collect());
+    kinds.sort();
+    kinds.dedup();
+    let pattern = build_pattern(&kinds)?;
+    let globs = build_globs(&include_globs.unwrap_or_default())?;
+
+    let key = (root.clone(), kinds);
+    let cached = state
+        .annotations
+        .scans
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&key)
+        .filter(|scan| scan.scanned_at.elapsed() < SCAN_TTL)
+        .cloned();
+    if let Some(scan) = cached {
+        return Ok(result_from(&scan, globs.as_ref(), true));
+    }
+
+    let registration = operation_id.as_deref().map(|id| cancel::register(&app, id));
+    let token = registration.as_ref().map(|r| r.token().clone()).unwrap_or_default();
+    let scan_app = app.clone();
+    let scan_root = root.clone();
+    let scan_globs = globs.clone();
+    let scan = tokio::task::spawn_blocking(move || {
+        scan_workspace(&scan_app, &scan_root, &pattern, scan_globs.as_ref(), &token, operation_id.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Failed to scan annotations: {}", e))?;
+    let Some(scan) = scan else {
+        return Err(match registration {
+            Some(registration) => ReadError::Cancelled {
+                operation_id: registration.operation_id().to_string(),
+            },
+            None => format!("Annotation scan of {} was stopped", working_dir).into(),
+        });
+    };
+
+    let scan = Arc::new(scan);
+    let result = result_from(&scan, globs.as_ref(), false);
+    state
+        .annotations
+        .scans
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(key, scan);
+    Ok(result)
+}
+
+/// Format selected annotations as a prompt asking for them to be resolved, grouped by file
+#[tauri::command]
+pub async fn annotations_to_prompt(entries: Vec<CodeAnnotation>) -> Result<String, String> {
+    if entries.is_empty() {
+        return Err("No annotations selected".to_string());
+    }
+    Ok(format_prompt(&entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture_path;
+
+    /// line, kind, author, text
+    type Row<'a> = (usize, &'a str, Option<&'a str>, &'a str);
+    type Found = (usize, String, Option<String>, String);
+
+    fn default_pattern() -> Regex {
+        build_pattern(&DEFAULT_KINDS.iter().map(|k| k.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    /// Each annotation in a fixture file
+    fn found(file: &str) -> Vec<Found> {
+        let root = fixture_path("annotations");
+        scan_file(&default_pattern(), &root, &root.join(file))
+            .map(|f| {
+                assert_eq!(f.path, file);
+                f.annotations.into_iter().map(|a| (a.line, a.kind, a.author, a.text)).collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn expected(rows: &[Row]) -> Vec<Found> {
+        rows.iter()
+            .map(|(line, kind, author, text)| (*line, kind.to_string(), author.map(String::from), text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn comment_styles_across_languages() {
+        let cases: &[(&str, &[Row])] = &[
+            (
+                "lib.rs",
+                &[
+                    (2, "TODO", None, "handle CRLF input"),
+                    (3, "FIXME", Some("ana"), "the doc example panics"),
+                    (6, "XXX", None, "wrong count after a merge"),
+                    (7, "HACK", None, "skip the BOM"),
+                    (9, "TODO", Some("li"), "split this function"),
+                ],
+            ),
+            (
+                "app.py",
+                &[
+                    (1, "TODO", None, "read the port from the environment"),
+                    (3, "FIXME", None, "log instead of print"),
+                    (4, "HACK", Some("dev"), "monkeypatch until the fix lands"),
+                ],
+            ),
+            (
+                "query.sql",
+                &[(1, "TODO", None, "add an index on user_id"), (2, "FIXME", Some("db-team"), "full scan")],
+            ),
+            (
+                "index.html",
+                &[
+                    (2, "TODO", None, "move inline styles to the stylesheet"),
+                    (4, "FIXME", None, "broken link in the footer"),
+                ],
+            ),
+            ("build.bat", &[(2, "TODO", None, "support paths with spaces")]),
+            ("core.clj", &[(1, "TODO", Some("rich"), "memoize"), (2, "HACK", None, "identity for now")]),
+            (
+                "paper.tex",
+                &[(1, "TODO", None, "cite the original paper"), (2, "XXX", None, "numbers are from the old run")],
+            ),
+        ];
+        for (file, rows) in cases {
+            assert_eq!(found(file), expected(rows), "{}", file);
+        }
+    }
+
+    #[test]
+    fn kinds_outside_comments_or_words_are_not_annotations() {
+        let pattern = default_pattern();
+        for line in [
+            r#"let label = "TODO: not a comment";"#,
+            "<p>TODO list for the week</p>",
+            "// TODOS are tracked in the issue tracker",
+            "// todo: lowercase is prose",
+            "let url = \"https://example.com/TODO\";",
+        ] {
+            assert!(parse_line(&pattern, "x", 1, line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn only_the_requested_kinds_are_matched() {
+        let pattern = build_pattern(&["NOTE".to_string()]).unwrap();
+        assert!(parse_line(&pattern, "x", 1, "// TODO: not asked for").is_none());
+        let note = parse_line(&pattern, "x", 1, "# @NOTE(kim)! keep in sync with the server").unwrap();
+        assert_eq!((note.kind.as_str(), note.author.as_deref(), note.text.as_str()), ("NOTE", Some("kim"), "keep in sync with the server"));
+    }
+
+    #[test]
+    fn prompt_groups_the_selection_by_file_in_line_order() {
+        let root = fixture_path("annotations");
+        let mut entries = scan_file(&default_pattern(), &root, &root.join("query.sql")).unwrap().annotations;
+        entries.reverse();
+        entries.push(CodeAnnotation {
+            path: "build.bat".to_string(),
+            line: 2,
+            kind: "TODO".to_string(),
+            author: None,
+            text: String::new(),
+        });
+        assert_eq!(
+            format_prompt(&entries),
+            "Address the following 3 code annotations. Resolve each one and remove the comment once it's done.\n\
+             \n## build.bat\n- Line 2: TODO\n\
+             \n## query.sql\n- Line 1: TODO: add an index on user_id\n- Line 2: FIXME (db-team): full scan\n"
+        );
+    }
+}
@@ -0,0 +1,693 @@
+// mensa - App Data Module
+// Export and import of mensa's stores as one versioned zip, for moving to another machine
+
+use crate::progress::ProgressReporter;
+use crate::{fsutil, git, history, paths, presets, review_drafts, sensitive, settings, status_filters, templates, workspace, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+use std::io::{Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tauri::{Emitter, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Bumped whenever the bundle layout changes incompatibly
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+
+/// Largest single file accepted from a bundle
+const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Stores kept as one file in the app data dir
+const FILE_STORES: &[&str] = &[
+    "settings",
+    "presets",
+    "templates",
+    "history",
+    "statusFilters",
+    "sensitivePatterns",
+    "reviewDrafts",
+];
+
+/// One file per workspace in the app data dir
+const WORKSPACE_STATES: &str = "workspaceStates";
+
+/// mensa-meta.json sidecars next to each project's transcripts (bookmarks)
+const PROJECT_META: &str = "projectMeta";
+
+/// Fields that identify an item inside a JSON array, so merges match items rather than positions
+const IDENTITY_KEYS: &[&str] = &["id", "name", "queryId", "prUrl"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestStore {
+    pub name: String,
+    /// Bundle entry names belonging to this store
+    pub entries: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format_version: u32,
+    app_version: String,
+    created_at: i64,
+    stores: Vec<ManifestStore>,
+}
+
+/// One file read from a bundle
+struct BundleEntry {
+    store: String,
+    name: String,
+    content: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportReport {
+    pub path: String,
+    pub bytes: u64,
+    pub stores: Vec<ManifestStore>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportMode {
+    /// Keep local data and add what's missing; on conflicts the local value wins
+    Merge,
+    /// Overwrite each imported file with the bundle's copy
+    Replace,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportConflict {
+    pub file: String,
+    /// Dotted path of the differing value, e.g. "global[Plan]"
+    pub key: String,
+    /// "keptExisting" | "replaced"
+    pub resolution: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreImport {
+    pub store: String,
+    pub files_written: usize,
+    pub conflicts: Vec<ImportConflict>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub format_version: u32,
+    pub mode: ImportMode,
+    pub stores: Vec<StoreImport>,
+    /// Workspace paths referenced by imported data that don't exist on this machine
+    pub missing_workspaces: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn store_file_path(app: &tauri::AppHandle, store: &str) -> Result<PathBuf, String> {
+    match store {
+        "settings" => settings::settings_path(app),
+        "presets" => presets::presets_path(app),
+        "templates" => templates::templates_path(app),
+        "history" => history::history_path(app),
+        "statusFilters" => status_filters::filters_path(app),
+        "sensitivePatterns" => sensitive::patterns_path(app),
+        "reviewDrafts" => review_drafts::drafts_path(app),
+        _ => Err(format!("Unknown store: {}", store)),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Whether `name` is usable as one path component (no separators, no `.`/`..`)
+fn is_plain_component(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
+
+/// (bundle entry, local file) for every file of a store that exists locally
+fn local_store_files(app: &tauri::AppHandle, store: &str) -> Result<Vec<(String, PathBuf)>, String> {
+    match store {
+        WORKSPACE_STATES => {
+            let dir = workspace::workspace_state_dir(app)?;
+            let mut files: Vec<(String, PathBuf)> = std::fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json") && path.is_file())
+                .map(|path| (format!("app/workspace-state/{}", file_name(&path)), path))
+                .collect();
+            files.sort();
+            Ok(files)
+        }
+        PROJECT_META => {
+            let mut files: Vec<(String, PathBuf)> = std::fs::read_dir(paths::ClaudeHome::current()?.projects())
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path().join("mensa-meta.json"))
+                .filter(|path| path.is_file())
+                .filter_map(|path| {
+                    let project = file_name(path.parent()?);
+                    Some((format!("projects/{}/mensa-meta.json", project), path))
+                })
+                .collect();
+            files.sort();
+            Ok(files)
+        }
+        _ => {
+            let path = store_file_path(app, store)?;
+            Ok(if path.is_file() {
+                vec![(format!("app/{}", file_name(&path)), path)]
+            } else {
+                Vec::new()
+            })
+        }
+    }
+}
+
+/// Where a bundle entry lands locally; rejects entries that don't belong to the store
+fn local_path_for_entry(app: &tauri::AppHandle, store: &str, entry: &str) -> Result<PathBuf, String> {
+    let invalid = || format!("Entry '{}' does not belong to store '{}'", entry, store);
+    match store {
+        WORKSPACE_STATES => {
+            let name = entry.strip_prefix("app/workspace-state/").ok_or_else(invalid)?;
+            let stem = name.strip_suffix(".json").ok_or_else(invalid)?;
+            if stem.len() != 32 || !stem.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(invalid());
+            }
+            Ok(workspace::workspace_state_dir(app)?.join(name))
+        }
+        PROJECT_META => {
+            let project = entry
+                .strip_prefix("projects/")
+                .and_then(|rest| rest.strip_suffix("/mensa-meta.json"))
+                .filter(|project| is_plain_component(project))
+                .ok_or_else(invalid)?;
+            Ok(paths::ClaudeHome::current()?.projects().join(project).join("mensa-meta.json"))
+        }
+        _ => {
+            let path = store_file_path(app, store)?;
+            if entry != format!("app/{}", file_name(&path)) {
+                return Err(invalid());
+            }
+            Ok(path)
+        }
+    }
+}
+
+fn join_key(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// The identity field shared by every item of both arrays, if any
+fn identity_key(a: &[Value], b: &[Value]) -> Option<&'static str> {
+    IDENTITY_KEYS.iter().copied().find(|key| {
+        a.iter().chain(b).all(|item| item.get(*key).is_some_and(|v| v.is_string()))
+    })
+}
+
+/// Merge `incoming` into `existing`, keeping existing values on conflict and recording where.
+/// Objects merge by key, arrays of identified items by identity, arrays of scalars by union.
+fn merge_values(existing: &mut Value, incoming: Value, path: &str, conflicts: &mut Vec<String>) {
+    if *existing == incoming {
+        return;
+    }
+    match (existing, incoming) {
+        (Value::Object(existing), Value::Object(incoming)) => {
+            for (key, value) in incoming {
+                match existing.get_mut(&key) {
+                    Some(current) => merge_values(current, value, &join_key(path, &key), conflicts),
+                    None => {
+                        existing.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(existing), Value::Array(incoming)) => {
+            if let Some(id) = identity_key(existing, &incoming) {
+                for item in incoming {
+                    let item_id = item[id].as_str().unwrap_or_default().to_string();
+                    match existing.iter().find(|e| e[id].as_str() == Some(item_id.as_str())) {
+                        Some(current) if *current != item => conflicts.push(format!("{}[{}]", path, item_id)),
+                        Some(_) => {}
+                        None => existing.push(item),
+                    }
+                }
+            } else if existing.iter().chain(&incoming).all(|v| !v.is_object() && !v.is_array()) {
+                for item in incoming {
+                    if !existing.contains(&item) {
+                        existing.push(item);
+                    }
+                }
+            } else {
+                conflicts.push(path.to_string());
+            }
+        }
+        _ => conflicts.push(if path.is_empty() { "(file)".to_string() } else { path.to_string() }),
+    }
+}
+
+/// Merge two JSON or JSONL store files; returns the merged bytes and the conflicting keys
+fn merge_file(entry: &str, existing: &[u8], incoming: &[u8]) -> Result<(Vec<u8>, Vec<String>), String> {
+    let mut conflicts = Vec::new();
+    if entry.ends_with(".jsonl") {
+        let parse = |bytes: &[u8]| -> Result<Vec<Value>, String> {
+            String::from_utf8_lossy(bytes)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).map_err(|e| format!("Failed to parse {}: {}", entry, e)))
+                .collect()
+        };
+        let mut merged = Value::Array(parse(existing)?);
+        merge_values(&mut merged, Value::Array(parse(incoming)?), "", &mut conflicts);
+        let mut out = Vec::new();
+        for record in merged.as_array().into_iter().flatten() {
+            serde_json::to_writer(&mut out, record).map_err(|e| format!("Failed to serialize {}: {}", entry, e))?;
+            out.push(b'\n');
+        }
+        return Ok((out, conflicts));
+    }
+
+    let mut merged: Value =
+        serde_json::from_slice(existing).map_err(|e| format!("Failed to parse local {}: {}", entry, e))?;
+    let incoming: Value =
+        serde_json::from_slice(incoming).map_err(|e| format!("Failed to parse {}: {}", entry, e))?;
+    merge_values(&mut merged, incoming, "", &mut conflicts);
+    let out = serde_json::to_vec_pretty(&merged).map_err(|e| format!("Failed to serialize {}: {}", entry, e))?;
+    Ok((out, conflicts))
+}
+
+/// Workspace paths a store file refers to (preset/template/filter scopes, history working dirs)
+fn referenced_workspaces(entry: &str, bytes: &[u8], found: &mut BTreeSet<String>) {
+    if entry.ends_with(".jsonl") {
+        for line in String::from_utf8_lossy(bytes).lines() {
+            if let Some(dir) = serde_json::from_str::<Value>(line).ok().and_then(|v| v["workingDir"].as_str().map(String::from)) {
+                found.insert(dir);
+            }
+        }
+        return;
+    }
+    if let Ok(value) = serde_json::from_slice::<Value>(bytes) {
+        if let Some(workspaces) = value.get("workspaces").and_then(|w| w.as_object()) {
+            found.extend(workspaces.keys().cloned());
+        }
+    }
+}
+
+fn build_bundle(app_version: String, files: Vec<(String, Vec<(String, PathBuf)>)>) -> Result<(Vec<u8>, Vec<ManifestStore>), String> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let mut stores = Vec::new();
+    for (store, entries) in files {
+        let mut names = Vec::new();
+        for (entry, path) in entries {
+            let content = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            zip.start_file(entry.as_str(), options)
+                .map_err(|e| format!("Failed to write bundle: {}", e))?;
+            zip.write_all(&content).map_err(|e| format!("Failed to write bundle: {}", e))?;
+            names.push(entry);
+        }
+        stores.push(ManifestStore { name: store, entries: names });
+    }
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        app_version,
+        created_at: history::now_secs(),
+        stores: stores.clone(),
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    zip.start_file(MANIFEST_ENTRY, options)
+        .map_err(|e| format!("Failed to write bundle: {}", e))?;
+    zip.write_all(&manifest).map_err(|e| format!("Failed to write bundle: {}", e))?;
+
+    let cursor = zip.finish().map_err(|e| format!("Failed to write bundle: {}", e))?;
+    Ok((cursor.into_inner(), stores))
+}
+
+/// The manifest and the content of every entry it lists
+fn read_bundle(path: &Path) -> Result<(Manifest, Vec<BundleEntry>), String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("Not a mensa data bundle: {}", e))?;
+
+    let mut read_entry = |name: &str| -> Result<Vec<u8>, String> {
+        let mut entry = archive
+            .by_name(name)
+            .map_err(|e| format!("Bundle entry '{}' is missing: {}", name, e))?;
+        if entry.size() > MAX_ENTRY_BYTES {
+            return Err(format!("Bundle entry '{}' is too large", name));
+        }
+        let mut content = Vec::with_capacity(entry.size() as usize);
+        entry
+            .read_to_end(&mut content)
+            .map_err(|e| format!("Failed to read bundle entry '{}': {}", name, e))?;
+        Ok(content)
+    };
+
+    let manifest: Manifest = serde_json::from_slice(&read_entry(MANIFEST_ENTRY)?)
+        .map_err(|e| format!("Invalid bundle manifest: {}", e))?;
+    if manifest.format_version == 0 || manifest.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "Bundle format version {} is not supported (this version reads up to {})",
+            manifest.format_version, FORMAT_VERSION
+        ));
+    }
+
+    let mut entries = Vec::new();
+    for store in &manifest.stores {
+        for name in &store.entries {
+            entries.push(BundleEntry {
+                store: store.name.clone(),
+                name: name.clone(),
+                content: read_entry(name)?,
+            });
+        }
+    }
+    Ok((manifest, entries))
+}
+
+/// Write one bundle file into place according to the mode
+fn import_entry(
+    path: &Path,
+    entry: &str,
+    incoming: &[u8],
+    mode: ImportMode,
+    result: &mut StoreImport,
+) -> Result<(), String> {
+    let existing = match std::fs::read(path) {
+        Ok(existing) => Some(existing),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    // Nothing local (or identical): write the bundle's bytes untouched
+    let existing = match existing {
+        Some(existing) if existing != incoming => existing,
+        Some(_) => return Ok(()),
+        None => {
+            fsutil::write_atomic(path, incoming)?;
+            result.files_written += 1;
+            return Ok(());
+        }
+    };
+
+    let (merged, conflicts) = merge_file(entry, &existing, incoming)?;
+    let resolution = match mode {
+        ImportMode::Merge => "keptExisting",
+        ImportMode::Replace => "replaced",
+    };
+    result.conflicts.extend(conflicts.into_iter().map(|key| ImportConflict {
+        file: entry.to_string(),
+        key,
+        resolution: resolution.to_string(),
+    }));
+
+    let content = match mode {
+        ImportMode::Merge => merged,
+        ImportMode::Replace => incoming.to_vec(),
+    };
+    if content != existing {
+        fsutil::write_atomic(path, &content)?;
+        result.files_written += 1;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Bundle the selected stores (all when `include` is empty) into a zip with a manifest.
+/// Keychain secrets are never included.
+#[tauri::command]
+pub async fn export_app_data(
+    app: tauri::AppHandle,
+    destination_path: String,
+    include: Vec<String>,
+) -> Result<ExportReport, String> {
+    let reporter = ProgressReporter::start(&app, "app-data-export", None, None, false);
+    let result = async {
+        let all: Vec<&str> = FILE_STORES.iter().copied().chain([WORKSPACE_STATES, PROJECT_META]).collect();
+        let selected: Vec<String> = if include.is_empty() {
+            all.iter().map(|s| s.to_string()).collect()
+        } else {
+            include
+        };
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        for store in selected {
+            if store == "secrets" {
+                return Err("Secrets stay in the system keychain and are never exported".to_string());
+            }
+            if !all.contains(&store.as_str()) {
+                return Err(format!("Unknown store: {} (expected one of {})", store, all.join(", ")));
+            }
+            if seen.insert(store.clone()) {
+                let entries = local_store_files(&app, &store)?;
+                files.push((store, entries));
+            }
+        }
+
+        reporter.phase("bundling", None);
+        let app_version = app.package_info().version.to_string();
+        let (bundle, stores) = tokio::task::spawn_blocking(move || build_bundle(app_version, files))
+            .await
+            .map_err(|e| format!("Failed to build bundle: {}", e))??;
+
+        let bytes = bundle.len() as u64;
+        reporter.phase("writing", None);
+        let path = PathBuf::from(&destination_path);
+        tokio::task::spawn_blocking(move || fsutil::write_atomic(&path, &bundle))
+            .await
+            .map_err(|e| format!("Failed to write bundle: {}", e))??;
+
+        Ok(ExportReport {
+            path: destination_path,
+            bytes,
+            stores,
+        })
+    }
+    .await;
+    reporter.settle(result)
+}
+
+/// Import a bundle made by export_app_data, merging into or replacing local stores.
+/// Each store reports its own result, so one bad file doesn't abort the rest.
+#[tauri::command]
+pub async fn import_app_data(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    git_state: State<'_, git::GitState>,
+    source_path: String,
+    mode: ImportMode,
+) -> Result<ImportReport, String> {
+    let reporter = ProgressReporter::start(&app, "app-data-import", None, None, false);
+    let result = async {
+        reporter.phase("reading", None);
+        let source = PathBuf::from(&source_path);
+        let (manifest, entries) = tokio::task::spawn_blocking(move || read_bundle(&source))
+            .await
+            .map_err(|e| format!("Failed to read bundle: {}", e))??;
+
+        // Resolve every destination up front; the blocking import below has no app handle
+        let mut planned = Vec::new();
+        let mut missing = BTreeSet::new();
+        for entry in entries {
+            referenced_workspaces(&entry.name, &entry.content, &mut missing);
+            let target = local_path_for_entry(&app, &entry.store, &entry.name);
+            planned.push((entry, target));
+        }
+        missing.retain(|path| !Path::new(path).is_dir());
+
+        let store_names: Vec<String> = manifest.stores.iter().map(|s| s.name.clone()).collect();
+        let import_reporter = reporter.clone();
+        let stores = tokio::task::spawn_blocking(move || {
+            let total = planned.len() as u64;
+            let mut results: Vec<StoreImport> = store_names
+                .into_iter()
+                .map(|store| StoreImport {
+                    store,
+                    files_written: 0,
+                    conflicts: Vec::new(),
+                    error: None,
+                })
+                .collect();
+            for (done, (entry, target)) in planned.into_iter().enumerate() {
+                import_reporter.update("importing", Some(done as u64), Some(total), Some(entry.name.clone()));
+                let Some(result) = results.iter_mut().find(|r| r.store == entry.store) else {
+                    continue;
+                };
+                if result.error.is_some() {
+                    continue;
+                }
+                if let Err(e) = target.and_then(|path| import_entry(&path, &entry.name, &entry.content, mode, result)) {
+                    result.error = Some(e);
+                }
+            }
+            results
+        })
+        .await
+        .map_err(|e| format!("Failed to import bundle: {}", e))?;
+
+        // Cached copies of the replaced files are now stale
+        settings::invalidate(&state.settings);
+        workspace::drop_clean_states(&state.workspace_states).await;
+        git_state.status_filters.lock().await.clear();
+
+        let report = ImportReport {
+            format_version: manifest.format_version,
+            mode,
+            stores,
+            missing_workspaces: missing.into_iter().collect(),
+        };
+        let _ = app.emit("app-data-imported", &report);
+        Ok(report)
+    }
+    .await;
+    reporter.settle(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture_path, write};
+
+    /// Bundle entries of the fixture profile, by store
+    const PROFILE: &[(&str, &str)] = &[
+        ("settings", "app/settings.json"),
+        ("presets", "app/query-presets.json"),
+        ("templates", "app/prompt-templates.json"),
+        ("history", "app/query-history.jsonl"),
+        ("statusFilters", "app/status-filters.json"),
+        (WORKSPACE_STATES, "app/workspace-state/0123456789abcdef0123456789abcdef.json"),
+        (PROJECT_META, "projects/-work-app/mensa-meta.json"),
+    ];
+
+    fn profile_root() -> PathBuf {
+        fixture_path("app_data/profile")
+    }
+
+    fn export(root: &Path, out: &Path) -> Vec<ManifestStore> {
+        let files = PROFILE
+            .iter()
+            .map(|(store, entry)| (store.to_string(), vec![(entry.to_string(), root.join(entry))]))
+            .collect();
+        let (bundle, stores) = build_bundle("1.0.0".to_string(), files).unwrap();
+        std::fs::write(out, bundle).unwrap();
+        stores
+    }
+
+    /// Import every entry of a bundle into a profile rooted at `root`
+    fn import(bundle: &Path, root: &Path, mode: ImportMode) -> StoreImport {
+        let (_, entries) = read_bundle(bundle).unwrap();
+        let mut result = StoreImport {
+            store: "all".to_string(),
+            files_written: 0,
+            conflicts: Vec::new(),
+            error: None,
+        };
+        for entry in entries {
+            import_entry(&root.join(&entry.name), &entry.name, &entry.content, mode, &mut result).unwrap();
+        }
+        result
+    }
+
+    #[test]
+    fn export_then_import_on_a_clean_profile_is_byte_identical() {
+        let scratch = tempfile::tempdir().unwrap();
+        let bundle = scratch.path().join("mensa-data.zip");
+        let stores = export(&profile_root(), &bundle);
+        assert_eq!(stores.len(), PROFILE.len());
+
+        for mode in [ImportMode::Merge, ImportMode::Replace] {
+            let clean = tempfile::tempdir().unwrap();
+            let result = import(&bundle, clean.path(), mode);
+            assert_eq!(result.files_written, PROFILE.len());
+            assert!(result.conflicts.is_empty());
+            for (_, entry) in PROFILE {
+                let original = std::fs::read(profile_root().join(entry)).unwrap();
+                let imported = std::fs::read(clean.path().join(entry)).unwrap();
+                assert_eq!(original, imported, "{} changed in the round trip ({:?})", entry, mode);
+            }
+
+            // Importing the same bundle again has nothing left to write
+            let again = import(&bundle, clean.path(), mode);
+            assert_eq!(again.files_written, 0);
+            assert!(again.conflicts.is_empty());
+        }
+    }
+
+    #[test]
+    fn conflicting_preset_is_kept_on_merge_and_replaced_on_replace() {
+        let scratch = tempfile::tempdir().unwrap();
+        let bundle = scratch.path().join("mensa-data.zip");
+        export(&profile_root(), &bundle);
+        let local = r#"{"global":[{"name":"Careful","config":{"maxTurns":2},"scope":"global","hidden":false},{"name":"Local only","config":{},"scope":"global","hidden":false}],"workspaces":{},"hiddenBuiltins":[]}"#;
+
+        let merged = tempfile::tempdir().unwrap();
+        write(merged.path(), "app/query-presets.json", local);
+        let result = import(&bundle, merged.path(), ImportMode::Merge);
+        let conflict = result.conflicts.iter().find(|c| c.file == "app/query-presets.json").unwrap();
+        assert_eq!((conflict.key.as_str(), conflict.resolution.as_str()), ("global[Careful]", "keptExisting"));
+        let presets: Value = serde_json::from_slice(&std::fs::read(merged.path().join("app/query-presets.json")).unwrap()).unwrap();
+        let names: Vec<&str> = presets["global"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["Careful", "Local only"]);
+        assert_eq!(presets["global"][0]["config"]["maxTurns"], 2);
+        // What the local file lacked was added
+        assert!(presets["workspaces"]["/work/app"].is_object());
+
+        let replaced = tempfile::tempdir().unwrap();
+        write(replaced.path(), "app/query-presets.json", local);
+        let result = import(&bundle, replaced.path(), ImportMode::Replace);
+        assert!(result.conflicts.iter().any(|c| c.key == "global[Careful]" && c.resolution == "replaced"));
+        assert_eq!(
+            std::fs::read(replaced.path().join("app/query-presets.json")).unwrap(),
+            std::fs::read(profile_root().join("app/query-presets.json")).unwrap()
+        );
+    }
+
+    #[test]
+    fn workspaces_referenced_by_the_bundle_are_collected() {
+        let mut found = BTreeSet::new();
+        for (_, entry) in PROFILE {
+            referenced_workspaces(entry, &std::fs::read(profile_root().join(entry)).unwrap(), &mut found);
+        }
+        assert_eq!(found.into_iter().collect::<Vec<_>>(), ["/work/app", "/work/gone"]);
+    }
+
+    #[test]
+    fn bundle_from_a_newer_format_is_refused() {
+        let scratch = tempfile::tempdir().unwrap();
+        let bundle = scratch.path().join("future.zip");
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(MANIFEST_ENTRY, zip::write::SimpleFileOptions::default()).unwrap();
+        let manifest = serde_json::json!({ "formatVersion": FORMAT_VERSION + 1, "appVersion": "9.0.0", "createdAt": 0, "stores": [] });
+        zip.write_all(manifest.to_string().as_bytes()).unwrap();
+        std::fs::write(&bundle, zip.finish().unwrap().into_inner()).unwrap();
+
+        let error = read_bundle(&bundle).err().unwrap();
+        assert!(error.contains("not supported"), "{}", error);
+    }
+}
@@ -0,0 +1,467 @@
+// mensa - Attachments Module
+// Downscales and re-encodes image attachments (dropping EXIF/GPS metadata) before they reach the agent,
+// and stages clipboard images so their bytes never cross the IPC boundary
+
+use crate::fsutil;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::{AnimationDecoder, DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Longest edge images are scaled down to unless the query overrides it
+pub const DEFAULT_MAX_IMAGE_EDGE: u32 = 1568;
+
+/// Smallest longest edge a caller may ask for; below it images are unreadable
+pub const MIN_IMAGE_EDGE: u32 = 64;
+
+/// Upper bound for one image after processing
+const MAX_PROCESSED_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// Staged images not sent within this long are deleted
+const STAGED_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Longest edge of the preview returned for a staged image
+const THUMBNAIL_EDGE: u32 = 160;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessedAttachment {
+    /// Position of the block in the prompt's content array
+    pub index: usize,
+    pub media_type: String,
+    pub width: u32,
+    pub height: u32,
+    pub original_bytes: usize,
+    pub bytes: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// An image waiting in the staging directory to be sent with a query (by `attachment_ids`)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StagedAttachment {
+    pub attachment_id: String,
+    pub media_type: String,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the processed image that will be sent
+    pub bytes: usize,
+    /// Small PNG preview, base64 encoded
+    pub thumbnail_b64: String,
+}
+
+/// Error returned by `ingest_clipboard_image`
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ClipboardImageError {
+    /// The clipboard holds no image (text, files, or nothing)
+    NoImage,
+    /// There is an image, in a format that can't be read
+    UnsupportedFormat { message: String },
+    Failed { message: String },
+}
+
+impl From<String> for ClipboardImageError {
+    fn from(message: String) -> Self {
+        ClipboardImageError::Failed { message }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Dimensions that fit within `max_edge` on the longest side, preserving aspect ratio.
+/// Images already small enough are left alone.
+pub fn fit_within(width: u32, height: u32, max_edge: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_edge || longest == 0 {
+        return (width, height);
+    }
+    let scale = max_edge as f64 / longest as f64;
+    let scaled = |v: u32| ((v as f64 * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+/// The longest edge a query or paste asked images to be scaled to, DEFAULT_MAX_IMAGE_EDGE when none
+pub fn max_image_edge(requested: Option<u32>) -> Result<u32, String> {
+    match requested {
+        None => Ok(DEFAULT_MAX_IMAGE_EDGE),
+        Some(edge) if edge >= MIN_IMAGE_EDGE => Ok(edge),
+        Some(edge) => Err(format!("Max image edge must be at least {}px, got {}", MIN_IMAGE_EDGE, edge)),
+    }
+}
+
+/// HEIC/HEIF files are ISO-BMFF containers with a heic-family brand at offset 4
+fn is_heic(bytes: &[u8]) -> bool {
+    bytes.len() >= 12
+        && &bytes[4..8] == b"ftyp"
+        && matches!(&bytes[8..12], b"heic" | b"heix" | b"hevc" | b"hevx" | b"mif1" | b"msf1")
+}
+
+fn gif_is_animated(bytes: &[u8]) -> bool {
+    GifDecoder::new(Cursor::new(bytes))
+        .map(|decoder| decoder.into_frames().take(2).count() > 1)
+        .unwrap_or(false)
+}
+
+/// Decode, apply EXIF orientation, downscale and re-encode one image.
+/// Re-encoding writes pixels only, so EXIF (including GPS) is dropped.
+pub fn process_image(bytes: &[u8], declared_type: &str, max_edge: u32) -> Result<(Vec<u8>, ProcessedAttachment), String> {
+    if declared_type.contains("heic") || declared_type.contains("heif") || is_heic(bytes) {
+        return Err("HEIC images are an unsupported format, please convert to PNG or JPEG first".to_string());
+    }
+
+    let reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let format = reader.format();
+
+    let mut warnings = Vec::new();
+    if format == Some(ImageFormat::Gif) && gif_is_animated(bytes) {
+        warnings.push("Animated GIF: only the first frame was sent".to_string());
+    }
+
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| format!("Unsupported or corrupt image: {}", e))?;
+    let orientation = decoder
+        .orientation()
+        .map_err(|e| format!("Failed to read image orientation: {}", e))?;
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    image.apply_orientation(orientation);
+
+    encode_image(image, bytes.len(), warnings, max_edge)
+}
+
+/// Downscale a decoded image and re-encode it: PNG when it has transparency, JPEG otherwise
+fn encode_image(
+    mut image: DynamicImage,
+    original_bytes: usize,
+    warnings: Vec<String>,
+    max_edge: u32,
+) -> Result<(Vec<u8>, ProcessedAttachment), String> {
+    let (width, height) = fit_within(image.width(), image.height(), max_edge);
+    if (width, height) != (image.width(), image.height()) {
+        image = image.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
+    }
+
+    let mut encoded = Vec::new();
+    let media_type = if image.color().has_alpha() {
+        image
+            .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+        "image/png"
+    } else {
+        let rgb = image.to_rgb8();
+        JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY)
+            .encode_image(&rgb)
+            .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+        "image/jpeg"
+    };
+
+    if encoded.len() > MAX_PROCESSED_IMAGE_BYTES {
+        return Err(format!(
+            "Image is still {:.1} MB after resizing (limit {} MB)",
+            encoded.len() as f64 / (1024.0 * 1024.0),
+            MAX_PROCESSED_IMAGE_BYTES / (1024 * 1024)
+        ));
+    }
+
+    let info = ProcessedAttachment {
+        index: 0,
+        media_type: media_type.to_string(),
+        width,
+        height,
+        original_bytes,
+        bytes: encoded.len(),
+        warnings,
+    };
+    Ok((encoded, info))
+}
+
+/// Rewrite the image blocks of an attachment prompt (a JSON array of content blocks)
+/// in place. Returns the new prompt and what was sent for each image.
+pub fn preprocess_prompt(prompt: &str, max_edge: u32) -> Result<(String, Vec<ProcessedAttachment>), String> {
+    let mut blocks: Value = serde_json::from_str(prompt)
+        .map_err(|e| format!("Failed to parse attachment prompt: {}", e))?;
+    let items = match blocks.as_array_mut() {
+        Some(items) => items,
+        None => return Ok((prompt.to_string(), Vec::new())),
+    };
+
+    let mut processed = Vec::new();
+    for (index, block) in items.iter_mut().enumerate() {
+        if block.get("type").and_then(|v| v.as_str()) != Some("image") {
+            continue;
+        }
+        let source = match block.get_mut("source") {
+            Some(source) if source.get("type").and_then(|v| v.as_str()) == Some("base64") => source,
+            _ => continue,
+        };
+
+        let declared_type = source
+            .get("media_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let data = source.get("data").and_then(|v| v.as_str()).unwrap_or("");
+        let bytes = BASE64
+            .decode(data)
+            .map_err(|e| format!("Attachment {} is not valid base64: {}", index + 1, e))?;
+
+        let (encoded, mut info) = process_image(&bytes, &declared_type, max_edge)
+            .map_err(|e| format!("Attachment {}: {}", index + 1, e))?;
+        info.index = index;
+
+        source["media_type"] = Value::String(info.media_type.clone());
+        source["data"] = Value::String(BASE64.encode(&encoded));
+        processed.push(info);
+    }
+
+    let prompt = serde_json::to_string(&blocks)
+        .map_err(|e| format!("Failed to serialize attachment prompt: {}", e))?;
+    Ok((prompt, processed))
+}
+
+/// Append image blocks to a prompt, turning a plain-text prompt into content blocks first
+pub fn append_image_blocks(prompt: &str, is_blocks: bool, images: Vec<Value>) -> Result<String, String> {
+    let mut blocks = if is_blocks {
+        match serde_json::from_str::<Value>(prompt) {
+            Ok(Value::Array(blocks)) => blocks,
+            _ => return Err("Failed to parse attachment prompt".to_string()),
+        }
+    } else {
+        vec![serde_json::json!({ "type": "text", "text": prompt })]
+    };
+    blocks.extend(images);
+    serde_json::to_string(&blocks).map_err(|e| format!("Failed to serialize attachment prompt: {}", e))
+}
+
+fn staging_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("attachment-staging"))
+}
+
+/// Delete staged images older than the TTL
+fn collect_expired(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > STAGED_TTL);
+        if expired {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Startup sweep of staged images left over from earlier runs
+pub fn cleanup_staged(app: &tauri::AppHandle) -> Result<(), String> {
+    collect_expired(&staging_dir(app)?);
+    Ok(())
+}
+
+/// Staging file of an attachment id, whichever format it was encoded as
+fn staged_file(dir: &Path, attachment_id: &str) -> Option<(PathBuf, &'static str)> {
+    if attachment_id.is_empty() || !attachment_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    [("png", "image/png"), ("jpg", "image/jpeg")]
+        .into_iter()
+        .map(|(ext, media_type)| (dir.join(format!("{}.{}", attachment_id, ext)), media_type))
+        .find(|(path, _)| path.is_file())
+}
+
+/// Image blocks for staged attachments, removing them from staging
+pub async fn take_staged(app: &tauri::AppHandle, attachment_ids: &[String]) -> Result<Vec<Value>, String> {
+    let dir = staging_dir(app)?;
+    let ids = attachment_ids.to_vec();
+    tokio::task::spawn_blocking(move || {
+        let mut files = Vec::new();
+        for id in &ids {
+            let (path, media_type) = staged_file(&dir, id)
+                .ok_or_else(|| format!("Attachment {} expired or was already sent; paste it again", id))?;
+            let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read attachment {}: {}", id, e))?;
+            files.push((path, media_type, bytes));
+        }
+        // Only consumed once every attachment could be read
+        let mut blocks = Vec::new();
+        for (path, media_type, bytes) in files {
+            let _ = std::fs::remove_file(&path);
+            blocks.push(serde_json::json!({
+                "type": "image",
+                "source": { "type": "base64", "media_type": media_type, "data": BASE64.encode(&bytes) }
+            }));
+        }
+        Ok(blocks)
+    })
+    .await
+    .map_err(|e| format!("Failed to load attachments: {}", e))?
+}
+
+/// Read the clipboard's image as pixels
+fn read_clipboard_image() -> Result<DynamicImage, ClipboardImageError> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("Failed to open clipboard: {}", e))?;
+    let data = clipboard.get_image().map_err(|e| match e {
+        arboard::Error::ContentNotAvailable => ClipboardImageError::NoImage,
+        arboard::Error::ConversionFailure => ClipboardImageError::UnsupportedFormat {
+            message: "The clipboard image is in a format that can't be read".to_string(),
+        },
+        other => ClipboardImageError::Failed {
+            message: format!("Failed to read clipboard: {}", other),
+        },
+    })?;
+    let rgba = image::RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned())
+        .ok_or_else(|| ClipboardImageError::UnsupportedFormat {
+            message: "The clipboard image has an unexpected pixel layout".to_string(),
+        })?;
+    // Screenshots come as RGBA but are opaque; encode those as JPEG
+    if rgba.pixels().all(|p| p[3] == u8::MAX) {
+        return Ok(DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).to_rgb8()));
+    }
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+fn thumbnail_b64(image: &DynamicImage) -> Result<String, String> {
+    let mut encoded = Vec::new();
+    image
+        .thumbnail(THUMBNAIL_EDGE, THUMBNAIL_EDGE)
+        .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode thumbnail: {}", e))?;
+    Ok(BASE64.encode(&encoded))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Read the image on the system clipboard, process it like a file attachment and stage it.
+/// Pass the returned id in `query_claude`'s `attachment_ids`; unsent images expire after an hour.
+#[tauri::command]
+pub async fn ingest_clipboard_image(
+    app: tauri::AppHandle,
+    max_edge: Option<u32>,
+) -> Result<StagedAttachment, ClipboardImageError> {
+    let dir = staging_dir(&app)?;
+    let max_edge = max_image_edge(max_edge)?;
+    tokio::task::spawn_blocking(move || {
+        collect_expired(&dir);
+        let image = read_clipboard_image()?;
+        let original_bytes = image.as_bytes().len();
+        let thumbnail_b64 = thumbnail_b64(&image)?;
+        let (encoded, info) = encode_image(image, original_bytes, Vec::new(), max_edge)?;
+
+        let attachment_id = uuid::Uuid::new_v4().to_string();
+        let ext = if info.media_type == "image/png" { "png" } else { "jpg" };
+        fsutil::write_atomic(&dir.join(format!("{}.{}", attachment_id, ext)), &encoded)?;
+        Ok(StagedAttachment {
+            attachment_id,
+            media_type: info.media_type,
+            width: info.width,
+            height: info.height,
+            bytes: info.bytes,
+            thumbnail_b64,
+        })
+    })
+    .await
+    .map_err(|e| format!("Clipboard task failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture_path;
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(width, height, image::Rgb([40, 90, 160])));
+        let mut bytes = Vec::new();
+        image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg).unwrap();
+        bytes
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn fit_within_keeps_the_aspect_ratio() {
+        assert_eq!(fit_within(4000, 3000, 1568), (1568, 1176));
+        assert_eq!(fit_within(3000, 4000, 1568), (1176, 1568));
+        assert_eq!(fit_within(2000, 2000, 1000), (1000, 1000));
+        // Already small enough, or exactly at the limit: untouched
+        assert_eq!(fit_within(800, 600, 1568), (800, 600));
+        assert_eq!(fit_within(1568, 10, 1568), (1568, 10));
+        // A sliver never rounds down to nothing
+        assert_eq!(fit_within(10_000, 3, 100), (100, 1));
+        assert_eq!(fit_within(0, 0, 100), (0, 0));
+    }
+
+    #[test]
+    fn max_image_edge_refuses_tiny_edges() {
+        assert_eq!(max_image_edge(None).unwrap(), DEFAULT_MAX_IMAGE_EDGE);
+        assert_eq!(max_image_edge(Some(MIN_IMAGE_EDGE)).unwrap(), MIN_IMAGE_EDGE);
+        assert_eq!(max_image_edge(Some(4096)).unwrap(), 4096);
+        for edge in [0, 1, MIN_IMAGE_EDGE - 1] {
+            assert!(max_image_edge(Some(edge)).is_err());
+        }
+    }
+
+    #[test]
+    fn large_image_is_downscaled_to_the_edge() {
+        let (encoded, info) = process_image(&jpeg(600, 300), "image/jpeg", 200).unwrap();
+        assert_eq!((info.width, info.height), (200, 100));
+        assert_eq!(info.media_type, "image/jpeg");
+        let decoded = image::load_from_memory(&encoded).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (200, 100));
+    }
+
+    #[test]
+    fn exif_metadata_is_dropped_after_orientation_is_applied() {
+        // 40x20 JPEG with EXIF orientation 6 (rotate 90°), a description and GPS coordinates
+        let original = std::fs::read(fixture_path("attachments/rotated_gps.jpg")).unwrap();
+        assert!(contains(&original, b"Exif\0\0"));
+        assert!(contains(&original, b"mensa-test-location"));
+
+        let (encoded, info) = process_image(&original, "image/jpeg", DEFAULT_MAX_IMAGE_EDGE).unwrap();
+        assert_eq!((info.width, info.height), (20, 40));
+        assert!(!contains(&encoded, b"Exif"));
+        assert!(!contains(&encoded, b"mensa-test-location"));
+
+        // Nothing left to rotate it a second time
+        let mut decoder = ImageReader::new(Cursor::new(&encoded)).with_guessed_format().unwrap().into_decoder().unwrap();
+        assert_eq!(decoder.orientation().unwrap(), image::metadata::Orientation::NoTransforms);
+    }
+
+    #[test]
+    fn heic_is_refused_by_content_or_declared_type() {
+        let mut heic = vec![0, 0, 0, 24];
+        heic.extend_from_slice(b"ftypheic");
+        heic.extend_from_slice(&[0; 12]);
+        assert!(process_image(&heic, "application/octet-stream", DEFAULT_MAX_IMAGE_EDGE).unwrap_err().contains("HEIC"));
+        assert!(process_image(&jpeg(4, 4), "image/heic", DEFAULT_MAX_IMAGE_EDGE).unwrap_err().contains("HEIC"));
+    }
+}
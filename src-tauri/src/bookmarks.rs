@@ -0,0 +1,256 @@
+// mensa - Bookmarks Module
+// Message bookmarks kept in each project's mensa-meta.json sidecar (next to the session transcripts)
+
+use crate::store::{JsonStore, StoreGuard};
+use crate::transcript::{parse_session_messages, SessionMessage};
+use crate::{find_session_in_any_project, find_session_path, fsutil, history, read_session_file};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Length of the content hash prefix used to re-locate a bookmarked message
+const FINGERPRINT_LEN: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageBookmark {
+    /// "<session id>:<uuid>"
+    pub id: String,
+    pub session_id: String,
+    /// Grouped index (as returned by load_session_messages) when the bookmark was made
+    pub message_index: usize,
+    pub note: String,
+    pub timestamp: String,
+    pub content_hash: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookmarkView {
+    #[serde(flatten)]
+    pub bookmark: MessageBookmark,
+    /// Where the message is now, or None if it can no longer be found
+    pub resolved_index: Option<usize>,
+}
+
+/// Sidecar next to a project's transcripts
+pub const META_FILE: &str = "mensa-meta.json";
+
+/// Every project's sidecar goes through this store's lock, so racing bookmark edits can't lose one
+static META_STORE: JsonStore<ProjectMeta> = JsonStore::new("project metadata");
+
+/// Per-project sidecar; unknown keys are preserved for other features
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectMeta {
+    #[serde(default)]
+    bookmarks: Vec<MessageBookmark>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn meta_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(META_FILE)
+}
+
+/// Take the lock on a project's sidecar, for a load-modify-save
+pub async fn lock_meta(app: &tauri::AppHandle, project_dir: &Path) -> StoreGuard<'static, ProjectMeta> {
+    META_STORE.lock(app, meta_path(project_dir)).await
+}
+
+pub async fn load_meta(app: &tauri::AppHandle, project_dir: &Path) -> Result<ProjectMeta, String> {
+    META_STORE.read(app, meta_path(project_dir)).await
+}
+
+/// Project directory holding a session's transcript (and so its bookmarks)
+pub fn session_project_dir(workspace_path: Option<&str>, session_id: &str) -> Result<PathBuf, String> {
+    if session_id.is_empty() || session_id.contains(['/', '\\']) || session_id.contains("..") {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    let session_path = match workspace_path {
+        Some(workspace) => find_session_path(workspace, session_id)?,
+        None => find_session_in_any_project(session_id)?,
+    };
+    session_path
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .ok_or_else(|| format!("Session not found: {}", session_id))
+}
+
+fn fingerprint(message: &SessionMessage) -> String {
+    let mut hash = fsutil::sha256_hex(message.content.as_bytes());
+    hash.truncate(FINGERPRINT_LEN);
+    hash
+}
+
+/// Find the bookmarked message again: its original index if it still matches,
+/// otherwise the message with the same timestamp and content, otherwise the same content
+fn resolve_index(bookmark: &MessageBookmark, messages: &[SessionMessage]) -> Option<usize> {
+    let matches = |m: &SessionMessage| fingerprint(m) == bookmark.content_hash;
+    if messages
+        .get(bookmark.message_index)
+        .is_some_and(|m| m.timestamp == bookmark.timestamp && matches(m))
+    {
+        return Some(bookmark.message_index);
+    }
+    messages
+        .iter()
+        .position(|m| m.timestamp == bookmark.timestamp && matches(m))
+        .or_else(|| messages.iter().position(matches))
+}
+
+/// Bookmarks of one session, each resolved against the current messages
+pub async fn session_bookmarks(
+    app: &tauri::AppHandle,
+    project_dir: &Path,
+    session_id: &str,
+    messages: &[SessionMessage],
+) -> Result<Vec<BookmarkView>, String> {
+    let meta = load_meta(app, project_dir).await?;
+    Ok(meta
+        .bookmarks
+        .into_iter()
+        .filter(|b| b.session_id == session_id)
+        .map(|bookmark| BookmarkView {
+            resolved_index: resolve_index(&bookmark, messages),
+            bookmark,
+        })
+        .collect())
+}
+
+/// Drop every bookmark of a deleted session
+pub async fn remove_session_bookmarks(app: &tauri::AppHandle, project_dir: &Path, session_id: &str) -> Result<(), String> {
+    if !meta_path(project_dir).exists() {
+        return Ok(());
+    }
+    let guard = lock_meta(app, project_dir).await;
+    let mut meta = guard.load().await?;
+    let before = meta.bookmarks.len();
+    meta.bookmarks.retain(|b| b.session_id != session_id);
+    if meta.bookmarks.len() != before {
+        guard.save(&meta).await?;
+    }
+    Ok(())
+}
+
+/// Copy the bookmarks of migrated sessions into another project's metadata under their new
+/// session ids; with `remove`, drop them from `from`. Returns how many were carried.
+pub fn carry_bookmarks(from: &mut ProjectMeta, to: &mut ProjectMeta, renames: &HashMap<String, String>, remove: bool) -> usize {
+    let mut carried = 0;
+    for bookmark in &from.bookmarks {
+        let Some(new_id) = renames.get(&bookmark.session_id) else {
+            continue;
+        };
+        let mut moved = bookmark.clone();
+        if let Some((_, suffix)) = bookmark.id.split_once(':') {
+            moved.id = format!("{}:{}", new_id, suffix);
+        }
+        moved.session_id = new_id.clone();
+        if !to.bookmarks.iter().any(|b| b.id == moved.id) {
+            to.bookmarks.push(moved);
+            carried += 1;
+        }
+    }
+    if remove {
+        from.bookmarks.retain(|b| !renames.contains_key(&b.session_id));
+    }
+    carried
+}
+
+async fn load_messages(project_dir: &Path, session_id: &str) -> Result<Vec<SessionMessage>, String> {
+    let path = project_dir.join(format!("{}.jsonl", session_id));
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+    parse_session_messages(&content)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Bookmark a message by its grouped index, with a note
+#[tauri::command]
+pub async fn add_message_bookmark(
+    app: tauri::AppHandle,
+    workspace_path: String,
+    session_id: String,
+    message_index: usize,
+    note: String,
+) -> Result<BookmarkView, String> {
+    let content = read_session_file(&workspace_path, &session_id)
+        .await?
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let messages = parse_session_messages(&content)?;
+    let message = messages
+        .get(message_index)
+        .ok_or_else(|| format!("Message {} does not exist in this session", message_index))?;
+
+    let bookmark = MessageBookmark {
+        id: format!("{}:{}", session_id, uuid::Uuid::new_v4()),
+        session_id: session_id.clone(),
+        message_index,
+        note,
+        timestamp: message.timestamp.clone(),
+        content_hash: fingerprint(message),
+        created_at: history::now_secs(),
+    };
+
+    let project_dir = session_project_dir(Some(&workspace_path), &session_id)?;
+    let guard = lock_meta(&app, &project_dir).await;
+    let mut meta = guard.load().await?;
+    meta.bookmarks.push(bookmark.clone());
+    guard.save(&meta).await?;
+
+    Ok(BookmarkView {
+        bookmark,
+        resolved_index: Some(message_index),
+    })
+}
+
+/// List a session's bookmarks with where each message is now
+#[tauri::command]
+pub async fn list_message_bookmarks(
+    app: tauri::AppHandle,
+    session_id: String,
+    workspace_path: Option<String>,
+) -> Result<Vec<BookmarkView>, String> {
+    let project_dir = match session_project_dir(workspace_path.as_deref(), &session_id) {
+        Ok(dir) => dir,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let messages = load_messages(&project_dir, &session_id).await?;
+    session_bookmarks(&app, &project_dir, &session_id, &messages).await
+}
+
+/// Remove one bookmark
+#[tauri::command]
+pub async fn remove_message_bookmark(app: tauri::AppHandle, id: String) -> Result<bool, String> {
+    let session_id = id
+        .split_once(':')
+        .map(|(session, _)| session)
+        .ok_or_else(|| format!("Invalid bookmark id: {}", id))?;
+    let project_dir = match session_project_dir(None, session_id) {
+        Ok(dir) => dir,
+        Err(_) => return Ok(false),
+    };
+
+    let guard = lock_meta(&app, &project_dir).await;
+    let mut meta = guard.load().await?;
+    let before = meta.bookmarks.len();
+    meta.bookmarks.retain(|b| b.id != id);
+    if meta.bookmarks.len() == before {
+        return Ok(false);
+    }
+    guard.save(&meta).await?;
+    Ok(true)
+}
@@ -0,0 +1,275 @@
+// mensa - Cancel Module
+// Cancellation tokens for in-flight reads (session loads), stopped by operation id
+
+use crate::{permissions, AppState};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{Manager, State};
+use tokio::sync::Notify;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Lines parsed between cancellation checks
+pub const CHECK_INTERVAL_LINES: usize = 256;
+
+/// Shared flag a long read checks as it goes (or a task awaits); cloning shares it
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+/// Tokens of the cancellable reads in flight, by operation id
+#[derive(Default)]
+pub struct CancelRegistry {
+    tokens: Mutex<HashMap<String, CancellationToken>>,
+}
+
+/// Removes an operation's token when the operation ends, however it ends
+pub struct Registration {
+    app: tauri::AppHandle,
+    operation_id: String,
+    token: CancellationToken,
+}
+
+/// Error returned by cancellable reads
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ReadError {
+    /// `cancel_operation` stopped the read; no result follows
+    Cancelled { operation_id: String },
+    /// See `permissions::SessionError::PermissionDenied`
+    PermissionDenied {
+        path: String,
+        suggested_fix: String,
+        message: String,
+    },
+    Failed { message: String },
+}
+
+impl From<String> for ReadError {
+    fn from(message: String) -> Self {
+        match permissions::SessionError::from(message) {
+            permissions::SessionError::PermissionDenied { path, suggested_fix, message } => {
+                ReadError::PermissionDenied { path, suggested_fix, message }
+            }
+            permissions::SessionError::Failed { message } => ReadError::Failed { message },
+            // Never parsed from a message
+            in_use @ permissions::SessionError::SessionInUse { .. } => ReadError::Failed {
+                message: in_use.to_string(),
+            },
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Resolves once the token is cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Created before the check, so a cancel in between still wakes it
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Registration {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub fn operation_id(&self) -> &str {
+        &self.operation_id
+    }
+
+    /// Err(Cancelled) once the operation was cancelled
+    pub fn check(&self) -> Result<(), ReadError> {
+        if self.token.is_cancelled() {
+            return Err(ReadError::Cancelled {
+                operation_id: self.operation_id.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let state = self.app.state::<AppState>();
+        let mut tokens = state.cancellations.tokens.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // A newer read may have reused the id; only remove our own token
+        if tokens
+            .get(&self.operation_id)
+            .is_some_and(|t| Arc::ptr_eq(&t.cancelled, &self.token.cancelled))
+        {
+            tokens.remove(&self.operation_id);
+        }
+    }
+}
+
+/// Register a cancellable operation; its token is dropped with the returned registration
+pub fn register(app: &tauri::AppHandle, operation_id: &str) -> Registration {
+    let token = CancellationToken::default();
+    app.state::<AppState>()
+        .cancellations
+        .tokens
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(operation_id.to_string(), token.clone());
+    Registration {
+        app: app.clone(),
+        operation_id: operation_id.to_string(),
+        token,
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Cancel an in-flight read; it returns a `cancelled` error shortly. False if nothing by that id is running.
+#[tauri::command]
+pub async fn cancel_operation(state: State<'_, AppState>, operation_id: String) -> Result<bool, String> {
+    let tokens = state.cancellations.tokens.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    match tokens.get(&operation_id) {
+        Some(token) => {
+            token.cancel();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::parse_session_messages_until;
+    use std::io::{BufRead, Read};
+    use std::sync::atomic::AtomicUsize;
+
+    /// Reader that counts the transcript lines handed to the parser, and cancels `token`
+    /// once `cancel_after` of them have been read
+    struct ParseProgress<'a, R> {
+        inner: R,
+        lines: &'a AtomicUsize,
+        cancel_after: usize,
+        token: &'a CancellationToken,
+    }
+
+    impl<R: BufRead> Read for ParseProgress<'_, R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.fill_buf()?.len().min(buf.len());
+            buf[..read].copy_from_slice(&self.fill_buf()?[..read]);
+            self.consume(read);
+            Ok(read)
+        }
+    }
+
+    impl<R: BufRead> BufRead for ParseProgress<'_, R> {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            self.inner.fill_buf()
+        }
+
+        fn consume(&mut self, amt: usize) {
+            let newlines = self.inner.fill_buf().map_or(0, |buf| buf[..amt].iter().filter(|&&b| b == b'\n').count());
+            self.inner.consume(amt);
+            if self.lines.fetch_add(newlines, Ordering::Relaxed) + newlines >= self.cancel_after {
+                self.token.cancel();
+            }
+        }
+    }
+
+    /// A transcript of `turns` question/answer pairs, each answer using a tool
+    fn large_transcript(turns: usize) -> Vec<u8> {
+        let mut content = String::new();
+        for i in 0..turns {
+            let lines = [
+                serde_json::json!({"type": "user", "message": {"role": "user", "content": format!("Question {}", i)}}),
+                serde_json::json!({"type": "assistant", "message": {"role": "assistant", "content": [
+                    {"type": "text", "text": format!("Answer {}", i)},
+                    {"type": "tool_use", "id": format!("toolu_{}", i), "name": "Read", "input": {"file_path": "src/lib.rs"}},
+                ]}}),
+                serde_json::json!({"type": "user", "message": {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": format!("toolu_{}", i), "content": "x".repeat(200)},
+                ]}}),
+            ];
+            for line in lines {
+                content.push_str(&line.to_string());
+                content.push('\n');
+            }
+        }
+        content.into_bytes()
+    }
+
+    fn parse(content: &[u8], cancel_after: usize, token: &CancellationToken) -> (Option<usize>, usize) {
+        let lines = AtomicUsize::new(0);
+        let reader = ParseProgress {
+            inner: std::io::BufReader::new(content),
+            lines: &lines,
+            cancel_after,
+            token,
+        };
+        let parsed = parse_session_messages_until(reader, token, false).unwrap();
+        (parsed.map(|messages| messages.len()), lines.load(Ordering::Relaxed))
+    }
+
+    #[test]
+    fn cancelled_parse_of_a_large_transcript_stops_early() {
+        let content = large_transcript(20_000);
+        let total_lines = 60_000;
+
+        let (parsed, read) = parse(&content, 1_000, &CancellationToken::default());
+        assert_eq!(parsed, None);
+        // Stopped at the first check after the cancel, not at the end of the file
+        assert!((1_000..=1_000 + CHECK_INTERVAL_LINES + 1).contains(&read), "read {} lines", read);
+        assert!(read < total_lines / 10);
+
+        // Uncancelled, the same reader goes through every line
+        let (parsed, read) = parse(&content, usize::MAX, &CancellationToken::default());
+        assert_eq!(parsed, Some(40_000));
+        assert_eq!(read, total_lines);
+    }
+
+    #[test]
+    fn parse_cancelled_before_it_starts_reads_nothing_past_the_first_line() {
+        let token = CancellationToken::default();
+        token.cancel();
+        let (parsed, read) = parse(&large_transcript(1_000), usize::MAX, &token);
+        assert_eq!((parsed, read), (None, 1));
+    }
+
+    #[tokio::test]
+    async fn waiters_wake_on_cancel() {
+        let token = CancellationToken::default();
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+        token.cancel();
+        tokio::time::timeout(std::time::Duration::from_secs(5), waiter).await.unwrap().unwrap();
+        // Already cancelled: resolves at once
+        token.cancelled().await;
+    }
+}
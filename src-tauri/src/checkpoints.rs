@@ -0,0 +1,240 @@
+// mensa - Checkpoints Module
+// Read-side access to Claude Code's file-history snapshots (~/.claude/file-history)
+
+use crate::{fsutil, paths, project_dir_for_workspace, workspace, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileCheckpoint {
+    /// "<session id>:<backup file name>"
+    pub id: String,
+    pub session_id: String,
+    pub file_path: String,
+    pub version: u32,
+    /// RFC 3339, as recorded in the transcript
+    pub backup_time: String,
+    /// `backup_time` in Unix milliseconds, when it parses
+    pub backup_time_ms: Option<i64>,
+    pub size_bytes: u64,
+    /// Previous name of `size_bytes`
+    #[cfg(feature = "legacy_fields")]
+    pub size: u64,
+    /// SHA-256 of the file as it is on disk now (None if it no longer exists)
+    pub current_hash: Option<String>,
+}
+
+/// One tracked file inside a `file-history-snapshot` transcript entry
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrackedFileBackup {
+    backup_file_name: Option<String>,
+    version: u32,
+    backup_time: String,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Checkpoint id parts come from disk and the UI; never let them address anything
+/// outside the file-history directory
+fn is_safe_component(part: &str) -> bool {
+    !part.is_empty() && part != "." && part != ".." && !part.contains(['/', '\\'])
+}
+
+fn parse_checkpoint_id(checkpoint_id: &str) -> Result<(&str, &str), String> {
+    match checkpoint_id.split_once(':') {
+        Some((session, backup)) if is_safe_component(session) && is_safe_component(backup) => Ok((session, backup)),
+        _ => Err(format!("Invalid checkpoint id: {}", checkpoint_id)),
+    }
+}
+
+/// Snapshot paths may be absolute or relative to the session's working directory
+fn resolve_snapshot_path(working_dir: &Path, file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        working_dir.join(path)
+    }
+}
+
+/// Collect checkpoints recorded in a workspace's session transcripts.
+/// Returns an "unsupported format" error when snapshot entries exist but none can be read.
+fn collect_checkpoints(workspace_path: &str, file_filter: Option<&str>) -> Result<Vec<FileCheckpoint>, String> {
+    let project_dir = project_dir_for_workspace(workspace_path)?;
+    let history_dir = paths::ClaudeHome::current()?.file_history();
+    if !project_dir.exists() || !history_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let workspace_root = Path::new(workspace_path);
+    let filter = file_filter.map(|f| resolve_snapshot_path(workspace_root, f));
+
+    let entries = std::fs::read_dir(&project_dir)
+        .map_err(|e| format!("Failed to read project directory: {}", e))?;
+
+    let mut checkpoints = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut hashes: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut unreadable_snapshots = 0usize;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let session_id = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(id) if is_safe_component(id) => id.to_string(),
+            _ => continue,
+        };
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        for line in content.lines() {
+            // Cheap pre-filter; most transcript lines are messages
+            if !line.contains("file-history-snapshot") {
+                continue;
+            }
+            let parsed: Value = match serde_json::from_str(line) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            if parsed.get("type").and_then(|v| v.as_str()) != Some("file-history-snapshot") {
+                continue;
+            }
+
+            let backups: HashMap<String, TrackedFileBackup> = match parsed
+                .pointer("/snapshot/trackedFileBackups")
+                .cloned()
+                .map(serde_json::from_value)
+            {
+                Some(Ok(backups)) => backups,
+                _ => {
+                    unreadable_snapshots += 1;
+                    continue;
+                }
+            };
+
+            for (file_path, backup) in backups {
+                // A missing backup name means the file didn't exist at that point
+                let backup_name = match backup.backup_file_name {
+                    Some(name) if is_safe_component(&name) => name,
+                    _ => continue,
+                };
+                let target = resolve_snapshot_path(workspace_root, &file_path);
+                if filter.as_ref().is_some_and(|f| f != &target) {
+                    continue;
+                }
+
+                let id = format!("{}:{}", session_id, backup_name);
+                if !seen.insert(id.clone()) {
+                    continue;
+                }
+                let stored = history_dir.join(&session_id).join(&backup_name);
+                let size = match std::fs::metadata(&stored) {
+                    Ok(meta) => meta.len(),
+                    Err(_) => continue,
+                };
+                let current_hash = hashes
+                    .entry(target.clone())
+                    .or_insert_with(|| fsutil::file_hash(&target).ok().flatten())
+                    .clone();
+
+                checkpoints.push(FileCheckpoint {
+                    id,
+                    session_id: session_id.clone(),
+                    file_path,
+                    version: backup.version,
+                    backup_time_ms: crate::digest::parse_utc_timestamp_ms(&backup.backup_time),
+                    backup_time: backup.backup_time,
+                    size_bytes: size,
+                    #[cfg(feature = "legacy_fields")]
+                    size,
+                    current_hash,
+                });
+            }
+        }
+    }
+
+    if checkpoints.is_empty() && unreadable_snapshots > 0 {
+        return Err("Unsupported checkpoint format version".to_string());
+    }
+
+    // Newest first
+    checkpoints.sort_by(|a, b| b.backup_time.cmp(&a.backup_time));
+    Ok(checkpoints)
+}
+
+fn read_checkpoint_bytes(checkpoint_id: &str) -> Result<Vec<u8>, String> {
+    let (session_id, backup_name) = parse_checkpoint_id(checkpoint_id)?;
+    let path = paths::ClaudeHome::current()?.file_history().join(session_id).join(backup_name);
+    std::fs::read(&path).map_err(|e| format!("Failed to read checkpoint: {}", e))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List file snapshots Claude Code recorded for a workspace, optionally for one file
+#[tauri::command]
+pub async fn list_file_checkpoints(
+    workspace_path: String,
+    file: Option<String>,
+) -> Result<Vec<FileCheckpoint>, String> {
+    tokio::task::spawn_blocking(move || collect_checkpoints(&workspace_path, file.as_deref()))
+        .await
+        .map_err(|e| format!("Checkpoint scan failed: {}", e))?
+}
+
+/// Read the stored content of a checkpoint
+#[tauri::command]
+pub async fn read_file_checkpoint(checkpoint_id: String) -> Result<String, String> {
+    let bytes = read_checkpoint_bytes(&checkpoint_id)?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
+
+/// Restore a checkpoint over the workspace file. `expected_hash` is the file's
+/// currentHash from the listing; the write is refused if the file changed since.
+#[tauri::command]
+pub async fn restore_file_checkpoint(
+    state: State<'_, AppState>,
+    working_dir: String,
+    checkpoint_id: String,
+    expected_hash: Option<String>,
+) -> Result<FileCheckpoint, String> {
+    let root = workspace::validate_selected_workspace(&state, &working_dir).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let checkpoint = collect_checkpoints(&working_dir, None)?
+            .into_iter()
+            .find(|c| c.id == checkpoint_id)
+            .ok_or_else(|| format!("Checkpoint not found: {}", checkpoint_id))?;
+
+        let target = resolve_snapshot_path(Path::new(&working_dir), &checkpoint.file_path);
+        let parent = target
+            .parent()
+            .and_then(|p| std::fs::canonicalize(p).ok())
+            .ok_or_else(|| format!("Directory for {} no longer exists", checkpoint.file_path))?;
+        if !parent.starts_with(&root) {
+            return Err(format!("Refusing to restore outside the workspace: {}", checkpoint.file_path));
+        }
+
+        let bytes = read_checkpoint_bytes(&checkpoint.id)?;
+        fsutil::write_checked(&target, &bytes, expected_hash.as_deref())?;
+        Ok(checkpoint)
+    })
+    .await
+    .map_err(|e| format!("Checkpoint restore failed: {}", e))?
+}
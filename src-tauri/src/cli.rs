@@ -0,0 +1,434 @@
+// mensa - CLI Module
+// Headless `mensa query` / `mensa sessions list` runs against the same backend as the app,
+// forwarded to the running app when there is one
+
+use crate::{settings, AppState, QueryInput};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::utils::config::WindowConfig;
+use tauri::{Listener, Manager};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+const USAGE: &str = "usage:
+  mensa query --cwd <path> --prompt <text> [--preset <name>] [--json] [--timeout <secs>]
+  mensa sessions list --cwd <path> [--json]
+  mensa cancel <query-id>";
+
+/// Events of a query, relayed to stdout
+const QUERY_EVENTS: &[&str] = &[
+    "claude-stream",
+    "claude-stderr",
+    "claude-done",
+    "claude-cost-warning",
+    "claude-cost-limit",
+    "claude-context-usage",
+    "claude-context-warning",
+    "claude-hook-event",
+    "claude-redaction-active",
+    "query-files-changed",
+    "session-trimmed",
+];
+
+/// Exit code of a run stopped by its timeout (as timeout(1) uses)
+const EXIT_TIMED_OUT: i32 = 124;
+
+/// Exit code of a run cancelled from the app
+const EXIT_CANCELLED: i32 = 130;
+
+/// Exit code of a bad command line
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct QueryArgs {
+    /// Absolute working directory
+    pub cwd: String,
+    pub prompt: String,
+    pub preset: Option<String>,
+    pub json: bool,
+    pub timeout_secs: Option<u64>,
+    /// Set when the command line is (re-)issued with an id, so a forwarded run keeps it
+    pub query_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CliCommand {
+    Query(QueryArgs),
+    SessionsList { cwd: String, json: bool },
+    Cancel { query_id: String },
+}
+
+/// Present while mensa runs headless: the window to open if the app is launched meanwhile
+pub struct HeadlessRun {
+    window: Option<WindowConfig>,
+    window_opened: AtomicBool,
+    finished: AtomicBool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn absolute_dir(path: &str) -> Result<String, String> {
+    let dir = std::fs::canonicalize(path).map_err(|e| format!("{}: {}", path, e))?;
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", path));
+    }
+    Ok(dir.to_string_lossy().to_string())
+}
+
+/// Parse a command line (including the program name). None means a normal app launch.
+pub fn parse(args: &[String]) -> Result<Option<CliCommand>, String> {
+    let Some(command) = args.get(1) else {
+        return Ok(None);
+    };
+    let mut rest = args[2..].iter();
+    let flag_value = |name: &str, rest: &mut std::slice::Iter<String>| {
+        rest.next().cloned().ok_or_else(|| format!("{} needs a value", name))
+    };
+
+    match command.as_str() {
+        "query" => {
+            let (mut cwd, mut prompt, mut preset, mut json, mut timeout_secs, mut query_id) =
+                (None, None, None, false, None, None);
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--cwd" => cwd = Some(flag_value(arg, &mut rest)?),
+                    "--prompt" => prompt = Some(flag_value(arg, &mut rest)?),
+                    "--preset" => preset = Some(flag_value(arg, &mut rest)?),
+                    "--json" => json = true,
+                    "--timeout" => {
+                        let value = flag_value(arg, &mut rest)?;
+                        let secs = value.parse::<u64>().map_err(|_| format!("invalid --timeout: {}", value))?;
+                        timeout_secs = Some(secs);
+                    }
+                    "--query-id" => query_id = Some(flag_value(arg, &mut rest)?),
+                    other => return Err(format!("unknown argument: {}", other)),
+                }
+            }
+            let prompt = prompt.filter(|p| !p.trim().is_empty()).ok_or("--prompt is required")?;
+            let cwd = absolute_dir(&cwd.ok_or("--cwd is required")?)?;
+            Ok(Some(CliCommand::Query(QueryArgs {
+                cwd,
+                prompt,
+                preset,
+                json,
+                timeout_secs,
+                query_id,
+            })))
+        }
+        "sessions" => {
+            if rest.next().map(String::as_str) != Some("list") {
+                return Err("expected `sessions list`".to_string());
+            }
+            let (mut cwd, mut json) = (None, false);
+            while let Some(arg) = rest.next() {
+                match arg.as_str() {
+                    "--cwd" => cwd = Some(flag_value(arg, &mut rest)?),
+                    "--json" => json = true,
+                    other => return Err(format!("unknown argument: {}", other)),
+                }
+            }
+            let cwd = absolute_dir(&cwd.ok_or("--cwd is required")?)?;
+            Ok(Some(CliCommand::SessionsList { cwd, json }))
+        }
+        "cancel" => {
+            let query_id = rest.next().cloned().ok_or("cancel needs a query id")?;
+            Ok(Some(CliCommand::Cancel { query_id }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The command line for `args`, with every value spelled out so the app it may be forwarded to reads the same
+fn query_command_line(args: &QueryArgs, query_id: &str) -> Vec<String> {
+    let mut line = vec![
+        "query".to_string(),
+        "--cwd".to_string(),
+        args.cwd.clone(),
+        "--prompt".to_string(),
+        args.prompt.clone(),
+        "--query-id".to_string(),
+        query_id.to_string(),
+    ];
+    if let Some(ref preset) = args.preset {
+        line.extend(["--preset".to_string(), preset.clone()]);
+    }
+    if let Some(secs) = args.timeout_secs {
+        line.extend(["--timeout".to_string(), secs.to_string()]);
+    }
+    if args.json {
+        line.push("--json".to_string());
+    }
+    line
+}
+
+fn print_json(value: &Value) {
+    println!("{}", value);
+}
+
+/// Assistant text of one stream line, for plain output
+fn stream_text(line: &str) -> Option<String> {
+    let parsed: Value = serde_json::from_str(line).ok()?;
+    if parsed.get("type").and_then(|v| v.as_str()) != Some("assistant") {
+        return None;
+    }
+    let text: Vec<&str> = parsed
+        .get("message")?
+        .get("content")?
+        .as_array()?
+        .iter()
+        .filter(|block| block.get("type").and_then(|v| v.as_str()) == Some("text"))
+        .filter_map(|block| block.get("text").and_then(|v| v.as_str()))
+        .collect();
+    (!text.is_empty()).then(|| text.join(""))
+}
+
+/// Relay a query's events to stdout: NDJSON `{event, payload}` or, plain, the assistant's text
+/// (stderr lines go to stderr). Records the exit code from `claude-done`.
+fn relay_events(app: &tauri::AppHandle, query_id: &str, json: bool, exit_code: &Arc<Mutex<Option<i32>>>) {
+    for &event in QUERY_EVENTS {
+        let (query_id, exit_code) = (query_id.to_string(), exit_code.clone());
+        app.listen_any(event, move |message| {
+            let Ok(mut payload) = serde_json::from_str::<Value>(message.payload()) else {
+                return;
+            };
+            if payload.get("query_id").and_then(|v| v.as_str()) != Some(query_id.as_str()) {
+                return;
+            }
+            // A cancelled run has no exit code of its own; it exits as EXIT_CANCELLED
+            let cancelled = payload.get("reason").and_then(|v| v.as_str()) == Some(crate::query_cancel::CANCELLED);
+            if event == "claude-done" && !cancelled {
+                let code = payload.get("code").and_then(|v| v.as_i64()).unwrap_or(-1);
+                if let Ok(mut exit_code) = exit_code.lock() {
+                    *exit_code = Some(code as i32);
+                }
+            }
+            if json {
+                // Stream lines are JSON themselves; nest them rather than as strings
+                if let Some(data) = payload.get("data").and_then(|v| v.as_str()) {
+                    if let Ok(parsed) = serde_json::from_str::<Value>(data) {
+                        payload["data"] = parsed;
+                    }
+                }
+                print_json(&serde_json::json!({ "event": event, "payload": payload }));
+                return;
+            }
+            let data = payload.get("data").and_then(|v| v.as_str()).unwrap_or_default();
+            match event {
+                "claude-stream" => {
+                    if let Some(text) = stream_text(data) {
+                        println!("{}", text);
+                    }
+                }
+                "claude-stderr" => eprintln!("{}", data),
+                "claude-cost-limit" => eprintln!("mensa: stopped at the cost ceiling"),
+                _ => {}
+            }
+        });
+    }
+}
+
+/// Run a query in this process and exit with the agent's code
+async fn run_query_headless(app: tauri::AppHandle, args: QueryArgs, query_id: String) -> i32 {
+    let exit_code = Arc::new(Mutex::new(None));
+    relay_events(&app, &query_id, args.json, &exit_code);
+
+    let timeout_secs = match args.timeout_secs {
+        Some(secs) => secs,
+        None => match settings::load(&app, &app.state::<AppState>().settings).await {
+            Ok(settings) => settings.default_timeout_secs,
+            Err(e) => {
+                eprintln!("mensa: {}", e);
+                return 1;
+            }
+        },
+    };
+    let input = QueryInput {
+        prompt: args.prompt,
+        working_dir: args.cwd,
+        preset: args.preset,
+        ..Default::default()
+    };
+    let run = crate::start_query(app.clone(), query_id.clone(), input);
+    match tokio::time::timeout(Duration::from_secs(timeout_secs), run).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => {
+            eprintln!("mensa: {}", serde_json::to_string(&e).unwrap_or_default());
+            return 1;
+        }
+        Err(_) => {
+            crate::query_cancel::stop_query(&app, &query_id).await;
+            eprintln!("mensa: query timed out after {}s", timeout_secs);
+            return EXIT_TIMED_OUT;
+        }
+    }
+    let code = exit_code.lock().ok().and_then(|code| *code);
+    code.unwrap_or(EXIT_CANCELLED)
+}
+
+/// Handle a command line passed on by a second `mensa` process
+pub fn forwarded(app: &tauri::AppHandle, argv: Vec<String>) {
+    match parse(&argv) {
+        Ok(Some(CliCommand::Query(args))) => {
+            let query_id = args.query_id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            let input = QueryInput {
+                prompt: args.prompt,
+                working_dir: args.cwd,
+                preset: args.preset,
+                ..Default::default()
+            };
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = crate::start_query(app, query_id, input).await {
+                    eprintln!("[mensa] forwarded query failed: {}", serde_json::to_string(&e).unwrap_or_default());
+                }
+            });
+        }
+        Ok(Some(CliCommand::Cancel { query_id })) => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                crate::query_cancel::cancel(&app, &query_id, false).await;
+            });
+        }
+        // `sessions list` runs in the calling process; anything else is the app being opened again
+        Ok(Some(CliCommand::SessionsList { .. })) => {}
+        Ok(None) | Err(_) => show_window(app),
+    }
+}
+
+/// Bring the main window forward, opening it if mensa is running headless
+pub fn show_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.webview_windows().values().next() {
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+    let Some(headless) = app.try_state::<HeadlessRun>() else {
+        return;
+    };
+    if let Some(ref config) = headless.window {
+        match tauri::WebviewWindowBuilder::from_config(app, config).and_then(|w| w.build()) {
+            Ok(_) => headless.window_opened.store(true, Ordering::SeqCst),
+            Err(e) => eprintln!("[mensa] Failed to open window: {}", e),
+        }
+    }
+}
+
+async fn list_sessions(cwd: String, json: bool) -> i32 {
+    let sessions = match crate::session_entries(cwd, None, &Default::default()).await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            eprintln!("mensa: {}", e);
+            return 1;
+        }
+    };
+    if json {
+        print_json(&serde_json::to_value(&sessions).unwrap_or_default());
+        return 0;
+    }
+    for session in sessions {
+        let prompt: String = session.first_prompt.lines().next().unwrap_or_default().chars().take(80).collect();
+        let missing = if session.missing_file { "  (missing)" } else { "" };
+        println!(
+            "{}  {:>5} msgs  {}  {}{}",
+            session.session_id, session.message_count, session.modified, prompt, missing
+        );
+    }
+    0
+}
+
+/// Run a command line subcommand to completion; returns the process exit code
+pub fn run(command: CliCommand, mut context: tauri::Context<tauri::Wry>) -> i32 {
+    let args = match command {
+        CliCommand::SessionsList { cwd, json } => {
+            return tauri::async_runtime::block_on(list_sessions(cwd, json));
+        }
+        CliCommand::Query(args) if args.query_id.is_none() => {
+            // Spell the id into the command line so a running app that takes the run over uses it too
+            let query_id = uuid::Uuid::new_v4().to_string();
+            let Ok(exe) = std::env::current_exe() else {
+                eprintln!("mensa: cannot locate the mensa executable");
+                return 1;
+            };
+            return match std::process::Command::new(exe).args(query_command_line(&args, &query_id)).status() {
+                Ok(status) => status.code().unwrap_or(1),
+                Err(e) => {
+                    eprintln!("mensa: {}", e);
+                    1
+                }
+            };
+        }
+        CliCommand::Query(args) => Some(args),
+        CliCommand::Cancel { .. } => None,
+    };
+
+    // Announced before the app starts: if one is already running, the single-instance plugin
+    // hands it this command line and exits here with status 0
+    if let Some(ref args) = args {
+        let query_id = args.query_id.clone().unwrap_or_default();
+        if args.json {
+            print_json(&serde_json::json!({ "event": "query-started", "payload": { "query_id": query_id } }));
+        } else {
+            eprintln!("mensa: query {}", query_id);
+        }
+    }
+
+    let window = context.config_mut().app.windows.drain(..).next();
+    let app = match crate::app_builder()
+        .manage(HeadlessRun {
+            window,
+            window_opened: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        })
+        .build(context)
+    {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("mensa: {}", e);
+            return 1;
+        }
+    };
+
+    let Some(args) = args else {
+        // Reaching here means no app took the cancel over
+        eprintln!("mensa: no running mensa instance to cancel in");
+        return 1;
+    };
+    let query_id = args.query_id.clone().unwrap_or_default();
+    app.run(move |handle, event| match event {
+        tauri::RunEvent::Ready => {
+            let (handle, args, query_id) = (handle.clone(), args.clone(), query_id.clone());
+            tauri::async_runtime::spawn(async move {
+                let code = run_query_headless(handle.clone(), args, query_id).await;
+                let headless = handle.state::<HeadlessRun>();
+                headless.finished.store(true, Ordering::SeqCst);
+                // A window opened meanwhile keeps mensa running as the app
+                if !headless.window_opened.load(Ordering::SeqCst) || handle.webview_windows().is_empty() {
+                    crate::shutdown(&handle).await;
+                    handle.cleanup_before_exit();
+                    std::process::exit(code);
+                }
+            });
+        }
+        // Closing a window opened meanwhile doesn't stop the run
+        tauri::RunEvent::ExitRequested { api, code: None, .. }
+            if !handle.state::<HeadlessRun>().finished.load(Ordering::SeqCst) =>
+        {
+            api.prevent_exit();
+        }
+        tauri::RunEvent::Exit => tauri::async_runtime::block_on(crate::shutdown(handle)),
+        _ => {}
+    });
+    0
+}
+
+/// Exit code for a rejected command line (after printing why)
+pub fn usage_error(message: &str) -> i32 {
+    eprintln!("mensa: {}\n{}", message, USAGE);
+    EXIT_USAGE
+}
@@ -0,0 +1,371 @@
+// mensa - Compare Module
+// Lines up two sessions turn by turn, e.g. the same prompts run with different models or presets
+
+use crate::stream::Usage;
+use crate::transcript::{parse_session_messages, SessionMessage};
+use crate::{cost, digest, read_session_file};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Prompts at least this similar (0..1) can be paired
+const MATCH_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+/// A prompt and everything the assistant did in reply to it
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparedTurn {
+    /// Position of the turn in its session
+    pub index: usize,
+    pub prompt: SessionMessage,
+    pub response: Option<SessionMessage>,
+    pub tool_calls: usize,
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+    /// From the prompt to the last reply message
+    pub duration_ms: Option<i64>,
+    /// Previous form of `duration_ms`, in whole seconds
+    #[cfg(feature = "legacy_fields")]
+    pub duration_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnPair {
+    /// Similarity of the two prompts (1.0 = identical after normalization)
+    pub similarity: f64,
+    pub a: ComparedTurn,
+    pub b: ComparedTurn,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTotals {
+    pub turns: usize,
+    pub tool_calls: usize,
+    pub usage: TokenUsage,
+    pub cost_usd: f64,
+    /// From the first prompt to the last message
+    pub wall_time_ms: Option<i64>,
+    /// Previous form of `wall_time_ms`, in whole seconds
+    #[cfg(feature = "legacy_fields")]
+    pub wall_time_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonSummary {
+    pub a: SessionTotals,
+    pub b: SessionTotals,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionComparison {
+    pub session_a: String,
+    pub session_b: String,
+    /// Matched turns in session order
+    pub pairs: Vec<TurnPair>,
+    pub unmatched_a: Vec<ComparedTurn>,
+    pub unmatched_b: Vec<ComparedTurn>,
+    pub summary: ComparisonSummary,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl TokenUsage {
+    fn add(&mut self, other: &TokenUsage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+}
+
+impl From<&Usage> for TokenUsage {
+    fn from(usage: &Usage) -> Self {
+        TokenUsage {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cache_creation_input_tokens: usage.cache_creation_input_tokens,
+            cache_read_input_tokens: usage.cache_read_input_tokens,
+        }
+    }
+}
+
+/// Whether a transcript message has something parse_session_messages keeps (text, image or tool call)
+fn has_visible_content(message: &Value) -> bool {
+    match message.get("content") {
+        Some(Value::String(s)) => !s.trim().is_empty(),
+        Some(Value::Array(blocks)) => blocks.iter().any(|block| match block.get("type").and_then(|v| v.as_str()) {
+            Some("text") => block.get("text").and_then(|v| v.as_str()).is_some_and(|t| !t.trim().is_empty()),
+            Some("image") => block.get("source").and_then(|s| s.get("data")).is_some(),
+            Some("tool_use") => true,
+            _ => false,
+        }),
+        _ => false,
+    }
+}
+
+/// Token usage and cost per turn, read from the raw transcript. Turns start where
+/// parse_session_messages starts a new user message; the SDK repeats a message's usage on
+/// every content block, so usage is taken once per message id.
+fn turn_usage(content: &str) -> Vec<(TokenUsage, f64)> {
+    let mut turns: Vec<HashMap<String, (TokenUsage, f64)>> = Vec::new();
+    let mut last_role = "";
+    let mut anonymous = 0usize;
+
+    for line in content.lines() {
+        let Ok(parsed) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let msg_type = parsed.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        if (msg_type != "user" && msg_type != "assistant") || crate::stream::hook_event_from_transcript(&parsed).is_some() {
+            continue;
+        }
+        let Some(message) = parsed.get("message") else {
+            continue;
+        };
+
+        if has_visible_content(message) {
+            if msg_type == "user" && last_role != "user" {
+                turns.push(HashMap::new());
+            }
+            last_role = if msg_type == "user" { "user" } else { "assistant" };
+        }
+
+        if msg_type != "assistant" {
+            continue;
+        }
+        let (Some(turn), Some(usage)) = (turns.last_mut(), message.get("usage")) else {
+            continue;
+        };
+        let Ok(usage) = serde_json::from_value::<Usage>(usage.clone()) else {
+            continue;
+        };
+        let model = message.get("model").and_then(|v| v.as_str());
+        let id = match message.get("id").and_then(|v| v.as_str()) {
+            Some(id) => id.to_string(),
+            None => {
+                anonymous += 1;
+                format!("anonymous-{}", anonymous)
+            }
+        };
+        turn.insert(id, (TokenUsage::from(&usage), cost::usage_cost(model, &usage)));
+    }
+
+    turns
+        .into_iter()
+        .map(|messages| {
+            messages.values().fold((TokenUsage::default(), 0.0), |(mut usage, cost), (u, c)| {
+                usage.add(u);
+                (usage, cost + c)
+            })
+        })
+        .collect()
+}
+
+/// A session's turns: each user message with the assistant message that follows it
+fn session_turns(content: &str) -> Result<Vec<ComparedTurn>, String> {
+    let usage = turn_usage(content);
+    let mut turns: Vec<ComparedTurn> = Vec::new();
+
+    for message in parse_session_messages(content)? {
+        if message.role == "user" {
+            let index = turns.len();
+            let (usage, cost_usd) = usage.get(index).cloned().unwrap_or_default();
+            turns.push(ComparedTurn {
+                index,
+                prompt: message,
+                response: None,
+                tool_calls: 0,
+                usage,
+                cost_usd,
+                duration_ms: None,
+                #[cfg(feature = "legacy_fields")]
+                duration_secs: None,
+            });
+            continue;
+        }
+        // Assistant output before the first prompt (e.g. a resumed summary) has no turn to join
+        let Some(turn) = turns.last_mut() else {
+            continue;
+        };
+        turn.tool_calls = message.tools.as_ref().map_or(0, |t| t.len());
+        turn.duration_ms = digest::parse_utc_timestamp_ms(&turn.prompt.timestamp)
+            .zip(digest::parse_utc_timestamp_ms(&message.timestamp))
+            .map(|(start, end)| (end - start).max(0));
+        #[cfg(feature = "legacy_fields")]
+        {
+            turn.duration_secs = turn.duration_ms.map(|ms| ms / 1000);
+        }
+        turn.response = Some(message);
+    }
+    Ok(turns)
+}
+
+fn totals(turns: &[ComparedTurn]) -> SessionTotals {
+    let mut totals = SessionTotals {
+        turns: turns.len(),
+        ..Default::default()
+    };
+    for turn in turns {
+        totals.tool_calls += turn.tool_calls;
+        totals.usage.add(&turn.usage);
+        totals.cost_usd += turn.cost_usd;
+    }
+    let first = turns.first().and_then(|t| digest::parse_utc_timestamp_ms(&t.prompt.timestamp));
+    let last = turns.last().and_then(|t| {
+        let message = t.response.as_ref().unwrap_or(&t.prompt);
+        digest::parse_utc_timestamp_ms(&message.timestamp)
+    });
+    totals.wall_time_ms = first.zip(last).map(|(start, end)| (end - start).max(0));
+    #[cfg(feature = "legacy_fields")]
+    {
+        totals.wall_time_secs = totals.wall_time_ms.map(|ms| ms / 1000);
+    }
+    totals
+}
+
+/// Lowercased words of a prompt, punctuation dropped
+fn normalized_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// Dice coefficient of two word multisets: 1.0 for the same words, tolerant of small edits
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for word in a {
+        *counts.entry(word).or_default() += 1;
+    }
+    let mut shared = 0usize;
+    for word in b {
+        if let Some(count) = counts.get_mut(word.as_str()) {
+            if *count > 0 {
+                *count -= 1;
+                shared += 1;
+            }
+        }
+    }
+    2.0 * shared as f64 / (a.len() + b.len()) as f64
+}
+
+/// Order-preserving pairing of prompts that maximizes total similarity over pairs above
+/// the threshold. Returns (index in a, index in b, similarity) in session order.
+fn align(a: &[ComparedTurn], b: &[ComparedTurn]) -> Vec<(usize, usize, f64)> {
+    let words_a: Vec<Vec<String>> = a.iter().map(|t| normalized_words(&t.prompt.content)).collect();
+    let words_b: Vec<Vec<String>> = b.iter().map(|t| normalized_words(&t.prompt.content)).collect();
+    let (n, m) = (a.len(), b.len());
+    let sim: Vec<Vec<f64>> = words_a
+        .iter()
+        .map(|wa| words_b.iter().map(|wb| similarity(wa, wb)).collect())
+        .collect();
+
+    // best[i][j]: best total for a[i..] and b[j..]
+    let mut best = vec![vec![0.0f64; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            let mut score = best[i + 1][j].max(best[i][j + 1]);
+            if sim[i][j] >= MATCH_THRESHOLD {
+                score = score.max(sim[i][j] + best[i + 1][j + 1]);
+            }
+            best[i][j] = score;
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if sim[i][j] >= MATCH_THRESHOLD && best[i][j] == sim[i][j] + best[i + 1][j + 1] {
+            pairs.push((i, j, sim[i][j]));
+            i += 1;
+            j += 1;
+        } else if best[i][j] == best[i + 1][j] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Compare two sessions of a workspace: prompts are paired in order by fuzzy text match,
+/// each pair carrying both replies with their tool calls, tokens, cost and duration.
+/// Both transcripts are only read.
+#[tauri::command]
+pub async fn compare_sessions(
+    workspace_path: String,
+    session_a: String,
+    session_b: String,
+) -> Result<SessionComparison, String> {
+    let content_a = read_session_file(&workspace_path, &session_a)
+        .await?
+        .ok_or_else(|| format!("Session not found: {}", session_a))?;
+    let content_b = read_session_file(&workspace_path, &session_b)
+        .await?
+        .ok_or_else(|| format!("Session not found: {}", session_b))?;
+
+    let turns_a = session_turns(&content_a)?;
+    let turns_b = session_turns(&content_b)?;
+    let summary = ComparisonSummary {
+        a: totals(&turns_a),
+        b: totals(&turns_b),
+    };
+    let alignment = align(&turns_a, &turns_b);
+
+    let matched_a: HashMap<usize, (usize, f64)> = alignment.iter().map(|&(i, j, s)| (i, (j, s))).collect();
+    let matched_b: HashSet<usize> = alignment.iter().map(|&(_, j, _)| j).collect();
+
+    let mut slots_b: Vec<Option<ComparedTurn>> = turns_b.into_iter().map(Some).collect();
+    let mut pairs = Vec::new();
+    let mut unmatched_a = Vec::new();
+    for turn in turns_a {
+        match matched_a.get(&turn.index) {
+            Some(&(j, similarity)) => {
+                if let Some(b) = slots_b.get_mut(j).and_then(Option::take) {
+                    pairs.push(TurnPair { similarity, a: turn, b });
+                }
+            }
+            None => unmatched_a.push(turn),
+        }
+    }
+    let unmatched_b = slots_b
+        .into_iter()
+        .flatten()
+        .filter(|t| !matched_b.contains(&t.index))
+        .collect();
+
+    Ok(SessionComparison {
+        session_a,
+        session_b,
+        pairs,
+        unmatched_a,
+        unmatched_b,
+        summary,
+    })
+}
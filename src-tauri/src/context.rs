@@ -0,0 +1,363 @@
+// mensa - Session Context Module
+// Estimates what resuming a session carries into context, and resumes image-heavy sessions from a trimmed copy
+
+use crate::{bookmarks, find_session_path, fsutil, history};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Rough characters per token for text estimates
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Heaviest items listed in a context report
+const MAX_HEAVY_ITEMS: usize = 10;
+
+/// Images at least this large (decoded bytes) are replaced in a trimmed copy
+const MIN_TRIMMED_IMAGE_BYTES: usize = 16 * 1024;
+
+/// Key of the trimmed-copy mapping in the project's mensa-meta.json
+const TRIMMED_SESSIONS_KEY: &str = "trimmedSessions";
+
+/// One large item of a transcript
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeavyItem {
+    /// "image" | "toolOutput" | "text"
+    pub kind: String,
+    /// 1-based line in the transcript file
+    pub line: usize,
+    pub role: String,
+    pub bytes: usize,
+    /// Media type of an image, start of a text or tool output
+    pub description: String,
+}
+
+/// Estimate of what a resume sends along
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionContextReport {
+    pub session_id: String,
+    pub transcript_bytes: u64,
+    /// Characters of text, tool inputs and tool outputs
+    pub text_chars: usize,
+    /// text_chars / 4
+    pub estimated_text_tokens: usize,
+    pub image_count: usize,
+    /// Decoded size of all images
+    pub image_bytes: usize,
+    /// Images a trimmed resume would replace, and their decoded size
+    pub trimmable_image_count: usize,
+    pub trimmable_image_bytes: usize,
+    pub tool_output_bytes: usize,
+    /// Largest items first
+    pub heaviest: Vec<HeavyItem>,
+}
+
+/// A resume that ran from a trimmed copy of its session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrimmedResume {
+    pub original_session_id: String,
+    pub trimmed_session_id: String,
+    pub images_removed: usize,
+    /// Transcript bytes the copy is smaller by
+    pub bytes_saved: u64,
+    pub created_at: i64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Media type and decoded size of a base64 image block
+fn image_info(block: &Value) -> Option<(String, usize)> {
+    if block.get("type").and_then(|v| v.as_str()) != Some("image") {
+        return None;
+    }
+    let source = block.get("source")?;
+    let data = source.get("data").and_then(|v| v.as_str())?;
+    let media_type = source
+        .get("media_type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("image")
+        .to_string();
+    Some((media_type, data.len() / 4 * 3))
+}
+
+fn preview(text: &str) -> String {
+    let line = text.lines().find(|l| !l.trim().is_empty()).unwrap_or_default().trim();
+    match line.char_indices().nth(80) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None => line.to_string(),
+    }
+}
+
+/// Message content blocks of a user or assistant transcript line
+fn content_blocks(parsed: &Value) -> Option<&Vec<Value>> {
+    let role = parsed.get("type").and_then(|v| v.as_str())?;
+    if role != "user" && role != "assistant" {
+        return None;
+    }
+    parsed.get("message")?.get("content")?.as_array()
+}
+
+fn analyze(session_id: &str, content: &str) -> SessionContextReport {
+    let mut report = SessionContextReport {
+        session_id: session_id.to_string(),
+        transcript_bytes: content.len() as u64,
+        ..Default::default()
+    };
+    let mut heavy: Vec<HeavyItem> = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let Ok(parsed) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let role = parsed.get("type").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let item = |kind: &str, bytes: usize, description: String| HeavyItem {
+            kind: kind.to_string(),
+            line: index + 1,
+            role: role.clone(),
+            bytes,
+            description,
+        };
+
+        if let Some(Value::String(text)) = parsed.get("message").and_then(|m| m.get("content")) {
+            report.text_chars += text.chars().count();
+            heavy.push(item("text", text.len(), preview(text)));
+            continue;
+        }
+        let Some(blocks) = content_blocks(&parsed) else {
+            continue;
+        };
+        for block in blocks {
+            let mut images = vec![block];
+            match block.get("type").and_then(|v| v.as_str()) {
+                Some("text") => {
+                    let text = block.get("text").and_then(|v| v.as_str()).unwrap_or_default();
+                    report.text_chars += text.chars().count();
+                    heavy.push(item("text", text.len(), preview(text)));
+                }
+                Some("tool_use") => {
+                    let input = block.get("input").map(|v| v.to_string()).unwrap_or_default();
+                    report.text_chars += input.chars().count();
+                }
+                Some("tool_result") => {
+                    let output = crate::tool_output::tool_result_text(block.get("content")).unwrap_or_default();
+                    report.text_chars += output.chars().count();
+                    report.tool_output_bytes += output.len();
+                    heavy.push(item("toolOutput", output.len(), preview(&output)));
+                    if let Some(Value::Array(inner)) = block.get("content") {
+                        images.extend(inner.iter());
+                    }
+                }
+                _ => {}
+            }
+            for (media_type, bytes) in images.into_iter().filter_map(image_info) {
+                report.image_count += 1;
+                report.image_bytes += bytes;
+                if bytes >= MIN_TRIMMED_IMAGE_BYTES {
+                    report.trimmable_image_count += 1;
+                    report.trimmable_image_bytes += bytes;
+                }
+                heavy.push(item("image", bytes, media_type));
+            }
+        }
+    }
+
+    report.estimated_text_tokens = report.text_chars / CHARS_PER_TOKEN;
+    heavy.sort_by_key(|item| std::cmp::Reverse(item.bytes));
+    heavy.truncate(MAX_HEAVY_ITEMS);
+    report.heaviest = heavy;
+    report
+}
+
+/// Replace a large image block with a short text placeholder; returns whether it did
+fn trim_image(block: &mut Value) -> bool {
+    match image_info(block) {
+        Some((media_type, bytes)) if bytes >= MIN_TRIMMED_IMAGE_BYTES => {
+            *block = serde_json::json!({
+                "type": "text",
+                "text": format!("[Image removed to save context: {}, {} KB]", media_type, bytes / 1024)
+            });
+            true
+        }
+        _ => false,
+    }
+}
+
+/// A copy of a transcript with large images replaced and the session id rewritten.
+/// Returns None when there was nothing to trim.
+fn trimmed_transcript(content: &str, original_id: &str, trimmed_id: &str) -> Option<(String, usize)> {
+    let mut removed = 0;
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        let Ok(mut parsed) = serde_json::from_str::<Value>(line) else {
+            lines.push(line.to_string());
+            continue;
+        };
+        if let Some(blocks) = parsed
+            .get_mut("message")
+            .and_then(|m| m.get_mut("content"))
+            .and_then(|c| c.as_array_mut())
+        {
+            for block in blocks.iter_mut() {
+                if trim_image(block) {
+                    removed += 1;
+                } else if let Some(Value::Array(inner)) = block.get_mut("content") {
+                    removed += inner.iter_mut().map(trim_image).filter(|&trimmed| trimmed).count();
+                }
+            }
+        }
+        if parsed.get("sessionId").and_then(|v| v.as_str()) == Some(original_id) {
+            parsed["sessionId"] = Value::from(trimmed_id);
+        }
+        lines.push(parsed.to_string());
+    }
+    if removed == 0 {
+        return None;
+    }
+    let mut trimmed = lines.join("\n");
+    trimmed.push('\n');
+    Some((trimmed, removed))
+}
+
+/// Copy the trimmed-copy mappings of migrated sessions into another project's metadata under
+/// their new session ids; with `remove`, drop them from `from`. Returns how many were carried.
+pub fn carry_trimmed_sessions(
+    from: &mut bookmarks::ProjectMeta,
+    to: &mut bookmarks::ProjectMeta,
+    renames: &HashMap<String, String>,
+    remove: bool,
+) -> usize {
+    let Some(Value::Object(source)) = from.other.get_mut(TRIMMED_SESSIONS_KEY) else {
+        return 0;
+    };
+    let mut carried = Map::new();
+    for (trimmed_id, entry) in source.iter() {
+        let Some(new_trimmed_id) = renames.get(trimmed_id) else {
+            continue;
+        };
+        let mut entry = entry.clone();
+        entry["trimmedSessionId"] = Value::from(new_trimmed_id.as_str());
+        if let Some(new_original) = entry
+            .get("originalSessionId")
+            .and_then(|v| v.as_str())
+            .and_then(|id| renames.get(id))
+        {
+            entry["originalSessionId"] = Value::from(new_original.as_str());
+        }
+        carried.insert(new_trimmed_id.clone(), entry);
+    }
+    if remove {
+        source.retain(|trimmed_id, _| !renames.contains_key(trimmed_id));
+    }
+    let count = carried.len();
+    if count > 0 {
+        match to.other.get_mut(TRIMMED_SESSIONS_KEY) {
+            Some(Value::Object(target)) => target.extend(carried),
+            _ => {
+                to.other.insert(TRIMMED_SESSIONS_KEY.to_string(), Value::Object(carried));
+            }
+        }
+    }
+    count
+}
+
+async fn record_trimmed_session(app: &tauri::AppHandle, project_dir: &Path, trimmed: &TrimmedResume) -> Result<(), String> {
+    let guard = bookmarks::lock_meta(app, project_dir).await;
+    let mut meta = guard.load().await?;
+    let entry = serde_json::to_value(trimmed).map_err(|e| format!("Failed to serialize trimmed session: {}", e))?;
+    match meta.other.get_mut(TRIMMED_SESSIONS_KEY) {
+        Some(Value::Object(map)) => {
+            map.insert(trimmed.trimmed_session_id.clone(), entry);
+        }
+        _ => {
+            let mut map = Map::new();
+            map.insert(trimmed.trimmed_session_id.clone(), entry);
+            meta.other.insert(TRIMMED_SESSIONS_KEY.to_string(), Value::Object(map));
+        }
+    }
+    guard.save(&meta).await
+}
+
+/// Write a trimmed companion of a session next to it (the original is left untouched) and
+/// record the mapping. Returns None when the session has no large images to trim.
+pub async fn prepare_trimmed_resume(app: &tauri::AppHandle, workspace_path: &str, session_id: &str) -> Result<Option<TrimmedResume>, String> {
+    let Some(original_path) = find_session_path(workspace_path, session_id)? else {
+        return Ok(None);
+    };
+    let content = tokio::fs::read_to_string(&original_path)
+        .await
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+
+    let trimmed_id = uuid::Uuid::new_v4().to_string();
+    let original_id = session_id.to_string();
+    let trimmed_id_for_copy = trimmed_id.clone();
+    let trimmed = tokio::task::spawn_blocking(move || {
+        trimmed_transcript(&content, &original_id, &trimmed_id_for_copy).map(|t| (t, content.len()))
+    })
+    .await
+    .map_err(|e| format!("Failed to trim session: {}", e))?;
+    let Some(((trimmed_content, images_removed), original_len)) = trimmed else {
+        return Ok(None);
+    };
+
+    let project_dir = original_path
+        .parent()
+        .ok_or_else(|| format!("Session not found: {}", session_id))?
+        .to_path_buf();
+    let trimmed_path = project_dir.join(format!("{}.jsonl", trimmed_id));
+    let bytes_saved = original_len.saturating_sub(trimmed_content.len()) as u64;
+    tokio::task::spawn_blocking(move || fsutil::write_atomic(&trimmed_path, trimmed_content.as_bytes()))
+        .await
+        .map_err(|e| format!("Failed to write trimmed session: {}", e))??;
+
+    let trimmed = TrimmedResume {
+        original_session_id: session_id.to_string(),
+        trimmed_session_id: trimmed_id,
+        images_removed,
+        bytes_saved,
+        created_at: history::now_secs(),
+    };
+    record_trimmed_session(app, &project_dir, &trimmed).await?;
+    Ok(Some(trimmed))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Estimate what resuming a session sends along: text tokens, images, tool output, heaviest items
+#[tauri::command]
+pub async fn analyze_session_context(workspace_path: String, session_id: String) -> Result<SessionContextReport, String> {
+    let content = crate::read_session_file(&workspace_path, &session_id)
+        .await?
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    tokio::task::spawn_blocking(move || analyze(&session_id, &content))
+        .await
+        .map_err(|e| format!("Failed to analyze session: {}", e))
+}
+
+/// The original of a trimmed companion session, so it can be shown instead of the copy
+#[tauri::command]
+pub async fn get_trimmed_session_origin(
+    app: tauri::AppHandle,
+    workspace_path: String,
+    session_id: String,
+) -> Result<Option<TrimmedResume>, String> {
+    let Ok(project_dir) = bookmarks::session_project_dir(Some(&workspace_path), &session_id) else {
+        return Ok(None);
+    };
+    let meta = bookmarks::load_meta(&app, &project_dir).await?;
+    Ok(meta
+        .other
+        .get(TRIMMED_SESSIONS_KEY)
+        .and_then(|map| map.get(&session_id))
+        .and_then(|entry| serde_json::from_value(entry.clone()).ok()))
+}
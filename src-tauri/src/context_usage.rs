@@ -0,0 +1,204 @@
+// mensa - Context Usage Module
+// How full the context window is while a query runs, from the token usage streamed by the agent
+
+use crate::models;
+use crate::stream::{MessageBody, Usage};
+use crate::AppState;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Percentages at which warnings fire when settings don't choose
+pub const DEFAULT_WARNING_PERCENTS: [u8; 2] = [80, 95];
+
+/// `claude-context-usage` goes out at most this often
+const EMIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Latest context usage of a running query; also the `claude-context-usage` payload
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ContextUsage {
+    pub query_id: String,
+    pub model: Option<String>,
+    /// Tokens the latest assistant message saw plus what it wrote
+    pub used_tokens: u64,
+    pub context_limit: u64,
+    pub percent: f64,
+    /// Running totals over the query's assistant messages
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    /// Output tokens per second since the query started
+    pub output_tokens_per_sec: f64,
+}
+
+/// Payload of `claude-context-warning`, sent once per threshold crossed
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextWarning {
+    pub query_id: String,
+    pub threshold: u8,
+    /// The highest configured threshold: the window is nearly full
+    pub critical: bool,
+    pub used_tokens: u64,
+    pub context_limit: u64,
+    pub percent: f64,
+}
+
+/// Context usage of one run. Usage is kept per message id and replaced, because the
+/// SDK repeats an assistant message's usage on every content block it streams.
+pub struct ContextTracker {
+    query_id: String,
+    started_at: Instant,
+    /// Model-substring → context window overrides from settings
+    limits: HashMap<String, u64>,
+    /// Ascending, deduplicated
+    thresholds: Vec<u8>,
+    by_message: HashMap<String, Usage>,
+    /// Messages that came without an id
+    unidentified: Vec<Usage>,
+    warned: Vec<u8>,
+    last_emit: Option<Instant>,
+    /// The latest snapshot was held back by the throttle
+    unsent: bool,
+    latest: ContextUsage,
+}
+
+/// What a recorded message calls for
+pub struct ContextUpdate {
+    /// Send as `claude-context-usage` (None while throttled)
+    pub usage: Option<ContextUsage>,
+    /// Thresholds crossed by this message
+    pub warnings: Vec<ContextWarning>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Context window of a model: the longest matching settings override, then the model catalog
+pub fn context_limit(model: Option<&str>, overrides: &HashMap<String, u64>) -> u64 {
+    let model = model.unwrap_or_default().to_ascii_lowercase();
+    overrides
+        .iter()
+        .filter(|(family, _)| model.contains(&family.to_ascii_lowercase()))
+        .max_by_key(|(family, _)| family.len())
+        .map(|(_, &limit)| limit)
+        .unwrap_or_else(|| models::context_window(Some(&model)))
+}
+
+impl ContextTracker {
+    pub fn new(query_id: &str, limits: HashMap<String, u64>, thresholds: &[u8]) -> Self {
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_unstable();
+        thresholds.dedup();
+        ContextTracker {
+            query_id: query_id.to_string(),
+            started_at: Instant::now(),
+            limits,
+            thresholds,
+            by_message: HashMap::new(),
+            unidentified: Vec::new(),
+            warned: Vec::new(),
+            last_emit: None,
+            unsent: false,
+            latest: ContextUsage {
+                query_id: query_id.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Account for an assistant message's usage
+    pub fn record(&mut self, message: &MessageBody) -> ContextUpdate {
+        let Some(usage) = &message.usage else {
+            return ContextUpdate {
+                usage: None,
+                warnings: Vec::new(),
+            };
+        };
+        match &message.id {
+            Some(id) => {
+                self.by_message.insert(id.clone(), usage.clone());
+            }
+            None => self.unidentified.push(usage.clone()),
+        }
+
+        let totals = self.by_message.values().chain(self.unidentified.iter());
+        let mut latest = ContextUsage {
+            query_id: self.query_id.clone(),
+            model: message.model.clone().or_else(|| self.latest.model.take()),
+            used_tokens: usage.input_tokens
+                + usage.cache_creation_input_tokens
+                + usage.cache_read_input_tokens
+                + usage.output_tokens,
+            ..Default::default()
+        };
+        for usage in totals {
+            latest.input_tokens += usage.input_tokens;
+            latest.output_tokens += usage.output_tokens;
+            latest.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+            latest.cache_read_input_tokens += usage.cache_read_input_tokens;
+        }
+        latest.context_limit = context_limit(latest.model.as_deref(), &self.limits);
+        latest.percent = latest.used_tokens as f64 * 100.0 / latest.context_limit as f64;
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            latest.output_tokens_per_sec = latest.output_tokens as f64 / elapsed;
+        }
+        self.latest = latest;
+
+        let highest = self.thresholds.last().copied();
+        let mut warnings = Vec::new();
+        for &threshold in &self.thresholds {
+            if self.latest.percent >= threshold as f64 && !self.warned.contains(&threshold) {
+                self.warned.push(threshold);
+                warnings.push(ContextWarning {
+                    query_id: self.query_id.clone(),
+                    threshold,
+                    critical: Some(threshold) == highest,
+                    used_tokens: self.latest.used_tokens,
+                    context_limit: self.latest.context_limit,
+                    percent: self.latest.percent,
+                });
+            }
+        }
+
+        self.unsent = self.last_emit.is_some_and(|at| at.elapsed() < EMIT_INTERVAL);
+        let usage = (!self.unsent).then(|| {
+            self.last_emit = Some(Instant::now());
+            self.latest.clone()
+        });
+        ContextUpdate { usage, warnings }
+    }
+
+    pub fn latest(&self) -> &ContextUsage {
+        &self.latest
+    }
+
+    /// The snapshot the throttle held back, so the run's final usage still goes out
+    pub fn take_unsent(&mut self) -> Option<ContextUsage> {
+        std::mem::take(&mut self.unsent).then(|| self.latest.clone())
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Latest context usage of a running query; None before its first assistant message
+#[tauri::command]
+pub async fn get_query_context_usage(
+    state: State<'_, AppState>,
+    query_id: String,
+) -> Result<Option<ContextUsage>, String> {
+    let queries = state.active_queries.lock().await;
+    let query = queries
+        .get(&query_id)
+        .ok_or_else(|| format!("Query {} is no longer running", query_id))?;
+    Ok(query.context_usage.clone())
+}
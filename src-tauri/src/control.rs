@@ -0,0 +1,793 @@
+// mensa - Control Module
+// Opt-in local endpoint through which editor extensions start, cancel and check queries:
+// NDJSON requests over a Unix socket (a named pipe on Windows), authenticated with a token file
+
+use crate::cancel::CancellationToken;
+use crate::query_cancel::CancelOutcome;
+use crate::{settings, ActiveQueryInfo, AppState, QueryInput};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::task::JoinSet;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Longest accepted request line, newline excluded
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Connections served at once; further ones are refused with an error
+const MAX_CONNECTIONS: usize = 16;
+
+/// Queries started through the endpoint whose outcome `status` still reports
+const MAX_LAUNCHED: usize = 100;
+
+/// File holding the token each request must carry, readable by the user only
+const TOKEN_FILE: &str = "control.token";
+
+/// Directory the socket is bound in, reachable by the user only, so the socket never is
+/// by anyone else (not even before its own permissions are set)
+#[cfg(unix)]
+const SOCKET_DIR: &str = "control";
+
+#[cfg(unix)]
+const SOCKET_FILE: &str = "control.sock";
+
+#[cfg(windows)]
+const PIPE_NAME: &str = r"\\.\pipe\mensa-control";
+
+/// One request line: `{"id": ..., "token": "...", "verb": "query", ...}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "verb", rename_all = "camelCase", rename_all_fields = "camelCase")]
+enum Request {
+    /// Start a query; answered with its id as soon as it's accepted
+    Query {
+        prompt: String,
+        working_dir: String,
+        preset: Option<String>,
+        resume_session: Option<String>,
+    },
+    Cancel {
+        query_id: String,
+    },
+    /// One query's state, or every running query without an id
+    Status {
+        query_id: Option<String>,
+    },
+    /// Bring the window forward, asking it to show `working_dir` when given
+    Open {
+        working_dir: Option<String>,
+    },
+}
+
+/// Error answered for a request (`{"id", "ok": false, "error": {"kind", "message"}}`)
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+enum ControlError {
+    /// Not a JSON object, or missing or mistyped fields
+    Malformed { message: String },
+    /// The line exceeded MAX_MESSAGE_BYTES; the connection is closed after this
+    TooLarge { message: String },
+    Unauthorized { message: String },
+    /// MAX_CONNECTIONS are already open
+    Busy { message: String },
+    Failed { message: String },
+}
+
+impl From<String> for ControlError {
+    fn from(message: String) -> Self {
+        ControlError::Failed { message }
+    }
+}
+
+/// What became of a query started through the endpoint, for `status`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Launched {
+    status: &'static str, // "starting" | "finished" | "failed"
+    error: Option<String>,
+}
+
+/// Queries started through the endpoint, oldest first
+#[derive(Default)]
+struct LaunchLog {
+    order: VecDeque<String>,
+    entries: HashMap<String, Launched>,
+}
+
+/// Future returned by a `Pipeline`
+type Pending<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// What the endpoint's verbs act on, behind a trait so the server can run against a stand-in
+trait Pipeline: Send + Sync {
+    /// Run `input` as `query_id`, resolving once it has finished; the error is the serialized QueryError
+    fn run(&self, query_id: String, input: QueryInput) -> Pending<Result<(), String>>;
+    fn cancel(&self, query_id: String) -> Pending<CancelOutcome>;
+    /// Running and queued queries
+    fn active(&self) -> Pending<Result<Vec<ActiveQueryInfo>, String>>;
+    /// Bring the window forward, asking it to show `working_dir` when given
+    fn open(&self, working_dir: Option<String>);
+}
+
+/// The app's own query pipeline
+struct AppPipeline(tauri::AppHandle);
+
+/// State shared by the connections of one server
+struct Server {
+    pipeline: Arc<dyn Pipeline>,
+    token: String,
+    launched: Mutex<LaunchLog>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))
+}
+
+/// Write a fresh token for this run, readable by the user only
+fn write_token(path: &Path) -> Result<String, String> {
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let _ = std::fs::remove_file(path);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    std::io::Write::write_all(&mut file, token.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(token)
+}
+
+/// Compare without stopping at the first differing byte
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Read one line of at most MAX_MESSAGE_BYTES. None at end of input; Err(TooLarge) past the cap.
+async fn read_line<R: AsyncRead + Unpin>(reader: &mut BufReader<R>) -> Result<Option<Vec<u8>>, ControlError> {
+    let mut line = Vec::new();
+    let read = (&mut *reader)
+        .take(MAX_MESSAGE_BYTES as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await
+        .map_err(|e| format!("Failed to read request: {}", e))?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    } else if line.len() > MAX_MESSAGE_BYTES {
+        return Err(ControlError::TooLarge {
+            message: format!("Requests are limited to {} bytes", MAX_MESSAGE_BYTES),
+        });
+    }
+    Ok(Some(line))
+}
+
+/// Check the line's token and parse its verb; the request id is returned either way to echo back
+fn parse_request(line: &[u8], token: &str) -> (Value, Result<Request, ControlError>) {
+    let mut value: Value = match serde_json::from_slice(line) {
+        Ok(Value::Object(value)) => Value::Object(value),
+        Ok(_) => return (Value::Null, Err(ControlError::Malformed { message: "Request must be a JSON object".to_string() })),
+        Err(e) => return (Value::Null, Err(ControlError::Malformed { message: format!("Invalid JSON: {}", e) })),
+    };
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let authorized = value.get("token").and_then(|t| t.as_str()).is_some_and(|t| token_matches(t, token));
+    if !authorized {
+        return (id, Err(ControlError::Unauthorized { message: "Missing or wrong token".to_string() }));
+    }
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("id");
+        fields.remove("token");
+    }
+    let request = serde_json::from_value(value).map_err(|e| ControlError::Malformed { message: e.to_string() });
+    (id, request)
+}
+
+fn response(id: Value, result: Result<Value, ControlError>) -> Value {
+    match result {
+        Ok(result) => serde_json::json!({ "id": id, "ok": true, "result": result }),
+        Err(error) => serde_json::json!({ "id": id, "ok": false, "error": error }),
+    }
+}
+
+impl Pipeline for AppPipeline {
+    fn run(&self, query_id: String, input: QueryInput) -> Pending<Result<(), String>> {
+        let app = self.0.clone();
+        Box::pin(async move {
+            crate::start_query(app, query_id, input)
+                .await
+                .map(|_| ())
+                .map_err(|e| serde_json::to_string(&e).unwrap_or_default())
+        })
+    }
+
+    fn cancel(&self, query_id: String) -> Pending<CancelOutcome> {
+        let app = self.0.clone();
+        Box::pin(async move { crate::query_cancel::cancel(&app, &query_id, false).await })
+    }
+
+    fn active(&self) -> Pending<Result<Vec<ActiveQueryInfo>, String>> {
+        let app = self.0.clone();
+        Box::pin(async move { crate::list_active_queries(app.state::<AppState>()).await })
+    }
+
+    fn open(&self, working_dir: Option<String>) {
+        crate::cli::show_window(&self.0);
+        if let Some(working_dir) = working_dir {
+            let _ = self.0.emit("workspace-open-requested", serde_json::json!({ "working_dir": working_dir }));
+        }
+    }
+}
+
+impl Server {
+    fn record(&self, query_id: &str, launched: Launched) {
+        let Ok(mut log) = self.launched.lock() else {
+            return;
+        };
+        if log.entries.insert(query_id.to_string(), launched).is_none() {
+            log.order.push_back(query_id.to_string());
+        }
+        while log.order.len() > MAX_LAUNCHED {
+            if let Some(oldest) = log.order.pop_front() {
+                log.entries.remove(&oldest);
+            }
+        }
+    }
+
+    async fn dispatch(self: &Arc<Self>, request: Request) -> Result<Value, ControlError> {
+        match request {
+            Request::Query { prompt, working_dir, preset, resume_session } => {
+                if prompt.trim().is_empty() {
+                    return Err(ControlError::Malformed { message: "prompt must not be empty".to_string() });
+                }
+                if !Path::new(&working_dir).is_dir() {
+                    return Err(format!("{} is not a directory", working_dir).into());
+                }
+                let query_id = uuid::Uuid::new_v4().to_string();
+                let input = QueryInput {
+                    prompt,
+                    working_dir,
+                    preset,
+                    resume_session,
+                    ..Default::default()
+                };
+                self.record(&query_id, Launched { status: "starting", error: None });
+                let (server, id) = (self.clone(), query_id.clone());
+                // Events are held until a window subscribes to the query
+                tauri::async_runtime::spawn(async move {
+                    let launched = match server.pipeline.run(id.clone(), input).await {
+                        Ok(()) => Launched { status: "finished", error: None },
+                        Err(error) => {
+                            eprintln!("[mensa] Control query failed: {}", error);
+                            Launched { status: "failed", error: Some(error) }
+                        }
+                    };
+                    server.record(&id, launched);
+                });
+                Ok(serde_json::json!({ "queryId": query_id }))
+            }
+            Request::Cancel { query_id } => {
+                let outcome = self.pipeline.cancel(query_id).await;
+                let cancelled = outcome.action != crate::query_cancel::CancelAction::Nothing;
+                Ok(serde_json::json!({ "cancelled": cancelled, "outcome": outcome }))
+            }
+            Request::Status { query_id: None } => {
+                let queries = self.pipeline.active().await?;
+                Ok(serde_json::json!({ "queries": queries }))
+            }
+            Request::Status { query_id: Some(query_id) } => {
+                let queries = self.pipeline.active().await?;
+                if let Some(active) = queries.into_iter().find(|q| q.query_id == query_id) {
+                    return Ok(serde_json::json!({ "queryId": query_id, "status": active.status }));
+                }
+                let launched = self.launched.lock().ok().and_then(|log| log.entries.get(&query_id).cloned());
+                Ok(match launched {
+                    Some(launched) => serde_json::json!({ "queryId": query_id, "status": launched.status, "error": launched.error }),
+                    None => serde_json::json!({ "queryId": query_id, "status": "unknown" }),
+                })
+            }
+            Request::Open { working_dir } => {
+                self.pipeline.open(working_dir);
+                Ok(serde_json::json!({}))
+            }
+        }
+    }
+}
+
+/// Answer requests on one connection until it closes or sends an oversized line
+async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(server: Arc<Server>, stream: S) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    loop {
+        let (id, result, close) = match read_line(&mut reader).await {
+            Ok(None) => return,
+            Ok(Some(line)) if line.iter().all(u8::is_ascii_whitespace) => continue,
+            Ok(Some(line)) => match parse_request(&line, &server.token) {
+                (id, Ok(request)) => (id, server.dispatch(request).await, false),
+                (id, Err(e)) => (id, Err(e), false),
+            },
+            Err(e) => (Value::Null, Err(e), true),
+        };
+        let mut line = response(id, result).to_string();
+        line.push('\n');
+        if writer.write_all(line.as_bytes()).await.is_err() || close {
+            return;
+        }
+    }
+}
+
+/// Serve `stream` if a connection slot is free, else refuse it
+fn accept<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(server: &Arc<Server>, connections: &mut JoinSet<()>, mut stream: S) {
+    while connections.try_join_next().is_some() {}
+    if connections.len() >= MAX_CONNECTIONS {
+        let refusal = response(Value::Null, Err(ControlError::Busy { message: "Too many open connections".to_string() }));
+        connections.spawn(async move {
+            let _ = stream.write_all(format!("{}\n", refusal).as_bytes()).await;
+        });
+        return;
+    }
+    connections.spawn(serve_connection(server.clone(), stream));
+}
+
+#[cfg(unix)]
+fn socket_path(dir: &Path) -> PathBuf {
+    dir.join(SOCKET_DIR).join(SOCKET_FILE)
+}
+
+/// Create the socket's directory owner-only, or tighten one left by an earlier run
+#[cfg(unix)]
+fn private_dir(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            let metadata = std::fs::symlink_metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            if !metadata.is_dir() {
+                return Err(format!("{} is not a directory", path.display()));
+            }
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700))
+                .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))
+        }
+        Err(e) => Err(format!("Failed to create {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(unix)]
+async fn listen(server: Arc<Server>, dir: &Path, token: CancellationToken) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = socket_path(dir);
+    private_dir(path.parent().unwrap_or(dir))?;
+    // Only one mensa runs at a time, so a socket left here is from a run that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).map_err(|e| format!("Failed to listen on {}: {}", path.display(), e))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+    eprintln!("[mensa] Control endpoint listening on {}", path.display());
+
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => accept(&server, &mut connections, stream),
+                Err(e) => eprintln!("[mensa] Control connection failed: {}", e),
+            },
+            _ = token.cancelled() => break,
+        }
+    }
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn listen(server: Arc<Server>, _dir: &Path, token: CancellationToken) -> Result<(), String> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let create = |first: bool| {
+        ServerOptions::new()
+            .first_pipe_instance(first)
+            .reject_remote_clients(true)
+            .create(PIPE_NAME)
+            .map_err(|e| format!("Failed to create pipe {}: {}", PIPE_NAME, e))
+    };
+    let mut pipe = create(true)?;
+    eprintln!("[mensa] Control endpoint listening on {}", PIPE_NAME);
+
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            connected = pipe.connect() => match connected {
+                // The next client needs a pipe instance of its own
+                Ok(()) => {
+                    let stream = std::mem::replace(&mut pipe, create(false)?);
+                    accept(&server, &mut connections, stream);
+                }
+                Err(e) => eprintln!("[mensa] Control connection failed: {}", e),
+            },
+            _ = token.cancelled() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Run the control endpoint until `token` is cancelled, when the setting is on at startup.
+/// Not started for headless `mensa query` runs.
+pub async fn serve(app: tauri::AppHandle, token: CancellationToken) {
+    if app.try_state::<crate::cli::HeadlessRun>().is_some() {
+        return;
+    }
+    let enabled = settings::load(&app, &app.state::<AppState>().settings)
+        .await
+        .map(|s| s.control_server_enabled)
+        .unwrap_or(false);
+    if !enabled {
+        return;
+    }
+    let dir = match data_dir(&app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("[mensa] {}", e);
+            return;
+        }
+    };
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        eprintln!("[mensa] Failed to create {}: {}", dir.display(), e);
+        return;
+    }
+    let token_path = dir.join(TOKEN_FILE);
+    let server = match write_token(&token_path) {
+        Ok(secret) => Arc::new(Server {
+            pipeline: Arc::new(AppPipeline(app)),
+            token: secret,
+            launched: Mutex::new(LaunchLog::default()),
+        }),
+        Err(e) => {
+            eprintln!("[mensa] {}", e);
+            return;
+        }
+    };
+    if let Err(e) = listen(server, &dir, token).await {
+        eprintln!("[mensa] {}", e);
+    }
+    let _ = std::fs::remove_file(&token_path);
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::query_cancel::{CancelAction, CancelFound};
+    use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+    use tokio::net::UnixStream;
+    use tokio::sync::Semaphore;
+
+    const TOKEN: &str = "secret";
+
+    /// How long a reply, or a state the tests poll for, may take on a loaded machine
+    const PATIENCE: std::time::Duration = std::time::Duration::from_secs(5);
+
+    /// Stand-in pipeline: a query runs until `finish` is released (a prompt of "fail" fails at
+    /// once), "busy" is the one active query and the only one a cancel finds
+    struct StubPipeline {
+        started: Mutex<Vec<(String, String)>>,
+        opened: Mutex<Vec<Option<String>>>,
+        finish: Arc<Semaphore>,
+    }
+
+    impl Default for StubPipeline {
+        fn default() -> Self {
+            Self {
+                started: Mutex::default(),
+                opened: Mutex::default(),
+                finish: Arc::new(Semaphore::new(0)),
+            }
+        }
+    }
+
+    impl Pipeline for StubPipeline {
+        fn run(&self, query_id: String, input: QueryInput) -> Pending<Result<(), String>> {
+            self.started.lock().unwrap().push((query_id, input.prompt.clone()));
+            let finish = self.finish.clone();
+            Box::pin(async move {
+                if input.prompt == "fail" {
+                    return Err(r#"{"kind":"failed","message":"no node"}"#.to_string());
+                }
+                let _ = finish.acquire().await.map(|permit| permit.forget());
+                Ok(())
+            })
+        }
+
+        fn cancel(&self, query_id: String) -> Pending<CancelOutcome> {
+            let (found, action) = if query_id == "busy" {
+                (CancelFound::Running, CancelAction::Stopped)
+            } else {
+                (CancelFound::NotFound, CancelAction::Nothing)
+            };
+            Box::pin(async move {
+                CancelOutcome {
+                    query_id,
+                    found,
+                    action,
+                    cancelled_followup: None,
+                    kept_followup: None,
+                }
+            })
+        }
+
+        fn active(&self) -> Pending<Result<Vec<ActiveQueryInfo>, String>> {
+            Box::pin(async {
+                Ok(vec![ActiveQueryInfo {
+                    query_id: "busy".to_string(),
+                    status: "running".to_string(),
+                    predecessor: None,
+                }])
+            })
+        }
+
+        fn open(&self, working_dir: Option<String>) {
+            self.opened.lock().unwrap().push(working_dir);
+        }
+    }
+
+    /// A server listening in a scratch directory, stopped when the token is cancelled
+    struct Endpoint {
+        dir: tempfile::TempDir,
+        pipeline: Arc<StubPipeline>,
+        stop: CancellationToken,
+    }
+
+    struct Client {
+        reader: BufReader<OwnedReadHalf>,
+        writer: OwnedWriteHalf,
+    }
+
+    async fn endpoint() -> Endpoint {
+        let dir = tempfile::tempdir().unwrap();
+        let pipeline = Arc::new(StubPipeline::default());
+        let server = Arc::new(Server {
+            pipeline: pipeline.clone(),
+            token: TOKEN.to_string(),
+            launched: Mutex::new(LaunchLog::default()),
+        });
+        let stop = CancellationToken::default();
+        let (path, token) = (dir.path().to_path_buf(), stop.clone());
+        tokio::spawn(async move { listen(server, &path, token).await.unwrap() });
+        while !socket_path(dir.path()).exists() {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        Endpoint { dir, pipeline, stop }
+    }
+
+    impl Endpoint {
+        async fn connect(&self) -> Client {
+            let (reader, writer) = UnixStream::connect(socket_path(self.dir.path())).await.unwrap().into_split();
+            Client { reader: BufReader::new(reader), writer }
+        }
+    }
+
+    impl Client {
+        async fn send(&mut self, raw: &str) {
+            self.writer.write_all(raw.as_bytes()).await.unwrap();
+        }
+
+        /// The next response line; None once the server closed the connection
+        async fn reply(&mut self) -> Option<Value> {
+            let mut line = String::new();
+            let read = tokio::time::timeout(PATIENCE, self.reader.read_line(&mut line))
+                .await
+                .expect("no reply within 5s")
+                .unwrap();
+            (read > 0).then(|| serde_json::from_str(&line).unwrap())
+        }
+
+        async fn request(&mut self, request: Value) -> Value {
+            self.send(&format!("{}\n", request)).await;
+            self.reply().await.unwrap()
+        }
+
+        /// Status of a query once it's no longer starting, polled for up to PATIENCE
+        async fn settled_status(&mut self, id: u64, query_id: &Value) -> Value {
+            let deadline = tokio::time::Instant::now() + PATIENCE;
+            loop {
+                let status = self.request(authorized(serde_json::json!({ "id": id, "verb": "status", "queryId": query_id }))).await;
+                if status["result"]["status"] != "starting" || tokio::time::Instant::now() >= deadline {
+                    return status;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            }
+        }
+    }
+
+    fn authorized(mut request: Value) -> Value {
+        request["token"] = Value::from(TOKEN);
+        request
+    }
+
+    #[tokio::test]
+    async fn raw_client_runs_the_verbs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let endpoint = endpoint().await;
+        let path = socket_path(endpoint.dir.path());
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let dir_mode = std::fs::metadata(path.parent().unwrap()).unwrap().permissions().mode();
+        assert_eq!(dir_mode & 0o777, 0o700);
+        let mut client = endpoint.connect().await;
+        let workspace = endpoint.dir.path().to_string_lossy().to_string();
+
+        let started = client
+            .request(authorized(serde_json::json!({ "id": 1, "verb": "query", "prompt": "hi", "workingDir": workspace })))
+            .await;
+        assert_eq!(started["id"], 1);
+        assert_eq!(started["ok"], true);
+        let query_id = started["result"]["queryId"].as_str().unwrap().to_string();
+
+        let status = client.request(authorized(serde_json::json!({ "id": 2, "verb": "status", "queryId": query_id }))).await;
+        assert_eq!(status["result"]["status"], "starting");
+        assert_eq!(endpoint.pipeline.started.lock().unwrap().clone(), vec![(query_id.clone(), "hi".to_string())]);
+
+        // The query finishes in the background; its outcome stays reportable
+        endpoint.pipeline.finish.add_permits(1);
+        let status = client.settled_status(3, &Value::from(query_id.clone())).await;
+        assert_eq!(status["result"]["status"], "finished");
+
+        let all = client.request(authorized(serde_json::json!({ "id": 4, "verb": "status" }))).await;
+        assert_eq!(all["result"]["queries"][0]["queryId"], "busy");
+        let active = client.request(authorized(serde_json::json!({ "id": 5, "verb": "status", "queryId": "busy" }))).await;
+        assert_eq!(active["result"]["status"], "running");
+        let unknown = client.request(authorized(serde_json::json!({ "id": 6, "verb": "status", "queryId": "nope" }))).await;
+        assert_eq!(unknown["result"]["status"], "unknown");
+
+        let cancelled = client.request(authorized(serde_json::json!({ "id": 7, "verb": "cancel", "queryId": "busy" }))).await;
+        assert_eq!(cancelled["result"]["cancelled"], true);
+        let missed = client.request(authorized(serde_json::json!({ "id": 8, "verb": "cancel", "queryId": "nope" }))).await;
+        assert_eq!(missed["result"]["cancelled"], false);
+
+        let opened = client.request(authorized(serde_json::json!({ "id": 9, "verb": "open", "workingDir": workspace }))).await;
+        assert_eq!(opened["ok"], true);
+        assert_eq!(endpoint.pipeline.opened.lock().unwrap().clone(), vec![Some(workspace)]);
+        endpoint.stop.cancel();
+    }
+
+    #[tokio::test]
+    async fn bad_requests_are_answered_and_the_connection_kept() {
+        let endpoint = endpoint().await;
+        let mut client = endpoint.connect().await;
+        let workspace = endpoint.dir.path().to_string_lossy().to_string();
+
+        // Several requests in one write, with a blank line between them
+        client
+            .send(&format!(
+                "not json\n[1]\n\n{}\n{}\n",
+                serde_json::json!({ "id": "a", "verb": "status" }),
+                serde_json::json!({ "id": "b", "token": "wrong!", "verb": "status" }),
+            ))
+            .await;
+        let replies: Vec<Value> = vec![
+            client.reply().await.unwrap(),
+            client.reply().await.unwrap(),
+            client.reply().await.unwrap(),
+            client.reply().await.unwrap(),
+        ];
+        let kinds: Vec<(&Value, &str)> = replies.iter().map(|r| (&r["id"], r["error"]["kind"].as_str().unwrap())).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (&Value::Null, "malformed"),
+                (&Value::Null, "malformed"),
+                (&Value::from("a"), "unauthorized"),
+                (&Value::from("b"), "unauthorized"),
+            ]
+        );
+
+        let unknown_verb = client.request(authorized(serde_json::json!({ "id": 1, "verb": "delete" }))).await;
+        assert_eq!(unknown_verb["error"]["kind"], "malformed");
+        let empty = client
+            .request(authorized(serde_json::json!({ "id": 2, "verb": "query", "prompt": "  ", "workingDir": workspace })))
+            .await;
+        assert_eq!(empty["error"]["kind"], "malformed");
+        let missing = client
+            .request(authorized(serde_json::json!({ "id": 3, "verb": "query", "prompt": "hi", "workingDir": "/no/such/dir" })))
+            .await;
+        assert_eq!(missing["error"]["kind"], "failed");
+        assert!(endpoint.pipeline.started.lock().unwrap().is_empty());
+
+        // A query that fails reports the pipeline's error
+        let started = client
+            .request(authorized(serde_json::json!({ "id": 4, "verb": "query", "prompt": "fail", "workingDir": workspace })))
+            .await;
+        let query_id = started["result"]["queryId"].clone();
+        let status = client.settled_status(5, &query_id).await;
+        assert_eq!(status["result"]["status"], "failed");
+        assert!(status["result"]["error"].as_str().unwrap().contains("no node"));
+        endpoint.stop.cancel();
+    }
+
+    #[test]
+    fn socket_directory_is_private_before_binding() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket_dir = dir.path().join(SOCKET_DIR);
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        private_dir(&socket_dir).unwrap();
+        assert_eq!(mode(&socket_dir), 0o700);
+
+        // One left open by an earlier run is tightened
+        std::fs::set_permissions(&socket_dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        private_dir(&socket_dir).unwrap();
+        assert_eq!(mode(&socket_dir), 0o700);
+
+        // Anything else in its place is refused rather than followed
+        let elsewhere = dir.path().join("elsewhere");
+        std::fs::create_dir(&elsewhere).unwrap();
+        std::fs::remove_dir(&socket_dir).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, &socket_dir).unwrap();
+        assert!(private_dir(&socket_dir).unwrap_err().contains("is not a directory"));
+    }
+
+    #[tokio::test]
+    async fn oversized_request_closes_the_connection() {
+        let endpoint = endpoint().await;
+        let mut client = endpoint.connect().await;
+        client.send(&"x".repeat(MAX_MESSAGE_BYTES + 1)).await;
+        let reply = client.reply().await.unwrap();
+        assert_eq!(reply["error"]["kind"], "tooLarge");
+        assert!(client.reply().await.is_none());
+
+        // Exactly at the cap is still read as a request
+        let mut client = endpoint.connect().await;
+        client.send(&format!("{}\n", "x".repeat(MAX_MESSAGE_BYTES))).await;
+        assert_eq!(client.reply().await.unwrap()["error"]["kind"], "malformed");
+        endpoint.stop.cancel();
+    }
+
+    #[tokio::test]
+    async fn connections_past_the_cap_are_refused() {
+        let endpoint = endpoint().await;
+        let mut open = Vec::new();
+        for _ in 0..MAX_CONNECTIONS {
+            let mut client = endpoint.connect().await;
+            // A reply means the server took the connection before the next one arrives
+            let reply = client.request(authorized(serde_json::json!({ "verb": "status" }))).await;
+            assert_eq!(reply["ok"], true);
+            open.push(client);
+        }
+        let mut refused = endpoint.connect().await;
+        assert_eq!(refused.reply().await.unwrap()["error"]["kind"], "busy");
+        assert!(refused.reply().await.is_none());
+
+        // Closing one frees its slot
+        drop(open.pop());
+        let deadline = tokio::time::Instant::now() + PATIENCE;
+        let mut reply = Value::Null;
+        while tokio::time::Instant::now() < deadline {
+            let mut client = endpoint.connect().await;
+            client.send(&format!("{}\n", authorized(serde_json::json!({ "verb": "status" })))).await;
+            reply = client.reply().await.unwrap();
+            if reply["ok"] == true {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        assert_eq!(reply["ok"], true);
+        endpoint.stop.cancel();
+    }
+}
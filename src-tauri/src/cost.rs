@@ -0,0 +1,64 @@
+// mensa - Cost Module
+// Running cost of a query, priced from the token usage streamed by the agent
+
+use crate::models;
+use crate::stream::{MessageBody, Usage};
+use std::collections::HashMap;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Fraction of the ceiling at which the one-time warning fires when the caller doesn't choose
+pub const DEFAULT_WARNING_FRACTION: f64 = 0.8;
+
+/// Accumulated cost of one run. The SDK repeats an assistant message's usage on
+/// every content block it streams, so usage is kept per message id and replaced.
+#[derive(Debug, Default)]
+pub struct CostTracker {
+    by_message: HashMap<String, f64>,
+    /// Messages that came without an id
+    unidentified: f64,
+    /// Total from the result message, which supersedes the estimate
+    reported: Option<f64>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Cost in USD of one message's usage, at the model catalog's prices
+pub fn usage_cost(model: Option<&str>, usage: &Usage) -> f64 {
+    let (input, output, cache_write, cache_read) = models::prices(model);
+    let tokens = usage.input_tokens as f64 * input
+        + usage.cache_creation_input_tokens as f64 * cache_write
+        + usage.cache_read_input_tokens as f64 * cache_read
+        + usage.output_tokens as f64 * output;
+    tokens / 1_000_000.0
+}
+
+impl CostTracker {
+    /// Account for an assistant message's usage
+    pub fn record(&mut self, message: &MessageBody) {
+        let Some(usage) = &message.usage else {
+            return;
+        };
+        let cost = usage_cost(message.model.as_deref(), usage);
+        match &message.id {
+            Some(id) => {
+                self.by_message.insert(id.clone(), cost);
+            }
+            None => self.unidentified += cost,
+        }
+    }
+
+    /// Take the SDK's own total once the run reports it
+    pub fn set_reported(&mut self, total: f64) {
+        self.reported = Some(total);
+    }
+
+    pub fn total(&self) -> f64 {
+        self.reported
+            .unwrap_or_else(|| self.by_message.values().sum::<f64>() + self.unidentified)
+    }
+}
@@ -0,0 +1,283 @@
+// mensa - Diff Attributes Module
+// .gitattributes flags that change how a path's diff is shown, and notebook output stripping
+
+use git2::{AttrCheckFlags, AttrValue, DiffDelta, Repository};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Cached attributes are reread after this long even when no attributes file changed,
+/// since a nested .gitattributes isn't watched
+const ATTRIBUTES_TTL: Duration = Duration::from_secs(30);
+
+/// Diff-related attributes of one path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffAttributes {
+    /// linguist-generated
+    pub generated: bool,
+    /// linguist-vendored
+    pub vendored: bool,
+    /// -diff: git shows the file as binary, so only its stats are returned
+    pub diff_suppressed: bool,
+    /// diff=<driver>
+    pub driver: Option<String>,
+}
+
+/// Modification time and size of the files attributes are read from
+type AttributesStamp = Vec<Option<(SystemTime, u64)>>;
+
+struct RepoAttributes {
+    read_at: Instant,
+    stamp: AttributesStamp,
+    paths: HashMap<String, DiffAttributes>,
+}
+
+/// Attributes by repository working directory, then path
+static ATTRIBUTES: Mutex<Option<HashMap<PathBuf, RepoAttributes>>> = Mutex::new(None);
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The root .gitattributes, .git/info/attributes and the index (attributes are read from the
+/// file, then the index)
+fn stamp(repo: &Repository) -> AttributesStamp {
+    let mut files = vec![repo.path().join("info").join("attributes"), repo.path().join("index")];
+    if let Some(workdir) = repo.workdir() {
+        files.push(workdir.join(".gitattributes"));
+    }
+    files
+        .iter()
+        .map(|f| std::fs::metadata(f).ok().and_then(|m| Some((m.modified().ok()?, m.len()))))
+        .collect()
+}
+
+/// Whether a linguist-* attribute is set (`attr`, `attr=true` or `attr=1`)
+fn is_set(repo: &Repository, path: &Path, name: &str) -> bool {
+    match repo.get_attr(path, name, AttrCheckFlags::FILE_THEN_INDEX) {
+        Ok(value) => match AttrValue::from_string(value) {
+            AttrValue::True => true,
+            AttrValue::String(v) => v.eq_ignore_ascii_case("true") || v == "1",
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+fn read(repo: &Repository, path: &str) -> DiffAttributes {
+    let p = Path::new(path);
+    let (diff_suppressed, driver) = match repo.get_attr(p, "diff", AttrCheckFlags::FILE_THEN_INDEX) {
+        Ok(value) => match AttrValue::from_string(value) {
+            AttrValue::False => (true, None),
+            AttrValue::String(driver) => (false, Some(driver.to_string())),
+            _ => (false, None),
+        },
+        Err(_) => (false, None),
+    };
+    DiffAttributes {
+        generated: is_set(repo, p, "linguist-generated"),
+        vendored: is_set(repo, p, "linguist-vendored"),
+        diff_suppressed,
+        driver,
+    }
+}
+
+/// Diff attributes of `path` (repo-relative), cached per repository until an attributes
+/// file or the index changes
+pub fn lookup(repo: &Repository, path: &str) -> DiffAttributes {
+    let Some(key) = repo.workdir().map(Path::to_path_buf) else {
+        return read(repo, path);
+    };
+    let current = stamp(repo);
+    if let Ok(mut cache) = ATTRIBUTES.lock() {
+        let repos = cache.get_or_insert_with(HashMap::new);
+        let entry = repos.entry(key).or_insert_with(|| RepoAttributes {
+            read_at: Instant::now(),
+            stamp: current.clone(),
+            paths: HashMap::new(),
+        });
+        if entry.stamp != current || entry.read_at.elapsed() >= ATTRIBUTES_TTL {
+            *entry = RepoAttributes {
+                read_at: Instant::now(),
+                stamp: current,
+                paths: HashMap::new(),
+            };
+        }
+        return entry.paths.entry(path.to_string()).or_insert_with(|| read(repo, path)).clone();
+    }
+    read(repo, path)
+}
+
+pub fn is_notebook(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".ipynb")
+}
+
+/// A Jupyter notebook without cell outputs and execution counts, so its diff shows the code
+/// and markdown that changed. None when it isn't notebook JSON.
+pub fn strip_notebook(content: &[u8]) -> Option<String> {
+    let mut notebook: Value = serde_json::from_slice(content).ok()?;
+    for cell in notebook.get_mut("cells")?.as_array_mut()? {
+        let Some(cell) = cell.as_object_mut() else {
+            continue;
+        };
+        if let Some(outputs) = cell.get_mut("outputs") {
+            *outputs = Value::Array(Vec::new());
+        }
+        if let Some(count) = cell.get_mut("execution_count") {
+            *count = Value::Null;
+        }
+    }
+    // One-space indent, like nbformat writes
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    serde::Serialize::serialize(&notebook, &mut serializer).ok()?;
+    out.push(b'\n');
+    String::from_utf8(out).ok()
+}
+
+/// One side of a delta: its blob, or for an unhashed working tree file the file on disk
+fn side_content(repo: &Repository, file: &git2::DiffFile, workdir: Option<&Path>) -> Option<Vec<u8>> {
+    if !file.id().is_zero() {
+        if let Ok(blob) = repo.find_blob(file.id()) {
+            return Some(blob.content().to_vec());
+        }
+    }
+    let path = file.path()?;
+    std::fs::read(workdir?.join(path)).ok()
+}
+
+/// Both sides of a notebook delta with outputs stripped, to diff in place of the raw files;
+/// None when either side isn't notebook JSON
+pub fn notebook_sides(repo: &Repository, delta: &DiffDelta, workdir: Option<&Path>) -> Option<(String, String)> {
+    let strip_side = |file: &git2::DiffFile, present: bool| -> Option<String> {
+        if !present {
+            return Some(String::new());
+        }
+        strip_notebook(&side_content(repo, file, workdir)?)
+    };
+    let old = strip_side(&delta.old_file(), delta.status() != git2::Delta::Added)?;
+    let new = strip_side(&delta.new_file(), delta.status() != git2::Delta::Deleted)?;
+    Some((old, new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, commit_all, fixture, write};
+
+    /// A repository with the fixture attributes: the root .gitattributes, a nested one under
+    /// sub/ and .git/info/attributes
+    fn attributed_repo() -> (tempfile::TempDir, Repository) {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), ".gitattributes", fixture("gitattributes/root.gitattributes"));
+        write(dir.path(), "sub/.gitattributes", fixture("gitattributes/nested.gitattributes"));
+        write(repo.path(), "info/attributes", fixture("gitattributes/info.attributes"));
+        (dir, repo)
+    }
+
+    fn attributes(generated: bool, vendored: bool, diff_suppressed: bool, driver: Option<&str>) -> DiffAttributes {
+        DiffAttributes {
+            generated,
+            vendored,
+            diff_suppressed,
+            driver: driver.map(String::from),
+        }
+    }
+
+    #[test]
+    fn gitattributes_fixture_flags() {
+        let (_dir, repo) = attributed_repo();
+        let cases = [
+            ("src/main.rs", attributes(false, false, false, None)),
+            ("web/app.min.js", attributes(true, false, false, None)),
+            ("gen/parser.rs", attributes(true, false, false, None)),
+            ("docs/guide.md", attributes(false, false, false, None)),
+            ("vendor/lib/left-pad.js", attributes(false, true, false, None)),
+            ("legacy/old.c", attributes(false, true, false, None)),
+            // Only true and 1 count as set
+            ("third_party/zlib.c", attributes(false, false, false, None)),
+            ("Cargo.lock", attributes(false, false, true, None)),
+            // The binary macro unsets diff
+            ("assets/logo.bin", attributes(false, false, true, None)),
+            ("notebooks/analysis.ipynb", attributes(false, false, false, Some("jupyter"))),
+            // The nested file overrides the root one for its directory
+            ("sub/Cargo.lock", attributes(false, false, false, None)),
+            ("sub/app.min.js", attributes(false, false, false, None)),
+            // .git/info/attributes overrides both
+            ("App.swift", attributes(false, false, true, None)),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(read(&repo, path), expected, "{}", path);
+            assert_eq!(lookup(&repo, path), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn committed_attributes_apply_when_the_file_is_gone() {
+        let (dir, repo) = attributed_repo();
+        commit_all(&repo, "attributes");
+        std::fs::remove_file(dir.path().join(".gitattributes")).unwrap();
+        assert!(read(&repo, "Cargo.lock").diff_suppressed);
+    }
+
+    #[test]
+    fn editing_gitattributes_invalidates_the_cache() {
+        let (dir, repo) = attributed_repo();
+        assert!(lookup(&repo, "Cargo.lock").diff_suppressed);
+        assert!(lookup(&repo, "src/main.rs").driver.is_none());
+
+        write(dir.path(), ".gitattributes", "*.rs diff=rust\n");
+        assert!(!lookup(&repo, "Cargo.lock").diff_suppressed);
+        assert_eq!(lookup(&repo, "src/main.rs").driver.as_deref(), Some("rust"));
+    }
+
+    #[test]
+    fn notebook_outputs_are_stripped() {
+        let raw = fixture("gitattributes/analysis.ipynb");
+        let stripped = strip_notebook(raw.as_bytes()).unwrap();
+        assert_eq!(stripped, fixture("gitattributes/analysis.stripped.ipynb"));
+        // Stripping is idempotent
+        assert_eq!(strip_notebook(stripped.as_bytes()).unwrap(), stripped);
+
+        assert!(strip_notebook(b"{\"name\": \"not a notebook\"}").is_none());
+        assert!(strip_notebook(b"{\"cells\": ").is_none());
+        assert!(is_notebook("notebooks/Analysis.IPYNB") && !is_notebook("notebook.py"));
+    }
+
+    #[test]
+    fn notebook_sides_diff_only_the_sources() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "analysis.ipynb", fixture("gitattributes/analysis.ipynb"));
+        commit_all(&repo, "notebook");
+
+        // Rerunning changes only outputs and counts; editing a cell changes its source
+        let rerun = fixture("gitattributes/analysis.ipynb")
+            .replace("loaded 3 rows", "loaded 4 rows")
+            .replace("\"execution_count\": 7", "\"execution_count\": 8");
+        write(dir.path(), "analysis.ipynb", rerun.replace("df.head()", "df.describe()"));
+        write(dir.path(), "added.ipynb", fixture("gitattributes/analysis.ipynb"));
+        test_support::stage(&repo, "added.ipynb");
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        let diff = repo.diff_tree_to_workdir_with_index(Some(&head), None).unwrap();
+
+        let sides: HashMap<String, (String, String)> = diff
+            .deltas()
+            .map(|delta| {
+                let path = delta.new_file().path().unwrap().to_string_lossy().to_string();
+                (path, notebook_sides(&repo, &delta, repo.workdir()).unwrap())
+            })
+            .collect();
+        let expected = fixture("gitattributes/analysis.stripped.ipynb");
+        let (old, new) = &sides["analysis.ipynb"];
+        assert_eq!(old, &expected);
+        assert_eq!(new, &expected.replace("df.head()", "df.describe()"));
+        assert_eq!(sides["added.ipynb"], (String::new(), expected));
+    }
+}
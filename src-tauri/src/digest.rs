@@ -0,0 +1,252 @@
+// mensa - Digest Module
+// "What changed since I last looked": sessions, commits, worktree and PR activity in one call
+
+use crate::git::{self, GhPRListItem, GitCommit, GitState, StatusSummary};
+use crate::{history, project_dir_for_workspace, workspace, AppState};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::future::Future;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Look-back used when the workspace has never had a digest
+const DEFAULT_LOOKBACK_SECS: i64 = 24 * 60 * 60;
+
+/// Most commits listed in one digest
+const MAX_DIGEST_COMMITS: usize = 100;
+
+// Per-section budgets; sections run concurrently, so the whole call stays near the largest
+const SESSIONS_TIMEOUT: Duration = Duration::from_secs(2);
+const COMMITS_TIMEOUT: Duration = Duration::from_secs(3);
+const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+const PULL_REQUESTS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One part of the digest; a failed or timed-out section carries its error instead of data
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestSection<T> {
+    pub data: Option<T>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestSession {
+    pub session_id: String,
+    pub first_prompt: String,
+    pub message_count: u32,
+    /// Transcript modification time (unix seconds)
+    pub modified_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDigest {
+    pub since: i64,
+    pub generated_at: i64,
+    pub sessions: DigestSection<Vec<DigestSession>>,
+    pub commits: DigestSection<Vec<GitCommit>>,
+    pub status: DigestSection<StatusSummary>,
+    pub pull_requests: DigestSection<Vec<GhPRListItem>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Run one section under its time budget
+async fn section<T, F>(name: &str, timeout: Duration, work: F) -> DigestSection<T>
+where
+    F: Future<Output = Result<T, String>>,
+{
+    match tokio::time::timeout(timeout, work).await {
+        Ok(Ok(data)) => DigestSection { data: Some(data), error: None },
+        Ok(Err(e)) => DigestSection { data: None, error: Some(e) },
+        Err(_) => DigestSection {
+            data: None,
+            error: Some(format!("Timed out collecting {} after {}s", name, timeout.as_secs())),
+        },
+    }
+}
+
+/// Unix seconds of an RFC 3339 UTC timestamp as gh prints them ("2024-05-01T12:34:56Z")
+pub fn parse_utc_timestamp(value: &str) -> Option<i64> {
+    let (date, time) = value.trim_end_matches('Z').split_once('T')?;
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date_parts.next()??, date_parts.next()??, date_parts.next()??);
+    let time = time.split(['.', '+']).next()?;
+    let mut time_parts = time.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hour, minute, second) = (time_parts.next()??, time_parts.next()??, time_parts.next()??);
+
+    // Days since the epoch for a proleptic Gregorian date
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+/// Unix milliseconds of an RFC 3339 UTC timestamp with a fraction ("2024-05-01T12:34:56.789Z")
+pub fn parse_utc_timestamp_ms(value: &str) -> Option<i64> {
+    let secs = parse_utc_timestamp(value)?;
+    let fraction = value
+        .split_once('.')
+        .map(|(_, rest)| rest.chars().take_while(char::is_ascii_digit).take(3).collect::<String>())
+        .unwrap_or_default();
+    let millis = format!("{:0<3}", fraction).parse::<i64>().unwrap_or(0);
+    Some(secs * 1000 + millis)
+}
+
+/// RFC 3339 UTC timestamp with milliseconds ("2024-05-01T12:34:56.789Z") of Unix milliseconds,
+/// the form Claude Code writes in its sessions index
+pub fn format_utc_timestamp(millis: i64) -> String {
+    let (secs, millis) = (millis.div_euclid(1000), millis.rem_euclid(1000));
+    let (days, secs) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Proleptic Gregorian date of a day count (inverse of parse_utc_timestamp)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60,
+        millis
+    )
+}
+
+async fn sessions_since(working_dir: String, since: i64) -> Result<Vec<DigestSession>, String> {
+    let project_dir = project_dir_for_workspace(&working_dir)?;
+    let mut sessions = Vec::new();
+    for entry in crate::session_entries(working_dir.clone(), None, &Default::default()).await.map_err(|e| e.to_string())? {
+        let modified_at = tokio::fs::metadata(project_dir.join(format!("{}.jsonl", entry.session_id)))
+            .await
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+        match modified_at {
+            Some(modified_at) if modified_at > since => sessions.push(DigestSession {
+                session_id: entry.session_id,
+                first_prompt: entry.first_prompt,
+                message_count: entry.message_count,
+                modified_at,
+            }),
+            _ => {}
+        }
+    }
+    Ok(sessions)
+}
+
+/// Commits on the current branch newer than `since`, newest first
+fn commits_since(working_dir: &str, since: i64) -> Result<Vec<GitCommit>, String> {
+    let repo = git::open_repo(working_dir)?;
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk
+        .set_sorting(git2::Sort::TIME)
+        .map_err(|e| format!("Failed to sort revwalk: {}", e))?;
+    revwalk
+        .push_head()
+        .map_err(|e| format!("Failed to push HEAD: {}", e))?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk {
+        // A shallow clone's missing parents end the walk like reaching `since` does
+        let Ok(oid) = oid else { break };
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        if commit.time().seconds() <= since || commits.len() >= MAX_DIGEST_COMMITS {
+            break;
+        }
+        commits.push(git::commit_summary(&repo, &commit));
+    }
+    Ok(commits)
+}
+
+async fn pull_requests_since(git_state: &GitState, working_dir: &str, since: i64) -> Result<Vec<GhPRListItem>, String> {
+    let prs = git::load_pr_list(git_state, working_dir, "all", git::PR_LIST_LIMIT, false).await?;
+    Ok(prs
+        .into_iter()
+        .filter(|pr| parse_utc_timestamp(&pr.updated_at).is_some_and(|updated| updated > since))
+        .collect())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Summarize activity in a workspace since `since` (default: the previous digest, or the last day).
+/// Sections are gathered concurrently under their own timeouts and fail independently. When all
+/// local sections succeed the digest time is remembered as the next default; PRs (gh may be missing
+/// or offline) don't hold that back.
+#[tauri::command]
+pub async fn workspace_digest(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    git_state: State<'_, GitState>,
+    working_dir: String,
+    since: Option<i64>,
+) -> Result<WorkspaceDigest, String> {
+    let root = workspace::canonical_dir(&working_dir)?;
+    let generated_at = history::now_secs();
+    let since = match since {
+        Some(since) => since,
+        None => workspace::load_state(&app, &state, &root)
+            .await?
+            .last_digest_at
+            .unwrap_or(generated_at - DEFAULT_LOOKBACK_SECS),
+    };
+
+    let commits_dir = working_dir.clone();
+    let status_dir = working_dir.clone();
+    let (sessions, commits, status, pull_requests) = tokio::join!(
+        section("sessions", SESSIONS_TIMEOUT, sessions_since(working_dir.clone(), since)),
+        section("commits", COMMITS_TIMEOUT, async move {
+            tokio::task::spawn_blocking(move || commits_since(&commits_dir, since))
+                .await
+                .map_err(|e| format!("Commit scan failed: {}", e))?
+        }),
+        section("status", STATUS_TIMEOUT, async move {
+            tokio::task::spawn_blocking(move || git::status_summary(&status_dir))
+                .await
+                .map_err(|e| format!("Status scan failed: {}", e))?
+        }),
+        section("pull requests", PULL_REQUESTS_TIMEOUT, pull_requests_since(&git_state, &working_dir, since)),
+    );
+
+    if sessions.error.is_none() && commits.error.is_none() && status.error.is_none() {
+        let mut patch = Map::new();
+        patch.insert("lastDigestAt".to_string(), Value::from(generated_at));
+        workspace::patch_state(&app, &state, root, patch).await?;
+    }
+
+    Ok(WorkspaceDigest {
+        since,
+        generated_at,
+        sessions,
+        commits,
+        status,
+        pull_requests,
+    })
+}
@@ -0,0 +1,304 @@
+// mensa - Disk Space Module
+// Free space on the volumes a query writes to (the workspace and ~/.claude), checked before and during runs
+
+use crate::cancel::CancellationToken;
+use crate::{paths, replay, settings, AppState};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// How often a running query's volumes are checked
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSpace {
+    /// The directory asked about (the volume is the one containing it)
+    pub path: String,
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskSpaceReport {
+    pub workspace: VolumeSpace,
+    /// Where Claude Code writes session transcripts
+    pub claude_dir: VolumeSpace,
+    pub same_volume: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiskLevel {
+    Ok,
+    /// Below the warning threshold: `low-disk-warning`
+    Low,
+    /// Below the hard floor: queries are refused
+    Critical,
+}
+
+/// The tighter of the two volumes, against the configured thresholds
+#[derive(Debug, Clone)]
+pub struct DiskCheck {
+    pub volume: VolumeSpace,
+    pub level: DiskLevel,
+    pub warning_bytes: u64,
+    pub floor_bytes: u64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Where free space falls against the thresholds; the floor wins if it's set above the warning
+pub fn classify(free_bytes: u64, warning_bytes: u64, floor_bytes: u64) -> DiskLevel {
+    if free_bytes < floor_bytes {
+        DiskLevel::Critical
+    } else if free_bytes < warning_bytes {
+        DiskLevel::Low
+    } else {
+        DiskLevel::Ok
+    }
+}
+
+/// (free, total) bytes of the volume containing `path`; free is what an unprivileged user may use
+#[cfg(unix)]
+#[allow(clippy::useless_conversion)] // the field types are u32 on some platforms
+fn volume_bytes(path: &Path) -> Result<(u64, u64), String> {
+    let stats = nix::sys::statvfs::statvfs(path)
+        .map_err(|e| format!("Failed to read free space of {}: {}", path.display(), e))?;
+    let fragment = u64::from(stats.fragment_size());
+    Ok((
+        u64::from(stats.blocks_available()).saturating_mul(fragment),
+        u64::from(stats.blocks()).saturating_mul(fragment),
+    ))
+}
+
+/// (free, total) bytes of the volume containing `path`; free is what the current user may use
+#[cfg(windows)]
+fn volume_bytes(path: &Path) -> Result<(u64, u64), String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let (mut free, mut total, mut total_free) = (0u64, 0u64, 0u64);
+    // SAFETY: `wide` is NUL-terminated and the out pointers are valid for the call
+    let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut free, &mut total, &mut total_free) };
+    if ok == 0 {
+        return Err(format!(
+            "Failed to read free space of {}: {}",
+            path.display(),
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok((free, total))
+}
+
+/// The path itself or its nearest existing ancestor (a workspace may not be created yet)
+fn existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|p| p.exists())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn volume_space(path: &Path) -> Result<VolumeSpace, String> {
+    let (free_bytes, total_bytes) = volume_bytes(&existing_ancestor(path))?;
+    Ok(VolumeSpace {
+        path: path.to_string_lossy().to_string(),
+        free_bytes,
+        total_bytes,
+    })
+}
+
+#[cfg(unix)]
+fn same_volume(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(existing_ancestor(a)), std::fs::metadata(existing_ancestor(b))) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+/// Same drive or share (the path prefix, e.g. "C:")
+#[cfg(windows)]
+fn same_volume(a: &Path, b: &Path) -> bool {
+    let prefix = |p: &Path| match existing_ancestor(p).components().next() {
+        Some(std::path::Component::Prefix(prefix)) => Some(prefix.as_os_str().to_ascii_lowercase()),
+        _ => None,
+    };
+    prefix(a).is_some_and(|a| Some(a) == prefix(b))
+}
+
+fn report_blocking(working_dir: &Path) -> Result<DiskSpaceReport, String> {
+    let claude = paths::ClaudeHome::current()?;
+    Ok(DiskSpaceReport {
+        workspace: volume_space(working_dir)?,
+        claude_dir: volume_space(claude.root())?,
+        same_volume: same_volume(working_dir, claude.root()),
+    })
+}
+
+pub async fn report(working_dir: &str) -> Result<DiskSpaceReport, String> {
+    let working_dir = PathBuf::from(working_dir);
+    tokio::task::spawn_blocking(move || report_blocking(&working_dir))
+        .await
+        .map_err(|e| format!("Failed to check disk space: {}", e))?
+}
+
+/// Check both volumes of a query against the configured thresholds; reports the one with less free space
+pub async fn check(app: &tauri::AppHandle, working_dir: &str) -> Result<DiskCheck, String> {
+    let settings = settings::load(app, &app.state::<AppState>().settings).await?;
+    let report = report(working_dir).await?;
+    Ok(check_report(report, settings.low_disk_warning_mb, settings.min_free_disk_mb))
+}
+
+/// The tighter volume of a report against thresholds given in MB
+fn check_report(report: DiskSpaceReport, warning_mb: u64, floor_mb: u64) -> DiskCheck {
+    let warning_bytes = warning_mb.saturating_mul(BYTES_PER_MB);
+    let floor_bytes = floor_mb.saturating_mul(BYTES_PER_MB);
+    let volume = match report.claude_dir.free_bytes < report.workspace.free_bytes {
+        true => report.claude_dir,
+        false => report.workspace,
+    };
+    DiskCheck {
+        level: classify(volume.free_bytes, warning_bytes, floor_bytes),
+        volume,
+        warning_bytes,
+        floor_bytes,
+    }
+}
+
+/// Whether a sample at `level` warns: once per drop below the threshold, re-armed by a
+/// sample back above it
+fn should_warn(level: DiskLevel, warned: &mut bool) -> bool {
+    match level {
+        DiskLevel::Ok => {
+            *warned = false;
+            false
+        }
+        DiskLevel::Low | DiskLevel::Critical => !std::mem::replace(warned, true),
+    }
+}
+
+/// Emit `low-disk-warning` for a query; `running` is false for the check before it starts
+pub async fn emit_warning(app: &tauri::AppHandle, query_id: &str, check: &DiskCheck, running: bool) {
+    replay::emit(app, query_id, "low-disk-warning", serde_json::json!({
+        "query_id": query_id,
+        "path": check.volume.path,
+        "free_bytes": check.volume.free_bytes,
+        "total_bytes": check.volume.total_bytes,
+        "threshold_bytes": check.warning_bytes,
+        "critical": check.level == DiskLevel::Critical,
+        "running": running,
+    }))
+    .await;
+}
+
+/// Sample free space while a query runs, warning each time it drops below the threshold
+/// (again only after it has recovered). Runs until `token` is cancelled.
+pub async fn watch(app: tauri::AppHandle, query_id: String, working_dir: String, token: CancellationToken) {
+    let mut warned = false;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(SAMPLE_INTERVAL) => {}
+            _ = token.cancelled() => break,
+        }
+        let Ok(check) = check(&app, &working_dir).await else {
+            continue;
+        };
+        if should_warn(check.level, &mut warned) {
+            emit_warning(&app, &query_id, &check, true).await;
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Free and total bytes of the volumes holding the workspace and ~/.claude
+#[tauri::command]
+pub async fn check_disk_space(working_dir: String) -> Result<DiskSpaceReport, String> {
+    report(&working_dir).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = BYTES_PER_MB;
+
+    fn report(workspace_free: u64, claude_free: u64) -> DiskSpaceReport {
+        let volume = |path: &str, free_bytes: u64| VolumeSpace {
+            path: path.to_string(),
+            free_bytes,
+            total_bytes: 100 * 1024 * MB,
+        };
+        DiskSpaceReport {
+            workspace: volume("/work/app", workspace_free),
+            claude_dir: volume("/home/me/.claude", claude_free),
+            same_volume: false,
+        }
+    }
+
+    #[test]
+    fn free_space_is_classified_against_both_thresholds() {
+        let cases = [
+            (2048 * MB, 1024 * MB, 256 * MB, DiskLevel::Ok),
+            (1024 * MB, 1024 * MB, 256 * MB, DiskLevel::Ok),
+            (1024 * MB - 1, 1024 * MB, 256 * MB, DiskLevel::Low),
+            (256 * MB, 1024 * MB, 256 * MB, DiskLevel::Low),
+            (256 * MB - 1, 1024 * MB, 256 * MB, DiskLevel::Critical),
+            (0, 1024 * MB, 256 * MB, DiskLevel::Critical),
+            // A floor set above the warning wins
+            (600 * MB, 500 * MB, 700 * MB, DiskLevel::Critical),
+            // Zero thresholds turn the checks off
+            (0, 0, 0, DiskLevel::Ok),
+            (10 * MB, 1024 * MB, 0, DiskLevel::Low),
+        ];
+        for (free, warning, floor, expected) in cases {
+            assert_eq!(classify(free, warning, floor), expected, "{} free, warn {}, floor {}", free, warning, floor);
+        }
+    }
+
+    #[test]
+    fn check_reports_the_tighter_volume() {
+        let check = check_report(report(50 * 1024 * MB, 800 * MB), 1024, 256);
+        assert_eq!((check.volume.path.as_str(), check.level), ("/home/me/.claude", DiskLevel::Low));
+        assert_eq!((check.warning_bytes, check.floor_bytes), (1024 * MB, 256 * MB));
+
+        let check = check_report(report(100 * MB, 800 * MB), 1024, 256);
+        assert_eq!((check.volume.path.as_str(), check.level), ("/work/app", DiskLevel::Critical));
+
+        // On a tie the workspace is named
+        assert_eq!(check_report(report(MB, MB), 0, 0).volume.path, "/work/app");
+
+        // Thresholds too large for bytes saturate instead of wrapping to a small number
+        let check = check_report(report(u64::MAX - 1, u64::MAX - 1), u64::MAX, 0);
+        assert_eq!((check.warning_bytes, check.level), (u64::MAX, DiskLevel::Low));
+    }
+
+    #[test]
+    fn a_drop_warns_once_until_space_recovers() {
+        use DiskLevel::*;
+        let samples = [Ok, Low, Low, Critical, Low, Ok, Ok, Critical, Ok, Low];
+        let mut warned = false;
+        let warnings: Vec<usize> = samples
+            .iter()
+            .enumerate()
+            .filter(|(_, level)| should_warn(**level, &mut warned))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(warnings, [1, 7, 9]);
+        assert!(warned);
+    }
+}
@@ -0,0 +1,123 @@
+// mensa - Editor Module
+// Opens files at a line in the preferred external editor, or reveals them in the file manager
+
+use crate::{find_session_path, settings, AppState};
+use serde::Serialize;
+use std::path::Path;
+use std::process::Stdio;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Editors that take `-g file:line`
+const GOTO_FLAG_EDITORS: &[&str] = &["code", "code-insiders", "codium", "vscodium", "cursor", "windsurf"];
+
+/// Editors that take `file:line`
+const LINE_SUFFIX_EDITORS: &[&str] = &["zed", "zeditor", "subl", "sublime_text", "lapce"];
+
+/// JetBrains launchers, which take `--line N file`
+const LINE_FLAG_EDITORS: &[&str] = &[
+    "idea", "webstorm", "pycharm", "goland", "rustrover", "clion", "phpstorm", "rubymine", "fleet",
+];
+
+/// Where a file was opened
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenedLocation {
+    pub path: String,
+    pub line: usize,
+    /// The editor launched; None when the file was revealed in the file manager instead
+    pub editor: Option<String>,
+    /// Whether the editor was told the line (unknown editors just get the file)
+    pub at_line: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Arguments that open `path` at `line` for the editor program, and whether the line is passed
+fn editor_args(program: &str, path: &Path, line: usize) -> (Vec<String>, bool) {
+    let name = Path::new(program)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let path = path.to_string_lossy().to_string();
+    if GOTO_FLAG_EDITORS.contains(&name.as_str()) {
+        (vec!["-g".to_string(), format!("{}:{}", path, line)], true)
+    } else if LINE_SUFFIX_EDITORS.contains(&name.as_str()) {
+        (vec![format!("{}:{}", path, line)], true)
+    } else if LINE_FLAG_EDITORS.contains(&name.as_str()) {
+        (vec!["--line".to_string(), line.to_string(), path], true)
+    } else {
+        (vec![path], false)
+    }
+}
+
+/// Launch the configured editor (a program plus optional arguments, e.g. "code --new-window")
+/// on `path` at `line` without waiting for it
+fn launch_editor(editor: &str, path: &Path, line: usize, proxy: Vec<(String, String)>) -> Result<bool, String> {
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or_else(|| "No editor configured".to_string())?;
+    let (args, at_line) = editor_args(program, path, line);
+    let mut child = std::process::Command::new(program)
+        .args(parts)
+        .args(args)
+        .envs(proxy)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    // Reap it whenever it exits (CLI launchers usually hand off and return at once)
+    std::thread::spawn(move || child.wait());
+    Ok(at_line)
+}
+
+/// Open `path` at `line` in the preferred editor, or reveal it in the file manager when none is set
+async fn open_at(app: &tauri::AppHandle, state: &AppState, path: &Path, line: usize) -> Result<OpenedLocation, String> {
+    let editor = settings::load(app, &state.settings).await?.preferred_editor.clone();
+    let (editor, at_line) = match editor {
+        Some(editor) => {
+            let at_line = launch_editor(&editor, path, line, state.proxy.vars())?;
+            (Some(editor), at_line)
+        }
+        None => {
+            tauri_plugin_opener::reveal_item_in_dir(path).map_err(|e| format!("Failed to reveal file: {}", e))?;
+            (None, false)
+        }
+    };
+    Ok(OpenedLocation {
+        path: path.to_string_lossy().to_string(),
+        line,
+        editor,
+        at_line,
+    })
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Open a session's jsonl transcript at `line` (1-based, e.g. a message's `sourceSpan` start)
+/// in the preferred editor, or reveal it in the file manager
+#[tauri::command]
+pub async fn open_session_at(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    workspace_path: String,
+    session_id: String,
+    line: usize,
+) -> Result<OpenedLocation, String> {
+    if session_id.is_empty() || session_id.contains(['/', '\\']) || session_id.contains("..") {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    if line == 0 {
+        return Err("Line numbers start at 1".to_string());
+    }
+    let path = find_session_path(&workspace_path, &session_id)?
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    open_at(&app, &state, &path, line).await
+}
@@ -0,0 +1,395 @@
+// mensa - Session Export Module
+// Renders parsed session transcripts as Markdown or a self-contained HTML page
+
+use crate::transcript::{SessionBlock, SessionMessage, SessionToolExecution};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+use std::sync::OnceLock;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Total budget for images embedded as data URIs in an HTML export
+const MAX_EMBEDDED_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+const HTML_STYLE: &str = r#"
+body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", sans-serif; background: #f6f7f9; color: #1f2328; margin: 0; }
+main { max-width: 860px; margin: 0 auto; padding: 32px 16px; }
+h1 { font-size: 20px; margin-bottom: 24px; }
+.message { border-radius: 12px; padding: 12px 16px; margin: 12px 0; box-shadow: 0 1px 2px rgba(0,0,0,0.06); }
+.user { background: #dbeafe; margin-left: 15%; }
+.assistant { background: #ffffff; margin-right: 15%; }
+.meta { font-size: 11px; color: #6e7781; margin-bottom: 6px; text-transform: uppercase; letter-spacing: 0.04em; }
+p { margin: 6px 0; line-height: 1.5; }
+pre { padding: 10px; border-radius: 8px; overflow-x: auto; font-size: 12px; }
+details { border: 1px solid #d0d7de; border-radius: 8px; margin: 8px 0; padding: 6px 10px; background: #f6f8fa; }
+summary { cursor: pointer; font-family: ui-monospace, Menlo, monospace; font-size: 12px; }
+.tool-error summary { color: #cf222e; }
+.label { font-size: 11px; color: #6e7781; margin-top: 6px; }
+img { max-width: 100%; border-radius: 8px; margin: 6px 0; }
+.omitted { font-style: italic; color: #6e7781; }
+"#;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionExport {
+    pub format: String, // "markdown" | "html"
+    pub content: String,
+    pub dropped_images: u32,
+    pub warnings: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn highlight_theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        themes
+            .themes
+            .remove("InspiredGitHub")
+            .unwrap_or_default()
+    })
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Syntax-highlight a code block as inline-styled HTML (no JS or external CSS needed)
+pub fn highlight_code(code: &str, lang: &str) -> String {
+    let syntaxes = syntax_set();
+    let syntax = syntaxes
+        .find_syntax_by_token(lang)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+
+    highlighted_html_for_string(code, syntaxes, syntax, highlight_theme())
+        .unwrap_or_else(|_| format!("<pre><code>{}</code></pre>", escape_html(code)))
+}
+
+/// Split text into prose and fenced code segments: (language, content).
+/// Prose segments have no language.
+fn split_fences(text: &str) -> Vec<(Option<String>, String)> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut fence_lang: Option<String> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            match fence_lang.take() {
+                Some(lang) => {
+                    segments.push((Some(lang), std::mem::take(&mut current)));
+                }
+                None => {
+                    if !current.trim().is_empty() {
+                        segments.push((None, std::mem::take(&mut current)));
+                    }
+                    current.clear();
+                    fence_lang = Some(trimmed.trim_start_matches('`').trim().to_string());
+                }
+            }
+            continue;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+
+    // An unterminated fence is still rendered as code
+    match fence_lang {
+        Some(lang) => segments.push((Some(lang), current)),
+        None if !current.trim().is_empty() => segments.push((None, current)),
+        None => {}
+    }
+
+    segments
+}
+
+fn render_text_html(text: &str) -> String {
+    let mut html = String::new();
+    for (lang, segment) in split_fences(text) {
+        match lang {
+            Some(lang) => html.push_str(&highlight_code(&segment, &lang)),
+            None => {
+                for paragraph in segment.split("\n\n").filter(|p| !p.trim().is_empty()) {
+                    html.push_str("<p>");
+                    html.push_str(&escape_html(paragraph.trim()).replace('\n', "<br>"));
+                    html.push_str("</p>\n");
+                }
+            }
+        }
+    }
+    html
+}
+
+fn render_tool_html(tool: &SessionToolExecution) -> String {
+    let class = if tool.status == "error" { " class=\"tool-error\"" } else { "" };
+    let mut html = format!(
+        "<details{}><summary>{} &middot; {}</summary>\n",
+        class,
+        escape_html(&tool.tool),
+        escape_html(&tool.status)
+    );
+    if let Some(ref input) = tool.input {
+        html.push_str("<div class=\"label\">Input</div>\n");
+        html.push_str(&highlight_code(input, "json"));
+    }
+    if let Some(ref output) = tool.output {
+        html.push_str("<div class=\"label\">Output</div>\n");
+        html.push_str(&format!("<pre>{}</pre>\n", escape_html(output)));
+    }
+    html.push_str("</details>\n");
+    html
+}
+
+// ============================================================================
+// Renderers
+// ============================================================================
+
+/// A code fence longer than any run of backticks in `content`, so the content can't close it
+fn fence_for(content: &str) -> String {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn push_fenced(md: &mut String, lang: &str, content: &str) {
+    let fence = fence_for(content);
+    md.push_str(&format!("{}{}\n{}\n{}\n\n", fence, lang, content, fence));
+}
+
+/// An image block as a data URI, re-encoded from its decoded bytes so nothing but base64 ends
+/// up in the attribute; None when the data isn't base64
+fn image_data_uri(media_type: &str, data: &str) -> Option<String> {
+    let compact: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let bytes = BASE64.decode(compact).ok()?;
+    let media_type = match media_type.strip_prefix("image/") {
+        Some(subtype) if !subtype.is_empty() && subtype.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) => {
+            media_type
+        }
+        _ => "application/octet-stream",
+    };
+    Some(format!("data:{};base64,{}", media_type, BASE64.encode(bytes)))
+}
+
+/// Render a transcript as Markdown
+pub fn render_markdown(messages: &[SessionMessage], title: &str) -> String {
+    let mut md = format!("# {}\n\n", title);
+
+    for message in messages {
+        let speaker = if message.role == "user" { "User" } else { "Assistant" };
+        md.push_str(&format!("## {}\n\n", speaker));
+        if !message.timestamp.is_empty() {
+            md.push_str(&format!("_{}_\n\n", message.timestamp));
+        }
+        if !message.content.trim().is_empty() {
+            md.push_str(message.content.trim());
+            md.push_str("\n\n");
+        }
+        for tool in message.tools.iter().flatten() {
+            md.push_str(&format!(
+                "<details><summary>{} ({})</summary>\n\n",
+                escape_html(&tool.tool),
+                escape_html(&tool.status)
+            ));
+            if let Some(ref input) = tool.input {
+                push_fenced(&mut md, "json", input);
+            }
+            if let Some(ref output) = tool.output {
+                push_fenced(&mut md, "", output);
+            }
+            md.push_str("</details>\n\n");
+        }
+    }
+
+    md
+}
+
+/// Render a transcript as a single self-contained HTML document.
+/// Images are embedded as data URIs until the size budget is spent; returns how many were
+/// dropped for the budget and how many for data that isn't base64.
+pub fn render_html(messages: &[SessionMessage], title: &str) -> (String, u32, u32) {
+    let mut body = String::new();
+    let mut image_bytes = 0usize;
+    let mut dropped_images = 0u32;
+    let mut invalid_images = 0u32;
+
+    for message in messages {
+        let role_class = if message.role == "user" { "user" } else { "assistant" };
+        body.push_str(&format!("<section class=\"message {}\">\n", role_class));
+        body.push_str(&format!(
+            "<div class=\"meta\">{} {}</div>\n",
+            role_class,
+            escape_html(&message.timestamp)
+        ));
+
+        match message.blocks {
+            Some(ref blocks) => {
+                let mut ordered: Vec<&SessionBlock> = blocks.iter().collect();
+                ordered.sort_by_key(|b| match b {
+                    SessionBlock::Text { order, .. }
+                    | SessionBlock::Tool { order, .. }
+                    | SessionBlock::Image { order, .. } => *order,
+                });
+
+                for block in ordered {
+                    match block {
+                        SessionBlock::Text { content, .. } => body.push_str(&render_text_html(content)),
+                        SessionBlock::Tool { tool_id, .. } => {
+                            let tool = message.tools.iter().flatten().find(|t| &t.id == tool_id);
+                            if let Some(tool) = tool {
+                                body.push_str(&render_tool_html(tool));
+                            }
+                        }
+                        SessionBlock::Image { media_type, data, .. } => {
+                            if image_bytes + data.len() > MAX_EMBEDDED_IMAGE_BYTES {
+                                dropped_images += 1;
+                                body.push_str("<p class=\"omitted\">[image omitted: export size limit reached]</p>\n");
+                            } else if let Some(uri) = image_data_uri(media_type, data) {
+                                image_bytes += data.len();
+                                body.push_str(&format!("<img alt=\"attachment\" src=\"{}\">\n", escape_html(&uri)));
+                            } else {
+                                invalid_images += 1;
+                                body.push_str("<p class=\"omitted\">[image omitted: not valid base64]</p>\n");
+                            }
+                        }
+                    }
+                }
+            }
+            None => {
+                body.push_str(&render_text_html(&message.content));
+                for tool in message.tools.iter().flatten() {
+                    body.push_str(&render_tool_html(tool));
+                }
+            }
+        }
+
+        body.push_str("</section>\n");
+    }
+
+    let html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{title}</title>\n<style>{style}</style>\n</head>\n<body>\n<main>\n<h1>{title}</h1>\n{body}</main>\n</body>\n</html>\n",
+        title = escape_html(title),
+        style = HTML_STYLE,
+        body = body
+    );
+
+    (html, dropped_images, invalid_images)
+}
+
+/// Render a transcript in the requested format
+pub fn export_messages(messages: &[SessionMessage], title: &str, format: &str) -> Result<SessionExport, String> {
+    match format {
+        "markdown" | "md" => Ok(SessionExport {
+            format: "markdown".to_string(),
+            content: render_markdown(messages, title),
+            dropped_images: 0,
+            warnings: Vec::new(),
+        }),
+        "html" => {
+            let (content, dropped_images, invalid_images) = render_html(messages, title);
+            let mut warnings = Vec::new();
+            if dropped_images > 0 {
+                warnings.push(format!(
+                    "{} image(s) were omitted to keep the export under {} MB",
+                    dropped_images,
+                    MAX_EMBEDDED_IMAGE_BYTES / (1024 * 1024)
+                ));
+            }
+            if invalid_images > 0 {
+                warnings.push(format!("{} image(s) were omitted because their data is not valid base64", invalid_images));
+            }
+            Ok(SessionExport {
+                format: "html".to_string(),
+                content,
+                dropped_images,
+                warnings,
+            })
+        }
+        other => Err(format!("Unsupported export format: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, fixture_path};
+    use crate::transcript::parse_session_messages;
+
+    /// Compare against a golden file; MENSA_UPDATE_GOLDEN=1 rewrites it instead
+    fn assert_golden(relative: &str, actual: &str) {
+        let path = fixture_path(relative);
+        if std::env::var_os("MENSA_UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap();
+        assert!(expected == actual, "{} differs from the export; rerun with MENSA_UPDATE_GOLDEN=1 to update it", relative);
+    }
+
+    fn messages() -> Vec<SessionMessage> {
+        parse_session_messages(&fixture("export/session.jsonl")).unwrap()
+    }
+
+    #[test]
+    fn markdown_matches_golden_file() {
+        let export = export_messages(&messages(), "Session golden", "markdown").unwrap();
+        assert_golden("export/session.md", &export.content);
+    }
+
+    #[test]
+    fn html_matches_golden_file() {
+        let export = export_messages(&messages(), "Session golden", "html").unwrap();
+        assert_eq!(export.warnings, ["1 image(s) were omitted because their data is not valid base64"]);
+        assert_golden("export/session.html", &export.content);
+    }
+
+    #[test]
+    fn crafted_image_blocks_cannot_inject_markup() {
+        let (html, dropped, invalid) = render_html(&messages(), "t");
+        assert_eq!((dropped, invalid), (0, 1));
+        assert!(!html.contains("<script"), "{}", html);
+        assert!(html.contains("<img alt=\"attachment\" src=\"data:image/png;base64,iVBORw0KGgo=\">"));
+
+        let uri = image_data_uri("image/png\" onload=\"x", "iVBORw0KGgo=").unwrap();
+        assert_eq!(uri, "data:application/octet-stream;base64,iVBORw0KGgo=");
+        assert!(image_data_uri("image/png", "abc\"<").is_none());
+    }
+
+    #[test]
+    fn fences_outrun_backticks_in_the_content() {
+        assert_eq!(fence_for("plain"), "```");
+        assert_eq!(fence_for("```sh\nls\n```"), "````");
+        assert_eq!(fence_for("a ````` b"), "``````");
+
+        let md = render_markdown(&messages(), "t");
+        // The tool output holds a run of four backticks, so its fence has five
+        assert!(md.contains("`````\n## Install\n\n```sh\nnpm install\n```\n\nA ```` run of four.\n`````\n"), "{}", md);
+    }
+}
@@ -0,0 +1,509 @@
+// mensa - File Index Module
+// Cached, gitignore-aware list of workspace files with fuzzy ranking for @-mentions
+
+use crate::{git, workspace, AppState};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Files indexed per workspace; the walk stops here
+const MAX_INDEXED_FILES: usize = 200_000;
+
+/// Paths published to a cold index at a time while the walk is running
+const BUILD_BATCH: usize = 2_000;
+
+/// A complete index older than this is rebuilt in the background (and served meanwhile)
+const INDEX_TTL: Duration = Duration::from_secs(60);
+
+/// How long git status boosts are reused
+const STATUS_TTL: Duration = Duration::from_secs(3);
+
+/// How long a call on a cold index waits for the walk before answering from what it has
+const SOFT_BUDGET: Duration = Duration::from_millis(150);
+
+/// How often a waiting call checks on the walk
+const BUILD_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Paths remembered per workspace for the recency boost
+const MAX_RECENT: usize = 100;
+
+/// Top results of a query remembered as recent
+const RECENT_PER_QUERY: usize = 3;
+
+/// Score added to modified, staged or untracked files
+const STATUS_BOOST: i64 = 40;
+
+/// Score added to the most recently returned path (older ones get proportionally less)
+const RECENT_BOOST: i64 = 30;
+
+#[derive(Default)]
+struct WorkspaceIndex {
+    /// Paths relative to the workspace root, `/`-separated
+    paths: Vec<String>,
+    complete: bool,
+    building: bool,
+    /// The walk stopped at MAX_INDEXED_FILES
+    truncated: bool,
+    built_at: Option<Instant>,
+    /// Files changed since the last build
+    dirty: bool,
+    /// Git status per path ("modified" | "staged" | "untracked") and when it was read
+    status: HashMap<String, &'static str>,
+    status_at: Option<Instant>,
+    /// Recently returned paths, newest first
+    recent: VecDeque<String>,
+}
+
+type SharedIndex = Arc<Mutex<WorkspaceIndex>>;
+
+/// File indexes of the workspaces the picker was used in, by canonical root
+#[derive(Default)]
+pub struct FileIndexCache {
+    workspaces: Mutex<HashMap<PathBuf, SharedIndex>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMatch {
+    pub path: String,
+    pub score: i64,
+    /// Char indices of the matched characters in `path`, for highlighting
+    pub positions: Vec<usize>,
+    /// "modified" | "staged" | "untracked" when the file has changes
+    pub status: Option<String>,
+    pub recent: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileMatchResult {
+    pub matches: Vec<FileMatch>,
+    /// The index is still being built; results come from the files seen so far
+    pub indexing: bool,
+    pub indexed_files: usize,
+    /// The workspace has more files than are indexed
+    pub truncated: bool,
+}
+
+/// A copy of a workspace's index at one point
+pub struct IndexSnapshot {
+    /// Workspace-relative, `/`-separated
+    pub paths: Vec<String>,
+    /// The walk had finished; otherwise `paths` holds what it had found so far
+    pub complete: bool,
+    pub truncated: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn lock(index: &SharedIndex) -> std::sync::MutexGuard<'_, WorkspaceIndex> {
+    index.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn workspace_index(cache: &FileIndexCache, root: &Path) -> SharedIndex {
+    let mut workspaces = cache.workspaces.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    workspaces.entry(root.to_path_buf()).or_default().clone()
+}
+
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?.to_string_lossy().to_string();
+    Some(if cfg!(windows) { relative.replace('\\', "/") } else { relative })
+}
+
+/// Walk the workspace into the index and announce it with `file-index-ready`
+fn build_index(app: &tauri::AppHandle, root: &Path, index: &SharedIndex) {
+    let (file_count, truncated) = walk_into(root, index);
+    let _ = app.emit("file-index-ready", serde_json::json!({
+        "working_dir": root.to_string_lossy(),
+        "file_count": file_count,
+        "truncated": truncated
+    }));
+}
+
+/// Walk the workspace (honouring .gitignore, .ignore and git excludes) into the index; the
+/// number of files and whether the walk was cut short.
+/// A cold index is filled in batches so callers can search it early; a rebuild swaps in at the end.
+fn walk_into(root: &Path, index: &SharedIndex) -> (usize, bool) {
+    let streaming = !lock(index).complete;
+    if streaming {
+        lock(index).paths.clear();
+    }
+
+    let walker = ignore::WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    // A cold index takes each batch as it fills; a rebuild collects everything first
+    let mut paths = Vec::new();
+    let mut batch = Vec::new();
+    let mut flush = |batch: &mut Vec<String>| {
+        if streaming {
+            lock(index).paths.append(batch);
+        } else {
+            paths.append(batch);
+        }
+    };
+    let mut file_count = 0;
+    let mut truncated = false;
+    for entry in walker.flatten() {
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Some(relative) = relative_path(root, entry.path()) else {
+            continue;
+        };
+        if file_count >= MAX_INDEXED_FILES {
+            truncated = true;
+            break;
+        }
+        file_count += 1;
+        batch.push(relative);
+        if batch.len() >= BUILD_BATCH {
+            flush(&mut batch);
+        }
+    }
+    flush(&mut batch);
+
+    {
+        let mut index = lock(index);
+        if !streaming {
+            index.paths = paths;
+        }
+        index.complete = true;
+        index.building = false;
+        index.truncated = truncated;
+        index.built_at = Some(Instant::now());
+    }
+    (file_count, truncated)
+}
+
+/// Start a background build when the index is cold, stale or dirty and none is running
+fn ensure_fresh(app: &tauri::AppHandle, root: &Path, index: &SharedIndex) {
+    {
+        let mut state = lock(index);
+        let stale = state.built_at.is_none_or(|at| at.elapsed() > INDEX_TTL);
+        if state.building || (state.complete && !stale && !state.dirty) {
+            return;
+        }
+        state.building = true;
+        state.dirty = false;
+    }
+    let (app, root, index) = (app.clone(), root.to_path_buf(), index.clone());
+    tauri::async_runtime::spawn_blocking(move || build_index(&app, &root, &index));
+}
+
+/// Git status of changed files, keyed by workspace-relative path
+fn read_status(root: &Path) -> HashMap<String, &'static str> {
+    let Ok(repo) = git::open_repo(&root.to_string_lossy()) else {
+        return HashMap::new();
+    };
+    let Some(workdir) = repo.workdir().map(Path::to_path_buf) else {
+        return HashMap::new();
+    };
+    let mut opts = git2::StatusOptions::new();
+    opts.include_untracked(true).recurse_untracked_dirs(true).include_ignored(false);
+    let Ok(statuses) = repo.statuses(Some(&mut opts)) else {
+        return HashMap::new();
+    };
+
+    let mut changed = HashMap::new();
+    for entry in statuses.iter() {
+        let Some(path) = entry.path() else { continue };
+        let Some(relative) = relative_path(root, &workdir.join(path)) else {
+            continue;
+        };
+        let status = entry.status();
+        let label = if status.is_wt_new() {
+            "untracked"
+        } else if status.is_index_new() || status.is_index_modified() || status.is_index_renamed() {
+            "staged"
+        } else if status.is_wt_modified() || status.is_wt_renamed() {
+            "modified"
+        } else {
+            continue;
+        };
+        changed.insert(relative, label);
+    }
+    changed
+}
+
+/// Re-read the git status boosts when they're older than STATUS_TTL
+async fn refresh_status(root: &Path, index: &SharedIndex) {
+    if lock(index).status_at.is_some_and(|at| at.elapsed() < STATUS_TTL) {
+        return;
+    }
+    let status_root = root.to_path_buf();
+    let Ok(status) = tokio::task::spawn_blocking(move || read_status(&status_root)).await else {
+        return;
+    };
+    let mut index = lock(index);
+    index.status = status;
+    index.status_at = Some(Instant::now());
+}
+
+/// Rank the indexed paths against `query` with status and recency boosts.
+/// An empty query lists boosted files first, then the shortest paths.
+fn rank(index: &WorkspaceIndex, query: &str, limit: usize) -> Vec<FileMatch> {
+    let matcher = SkimMatcherV2::default().smart_case();
+    let query = query.trim();
+    let recent: HashMap<&str, usize> = index.recent.iter().enumerate().map(|(i, p)| (p.as_str(), i)).collect();
+    let boost = |path: &str| {
+        let status = if index.status.contains_key(path) { STATUS_BOOST } else { 0 };
+        let recency = recent
+            .get(path)
+            .map_or(0, |&i| RECENT_BOOST * (MAX_RECENT - i) as i64 / MAX_RECENT as i64);
+        status + recency
+    };
+
+    let mut scored: Vec<(i64, &String)> = index
+        .paths
+        .iter()
+        .filter_map(|path| {
+            let base = if query.is_empty() { 0 } else { matcher.fuzzy_match(path, query)? };
+            Some((base + boost(path), path))
+        })
+        .collect();
+    scored.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.len().cmp(&b.1.len()))
+            .then_with(|| a.1.cmp(b.1))
+    });
+    scored.truncate(limit);
+
+    scored
+        .into_iter()
+        .map(|(score, path)| FileMatch {
+            positions: if query.is_empty() {
+                Vec::new()
+            } else {
+                matcher.fuzzy_indices(path, query).map(|(_, positions)| positions).unwrap_or_default()
+            },
+            status: index.status.get(path.as_str()).map(|s| s.to_string()),
+            recent: recent.contains_key(path.as_str()),
+            path: path.clone(),
+            score,
+        })
+        .collect()
+}
+
+/// Remember the top results of a query for the recency boost
+fn remember(index: &mut WorkspaceIndex, matches: &[FileMatch]) {
+    for found in matches.iter().take(RECENT_PER_QUERY).rev() {
+        index.recent.retain(|p| p != &found.path);
+        index.recent.push_front(found.path.clone());
+    }
+    index.recent.truncate(MAX_RECENT);
+}
+
+/// Give a cold index up to SOFT_BUDGET to finish its walk
+async fn wait_for_build(index: &SharedIndex) {
+    wait_for_build_within(index, SOFT_BUDGET).await;
+}
+
+async fn wait_for_build_within(index: &SharedIndex, budget: Duration) {
+    let deadline = Instant::now() + budget;
+    while !lock(index).complete && Instant::now() < deadline {
+        tokio::time::sleep(BUILD_POLL_INTERVAL).await;
+    }
+}
+
+/// The indexed paths of a workspace, waiting up to `budget` for a cold index to finish its walk
+pub async fn snapshot(app: &tauri::AppHandle, root: &Path, budget: Duration) -> IndexSnapshot {
+    let index = workspace_index(&app.state::<AppState>().file_index, root);
+    ensure_fresh(app, root, &index);
+    wait_for_build_within(&index, budget).await;
+    snapshot_of(&index)
+}
+
+fn snapshot_of(index: &SharedIndex) -> IndexSnapshot {
+    let index = lock(index);
+    IndexSnapshot {
+        paths: index.paths.clone(),
+        complete: index.complete,
+        truncated: index.truncated,
+    }
+}
+
+/// Walk a workspace into an index of its own, outside the cache
+#[cfg(test)]
+pub fn walked(root: &Path) -> IndexSnapshot {
+    let index = SharedIndex::default();
+    walk_into(root, &index);
+    snapshot_of(&index)
+}
+
+/// Indexed paths closest to `query`, best first, for "did you mean" suggestions.
+/// Doesn't count towards the recency boost.
+pub async fn closest_paths(app: &tauri::AppHandle, root: &Path, query: &str, limit: usize) -> Vec<String> {
+    let index = workspace_index(&app.state::<AppState>().file_index, root);
+    ensure_fresh(app, root, &index);
+    wait_for_build(&index).await;
+    let query = query.to_string();
+    tokio::task::spawn_blocking(move || rank(&lock(&index), &query, limit))
+        .await
+        .map(|matches| matches.into_iter().map(|m| m.path).collect())
+        .unwrap_or_default()
+}
+
+/// Mark the indexes containing any of these files as changed, so the next match rebuilds them
+pub fn mark_dirty(app: &tauri::AppHandle, files: &HashSet<PathBuf>) {
+    let state = app.state::<AppState>();
+    let workspaces = state.file_index.workspaces.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for (root, index) in workspaces.iter() {
+        if files.iter().any(|f| f.starts_with(root)) {
+            lock(index).dirty = true;
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Fuzzy-match workspace files for @-mentions. Files with git changes and recently returned
+/// files rank higher. A cold index answers within a soft budget from the files walked so far
+/// (`indexing: true`) and emits `file-index-ready` once complete.
+#[tauri::command]
+pub async fn fuzzy_match_files(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<FileMatchResult, String> {
+    let root = workspace::canonical_dir(&working_dir)?;
+    let index = workspace_index(&state.file_index, &root);
+    ensure_fresh(&app, &root, &index);
+
+    wait_for_build(&index).await;
+    refresh_status(&root, &index).await;
+
+    let limit = limit.unwrap_or(50);
+    let ranking_index = index.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut index = lock(&ranking_index);
+        let matches = rank(&index, &query, limit);
+        if !query.trim().is_empty() {
+            remember(&mut index, &matches);
+        }
+        FileMatchResult {
+            matches,
+            indexing: !index.complete,
+            indexed_files: index.paths.len(),
+            truncated: index.truncated,
+        }
+    })
+    .await
+    .map_err(|e| format!("File matching failed: {}", e))
+}
+
+/// Rebuild a workspace's file index on its next use (e.g. after files were created outside mensa)
+#[tauri::command]
+pub async fn invalidate_file_index(state: State<'_, AppState>, working_dir: String) -> Result<(), String> {
+    let root = workspace::canonical_dir(&working_dir)?;
+    lock(&workspace_index(&state.file_index, &root)).dirty = true;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write;
+
+    /// `dirs` x `per_dir` source files, plus an ignored build directory and a .git to skip
+    fn large_tree(dirs: usize, per_dir: usize) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for d in 0..dirs {
+            for f in 0..per_dir {
+                write(dir.path(), &format!("src/module_{}/file_{}.rs", d, f), "");
+            }
+        }
+        write(dir.path(), ".gitignore", "target/\n");
+        for f in 0..500 {
+            write(dir.path(), &format!("target/debug/build_{}.rs", f), "");
+        }
+        write(dir.path(), ".git/HEAD", "ref: refs/heads/main\n");
+        write(dir.path(), "src/module_7/needle_handler.rs", "");
+        dir
+    }
+
+    fn index_of(paths: &[&str]) -> WorkspaceIndex {
+        WorkspaceIndex {
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            complete: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn large_tree_is_walked_and_ranked_within_budget() {
+        let dir = large_tree(200, 100);
+        let index = SharedIndex::default();
+
+        let started = Instant::now();
+        let (file_count, truncated) = walk_into(dir.path(), &index);
+        let walked = started.elapsed();
+        assert_eq!((file_count, truncated), (20_002, false));
+        let index = lock(&index);
+        assert!(index.complete && !index.building);
+        assert!(index.paths.iter().all(|p| !p.starts_with("target/") && !p.starts_with(".git/")));
+
+        let started = Instant::now();
+        let matches = rank(&index, "needlehand", 20);
+        let ranked = started.elapsed();
+        assert_eq!(matches[0].path, "src/module_7/needle_handler.rs");
+        let highlighted: String = matches[0].positions.iter().map(|&i| matches[0].path.chars().nth(i).unwrap()).collect();
+        assert_eq!(highlighted.to_lowercase(), "needlehand");
+
+        // Generous for an unoptimized build on a loaded machine; a regression here is orders of magnitude
+        assert!(walked < Duration::from_secs(10), "walk took {:?}", walked);
+        assert!(ranked < Duration::from_secs(2), "ranking took {:?}", ranked);
+    }
+
+    #[test]
+    fn changed_and_recent_files_outrank_equal_matches() {
+        let mut index = index_of(&["src/a/config.rs", "src/b/config.rs", "src/c/config.rs"]);
+        index.status.insert("src/c/config.rs".to_string(), "modified");
+
+        let matches = rank(&index, "config", 3);
+        assert_eq!(matches[0].path, "src/c/config.rs");
+        assert_eq!(matches[0].status.as_deref(), Some("modified"));
+
+        // Returned once, b is now recent; the change still weighs more
+        let picked = rank(&index, "b/config", 1);
+        remember(&mut index, &picked);
+        let order: Vec<String> = rank(&index, "config", 3).into_iter().map(|m| m.path).collect();
+        assert_eq!(order, ["src/c/config.rs", "src/b/config.rs", "src/a/config.rs"]);
+        assert!(rank(&index, "config", 3)[1].recent);
+
+        // An empty query lists the boosted files first, without highlights
+        let listed = rank(&index, "", 2);
+        assert_eq!(listed.iter().map(|m| m.path.as_str()).collect::<Vec<_>>(), ["src/c/config.rs", "src/b/config.rs"]);
+        assert!(listed.iter().all(|m| m.positions.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn cold_index_answers_from_a_partial_walk_within_the_budget() {
+        let index = SharedIndex::default();
+        lock(&index).paths = vec!["src/main.rs".to_string()];
+
+        let started = Instant::now();
+        wait_for_build_within(&index, Duration::from_millis(50)).await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let index = lock(&index);
+        assert!(!index.complete);
+        assert_eq!(rank(&index, "main", 10).len(), 1);
+    }
+}
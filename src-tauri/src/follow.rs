@@ -0,0 +1,364 @@
+// mensa - Follow Module
+// Live view of a file while the agent edits it: polls for changes and emits line patches
+
+use crate::cancel::CancellationToken;
+use crate::{workspace, AppState};
+use serde::Serialize;
+use similar::{DiffTag, TextDiff};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tauri::{Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Most files that can be followed at once
+const MAX_FOLLOWS: usize = 8;
+
+/// Larger files are reported as changed without content
+const MAX_FOLLOW_BYTES: u64 = 2 * 1024 * 1024;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Send the whole file instead of hunks once the patch touches more than this share of lines
+const FULL_CONTENT_RATIO: f64 = 0.5;
+
+/// Followed files by follow id; cancelling a handle's token stops its watcher
+#[derive(Default)]
+pub struct FollowRegistry {
+    entries: Arc<Mutex<HashMap<String, FollowHandle>>>,
+}
+
+struct FollowHandle {
+    path: PathBuf,
+    token: CancellationToken,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FollowInfo {
+    pub follow_id: String,
+    pub path: String,
+    /// Content the first patch applies to (None if missing, binary or too large)
+    pub content: Option<String>,
+}
+
+/// Replace `old_len` lines starting at `old_start` (0-based, in the previous content) with `lines`.
+/// Hunks are in ascending order and don't overlap, so apply them back to front.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchHunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub lines: Vec<String>,
+}
+
+/// Identity and version of the file on disk, used to detect edits and replacements
+#[derive(Debug, Clone, PartialEq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    inode: u64,
+}
+
+enum Snapshot {
+    Missing,
+    Unreadable,
+    Text(String),
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn file_stamp(metadata: &std::fs::Metadata) -> FileStamp {
+    #[cfg(unix)]
+    let inode = {
+        use std::os::unix::fs::MetadataExt;
+        metadata.ino()
+    };
+    #[cfg(not(unix))]
+    let inode = 0;
+
+    FileStamp {
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+        inode,
+    }
+}
+
+/// Read a followed file. A symlink (say one swapped in after following started) is never
+/// read through: its target could be anywhere.
+fn read_snapshot(path: &Path) -> (Option<FileStamp>, Snapshot) {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return (None, Snapshot::Missing),
+    };
+    let stamp = file_stamp(&metadata);
+    if metadata.file_type().is_symlink() || metadata.len() > MAX_FOLLOW_BYTES {
+        return (Some(stamp), Snapshot::Unreadable);
+    }
+    match read_no_follow(path).map(String::from_utf8) {
+        Ok(Ok(text)) => (Some(stamp), Snapshot::Text(text)),
+        _ => (Some(stamp), Snapshot::Unreadable),
+    }
+}
+
+/// Read a file, failing if the last component has become a symlink since it was checked
+fn read_no_follow(path: &Path) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(nix::libc::O_NOFOLLOW);
+    }
+    let mut content = Vec::new();
+    options.open(path)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// The file `path` names inside `root`, checked to stay there: its directory must resolve
+/// under the workspace and the file itself must not be a symlink (nor resolve outside)
+fn followable_target(root: &Path, path: &str) -> Result<PathBuf, String> {
+    let joined = root.join(path);
+    let name = joined
+        .file_name()
+        .ok_or_else(|| format!("Not a file path: {}", path))?
+        .to_os_string();
+    let parent = joined
+        .parent()
+        .and_then(|p| std::fs::canonicalize(p).ok())
+        .ok_or_else(|| format!("Directory for {} does not exist", path))?;
+    let outside = || format!("Refusing to follow a file outside the workspace: {}", path);
+    if !parent.starts_with(root) {
+        return Err(outside());
+    }
+
+    let target = parent.join(name);
+    match std::fs::symlink_metadata(&target) {
+        Ok(metadata) if metadata.file_type().is_symlink() => Err(format!("Refusing to follow a symlink: {}", path)),
+        Ok(_) => match std::fs::canonicalize(&target) {
+            Ok(canonical) if canonical.starts_with(root) => Ok(canonical),
+            _ => Err(outside()),
+        },
+        // Not there yet: followed until it appears
+        Err(_) => Ok(target),
+    }
+}
+
+/// Line hunks turning `old` into `new`, or None when a full reload is cheaper
+fn compute_hunks(old: &str, new: &str) -> Option<Vec<PatchHunk>> {
+    let diff = TextDiff::from_lines(old, new);
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let mut hunks = Vec::new();
+    let mut touched = 0usize;
+    for op in diff.ops() {
+        if op.tag() == DiffTag::Equal {
+            continue;
+        }
+        let old_range = op.old_range();
+        let new_range = op.new_range();
+        touched += old_range.len().max(new_range.len());
+        hunks.push(PatchHunk {
+            old_start: old_range.start,
+            old_len: old_range.len(),
+            lines: new_lines[new_range].iter().map(|l| l.to_string()).collect(),
+        });
+    }
+
+    let total = new_lines.len().max(old.lines().count()).max(1);
+    if touched as f64 / total as f64 > FULL_CONTENT_RATIO {
+        return None;
+    }
+    Some(hunks)
+}
+
+/// Poll the file and emit patches, full reloads, removals and recreations until cancelled
+fn spawn_watcher(
+    app: tauri::AppHandle,
+    follow_id: String,
+    path: PathBuf,
+    mut stamp: Option<FileStamp>,
+    mut served: Option<String>,
+) -> CancellationToken {
+    let tasks = app.state::<AppState>().tasks.clone();
+    let name = format!("follow {}", path.display());
+    tasks.spawn(name, |token| async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = token.cancelled() => return,
+            }
+
+            let check_path = path.clone();
+            let (current_stamp, snapshot) = match tokio::task::spawn_blocking(move || read_snapshot(&check_path)).await {
+                Ok(result) => result,
+                Err(_) => continue,
+            };
+            if current_stamp == stamp {
+                continue;
+            }
+            let replaced = matches!((&stamp, &current_stamp), (Some(a), Some(b)) if a.inode != b.inode);
+            let existed = stamp.is_some();
+            stamp = current_stamp;
+
+            match snapshot {
+                Snapshot::Missing => {
+                    served = None;
+                    let _ = app.emit("file-removed", serde_json::json!({ "follow_id": follow_id }));
+                }
+                Snapshot::Unreadable => {
+                    served = None;
+                    let _ = app.emit(
+                        "file-content",
+                        serde_json::json!({ "follow_id": follow_id, "content": null, "recreated": !existed }),
+                    );
+                }
+                Snapshot::Text(text) => {
+                    let hunks = match (&served, replaced) {
+                        (Some(previous), false) => compute_hunks(previous, &text),
+                        _ => None,
+                    };
+                    match hunks {
+                        Some(hunks) if hunks.is_empty() => {}
+                        Some(hunks) => {
+                            let _ = app.emit("file-patched", serde_json::json!({ "follow_id": follow_id, "hunks": hunks }));
+                        }
+                        None => {
+                            let _ = app.emit(
+                                "file-content",
+                                serde_json::json!({ "follow_id": follow_id, "content": text, "recreated": !existed }),
+                            );
+                        }
+                    }
+                    served = Some(text);
+                }
+            }
+        }
+    })
+    .token
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Start following a workspace file; changes arrive as file-patched / file-content / file-removed events
+#[tauri::command]
+pub async fn follow_file(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+    path: String,
+) -> Result<FollowInfo, String> {
+    let root = workspace::validate_selected_workspace(&state, &working_dir).await?;
+    let target = followable_target(&root, &path)?;
+
+    let mut entries = state.followed_files.entries.lock().await;
+    if let Some((follow_id, _)) = entries.iter().find(|(_, handle)| handle.path == target) {
+        return Err(format!("{} is already followed ({})", path, follow_id));
+    }
+    if entries.len() >= MAX_FOLLOWS {
+        return Err(format!("Too many followed files (limit {})", MAX_FOLLOWS));
+    }
+
+    let read_path = target.clone();
+    let (stamp, snapshot) = tokio::task::spawn_blocking(move || read_snapshot(&read_path))
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let content = match snapshot {
+        Snapshot::Text(text) => Some(text),
+        _ => None,
+    };
+
+    let follow_id = uuid::Uuid::new_v4().to_string();
+    let token = spawn_watcher(app, follow_id.clone(), target.clone(), stamp, content.clone());
+    entries.insert(follow_id.clone(), FollowHandle { path: target, token });
+
+    Ok(FollowInfo {
+        follow_id,
+        path,
+        content,
+    })
+}
+
+/// Stop following a file; returns whether the follow existed
+#[tauri::command]
+pub async fn unfollow_file(state: State<'_, AppState>, follow_id: String) -> Result<bool, String> {
+    match state.followed_files.entries.lock().await.remove(&follow_id) {
+        Some(handle) => {
+            handle.token.cancel();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write;
+
+    fn workspace() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn files_inside_the_workspace_can_be_followed() {
+        let (_dir, root) = workspace();
+        write(&root, "src/main.rs", "fn main() {}\n");
+        assert_eq!(followable_target(&root, "src/main.rs").unwrap(), root.join("src/main.rs"));
+        // Not created yet
+        assert_eq!(followable_target(&root, "src/new.rs").unwrap(), root.join("src/new.rs"));
+    }
+
+    #[test]
+    fn paths_leaving_the_workspace_are_refused() {
+        let (_dir, root) = workspace();
+        write(&root, "a.txt", "a");
+        assert!(followable_target(&root, "../outside.txt").is_err());
+        assert!(followable_target(&root, "src/..").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_refused() {
+        let (_dir, root) = workspace();
+        let (_elsewhere, outside) = workspace();
+        write(&outside, "secret.txt", "secret");
+        write(&root, "inside.txt", "inside");
+        std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(root.join("inside.txt"), root.join("inner-link.txt")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("linked-dir")).unwrap();
+
+        let error = followable_target(&root, "link.txt").unwrap_err();
+        assert!(error.contains("symlink"), "{}", error);
+        assert!(followable_target(&root, "inner-link.txt").is_err());
+        assert!(followable_target(&root, "linked-dir/secret.txt").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_file_swapped_for_a_symlink_is_not_read_through() {
+        let (_dir, root) = workspace();
+        let (_elsewhere, outside) = workspace();
+        write(&outside, "secret.txt", "secret");
+        write(&root, "notes.txt", "notes");
+        assert!(matches!(read_snapshot(&root.join("notes.txt")).1, Snapshot::Text(ref text) if text == "notes"));
+
+        std::fs::remove_file(root.join("notes.txt")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("notes.txt")).unwrap();
+        assert!(matches!(read_snapshot(&root.join("notes.txt")).1, Snapshot::Unreadable));
+        assert!(read_no_follow(&root.join("notes.txt")).is_err());
+    }
+}
@@ -0,0 +1,641 @@
+// mensa - Forge Module
+// Code hosts behind review URLs and remotes: GitHub through gh, GitLab merge requests over REST, Bitbucket
+
+use crate::git::GhPRInfo;
+use crate::secrets::{self, GITLAB_TOKEN_SECRET};
+use crate::{git, proxy};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// How long one GitLab API request may take
+const GITLAB_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Provider {
+    Github,
+    Gitlab,
+    Bitbucket,
+    /// A host we can't place (e.g. GitHub Enterprise); PR features are left to gh
+    Unknown,
+}
+
+impl Provider {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Provider::Github => "GitHub",
+            Provider::Gitlab => "GitLab",
+            Provider::Bitbucket => "Bitbucket",
+            Provider::Unknown => "this host",
+        }
+    }
+
+    /// Creating and listing pull requests goes through gh, so it's off for known non-GitHub hosts
+    pub fn supports_pull_requests(&self) -> bool {
+        matches!(self, Provider::Github | Provider::Unknown)
+    }
+
+    /// Whether a review can load the PR/MR info and diff
+    pub fn supports_review(&self) -> bool {
+        matches!(self, Provider::Github | Provider::Gitlab)
+    }
+
+    /// Whether a review can be posted back
+    pub fn supports_review_posting(&self) -> bool {
+        matches!(self, Provider::Github)
+    }
+}
+
+/// A pull or merge request parsed from its web URL
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewTarget {
+    pub provider: Provider,
+    /// e.g. "https://gitlab.example.com"
+    pub base_url: String,
+    /// "owner/repo" on GitHub and Bitbucket Cloud, the full group path on GitLab,
+    /// "PROJECT/repo" on Bitbucket Server
+    pub project: String,
+    pub number: String,
+}
+
+/// What the code host of a repository's remote supports, so the UI offers the right actions
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoInfo {
+    pub provider: Provider,
+    /// "origin", or the first remote when there is no origin
+    pub remote_name: Option<String>,
+    pub remote_url: Option<String>,
+    pub host: Option<String>,
+    pub project: Option<String>,
+    pub supports_pull_requests: bool,
+    pub supports_review: bool,
+    pub supports_review_posting: bool,
+}
+
+/// Error for review commands that only some code hosts support.
+/// Serialized as `{ kind, message, ... }` so the UI can hide the action instead of failing.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ReviewError {
+    UnsupportedProvider {
+        provider: Provider,
+        operation: String,
+        message: String,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl From<String> for ReviewError {
+    fn from(message: String) -> Self {
+        ReviewError::Failed { message }
+    }
+}
+
+impl std::fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReviewError::UnsupportedProvider { message, .. } | ReviewError::Failed { message } => f.write_str(message),
+        }
+    }
+}
+
+impl ReviewError {
+    pub fn unsupported(provider: Provider, operation: &str) -> Self {
+        ReviewError::UnsupportedProvider {
+            provider,
+            operation: operation.to_string(),
+            message: format!("{} isn't supported for {} yet", operation, provider.label()),
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// https://github.com/owner/repo/pull/123
+fn github_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(?:(https?)://)?(?:www\.)?(github\.com)/([^/\s]+/[^/\s]+)/pull/(\d+)").unwrap())
+}
+
+/// https://gitlab.com/group/subgroup/project/-/merge_requests/12 (any host, older URLs lack the `-/`)
+fn gitlab_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(?:(https?)://)?([^/\s]+)/([^\s]+?)(?:/-)?/merge_requests/(\d+)").unwrap())
+}
+
+/// https://bitbucket.org/workspace/repo/pull-requests/7
+fn bitbucket_cloud_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(?:(https?)://)?(bitbucket\.org)/([^/\s]+/[^/\s]+)/pull-requests/(\d+)").unwrap())
+}
+
+/// https://bitbucket.example.com/projects/KEY/repos/repo/pull-requests/7 (Bitbucket Server)
+fn bitbucket_server_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^(?:(https?)://)?([^/\s]+)(?:/[^\s]*?)?/projects/([^/\s]+)/repos/([^/\s]+)/pull-requests/(\d+)").unwrap()
+    })
+}
+
+/// Parse a GitHub PR, GitLab merge request or Bitbucket PR URL
+pub fn parse_review_url(url: &str) -> Result<ReviewTarget, String> {
+    let url = url.trim();
+    let base_url = |scheme: Option<regex::Match>, host: &str| {
+        format!("{}://{}", scheme.map(|m| m.as_str()).unwrap_or("https"), host)
+    };
+    let simple = [
+        (Provider::Github, github_pattern()),
+        (Provider::Bitbucket, bitbucket_cloud_pattern()),
+        (Provider::Gitlab, gitlab_pattern()),
+    ];
+    for (provider, pattern) in simple {
+        if let Some(caps) = pattern.captures(url) {
+            return Ok(ReviewTarget {
+                provider,
+                base_url: base_url(caps.get(1), &caps[2]),
+                project: caps[3].trim_end_matches(".git").to_string(),
+                number: caps[4].to_string(),
+            });
+        }
+    }
+    if let Some(caps) = bitbucket_server_pattern().captures(url) {
+        return Ok(ReviewTarget {
+            provider: Provider::Bitbucket,
+            base_url: base_url(caps.get(1), &caps[2]),
+            project: format!("{}/{}", &caps[3], &caps[4]),
+            number: caps[5].to_string(),
+        });
+    }
+    Err(format!(
+        "Invalid PR URL format: {} (expected a GitHub pull request, GitLab merge request or Bitbucket pull request URL)",
+        url
+    ))
+}
+
+impl ReviewTarget {
+    /// Stable key regardless of URL form; GitHub keeps the "owner/repo#number" form drafts were saved under
+    pub fn key(&self) -> String {
+        let key = match self.provider {
+            Provider::Github => format!("{}#{}", self.project, self.number),
+            Provider::Gitlab => format!("{}/{}!{}", host_of(&self.base_url), self.project, self.number),
+            _ => format!("{}/{}#{}", host_of(&self.base_url), self.project, self.number),
+        };
+        key.to_lowercase()
+    }
+}
+
+fn host_of(base_url: &str) -> &str {
+    base_url.split_once("://").map(|(_, host)| host).unwrap_or(base_url)
+}
+
+fn provider_for_host(host: &str) -> Provider {
+    let host = host.to_lowercase();
+    if host == "github.com" || host == "www.github.com" || host.ends_with(".github.com") {
+        Provider::Github
+    } else if host.contains("gitlab") {
+        Provider::Gitlab
+    } else if host.contains("bitbucket") {
+        Provider::Bitbucket
+    } else {
+        Provider::Unknown
+    }
+}
+
+/// Host and project path of a remote URL (https, ssh:// or scp-like `git@host:path`);
+/// None for local paths
+fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let url = url.trim();
+    let (host, path) = if url.contains("://") {
+        let parsed = reqwest::Url::parse(url).ok()?;
+        if parsed.scheme() == "file" {
+            return None;
+        }
+        (parsed.host_str()?.to_string(), parsed.path().to_string())
+    } else {
+        // scp-like: [user@]host:path, where a drive letter or a slash before the colon means a local path
+        let (authority, path) = url.split_once(':')?;
+        let host = authority.rsplit('@').next()?;
+        if host.len() < 2 || host.contains('/') || host.contains('\\') {
+            return None;
+        }
+        (host.to_string(), path.to_string())
+    };
+    let project = path.trim_matches('/').trim_end_matches(".git").to_string();
+    (!host.is_empty() && !project.is_empty()).then_some((host, project))
+}
+
+fn read_repo_info(working_dir: &str) -> Result<RepoInfo, String> {
+    let repo = git::open_repo(working_dir)?;
+    let remotes = repo.remotes().map_err(|e| format!("Failed to list remotes: {}", e))?;
+    let remote_name = if remotes.iter().flatten().any(|name| name == "origin") {
+        Some("origin".to_string())
+    } else {
+        remotes.iter().flatten().next().map(str::to_string)
+    };
+    let remote_url = remote_name
+        .as_deref()
+        .and_then(|name| repo.find_remote(name).ok())
+        .and_then(|remote| remote.url().map(str::to_string));
+    let parsed = remote_url.as_deref().and_then(parse_remote_url);
+    let provider = parsed
+        .as_ref()
+        .map(|(host, _)| provider_for_host(host))
+        .unwrap_or(Provider::Unknown);
+    Ok(RepoInfo {
+        provider,
+        remote_name,
+        remote_url,
+        host: parsed.as_ref().map(|(host, _)| host.clone()),
+        project: parsed.map(|(_, project)| project),
+        supports_pull_requests: provider.supports_pull_requests(),
+        supports_review: provider.supports_review(),
+        supports_review_posting: provider.supports_review_posting(),
+    })
+}
+
+/// Fail with a clear message when the repository's remote is a host gh can't open PRs on
+pub async fn ensure_pull_requests(working_dir: &str) -> Result<(), String> {
+    let dir = working_dir.to_string();
+    let info = tokio::task::spawn_blocking(move || read_repo_info(&dir))
+        .await
+        .map_err(|e| format!("Failed to read remotes: {}", e))??;
+    if info.supports_pull_requests {
+        Ok(())
+    } else {
+        Err(format!(
+            "Pull requests aren't supported for {} remotes yet",
+            info.provider.label()
+        ))
+    }
+}
+
+/// GitLab's state names in the GitHub form the review UI expects
+fn gitlab_state(state: &str) -> &'static str {
+    match state {
+        "merged" => "MERGED",
+        "closed" | "locked" => "CLOSED",
+        _ => "OPEN",
+    }
+}
+
+/// Added and removed lines of the changes endpoint's per-file diffs (hunks only, no file headers)
+fn count_changed_lines(changes: &[Value]) -> (u32, u32) {
+    let mut additions = 0;
+    let mut deletions = 0;
+    for change in changes {
+        for line in change["diff"].as_str().unwrap_or("").lines() {
+            if line.starts_with('+') {
+                additions += 1;
+            } else if line.starts_with('-') {
+                deletions += 1;
+            }
+        }
+    }
+    (additions, deletions)
+}
+
+/// A merge request and its changes mapped into the shape the GitHub review path returns
+fn map_gitlab_mr(mr: &Value, changes: &[Value], commits: u32) -> GhPRInfo {
+    let (additions, deletions) = count_changed_lines(changes);
+    let changed_files = mr["changes_count"]
+        .as_str()
+        .and_then(|count| count.trim_end_matches('+').parse().ok())
+        .unwrap_or(changes.len() as u32);
+    GhPRInfo {
+        title: mr["title"].as_str().unwrap_or("").to_string(),
+        body: mr["description"].as_str().unwrap_or("").to_string(),
+        author: mr["author"]["username"].as_str().unwrap_or("").to_string(),
+        state: gitlab_state(mr["state"].as_str().unwrap_or("opened")).to_string(),
+        additions,
+        deletions,
+        changed_files,
+        commits,
+        base_ref_name: mr["target_branch"].as_str().unwrap_or("").to_string(),
+        head_ref_name: mr["source_branch"].as_str().unwrap_or("").to_string(),
+        created_at: mr["created_at"].as_str().unwrap_or("").to_string(),
+        updated_at: mr["updated_at"].as_str().unwrap_or("").to_string(),
+        head_sha: mr["sha"].as_str().unwrap_or("").to_string(),
+        body_html: None,
+        schema_warnings: Vec::new(),
+        extra: serde_json::Map::new(),
+    }
+}
+
+/// A git-style unified diff from the changes endpoint, so the review parses it like `gh pr diff`
+fn gitlab_unified_diff(changes: &[Value]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        let old_path = change["old_path"].as_str().unwrap_or("");
+        let new_path = change["new_path"].as_str().unwrap_or(old_path);
+        let new_file = change["new_file"].as_bool().unwrap_or(false);
+        let deleted_file = change["deleted_file"].as_bool().unwrap_or(false);
+        let renamed_file = change["renamed_file"].as_bool().unwrap_or(false);
+        let a_mode = change["a_mode"].as_str().unwrap_or("");
+        let b_mode = change["b_mode"].as_str().unwrap_or("");
+
+        out.push_str(&format!("diff --git a/{} b/{}\n", old_path, new_path));
+        if new_file {
+            out.push_str(&format!("new file mode {}\n", b_mode));
+        } else if deleted_file {
+            out.push_str(&format!("deleted file mode {}\n", a_mode));
+        } else if a_mode != b_mode && !a_mode.is_empty() && !b_mode.is_empty() {
+            out.push_str(&format!("old mode {}\nnew mode {}\n", a_mode, b_mode));
+        }
+        if renamed_file {
+            out.push_str(&format!("rename from {}\nrename to {}\n", old_path, new_path));
+        }
+
+        let diff = change["diff"].as_str().unwrap_or("");
+        if diff.is_empty() {
+            // Pure renames and mode changes have no hunks; anything else empty is binary
+            if !renamed_file && (new_file || deleted_file || a_mode == b_mode) {
+                out.push_str(&format!("Binary files a/{} and b/{} differ\n", old_path, new_path));
+            }
+            continue;
+        }
+        let old_label = if new_file { "/dev/null".to_string() } else { format!("a/{}", old_path) };
+        let new_label = if deleted_file { "/dev/null".to_string() } else { format!("b/{}", new_path) };
+        out.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+        out.push_str(diff);
+        if !diff.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// A GitLab API session for one instance: the proxy-aware client and the token, if one is stored
+struct GitlabApi {
+    client: reqwest::Client,
+    api_base: String,
+    token: Option<String>,
+}
+
+impl GitlabApi {
+    async fn connect(app: &tauri::AppHandle, target: &ReviewTarget) -> Result<Self, String> {
+        let resolved = proxy::resolve(app).await?;
+        let client = proxy::http_client(resolved.as_ref(), GITLAB_TIMEOUT)?;
+        let host_secret = format!("{}@{}", GITLAB_TOKEN_SECRET, host_of(&target.base_url));
+        let mut token = None;
+        for name in [host_secret.as_str(), GITLAB_TOKEN_SECRET] {
+            if secrets::has_secret(app, name).await? {
+                token = Some(secrets::read_secret(app, name).await?);
+                break;
+            }
+        }
+        Ok(GitlabApi {
+            client,
+            api_base: format!("{}/api/v4", target.base_url),
+            token,
+        })
+    }
+
+    /// GET an API path given as segments (each percent-encoded, so a project path becomes one segment)
+    async fn get(&self, segments: &[&str], query: &[(&str, &str)]) -> Result<(Value, Option<u32>), String> {
+        let mut url = reqwest::Url::parse(&self.api_base).map_err(|e| format!("Invalid GitLab URL {}: {}", self.api_base, e))?;
+        url.path_segments_mut()
+            .map_err(|_| format!("Invalid GitLab URL {}", self.api_base))?
+            .extend(segments);
+        url.query_pairs_mut().extend_pairs(query);
+
+        let mut request = self.client.get(url.clone());
+        if let Some(token) = &self.token {
+            request = request.header("PRIVATE-TOKEN", token);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach GitLab: {}", e))?;
+        let status = response.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::NOT_FOUND {
+            let hint = if self.token.is_some() {
+                "check that the stored GitLab token has the read_api scope"
+            } else {
+                "store a GitLab token with the read_api scope as the secret 'gitlab_token'"
+            };
+            return Err(format!("GitLab returned {} for {} ({})", status, url.path(), hint));
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("GitLab returned {} for {}: {}", status, url.path(), body.trim()));
+        }
+        let total = response
+            .headers()
+            .get("x-total")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok());
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read GitLab response: {}", e))?;
+        let json = serde_json::from_str(&body).map_err(|e| format!("Failed to parse GitLab response: {}", e))?;
+        Ok((json, total))
+    }
+
+    /// The numeric id of a project, looked up by its URL-encoded path
+    async fn project_id(&self, project: &str) -> Result<String, String> {
+        let (json, _) = self.get(&["projects", project], &[]).await?;
+        json["id"]
+            .as_u64()
+            .map(|id| id.to_string())
+            .ok_or_else(|| format!("GitLab project not found: {}", project))
+    }
+
+    async fn changes(&self, project_id: &str, iid: &str) -> Result<Vec<Value>, String> {
+        let (json, _) = self
+            .get(
+                &["projects", project_id, "merge_requests", iid, "changes"],
+                &[("access_raw_diffs", "true")],
+            )
+            .await?;
+        Ok(json["changes"].as_array().cloned().unwrap_or_default())
+    }
+}
+
+/// Fetch a merge request as PR info
+pub async fn fetch_mr_info(app: &tauri::AppHandle, target: &ReviewTarget) -> Result<GhPRInfo, String> {
+    let api = GitlabApi::connect(app, target).await?;
+    let project_id = api.project_id(&target.project).await?;
+    let iid = target.number.as_str();
+    let (mr, changes, commits) = tokio::try_join!(
+        async { api.get(&["projects", &project_id, "merge_requests", iid], &[]).await.map(|(json, _)| json) },
+        api.changes(&project_id, iid),
+        async {
+            api.get(
+                &["projects", &project_id, "merge_requests", iid, "commits"],
+                &[("per_page", "100")],
+            )
+            .await
+            .map(|(json, total)| total.unwrap_or(json.as_array().map(|a| a.len()).unwrap_or(0) as u32))
+        },
+    )?;
+    Ok(map_gitlab_mr(&mr, &changes, commits))
+}
+
+/// Fetch a merge request's diff in unified git form
+pub async fn fetch_mr_diff(app: &tauri::AppHandle, target: &ReviewTarget) -> Result<String, String> {
+    let api = GitlabApi::connect(app, target).await?;
+    let project_id = api.project_id(&target.project).await?;
+    let changes = api.changes(&project_id, &target.number).await?;
+    Ok(gitlab_unified_diff(&changes))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Which code host a repository's remote points at and what mensa can do there
+#[tauri::command]
+pub async fn get_repo_info(working_dir: String) -> Result<RepoInfo, String> {
+    tokio::task::spawn_blocking(move || read_repo_info(&working_dir))
+        .await
+        .map_err(|e| format!("Failed to read remotes: {}", e))?
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn target(provider: Provider, base_url: &str, project: &str, number: &str) -> ReviewTarget {
+        ReviewTarget {
+            provider,
+            base_url: base_url.to_string(),
+            project: project.to_string(),
+            number: number.to_string(),
+        }
+    }
+
+    #[test]
+    fn review_urls_of_each_host() {
+        let cases = [
+            ("https://github.com/tauri-apps/tauri/pull/9876", target(Provider::Github, "https://github.com", "tauri-apps/tauri", "9876")),
+            ("github.com/owner/repo/pull/1/files#diff-abc", target(Provider::Github, "https://github.com", "owner/repo", "1")),
+            ("  https://www.github.com/owner/repo.git/pull/12/commits  ", target(Provider::Github, "https://github.com", "owner/repo", "12")),
+            (
+                "https://gitlab.com/acme/platform/web/-/merge_requests/42/diffs",
+                target(Provider::Gitlab, "https://gitlab.com", "acme/platform/web", "42"),
+            ),
+            (
+                "http://git.internal:8080/team/app/merge_requests/7",
+                target(Provider::Gitlab, "http://git.internal:8080", "team/app", "7"),
+            ),
+            (
+                "https://bitbucket.org/workspace/repo/pull-requests/3/overview",
+                target(Provider::Bitbucket, "https://bitbucket.org", "workspace/repo", "3"),
+            ),
+            (
+                "https://bitbucket.example.com/projects/KEY/repos/service/pull-requests/15/diff",
+                target(Provider::Bitbucket, "https://bitbucket.example.com", "KEY/service", "15"),
+            ),
+            (
+                "https://code.example.com/bitbucket/projects/KEY/repos/service/pull-requests/16",
+                target(Provider::Bitbucket, "https://code.example.com", "KEY/service", "16"),
+            ),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(parse_review_url(url), Ok(expected), "{}", url);
+        }
+
+        for url in [
+            "",
+            "https://github.com/owner/repo",
+            "https://github.com/owner/repo/issues/5",
+            "https://github.com/owner/repo/pull/abc",
+            "https://gitlab.com/group/project/-/issues/3",
+            "https://bitbucket.org/workspace/repo/src/main",
+        ] {
+            assert!(parse_review_url(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn review_keys_ignore_the_url_form() {
+        let key = |url: &str| parse_review_url(url).unwrap().key();
+        assert_eq!(key("https://github.com/Owner/Repo/pull/5"), "owner/repo#5");
+        assert_eq!(key("github.com/owner/repo/pull/5/files"), "owner/repo#5");
+        assert_eq!(key("https://gitlab.com/a/b/-/merge_requests/9"), "gitlab.com/a/b!9");
+        assert_eq!(key("https://GitLab.com/a/b/merge_requests/9"), "gitlab.com/a/b!9");
+        assert_eq!(key("https://bitbucket.org/w/r/pull-requests/2"), "bitbucket.org/w/r#2");
+    }
+
+    #[test]
+    fn remotes_map_to_providers() {
+        let cases = [
+            ("git@github.com:owner/repo.git", Some(("github.com", "owner/repo")), Provider::Github),
+            ("https://gitlab.example.com/group/sub/project.git", Some(("gitlab.example.com", "group/sub/project")), Provider::Gitlab),
+            ("ssh://git@bitbucket.org:7999/ws/repo.git", Some(("bitbucket.org", "ws/repo")), Provider::Bitbucket),
+            ("https://ghe.corp.example/org/repo", Some(("ghe.corp.example", "org/repo")), Provider::Unknown),
+            ("/srv/git/repo.git", None, Provider::Unknown),
+            ("C:\\repos\\app", None, Provider::Unknown),
+            ("file:///srv/git/repo.git", None, Provider::Unknown),
+        ];
+        for (url, expected, provider) in cases {
+            let parsed = parse_remote_url(url);
+            assert_eq!(
+                parsed.as_ref().map(|(host, project)| (host.as_str(), project.as_str())),
+                expected,
+                "{}",
+                url
+            );
+            let found = parsed.map(|(host, _)| provider_for_host(&host)).unwrap_or(Provider::Unknown);
+            assert_eq!(found, provider, "{}", url);
+        }
+    }
+
+    #[test]
+    fn gitlab_merge_request_maps_to_the_review_shape() {
+        let mr: Value = serde_json::from_str(&fixture("forge/gitlab_mr.json")).unwrap();
+        let changes: Vec<Value> = serde_json::from_str(&fixture("forge/gitlab_changes.json")).unwrap();
+
+        let info = map_gitlab_mr(&mr, &changes, 3);
+        assert_eq!(info.title, "Draft: Cache compiled templates between renders");
+        assert_eq!(info.author, "mira");
+        assert_eq!(info.state, "MERGED");
+        assert_eq!((info.additions, info.deletions), (5, 2));
+        assert_eq!((info.changed_files, info.commits), (5, 3));
+        assert_eq!((info.base_ref_name.as_str(), info.head_ref_name.as_str()), ("main", "feature/template-cache"));
+        assert_eq!(info.head_sha, "8f4e2a91c0b7d35e6a1f09c2d4b8e7a6f5c3d2e1");
+        assert_eq!(info.created_at, "2025-02-11T09:14:52.118Z");
+
+        // Large merge requests report "1000+"; missing fields fall back to defaults
+        let capped = serde_json::json!({ "changes_count": "1000+", "state": "locked" });
+        let info = map_gitlab_mr(&capped, &[], 0);
+        assert_eq!((info.changed_files, info.state.as_str(), info.title.as_str()), (1000, "CLOSED", ""));
+        assert_eq!(map_gitlab_mr(&serde_json::json!({}), &changes, 0).state, "OPEN");
+        assert_eq!(map_gitlab_mr(&serde_json::json!({}), &changes, 0).changed_files, 5);
+    }
+
+    #[test]
+    fn gitlab_changes_become_a_git_diff() {
+        let changes: Vec<Value> = serde_json::from_str(&fixture("forge/gitlab_changes.json")).unwrap();
+        let diff = gitlab_unified_diff(&changes);
+        let files: Vec<&str> = diff.split("diff --git ").skip(1).collect();
+        assert_eq!(files.len(), 5);
+
+        assert!(files[0].starts_with("a/src/render.rs b/src/render.rs\n--- a/src/render.rs\n+++ b/src/render.rs\n@@ -10,7"));
+        assert!(files[1].starts_with("a/src/cache.rs b/src/cache.rs\nnew file mode 100644\n--- /dev/null\n+++ b/src/cache.rs\n"));
+        // A diff without a final newline gets one
+        assert!(files[1].ends_with("+impl Cache {}\n"));
+        assert!(files[2].contains("deleted file mode 100644\n--- a/src/legacy.rs\n+++ /dev/null\n"));
+        assert_eq!(
+            files[3],
+            "a/scripts/bench b/tools/bench\nold mode 100644\nnew mode 100755\nrename from scripts/bench\nrename to tools/bench\n"
+        );
+        assert_eq!(files[4], "a/assets/logo.png b/assets/logo.png\nBinary files a/assets/logo.png and b/assets/logo.png differ\n");
+    }
+}
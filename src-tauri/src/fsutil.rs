@@ -0,0 +1,182 @@
+// mensa - File System Utilities
+// Atomic writes, content hashing and Unicode-normalized path matching shared across commands
+
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+// ============================================================================
+// Hashing
+// ============================================================================
+
+/// Hex-encoded SHA-256 of some bytes
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Hash of a file's current content, or None when it doesn't exist
+pub fn file_hash(path: &Path) -> Result<Option<String>, String> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(sha256_hex(&bytes))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+// ============================================================================
+// Atomic Writes
+// ============================================================================
+
+/// Directory whose permissions decide whether a file can be created or replaced
+fn dir_of(path: &Path) -> &Path {
+    path.parent().unwrap_or(path)
+}
+
+/// `permissions::denied` for a permission error on `path`, else the given message
+fn denied_or(path: &Path, e: &std::io::Error, message: impl FnOnce() -> String) -> String {
+    match e.kind() {
+        std::io::ErrorKind::PermissionDenied => crate::permissions::denied(path),
+        _ => message(),
+    }
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.mensa-{}.tmp", name, uuid::Uuid::new_v4()))
+}
+
+/// Write content to a sibling temp file, so a later rename/link stays on one filesystem
+fn write_temp(path: &Path, content: &[u8]) -> std::io::Result<PathBuf> {
+    let tmp = temp_path_for(path);
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    Ok(tmp)
+}
+
+/// Atomically create a file, leaving any existing file untouched.
+/// Returns false when the file already existed.
+pub fn create_new_atomic(path: &Path, content: &[u8]) -> Result<bool, String> {
+    if path.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            denied_or(parent, &e, || format!("Failed to create directory {}: {}", parent.display(), e))
+        })?;
+    }
+
+    let tmp = write_temp(path, content)
+        .map_err(|e| denied_or(dir_of(path), &e, || format!("Failed to write {}: {}", path.display(), e)))?;
+    // hard_link fails if the destination appeared in the meantime, so nothing is clobbered
+    let linked = std::fs::hard_link(&tmp, path);
+    let _ = std::fs::remove_file(&tmp);
+    match linked {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(denied_or(dir_of(path), &e, || format!("Failed to create {}: {}", path.display(), e))),
+    }
+}
+
+/// Atomically create or replace a file's content
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            denied_or(parent, &e, || format!("Failed to create directory {}: {}", parent.display(), e))
+        })?;
+    }
+
+    let tmp = write_temp(path, content)
+        .map_err(|e| denied_or(dir_of(path), &e, || format!("Failed to write {}: {}", path.display(), e)))?;
+    std::fs::rename(&tmp, path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        denied_or(dir_of(path), &e, || format!("Failed to replace {}: {}", path.display(), e))
+    })
+}
+
+/// Atomically write a file only if its current content still hashes to `expected_hash`
+/// (None meaning the file must not exist). Guards against clobbering concurrent edits.
+pub fn write_checked(path: &Path, content: &[u8], expected_hash: Option<&str>) -> Result<(), String> {
+    let current = file_hash(path)?;
+    if current.as_deref() != expected_hash {
+        return Err(format!(
+            "Conflict: {} changed since it was last read",
+            path.display()
+        ));
+    }
+    write_atomic(path, content)
+}
+
+// ============================================================================
+// Unicode Paths
+// ============================================================================
+
+/// NFC form of a string. macOS file systems and git may hand back decomposed
+/// names (e + combining accent) for what the user typed as one code point.
+pub fn nfc(text: &str) -> String {
+    if is_nfc(text) {
+        text.to_string()
+    } else {
+        text.nfc().collect()
+    }
+}
+
+/// Compare two paths ignoring composed vs decomposed Unicode differences
+pub fn same_path_nfc(a: &Path, b: &Path) -> bool {
+    a == b || nfc(&a.to_string_lossy()) == nfc(&b.to_string_lossy())
+}
+
+/// Name of the entry in `dir` that matches `name` up to Unicode normalization,
+/// in the form it is actually stored on disk
+pub fn find_entry_nfc(dir: &Path, name: &str) -> Option<OsString> {
+    let wanted = nfc(name);
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name())
+        .find(|candidate| nfc(&candidate.to_string_lossy()) == wanted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSED: &str = "caf\u{e9}/r\u{e9}sum\u{e9}.txt";
+    const DECOMPOSED: &str = "cafe\u{301}/re\u{301}sume\u{301}.txt";
+
+    #[test]
+    fn nfc_composes_and_leaves_composed_text_alone() {
+        assert_eq!(nfc(DECOMPOSED), COMPOSED);
+        assert_eq!(nfc(COMPOSED), COMPOSED);
+        assert_eq!(nfc("plain/ascii.txt"), "plain/ascii.txt");
+        // Hangul jamo compose into one syllable
+        assert_eq!(nfc("\u{1100}\u{1161}"), "\u{ac00}");
+    }
+
+    #[test]
+    fn paths_compare_across_normalization_forms() {
+        assert!(same_path_nfc(Path::new(COMPOSED), Path::new(DECOMPOSED)));
+        assert!(same_path_nfc(Path::new(DECOMPOSED), Path::new(DECOMPOSED)));
+        assert!(!same_path_nfc(Path::new(COMPOSED), Path::new("cafe/resume.txt")));
+    }
+
+    // macOS file systems look names up normalization-insensitively, so both spellings exist
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn entry_is_found_in_its_stored_form() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("re\u{301}sume\u{301}.txt"), "").unwrap();
+        let found = find_entry_nfc(dir.path(), "r\u{e9}sum\u{e9}.txt").unwrap();
+        assert_eq!(found, OsString::from("re\u{301}sume\u{301}.txt"));
+        assert!(find_entry_nfc(dir.path(), "resume.txt").is_none());
+        assert!(find_entry_nfc(&dir.path().join("missing"), "r\u{e9}sum\u{e9}.txt").is_none());
+    }
+}
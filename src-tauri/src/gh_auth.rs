@@ -0,0 +1,280 @@
+// mensa - GitHub Auth Module
+// Who gh is logged in as, with which token scopes, and whether that's enough for a feature
+
+use crate::git::{run_external, GitState};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// How long an auth status read is trusted (gh calls failing with 401/403 drop it sooner)
+const AUTH_CACHE_TTL: Duration = Duration::from_secs(600);
+
+/// Scopes each feature needs, by feature name
+const FEATURE_SCOPES: &[(&str, &[&str])] = &[
+    ("post_review", &["repo"]),
+    // Merging a PR that touches .github/workflows also needs `workflow`
+    ("merge_pr", &["repo", "workflow"]),
+    ("read_checks", &["repo"]),
+    ("manage_labels", &["repo"]),
+];
+
+/// Scopes implied by a broader one GitHub grants
+const IMPLIED_SCOPES: &[(&str, &[&str])] = &[
+    ("repo", &["public_repo", "repo:status", "repo_deployment", "repo:invite", "security_events"]),
+    ("admin:org", &["write:org", "read:org"]),
+    ("write:org", &["read:org"]),
+    ("admin:repo_hook", &["write:repo_hook", "read:repo_hook"]),
+    ("write:repo_hook", &["read:repo_hook"]),
+    ("user", &["read:user", "user:email", "user:follow"]),
+    ("write:packages", &["read:packages"]),
+];
+
+/// One host gh has an account for
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GhHostAuth {
+    pub host: String,
+    pub user: Option<String>,
+    /// The account gh uses for this host
+    pub active: bool,
+    /// False when gh reports the token as invalid
+    pub logged_in: bool,
+    /// None when gh can't tell (fine-grained tokens, GH_TOKEN without scope headers)
+    pub scopes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GhAuthStatus {
+    pub installed: bool,
+    /// Logged in with a valid token on at least one host
+    pub authenticated: bool,
+    pub hosts: Vec<GhHostAuth>,
+}
+
+/// Result of checking a feature's scopes against the active github.com account
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GhPreflight {
+    pub feature: String,
+    pub ok: bool,
+    pub required_scopes: Vec<String>,
+    pub missing_scopes: Vec<String>,
+    /// Command that fixes it, e.g. `gh auth refresh -h github.com -s repo,workflow`
+    pub fix_command: Option<String>,
+    /// Why the feature is unavailable (or why scopes couldn't be checked), for a tooltip
+    pub reason: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Split a scope list as gh prints it: `'repo', 'read:org'` or `repo, read:org`
+fn parse_scopes(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|s| s.trim().trim_matches('\'').trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parse `gh auth status`: a line per host, then indented detail lines. Handles both the
+/// current layout ("Logged in to github.com account octocat", "- Active account: true") and
+/// the older one ("Logged in to github.com as octocat").
+fn parse_auth_status(output: &str) -> Vec<GhHostAuth> {
+    let mut hosts: Vec<GhHostAuth> = Vec::new();
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            hosts.push(GhHostAuth {
+                host: line.trim().to_string(),
+                user: None,
+                active: true,
+                logged_in: false,
+                scopes: None,
+            });
+            continue;
+        }
+        let Some(host) = hosts.last_mut() else {
+            continue;
+        };
+        let detail = line.trim().trim_start_matches(['✓', 'X', '!', '-', '*']).trim();
+        if let Some(rest) = detail
+            .strip_prefix("Logged in to ")
+            .or_else(|| detail.strip_prefix("Failed to log in to "))
+        {
+            // A second account on the same host starts a new entry
+            if host.user.is_some() {
+                let name = host.host.clone();
+                hosts.push(GhHostAuth {
+                    host: name,
+                    user: None,
+                    active: false,
+                    logged_in: false,
+                    scopes: None,
+                });
+            }
+            let Some(host) = hosts.last_mut() else {
+                continue;
+            };
+            host.logged_in = detail.starts_with("Logged in");
+            let user = rest
+                .split_once(" account ")
+                .or_else(|| rest.split_once(" as "))
+                .map(|(_, user)| user.split_whitespace().next().unwrap_or_default().to_string());
+            host.user = user.filter(|u| !u.is_empty());
+        } else if let Some(active) = detail.strip_prefix("Active account:") {
+            host.active = active.trim() == "true";
+        } else if let Some(scopes) = detail.strip_prefix("Token scopes:") {
+            host.scopes = Some(parse_scopes(scopes));
+        } else if detail.contains("token") && detail.contains("invalid") {
+            host.logged_in = false;
+        }
+    }
+    hosts
+}
+
+/// Scopes from the `X-OAuth-Scopes` header of `gh api -i` output
+fn scopes_from_headers(output: &str) -> Option<Vec<String>> {
+    output
+        .lines()
+        .take_while(|line| !line.trim().is_empty())
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim().eq_ignore_ascii_case("x-oauth-scopes").then(|| parse_scopes(value))
+        })
+}
+
+async fn read_auth_status(state: &GitState) -> GhAuthStatus {
+    let output = match run_external(state, "gh", &["auth", "status"], None, &[], None, None).await {
+        Ok(output) => output,
+        Err(_) => {
+            return GhAuthStatus {
+                installed: false,
+                authenticated: false,
+                hosts: Vec::new(),
+            }
+        }
+    };
+    // gh has printed the status to stderr or stdout depending on the version
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let mut hosts = parse_auth_status(&text);
+
+    // Tokens from the environment don't list scopes; the API's response headers do
+    for host in hosts.iter_mut().filter(|h| h.active && h.logged_in && h.scopes.is_none()) {
+        let args = ["api", "-i", "--hostname", host.host.as_str(), "user"];
+        if let Ok(output) = run_external(state, "gh", &args, None, &[], None, None).await {
+            if output.status.success() {
+                host.scopes = scopes_from_headers(&String::from_utf8_lossy(&output.stdout));
+            }
+        }
+    }
+
+    GhAuthStatus {
+        installed: true,
+        authenticated: output.status.success() && hosts.iter().any(|h| h.logged_in),
+        hosts,
+    }
+}
+
+/// Auth status, from the cache while it's fresh
+pub async fn auth_status(state: &GitState, refresh: bool) -> GhAuthStatus {
+    if !refresh {
+        if let Some((at, status)) = state.gh_auth_cache.lock().await.as_ref() {
+            if at.elapsed() < AUTH_CACHE_TTL {
+                return status.clone();
+            }
+        }
+    }
+    let status = read_auth_status(state).await;
+    *state.gh_auth_cache.lock().await = Some((Instant::now(), status.clone()));
+    status
+}
+
+/// Whether gh stderr reports an authentication or permission failure
+pub fn is_auth_failure(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr);
+    stderr.contains("HTTP 401") || stderr.contains("HTTP 403") || stderr.contains("Bad credentials")
+}
+
+fn has_scope(granted: &[String], required: &str) -> bool {
+    granted.iter().any(|scope| {
+        scope == required
+            || IMPLIED_SCOPES
+                .iter()
+                .any(|(broad, implied)| broad == scope && implied.contains(&required))
+    })
+}
+
+fn preflight(feature: &str, status: &GhAuthStatus) -> Result<GhPreflight, String> {
+    let required = FEATURE_SCOPES
+        .iter()
+        .find(|(name, _)| *name == feature)
+        .map(|(_, scopes)| scopes.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        .ok_or_else(|| format!("Unknown GitHub feature: {}", feature))?;
+    let mut result = GhPreflight {
+        feature: feature.to_string(),
+        ok: false,
+        required_scopes: required.clone(),
+        missing_scopes: Vec::new(),
+        fix_command: None,
+        reason: None,
+    };
+
+    if !status.installed {
+        result.reason = Some("GitHub CLI (gh) is not installed".to_string());
+        return Ok(result);
+    }
+    let account = status
+        .hosts
+        .iter()
+        .find(|h| h.host == "github.com" && h.active && h.logged_in)
+        .or_else(|| status.hosts.iter().find(|h| h.active && h.logged_in));
+    let Some(account) = account else {
+        result.reason = Some("Not logged in to GitHub".to_string());
+        result.fix_command = Some("gh auth login".to_string());
+        return Ok(result);
+    };
+    let Some(ref granted) = account.scopes else {
+        // Fine-grained tokens don't report scopes; GitHub decides when the request is made
+        result.ok = true;
+        result.reason = Some("Token scopes couldn't be read; permissions are checked when the request is made".to_string());
+        return Ok(result);
+    };
+
+    result.missing_scopes = required.into_iter().filter(|s| !has_scope(granted, s)).collect();
+    result.ok = result.missing_scopes.is_empty();
+    if !result.ok {
+        let missing = result.missing_scopes.join(",");
+        result.reason = Some(format!("The GitHub token is missing the {} scope(s)", missing));
+        result.fix_command = Some(format!("gh auth refresh -h {} -s {}", account.host, missing));
+    }
+    Ok(result)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// The gh accounts, hosts and token scopes; `refresh` skips the cache
+#[tauri::command]
+pub async fn get_gh_auth_status(state: State<'_, GitState>, refresh: Option<bool>) -> Result<GhAuthStatus, String> {
+    Ok(auth_status(&state, refresh.unwrap_or(false)).await)
+}
+
+/// Whether the gh token can do `feature` (post_review, merge_pr, read_checks, manage_labels),
+/// with the missing scopes and the command that adds them
+#[tauri::command]
+pub async fn preflight_gh_feature(state: State<'_, GitState>, feature: String) -> Result<GhPreflight, String> {
+    preflight(&feature, &auth_status(&state, false).await)
+}
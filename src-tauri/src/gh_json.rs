@@ -0,0 +1,313 @@
+// mensa - gh JSON Module
+// Tolerant parsing of `gh ... --json` output: known shape changes are absorbed, any other
+// drift falls back visibly (schema warnings) instead of turning into silent zeros
+
+use crate::git::{GhPRInfo, GhPRListItem};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Characters of an unexpected value quoted in its warning
+const WARNING_VALUE_CHARS: usize = 80;
+
+/// Login GitHub shows for the author of a deleted account
+const GHOST_LOGIN: &str = "ghost";
+
+/// One field as gh sent it. Anything that isn't the expected type is kept for the warning
+/// rather than failing the whole response.
+#[derive(Debug, Default)]
+pub enum Field<T> {
+    #[default]
+    Missing,
+    Null,
+    Expected(T),
+    Unexpected(Value),
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Field<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        if value.is_null() {
+            return Ok(Field::Null);
+        }
+        Ok(match serde_json::from_value::<T>(value.clone()) {
+            Ok(parsed) => Field::Expected(parsed),
+            Err(_) => Field::Unexpected(value),
+        })
+    }
+}
+
+/// `commits`: a count (older gh), a connection with `totalCount`, or the commits themselves
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum CommitCount {
+    Count(u64),
+    Connection {
+        #[serde(rename = "totalCount")]
+        total_count: u64,
+    },
+    List(Vec<Value>),
+}
+
+/// `author`: an actor object or a bare login; null when the account was deleted
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Actor {
+    Object { login: String },
+    Login(String),
+}
+
+/// `gh pr view --json` holding `git::PR_INFO_FIELDS`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PrView {
+    title: Field<String>,
+    body: Field<String>,
+    author: Field<Actor>,
+    state: Field<String>,
+    additions: Field<u32>,
+    deletions: Field<u32>,
+    changed_files: Field<u32>,
+    commits: Field<CommitCount>,
+    base_ref_name: Field<String>,
+    head_ref_name: Field<String>,
+    head_ref_oid: Field<String>,
+    created_at: Field<String>,
+    updated_at: Field<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// One entry of `gh pr list --json` with `git::PR_LIST_FIELDS`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PrListEntry {
+    number: Field<u32>,
+    title: Field<String>,
+    author: Field<Actor>,
+    state: Field<String>,
+    head_ref_name: Field<String>,
+    base_ref_name: Field<String>,
+    created_at: Field<String>,
+    updated_at: Field<String>,
+    url: Field<String>,
+    is_draft: Field<bool>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// Fallbacks taken while reading one response
+#[derive(Debug, Default)]
+struct SchemaWarnings(Vec<String>);
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn describe(value: &Value) -> String {
+    let kind = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    };
+    let text = value.to_string();
+    match text.char_indices().nth(WARNING_VALUE_CHARS) {
+        Some((cut, _)) => format!("{} {}…", kind, &text[..cut]),
+        None => format!("{} {}", kind, text),
+    }
+}
+
+impl SchemaWarnings {
+    /// The field's value, or `fallback` with a warning naming what gh sent instead
+    fn take<T>(&mut self, name: &str, field: Field<T>, fallback: T) -> T {
+        match field {
+            Field::Expected(value) => return value,
+            Field::Missing => self.0.push(format!("{} is missing", name)),
+            Field::Null => self.0.push(format!("{} is null", name)),
+            Field::Unexpected(value) => self.0.push(format!("{} has an unexpected {}", name, describe(&value))),
+        }
+        fallback
+    }
+
+    fn string(&mut self, name: &str, field: Field<String>) -> String {
+        self.take(name, field, String::new())
+    }
+
+    /// A null author is a deleted account, shown as GitHub shows it
+    fn author(&mut self, field: Field<Actor>) -> String {
+        match field {
+            Field::Null => GHOST_LOGIN.to_string(),
+            field => match self.take("author", field, Actor::Login(String::new())) {
+                Actor::Object { login } | Actor::Login(login) => login,
+            },
+        }
+    }
+
+    fn commits(&mut self, field: Field<CommitCount>) -> u32 {
+        match self.take("commits", field, CommitCount::Count(0)) {
+            CommitCount::Count(count) | CommitCount::Connection { total_count: count } => count as u32,
+            CommitCount::List(commits) => commits.len() as u32,
+        }
+    }
+
+    /// Log the warnings of a response once, then hand them over for the UI
+    fn finish(self, context: &str) -> Vec<String> {
+        if !self.0.is_empty() {
+            eprintln!("[mensa] {} output changed shape: {}", context, self.0.join("; "));
+        }
+        self.0
+    }
+}
+
+/// `GhPRInfo` from `gh pr view --json` output holding PR_INFO_FIELDS; fields it doesn't read
+/// are kept in `extra`
+pub fn pr_info(json: Value) -> Result<GhPRInfo, String> {
+    let view: PrView = serde_json::from_value(json).map_err(|e| format!("Failed to parse PR info JSON: {}", e))?;
+    let mut warnings = SchemaWarnings::default();
+    let info = GhPRInfo {
+        title: warnings.string("title", view.title),
+        body: warnings.string("body", view.body),
+        author: warnings.author(view.author),
+        state: warnings.take("state", view.state, "OPEN".to_string()),
+        additions: warnings.take("additions", view.additions, 0),
+        deletions: warnings.take("deletions", view.deletions, 0),
+        changed_files: warnings.take("changedFiles", view.changed_files, 0),
+        commits: warnings.commits(view.commits),
+        base_ref_name: warnings.string("baseRefName", view.base_ref_name),
+        head_ref_name: warnings.string("headRefName", view.head_ref_name),
+        created_at: warnings.string("createdAt", view.created_at),
+        updated_at: warnings.string("updatedAt", view.updated_at),
+        head_sha: warnings.string("headRefOid", view.head_ref_oid),
+        body_html: None,
+        schema_warnings: Vec::new(),
+        extra: view.extra,
+    };
+    Ok(GhPRInfo {
+        schema_warnings: warnings.finish("gh pr view"),
+        ..info
+    })
+}
+
+/// `GhPRListItem`s from `gh pr list --json` output holding PR_LIST_FIELDS
+pub fn pr_list(json: Value) -> Result<Vec<GhPRListItem>, String> {
+    let entries: Vec<PrListEntry> =
+        serde_json::from_value(json).map_err(|e| format!("Failed to parse PR list JSON: {}", e))?;
+    Ok(entries
+        .into_iter()
+        .map(|pr| {
+            let mut warnings = SchemaWarnings::default();
+            let item = GhPRListItem {
+                number: warnings.take("number", pr.number, 0),
+                title: warnings.string("title", pr.title),
+                author: warnings.author(pr.author),
+                state: warnings.take("state", pr.state, "OPEN".to_string()),
+                head_ref_name: warnings.string("headRefName", pr.head_ref_name),
+                base_ref_name: warnings.string("baseRefName", pr.base_ref_name),
+                created_at: warnings.string("createdAt", pr.created_at),
+                updated_at: warnings.string("updatedAt", pr.updated_at),
+                url: warnings.string("url", pr.url),
+                is_draft: warnings.take("isDraft", pr.is_draft, false),
+                schema_warnings: Vec::new(),
+                extra: pr.extra,
+            };
+            GhPRListItem {
+                schema_warnings: warnings.finish(&format!("gh pr list (#{})", item.number)),
+                ..item
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn json(relative: &str) -> Value {
+        serde_json::from_str(&fixture(relative)).unwrap()
+    }
+
+    #[test]
+    fn pr_view_from_two_gh_versions_reads_the_same() {
+        for version in ["gh-2.14", "gh-2.40"] {
+            let info = pr_info(json(&format!("gh/pr_view.{}.json", version))).unwrap();
+            assert!(info.schema_warnings.is_empty(), "{}: {:?}", version, info.schema_warnings);
+            assert_eq!((info.title.as_str(), info.author.as_str(), info.state.as_str()), ("Retry failed syncs", "octocat", "OPEN"), "{}", version);
+            // commits is a connection in the older output and the commits themselves in the newer
+            assert_eq!((info.additions, info.deletions, info.changed_files, info.commits), (120, 35, 4, 3), "{}", version);
+            assert_eq!((info.base_ref_name.as_str(), info.head_ref_name.as_str()), ("main", "feature/retry"), "{}", version);
+            assert_eq!(info.head_sha, "9fceb02d0ae598e95dc970b74767f19372d61af8", "{}", version);
+        }
+
+        // Fields nobody asked for are kept, not dropped
+        let newer = pr_info(json("gh/pr_view.gh-2.40.json")).unwrap();
+        assert_eq!(newer.extra.get("mergeStateStatus"), Some(&Value::from("CLEAN")));
+        assert!(pr_info(json("gh/pr_view.gh-2.14.json")).unwrap().extra.is_empty());
+    }
+
+    #[test]
+    fn pr_list_from_two_gh_versions_reads_the_same() {
+        for version in ["gh-2.14", "gh-2.40"] {
+            let prs = pr_list(json(&format!("gh/pr_list.{}.json", version))).unwrap();
+            assert_eq!(prs.len(), 2, "{}", version);
+            assert!(prs.iter().all(|pr| pr.schema_warnings.is_empty()), "{}", version);
+            assert_eq!((prs[0].number, prs[0].author.as_str(), prs[0].is_draft), (42, "octocat", false), "{}", version);
+            assert_eq!(prs[0].url, "https://github.com/acme/sync/pull/42", "{}", version);
+            assert!(prs[1].is_draft, "{}", version);
+        }
+
+        // A null author is a deleted account: shown as GitHub shows it, not as drift
+        let newer = pr_list(json("gh/pr_list.gh-2.40.json")).unwrap();
+        assert_eq!(newer[1].author, GHOST_LOGIN);
+    }
+
+    #[test]
+    fn mutated_pr_view_warns_instead_of_reading_zeros() {
+        let info = pr_info(json("gh/pr_view.mutated.json")).unwrap();
+        assert_eq!(
+            info.schema_warnings,
+            [
+                "body is null",
+                "state has an unexpected number 1",
+                "additions has an unexpected string \"120\"",
+                "changedFiles is missing",
+                "commits has an unexpected object {\"nodes\":[]}",
+            ]
+        );
+        // The fallbacks are still taken, but now they're flagged; what did parse is intact
+        assert_eq!((info.additions, info.changed_files, info.commits, info.deletions), (0, 0, 0, 35));
+        assert_eq!((info.state.as_str(), info.author.as_str()), ("OPEN", GHOST_LOGIN));
+        assert_eq!(info.title, "Retry failed syncs");
+    }
+
+    #[test]
+    fn mutated_pr_list_warns_per_entry() {
+        let prs = pr_list(json("gh/pr_list.mutated.json")).unwrap();
+        assert_eq!(
+            prs[0].schema_warnings,
+            [
+                "number has an unexpected string \"42\"",
+                "url is missing",
+                "isDraft has an unexpected string \"false\"",
+            ]
+        );
+        assert_eq!((prs[0].number, prs[0].url.as_str(), prs[0].is_draft), (0, "", false));
+        assert!(prs[1].schema_warnings.is_empty());
+        assert_eq!(prs[1].number, 43);
+
+        // Long unexpected values are cut short in the warning
+        let long = serde_json::json!([{ "title": ["x".repeat(200)] }]);
+        let warning = pr_list(long).unwrap()[0].schema_warnings[1].clone();
+        assert!(warning.starts_with("title has an unexpected array [\"xxx") && warning.ends_with('…'), "{}", warning);
+        assert!(warning.chars().count() < 120, "{}", warning);
+
+        assert!(pr_list(serde_json::json!({ "number": 1 })).is_err());
+    }
+}
@@ -1,12 +1,24 @@
 // mensa - Git Integration Module
 // Provides Tauri commands for Git operations using git2
 
-use git2::{BranchType, DiffOptions, Repository, Signature, StatusOptions};
+use crate::pagination::{self, CursorScope, Page, PageError};
+use crate::progress::ProgressReporter;
+use crate::{diff_attributes, forge, fsutil, gh_json, status_filters};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use git2::{BranchType, Delta, Diff, DiffFindOptions, DiffOptions, Patch, Repository, Signature, StatusOptions};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
-use std::process::Stdio;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, State};
+use tokio::io::AsyncReadExt;
 use tokio::process::Command;
+use tokio::sync::{oneshot, Mutex};
 
 // ============================================================================
 // Data Types
@@ -19,6 +31,9 @@ pub struct GitFile {
     pub status: String, // "added" | "modified" | "deleted" | "renamed" | "untracked"
     #[serde(skip_serializing_if = "Option::is_none")]
     pub old_path: Option<String>,
+    /// Base64 of the raw path bytes, only set when the path isn't valid UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_b64: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +48,46 @@ pub struct GitStatus {
     pub modified: Vec<GitFile>,
     pub untracked: Vec<GitFile>,
     pub deleted: Vec<GitFile>,
+    /// One row per changed file with its index and worktree state; supersedes the four
+    /// lists above, which are kept for older callers
+    #[serde(default)]
+    pub files: Vec<GitFileEx>,
+    /// Files hidden by the workspace's status filters, still listed so nothing is lost
+    #[serde(default)]
+    pub filtered: Vec<GitFile>,
+    /// Age of a reused result: set when a call inside the minimum refresh interval got
+    /// the previous status instead of a new walk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_ms: Option<u64>,
+}
+
+/// A changed file's staged and unstaged state together. Renames (staged or only in the
+/// worktree) are one row at the new path with `old_path` set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitFileEx {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    /// "added" | "modified" | "deleted" | "renamed" | "typechange", None if nothing is staged
+    pub index_status: Option<String>,
+    /// "untracked" | "modified" | "deleted" | "renamed" | "typechange", None if the worktree matches the index
+    pub worktree_status: Option<String>,
+    #[serde(default)]
+    pub conflicted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_b64: Option<String>,
+}
+
+/// File counts only, for callers that don't need the full status lists
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusSummary {
+    pub staged: u32,
+    pub modified: u32,
+    pub untracked: u32,
+    pub deleted: u32,
+    pub conflicted: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,9 +98,36 @@ pub struct BranchInfo {
     pub upstream: Option<String>,
     pub ahead: u32,
     pub behind: u32,
+    /// An upstream is configured but its remote-tracking ref no longer exists
+    #[serde(default)]
+    pub upstream_gone: bool,
     pub recent_branches: Vec<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchListItem {
+    pub name: String,
+    pub is_current: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    /// An upstream is configured but its remote-tracking ref no longer exists
+    #[serde(default)]
+    pub upstream_gone: bool,
+    pub last_commit_time: i64,
+    pub last_commit_summary: String,
+}
+
+/// Branch list response: plain names for the fast path, full items otherwise
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum BranchList {
+    Names(Vec<String>),
+    Detailed(Vec<BranchListItem>),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GitCommit {
@@ -60,6 +142,33 @@ pub struct GitCommit {
     pub deletions: u32,
 }
 
+/// Commits listed by git_log, with whether a shallow clone cut the history short
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitLog {
+    pub commits: Vec<GitCommit>,
+    pub is_shallow: bool,
+    /// The walk reached a commit whose parents weren't fetched
+    pub shallow_boundary_reached: bool,
+}
+
+/// A page of `git_log_paged`, with the shallow-clone flags of `GitLog`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitLogPage {
+    #[serde(flatten)]
+    pub page: Page<GitCommit>,
+    pub is_shallow: bool,
+    pub shallow_boundary_reached: bool,
+}
+
+/// Where a `git_log_paged` walk stands: the commit it started from and how many it has listed
+#[derive(Debug, Serialize, Deserialize)]
+struct LogPosition {
+    tip: String,
+    skip: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PRCreationOptions {
@@ -90,6 +199,17 @@ pub struct GhPRInfo {
     pub head_ref_name: String,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(default)]
+    pub head_sha: String,
+    /// Sanitized HTML of the body, only when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_html: Option<String>,
+    /// Fields gh sent in an unexpected shape (or not at all) and that fell back to a default
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schema_warnings: Vec<String>,
+    /// Top-level fields of the gh output that nothing reads, kept for debugging
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,873 +225,4348 @@ pub struct GhPRListItem {
     pub updated_at: String,
     pub url: String,
     pub is_draft: bool,
+    /// Fields gh sent in an unexpected shape (or not at all) and that fell back to a default
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schema_warnings: Vec<String>,
+    /// Top-level fields of the gh output that nothing reads, kept for debugging
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-// ============================================================================
-// Helper Functions
-// ============================================================================
-
-fn open_repo(working_dir: &str) -> Result<Repository, String> {
-    Repository::open(working_dir).map_err(|e| format!("Failed to open repository: {}", e))
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebasePlanEntry {
+    pub hash: String,
+    pub short_hash: String,
+    pub summary: String,
+    pub message: String,
+    pub author: String,
+    pub timestamp: i64,
+    pub action: String, // "pick" | "squash" | "fixup" | "reword" | "drop"
 }
 
-fn get_branch_ahead_behind(repo: &Repository) -> (u32, u32) {
-    let head = match repo.head() {
-        Ok(h) => h,
-        Err(_) => return (0, 0),
-    };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebasePlanStep {
+    pub hash: String,
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_message: Option<String>,
+}
 
-    let local_oid = match head.target() {
-        Some(oid) => oid,
-        None => return (0, 0),
-    };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebaseResult {
+    pub status: String, // "completed" | "conflicts"
+    pub conflicts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub head: Option<String>,
+}
 
-    // Try to get upstream branch
-    let branch_name = match head.shorthand() {
-        Some(name) => name,
-        None => return (0, 0),
-    };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffFileStat {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    pub status: String, // "added" | "modified" | "deleted" | "renamed" | "copied" | "typechange"
+    pub additions: u32,
+    pub deletions: u32,
+    pub binary: bool,
+    /// Only reported for binary files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_size_bytes: Option<u64>,
+    /// Previous names of the two above
+    #[cfg(feature = "legacy_fields")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old_size: Option<u64>,
+    #[cfg(feature = "legacy_fields")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new_size: Option<u64>,
+    /// The only difference is CRLF vs LF (e.g. an autocrlf checkout)
+    pub line_endings_only: bool,
+    /// Marked linguist-generated in .gitattributes
+    #[serde(default)]
+    pub generated: bool,
+    /// Marked linguist-vendored in .gitattributes
+    #[serde(default)]
+    pub vendored: bool,
+    /// Marked -diff: shown as binary, stats only
+    #[serde(default)]
+    pub diff_suppressed: bool,
+    /// The diff=<driver> attribute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff_driver: Option<String>,
+}
 
-    let branch = match repo.find_branch(branch_name, BranchType::Local) {
-        Ok(b) => b,
-        Err(_) => return (0, 0),
-    };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffStats {
+    pub files: Vec<DiffFileStat>,
+    /// Totals over `files` only
+    pub additions: u32,
+    pub deletions: u32,
+    /// Files hidden by the workspace's status filters
+    #[serde(default)]
+    pub filtered: Vec<DiffFileStat>,
+}
 
-    let upstream = match branch.upstream() {
-        Ok(u) => u,
-        Err(_) => return (0, 0),
-    };
+/// Effective line-ending attributes for one path ("set", "unset", "unspecified" or a value)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathAttributes {
+    pub path: String,
+    pub text: String,
+    pub eol: String,
+    pub crlf: String,
+}
 
-    let upstream_oid = match upstream.get().target() {
-        Some(oid) => oid,
-        None => return (0, 0),
-    };
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineEndingReport {
+    pub core_autocrlf: Option<String>,
+    pub core_eol: Option<String>,
+    pub files: Vec<PathAttributes>,
+}
 
-    match repo.graph_ahead_behind(local_oid, upstream_oid) {
-        Ok((ahead, behind)) => (ahead as u32, behind as u32),
-        Err(_) => (0, 0),
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrecommitWarning {
+    pub path: String,
+    pub kind: String, // "sensitive_file"
+    pub message: String,
 }
 
-// ============================================================================
-// Tauri Commands
-// ============================================================================
+/// Cancellable external git/gh processes, keyed by operation id
+#[derive(Default)]
+pub struct GitState {
+    pub operations: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+    /// Branch protection lookups keyed by "owner/repo#branch"
+    pub protection_cache: Arc<Mutex<HashMap<String, (Instant, BranchProtection)>>>,
+    /// PR info keyed by "owner/repo#number"
+    pub pr_info_cache: Arc<Mutex<HashMap<String, (Instant, GhPRInfo)>>>,
+    /// Commits and diff stats for PR drafting keyed by "workdir|head sha|base sha"
+    pub pr_context_cache: Arc<Mutex<HashMap<String, PrContextEntry>>>,
+    /// Compiled status filters keyed by canonical workspace path
+    pub status_filters: Arc<Mutex<HashMap<String, Arc<globset::GlobSet>>>>,
+    /// PR lists keyed by "workdir|state"
+    pub pr_list_cache: Arc<Mutex<HashMap<String, CachedPrList>>>,
+    /// Info and diffs fetched ahead by `fetch_pr_batch`, keyed like `pr_info_cache`; opening the PR uses them once
+    pub pr_prefetch: Arc<Mutex<HashMap<String, PrefetchedPr>>>,
+    /// gh accounts and token scopes, and when they were read
+    pub gh_auth_cache: Arc<Mutex<Option<(Instant, crate::gh_auth::GhAuthStatus)>>>,
+    /// The app's background task registry (shared with AppState), for output readers
+    pub tasks: crate::tasks::TaskRegistry,
+    /// Proxy variables for spawned commands (shared with AppState)
+    pub proxy: crate::proxy::ProxyEnv,
+    /// The stored GitHub token, handed to `gh` as GH_TOKEN
+    pub gh_token: crate::secrets::GhTokenSource,
+    /// Recent and in-flight `git_status` walks keyed by "canonical workdir|filters|threshold"
+    pub status_runs: Arc<Mutex<HashMap<String, StatusRun>>>,
+}
 
-/// Get the current git status of the repository
-#[tauri::command]
-pub async fn git_status(working_dir: String) -> Result<GitStatus, String> {
-    let repo = open_repo(&working_dir)?;
+/// A status walk shared by every caller that arrives while it runs
+type SharedStatus = Arc<tokio::sync::OnceCell<Result<GitStatus, String>>>;
 
-    // Get current branch name
-    let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
-    let branch = head
-        .shorthand()
-        .unwrap_or("HEAD")
-        .to_string();
+/// The last status computed for a repository and the walk currently running, if any
+#[derive(Default)]
+pub struct StatusRun {
+    last: Option<(Instant, GitStatus)>,
+    in_flight: Option<SharedStatus>,
+}
 
-    // Get upstream info
-    let upstream = if let Ok(local_branch) = repo.find_branch(&branch, BranchType::Local) {
-        local_branch
-            .upstream()
-            .ok()
-            .and_then(|u| u.name().ok().flatten().map(String::from))
-    } else {
-        None
-    };
+/// A PR list, when it was fetched and the --limit it was fetched with
+type CachedPrList = (Instant, usize, Vec<GhPRListItem>);
 
-    // Get ahead/behind counts
-    let (ahead, behind) = get_branch_ahead_behind(&repo);
+/// PRs `list_prs` returns
+pub const PR_LIST_LIMIT: usize = 50;
 
-    // Get file statuses
-    let mut opts = StatusOptions::new();
-    opts.include_untracked(true)
-        .recurse_untracked_dirs(true)
-        .include_ignored(false);
+/// Most PRs one `gh pr list` is asked for
+const PR_LIST_MAX: usize = 1000;
 
-    let statuses = repo
-        .statuses(Some(&mut opts))
-        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+/// Where a `list_prs_paged` walk stands: the lowest PR number listed and how many were
+#[derive(Debug, Serialize, Deserialize)]
+struct PrPosition {
+    before: u32,
+    seen: usize,
+}
 
-    let mut staged = Vec::new();
-    let mut modified = Vec::new();
-    let mut untracked = Vec::new();
-    let mut deleted = Vec::new();
+/// When a batch fetched a PR, and the info and diff it got that haven't been used yet
+pub type PrefetchedPr = (Instant, Option<GhPRInfo>, Option<String>);
 
-    for entry in statuses.iter() {
-        let path = entry.path().unwrap_or("").to_string();
-        let status = entry.status();
+/// Commits ahead of the base and the change summary for one head/base pair
+pub type PrContextEntry = (Vec<GitCommit>, DiffStats);
 
-        // Check index (staged) changes
-        if status.is_index_new() {
-            staged.push(GitFile {
-                path: path.clone(),
-                status: "added".to_string(),
-                old_path: None,
-            });
-        } else if status.is_index_modified() {
-            staged.push(GitFile {
-                path: path.clone(),
-                status: "modified".to_string(),
-                old_path: None,
-            });
-        } else if status.is_index_deleted() {
-            staged.push(GitFile {
-                path: path.clone(),
-                status: "deleted".to_string(),
-                old_path: None,
-            });
-        } else if status.is_index_renamed() {
-            staged.push(GitFile {
-                path: path.clone(),
-                status: "renamed".to_string(),
-                old_path: entry.head_to_index().and_then(|d| {
-                    d.old_file().path().map(|p| p.to_string_lossy().to_string())
-                }),
-            });
-        }
+/// Similarity (percent) above which a deleted file and a new one are reported as a rename
+pub const DEFAULT_RENAME_THRESHOLD: u16 = 50;
 
-        // Check working tree changes (not staged)
-        if status.is_wt_new() {
-            untracked.push(GitFile {
-                path: path.clone(),
-                status: "untracked".to_string(),
-                old_path: None,
-            });
-        } else if status.is_wt_modified() {
-            modified.push(GitFile {
-                path: path.clone(),
-                status: "modified".to_string(),
-                old_path: None,
-            });
-        } else if status.is_wt_deleted() {
-            deleted.push(GitFile {
-                path: path.clone(),
-                status: "deleted".to_string(),
-                old_path: None,
-            });
-        }
-    }
+/// How long a branch protection lookup is trusted
+const PROTECTION_CACHE_TTL: Duration = Duration::from_secs(300);
 
-    Ok(GitStatus {
-        branch,
-        upstream,
-        ahead,
-        behind,
-        staged,
-        modified,
-        untracked,
-        deleted,
-    })
-}
+/// How long fetched PR info is reused (head SHA checks, repeated views)
+const PR_INFO_CACHE_TTL: Duration = Duration::from_secs(60);
 
-/// Get the diff for a specific file or the entire working tree
-#[tauri::command]
-pub async fn git_diff(
-    working_dir: String,
-    file_path: Option<String>,
-    staged: bool,
-) -> Result<String, String> {
-    let repo = open_repo(&working_dir)?;
+/// How long a PR fetched by a batch opens without refetching
+const PR_PREFETCH_TTL: Duration = Duration::from_secs(15 * 60);
 
-    let mut opts = DiffOptions::new();
-    opts.context_lines(3);
+/// Fields of `gh pr view --json` that make up `GhPRInfo`
+pub const PR_INFO_FIELDS: &str =
+    "title,body,author,state,additions,deletions,changedFiles,commits,baseRefName,headRefName,headRefOid,createdAt,updatedAt";
 
-    if let Some(ref path) = file_path {
-        opts.pathspec(path);
-    }
+/// Fields of `gh pr list --json` that make up `GhPRListItem`
+pub const PR_LIST_FIELDS: &str = "number,title,author,state,headRefName,baseRefName,createdAt,updatedAt,url,isDraft";
 
-    let diff = if staged {
-        // Staged changes: compare HEAD to index
-        let head_tree = repo
-            .head()
-            .ok()
-            .and_then(|h| h.peel_to_tree().ok());
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchProtection {
+    pub branch: String,
+    pub status: String, // "protected" | "unprotected" | "insufficient_scope" | "unknown"
+    pub restricts_pushes: bool,
+    pub required_status_checks: Vec<String>,
+    pub required_review_count: u32,
+    pub enforce_admins: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
 
-        repo.diff_tree_to_index(head_tree.as_ref(), None, Some(&mut opts))
-            .map_err(|e| format!("Failed to get staged diff: {}", e))?
-    } else {
-        // Unstaged changes: compare index to working tree
-        repo.diff_index_to_workdir(None, Some(&mut opts))
-            .map_err(|e| format!("Failed to get diff: {}", e))?
-    };
+/// Error for commands that can refuse to act on a protected branch.
+/// Serialized as `{ kind, message, ... }` so the UI can offer the branch-off flow.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum GitCommandError {
+    ProtectedBranch {
+        branch: String,
+        message: String,
+        suggestion: String,
+    },
+    /// The repository can't be used (not a repo, bare, damaged, unsupported format)
+    RepoUnsupported {
+        reason: String,
+        message: String,
+    },
+    /// The operation needs history a shallow clone doesn't have
+    ShallowHistory {
+        message: String,
+        suggestion: String,
+    },
+    /// The branch tracks a remote branch that was deleted
+    UpstreamGone {
+        branch: String,
+        upstream: String,
+        message: String,
+        suggestion: String,
+    },
+    Failed {
+        message: String,
+    },
+}
 
-    let mut diff_str = String::new();
-    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        let prefix = match line.origin() {
-            '+' | '-' | ' ' => line.origin(),
-            _ => ' ',
-        };
-        if prefix != ' ' || !line.content().is_empty() {
-            if prefix != ' ' {
-                diff_str.push(prefix);
-            }
-            diff_str.push_str(&String::from_utf8_lossy(line.content()));
+impl From<String> for GitCommandError {
+    fn from(message: String) -> Self {
+        if let Some(reason) = message.strip_prefix(REPO_UNSUPPORTED_PREFIX) {
+            return GitCommandError::RepoUnsupported {
+                reason: reason.to_string(),
+                message,
+            };
         }
-        true
-    })
-    .map_err(|e| format!("Failed to print diff: {}", e))?;
-
-    Ok(diff_str)
+        if message.starts_with(SHALLOW_HISTORY_PREFIX) {
+            return GitCommandError::ShallowHistory {
+                message,
+                suggestion: "Fetch more history (git_fetch_deepen) and try again".to_string(),
+            };
+        }
+        GitCommandError::Failed { message }
+    }
 }
 
-/// Stage files for commit
-#[tauri::command]
-pub async fn git_stage(working_dir: String, paths: Vec<String>) -> Result<bool, String> {
-    let repo = open_repo(&working_dir)?;
-    let mut index = repo
-        .index()
-        .map_err(|e| format!("Failed to get index: {}", e))?;
+/// Every command fails with this prefix (then the reason) when the repository is unusable,
+/// so String errors stay recognisable and typed errors carry `RepoUnsupported`
+pub const REPO_UNSUPPORTED_PREFIX: &str = "Repository unsupported: ";
 
-    for path in &paths {
-        let file_path = Path::new(path);
+/// Prefix of errors caused by history missing from a shallow clone (typed as `ShallowHistory`)
+pub const SHALLOW_HISTORY_PREFIX: &str = "Shallow history: ";
 
-        // Check if file exists - if not, it might be a deletion
-        let full_path = Path::new(&working_dir).join(file_path);
-        if full_path.exists() {
-            index
-                .add_path(file_path)
-                .map_err(|e| format!("Failed to stage {}: {}", path, e))?;
-        } else {
-            // File was deleted, remove from index
-            index
-                .remove_path(file_path)
-                .map_err(|e| format!("Failed to stage deletion of {}: {}", path, e))?;
-        }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepoCapabilities {
+    pub is_repo: bool,
+    pub is_bare: bool,
+    /// "sha1" or "sha256" (None if it couldn't be read)
+    pub object_format: Option<String>,
+    pub has_commits: bool,
+    pub supports_status: bool,
+    /// A .git is present but libgit2 can't read it
+    pub damaged: bool,
+    pub failure_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl From<ExternalError> for GitCommandError {
+    fn from(err: ExternalError) -> Self {
+        GitCommandError::Failed { message: err.to_string() }
     }
+}
 
-    index
-        .write()
-        .map_err(|e| format!("Failed to write index: {}", e))?;
+/// Result of `git_commit`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitResult {
+    pub oid: String,
+    /// Who the commit was authored as
+    pub author: crate::identity::EffectiveIdentity,
+}
 
-    Ok(true)
+/// A file a destructive command overwrites or removes
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewFile {
+    pub path: String,
+    /// Set when `path` isn't valid UTF-8 or NFC; pass back as `raw_path`
+    pub raw_path: Option<String>,
+    /// Size of the content that is lost (0 for a file already missing)
+    pub bytes: u64,
+    /// The lost content is stored in git (e.g. it matches a committed or staged version)
+    pub recoverable: bool,
 }
 
-/// Unstage files
-#[tauri::command]
-pub async fn git_unstage(working_dir: String, paths: Vec<String>) -> Result<bool, String> {
-    let repo = open_repo(&working_dir)?;
+/// Exactly what a destructive command does (or, with `dry_run`, would do)
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DestructionPreview {
+    /// Working tree files replaced by their index or committed version
+    pub files_restored: Vec<PreviewFile>,
+    pub files_deleted: Vec<PreviewFile>,
+    pub branches_deleted: Vec<String>,
+    /// Commits no longer reachable from any branch afterwards
+    pub commits_orphaned: Vec<String>,
+    /// Hash of the plan; passing it back as `preview_token` refuses to run a plan that changed since
+    pub token: String,
+    /// False for a dry run
+    pub executed: bool,
+}
+
+/// Captured result of an external command that ran to completion
+pub struct ExternalOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ExternalError {
+    Spawn { command: String, error: String },
+    TimedOut { command: String, after: Duration },
+    Cancelled { command: String },
+}
+
+impl std::fmt::Display for ExternalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExternalError::Spawn { command, error } => write!(f, "Failed to execute {}: {}", command, error),
+            ExternalError::TimedOut { command, after } => {
+                write!(f, "{} timed out after {}s", command, after.as_secs())
+            }
+            ExternalError::Cancelled { command } => write!(f, "{} was cancelled", command),
+        }
+    }
+}
+
+impl From<ExternalError> for String {
+    fn from(err: ExternalError) -> String {
+        err.to_string()
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Open the repository, failing with `Repository unsupported: ...` (and the probe's reason)
+/// when it can't be opened, is bare, or its index can't be read. Nothing about a failure is
+/// remembered: a repository repaired or created since works on the next call.
+pub fn open_repo(working_dir: &str) -> Result<Repository, String> {
+    if let Ok(repo) = Repository::open(working_dir) {
+        // The index is loaded once per handle, so the commands reading it pay nothing extra
+        if !repo.is_bare() && repo.index().is_ok() {
+            return Ok(repo);
+        }
+    }
+    let caps = probe_repo(Path::new(working_dir));
+    Err(format!(
+        "{}{}",
+        REPO_UNSUPPORTED_PREFIX,
+        caps.failure_reason.unwrap_or_else(|| "Failed to open repository".to_string())
+    ))
+}
+
+/// Convert raw path bytes from libgit2 into an NFC display string, keeping the
+/// original bytes (as base64) when they aren't valid UTF-8 or aren't already NFC
+fn path_from_bytes(bytes: &[u8]) -> (String, Option<String>) {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => {
+            let normalized = fsutil::nfc(s);
+            let raw = (normalized != s).then(|| BASE64.encode(bytes));
+            (normalized, raw)
+        }
+        Err(_) => (
+            String::from_utf8_lossy(bytes).to_string(),
+            Some(BASE64.encode(bytes)),
+        ),
+    }
+}
+
+/// Map frontend paths onto the form stored in the repository (index entry or on-disk
+/// name), so an NFC path still hits a file committed or created as NFD. The index is
+/// read once for all of them, and only when some path isn't ASCII.
+fn repo_path_forms(repo: &Repository, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    if paths.iter().all(|path| path.to_str().is_none_or(str::is_ascii)) {
+        return paths;
+    }
+    let stored: HashMap<String, String> = repo
+        .index()
+        .map(|index| {
+            index
+                .iter()
+                .filter_map(|entry| String::from_utf8(entry.path).ok())
+                .filter(|path| !path.is_ascii())
+                .map(|path| (fsutil::nfc(&path), path))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let text = match path.to_str() {
+                Some(text) if !text.is_ascii() => text,
+                _ => return path,
+            };
+            if let Some(stored) = stored.get(&fsutil::nfc(text)) {
+                return PathBuf::from(stored);
+            }
+            let Some(workdir) = repo.workdir() else {
+                return path;
+            };
+            let mut resolved = PathBuf::new();
+            for component in path.components() {
+                let name = component.as_os_str();
+                match fsutil::find_entry_nfc(&workdir.join(&resolved), &name.to_string_lossy()) {
+                    Some(found) => resolved.push(found),
+                    None => resolved.push(name),
+                }
+            }
+            resolved
+        })
+        .collect()
+}
+
+fn repo_path_form(repo: &Repository, path: PathBuf) -> PathBuf {
+    repo_path_forms(repo, vec![path]).remove(0)
+}
+
+/// Resolve a path argument from the frontend, preferring the raw bytes when provided
+fn resolve_path_arg(path: &str, raw_b64: Option<&str>) -> Result<PathBuf, String> {
+    let raw = match raw_b64 {
+        Some(raw) => raw,
+        None => return Ok(PathBuf::from(path)),
+    };
+
+    let bytes = BASE64
+        .decode(raw)
+        .map_err(|e| format!("Invalid raw path for {}: {}", path, e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(&bytes)))
+    }
+
+    #[cfg(not(unix))]
+    {
+        String::from_utf8(bytes)
+            .map(PathBuf::from)
+            .map_err(|_| format!("Path is not valid UTF-8 on this platform: {}", path))
+    }
+}
+
+/// Escape glob characters and `!` so libgit2 pathspec matching, which
+/// checkout and reset use, only matches the path itself
+fn literal_pathspec(path: &Path) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        let mut escaped = Vec::new();
+        for &byte in path.as_os_str().as_bytes() {
+            if b"*?[]!\\".contains(&byte) {
+                escaped.push(b'\\');
+            }
+            escaped.push(byte);
+        }
+        PathBuf::from(std::ffi::OsString::from_vec(escaped))
+    }
+
+    #[cfg(not(unix))]
+    {
+        // Backslashes are separators here, so only the glob characters are escaped
+        let mut escaped = String::new();
+        for c in path.to_string_lossy().chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '!') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        PathBuf::from(escaped)
+    }
+}
+
+/// Size of a working tree file and whether git has its exact content; a missing file loses nothing
+fn lost_content(repo: &Repository, path: &Path) -> (u64, bool, Option<git2::Oid>) {
+    let Some(full) = repo.workdir().map(|dir| dir.join(path)) else {
+        return (0, true, None);
+    };
+    let Ok(meta) = std::fs::symlink_metadata(&full) else {
+        return (0, true, None);
+    };
+    let oid = git2::Oid::hash_file(git2::ObjectType::Blob, &full).ok();
+    let stored = oid.is_some_and(|oid| repo.odb().is_ok_and(|odb| odb.exists(oid)));
+    (meta.len(), stored, oid)
+}
+
+/// Plan `git_discard`: the working tree files `pathspec` matches that differ from the index.
+/// The preview's token covers each file's current content, so an edit in between changes it.
+fn plan_discard(repo: &Repository, pathspec: &Path) -> Result<(Vec<PathBuf>, DestructionPreview), String> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false).pathspec(pathspec);
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+
+    let mut paths = Vec::new();
+    let mut preview = DestructionPreview::default();
+    let mut plan = String::from("discard\n");
+    for entry in statuses.iter() {
+        let discarded = git2::Status::WT_MODIFIED | git2::Status::WT_DELETED | git2::Status::WT_TYPECHANGE;
+        if !entry.status().intersects(discarded) {
+            continue;
+        }
+        let (display, raw_path) = path_from_bytes(entry.path_bytes());
+        let path = resolve_path_arg(&display, raw_path.as_deref())?;
+        let (bytes, recoverable, oid) = lost_content(repo, &path);
+        plan.push_str(&format!("{}\t{}\t{}\n", BASE64.encode(entry.path_bytes()), bytes, oid.unwrap_or_else(git2::Oid::zero)));
+        paths.push(path);
+        preview.files_restored.push(PreviewFile {
+            path: display,
+            raw_path,
+            bytes,
+            recoverable,
+        });
+    }
+    preview.token = fsutil::sha256_hex(plan.as_bytes());
+    Ok((paths, preview))
+}
+
+/// Abbreviated hash as git prints it: core.abbrev long (or "auto"), lengthened until unique.
+/// Falls back to the first 7 characters when the object can't be looked up (e.g. past a
+/// shallow boundary).
+pub fn short_hash(repo: &Repository, oid: git2::Oid) -> String {
+    let full = oid.to_string();
+    let abbreviated = repo
+        .find_object(oid, None)
+        .and_then(|object| object.short_id())
+        .ok()
+        .and_then(|buf| buf.as_str().map(str::to_string));
+    match abbreviated {
+        Some(short) if full.starts_with(&short) => short,
+        // libgit2 answers zeros when core.abbrev asks for the full length
+        Some(_) => full,
+        None => full.get(..7).unwrap_or(&full).to_string(),
+    }
+}
+
+/// A commit with its first-parent diff stats, as listed by git_log
+pub fn commit_summary(repo: &Repository, commit: &git2::Commit) -> GitCommit {
+    // Get diff stats for this commit
+    let (files_changed, insertions, deletions) = if commit.parent_count() > 0 {
+        if let Ok(parent) = commit.parent(0) {
+            if let (Ok(parent_tree), Ok(commit_tree)) = (parent.tree(), commit.tree()) {
+                if let Ok(diff) =
+                    repo.diff_tree_to_tree(Some(&parent_tree), Some(&commit_tree), None)
+                {
+                    if let Ok(stats) = diff.stats() {
+                        (
+                            stats.files_changed() as u32,
+                            stats.insertions() as u32,
+                            stats.deletions() as u32,
+                        )
+                    } else {
+                        (0, 0, 0)
+                    }
+                } else {
+                    (0, 0, 0)
+                }
+            } else {
+                (0, 0, 0)
+            }
+        } else {
+            (0, 0, 0)
+        }
+    } else {
+        (0, 0, 0)
+    };
+
+    GitCommit {
+        hash: commit.id().to_string(),
+        short_hash: short_hash(repo, commit.id()),
+        message: commit.message().unwrap_or("").trim().to_string(),
+        author: commit.author().name().unwrap_or("Unknown").to_string(),
+        email: commit.author().email().unwrap_or("").to_string(),
+        timestamp: commit.time().seconds(),
+        files_changed,
+        insertions,
+        deletions,
+    }
+}
+
+fn get_branch_ahead_behind(repo: &Repository) -> (u32, u32) {
+    let head = match repo.head() {
+        Ok(h) => h,
+        Err(_) => return (0, 0),
+    };
+
+    // Try to get upstream branch
+    let branch_name = match head.shorthand() {
+        Some(name) => name,
+        None => return (0, 0),
+    };
+
+    match repo.find_branch(branch_name, BranchType::Local) {
+        Ok(branch) => {
+            let tracking = branch_tracking(repo, &branch);
+            (tracking.ahead, tracking.behind)
+        }
+        Err(_) => (0, 0),
+    }
+}
+
+/// Upstream of a local branch and how far the two have diverged
+#[derive(Default)]
+struct BranchTracking {
+    upstream: Option<String>,
+    ahead: u32,
+    behind: u32,
+    /// Configured upstream whose remote-tracking ref is missing (`upstream` still names it)
+    gone: bool,
+}
+
+/// Configured upstream of a branch whose remote-tracking ref doesn't exist, e.g. "origin/feature"
+fn gone_upstream(repo: &Repository, branch: &git2::Branch) -> Option<String> {
+    let refname = branch.get().name()?;
+    let upstream_ref = repo.branch_upstream_name(refname).ok()?;
+    let upstream_ref = upstream_ref.as_str()?;
+    if repo.find_reference(upstream_ref).is_ok() {
+        return None;
+    }
+    Some(upstream_ref.strip_prefix("refs/remotes/").unwrap_or(upstream_ref).to_string())
+}
+
+/// Upstream name and ahead/behind counts for a local branch.
+/// Branches without an upstream skip the graph walk entirely.
+fn branch_tracking(repo: &Repository, branch: &git2::Branch) -> BranchTracking {
+    let upstream = match branch.upstream() {
+        Ok(u) => u,
+        Err(_) => {
+            return match gone_upstream(repo, branch) {
+                Some(name) => BranchTracking {
+                    upstream: Some(name),
+                    gone: true,
+                    ..Default::default()
+                },
+                None => BranchTracking::default(),
+            };
+        }
+    };
+
+    let upstream_name = upstream.name().ok().flatten().map(String::from);
+
+    let (ahead, behind) = match (branch.get().target(), upstream.get().target()) {
+        (Some(local_oid), Some(upstream_oid)) => repo
+            .graph_ahead_behind(local_oid, upstream_oid)
+            .map(|(ahead, behind)| (ahead as u32, behind as u32))
+            .unwrap_or((0, 0)),
+        _ => (0, 0),
+    };
+
+    BranchTracking {
+        upstream: upstream_name,
+        ahead,
+        behind,
+        gone: false,
+    }
+}
+
+/// Collect local branches with tracking info, sorted by last commit time
+/// (most recent first) or alphabetically when `alphabetical` is set
+fn collect_branches(repo: &Repository, alphabetical: bool) -> Vec<BranchListItem> {
+    let current = repo
+        .head()
+        .ok()
+        .filter(|h| h.is_branch())
+        .and_then(|h| h.shorthand().map(String::from));
+
+    let mut items = Vec::new();
+
+    if let Ok(branch_iter) = repo.branches(Some(BranchType::Local)) {
+        for (branch, _) in branch_iter.flatten() {
+            let name = match branch.name().ok().flatten() {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+
+            let tracking = branch_tracking(repo, &branch);
+            let (last_commit_time, last_commit_summary) = match branch.get().peel_to_commit() {
+                Ok(commit) => (
+                    commit.time().seconds(),
+                    commit.summary().unwrap_or("").to_string(),
+                ),
+                Err(_) => (0, String::new()),
+            };
+
+            items.push(BranchListItem {
+                is_current: current.as_deref() == Some(name.as_str()),
+                name,
+                upstream: tracking.upstream,
+                ahead: tracking.ahead,
+                behind: tracking.behind,
+                upstream_gone: tracking.gone,
+                last_commit_time,
+                last_commit_summary,
+            });
+        }
+    }
+
+    if alphabetical {
+        items.sort_by(|a, b| a.name.cmp(&b.name));
+    } else {
+        items.sort_by_key(|b| std::cmp::Reverse(b.last_commit_time));
+    }
+
+    items
+}
+
+/// Local branch names, most recent commit first, without any tracking lookups
+fn recent_branch_names(repo: &Repository) -> Vec<String> {
+    let mut branches: Vec<(String, i64)> = Vec::new();
+    if let Ok(branch_iter) = repo.branches(Some(BranchType::Local)) {
+        for (branch, _) in branch_iter.flatten() {
+            let Some(name) = branch.name().ok().flatten().map(String::from) else {
+                continue;
+            };
+            let time = branch.get().peel_to_commit().map(|c| c.time().seconds()).unwrap_or(0);
+            branches.push((name, time));
+        }
+    }
+    branches.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+    branches.into_iter().map(|(name, _)| name).collect()
+}
+
+/// Diff options shared by the patch and stats commands so their numbers agree.
+/// The pathspec matches literally (a file named `[wip].rs` is just that file) unless `glob` is set.
+fn diff_options(pathspec: Option<PathBuf>, glob: bool) -> DiffOptions {
+    let mut opts = DiffOptions::new();
+    opts.context_lines(3);
+    if let Some(path) = pathspec {
+        opts.pathspec(path);
+        opts.disable_pathspec_match(!glob);
+    }
+    opts
+}
+
+/// The tree a revspec names: a branch, remote-tracking ref, tag (peeled) or commit
+fn resolve_tree<'a>(repo: &'a Repository, rev: &str) -> Result<git2::Tree<'a>, String> {
+    repo.revparse_single(rev)
+        .and_then(|o| o.peel_to_tree())
+        .map_err(|e| format!("Failed to resolve '{}': {}", rev, e))
+}
+
+/// Staged (HEAD..index) or unstaged (index..workdir) changes, with rename detection. With a
+/// `base` tree, its changes up to the index (staged) or the working tree instead.
+fn workspace_diff<'a>(
+    repo: &'a Repository,
+    base: Option<&git2::Tree>,
+    staged: bool,
+    opts: &mut DiffOptions,
+) -> Result<Diff<'a>, String> {
+    let mut diff = if let Some(base) = base {
+        let diff = match staged {
+            true => repo.diff_tree_to_index(Some(base), None, Some(opts)),
+            false => repo.diff_tree_to_workdir_with_index(Some(base), Some(opts)),
+        };
+        diff.map_err(|e| format!("Failed to get diff against base: {}", e))?
+    } else if staged {
+        // Staged changes: compare HEAD to index
+        let head_tree = repo
+            .head()
+            .ok()
+            .and_then(|h| h.peel_to_tree().ok());
+
+        repo.diff_tree_to_index(head_tree.as_ref(), None, Some(opts))
+            .map_err(|e| format!("Failed to get staged diff: {}", e))?
+    } else {
+        // Unstaged changes: compare index to working tree
+        repo.diff_index_to_workdir(None, Some(opts))
+            .map_err(|e| format!("Failed to get diff: {}", e))?
+    };
+
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(|e| format!("Failed to detect renames: {}", e))?;
+    Ok(diff)
+}
+
+/// Whether the tree has uncommitted changes, checking the cheap HEAD..index diff first
+/// and only walking the working tree when nothing is staged. The walk stops at the first
+/// change, untracked directories are not recursed into and the index is never rewritten.
+fn tree_is_dirty(repo: &Repository, include_untracked: bool) -> Result<bool, String> {
+    let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let mut opts = DiffOptions::new();
+    opts.ignore_submodules(true).skip_binary_check(true);
+    let staged = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut opts))
+        .map_err(|e| format!("Failed to check staged changes: {}", e))?;
+    if staged.deltas().len() > 0 {
+        return Ok(true);
+    }
+
+    workdir_differs(repo, include_untracked).map_err(|e| format!("Failed to check working tree: {}", e))
+}
+
+/// libgit2 diff callback that aborts at the first delta; the diff then fails with GIT_EUSER
+extern "C" fn stop_at_first_delta(
+    _diff: *const libgit2_sys::git_diff,
+    _delta: *const libgit2_sys::git_diff_delta,
+    _matched_pathspec: *const std::ffi::c_char,
+    _payload: *mut std::ffi::c_void,
+) -> std::ffi::c_int {
+    libgit2_sys::GIT_EUSER
+}
+
+/// Whether the working tree differs from the index, stopping at the first difference instead
+/// of diffing every file. git2 doesn't expose libgit2's notify callback (or the raw repository),
+/// so this opens its own handle on the same repository.
+fn workdir_differs(repo: &Repository, include_untracked: bool) -> Result<bool, git2::Error> {
+    use libgit2_sys as raw;
+
+    #[cfg(unix)]
+    let path = std::os::unix::ffi::OsStrExt::as_bytes(repo.path().as_os_str()).to_vec();
+    #[cfg(not(unix))]
+    let path = repo.path().to_string_lossy().into_owned().into_bytes();
+    let path = std::ffi::CString::new(path).map_err(|_| git2::Error::from_str("repository path contains a NUL byte"))?;
+
+    let mut flags = raw::GIT_DIFF_IGNORE_SUBMODULES | raw::GIT_DIFF_SKIP_BINARY_CHECK;
+    if include_untracked {
+        flags |= raw::GIT_DIFF_INCLUDE_UNTRACKED;
+    }
+
+    // SAFETY: every pointer handed to libgit2 outlives the call using it, and the repository
+    // and diff it allocates are freed here exactly once
+    let code = unsafe {
+        let mut raw_repo = std::ptr::null_mut();
+        let code = raw::git_repository_open(&mut raw_repo, path.as_ptr());
+        if code < 0 {
+            return Err(git2::Error::last_error(code).unwrap_or_else(|| git2::Error::from_str("failed to open repository")));
+        }
+        let mut opts: raw::git_diff_options = std::mem::zeroed();
+        raw::git_diff_init_options(&mut opts, 1);
+        // The flag constants are signed on MSVC
+        #[allow(clippy::unnecessary_cast)]
+        {
+            opts.flags = flags as u32;
+        }
+        opts.notify_cb = Some(stop_at_first_delta);
+
+        let mut diff = std::ptr::null_mut();
+        let code = raw::git_diff_index_to_workdir(&mut diff, raw_repo, std::ptr::null_mut(), &opts);
+        if !diff.is_null() {
+            raw::git_diff_free(diff);
+        }
+        raw::git_repository_free(raw_repo);
+        code
+    };
+    match code {
+        0 => Ok(false),
+        raw::GIT_EUSER => Ok(true),
+        code => Err(git2::Error::last_error(code).unwrap_or_else(|| git2::Error::from_str("diff failed"))),
+    }
+}
+
+/// A working-tree entry libgit2 lists as modified (its size or stat changed) whose content is
+/// the same once run through the eol/autocrlf filters: git doesn't show it, so neither do we
+fn unchanged_after_filters(patch: &Patch) -> bool {
+    let delta = patch.delta();
+    delta.status() == Delta::Modified
+        && patch.num_hunks() == 0
+        && !delta.flags().is_binary()
+        && delta.old_file().mode() == delta.new_file().mode()
+}
+
+/// Paths of the `unchanged_after_filters` entries of a working-tree diff
+fn filter_only_changes(diff: &Diff) -> Result<std::collections::HashSet<PathBuf>, String> {
+    let mut paths = std::collections::HashSet::new();
+    for (idx, delta) in diff.deltas().enumerate() {
+        if delta.status() != Delta::Modified {
+            continue;
+        }
+        let patch = Patch::from_diff(diff, idx).map_err(|e| format!("Failed to compute diff: {}", e))?;
+        if patch.as_ref().is_some_and(unchanged_after_filters) {
+            paths.extend(delta.new_file().path().map(Path::to_path_buf));
+        }
+    }
+    Ok(paths)
+}
+
+fn delta_status(delta: Delta) -> &'static str {
+    match delta {
+        Delta::Added | Delta::Untracked => "added",
+        Delta::Deleted => "deleted",
+        Delta::Renamed => "renamed",
+        Delta::Copied => "copied",
+        Delta::Typechange => "typechange",
+        _ => "modified",
+    }
+}
+
+/// Content of one side of a delta: the blob when it's hashed, otherwise the file on disk
+fn delta_side_content(repo: &Repository, file: &git2::DiffFile, workdir: Option<&Path>) -> Option<Vec<u8>> {
+    if !file.id().is_zero() {
+        if let Ok(blob) = repo.find_blob(file.id()) {
+            return Some(blob.content().to_vec());
+        }
+    }
+    let path = file.path()?;
+    std::fs::read(workdir?.join(path)).ok()
+}
+
+fn strip_carriage_returns(content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len());
+    for (i, &b) in content.iter().enumerate() {
+        if b == b'\r' && content.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// True when a modified file differs from its old version only in CRLF vs LF
+fn is_line_ending_only_change(repo: &Repository, delta: &git2::DiffDelta, workdir: Option<&Path>) -> bool {
+    if delta.status() != Delta::Modified {
+        return false;
+    }
+    let old = match delta_side_content(repo, &delta.old_file(), None) {
+        Some(content) => content,
+        None => return false,
+    };
+    let new = match delta_side_content(repo, &delta.new_file(), workdir) {
+        Some(content) => content,
+        None => return false,
+    };
+    old != new && strip_carriage_returns(&old) == strip_carriage_returns(&new)
+}
+
+/// Per-file stats of `diff`, annotated with each path's diff attributes. With
+/// `strip_notebooks`, notebooks are counted with their outputs stripped.
+fn collect_diff_stats(repo: &Repository, diff: &Diff, workdir: Option<&Path>, strip_notebooks: bool) -> Result<DiffStats, String> {
+    let mut stats = DiffStats {
+        files: Vec::new(),
+        additions: 0,
+        deletions: 0,
+        filtered: Vec::new(),
+    };
+
+    for idx in 0..diff.deltas().len() {
+        let patch = Patch::from_diff(diff, idx)
+            .map_err(|e| format!("Failed to compute diff stats: {}", e))?;
+        if workdir.is_some() && patch.as_ref().is_some_and(unchanged_after_filters) {
+            continue;
+        }
+        let delta = match patch {
+            Some(ref patch) => patch.delta(),
+            None => match diff.get_delta(idx) {
+                Some(delta) => delta,
+                None => continue,
+            },
+        };
+
+        let new_file = delta.new_file();
+        let old_file = delta.old_file();
+        let (path, _) = path_from_bytes(new_file.path_bytes().or(old_file.path_bytes()).unwrap_or_default());
+        let old_path = match delta.status() {
+            Delta::Renamed | Delta::Copied => old_file.path_bytes().map(|b| path_from_bytes(b).0),
+            _ => None,
+        };
+
+        let binary = delta.flags().is_binary() || patch.is_none();
+        let attributes = diff_attributes::lookup(repo, &path);
+        let mut entry = DiffFileStat {
+            path,
+            old_path,
+            status: delta_status(delta.status()).to_string(),
+            additions: 0,
+            deletions: 0,
+            binary,
+            old_size_bytes: None,
+            new_size_bytes: None,
+            #[cfg(feature = "legacy_fields")]
+            old_size: None,
+            #[cfg(feature = "legacy_fields")]
+            new_size: None,
+            line_endings_only: false,
+            generated: attributes.generated,
+            vendored: attributes.vendored,
+            diff_suppressed: attributes.diff_suppressed,
+            diff_driver: attributes.driver,
+        };
+        let notebook = match strip_notebooks && !binary && diff_attributes::is_notebook(&entry.path) {
+            true => diff_attributes::notebook_sides(repo, &delta, workdir),
+            false => None,
+        };
+
+        if binary {
+            let exists = |f: &git2::DiffFile| !f.id().is_zero() || f.size() > 0;
+            entry.old_size_bytes = exists(&old_file).then(|| old_file.size());
+            // Workdir blobs aren't hashed, so fall back to the file on disk
+            entry.new_size_bytes = if delta.status() == Delta::Deleted {
+                None
+            } else if new_file.size() > 0 {
+                Some(new_file.size())
+            } else {
+                workdir
+                    .and_then(|dir| std::fs::metadata(dir.join(&entry.path)).ok())
+                    .map(|m| m.len())
+            };
+            #[cfg(feature = "legacy_fields")]
+            {
+                (entry.old_size, entry.new_size) = (entry.old_size_bytes, entry.new_size_bytes);
+            }
+        } else if let Some((old, new)) = notebook {
+            let stripped = Patch::from_buffers(old.as_bytes(), None, new.as_bytes(), None, None)
+                .and_then(|patch| patch.line_stats())
+                .map_err(|e| format!("Failed to compute diff stats: {}", e))?;
+            entry.additions = stripped.1 as u32;
+            entry.deletions = stripped.2 as u32;
+        } else if let Some(ref patch) = patch {
+            let (_, additions, deletions) = patch
+                .line_stats()
+                .map_err(|e| format!("Failed to compute diff stats: {}", e))?;
+            entry.additions = additions as u32;
+            entry.deletions = deletions as u32;
+            entry.line_endings_only = additions > 0 && is_line_ending_only_change(repo, &delta, workdir);
+        }
+
+        stats.additions += entry.additions;
+        stats.deletions += entry.deletions;
+        stats.files.push(entry);
+    }
+
+    Ok(stats)
+}
+
+/// Where `git_log` starts: a local branch's commit, or HEAD's
+fn log_tip(repo: &Repository, branch: Option<&str>) -> Result<git2::Oid, String> {
+    match branch {
+        Some(branch_name) => {
+            let reference = repo
+                .find_branch(branch_name, BranchType::Local)
+                .map_err(|e| format!("Branch not found: {}", e))?;
+            reference.get().target().ok_or_else(|| "Invalid branch target".to_string())
+        }
+        None => repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map(|commit| commit.id())
+            .map_err(|e| format!("Failed to resolve HEAD: {}", e)),
+    }
+}
+
+/// Up to `limit` commits reachable from `tip`, after the first `skip`; whether more follow, and
+/// whether a shallow clone's boundary was reached
+fn walk_log(repo: &Repository, tip: git2::Oid, skip: usize, limit: usize) -> Result<(Vec<GitCommit>, bool, bool), String> {
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk
+        .push(tip)
+        .map_err(|e| format!("Failed to push branch: {}", e))?;
+
+    let is_shallow = repo.is_shallow();
+    let boundary = if is_shallow { shallow_boundary(repo) } else { Default::default() };
+    let mut shallow_boundary_reached = false;
+    let mut commits = Vec::new();
+    let mut more = false;
+
+    for oid_result in revwalk.skip(skip) {
+        let oid = match oid_result {
+            Ok(oid) => oid,
+            // A parent missing from a shallow clone ends the walk rather than failing it
+            Err(_) if is_shallow => {
+                shallow_boundary_reached = true;
+                break;
+            }
+            Err(e) => return Err(format!("Failed to get OID: {}", e)),
+        };
+        if commits.len() >= limit {
+            more = true;
+            break;
+        }
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+
+        shallow_boundary_reached |= boundary.contains(&oid);
+        commits.push(commit_summary(repo, &commit));
+    }
+    Ok((commits, more, shallow_boundary_reached))
+}
+
+// ============================================================================
+// External Processes
+// ============================================================================
+
+/// Boundary commits of a shallow clone (their parents weren't fetched)
+fn shallow_boundary(repo: &Repository) -> std::collections::HashSet<git2::Oid> {
+    std::fs::read_to_string(repo.path().join("shallow"))
+        .map(|content| content.lines().filter_map(|line| git2::Oid::from_str(line.trim()).ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Merge base of two commits; in a shallow clone a missing base means the history stops too early
+fn merge_base_or_shallow(repo: &Repository, base: git2::Oid, head: git2::Oid) -> Result<git2::Oid, String> {
+    repo.merge_base(base, head).map_err(|e| {
+        if repo.is_shallow() && e.code() == git2::ErrorCode::NotFound {
+            format!(
+                "{}the merge base is beyond the shallow clone's history; fetch more history to compare these revisions",
+                SHALLOW_HISTORY_PREFIX
+            )
+        } else {
+            format!("Failed to find merge base: {}", e)
+        }
+    })
+}
+
+/// The `git fetch` flag for `git_fetch_deepen`: `depth` more commits, or all of them
+fn deepen_arg(depth: Option<u32>) -> Result<String, String> {
+    match depth {
+        Some(0) => Err("Depth must be at least 1".to_string()),
+        Some(depth) => Ok(format!("--deepen={}", depth)),
+        None => Ok("--unshallow".to_string()),
+    }
+}
+
+/// Default time limit for a git/gh invocation, by subcommand
+fn default_timeout(program: &str, subcommand: &str) -> Duration {
+    match (program, subcommand) {
+        ("git", "push" | "pull" | "fetch" | "clone") => Duration::from_secs(300),
+        ("git", "rebase") => Duration::from_secs(300),
+        ("git", "gc") => Duration::from_secs(600),
+        ("gh", "pr") => Duration::from_secs(120),
+        ("gh", _) => Duration::from_secs(60),
+        _ => Duration::from_secs(120),
+    }
+}
+
+/// Kill the child and everything it spawned (credential helpers, ssh, ...)
+async fn kill_process_tree(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+
+        if let Some(pid) = child.id() {
+            let _ = killpg(Pid::from_raw(pid as i32), Signal::SIGKILL);
+        }
+    }
+    let _ = child.kill().await;
+}
+
+/// Run a git/gh command with a timeout, registered under `op_id` so it can be
+/// cancelled via `cancel_git_operation`. Stdin is closed so credential prompts
+/// fail fast instead of hanging.
+pub async fn run_external<S: AsRef<OsStr>>(
+    state: &GitState,
+    program: &str,
+    args: &[S],
+    dir: Option<&str>,
+    envs: &[(&str, &str)],
+    timeout: Option<Duration>,
+    op_id: Option<String>,
+) -> Result<ExternalOutput, ExternalError> {
+    run_external_with_progress(state, program, args, dir, envs, timeout, op_id, None).await
+}
+
+/// Receives progress lines from a running external command
+pub type ProgressSink = Box<dyn Fn(&str) + Send>;
+
+/// `run_external`, also handing each stderr line (split on `\r` too, as git redraws
+/// progress in place) to `progress` as it arrives
+#[allow(clippy::too_many_arguments)]
+pub async fn run_external_with_progress<S: AsRef<OsStr>>(
+    state: &GitState,
+    program: &str,
+    args: &[S],
+    dir: Option<&str>,
+    envs: &[(&str, &str)],
+    timeout: Option<Duration>,
+    op_id: Option<String>,
+    progress: Option<ProgressSink>,
+) -> Result<ExternalOutput, ExternalError> {
+    let subcommand = args
+        .first()
+        .map(|a| a.as_ref().to_string_lossy().to_string())
+        .unwrap_or_default();
+    let command = format!("{} {}", program, subcommand).trim().to_string();
+    let timeout = timeout.unwrap_or_else(|| default_timeout(program, &subcommand));
+
+    let mut cmd = Command::new(program);
+    if program == "git" {
+        // Paths passed to git are file names, never patterns
+        cmd.env("GIT_LITERAL_PATHSPECS", "1");
+    }
+    if program == "gh" {
+        if let Some(token) = state.gh_token.token(envs).await {
+            cmd.env("GH_TOKEN", token);
+        }
+    }
+    // The caller's variables win over the proxy's
+    cmd.envs(state.proxy.vars())
+        .args(args)
+        .envs(envs.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let mut child = cmd.spawn().map_err(|e| ExternalError::Spawn {
+        command: command.clone(),
+        error: e.to_string(),
+    })?;
+
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let stdout_task = state.tasks.spawn(format!("{} stdout", command), |token| async move {
+        let mut buf = Vec::new();
+        if let Some(ref mut pipe) = stdout {
+            tokio::select! {
+                _ = pipe.read_to_end(&mut buf) => {}
+                _ = token.cancelled() => {}
+            }
+        }
+        buf
+    });
+    let stderr_task = state.tasks.spawn(format!("{} stderr", command), |token| async move {
+        let mut buf = Vec::new();
+        match (stderr.as_mut(), progress) {
+            (Some(pipe), Some(progress)) => {
+                let mut chunk = [0u8; 4096];
+                let mut line_start = 0;
+                loop {
+                    let read = tokio::select! {
+                        read = pipe.read(&mut chunk) => read,
+                        _ = token.cancelled() => break,
+                    };
+                    let Ok(read) = read else {
+                        break;
+                    };
+                    if read == 0 {
+                        break;
+                    }
+                    buf.extend_from_slice(&chunk[..read]);
+                    while let Some(end) = buf[line_start..].iter().position(|b| *b == b'\r' || *b == b'\n') {
+                        let line = String::from_utf8_lossy(&buf[line_start..line_start + end]);
+                        if !line.trim().is_empty() {
+                            progress(line.trim());
+                        }
+                        line_start += end + 1;
+                    }
+                }
+            }
+            (Some(pipe), None) => {
+                tokio::select! {
+                    _ = pipe.read_to_end(&mut buf) => {}
+                    _ = token.cancelled() => {}
+                }
+            }
+            (None, _) => {}
+        }
+        buf
+    });
+
+    let op_id = op_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    state.operations.lock().await.insert(op_id.clone(), cancel_tx);
+
+    let outcome = tokio::select! {
+        status = child.wait() => Ok(status),
+        _ = tokio::time::sleep(timeout) => Err(ExternalError::TimedOut { command: command.clone(), after: timeout }),
+        _ = cancel_rx => Err(ExternalError::Cancelled { command: command.clone() }),
+    };
+
+    state.operations.lock().await.remove(&op_id);
+
+    let status = match outcome {
+        Ok(Ok(status)) => status,
+        Ok(Err(e)) => {
+            stdout_task.handle.abort();
+            stderr_task.handle.abort();
+            return Err(ExternalError::Spawn { command, error: e.to_string() });
+        }
+        Err(err) => {
+            kill_process_tree(&mut child).await;
+            stdout_task.handle.abort();
+            stderr_task.handle.abort();
+            return Err(err);
+        }
+    };
+
+    let stderr = stderr_task.handle.await.unwrap_or_default();
+    // A rejected token or missing scope makes the cached auth status suspect
+    if program == "gh" && !status.success() && crate::gh_auth::is_auth_failure(&stderr) {
+        *state.gh_auth_cache.lock().await = None;
+    }
+    Ok(ExternalOutput {
+        status,
+        stdout: stdout_task.handle.await.unwrap_or_default(),
+        stderr,
+    })
+}
+
+/// Run a git/gh command as a reported operation: git's progress lines become `progress`
+/// steps, and the reporter finishes with the command's outcome (`failure` prefixes the error)
+#[allow(clippy::too_many_arguments)]
+async fn run_reported<S: AsRef<OsStr>>(
+    app: &tauri::AppHandle,
+    state: &GitState,
+    kind: &str,
+    program: &str,
+    args: &[S],
+    dir: Option<&str>,
+    op_id: Option<String>,
+    failure: &str,
+) -> Result<ExternalOutput, String> {
+    let reporter = ProgressReporter::start(app, kind, op_id, dir, true);
+    let sink = (program == "git").then(|| reporter.git_sink());
+    let output = run_external_with_progress(
+        state,
+        program,
+        args,
+        dir,
+        &[],
+        None,
+        Some(reporter.operation_id().to_string()),
+        sink,
+    )
+    .await
+    .map_err(String::from)
+    .and_then(|output| {
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(format!("{}: {}", failure, String::from_utf8_lossy(&output.stderr)))
+        }
+    });
+    reporter.settle(output)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Get the current git status of the repository.
+///
+/// Calls for the same repository share one walk while it runs, and a call within the
+/// minimum refresh interval (`gitStatusMinIntervalMs`) of the last walk gets that result
+/// back with `staleMs` set, unless `force` is set. `git-state-changed` is emitted once
+/// per walk whose result differs from the previous one.
+#[tauri::command]
+pub async fn git_status(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    apply_filters: Option<bool>,
+    rename_threshold: Option<u16>,
+    force: Option<bool>,
+) -> Result<GitStatus, GitCommandError> {
+    let threshold = rename_threshold.unwrap_or(DEFAULT_RENAME_THRESHOLD);
+    if threshold > 100 {
+        return Err(format!("Rename threshold must be a percentage (0-100), got {}", threshold).into());
+    }
+    let apply_filters = apply_filters.unwrap_or(true);
+    let key = format!(
+        "{}|{}|{}",
+        std::fs::canonicalize(&working_dir).unwrap_or_else(|_| PathBuf::from(&working_dir)).display(),
+        apply_filters,
+        threshold
+    );
+    let min_interval = {
+        let app_state = app.state::<crate::AppState>();
+        let settings = crate::settings::load(&app, &app_state.settings).await?;
+        Duration::from_millis(settings.git_status_min_interval_ms)
+    };
+
+    let walk = || async {
+        let matcher = match apply_filters {
+            true => status_filters::matcher_for(&app, &state, &working_dir).await?,
+            false => None,
+        };
+        let dir = working_dir.clone();
+        tokio::task::spawn_blocking(move || compute_status(&dir, matcher, threshold))
+            .await
+            .map_err(|e| format!("Status task failed: {}", e))?
+    };
+    let (result, changed) = coalesced_status(&state.status_runs, key, min_interval, force == Some(true), walk).await;
+    if let (Ok(status), true) = (&result, changed) {
+        let _ = app.emit(
+            "git-state-changed",
+            serde_json::json!({
+                "working_dir": working_dir,
+                "branch": status.branch,
+                "ahead": status.ahead,
+                "behind": status.behind,
+                "changed_files": status.files.len(),
+            }),
+        );
+    }
+    result.map_err(GitCommandError::from)
+}
+
+/// The status behind `git_status`'s cache: the last result when it is younger than
+/// `min_interval` (with `staleMs` set), else a share of the walk already running for `key`,
+/// else a new walk. True alongside a result that differs from the previous one, for the
+/// caller that retired the walk only.
+async fn coalesced_status<F, Fut>(
+    runs: &Mutex<HashMap<String, StatusRun>>,
+    key: String,
+    min_interval: Duration,
+    force: bool,
+    compute: F,
+) -> (Result<GitStatus, String>, bool)
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<GitStatus, String>>,
+{
+    let walk = {
+        let mut runs = runs.lock().await;
+        let run = runs.entry(key.clone()).or_default();
+        if !force {
+            if let Some((at, status)) = &run.last {
+                let age = at.elapsed();
+                if age < min_interval {
+                    let mut status = status.clone();
+                    status.stale_ms = Some(age.as_millis() as u64);
+                    return (Ok(status), false);
+                }
+            }
+        }
+        run.in_flight.get_or_insert_with(SharedStatus::default).clone()
+    };
+
+    let result = walk.get_or_init(compute).await.clone();
+
+    // Whoever finishes first retires the walk and records its result
+    let mut runs = runs.lock().await;
+    let run = runs.entry(key).or_default();
+    let mut changed = false;
+    if run.in_flight.as_ref().is_some_and(|current| Arc::ptr_eq(current, &walk)) {
+        run.in_flight = None;
+        if let Ok(status) = &result {
+            changed = run
+                .last
+                .as_ref()
+                .is_none_or(|(_, last)| serde_json::to_value(last).ok() != serde_json::to_value(status).ok());
+            run.last = Some((Instant::now(), status.clone()));
+        }
+    }
+    (result, changed)
+}
+
+/// The branch HEAD points at before its first commit ("HEAD" if it can't be read)
+fn unborn_branch_name(repo: &Repository) -> String {
+    repo.find_reference("HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().map(|target| target.trim_start_matches("refs/heads/").to_string()))
+        .unwrap_or_else(|| "HEAD".to_string())
+}
+
+/// Walk the repository's status (the uncached work behind `git_status`)
+pub fn compute_status(
+    working_dir: &str,
+    matcher: Option<Arc<globset::GlobSet>>,
+    threshold: u16,
+) -> Result<GitStatus, String> {
+    let repo = open_repo(working_dir)?;
+
+    // Get current branch name; a new repository's branch has no commit yet
+    let branch = match repo.head() {
+        Ok(head) => head.shorthand().unwrap_or("HEAD").to_string(),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => unborn_branch_name(&repo),
+        Err(e) => return Err(format!("Failed to get HEAD: {}", e)),
+    };
+
+    // Get upstream info
+    let upstream = if let Ok(local_branch) = repo.find_branch(&branch, BranchType::Local) {
+        local_branch
+            .upstream()
+            .ok()
+            .and_then(|u| u.name().ok().flatten().map(String::from))
+    } else {
+        None
+    };
+
+    // Get ahead/behind counts
+    let (ahead, behind) = get_branch_ahead_behind(&repo);
+
+    // Get file statuses, pairing deletes with similar new files as renames
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false)
+        .renames_head_to_index(true)
+        .renames_index_to_workdir(true)
+        .rename_threshold(threshold);
+
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+
+    let mut staged = Vec::new();
+    let mut modified = Vec::new();
+    let mut untracked = Vec::new();
+    let mut deleted = Vec::new();
+    let mut files = Vec::new();
+
+    for entry in statuses.iter() {
+        let status = entry.status();
+        let head_to_index = entry.head_to_index();
+        let index_to_workdir = entry.index_to_workdir();
+
+        // path_bytes() is the pre-rename path; the file now lives at the newest side's path
+        let (original, original_b64) = path_from_bytes(entry.path_bytes());
+        let (path, raw_b64) = index_to_workdir
+            .as_ref()
+            .or(head_to_index.as_ref())
+            .and_then(|d| d.new_file().path_bytes())
+            .map(path_from_bytes)
+            .unwrap_or((original.clone(), original_b64.clone()));
+        // Where the index has the file, i.e. the old side of a worktree rename
+        let index_path = index_to_workdir
+            .as_ref()
+            .and_then(|d| d.old_file().path_bytes())
+            .map(path_from_bytes)
+            .unwrap_or((path.clone(), raw_b64.clone()));
+
+        // Renamed first: an edited rename carries the modified flag too
+        let index_status = if status.is_index_new() {
+            Some("added")
+        } else if status.is_index_renamed() {
+            Some("renamed")
+        } else if status.is_index_modified() {
+            Some("modified")
+        } else if status.is_index_deleted() {
+            Some("deleted")
+        } else if status.is_index_typechange() {
+            Some("typechange")
+        } else {
+            None
+        };
+        let worktree_status = if status.is_wt_new() {
+            Some("untracked")
+        } else if status.is_wt_renamed() {
+            Some("renamed")
+        } else if status.is_wt_modified() {
+            Some("modified")
+        } else if status.is_wt_deleted() {
+            Some("deleted")
+        } else if status.is_wt_typechange() {
+            Some("typechange")
+        } else {
+            None
+        };
+
+        // Check index (staged) changes; a staged rename keeps its old shape here too
+        match index_status {
+            Some("renamed") => {
+                staged.push(GitFile {
+                    path: original.clone(),
+                    status: "deleted".to_string(),
+                    old_path: None,
+                    raw_b64: original_b64.clone(),
+                });
+                staged.push(GitFile {
+                    path: index_path.0.clone(),
+                    status: "added".to_string(),
+                    old_path: None,
+                    raw_b64: index_path.1.clone(),
+                });
+            }
+            Some(kind @ ("added" | "modified" | "deleted")) => staged.push(GitFile {
+                path: index_path.0.clone(),
+                status: kind.to_string(),
+                old_path: None,
+                raw_b64: index_path.1.clone(),
+            }),
+            _ => {}
+        }
+
+        // Check working tree changes (not staged); likewise a worktree rename is a
+        // deleted file plus an untracked one
+        match worktree_status {
+            Some("untracked") => untracked.push(GitFile {
+                path: path.clone(),
+                status: "untracked".to_string(),
+                old_path: None,
+                raw_b64: raw_b64.clone(),
+            }),
+            Some("modified") => modified.push(GitFile {
+                path: path.clone(),
+                status: "modified".to_string(),
+                old_path: None,
+                raw_b64: raw_b64.clone(),
+            }),
+            Some("deleted") => deleted.push(GitFile {
+                path: path.clone(),
+                status: "deleted".to_string(),
+                old_path: None,
+                raw_b64: raw_b64.clone(),
+            }),
+            Some("renamed") => {
+                deleted.push(GitFile {
+                    path: index_path.0.clone(),
+                    status: "deleted".to_string(),
+                    old_path: None,
+                    raw_b64: index_path.1.clone(),
+                });
+                untracked.push(GitFile {
+                    path: path.clone(),
+                    status: "untracked".to_string(),
+                    old_path: None,
+                    raw_b64: raw_b64.clone(),
+                });
+            }
+            _ => {}
+        }
+
+        let renamed = index_status == Some("renamed") || worktree_status == Some("renamed");
+        files.push(GitFileEx {
+            path,
+            old_path: renamed.then_some(original),
+            index_status: index_status.map(String::from),
+            worktree_status: worktree_status.map(String::from),
+            conflicted: status.is_conflicted(),
+            raw_b64,
+        });
+    }
+
+    let mut status = GitStatus {
+        branch,
+        upstream,
+        ahead,
+        behind,
+        staged,
+        modified,
+        untracked,
+        deleted,
+        files,
+        filtered: Vec::new(),
+        stale_ms: None,
+    };
+    if let Some(matcher) = matcher {
+        status_filters::partition_status(&mut status, &matcher);
+    }
+    Ok(status)
+}
+
+/// Get the diff for a specific file or the entire working tree. `base` (any revspec, e.g.
+/// "origin/main" or a tag) diffs from that revision instead of from the index or HEAD.
+/// With `strip_notebook_outputs`, notebooks are diffed without cell outputs and execution
+/// counts. Paths marked -diff show as binary, without content.
+#[tauri::command]
+pub async fn git_diff(
+    working_dir: String,
+    file_path: Option<String>,
+    staged: bool,
+    raw_path: Option<String>,
+    glob: Option<bool>,
+    base: Option<String>,
+    strip_notebook_outputs: Option<bool>,
+) -> Result<String, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let base_tree = base.as_deref().map(|rev| resolve_tree(&repo, rev)).transpose()?;
+
+    let pathspec = match file_path {
+        Some(ref path) => Some(match raw_path {
+            Some(ref raw) => resolve_path_arg(path, Some(raw))?,
+            None => repo_path_form(&repo, PathBuf::from(path)),
+        }),
+        None => None,
+    };
+    let mut opts = diff_options(pathspec, glob.unwrap_or(false));
+    let diff = workspace_diff(&repo, base_tree.as_ref(), staged, &mut opts)?;
+    let workdir = if staged { None } else { repo.workdir() };
+    let strip_notebooks = strip_notebook_outputs.unwrap_or(false);
+    let unchanged = match workdir {
+        Some(_) => filter_only_changes(&diff)?,
+        None => Default::default(),
+    };
+
+    let mut diff_str = String::new();
+    // The delta being printed, and whether its stripped notebook patch replaced its lines
+    let mut current: Option<(Option<PathBuf>, bool)> = None;
+    diff.print(git2::DiffFormat::Patch, |delta, _hunk, line| {
+        let path = delta.new_file().path().or(delta.old_file().path()).map(Path::to_path_buf);
+        if delta.status() == Delta::Modified && path.as_ref().is_some_and(|p| unchanged.contains(p)) {
+            return true;
+        }
+        if current.as_ref().map(|(p, _)| p) != Some(&path) {
+            let stripped = path
+                .as_deref()
+                .and_then(|p| p.to_str())
+                .filter(|p| strip_notebooks && !delta.flags().is_binary() && diff_attributes::is_notebook(p))
+                .and_then(|_| stripped_notebook_diff(&repo, &delta, workdir));
+            if let Some(ref text) = stripped {
+                diff_str.push_str(text);
+            }
+            current = Some((path, stripped.is_some()));
+        }
+        if !current.as_ref().is_some_and(|(_, stripped)| *stripped) {
+            push_diff_line(&mut diff_str, &line);
+        }
+        true
+    })
+    .map_err(|e| format!("Failed to print diff: {}", e))?;
+
+    Ok(diff_str)
+}
+
+/// Append a printed diff line as `git_diff` returns it
+fn push_diff_line(out: &mut String, line: &git2::DiffLine) {
+    let prefix = match line.origin() {
+        '+' | '-' | ' ' => line.origin(),
+        _ => ' ',
+    };
+    if prefix != ' ' || !line.content().is_empty() {
+        if prefix != ' ' {
+            out.push(prefix);
+        }
+        out.push_str(&String::from_utf8_lossy(line.content()));
+    }
+}
+
+/// The printed patch of a notebook delta with outputs stripped from both sides
+fn stripped_notebook_diff(repo: &Repository, delta: &git2::DiffDelta, workdir: Option<&Path>) -> Option<String> {
+    let (old, new) = diff_attributes::notebook_sides(repo, delta, workdir)?;
+    let mut opts = diff_options(None, false);
+    let mut patch = Patch::from_buffers(
+        old.as_bytes(),
+        delta.old_file().path(),
+        new.as_bytes(),
+        delta.new_file().path(),
+        Some(&mut opts),
+    )
+    .ok()?;
+    let mut text = String::new();
+    patch
+        .print(&mut |_delta, _hunk, line| {
+            push_diff_line(&mut text, &line);
+            true
+        })
+        .ok()?;
+    Some(text)
+}
+
+/// Whether the working tree has any uncommitted change (tracked or untracked)
+#[tauri::command]
+pub async fn git_is_dirty(working_dir: String) -> Result<bool, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    tree_is_dirty(&repo, true).map_err(GitCommandError::from)
+}
+
+/// Count changed files by kind without building the file lists
+#[tauri::command]
+pub async fn git_status_summary(working_dir: String) -> Result<StatusSummary, GitCommandError> {
+    status_summary(&working_dir).map_err(GitCommandError::from)
+}
+
+/// Blocking body of `git_status_summary`
+pub fn status_summary(working_dir: &str) -> Result<StatusSummary, String> {
+    let repo = open_repo(working_dir)?;
+
+    // Untracked directories count once, as `git status` shows them, instead of walking them
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+
+    let mut summary = StatusSummary::default();
+    for entry in statuses.iter() {
+        let status = entry.status();
+        if status.is_conflicted() {
+            summary.conflicted += 1;
+            continue;
+        }
+        if status.is_index_new() || status.is_index_modified() || status.is_index_deleted() || status.is_index_renamed() {
+            summary.staged += 1;
+        }
+        if status.is_wt_new() {
+            summary.untracked += 1;
+        } else if status.is_wt_modified() {
+            summary.modified += 1;
+        } else if status.is_wt_deleted() {
+            summary.deleted += 1;
+        }
+    }
+    Ok(summary)
+}
+
+/// Move files hidden by the workspace's status filters into `stats.filtered` unless disabled
+async fn apply_status_filters(
+    app: &tauri::AppHandle,
+    state: &GitState,
+    working_dir: &str,
+    apply_filters: Option<bool>,
+    mut stats: DiffStats,
+) -> Result<DiffStats, String> {
+    if apply_filters.unwrap_or(true) {
+        if let Some(matcher) = status_filters::matcher_for(app, state, working_dir).await? {
+            status_filters::partition_stats(&mut stats, &matcher);
+        }
+    }
+    Ok(stats)
+}
+
+/// Per-file additions/deletions for staged or unstaged changes, without patch text; `base`
+/// and `strip_notebook_outputs` as in `git_diff`
+#[tauri::command]
+pub async fn git_diff_stats(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    staged: bool,
+    apply_filters: Option<bool>,
+    base: Option<String>,
+    strip_notebook_outputs: Option<bool>,
+) -> Result<DiffStats, GitCommandError> {
+    let stats = {
+        let repo = open_repo(&working_dir)?;
+        let base_tree = base.as_deref().map(|rev| resolve_tree(&repo, rev)).transpose()?;
+        let mut opts = diff_options(None, false);
+        let diff = workspace_diff(&repo, base_tree.as_ref(), staged, &mut opts)?;
+        let workdir = if staged { None } else { repo.workdir() };
+        collect_diff_stats(&repo, &diff, workdir, strip_notebook_outputs.unwrap_or(false))?
+    };
+    apply_status_filters(&app, &state, &working_dir, apply_filters, stats).await.map_err(GitCommandError::from)
+}
+
+/// Per-file additions/deletions between the merge base of `base` and `head`, and `head`
+/// (the same range as `git_diff_commits`)
+#[tauri::command]
+pub async fn git_diff_stats_range(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    base: String,
+    head: String,
+    apply_filters: Option<bool>,
+) -> Result<DiffStats, GitCommandError> {
+    let stats = diff_stats_range(&working_dir, &base, &head)?;
+    apply_status_filters(&app, &state, &working_dir, apply_filters, stats).await.map_err(GitCommandError::from)
+}
+
+/// Unfiltered stats for `git_diff_stats_range`
+pub fn diff_stats_range(working_dir: &str, base: &str, head: &str) -> Result<DiffStats, String> {
+    let repo = open_repo(working_dir)?;
+    let resolve = |rev: &str| {
+        repo.revparse_single(rev)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|e| format!("Failed to resolve '{}': {}", rev, e))
+    };
+    let base_commit = resolve(base)?;
+    let head_commit = resolve(head)?;
+
+    let merge_base = merge_base_or_shallow(&repo, base_commit.id(), head_commit.id())?;
+    let base_tree = repo
+        .find_commit(merge_base)
+        .and_then(|c| c.tree())
+        .map_err(|e| format!("Failed to read merge base tree: {}", e))?;
+    let head_tree = head_commit
+        .tree()
+        .map_err(|e| format!("Failed to read head tree: {}", e))?;
+
+    let mut opts = diff_options(None, false);
+    let mut diff = repo
+        .diff_tree_to_tree(Some(&base_tree), Some(&head_tree), Some(&mut opts))
+        .map_err(|e| format!("Failed to get diff: {}", e))?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))
+        .map_err(|e| format!("Failed to detect renames: {}", e))?;
+
+    collect_diff_stats(&repo, &diff, None, false)
+}
+
+/// Collect UTF-8 paths plus base64-encoded raw paths into one list
+fn collect_path_args(
+    repo: &Repository,
+    paths: &[String],
+    raw_paths: Option<&[String]>,
+) -> Result<Vec<PathBuf>, String> {
+    let mut resolved = repo_path_forms(repo, paths.iter().map(PathBuf::from).collect());
+    for raw in raw_paths.unwrap_or_default() {
+        resolved.push(resolve_path_arg(raw, Some(raw))?);
+    }
+    Ok(resolved)
+}
+
+/// Stage files for commit. Paths are literal; with `glob` they are patterns
+/// (matching files are added, and tracked matches that were deleted are removed).
+#[tauri::command]
+pub async fn git_stage(
+    working_dir: String,
+    paths: Vec<String>,
+    raw_paths: Option<Vec<String>>,
+    glob: Option<bool>,
+) -> Result<bool, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let mut index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+
+    if glob == Some(true) {
+        let patterns = collect_path_args(&repo, &paths, raw_paths.as_deref())?;
+        index
+            .add_all(&patterns, git2::IndexAddOption::DEFAULT, None)
+            .map_err(|e| format!("Failed to stage: {}", e))?;
+        index
+            .update_all(&patterns, None)
+            .map_err(|e| format!("Failed to stage deletions: {}", e))?;
+        index
+            .write()
+            .map_err(|e| format!("Failed to write index: {}", e))?;
+        return Ok(true);
+    }
+
+    // add_path/remove_path take index paths, never patterns
+    for file_path in collect_path_args(&repo, &paths, raw_paths.as_deref())? {
+        // Check if file exists - if not, it might be a deletion
+        let full_path = Path::new(&working_dir).join(&file_path);
+        if full_path.exists() {
+            index
+                .add_path(&file_path)
+                .map_err(|e| format!("Failed to stage {}: {}", file_path.display(), e))?;
+        } else {
+            // File was deleted, remove from index
+            index
+                .remove_path(&file_path)
+                .map_err(|e| format!("Failed to stage deletion of {}: {}", file_path.display(), e))?;
+        }
+    }
+
+    index
+        .write()
+        .map_err(|e| format!("Failed to write index: {}", e))?;
+
+    Ok(true)
+}
+
+/// Unstage files (literal paths, or patterns with `glob`)
+#[tauri::command]
+pub async fn git_unstage(
+    working_dir: String,
+    paths: Vec<String>,
+    raw_paths: Option<Vec<String>>,
+    glob: Option<bool>,
+) -> Result<bool, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let mut file_paths = collect_path_args(&repo, &paths, raw_paths.as_deref())?;
+    if glob != Some(true) {
+        file_paths = file_paths.iter().map(|p| literal_pathspec(p)).collect();
+    }
 
     let head = repo
         .head()
-        .map_err(|e| format!("Failed to get HEAD: {}", e))?;
-    let head_commit = head
-        .peel_to_commit()
-        .map_err(|e| format!("Failed to get HEAD commit: {}", e))?;
+        .map_err(|e| format!("Failed to get HEAD: {}", e))?;
+    let head_commit = head
+        .peel_to_commit()
+        .map_err(|e| format!("Failed to get HEAD commit: {}", e))?;
+
+    repo.reset_default(Some(head_commit.as_object()), &file_paths)
+        .map_err(|e| format!("Failed to unstage: {}", e))?;
+
+    Ok(true)
+}
+
+/// Get branch information
+#[tauri::command]
+pub async fn git_branch_info(working_dir: String) -> Result<BranchInfo, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+
+    // Get current branch
+    let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
+    let current = head
+        .shorthand()
+        .unwrap_or("HEAD")
+        .to_string();
+
+    // Only the current branch is shown with its tracking state; the same lookup as the switcher
+    let tracking = match head.is_branch() {
+        true => repo
+            .find_branch(&current, BranchType::Local)
+            .map(|branch| branch_tracking(&repo, &branch))
+            .unwrap_or_default(),
+        false => BranchTracking::default(),
+    };
+    let (upstream, ahead, behind, upstream_gone) = (tracking.upstream, tracking.ahead, tracking.behind, tracking.gone);
+
+    let recent_branches = recent_branch_names(&repo).into_iter().take(10).collect();
+
+    Ok(BranchInfo {
+        current,
+        upstream,
+        ahead,
+        behind,
+        upstream_gone,
+        recent_branches,
+    })
+}
+
+/// Commit the index on HEAD as `name <email>`
+fn commit_staged(repo: &Repository, name: &str, email: &str, message: &str) -> Result<git2::Oid, String> {
+    // Get the index, picking up whatever was staged since this handle first loaded it
+    let mut index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+    index
+        .read(false)
+        .map_err(|e| format!("Failed to read index: {}", e))?;
+
+    let tree_oid = index
+        .write_tree()
+        .map_err(|e| format!("Failed to write tree: {}", e))?;
+
+    let tree = repo
+        .find_tree(tree_oid)
+        .map_err(|e| format!("Failed to find tree: {}", e))?;
+
+    let signature = Signature::now(name, email)
+        .map_err(|e| format!("Failed to create signature: {}", e))?;
+
+    // Get parent commit (HEAD)
+    let parent = repo
+        .head()
+        .ok()
+        .and_then(|h| h.peel_to_commit().ok());
+
+    let parents: Vec<&git2::Commit> = parent.as_ref().map(|p| vec![p]).unwrap_or_default();
+
+    repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .map_err(|e| format!("Failed to create commit: {}", e))
+}
+
+/// Create a commit with the staged changes, authored by the workspace's commit identity
+/// (see `set_commit_identity`) or git config's user
+#[tauri::command]
+pub async fn git_commit(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    message: String,
+    paths: Option<Vec<String>>,
+    check_protection: Option<bool>,
+) -> Result<CommitResult, GitCommandError> {
+    let current_branch = {
+        let repo = open_repo(&working_dir)?;
+        let head = repo.head().ok();
+        head.and_then(|h| h.shorthand().map(|s| s.to_string()))
+    };
+    if let Some(ref branch) = current_branch {
+        ensure_branch_writable(&state, &working_dir, branch, check_protection).await?;
+    }
+
+    let author = crate::identity::effective(&app, &app.state::<crate::AppState>(), &working_dir).await?;
+    let repo = open_repo(&working_dir)?;
+
+    // Stage specific paths if provided
+    if let Some(ref file_paths) = paths {
+        git_stage(working_dir.clone(), file_paths.clone(), None, None).await?;
+    }
+
+    let commit_oid = commit_staged(&repo, &author.name, &author.email, &message)?;
+
+    crate::integrations::notify(
+        &app,
+        crate::integrations::IntegrationEventKind::CommitCreated,
+        serde_json::json!({
+            "working_dir": working_dir,
+            "commit": commit_oid.to_string(),
+            "branch": current_branch,
+            "summary": message.lines().next().unwrap_or(""),
+        }),
+    )
+    .await;
+
+    Ok(CommitResult {
+        oid: commit_oid.to_string(),
+        author,
+    })
+}
+
+/// Push changes to remote
+#[tauri::command]
+pub async fn git_push(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    set_upstream: bool,
+    branch: Option<String>,
+    op_id: Option<String>,
+    check_protection: Option<bool>,
+) -> Result<bool, GitCommandError> {
+    let target_branch = match branch {
+        Some(ref b) => Some(b.clone()),
+        None => {
+            let repo = open_repo(&working_dir)?;
+            let head = repo.head().ok();
+            head.and_then(|h| h.shorthand().map(|s| s.to_string()))
+        }
+    };
+    if let Some(ref target) = target_branch {
+        ensure_branch_writable(&state, &working_dir, target, check_protection).await?;
+
+        // Without -u, git would push to an upstream that no longer exists and fail obscurely
+        if !set_upstream {
+            let repo = open_repo(&working_dir)?;
+            let gone = repo
+                .find_branch(target, BranchType::Local)
+                .ok()
+                .and_then(|b| gone_upstream(&repo, &b));
+            if let Some(upstream) = gone {
+                return Err(GitCommandError::UpstreamGone {
+                    message: format!("The upstream of '{}' ({}) no longer exists on the remote", target, upstream),
+                    suggestion: "Set a new upstream with git_set_upstream, or push with set_upstream to recreate it".to_string(),
+                    branch: target.clone(),
+                    upstream,
+                });
+            }
+        }
+    }
+
+    // Use git CLI for push as it handles authentication better
+    let mut args = vec!["push".to_string()];
+
+    if set_upstream {
+        args.push("-u".to_string());
+        args.push("origin".to_string());
+
+        if let Some(ref b) = branch {
+            args.push(b.clone());
+        } else {
+            // Get current branch name
+            let repo = open_repo(&working_dir)?;
+            let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
+            let branch_name = head.shorthand().unwrap_or("HEAD").to_string();
+            args.push(branch_name);
+        }
+    }
+
+    args.insert(1, "--progress".to_string());
+    run_reported(&app, &state, "git-push", "git", &args, Some(&working_dir), op_id, "Push failed").await?;
+
+    Ok(true)
+}
+
+/// Get recent commits
+#[tauri::command]
+pub async fn git_log(
+    working_dir: String,
+    limit: u32,
+    branch: Option<String>,
+) -> Result<GitLog, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let tip = log_tip(&repo, branch.as_deref())?;
+    let (commits, _, shallow_boundary_reached) = walk_log(&repo, tip, 0, limit as usize)?;
+    Ok(GitLog {
+        commits,
+        is_shallow: repo.is_shallow(),
+        shallow_boundary_reached,
+    })
+}
+
+/// `git_log` a page at a time. A walk stays on the commit it started from, so commits made
+/// while paging don't shift it; the next walk starts from the new tip.
+#[tauri::command]
+pub async fn git_log_paged(
+    working_dir: String,
+    branch: Option<String>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<GitLogPage, PageError> {
+    let size = pagination::page_size(limit)?;
+    let scope = CursorScope::new(
+        "git_log",
+        &[&pagination::path_filter(&working_dir), branch.as_deref().unwrap_or_default()],
+    );
+    let position: Option<LogPosition> = scope.decode(cursor.as_deref())?;
+    let repo = open_repo(&working_dir)?;
+    let (tip, skip) = match position {
+        Some(position) => {
+            let tip = git2::Oid::from_str(&position.tip)
+                .ok()
+                .filter(|oid| repo.find_commit(*oid).is_ok())
+                .ok_or_else(|| PageError::ExpiredCursor {
+                    message: "The commit this log walk started from is gone; start from the first page".to_string(),
+                })?;
+            (tip, position.skip)
+        }
+        None => (log_tip(&repo, branch.as_deref())?, 0),
+    };
+    let (commits, more, shallow_boundary_reached) = walk_log(&repo, tip, skip, size)?;
+    let next = more.then(|| LogPosition {
+        tip: tip.to_string(),
+        skip: skip + commits.len(),
+    });
+    Ok(GitLogPage {
+        page: scope.page(commits, None, next),
+        is_shallow: repo.is_shallow(),
+        shallow_boundary_reached,
+    })
+}
+
+/// Fetch from remote
+#[tauri::command]
+pub async fn git_fetch(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    op_id: Option<String>,
+    prune_gone_upstreams: Option<bool>,
+) -> Result<bool, GitCommandError> {
+    run_reported(
+        &app,
+        &state,
+        "git-fetch",
+        "git",
+        &["fetch", "--progress", "--all", "--prune"],
+        Some(&working_dir),
+        op_id,
+        "Fetch failed",
+    )
+    .await?;
+
+    // --prune removed deleted remote branches; optionally drop the tracking config pointing at them
+    if prune_gone_upstreams.unwrap_or(false) {
+        let repo = open_repo(&working_dir)?;
+        let branches = repo
+            .branches(Some(BranchType::Local))
+            .map_err(|e| format!("Failed to list branches: {}", e))?;
+        for (mut branch, _) in branches.flatten() {
+            if gone_upstream(&repo, &branch).is_some() {
+                branch
+                    .set_upstream(None)
+                    .map_err(|e| format!("Failed to clear upstream: {}", e))?;
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Pack loose objects and consolidate packs (`git gc`), reported as a `git-gc` operation
+#[tauri::command]
+pub async fn git_gc(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    op_id: Option<String>,
+) -> Result<bool, GitCommandError> {
+    run_reported(&app, &state, "git-gc", "git", &["gc", "--progress"], Some(&working_dir), op_id, "Cleanup failed").await?;
+    Ok(true)
+}
+
+/// Point a local branch at a new upstream (e.g. "origin/main"), or stop tracking when `upstream` is None
+#[tauri::command]
+pub async fn git_set_upstream(
+    working_dir: String,
+    branch: String,
+    upstream: Option<String>,
+) -> Result<bool, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let mut local = repo
+        .find_branch(&branch, BranchType::Local)
+        .map_err(|e| format!("Branch not found: {}", e))?;
+    if let Some(ref upstream) = upstream {
+        repo.find_branch(upstream, BranchType::Remote)
+            .map_err(|_| format!("Remote branch not found: {} (fetch first?)", upstream))?;
+    }
+    local
+        .set_upstream(upstream.as_deref())
+        .map_err(|e| format!("Failed to set upstream: {}", e))?;
+    Ok(true)
+}
+
+/// Fetch more history into a shallow clone: `depth` more commits below the boundary,
+/// or everything (`--unshallow`) when no depth is given. Git's progress is reported as
+/// `progress` events. Returns whether the clone is still shallow.
+#[tauri::command]
+pub async fn git_fetch_deepen(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    depth: Option<u32>,
+    op_id: Option<String>,
+) -> Result<bool, GitCommandError> {
+    if !open_repo(&working_dir)?.is_shallow() {
+        return Ok(false);
+    }
+    let deepen = deepen_arg(depth)?;
+
+    run_reported(
+        &app,
+        &state,
+        "git-fetch-deepen",
+        "git",
+        &["fetch", "--progress", &deepen],
+        Some(&working_dir),
+        op_id,
+        "Fetch failed",
+    )
+    .await?;
+
+    Ok(open_repo(&working_dir)?.is_shallow())
+}
+
+/// Pull from remote
+#[tauri::command]
+pub async fn git_pull(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    op_id: Option<String>,
+) -> Result<bool, GitCommandError> {
+    run_reported(&app, &state, "git-pull", "git", &["pull", "--progress"], Some(&working_dir), op_id, "Pull failed").await?;
+
+    Ok(true)
+}
+
+/// Discard unstaged changes in a file (restore it from the index). With `dry_run` nothing is
+/// touched and the preview lists what would be. The discard itself requires that preview's
+/// token as `preview_token`, and fails instead of acting on files that changed since.
+#[tauri::command]
+pub async fn git_discard(
+    working_dir: String,
+    file_path: String,
+    raw_path: Option<String>,
+    glob: Option<bool>,
+    dry_run: Option<bool>,
+    preview_token: Option<String>,
+) -> Result<DestructionPreview, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let path = match raw_path {
+        Some(ref raw) => resolve_path_arg(&file_path, Some(raw))?,
+        None => repo_path_form(&repo, PathBuf::from(&file_path)),
+    };
+
+    let glob = glob == Some(true);
+    let in_index = glob
+        || repo
+            .index()
+            .map_err(|e| format!("Failed to get index: {}", e))?
+            .get_path(&path, 0)
+            .is_some();
+    if !in_index {
+        return Err(format!("Discard failed: {} is not tracked by git", path.display()).into());
+    }
+
+    let (paths, mut preview) = plan_discard(&repo, &if glob { path } else { literal_pathspec(&path) })?;
+    if preview_token.as_ref().is_some_and(|token| *token != preview.token) {
+        return Err("Discard failed: the files changed since the preview; preview again".to_string().into());
+    }
+    if dry_run == Some(true) {
+        return Ok(preview);
+    }
+    if preview_token.is_none() {
+        return Err("Discard failed: preview it with dry_run first and pass back its token".to_string().into());
+    }
+
+    // Checkout runs the smudge/eol filters, so the file comes back with the
+    // line endings the repo's attributes ask for rather than the raw blob bytes.
+    // Only the planned files are checked out, so the preview is exactly what happens.
+    if !paths.is_empty() {
+        let mut checkout = git2::build::CheckoutBuilder::new();
+        checkout.force();
+        for path in &paths {
+            checkout.path(literal_pathspec(path));
+        }
+        repo.checkout_index(None, Some(&mut checkout))
+            .map_err(|e| format!("Discard failed: {}", e))?;
+    }
+
+    preview.executed = true;
+    Ok(preview)
+}
+
+/// Effective text/eol attributes and autocrlf config for paths, for debugging line endings
+#[tauri::command]
+pub async fn git_check_attr(working_dir: String, paths: Vec<String>) -> Result<LineEndingReport, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let config = repo
+        .config()
+        .map_err(|e| format!("Failed to read git config: {}", e))?;
+
+    let describe = |path: &Path, name: &str| -> Result<String, String> {
+        let value = repo
+            .get_attr(path, name, git2::AttrCheckFlags::FILE_THEN_INDEX)
+            .map_err(|e| format!("Failed to read attribute '{}' for {}: {}", name, path.display(), e))?;
+        Ok(match git2::AttrValue::from_string(value) {
+            git2::AttrValue::True => "set".to_string(),
+            git2::AttrValue::False => "unset".to_string(),
+            git2::AttrValue::String(v) => v.to_string(),
+            git2::AttrValue::Bytes(v) => String::from_utf8_lossy(v).to_string(),
+            git2::AttrValue::Unspecified => "unspecified".to_string(),
+        })
+    };
+
+    let mut files = Vec::new();
+    for path in &paths {
+        let p = Path::new(path);
+        files.push(PathAttributes {
+            path: path.clone(),
+            text: describe(p, "text")?,
+            eol: describe(p, "eol")?,
+            crlf: describe(p, "crlf")?,
+        });
+    }
+
+    Ok(LineEndingReport {
+        core_autocrlf: config.get_string("core.autocrlf").ok(),
+        core_eol: config.get_string("core.eol").ok(),
+        files,
+    })
+}
+
+/// Check if gh CLI is available and authenticated (see get_gh_auth_status for accounts and scopes)
+#[tauri::command]
+pub async fn check_gh_cli_available(state: State<'_, GitState>) -> Result<bool, String> {
+    Ok(crate::gh_auth::auth_status(&state, false).await.authenticated)
+}
+
+/// Create a pull request using gh CLI
+#[tauri::command]
+pub async fn create_pull_request(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    options: PRCreationOptions,
+) -> Result<String, String> {
+    forge::ensure_pull_requests(&working_dir).await?;
+    let opened = serde_json::json!({
+        "working_dir": working_dir,
+        "base": options.base,
+        "head": options.head,
+        "title": options.title,
+        "draft": options.draft,
+    });
+    let mut args = vec![
+        "pr".to_string(),
+        "create".to_string(),
+        "--base".to_string(),
+        options.base,
+        "--head".to_string(),
+        options.head,
+        "--title".to_string(),
+        options.title,
+        "--body".to_string(),
+        options.body,
+    ];
+
+    if options.draft {
+        args.push("--draft".to_string());
+    }
+
+    if let Some(reviewers) = options.reviewers {
+        if !reviewers.is_empty() {
+            args.push("--reviewer".to_string());
+            args.push(reviewers.join(","));
+        }
+    }
+
+    if let Some(labels) = options.labels {
+        if !labels.is_empty() {
+            args.push("--label".to_string());
+            args.push(labels.join(","));
+        }
+    }
+
+    let output = run_external(&state, "gh", &args, Some(&working_dir), &[], None, None).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("PR creation failed: {}", stderr));
+    }
+
+    let pr_url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let mut opened = opened;
+    opened["url"] = serde_json::Value::from(pr_url.clone());
+    crate::integrations::notify(&app, crate::integrations::IntegrationEventKind::PrOpened, opened).await;
+    Ok(pr_url)
+}
+
+/// Get list of available branches.
+/// `names_only` returns sorted branch names without computing tracking info;
+/// otherwise `sort` is "recent" (default) or "name".
+#[tauri::command]
+pub async fn git_list_branches(
+    working_dir: String,
+    names_only: Option<bool>,
+    sort: Option<String>,
+) -> Result<BranchList, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+
+    if names_only.unwrap_or(false) {
+        let mut branches = Vec::new();
+        if let Ok(branch_iter) = repo.branches(Some(BranchType::Local)) {
+            for (branch, _) in branch_iter.flatten() {
+                if let Some(name) = branch.name().ok().flatten() {
+                    branches.push(name.to_string());
+                }
+            }
+        }
+        branches.sort();
+        return Ok(BranchList::Names(branches));
+    }
+
+    let alphabetical = match sort.as_deref() {
+        None | Some("recent") => false,
+        Some("name") => true,
+        Some(other) => return Err(format!("Invalid branch sort: {}", other).into()),
+    };
+
+    Ok(BranchList::Detailed(collect_branches(&repo, alphabetical)))
+}
+
+/// Get the diff between two commits or branches
+#[tauri::command]
+pub async fn git_diff_commits(
+    state: State<'_, GitState>,
+    working_dir: String,
+    base: String,
+    head: String,
+) -> Result<String, GitCommandError> {
+    let range = format!("{}...{}", base, head);
+    let output = run_external(&state, "git", &["diff", &range], Some(&working_dir), &[], None, None).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let shallow = open_repo(&working_dir).map(|repo| repo.is_shallow()).unwrap_or(false);
+        if shallow && stderr.contains("no merge base") {
+            return Err(format!(
+                "{}the merge base of {} and {} is beyond the shallow clone's history; fetch more history to compare them",
+                SHALLOW_HISTORY_PREFIX, base, head
+            ).into());
+        }
+        return Err(format!("Diff failed: {}", stderr).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Scan staged changes for things worth warning about before committing
+#[tauri::command]
+pub async fn git_precommit_scan(
+    app: tauri::AppHandle,
+    working_dir: String,
+) -> Result<Vec<PrecommitWarning>, GitCommandError> {
+    let patterns = crate::sensitive::load_patterns(&app).await?;
+    let matcher = crate::sensitive::build_matcher(&patterns)?;
+
+    let repo = open_repo(&working_dir)?;
+
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+
+    let statuses = repo
+        .statuses(Some(&mut opts))
+        .map_err(|e| format!("Failed to get statuses: {}", e))?;
+
+    let mut warnings = Vec::new();
+
+    for entry in statuses.iter() {
+        let status = entry.status();
+        if !(status.is_index_new() || status.is_index_modified() || status.is_index_renamed()) {
+            continue;
+        }
+
+        let (path, _) = path_from_bytes(entry.path_bytes());
+        if crate::sensitive::is_sensitive(&matcher, Path::new(&path)) {
+            warnings.push(PrecommitWarning {
+                message: format!("{} looks like it contains secrets and is staged for commit", path),
+                path,
+                kind: "sensitive_file".to_string(),
+            });
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Cancel a running git/gh operation by the operation id of its `progress` events
+#[tauri::command]
+pub async fn cancel_git_operation(state: State<'_, GitState>, op_id: String) -> Result<bool, String> {
+    let cancel = state.operations.lock().await.remove(&op_id);
+    Ok(match cancel {
+        Some(tx) => tx.send(()).is_ok(),
+        None => false,
+    })
+}
+
+// ============================================================================
+// Rebase Commands
+// ============================================================================
+
+/// Directory inside .git holding the todo and message files for a mensa-driven rebase
+fn rebase_scratch_dir(repo: &Repository) -> PathBuf {
+    repo.path().join("mensa-rebase")
+}
+
+/// Quote a string for the POSIX shell git uses to run editors and exec lines
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Commits between `base` and HEAD, oldest first, refusing ranges that can't be
+/// rewritten safely (merge commits, commits already on the upstream)
+fn rebase_range(repo: &Repository, base: &str) -> Result<(git2::Oid, Vec<git2::Oid>), String> {
+    let base_oid = repo
+        .revparse_single(base)
+        .and_then(|o| o.peel_to_commit())
+        .map_err(|e| format!("Base revision not found: {}", e))?
+        .id();
+
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk
+        .set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)
+        .map_err(|e| format!("Failed to sort revwalk: {}", e))?;
+    revwalk
+        .push_head()
+        .map_err(|e| format!("Failed to push HEAD: {}", e))?;
+    revwalk
+        .hide(base_oid)
+        .map_err(|e| format!("Failed to hide base: {}", e))?;
+
+    let oids = revwalk
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to walk commits: {}", e))?;
+
+    // Commits already on the upstream are shared history and must not be rewritten
+    let upstream_oid = repo
+        .head()
+        .ok()
+        .and_then(|h| h.shorthand().map(String::from))
+        .and_then(|name| repo.find_branch(&name, BranchType::Local).ok())
+        .and_then(|b| b.upstream().ok())
+        .and_then(|u| u.get().target());
+
+    for oid in &oids {
+        let commit = repo
+            .find_commit(*oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        if commit.parent_count() > 1 {
+            return Err(format!(
+                "Cannot rebase: {} is a merge commit",
+                short_hash(repo, *oid)
+            ));
+        }
+        if let Some(upstream) = upstream_oid {
+            if upstream == *oid || repo.graph_descendant_of(upstream, *oid).unwrap_or(false) {
+                return Err(format!(
+                    "Cannot rebase: {} is already on the upstream branch",
+                    short_hash(repo, *oid)
+                ));
+            }
+        }
+    }
+
+    Ok((base_oid, oids))
+}
+
+/// Inspect the repository after a rebase command and describe where it stopped
+fn rebase_outcome(working_dir: &str) -> Result<RebaseResult, String> {
+    let repo = open_repo(working_dir)?;
+
+    let in_progress = matches!(
+        repo.state(),
+        git2::RepositoryState::Rebase
+            | git2::RepositoryState::RebaseInteractive
+            | git2::RepositoryState::RebaseMerge
+    );
+
+    if !in_progress {
+        let _ = std::fs::remove_dir_all(rebase_scratch_dir(&repo));
+        let head = repo.head().ok().and_then(|h| h.target()).map(|oid| oid.to_string());
+        return Ok(RebaseResult {
+            status: "completed".to_string(),
+            conflicts: Vec::new(),
+            head,
+        });
+    }
+
+    let index = repo
+        .index()
+        .map_err(|e| format!("Failed to get index: {}", e))?;
+    let mut conflicts = Vec::new();
+    if let Ok(iter) = index.conflicts() {
+        for conflict in iter.flatten() {
+            let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+            if let Some(entry) = entry {
+                conflicts.push(path_from_bytes(&entry.path).0);
+            }
+        }
+    }
+
+    Ok(RebaseResult {
+        status: "conflicts".to_string(),
+        conflicts,
+        head: None,
+    })
+}
+
+/// Run a `git rebase` invocation with editors disabled and report the outcome
+async fn run_rebase_command(
+    state: &GitState,
+    working_dir: &str,
+    args: &[&str],
+    sequence_editor: Option<String>,
+) -> Result<RebaseResult, String> {
+    let mut envs = vec![("GIT_EDITOR", "true")];
+    if let Some(ref editor) = sequence_editor {
+        envs.push(("GIT_SEQUENCE_EDITOR", editor.as_str()));
+    }
+
+    let output = run_external(state, "git", args, Some(working_dir), &envs, None, None).await?;
+
+    let outcome = rebase_outcome(working_dir)?;
+    if !output.status.success() && outcome.status == "completed" {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Rebase failed: {}", stderr));
+    }
+
+    Ok(outcome)
+}
+
+/// List the commits between `base` and HEAD as an editable rebase plan (oldest first)
+#[tauri::command]
+pub async fn git_rebase_plan(
+    working_dir: String,
+    base: String,
+) -> Result<Vec<RebasePlanEntry>, GitCommandError> {
+    let repo = open_repo(&working_dir)?;
+    let (_, oids) = rebase_range(&repo, &base)?;
+
+    let mut entries = Vec::new();
+    for oid in oids {
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        entries.push(RebasePlanEntry {
+            hash: oid.to_string(),
+            short_hash: short_hash(&repo, oid),
+            summary: commit.summary().unwrap_or("").to_string(),
+            message: commit.message().unwrap_or("").trim().to_string(),
+            author: commit.author().name().unwrap_or("Unknown").to_string(),
+            timestamp: commit.time().seconds(),
+            action: "pick".to_string(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Execute an edited rebase plan non-interactively.
+/// Stops with the conflicted paths when a step doesn't apply cleanly.
+#[tauri::command]
+pub async fn git_rebase_execute(
+    state: State<'_, GitState>,
+    working_dir: String,
+    base: String,
+    plan: Vec<RebasePlanStep>,
+) -> Result<RebaseResult, GitCommandError> {
+    let (base_oid, sequence_editor) = {
+        let repo = open_repo(&working_dir)?;
+        if tree_is_dirty(&repo, false)? {
+            return Err("Cannot rebase with uncommitted changes; commit or stash them first".to_string().into());
+        }
+        let (base_oid, oids) = rebase_range(&repo, &base)?;
+
+        // The plan must cover exactly the original commits, each once
+        let mut expected: Vec<String> = oids.iter().map(|o| o.to_string()).collect();
+        let mut given: Vec<String> = plan.iter().map(|s| s.hash.clone()).collect();
+        expected.sort();
+        given.sort();
+        if expected != given {
+            return Err("Rebase plan must contain exactly the commits between base and HEAD".to_string().into());
+        }
+
+        let scratch = rebase_scratch_dir(&repo);
+        std::fs::create_dir_all(&scratch)
+            .map_err(|e| format!("Failed to prepare rebase files: {}", e))?;
+
+        let mut todo = String::new();
+        let mut has_target = false;
+        for (i, step) in plan.iter().enumerate() {
+            let action = match step.action.as_str() {
+                "pick" | "reword" => "pick",
+                "squash" | "fixup" if !has_target => {
+                    return Err(format!(
+                        "Cannot {} {}: there is no earlier commit to combine with",
+                        step.action,
+                        &step.hash[..7.min(step.hash.len())]
+                    ).into());
+                }
+                "squash" => "squash",
+                "fixup" => "fixup",
+                "drop" => "drop",
+                other => return Err(format!("Invalid rebase action: {}", other).into()),
+            };
+            if action != "drop" {
+                has_target = true;
+            }
+            todo.push_str(&format!("{} {}\n", action, step.hash));
+
+            // Reworded messages (and replacement squash messages) are applied with an amend
+            let new_message = step.new_message.as_ref().filter(|m| !m.trim().is_empty());
+            if let (Some(message), true) = (new_message, action != "drop") {
+                let message_path = scratch.join(format!("message-{}", i));
+                std::fs::write(&message_path, message)
+                    .map_err(|e| format!("Failed to write rebase message: {}", e))?;
+                todo.push_str(&format!(
+                    "exec git commit --amend --allow-empty -F {}\n",
+                    shell_quote(&message_path.to_string_lossy())
+                ));
+            } else if step.action == "reword" {
+                return Err(format!(
+                    "Reword of {} needs a new message",
+                    &step.hash[..7.min(step.hash.len())]
+                ).into());
+            }
+        }
+
+        let todo_path = scratch.join("git-rebase-todo");
+        std::fs::write(&todo_path, todo)
+            .map_err(|e| format!("Failed to write rebase plan: {}", e))?;
+
+        let editor = format!("cp {}", shell_quote(&todo_path.to_string_lossy()));
+        (base_oid, editor)
+    };
+
+    run_rebase_command(
+        &state,
+        &working_dir,
+        &["rebase", "-i", "--no-autosquash", &base_oid.to_string()],
+        Some(sequence_editor),
+    )
+    .await.map_err(GitCommandError::from)
+}
+
+/// Continue a stopped rebase once conflicts are resolved and staged
+#[tauri::command]
+pub async fn git_rebase_continue(
+    state: State<'_, GitState>,
+    working_dir: String,
+) -> Result<RebaseResult, GitCommandError> {
+    {
+        let repo = open_repo(&working_dir)?;
+        let index = repo
+            .index()
+            .map_err(|e| format!("Failed to get index: {}", e))?;
+        if index.has_conflicts() {
+            return Err("Resolve and stage all conflicted files before continuing".to_string().into());
+        }
+    }
 
-    repo.reset_default(Some(&head_commit.as_object()), paths.iter().map(|s| Path::new(s)))
-        .map_err(|e| format!("Failed to unstage: {}", e))?;
+    run_rebase_command(&state, &working_dir, &["rebase", "--continue"], None).await.map_err(GitCommandError::from)
+}
+
+/// Abort an in-progress rebase and restore the original branch
+#[tauri::command]
+pub async fn git_rebase_abort(state: State<'_, GitState>, working_dir: String) -> Result<bool, GitCommandError> {
+    let output = run_external(&state, "git", &["rebase", "--abort"], Some(&working_dir), &[], None, None).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Rebase abort failed: {}", stderr).into());
+    }
+
+    if let Ok(repo) = open_repo(&working_dir) {
+        let _ = std::fs::remove_dir_all(rebase_scratch_dir(&repo));
+    }
 
     Ok(true)
 }
 
-/// Get branch information
-#[tauri::command]
-pub async fn git_branch_info(working_dir: String) -> Result<BranchInfo, String> {
-    let repo = open_repo(&working_dir)?;
+// ============================================================================
+// Branch Protection
+// ============================================================================
 
-    // Get current branch
-    let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
-    let current = head
-        .shorthand()
-        .unwrap_or("HEAD")
-        .to_string();
+/// owner/repo of the origin remote when it points at GitHub
+fn github_slug(repo: &Repository) -> Option<(String, String)> {
+    let remote = repo.find_remote("origin").ok()?;
+    let url = remote.url()?;
+    let re = Regex::new(r"github\.com[:/]([^/]+)/([^/]+?)(?:\.git)?/?$").ok()?;
+    let caps = re.captures(url)?;
+    Some((caps[1].to_string(), caps[2].to_string()))
+}
 
-    // Get upstream
-    let upstream = if let Ok(branch) = repo.find_branch(&current, BranchType::Local) {
-        branch
-            .upstream()
-            .ok()
-            .and_then(|u| u.name().ok().flatten().map(String::from))
-    } else {
-        None
-    };
+/// The remote's default branch (origin/HEAD), if the remote has one set
+pub fn remote_default_branch(repo: &Repository) -> Option<String> {
+    repo.find_reference("refs/remotes/origin/HEAD")
+        .ok()
+        .and_then(|r| r.symbolic_target().map(|t| t.to_string()))
+        .and_then(|t| t.strip_prefix("refs/remotes/origin/").map(|b| b.to_string()))
+}
 
-    // Get ahead/behind
-    let (ahead, behind) = get_branch_ahead_behind(&repo);
+/// The remote's default branch (origin/HEAD), falling back to main/master
+fn is_default_branch(repo: &Repository, branch: &str) -> bool {
+    match remote_default_branch(repo) {
+        Some(default) => default == branch,
+        None => branch == "main" || branch == "master",
+    }
+}
 
-    // Get recent branches (local branches sorted by last commit time)
-    let mut recent_branches = Vec::new();
-    if let Ok(branches) = repo.branches(Some(BranchType::Local)) {
-        let mut branch_times: Vec<(String, i64)> = Vec::new();
-
-        for branch_result in branches {
-            if let Ok((branch, _)) = branch_result {
-                // Clone the name before moving branch
-                let name = branch.name().ok().flatten().map(|s| s.to_string());
-                if let Some(name) = name {
-                    if let Ok(reference) = branch.into_reference().peel_to_commit() {
-                        branch_times.push((name, reference.time().seconds()));
-                    }
+fn parse_branch_protection(branch: &str, json: &serde_json::Value) -> BranchProtection {
+    let mut checks: Vec<String> = json["required_status_checks"]["contexts"]
+        .as_array()
+        .map(|a| a.iter().filter_map(|c| c.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    if let Some(extra) = json["required_status_checks"]["checks"].as_array() {
+        for check in extra {
+            if let Some(context) = check["context"].as_str() {
+                if !checks.iter().any(|c| c == context) {
+                    checks.push(context.to_string());
                 }
             }
         }
+    }
 
-        branch_times.sort_by(|a, b| b.1.cmp(&a.1));
-        recent_branches = branch_times.into_iter().take(10).map(|(name, _)| name).collect();
+    let reviews = &json["required_pull_request_reviews"];
+    BranchProtection {
+        branch: branch.to_string(),
+        status: "protected".to_string(),
+        restricts_pushes: !json["restrictions"].is_null()
+            || !reviews.is_null()
+            || json["lock_branch"]["enabled"].as_bool().unwrap_or(false),
+        required_status_checks: checks,
+        required_review_count: reviews["required_approving_review_count"].as_u64().unwrap_or(0) as u32,
+        enforce_admins: json["enforce_admins"]["enabled"].as_bool().unwrap_or(false),
+        message: None,
     }
+}
 
-    Ok(BranchInfo {
-        current,
-        upstream,
-        ahead,
-        behind,
-        recent_branches,
-    })
+/// Look up (or reuse a cached) protection record for a branch
+async fn branch_protection(state: &GitState, working_dir: &str, branch: &str) -> Result<BranchProtection, String> {
+    let slug = {
+        let repo = open_repo(working_dir)?;
+        github_slug(&repo)
+    };
+    let (owner, name) = match slug {
+        Some(slug) => slug,
+        None => {
+            return Ok(BranchProtection {
+                branch: branch.to_string(),
+                status: "unknown".to_string(),
+                restricts_pushes: false,
+                required_status_checks: Vec::new(),
+                required_review_count: 0,
+                enforce_admins: false,
+                message: Some("Origin is not a GitHub remote".to_string()),
+            })
+        }
+    };
+
+    let cache_key = format!("{}/{}#{}", owner, name, branch);
+    if let Some((fetched_at, cached)) = state.protection_cache.lock().await.get(&cache_key) {
+        if fetched_at.elapsed() < PROTECTION_CACHE_TTL {
+            return Ok(cached.clone());
+        }
+    }
+
+    let endpoint = format!("repos/{}/{}/branches/{}/protection", owner, name, branch);
+    let output = run_external(state, "gh", &["api", &endpoint], Some(working_dir), &[], None, None).await?;
+
+    let mut protection = BranchProtection {
+        branch: branch.to_string(),
+        status: "unprotected".to_string(),
+        restricts_pushes: false,
+        required_status_checks: Vec::new(),
+        required_review_count: 0,
+        enforce_admins: false,
+        message: None,
+    };
+
+    if output.status.success() {
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| format!("Failed to parse branch protection JSON: {}", e))?;
+        protection = parse_branch_protection(branch, &json);
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("HTTP 403") {
+            // Not cached: the user may fix their token scopes right away
+            protection.status = "insufficient_scope".to_string();
+            protection.message = Some("The GitHub token can't read branch protection (needs admin or repo scope)".to_string());
+            return Ok(protection);
+        } else if !stderr.contains("HTTP 404") {
+            return Err(format!("Failed to fetch branch protection: {}", stderr.trim()));
+        }
+    }
+
+    state
+        .protection_cache
+        .lock()
+        .await
+        .insert(cache_key, (Instant::now(), protection.clone()));
+    Ok(protection)
+}
+
+/// Refuse to commit/push to a push-restricted branch. Checked by default only on the
+/// default branch; lookup failures never block the operation.
+async fn ensure_branch_writable(
+    state: &GitState,
+    working_dir: &str,
+    branch: &str,
+    check_protection: Option<bool>,
+) -> Result<(), GitCommandError> {
+    let should_check = match check_protection {
+        Some(check) => check,
+        None => {
+            let repo = open_repo(working_dir)?;
+            is_default_branch(&repo, branch)
+        }
+    };
+    if !should_check {
+        return Ok(());
+    }
+
+    match branch_protection(state, working_dir, branch).await {
+        Ok(protection) if protection.status == "protected" && protection.restricts_pushes => {
+            Err(GitCommandError::ProtectedBranch {
+                branch: branch.to_string(),
+                message: format!("{} is protected and doesn't accept direct pushes", branch),
+                suggestion: "Create a feature branch for these changes and open a pull request".to_string(),
+            })
+        }
+        _ => Ok(()),
+    }
 }
 
-/// Create a commit with the staged changes
+/// Get the protection rules for a branch (defaults to the current branch)
 #[tauri::command]
-pub async fn git_commit(
+pub async fn get_branch_protection(
+    state: State<'_, GitState>,
     working_dir: String,
-    message: String,
-    paths: Option<Vec<String>>,
-) -> Result<String, String> {
+    branch: Option<String>,
+) -> Result<BranchProtection, GitCommandError> {
+    let branch = match branch {
+        Some(branch) => branch,
+        None => {
+            let repo = open_repo(&working_dir)?;
+            let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
+            head.shorthand().unwrap_or("HEAD").to_string()
+        }
+    };
+    branch_protection(&state, &working_dir, &branch).await.map_err(GitCommandError::from)
+}
+
+/// Create a branch at HEAD and switch to it. The working tree and index are left
+/// as they are, so uncommitted changes move to the new branch.
+#[tauri::command]
+pub async fn git_create_branch_and_move_changes(
+    working_dir: String,
+    new_branch: String,
+) -> Result<String, GitCommandError> {
     let repo = open_repo(&working_dir)?;
+    let head_commit = repo
+        .head()
+        .and_then(|h| h.peel_to_commit())
+        .map_err(|e| format!("Failed to get HEAD commit: {}", e))?;
 
-    // Stage specific paths if provided
-    if let Some(ref file_paths) = paths {
-        git_stage(working_dir.clone(), file_paths.clone()).await?;
+    let branch = repo
+        .branch(&new_branch, &head_commit, false)
+        .map_err(|e| format!("Failed to create branch: {}", e))?;
+    let refname = branch
+        .get()
+        .name()
+        .ok_or_else(|| "Branch name is not valid UTF-8".to_string())?
+        .to_string();
+
+    repo.set_head(&refname)
+        .map_err(|e| format!("Failed to switch to {}: {}", new_branch, e))?;
+
+    Ok(new_branch)
+}
+
+// ============================================================================
+// PR Review Commands
+// ============================================================================
+
+/// Parse a GitHub PR URL to extract owner, repo, and PR number
+pub fn parse_pr_url(pr_url: &str) -> Result<(String, String, String), String> {
+    // Match patterns like:
+    // https://github.com/owner/repo/pull/123
+    // github.com/owner/repo/pull/123
+    let re = Regex::new(r"(?:https?://)?github\.com/([^/]+)/([^/]+)/pull/(\d+)")
+        .map_err(|e| format!("Invalid regex: {}", e))?;
+
+    if let Some(caps) = re.captures(pr_url) {
+        let owner = caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default();
+        let repo = caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default();
+        let pr_number = caps.get(3).map(|m| m.as_str().to_string()).unwrap_or_default();
+
+        if !owner.is_empty() && !repo.is_empty() && !pr_number.is_empty() {
+            return Ok((owner, repo, pr_number));
+        }
     }
 
-    // Get the index
-    let mut index = repo
-        .index()
-        .map_err(|e| format!("Failed to get index: {}", e))?;
+    Err(format!("Invalid PR URL format: {}", pr_url))
+}
 
-    let tree_oid = index
-        .write_tree()
-        .map_err(|e| format!("Failed to write tree: {}", e))?;
+/// Stable key for a PR or MR regardless of URL form ("owner/repo#number" on GitHub, lowercased)
+pub fn normalize_pr_url(pr_url: &str) -> Result<String, String> {
+    Ok(forge::parse_review_url(pr_url)?.key())
+}
 
-    let tree = repo
-        .find_tree(tree_oid)
-        .map_err(|e| format!("Failed to find tree: {}", e))?;
+/// Fetch PR info (through gh, or the GitLab API for a merge request), reusing a recent
+/// result unless `refresh` is set
+pub async fn load_pr_info(
+    app: &tauri::AppHandle,
+    state: &GitState,
+    pr_url: &str,
+    refresh: bool,
+) -> Result<GhPRInfo, String> {
+    let target = forge::parse_review_url(pr_url)?;
+    let key = target.key();
+    if !refresh {
+        if let Some((fetched_at, cached)) = state.pr_info_cache.lock().await.get(&key) {
+            if fetched_at.elapsed() < PR_INFO_CACHE_TTL {
+                return Ok(cached.clone());
+            }
+        }
+    }
 
-    // Get signature from git config
-    let signature = repo
-        .signature()
-        .or_else(|_| Signature::now("Mensa User", "user@mensa.local"))
-        .map_err(|e| format!("Failed to create signature: {}", e))?;
+    let info = match target.provider {
+        forge::Provider::Github => load_github_pr_info(state, pr_url).await?,
+        forge::Provider::Gitlab => forge::fetch_mr_info(app, &target).await?,
+        provider => return Err(forge::ReviewError::unsupported(provider, "Loading pull requests").to_string()),
+    };
+    state
+        .pr_info_cache
+        .lock()
+        .await
+        .insert(key, (Instant::now(), info.clone()));
+    Ok(info)
+}
 
-    // Get parent commit (HEAD)
-    let parent = repo
-        .head()
-        .ok()
-        .and_then(|h| h.peel_to_commit().ok());
+async fn load_github_pr_info(state: &GitState, pr_url: &str) -> Result<GhPRInfo, String> {
+    let (owner, repo, pr_number) = parse_pr_url(pr_url)?;
+    let repo_arg = format!("{}/{}", owner, repo);
+    let args = [
+        "pr",
+        "view",
+        &pr_number,
+        "--repo",
+        &repo_arg,
+        "--json",
+        PR_INFO_FIELDS,
+    ];
+    let output = run_external(state, "gh", &args, None, &[], None, None).await?;
 
-    let parents: Vec<&git2::Commit> = parent.as_ref().map(|p| vec![p]).unwrap_or_default();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to fetch PR info: {}", stderr));
+    }
 
-    let commit_oid = repo
-        .commit(
-            Some("HEAD"),
-            &signature,
-            &signature,
-            &message,
-            &tree,
-            &parents,
-        )
-        .map_err(|e| format!("Failed to create commit: {}", e))?;
+    let json_str = String::from_utf8_lossy(&output.stdout);
+
+    // Parse the JSON response
+    let json: serde_json::Value = serde_json::from_str(&json_str)
+        .map_err(|e| format!("Failed to parse PR info JSON: {}", e))?;
 
-    Ok(commit_oid.to_string())
+    gh_json::pr_info(json)
 }
 
-/// Push changes to remote
+/// Take what a batch prefetched for a PR: its info, or with `diff` its diff
+async fn take_prefetched<T>(
+    state: &GitState,
+    pr_url: &str,
+    take: impl FnOnce(&mut PrefetchedPr) -> Option<T>,
+) -> Option<T> {
+    let key = forge::parse_review_url(pr_url).ok()?.key();
+    let mut prefetch = state.pr_prefetch.lock().await;
+    prefetch.retain(|_, (fetched_at, _, _)| fetched_at.elapsed() < PR_PREFETCH_TTL);
+    prefetch.get_mut(&key).and_then(take)
+}
+
+/// List up to `limit` of a repository's PRs through gh, newest first; a list younger than the
+/// PR info TTL that holds them is reused unless `refresh` is set
+pub async fn load_pr_list(
+    git_state: &GitState,
+    working_dir: &str,
+    pr_state: &str,
+    limit: usize,
+    refresh: bool,
+) -> Result<Vec<GhPRListItem>, String> {
+    forge::ensure_pull_requests(working_dir).await?;
+    let key = format!("{}|{}", working_dir, pr_state);
+    if !refresh {
+        if let Some((fetched_at, fetched_limit, cached)) = git_state.pr_list_cache.lock().await.get(&key) {
+            // A list shorter than its own limit is every PR there is
+            let covers = *fetched_limit >= limit || cached.len() < *fetched_limit;
+            if fetched_at.elapsed() < PR_INFO_CACHE_TTL && covers {
+                return Ok(cached.iter().take(limit).cloned().collect());
+            }
+        }
+    }
+    let limit_arg = limit.to_string();
+
+    let args = [
+        "pr",
+        "list",
+        "--state",
+        pr_state,
+        "--json",
+        PR_LIST_FIELDS,
+        "--limit",
+        &limit_arg,
+    ];
+    let output = run_external(git_state, "gh", &args, Some(working_dir), &[], None, None).await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to list PRs: {}", stderr));
+    }
+
+    let json_str = String::from_utf8_lossy(&output.stdout);
+
+    let json: serde_json::Value = serde_json::from_str(&json_str)
+        .map_err(|e| format!("Failed to parse PR list JSON: {}", e))?;
+    let prs = gh_json::pr_list(json)?;
+
+    git_state
+        .pr_list_cache
+        .lock()
+        .await
+        .insert(key, (Instant::now(), limit, prs.clone()));
+    Ok(prs)
+}
+
+/// List PRs for the current repository using gh CLI
 #[tauri::command]
-pub async fn git_push(
+pub async fn list_prs(
+    git_state: State<'_, GitState>,
     working_dir: String,
-    set_upstream: bool,
-    branch: Option<String>,
-) -> Result<bool, String> {
-    // Use git CLI for push as it handles authentication better
-    let mut args = vec!["push".to_string()];
+    state: Option<String>,
+) -> Result<Vec<GhPRListItem>, String> {
+    let pr_state = state.unwrap_or_else(|| "open".to_string());
+    load_pr_list(&git_state, &working_dir, &pr_state, PR_LIST_LIMIT, true).await
+}
 
-    if set_upstream {
-        args.push("-u".to_string());
-        args.push("origin".to_string());
+/// `list_prs` a page at a time, by PR number, newest first. The first page fetches afresh and
+/// later ones reuse that fetch while it is fresh; PRs opened during a walk don't shift it.
+#[tauri::command]
+pub async fn list_prs_paged(
+    git_state: State<'_, GitState>,
+    working_dir: String,
+    state: Option<String>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<Page<GhPRListItem>, PageError> {
+    let size = pagination::page_size(limit)?;
+    let pr_state = state.unwrap_or_else(|| "open".to_string());
+    let scope = CursorScope::new("prs", &[&pagination::path_filter(&working_dir), &pr_state]);
+    let position: Option<PrPosition> = scope.decode(cursor.as_deref())?;
+    let (before, seen) = match position {
+        Some(position) => (Some(position.before), position.seen),
+        None => (None, 0),
+    };
 
-        if let Some(ref b) = branch {
-            args.push(b.clone());
-        } else {
-            // Get current branch name
-            let repo = open_repo(&working_dir)?;
-            let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
-            let branch_name = head.shorthand().unwrap_or("HEAD").to_string();
-            args.push(branch_name);
+    // gh has no offset: fetch enough from the top to reach past the cursor, and more when
+    // PRs opened since the walk began pushed the rest beyond the fetch
+    let mut fetch_limit = (seen + size + 1).min(PR_LIST_MAX);
+    loop {
+        let mut prs = load_pr_list(&git_state, &working_dir, &pr_state, fetch_limit, before.is_none()).await?;
+        let complete = prs.len() < fetch_limit;
+        prs.sort_by_key(|pr| std::cmp::Reverse(pr.number));
+        let (page, next) = pagination::keyset_page(prs, |pr| pr.number, before.as_ref(), size);
+        if next.is_some() || complete || fetch_limit == PR_LIST_MAX {
+            let next = next.map(|before| PrPosition {
+                before,
+                seen: seen + page.len(),
+            });
+            return Ok(scope.page(page, None, next));
         }
+        fetch_limit = (fetch_limit * 2).min(PR_LIST_MAX);
     }
+}
 
-    let output = Command::new("git")
-        .args(&args)
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute git push: {}", e))?;
+/// Fetch PR (or GitLab merge request) information
+#[tauri::command]
+pub async fn fetch_pr_info(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    pr_url: String,
+    rendered: Option<bool>,
+) -> Result<GhPRInfo, String> {
+    // Opening a PR shows fresh data unless a batch just fetched it; the cache serves background checks
+    let mut info = match take_prefetched(&state, &pr_url, |(_, info, _)| info.take()).await {
+        Some(info) => info,
+        None => load_pr_info(&app, &state, &pr_url, true).await?,
+    };
+    if rendered == Some(true) {
+        info.body_html = Some(crate::markdown::render(&info.body, &crate::markdown::MarkdownOptions::default()).html);
+    }
+    Ok(info)
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Push failed: {}", stderr));
+/// Fetch a PR diff using gh CLI, or a GitLab merge request's from its changes
+#[tauri::command]
+pub async fn fetch_pr_diff(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    pr_url: String,
+    op_id: Option<String>,
+) -> Result<String, String> {
+    if let Some(diff) = take_prefetched(&state, &pr_url, |(_, _, diff)| diff.take()).await {
+        return Ok(diff);
+    }
+    let target = forge::parse_review_url(&pr_url)?;
+    match target.provider {
+        forge::Provider::Github => {}
+        forge::Provider::Gitlab => return forge::fetch_mr_diff(&app, &target).await,
+        provider => return Err(forge::ReviewError::unsupported(provider, "Loading pull request diffs").to_string()),
     }
+    let (owner, repo, pr_number) = parse_pr_url(&pr_url)?;
 
-    Ok(true)
+    let repo_arg = format!("{}/{}", owner, repo);
+    let args = ["pr", "diff", &pr_number, "--repo", &repo_arg];
+    let output = run_reported(&app, &state, "pr-diff", "gh", &args, None, op_id, "Failed to fetch PR diff").await?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-/// Get recent commits
+/// Post a review to a GitHub PR using gh CLI; other providers get `UnsupportedProvider`
 #[tauri::command]
-pub async fn git_log(
-    working_dir: String,
-    limit: u32,
-    branch: Option<String>,
-) -> Result<Vec<GitCommit>, String> {
-    let repo = open_repo(&working_dir)?;
+pub async fn post_pr_review(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    pr_url: String,
+    verdict: String, // "approve" | "request-changes" | "comment"
+    body: String,
+) -> Result<(), forge::ReviewError> {
+    let target = forge::parse_review_url(&pr_url)?;
+    if target.provider != forge::Provider::Github {
+        return Err(forge::ReviewError::unsupported(target.provider, "Posting reviews"));
+    }
+    let (owner, repo, pr_number) = parse_pr_url(&pr_url)?;
 
-    let mut revwalk = repo
-        .revwalk()
-        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    let verdict_flag = match verdict.as_str() {
+        "approve" => "--approve",
+        "request-changes" => "--request-changes",
+        "comment" => "--comment",
+        _ => return Err(format!("Invalid review verdict: {}", verdict).into()),
+    };
 
-    // Start from specified branch or HEAD
-    if let Some(ref branch_name) = branch {
-        let reference = repo
-            .find_branch(branch_name, BranchType::Local)
-            .map_err(|e| format!("Branch not found: {}", e))?;
-        revwalk
-            .push(reference.get().target().ok_or("Invalid branch target")?)
-            .map_err(|e| format!("Failed to push branch: {}", e))?;
-    } else {
-        revwalk
-            .push_head()
-            .map_err(|e| format!("Failed to push HEAD: {}", e))?;
+    let repo_arg = format!("{}/{}", owner, repo);
+    let args = [
+        "pr",
+        "review",
+        &pr_number,
+        "--repo",
+        &repo_arg,
+        verdict_flag,
+        "--body",
+        &body,
+    ];
+    let output = run_external(&state, "gh", &args, None, &[], None, None).await.map_err(String::from)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to post PR review: {}", stderr).into());
+    }
+
+    // The review is on GitHub now; a failure to clean up the draft isn't worth surfacing
+    if let Err(e) = crate::review_drafts::remove_draft(&app, &pr_url).await {
+        eprintln!("[mensa] {}", e);
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// Repository Capabilities
+// ============================================================================
+
+/// `extensions.objectformat` from a git config file, read as text since libgit2
+/// refuses to open repositories that set it
+fn config_object_format(config_path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(config_path).ok()?;
+    let mut in_extensions = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_extensions = line.trim_matches(['[', ']']).trim().eq_ignore_ascii_case("extensions");
+        } else if in_extensions {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim().eq_ignore_ascii_case("objectformat") {
+                    return Some(value.trim().to_lowercase());
+                }
+            }
+        }
     }
+    None
+}
 
-    let mut commits = Vec::new();
-
-    for (i, oid_result) in revwalk.enumerate() {
-        if i >= limit as usize {
-            break;
-        }
+/// The git directory of `dir` if one appears to exist (a .git dir or file, or a bare layout)
+fn git_dir_candidate(dir: &Path) -> Option<PathBuf> {
+    let dot_git = dir.join(".git");
+    if dot_git.exists() {
+        return Some(dot_git);
+    }
+    (dir.join("HEAD").is_file() && dir.join("objects").is_dir()).then(|| dir.to_path_buf())
+}
 
-        let oid = oid_result.map_err(|e| format!("Failed to get OID: {}", e))?;
-        let commit = repo
-            .find_commit(oid)
-            .map_err(|e| format!("Failed to find commit: {}", e))?;
+const FSCK_HINT: &str = "The repository looks damaged. Run `git fsck` in a terminal to find the problem.";
+
+fn probe_repo(dir: &Path) -> RepoCapabilities {
+    let mut caps = RepoCapabilities {
+        is_repo: false,
+        is_bare: false,
+        object_format: None,
+        has_commits: false,
+        supports_status: false,
+        damaged: false,
+        failure_reason: None,
+        hint: None,
+    };
 
-        // Get diff stats for this commit
-        let (files_changed, insertions, deletions) = if commit.parent_count() > 0 {
-            if let Ok(parent) = commit.parent(0) {
-                if let (Ok(parent_tree), Ok(commit_tree)) =
-                    (parent.tree(), commit.tree())
-                {
-                    if let Ok(diff) = repo.diff_tree_to_tree(
-                        Some(&parent_tree),
-                        Some(&commit_tree),
-                        None,
-                    ) {
-                        if let Ok(stats) = diff.stats() {
-                            (
-                                stats.files_changed() as u32,
-                                stats.insertions() as u32,
-                                stats.deletions() as u32,
-                            )
-                        } else {
-                            (0, 0, 0)
-                        }
-                    } else {
-                        (0, 0, 0)
-                    }
-                } else {
-                    (0, 0, 0)
+    let repo = match Repository::open(dir) {
+        Ok(repo) => repo,
+        Err(e) => {
+            let git_dir = match git_dir_candidate(dir) {
+                Some(git_dir) => git_dir,
+                None => {
+                    caps.failure_reason = Some("Not a git repository".to_string());
+                    return caps;
+                }
+            };
+            caps.is_repo = true;
+            // A .git file (worktree/submodule) points elsewhere; its config lives there
+            caps.object_format = config_object_format(&git_dir.join("config"));
+            match caps.object_format.as_deref() {
+                Some(format) if format != "sha1" => {
+                    caps.failure_reason = Some(format!("The {} object format is not supported", format));
+                    caps.hint = Some("mensa can only read SHA-1 repositories.".to_string());
+                }
+                _ => {
+                    caps.damaged = true;
+                    caps.failure_reason = Some(format!("Repository could not be opened: {}", e.message()));
+                    caps.hint = Some(FSCK_HINT.to_string());
                 }
-            } else {
-                (0, 0, 0)
             }
-        } else {
-            (0, 0, 0)
-        };
+            return caps;
+        }
+    };
 
-        commits.push(GitCommit {
-            hash: oid.to_string(),
-            short_hash: oid.to_string()[..7].to_string(),
-            message: commit.message().unwrap_or("").trim().to_string(),
-            author: commit.author().name().unwrap_or("Unknown").to_string(),
-            email: commit.author().email().unwrap_or("").to_string(),
-            timestamp: commit.time().seconds(),
-            files_changed,
-            insertions,
-            deletions,
-        });
+    caps.is_repo = true;
+    caps.is_bare = repo.is_bare();
+    caps.object_format = Some(config_object_format(&repo.path().join("config")).unwrap_or_else(|| "sha1".to_string()));
+    caps.has_commits = repo.head().and_then(|h| h.peel_to_commit()).is_ok();
+
+    if caps.is_bare {
+        caps.failure_reason = Some("Bare repositories have no working tree".to_string());
+        caps.hint = Some("Open a clone or worktree of this repository instead.".to_string());
+        return caps;
     }
 
-    Ok(commits)
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false);
+    match repo.index().and_then(|_| repo.statuses(Some(&mut opts))) {
+        Ok(_) => caps.supports_status = true,
+        Err(e) => {
+            caps.damaged = true;
+            caps.failure_reason = Some(format!("Repository status could not be read: {}", e.message()));
+            caps.hint = Some(FSCK_HINT.to_string());
+        }
+    }
+    caps
 }
 
-/// Fetch from remote
+/// Probe what mensa can do with a directory's repository
 #[tauri::command]
-pub async fn git_fetch(working_dir: String) -> Result<bool, String> {
-    let output = Command::new("git")
-        .args(["fetch", "--all", "--prune"])
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
+pub async fn get_repo_capabilities(working_dir: String) -> Result<RepoCapabilities, String> {
+    tokio::task::spawn_blocking(move || probe_repo(Path::new(&working_dir)))
         .await
-        .map_err(|e| format!("Failed to execute git fetch: {}", e))?;
+        .map_err(|e| format!("Repository probe failed: {}", e))
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Fetch failed: {}", stderr));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, commit_all, stage, working_dir, write};
+
+    fn status(dir: &tempfile::TempDir) -> GitStatus {
+        compute_status(&working_dir(dir), None, DEFAULT_RENAME_THRESHOLD).unwrap()
     }
 
-    Ok(true)
-}
+    fn paths(files: &[GitFile]) -> Vec<(&str, &str)> {
+        let mut paths: Vec<(&str, &str)> = files.iter().map(|f| (f.path.as_str(), f.status.as_str())).collect();
+        paths.sort();
+        paths
+    }
 
-/// Pull from remote
-#[tauri::command]
-pub async fn git_pull(working_dir: String) -> Result<bool, String> {
-    let output = Command::new("git")
-        .args(["pull"])
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute git pull: {}", e))?;
+    async fn diff(dir: &tempfile::TempDir, file: Option<&str>, staged: bool) -> String {
+        git_diff(working_dir(dir), file.map(String::from), staged, None, None, None, None)
+            .await
+            .unwrap()
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Pull failed: {}", stderr));
+    #[test]
+    fn status_sorts_changes_into_their_lists() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "kept.txt", "one\n");
+        write(dir.path(), "edited.txt", "one\n");
+        write(dir.path(), "removed.txt", "one\n");
+        commit_all(&repo, "initial");
+
+        write(dir.path(), "edited.txt", "two\n");
+        write(dir.path(), "added.txt", "new\n");
+        stage(&repo, "added.txt");
+        write(dir.path(), "nested/untracked.txt", "loose\n");
+        std::fs::remove_file(dir.path().join("removed.txt")).unwrap();
+
+        let status = status(&dir);
+        assert_eq!(status.branch, repo.head().unwrap().shorthand().unwrap());
+        assert_eq!(paths(&status.staged), [("added.txt", "added")]);
+        assert_eq!(paths(&status.modified), [("edited.txt", "modified")]);
+        assert_eq!(paths(&status.untracked), [("nested/untracked.txt", "untracked")]);
+        assert_eq!(paths(&status.deleted), [("removed.txt", "deleted")]);
+        assert_eq!(status.files.len(), 4);
+        assert!(status.files.iter().all(|f| f.path != "kept.txt"));
     }
 
-    Ok(true)
-}
+    #[test]
+    fn clean_repository_has_no_changes() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "a\n");
+        commit_all(&repo, "initial");
+        let status = status(&dir);
+        assert!(status.files.is_empty() && status.staged.is_empty() && status.untracked.is_empty());
+        assert!(!tree_is_dirty(&repo, true).unwrap());
+    }
 
-/// Discard changes in a file (restore to HEAD)
-#[tauri::command]
-pub async fn git_discard(working_dir: String, file_path: String) -> Result<bool, String> {
-    let output = Command::new("git")
-        .args(["checkout", "--", &file_path])
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute git checkout: {}", e))?;
+    #[tokio::test]
+    async fn diff_separates_staged_and_unstaged_changes() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "one\ntwo\n");
+        write(dir.path(), "b.txt", "bee\n");
+        commit_all(&repo, "initial");
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Discard failed: {}", stderr));
+        write(dir.path(), "a.txt", "one\nthree\n");
+        stage(&repo, "a.txt");
+        write(dir.path(), "b.txt", "bee\nsting\n");
+
+        let staged = diff(&dir, None, true).await;
+        assert!(staged.contains("-two\n+three\n"), "{}", staged);
+        assert!(!staged.contains("sting"));
+
+        let unstaged = diff(&dir, None, false).await;
+        assert!(unstaged.contains("+sting\n"), "{}", unstaged);
+        assert!(!unstaged.contains("three"));
+
+        let only_a = diff(&dir, Some("a.txt"), true).await;
+        assert!(only_a.contains("a/a.txt") && !only_a.contains("b.txt"));
     }
 
-    Ok(true)
-}
+    #[tokio::test]
+    async fn line_ending_only_changes_leave_the_diff_and_index_alone() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), ".gitattributes", "* text eol=crlf\n");
+        write(dir.path(), "notes.txt", "one\r\ntwo\r\n");
+        commit_all(&repo, "initial");
+
+        // Same content once normalized; only the line endings on disk differ
+        write(dir.path(), "notes.txt", "one\ntwo\n");
+        let index_path = repo.path().join("index");
+        let index_before = std::fs::read(&index_path).unwrap();
+
+        let unstaged = diff(&dir, None, false).await;
+        assert_eq!(unstaged, "");
+        let stats = collect_diff_stats(
+            &repo,
+            &workspace_diff(&repo, None, false, &mut diff_options(None, false)).unwrap(),
+            repo.workdir(),
+            false,
+        )
+        .unwrap();
+        assert!(stats.files.is_empty(), "{:?}", stats.files);
+        assert_eq!(std::fs::read(&index_path).unwrap(), index_before);
+
+        // A real edit next to it still shows
+        write(dir.path(), "notes.txt", "one\nthree\n");
+        let unstaged = diff(&dir, None, false).await;
+        assert!(unstaged.contains("-two\n+three\n"), "{}", unstaged);
+        assert_eq!(std::fs::read(&index_path).unwrap(), index_before);
+    }
 
-/// Check if gh CLI is available and authenticated
-#[tauri::command]
-pub async fn check_gh_cli_available() -> Result<bool, String> {
-    let output = Command::new("gh")
-        .args(["auth", "status"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await;
+    #[test]
+    fn dirty_check_sees_edits_and_optionally_untracked_files() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "a\n");
+        commit_all(&repo, "initial");
+
+        write(dir.path(), "loose/new.txt", "new\n");
+        assert!(!tree_is_dirty(&repo, false).unwrap());
+        assert!(tree_is_dirty(&repo, true).unwrap());
 
-    match output {
-        Ok(result) => Ok(result.status.success()),
-        Err(_) => Ok(false),
+        write(dir.path(), "a.txt", "b\n");
+        assert!(tree_is_dirty(&repo, false).unwrap());
     }
-}
 
-/// Create a pull request using gh CLI
-#[tauri::command]
-pub async fn create_pull_request(
-    working_dir: String,
-    options: PRCreationOptions,
-) -> Result<String, String> {
-    let mut args = vec![
-        "pr".to_string(),
-        "create".to_string(),
-        "--base".to_string(),
-        options.base,
-        "--head".to_string(),
-        options.head,
-        "--title".to_string(),
-        options.title,
-        "--body".to_string(),
-        options.body,
-    ];
+    #[test]
+    fn dirty_check_and_summary_stay_fast_on_a_large_tree() {
+        let (dir, repo) = test_support::repo();
+        for i in 0..10_000 {
+            write(dir.path(), &format!("src/{:02}/file{}.txt", i % 50, i), format!("{}\n", i));
+        }
+        commit_all(&repo, "10k files");
+        for i in 0..10_000 {
+            write(dir.path(), &format!("build/out{}.o", i), "x");
+        }
+        write(dir.path(), "src/00/file0.txt", "edited\n");
 
-    if options.draft {
-        args.push("--draft".to_string());
+        let started = Instant::now();
+        assert!(tree_is_dirty(&repo, false).unwrap());
+        assert!(tree_is_dirty(&repo, true).unwrap());
+        let summary = status_summary(&working_dir(&dir)).unwrap();
+        let elapsed = started.elapsed();
+
+        assert_eq!((summary.modified, summary.untracked, summary.staged), (1, 1, 0));
+        assert!(elapsed < Duration::from_secs(5), "took {:?}", elapsed);
     }
 
-    if let Some(reviewers) = options.reviewers {
-        if !reviewers.is_empty() {
-            args.push("--reviewer".to_string());
-            args.push(reviewers.join(","));
+    #[tokio::test]
+    async fn branch_info_tracks_only_the_current_branch() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "a\n");
+        let base = commit_all(&repo, "initial");
+        let current = repo.head().unwrap().shorthand().unwrap().to_string();
+
+        repo.remote("origin", "https://example.invalid/repo.git").unwrap();
+        repo.reference(&format!("refs/remotes/origin/{}", current), base, true, "test").unwrap();
+        repo.find_branch(&current, BranchType::Local)
+            .unwrap()
+            .set_upstream(Some(&format!("origin/{}", current)))
+            .unwrap();
+        for i in 0..12 {
+            repo.branch(&format!("topic-{}", i), &repo.find_commit(base).unwrap(), false).unwrap();
         }
+        write(dir.path(), "a.txt", "b\n");
+        commit_all(&repo, "ahead");
+
+        let info = git_branch_info(working_dir(&dir)).await.unwrap();
+        assert_eq!(info.current, current);
+        assert_eq!(info.upstream.as_deref(), Some(format!("origin/{}", current).as_str()));
+        assert_eq!((info.ahead, info.behind, info.upstream_gone), (1, 0, false));
+        assert_eq!(info.recent_branches.len(), 10);
+        assert_eq!(info.recent_branches[0], current);
     }
 
-    if let Some(labels) = options.labels {
-        if !labels.is_empty() {
-            args.push("--label".to_string());
-            args.push(labels.join(","));
+    fn unsupported_reason(result: Result<bool, GitCommandError>) -> String {
+        match result {
+            Err(GitCommandError::RepoUnsupported { reason, message }) => {
+                assert!(message.starts_with(REPO_UNSUPPORTED_PREFIX));
+                reason
+            }
+            other => panic!("expected RepoUnsupported, got {:?}", other),
         }
     }
 
-    let output = Command::new("gh")
-        .args(&args)
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute gh pr create: {}", e))?;
+    #[tokio::test]
+    async fn bare_repository_is_reported_as_unsupported() {
+        let dir = tempfile::tempdir().unwrap();
+        Repository::init_bare(dir.path()).unwrap();
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("PR creation failed: {}", stderr));
+        let reason = unsupported_reason(git_is_dirty(working_dir(&dir)).await);
+        assert!(reason.contains("Bare"), "{}", reason);
+        let caps = get_repo_capabilities(working_dir(&dir)).await.unwrap();
+        assert!(caps.is_repo && caps.is_bare && !caps.supports_status);
     }
 
-    let pr_url = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    Ok(pr_url)
-}
+    #[tokio::test]
+    async fn truncated_index_is_reported_as_damaged() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "a\n");
+        commit_all(&repo, "initial");
+        let index_path = repo.path().join("index");
+        let index = std::fs::read(&index_path).unwrap();
+        std::fs::write(&index_path, &index[..index.len() / 2]).unwrap();
+
+        let reason = unsupported_reason(git_is_dirty(working_dir(&dir)).await);
+        assert!(reason.contains("could not be read"), "{}", reason);
+        let caps = get_repo_capabilities(working_dir(&dir)).await.unwrap();
+        assert!(caps.damaged && caps.hint.is_some());
+
+        // Repaired: the next call opens it, nothing of the failure lingers
+        std::fs::write(&index_path, &index).unwrap();
+        assert!(!git_is_dirty(working_dir(&dir)).await.unwrap());
+    }
 
-/// Get list of available branches
-#[tauri::command]
-pub async fn git_list_branches(working_dir: String) -> Result<Vec<String>, String> {
-    let repo = open_repo(&working_dir)?;
+    #[tokio::test]
+    async fn failed_open_is_retried_once_the_repository_exists() {
+        let dir = tempfile::tempdir().unwrap();
+        let reason = unsupported_reason(git_is_dirty(working_dir(&dir)).await);
+        assert_eq!(reason, "Not a git repository");
 
-    let mut branches = Vec::new();
+        Repository::init(dir.path()).unwrap();
+        assert!(!git_is_dirty(working_dir(&dir)).await.unwrap());
+    }
 
-    // Get local branches
-    if let Ok(branch_iter) = repo.branches(Some(BranchType::Local)) {
-        for branch_result in branch_iter {
-            if let Ok((branch, _)) = branch_result {
-                if let Some(name) = branch.name().ok().flatten() {
-                    branches.push(name.to_string());
-                }
-            }
-        }
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn non_utf8_file_name_goes_from_status_to_a_commit() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "plain.txt", "one\n");
+        commit_all(&repo, "initial");
+        let name: &[u8] = b"caf\xe9-latin1.txt";
+        std::fs::write(dir.path().join(std::ffi::OsStr::from_bytes(name)), "bytes\n").unwrap();
+
+        let untracked = status(&dir).untracked;
+        let file = untracked.iter().find(|f| f.raw_b64.is_some()).expect("the file should be listed");
+        assert_eq!(file.path, "caf\u{fffd}-latin1.txt");
+        let raw_path = file.raw_b64.clone().unwrap();
+        assert_eq!(BASE64.decode(&raw_path).unwrap(), name);
+
+        // The lossy display name would miss the file; the raw bytes reach it
+        git_stage(working_dir(&dir), Vec::new(), Some(vec![raw_path]), None).await.unwrap();
+        let staged = status(&dir);
+        assert!(staged.staged.iter().any(|f| f.raw_b64.as_deref().is_some_and(|raw| BASE64.decode(raw).unwrap() == name)));
+
+        let oid = commit_staged(&repo, "Test", "test@example.com", "add latin-1 name").unwrap();
+        let tree = repo.find_commit(oid).unwrap().tree().unwrap();
+        assert!(tree.iter().any(|entry| entry.name_bytes() == name));
+        assert!(status(&dir).files.is_empty());
     }
 
-    branches.sort();
-    Ok(branches)
-}
+    // macOS file systems look names up normalization-insensitively; there both spellings exist
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn accented_names_are_staged_by_their_composed_spelling() {
+        let parent = tempfile::tempdir().unwrap();
+        let root = parent.path().join("Proje\u{301}t");
+        let repo = Repository::init(&root).unwrap();
+        write(&root, "notes/cafe\u{301}.txt", "one\n");
+        commit_all(&repo, "initial");
+        write(&root, "notes/cafe\u{301}.txt", "two\n");
+        write(&root, "re\u{301}sume\u{301}.md", "new\n");
+        let dir = root.to_string_lossy().to_string();
+
+        // Listed composed, with the stored spelling as raw bytes
+        let status = compute_status(&dir, None, DEFAULT_RENAME_THRESHOLD).unwrap();
+        assert_eq!(paths(&status.modified), [("notes/caf\u{e9}.txt", "modified")]);
+        assert_eq!(paths(&status.untracked), [("r\u{e9}sum\u{e9}.md", "untracked")]);
+        let raw = status.modified[0].raw_b64.as_deref().unwrap();
+        assert_eq!(BASE64.decode(raw).unwrap(), "notes/cafe\u{301}.txt".as_bytes());
+
+        // The composed spellings reach the index entry and the file on disk
+        let composed = vec!["notes/caf\u{e9}.txt".to_string(), "r\u{e9}sum\u{e9}.md".to_string()];
+        git_stage(dir.clone(), composed, None, None).await.unwrap();
+        let status = compute_status(&dir, None, DEFAULT_RENAME_THRESHOLD).unwrap();
+        assert!(status.modified.is_empty() && status.untracked.is_empty());
+        assert_eq!(paths(&status.staged).len(), 2);
+
+        let mut index = repo.index().unwrap();
+        index.read(true).unwrap();
+        let mut stored: Vec<Vec<u8>> = index.iter().map(|entry| entry.path).collect();
+        stored.sort();
+        assert_eq!(stored, ["notes/cafe\u{301}.txt".as_bytes(), "re\u{301}sume\u{301}.md".as_bytes()]);
+    }
 
-/// Get the diff between two commits or branches
-#[tauri::command]
-pub async fn git_diff_commits(
-    working_dir: String,
-    base: String,
-    head: String,
-) -> Result<String, String> {
-    let output = Command::new("git")
-        .args(["diff", &format!("{}...{}", base, head)])
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute git diff: {}", e))?;
+    #[test]
+    fn path_forms_leave_ascii_and_unknown_paths_alone() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "plain.txt", "");
+        write(dir.path(), "na\u{ef}ve.txt", "");
+        commit_all(&repo, "initial");
+        let forms = repo_path_forms(
+            &repo,
+            vec![PathBuf::from("plain.txt"), PathBuf::from("na\u{ef}ve.txt"), PathBuf::from("\u{e9}t\u{e9}.txt")],
+        );
+        assert_eq!(forms, [PathBuf::from("plain.txt"), PathBuf::from("na\u{ef}ve.txt"), PathBuf::from("\u{e9}t\u{e9}.txt")]);
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Diff failed: {}", stderr));
+    #[tokio::test]
+    async fn filtered_file_can_still_be_staged_and_committed() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "Cargo.lock", "v1\n");
+        write(dir.path(), "src/main.rs", "fn main() {}\n");
+        commit_all(&repo, "initial");
+        write(dir.path(), "Cargo.lock", "v2\n");
+        write(dir.path(), "src/main.rs", "fn main() { run() }\n");
+
+        let mut builder = globset::GlobSetBuilder::new();
+        builder.add(globset::Glob::new("*.lock").unwrap());
+        let matcher = Arc::new(builder.build().unwrap());
+        let filtered = |dir: &tempfile::TempDir| compute_status(&working_dir(dir), Some(matcher.clone()), DEFAULT_RENAME_THRESHOLD).unwrap();
+
+        let status = filtered(&dir);
+        assert_eq!(paths(&status.modified), [("src/main.rs", "modified")]);
+        assert_eq!(paths(&status.filtered), [("Cargo.lock", "modified")]);
+
+        // Hidden from the lists, not from the commands
+        git_stage(working_dir(&dir), vec!["Cargo.lock".to_string()], None, None).await.unwrap();
+        let status = filtered(&dir);
+        assert!(status.staged.is_empty());
+        assert_eq!(paths(&status.filtered), [("Cargo.lock", "modified")]);
+        let file_status = repo.status_file(Path::new("Cargo.lock")).unwrap();
+        assert_eq!(file_status, git2::Status::INDEX_MODIFIED);
+
+        let oid = commit_staged(&repo, "Test", "test@example.com", "bump lockfile").unwrap();
+        let tree = repo.find_commit(oid).unwrap().tree().unwrap();
+        let blob = tree.get_path(Path::new("Cargo.lock")).unwrap().to_object(&repo).unwrap().peel_to_blob().unwrap();
+        assert_eq!(blob.content(), b"v2\n");
+        let status = filtered(&dir);
+        assert!(status.filtered.is_empty());
+        assert_eq!(paths(&status.modified), [("src/main.rs", "modified")]);
     }
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
-}
+    /// path, old path, index status, worktree status
+    type Row<'a> = (&'a str, Option<&'a str>, Option<&'a str>, Option<&'a str>);
 
-// ============================================================================
-// PR Review Commands
-// ============================================================================
+    fn rows(status: &GitStatus) -> Vec<Row<'_>> {
+        status
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.old_path.as_deref(), f.index_status.as_deref(), f.worktree_status.as_deref()))
+            .collect()
+    }
 
-/// Parse a GitHub PR URL to extract owner, repo, and PR number
-fn parse_pr_url(pr_url: &str) -> Result<(String, String, String), String> {
-    // Match patterns like:
-    // https://github.com/owner/repo/pull/123
-    // github.com/owner/repo/pull/123
-    let re = Regex::new(r"(?:https?://)?github\.com/([^/]+)/([^/]+)/pull/(\d+)")
-        .map_err(|e| format!("Invalid regex: {}", e))?;
+    fn numbered_lines(count: usize) -> String {
+        (1..=count).map(|i| format!("line {}\n", i)).collect()
+    }
 
-    if let Some(caps) = re.captures(pr_url) {
-        let owner = caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default();
-        let repo = caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default();
-        let pr_number = caps.get(3).map(|m| m.as_str().to_string()).unwrap_or_default();
+    #[test]
+    fn staged_then_modified_file_is_one_row() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "one\n");
+        commit_all(&repo, "initial");
+        write(dir.path(), "a.txt", "two\n");
+        stage(&repo, "a.txt");
+        write(dir.path(), "a.txt", "three\n");
+
+        let status = status(&dir);
+        assert_eq!(rows(&status), [("a.txt", None, Some("modified"), Some("modified"))]);
+        assert_eq!(paths(&status.staged), [("a.txt", "modified")]);
+        assert_eq!(paths(&status.modified), [("a.txt", "modified")]);
+    }
 
-        if !owner.is_empty() && !repo.is_empty() && !pr_number.is_empty() {
-            return Ok((owner, repo, pr_number));
-        }
+    #[test]
+    fn worktree_rename_is_one_row_with_its_old_path() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "old.txt", numbered_lines(20));
+        commit_all(&repo, "initial");
+        std::fs::rename(dir.path().join("old.txt"), dir.path().join("new.txt")).unwrap();
+
+        let status = status(&dir);
+        assert_eq!(rows(&status), [("new.txt", Some("old.txt"), None, Some("renamed"))]);
+        // The legacy lists keep the old shape: a deletion plus an untracked file
+        assert_eq!(paths(&status.deleted), [("old.txt", "deleted")]);
+        assert_eq!(paths(&status.untracked), [("new.txt", "untracked")]);
+        assert!(status.staged.is_empty());
     }
 
-    Err(format!("Invalid PR URL format: {}", pr_url))
-}
+    #[test]
+    fn edited_rename_is_paired_above_the_threshold_only() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "old.txt", numbered_lines(20));
+        commit_all(&repo, "initial");
+        std::fs::remove_file(dir.path().join("old.txt")).unwrap();
+        write(dir.path(), "new.txt", numbered_lines(20).replace("line 7\n", "line seven\n"));
+
+        let status = status(&dir);
+        assert_eq!(rows(&status), [("new.txt", Some("old.txt"), None, Some("renamed"))]);
+
+        // Demanding an exact match, the edit splits it back into a deletion and an add
+        let exact = compute_status(&working_dir(&dir), None, 100).unwrap();
+        let mut rows = rows(&exact);
+        rows.sort();
+        assert_eq!(rows, [("new.txt", None, None, Some("untracked")), ("old.txt", None, None, Some("deleted"))]);
+    }
 
-/// List PRs for the current repository using gh CLI
-#[tauri::command]
-pub async fn list_prs(working_dir: String, state: Option<String>) -> Result<Vec<GhPRListItem>, String> {
-    let pr_state = state.unwrap_or_else(|| "open".to_string());
+    fn run_git(dir: &Path, args: &[&str]) {
+        git_stdout(dir, args);
+    }
 
-    let output = Command::new("gh")
-        .args([
-            "pr",
-            "list",
-            "--state",
-            &pr_state,
-            "--json",
-            "number,title,author,state,headRefName,baseRefName,createdAt,updatedAt,url,isDraft",
-            "--limit",
-            "50",
-        ])
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute gh pr list: {}", e))?;
+    fn git_stdout(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git").args(args).current_dir(dir).output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to list PRs: {}", stderr));
+    /// A depth-1 clone (every branch) of a bundled history: "main" is five commits, "side"
+    /// forks after the first. The bundle is unpacked into a bare origin, since bundles
+    /// can't be cloned shallow.
+    fn shallow_clone() -> (tempfile::TempDir, String) {
+        let (source, repo) = test_support::repo();
+        write(source.path(), "a.txt", "1\n");
+        let first = commit_all(&repo, "one");
+        for i in 2..=5 {
+            write(source.path(), "a.txt", format!("{}\n", i));
+            commit_all(&repo, &format!("main {}", i));
+        }
+        repo.branch("side", &repo.find_commit(first).unwrap(), false).unwrap();
+        let main = repo.head().unwrap().name().unwrap().to_string();
+        let checkout = |branch: &str| {
+            repo.set_head(branch).unwrap();
+            repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        };
+        checkout("refs/heads/side");
+        write(source.path(), "b.txt", "side\n");
+        commit_all(&repo, "side 2");
+        checkout(&main);
+
+        let scratch = tempfile::tempdir().unwrap();
+        let bundle = scratch.path().join("history.bundle");
+        run_git(source.path(), &["bundle", "create", bundle.to_str().unwrap(), "--all"]);
+        run_git(scratch.path(), &["clone", "-q", "--bare", "history.bundle", "origin.git"]);
+        let url = format!("file://{}", scratch.path().join("origin.git").display());
+        run_git(scratch.path(), &["clone", "-q", "--depth", "1", "--no-single-branch", &url, "clone"]);
+        let clone = scratch.path().join("clone").to_string_lossy().to_string();
+        (scratch, clone)
     }
 
-    let json_str = String::from_utf8_lossy(&output.stdout);
+    fn messages(log: &GitLog) -> Vec<&str> {
+        log.commits.iter().map(|c| c.message.trim()).collect()
+    }
 
-    let json: Vec<serde_json::Value> = serde_json::from_str(&json_str)
-        .map_err(|e| format!("Failed to parse PR list JSON: {}", e))?;
+    #[tokio::test]
+    async fn shallow_clone_log_stops_at_the_boundary_until_deepened() {
+        let (_scratch, clone) = shallow_clone();
+
+        let log = git_log(clone.clone(), 50, None).await.unwrap();
+        assert!(log.is_shallow && log.shallow_boundary_reached);
+        assert_eq!(messages(&log), ["main 5"]);
+        let page = git_log_paged(clone.clone(), None, None, Some(50)).await.unwrap();
+        assert!(page.is_shallow && page.shallow_boundary_reached);
+
+        // Below the boundary: two more commits, still shallow
+        let state = GitState::default();
+        let deepen = deepen_arg(Some(2)).unwrap();
+        let output = run_external(&state, "git", &["fetch", "--progress", &deepen], Some(&clone), &[], None, None)
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        let log = git_log(clone.clone(), 50, None).await.unwrap();
+        assert_eq!(messages(&log), ["main 5", "main 4", "main 3"]);
+        assert!(log.is_shallow && log.shallow_boundary_reached);
+
+        // A log that ends before the boundary doesn't claim to have reached it
+        let log = git_log(clone.clone(), 2, None).await.unwrap();
+        assert!(log.is_shallow && !log.shallow_boundary_reached);
+
+        let unshallow = deepen_arg(None).unwrap();
+        run_external(&state, "git", &["fetch", "--progress", &unshallow], Some(&clone), &[], None, None)
+            .await
+            .unwrap();
+        let log = git_log(clone.clone(), 50, None).await.unwrap();
+        assert!(!log.is_shallow && !log.shallow_boundary_reached);
+        assert_eq!(log.commits.len(), 5);
+        assert_eq!(deepen_arg(Some(0)), Err("Depth must be at least 1".to_string()));
+    }
 
-    let prs: Vec<GhPRListItem> = json
-        .iter()
-        .map(|pr| GhPRListItem {
-            number: pr["number"].as_u64().unwrap_or(0) as u32,
-            title: pr["title"].as_str().unwrap_or("").to_string(),
-            author: pr["author"]["login"].as_str().unwrap_or("").to_string(),
-            state: pr["state"].as_str().unwrap_or("OPEN").to_string(),
-            head_ref_name: pr["headRefName"].as_str().unwrap_or("").to_string(),
-            base_ref_name: pr["baseRefName"].as_str().unwrap_or("").to_string(),
-            created_at: pr["createdAt"].as_str().unwrap_or("").to_string(),
-            updated_at: pr["updatedAt"].as_str().unwrap_or("").to_string(),
-            url: pr["url"].as_str().unwrap_or("").to_string(),
-            is_draft: pr["isDraft"].as_bool().unwrap_or(false),
-        })
-        .collect();
+    #[test]
+    fn merge_base_beyond_the_boundary_is_a_shallow_history_error() {
+        let (_scratch, clone) = shallow_clone();
 
-    Ok(prs)
-}
+        let err = diff_stats_range(&clone, "origin/side", "HEAD").unwrap_err();
+        assert!(err.starts_with(SHALLOW_HISTORY_PREFIX), "{}", err);
+        match GitCommandError::from(err) {
+            GitCommandError::ShallowHistory { suggestion, .. } => assert!(suggestion.contains("git_fetch_deepen")),
+            other => panic!("expected ShallowHistory, got {:?}", other),
+        }
 
-/// Fetch PR information using gh CLI
-#[tauri::command]
-pub async fn fetch_pr_info(pr_url: String) -> Result<GhPRInfo, String> {
-    let (owner, repo, pr_number) = parse_pr_url(&pr_url)?;
+        run_git(Path::new(&clone), &["fetch", "-q", &deepen_arg(None).unwrap()]);
+        let stats = diff_stats_range(&clone, "origin/side", "HEAD").unwrap();
+        assert_eq!(stats.files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), ["a.txt"]);
+    }
 
-    let output = Command::new("gh")
-        .args([
-            "pr",
-            "view",
-            &pr_number,
-            "--repo",
-            &format!("{}/{}", owner, repo),
-            "--json",
-            "title,body,author,state,additions,deletions,changedFiles,commits,baseRefName,headRefName,createdAt,updatedAt",
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute gh pr view: {}", e))?;
+    #[tokio::test]
+    async fn short_hash_matches_git_in_a_shallow_clone() {
+        let (_scratch, clone) = shallow_clone();
+        let dir = Path::new(&clone);
+        let head = git_stdout(dir, &["rev-parse", "HEAD"]);
+        let oid = git2::Oid::from_str(&head).unwrap();
+
+        for abbrev in ["auto", "4", "12", "40"] {
+            run_git(dir, &["config", "core.abbrev", abbrev]);
+            // Opened afresh so the new setting is read
+            let repo = Repository::open(dir).unwrap();
+            let expected = git_stdout(dir, &["rev-parse", "--short", "HEAD"]);
+            assert_eq!(short_hash(&repo, oid), expected, "core.abbrev={}", abbrev);
+            let log = git_log(clone.clone(), 1, None).await.unwrap();
+            assert_eq!(log.commits[0].short_hash, expected, "core.abbrev={}", abbrev);
+        }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to fetch PR info: {}", stderr));
+        // The grafted tip names a parent that was never fetched
+        run_git(dir, &["config", "core.abbrev", "4"]);
+        let repo = Repository::open(dir).unwrap();
+        let parent = git_stdout(dir, &["cat-file", "-p", "HEAD"])
+            .lines()
+            .find_map(|line| line.strip_prefix("parent ").map(str::to_string))
+            .unwrap();
+        assert!(repo.find_object(git2::Oid::from_str(&parent).unwrap(), None).is_err());
+        assert_eq!(short_hash(&repo, git2::Oid::from_str(&parent).unwrap()), parent[..7]);
     }
 
-    let json_str = String::from_utf8_lossy(&output.stdout);
+    // Each special name has a decoy its glob reading would match instead
+    #[cfg(unix)]
+    const GLOB_NAMES: [(&str, &str); 4] = [
+        ("src/[wip]/mod.rs", "src/w/mod.rs"),
+        ("star*.txt", "starfish.txt"),
+        ("what?.txt", "whatx.txt"),
+        ("!bang.txt", "bang.txt"),
+    ];
 
-    // Parse the JSON response
-    let json: serde_json::Value = serde_json::from_str(&json_str)
-        .map_err(|e| format!("Failed to parse PR info JSON: {}", e))?;
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn glob_characters_in_names_are_taken_literally() {
+        let (dir, repo) = test_support::repo();
+        for (name, decoy) in GLOB_NAMES {
+            write(dir.path(), name, "one\n");
+            write(dir.path(), decoy, "one\n");
+        }
+        commit_all(&repo, "initial");
+        for (name, decoy) in GLOB_NAMES {
+            write(dir.path(), name, format!("{} two\n", name));
+            write(dir.path(), decoy, format!("{} two\n", decoy));
+        }
 
-    Ok(GhPRInfo {
-        title: json["title"].as_str().unwrap_or("").to_string(),
-        body: json["body"].as_str().unwrap_or("").to_string(),
-        author: json["author"]["login"].as_str().unwrap_or("").to_string(),
-        state: json["state"].as_str().unwrap_or("OPEN").to_string(),
-        additions: json["additions"].as_u64().unwrap_or(0) as u32,
-        deletions: json["deletions"].as_u64().unwrap_or(0) as u32,
-        changed_files: json["changedFiles"].as_u64().unwrap_or(0) as u32,
-        commits: json["commits"]["totalCount"].as_u64()
-            .or_else(|| json["commits"].as_u64())
-            .unwrap_or(0) as u32,
-        base_ref_name: json["baseRefName"].as_str().unwrap_or("").to_string(),
-        head_ref_name: json["headRefName"].as_str().unwrap_or("").to_string(),
-        created_at: json["createdAt"].as_str().unwrap_or("").to_string(),
-        updated_at: json["updatedAt"].as_str().unwrap_or("").to_string(),
-    })
-}
+        for (name, decoy) in GLOB_NAMES {
+            let diff = diff(&dir, Some(name), false).await;
+            assert!(diff.contains(&format!("+{} two", name)), "{}: {}", name, diff);
+            assert!(!diff.contains(&format!(" a/{}", decoy)), "{}: {}", name, diff);
+        }
 
-/// Fetch PR diff using gh CLI
-#[tauri::command]
-pub async fn fetch_pr_diff(pr_url: String) -> Result<String, String> {
-    let (owner, repo, pr_number) = parse_pr_url(&pr_url)?;
+        let preview = git_discard(working_dir(&dir), "star*.txt".to_string(), None, None, Some(true), None).await.unwrap();
+        let discarded = git_discard(working_dir(&dir), "star*.txt".to_string(), None, None, None, Some(preview.token)).await.unwrap();
+        assert!(discarded.executed);
+        assert_eq!(std::fs::read_to_string(dir.path().join("star*.txt")).unwrap(), "one\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("starfish.txt")).unwrap(), "starfish.txt two\n");
+
+        let special = vec!["src/[wip]/mod.rs".to_string(), "what?.txt".to_string(), "!bang.txt".to_string()];
+        git_stage(working_dir(&dir), special.clone(), None, None).await.unwrap();
+        let after_stage = status(&dir);
+        let staged: Vec<&str> = paths(&after_stage.staged).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(staged, ["!bang.txt", "src/[wip]/mod.rs", "what?.txt"]);
+
+        let oid = commit_staged(&repo, "Test", "test@example.com", "special names").unwrap();
+        let tree = repo.find_commit(oid).unwrap().tree().unwrap();
+        let committed = |path: &str| {
+            let blob = tree.get_path(Path::new(path)).unwrap().to_object(&repo).unwrap().peel_to_blob().unwrap();
+            String::from_utf8(blob.content().to_vec()).unwrap()
+        };
+        for (name, decoy) in GLOB_NAMES {
+            let expected = if special.iter().any(|s| s == name) { format!("{} two\n", name) } else { "one\n".to_string() };
+            assert_eq!(committed(name), expected, "{}", name);
+            assert_eq!(committed(decoy), "one\n", "{}", decoy);
+        }
+        let after_commit = status(&dir);
+        let modified: Vec<&str> = paths(&after_commit.modified).into_iter().map(|(path, _)| path).collect();
+        assert_eq!(modified, ["bang.txt", "src/w/mod.rs", "starfish.txt", "whatx.txt"]);
+    }
 
-    let output = Command::new("gh")
-        .args([
-            "pr",
-            "diff",
-            &pr_number,
-            "--repo",
-            &format!("{}/{}", owner, repo),
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute gh pr diff: {}", e))?;
+    #[tokio::test]
+    async fn concurrent_status_calls_share_one_walk() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "one\n");
+        commit_all(&repo, "init");
+        write(dir.path(), "a.txt", "two\n");
+        write(dir.path(), "new.txt", "new\n");
+
+        let runs: Arc<Mutex<HashMap<String, StatusRun>>> = Arc::default();
+        let walks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let call = |min_interval: Duration, force: bool| {
+            let (runs, walks, dir) = (runs.clone(), walks.clone(), working_dir(&dir));
+            async move {
+                let compute = || async move {
+                    walks.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    // Long enough that every caller arrives while the walk runs
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    tokio::task::spawn_blocking(move || compute_status(&dir, None, DEFAULT_RENAME_THRESHOLD))
+                        .await
+                        .unwrap()
+                };
+                coalesced_status(&runs, "repo".to_string(), min_interval, force, compute).await
+            }
+        };
+        let burst = |count: usize, min_interval: Duration| {
+            let mut set = tokio::task::JoinSet::new();
+            for _ in 0..count {
+                set.spawn(call(min_interval, false));
+            }
+            set.join_all()
+        };
+        let walked = || walks.load(std::sync::atomic::Ordering::SeqCst);
+
+        let results = burst(100, Duration::ZERO).await;
+        assert_eq!(walked(), 1);
+        assert_eq!(results.iter().filter(|(_, changed)| *changed).count(), 1);
+        let first = serde_json::to_value(results[0].0.as_ref().unwrap()).unwrap();
+        assert!(results.iter().all(|(status, _)| serde_json::to_value(status.as_ref().unwrap()).unwrap() == first));
+        assert_eq!(results[0].0.as_ref().unwrap().files.len(), 2);
+        assert!(runs.lock().await["repo"].in_flight.is_none());
+
+        // Once the walk is retired the next burst walks again, and reports no change
+        let results = burst(100, Duration::ZERO).await;
+        assert_eq!(walked(), 2);
+        assert!(results.iter().all(|(status, changed)| status.is_ok() && !changed));
+
+        // Within the minimum interval nothing walks, unless forced
+        let results = burst(100, Duration::from_secs(60)).await;
+        assert_eq!(walked(), 2);
+        assert!(results.iter().all(|(status, _)| status.as_ref().unwrap().stale_ms.is_some()));
+        let (forced, _) = call(Duration::from_secs(60), true).await;
+        assert_eq!((walked(), forced.unwrap().stale_ms), (3, None));
+
+        // A failed walk is shared too, and leaves the last good status in place
+        std::fs::remove_dir_all(dir.path().join(".git")).unwrap();
+        let results = burst(100, Duration::ZERO).await;
+        assert_eq!(walked(), 4);
+        assert!(results.iter().all(|(status, changed)| status.is_err() && !changed));
+        assert!(runs.lock().await["repo"].last.is_some());
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to fetch PR diff: {}", stderr));
+    /// A repository with history to diff against: "v1" (and the annotated "release-1") at the
+    /// first commit, origin/main at the second, which added, deleted and edited a file; then
+    /// a staged new file, an unstaged deletion and an untracked file
+    fn repo_with_bases() -> tempfile::TempDir {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "keep.txt", "keep\n");
+        write(dir.path(), "gone.txt", "gone\n");
+        write(dir.path(), "edit.txt", "one\n");
+        let first = commit_all(&repo, "first");
+        let first = repo.find_object(first, None).unwrap();
+        repo.tag_lightweight("v1", &first, false).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        repo.tag("release-1", &first, &signature, "Release 1", false).unwrap();
+
+        write(dir.path(), "added.txt", "added\n");
+        std::fs::remove_file(dir.path().join("gone.txt")).unwrap();
+        write(dir.path(), "edit.txt", "two\n");
+        let second = commit_all(&repo, "second");
+        repo.reference("refs/remotes/origin/main", second, false, "fetch").unwrap();
+
+        write(dir.path(), "staged.txt", "staged\n");
+        stage(&repo, "staged.txt");
+        std::fs::remove_file(dir.path().join("keep.txt")).unwrap();
+        write(dir.path(), "untracked.txt", "untracked\n");
+        dir
     }
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
-}
+    async fn diff_against(dir: &tempfile::TempDir, base: &str, staged: bool) -> String {
+        git_diff(working_dir(dir), None, staged, None, None, Some(base.to_string()), None)
+            .await
+            .unwrap()
+    }
 
-/// Post a review to a GitHub PR using gh CLI
-#[tauri::command]
-pub async fn post_pr_review(
-    pr_url: String,
-    verdict: String, // "approve" | "request-changes" | "comment"
-    body: String,
-) -> Result<(), String> {
-    let (owner, repo, pr_number) = parse_pr_url(&pr_url)?;
+    /// The files a diff touches, with whether each is new, deleted or modified
+    fn diffed_files(diff: &str) -> Vec<(String, &'static str)> {
+        diff.split("diff --git a/")
+            .skip(1)
+            .map(|file| {
+                let name = file.split(' ').next().unwrap().to_string();
+                let kind = match () {
+                    _ if file.contains("\nnew file mode") => "added",
+                    _ if file.contains("\ndeleted file mode") => "deleted",
+                    _ => "modified",
+                };
+                (name, kind)
+            })
+            .collect()
+    }
 
-    let verdict_flag = match verdict.as_str() {
-        "approve" => "--approve",
-        "request-changes" => "--request-changes",
-        "comment" => "--comment",
-        _ => return Err(format!("Invalid review verdict: {}", verdict)),
-    };
-
-    let output = Command::new("gh")
-        .args([
-            "pr",
-            "review",
-            &pr_number,
-            "--repo",
-            &format!("{}/{}", owner, repo),
-            verdict_flag,
-            "--body",
-            &body,
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-        .await
-        .map_err(|e| format!("Failed to execute gh pr review: {}", e))?;
+    #[tokio::test]
+    async fn diff_against_a_tag_base_covers_commits_and_work_in_progress() {
+        let dir = repo_with_bases();
+        let expected = [
+            ("added.txt".to_string(), "added"),
+            ("edit.txt".to_string(), "modified"),
+            ("gone.txt".to_string(), "deleted"),
+            ("keep.txt".to_string(), "deleted"),
+            ("staged.txt".to_string(), "added"),
+        ];
+        let diff = diff_against(&dir, "v1", false).await;
+        assert_eq!(diffed_files(&diff), expected);
+        assert!(diff.contains("-one\n+two\n") && diff.contains("+added\n") && diff.contains("-gone\n"));
+        assert!(!diff.contains("untracked"));
+        // An annotated tag is peeled to its commit
+        assert_eq!(diff_against(&dir, "release-1", false).await, diff);
+
+        // Staged: the base up to the index, so the unstaged deletion isn't there
+        let staged = diffed_files(&diff_against(&dir, "v1", true).await);
+        assert_eq!(staged, [&expected[..3], &expected[4..]].concat());
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to post PR review: {}", stderr));
+    #[tokio::test]
+    async fn diff_against_a_remote_tracking_base_leaves_out_pushed_commits() {
+        let dir = repo_with_bases();
+        assert_eq!(
+            diffed_files(&diff_against(&dir, "origin/main", false).await),
+            [("keep.txt".to_string(), "deleted"), ("staged.txt".to_string(), "added")]
+        );
+        assert_eq!(
+            diffed_files(&diff_against(&dir, "refs/remotes/origin/main", true).await),
+            [("staged.txt".to_string(), "added")]
+        );
+        // Without a base the same work in progress is split by staging
+        assert_eq!(diffed_files(&diff(&dir, None, false).await), [("keep.txt".to_string(), "deleted")]);
+        assert_eq!(diffed_files(&diff(&dir, None, true).await), [("staged.txt".to_string(), "added")]);
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn diff_of_one_file_against_a_base() {
+        let dir = repo_with_bases();
+        let one = |file: &str, base: &str| {
+            git_diff(working_dir(&dir), Some(file.to_string()), false, None, None, Some(base.to_string()), None)
+        };
+        let gone = one("gone.txt", "v1").await.unwrap();
+        assert_eq!(diffed_files(&gone), [("gone.txt".to_string(), "deleted")]);
+        assert!(gone.contains("--- a/gone.txt\n+++ /dev/null\n"));
+        let added = one("added.txt", "v1").await.unwrap();
+        assert!(added.contains("--- /dev/null\n+++ b/added.txt\n@@ -0,0 +1 @@\n+added\n"), "{}", added);
+        assert_eq!(one("added.txt", "origin/main").await.unwrap(), "");
+
+        match one("edit.txt", "no-such-ref").await {
+            Err(GitCommandError::Failed { message }) => assert!(message.starts_with("Failed to resolve 'no-such-ref'"), "{}", message),
+            other => panic!("expected a resolve failure, got {:?}", other),
+        }
+    }
+
+    async fn discard(dir: &tempfile::TempDir, pattern: &str, dry_run: bool, token: Option<&str>) -> Result<DestructionPreview, String> {
+        git_discard(working_dir(dir), pattern.to_string(), None, Some(true), Some(dry_run), token.map(String::from))
+            .await
+            .map_err(|e| match e {
+                GitCommandError::Failed { message } => message,
+                other => panic!("unexpected error {:?}", other),
+            })
+    }
+
+    fn restored(preview: &DestructionPreview) -> Vec<&str> {
+        let mut paths: Vec<&str> = preview.files_restored.iter().map(|f| f.path.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn discard_requires_the_token_of_an_unchanged_preview() {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "a.txt", "one\n");
+        write(dir.path(), "b.txt", "one\n");
+        write(dir.path(), "c.txt", "one\n");
+        commit_all(&repo, "initial");
+        write(dir.path(), "a.txt", "two\n");
+        write(dir.path(), "b.txt", "two\n");
+
+        let preview = discard(&dir, "*.txt", true, None).await.unwrap();
+        assert!(!preview.executed);
+        assert_eq!(restored(&preview), ["a.txt", "b.txt"]);
+
+        // Without a token nothing is discarded
+        let missing = discard(&dir, "*.txt", false, None).await.unwrap_err();
+        assert!(missing.contains("preview it with dry_run first"), "{}", missing);
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "two\n");
+
+        // An edit after the preview makes its token stale, for the dry run and the discard alike
+        write(dir.path(), "a.txt", "three\n");
+        for dry_run in [true, false] {
+            let stale = discard(&dir, "*.txt", dry_run, Some(&preview.token)).await.unwrap_err();
+            assert!(stale.contains("changed since the preview"), "{}", stale);
+        }
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "three\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("b.txt")).unwrap(), "two\n");
+
+        // So does a file the preview didn't cover starting to differ
+        let fresh = discard(&dir, "*.txt", true, None).await.unwrap();
+        assert_ne!(fresh.token, preview.token);
+        write(dir.path(), "c.txt", "two\n");
+        assert!(discard(&dir, "*.txt", false, Some(&fresh.token)).await.is_err());
+
+        let current = discard(&dir, "*.txt", true, None).await.unwrap();
+        let executed = discard(&dir, "*.txt", false, Some(&current.token)).await.unwrap();
+        assert!(executed.executed);
+        assert_eq!(restored(&executed), ["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\n");
+    }
+
+    #[tokio::test]
+    async fn dry_run_lists_exactly_what_the_discard_restores() {
+        let (dir, repo) = test_support::repo();
+        for path in ["src/edited.rs", "src/deleted.rs", "src/staged.rs", "src/kept.rs", "docs/edited.md"] {
+            write(dir.path(), path, "one\n");
+        }
+        commit_all(&repo, "initial");
+        write(dir.path(), "src/edited.rs", "two\n");
+        std::fs::remove_file(dir.path().join("src/deleted.rs")).unwrap();
+        write(dir.path(), "src/staged.rs", "two\n");
+        stage(&repo, "src/staged.rs");
+        write(dir.path(), "src/untracked.rs", "new\n");
+        write(dir.path(), "docs/edited.md", "two\n");
+
+        let changed = |dir: &tempfile::TempDir| -> Vec<String> {
+            let status = status(dir);
+            status.modified.iter().chain(&status.deleted).map(|f| f.path.clone()).collect()
+        };
+        let before = changed(&dir);
+
+        let preview = discard(&dir, "src/*", true, None).await.unwrap();
+        assert_eq!(restored(&preview), ["src/deleted.rs", "src/edited.rs"]);
+        assert_eq!(changed(&dir), before);
+
+        let executed = discard(&dir, "src/*", false, Some(&preview.token)).await.unwrap();
+        assert_eq!(restored(&executed), restored(&preview));
+        assert_eq!(executed.token, preview.token);
+
+        // What stopped differing from the index is exactly what the preview listed
+        let after = changed(&dir);
+        let mut gone: Vec<&str> = before.iter().filter(|path| !after.contains(path)).map(String::as_str).collect();
+        gone.sort();
+        assert_eq!(gone, restored(&preview));
+        assert_eq!(std::fs::read_to_string(dir.path().join("src/deleted.rs")).unwrap(), "one\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("src/staged.rs")).unwrap(), "two\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("src/untracked.rs")).unwrap(), "new\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("docs/edited.md")).unwrap(), "two\n");
+    }
 }
@@ -0,0 +1,246 @@
+// mensa - Query History Module
+// Appends a record for every finished query to app data (query-history.jsonl)
+
+use crate::context::TrimmedResume;
+use crate::pagination::{self, CursorScope, Page, PageError};
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Serializes appends, so concurrent records never interleave
+static HISTORY_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRecord {
+    pub query_id: String,
+    pub working_dir: String,
+    /// Query preset the config was resolved from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Recorded cwd of a resumed session that ran in a different directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remapped_from: Option<String>,
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub exit_code: Option<i32>,
+    pub cancelled: bool,
+    #[serde(default)]
+    pub changed_files: Vec<String>,
+    /// Hooks that finished during the run (None when none were seen)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HookSummary>,
+    /// Cost in USD: the SDK's total when the run reported one, otherwise the streamed estimate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    /// "completed" | "failed" | "cancelled" | "cost_limit_exceeded"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_reason: Option<String>,
+    /// Set when a resume ran from a copy of the session with large images trimmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trimmed_resume: Option<TrimmedResume>,
+    /// Query group (from `query_claude_multi`) this run was part of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    /// Benign stderr lines (deprecation notices and the like) kept out of error messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_suppressed: Option<u32>,
+    /// Session the run wrote to, so its transcript can be found again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Model of the run's last assistant message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Set when the run was started by approving a plan (`approve_plan_and_continue`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_approval: Option<PlanApproval>,
+    /// Set when read-only mode narrowed the run to plan mode without write tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    /// Set on quick questions, whose session is deleted once they finish
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ephemeral: Option<bool>,
+}
+
+/// Who approved which plan, and the exact text they approved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanApproval {
+    pub plan_filename: String,
+    /// SHA-256 of the plan as approved, edits included
+    pub plan_hash: String,
+    /// "Name <email>" of the workspace's commit identity
+    pub approved_by: String,
+    pub approved_at: i64,
+    /// The plan was edited before it was approved
+    pub edited: bool,
+    /// Query that wrote the plan
+    pub planned_in: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookSummary {
+    pub run: u32,
+    pub blocked: u32,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+pub fn history_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("query-history.jsonl"))
+}
+
+/// Append one record to the history file (and to the usage log, while that records)
+pub async fn append_record(app: &tauri::AppHandle, record: &QueryRecord) -> Result<(), String> {
+    let path = history_path(app)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+
+    let mut line = serde_json::to_string(record)
+        .map_err(|e| format!("Failed to serialize query record: {}", e))?;
+    line.push('\n');
+
+    let _guard = HISTORY_LOCK.lock().await;
+    // A crash mid-append leaves a partial last line; end it so this record starts on its own
+    // (load_records skips the partial one)
+    if !ends_with_newline(&path).await {
+        line.insert(0, '\n');
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .map_err(|e| format!("Failed to open query history: {}", e))?;
+    file.write_all(line.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to write query history: {}", e))?;
+    file.sync_data()
+        .await
+        .map_err(|e| format!("Failed to write query history: {}", e))?;
+    drop(_guard);
+    crate::usage::record(app, record).await
+}
+
+/// Whether a file is empty, missing, or ends with a complete line
+async fn ends_with_newline(path: &Path) -> bool {
+    let Ok(mut file) = tokio::fs::File::open(path).await else {
+        return true;
+    };
+    let len = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+    if len == 0 || file.seek(SeekFrom::Start(len - 1)).await.is_err() {
+        return true;
+    }
+    let mut last = [0u8; 1];
+    file.read_exact(&mut last).await.map(|_| last[0] == b'\n').unwrap_or(true)
+}
+
+/// Load all records, oldest first. Corrupt lines are skipped.
+pub async fn load_records(app: &tauri::AppHandle) -> Result<Vec<QueryRecord>, String> {
+    let path = history_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read query history: {}", e))?;
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Find the record for a finished query
+pub async fn find_record(app: &tauri::AppHandle, query_id: &str) -> Result<Option<QueryRecord>, String> {
+    Ok(load_records(app)
+        .await?
+        .into_iter()
+        .rev()
+        .find(|r| r.query_id == query_id))
+}
+
+/// Where a record sits in the history list: finish time, then query id
+type HistoryKey = (i64, String);
+
+/// The `size` records after `after`, newest first, with how many there are and the key to
+/// continue from
+async fn history_page(
+    app: &tauri::AppHandle,
+    group_id: Option<&str>,
+    after: Option<HistoryKey>,
+    size: usize,
+) -> Result<(Vec<QueryRecord>, u64, Option<HistoryKey>), String> {
+    let mut records = load_records(app).await?;
+    if let Some(group_id) = group_id {
+        records.retain(|r| r.group_id.as_deref() == Some(group_id));
+    }
+    let total = records.len() as u64;
+    records.sort_by(|a, b| (b.finished_at, &b.query_id).cmp(&(a.finished_at, &a.query_id)));
+    let (records, next) = pagination::keyset_page(records, |r| (r.finished_at, r.query_id.clone()), after.as_ref(), size);
+    Ok((records, total, next))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List the most recent finished queries, newest first; `group_id` keeps one query group's runs
+#[tauri::command]
+pub async fn list_query_history(
+    app: tauri::AppHandle,
+    limit: Option<usize>,
+    group_id: Option<String>,
+) -> Result<Vec<QueryRecord>, String> {
+    let (records, _, _) = history_page(&app, group_id.as_deref(), None, limit.unwrap_or(100)).await?;
+    Ok(records)
+}
+
+/// `list_query_history` a page at a time. Pages follow finish time then query id, newest first,
+/// so queries finishing during a walk don't shift it.
+#[tauri::command]
+pub async fn list_query_history_paged(
+    app: tauri::AppHandle,
+    group_id: Option<String>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<Page<QueryRecord>, PageError> {
+    let size = pagination::page_size(limit)?;
+    let scope = CursorScope::new("query_history", &[group_id.as_deref().unwrap_or_default()]);
+    let after: Option<HistoryKey> = scope.decode(cursor.as_deref())?;
+    let (records, total, next) = history_page(&app, group_id.as_deref(), after, size).await?;
+    Ok(scope.page(records, Some(total), next))
+}
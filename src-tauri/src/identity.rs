@@ -0,0 +1,248 @@
+// mensa - Commit Identity Module
+// Per-workspace author overrides for commits made from mensa, and a check of the identity
+// against who has been committing to the repository
+
+use crate::{git, workspace, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Used when neither the workspace nor git config names an author
+const FALLBACK_NAME: &str = "Mensa User";
+const FALLBACK_EMAIL: &str = "user@mensa.local";
+
+/// Commits on HEAD whose authors the mismatch check compares against
+const RECENT_COMMITS: usize = 50;
+
+/// A commit author, as stored in the workspace state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitIdentity {
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdentitySource {
+    /// Set for this workspace with `set_commit_identity`
+    Workspace,
+    /// user.name and user.email from git config
+    GitConfig,
+    /// Nothing configured; mensa's placeholder
+    Fallback,
+}
+
+/// The author commits from mensa use in a workspace, and where it came from
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveIdentity {
+    pub name: String,
+    pub email: String,
+    pub source: IdentitySource,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentAuthor {
+    pub name: String,
+    pub email: String,
+    pub commits: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityMismatch {
+    pub identity: EffectiveIdentity,
+    /// Authors of the last commits on HEAD, most commits first
+    pub recent_authors: Vec<RecentAuthor>,
+    /// None of the recent commits was authored with the identity's email
+    pub mismatch: bool,
+    pub message: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Reject empty names, names git can't store, and emails that aren't `local@domain.tld`
+pub fn validate(name: &str, email: &str) -> Result<CommitIdentity, String> {
+    let (name, email) = (name.trim(), email.trim());
+    if name.is_empty() {
+        return Err("Author name can't be empty".to_string());
+    }
+    if name.contains(['<', '>', '\n']) {
+        return Err(format!("Invalid author name: {}", name));
+    }
+    let well_formed = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.contains(|c: char| c.is_whitespace() || c == '<' || c == '>')
+        }
+        None => false,
+    };
+    if !well_formed {
+        return Err(format!("Invalid email address: {}", email));
+    }
+    Ok(CommitIdentity {
+        name: name.to_string(),
+        email: email.to_string(),
+    })
+}
+
+/// The workspace's override, else git config's user, else the placeholder
+pub async fn effective(app: &tauri::AppHandle, state: &AppState, working_dir: &str) -> Result<EffectiveIdentity, String> {
+    let root = workspace::canonical_dir(working_dir)?;
+    if let Some(identity) = workspace::load_state(app, state, &root).await?.commit_identity {
+        return Ok(EffectiveIdentity {
+            name: identity.name,
+            email: identity.email,
+            source: IdentitySource::Workspace,
+        });
+    }
+    let repo = git::open_repo(working_dir)?;
+    let configured = repo.signature().ok().and_then(|signature| {
+        Some(EffectiveIdentity {
+            name: signature.name()?.to_string(),
+            email: signature.email()?.to_string(),
+            source: IdentitySource::GitConfig,
+        })
+    });
+    Ok(configured.unwrap_or_else(|| EffectiveIdentity {
+        name: FALLBACK_NAME.to_string(),
+        email: FALLBACK_EMAIL.to_string(),
+        source: IdentitySource::Fallback,
+    }))
+}
+
+/// Authors of the last RECENT_COMMITS commits on HEAD, most commits first
+fn recent_authors(working_dir: &str) -> Result<Vec<RecentAuthor>, String> {
+    let repo = git::open_repo(working_dir)?;
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    if revwalk.push_head().is_err() {
+        return Ok(Vec::new());
+    }
+
+    let mut authors: Vec<RecentAuthor> = Vec::new();
+    // A shallow clone's missing parents end the walk early
+    for oid in revwalk.take(RECENT_COMMITS).map_while(Result::ok) {
+        let Ok(commit) = repo.find_commit(oid) else {
+            break;
+        };
+        let author = commit.author();
+        let (name, email) = (author.name().unwrap_or_default(), author.email().unwrap_or_default());
+        match authors.iter_mut().find(|a| a.email.eq_ignore_ascii_case(email)) {
+            Some(known) => known.commits += 1,
+            None => authors.push(RecentAuthor {
+                name: name.to_string(),
+                email: email.to_string(),
+                commits: 1,
+            }),
+        }
+    }
+    authors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.email.cmp(&b.email)));
+    Ok(authors)
+}
+
+/// Compare the identity with the recent authors. Someone with the identity's name committing
+/// under another email is called out, as that's usually the same person on a different machine.
+fn mismatch(identity: EffectiveIdentity, recent_authors: Vec<RecentAuthor>) -> IdentityMismatch {
+    let mismatch = !recent_authors.is_empty() && !recent_authors.iter().any(|a| a.email.eq_ignore_ascii_case(&identity.email));
+    let same_name = recent_authors.iter().find(|a| a.name.eq_ignore_ascii_case(&identity.name));
+    let message = match (mismatch, same_name) {
+        (false, _) => None,
+        (true, Some(author)) => Some(format!(
+            "Commits would be authored as {} <{}>, but {} recently committed here as <{}>",
+            identity.name, identity.email, author.name, author.email
+        )),
+        (true, None) => Some(format!(
+            "None of the last {} commits was authored as <{}>",
+            recent_authors.iter().map(|a| a.commits as usize).sum::<usize>(),
+            identity.email
+        )),
+    };
+    IdentityMismatch {
+        identity,
+        recent_authors,
+        mismatch,
+        message,
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// The author a commit from mensa would use right now, so it can be shown before committing
+#[tauri::command]
+pub async fn get_commit_identity(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+) -> Result<EffectiveIdentity, String> {
+    effective(&app, &state, &working_dir).await
+}
+
+/// Set the author of commits made from mensa in this workspace; with both `name` and `email`
+/// None the override is removed and git config applies again. `write_git_config` also sets
+/// user.name and user.email in the repository's local git config.
+#[tauri::command]
+pub async fn set_commit_identity(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+    name: Option<String>,
+    email: Option<String>,
+    write_git_config: Option<bool>,
+) -> Result<EffectiveIdentity, String> {
+    let root = workspace::canonical_dir(&working_dir)?;
+    let identity = match (name, email) {
+        (Some(name), Some(email)) => Some(validate(&name, &email)?),
+        (None, None) => None,
+        _ => return Err("Set both the author name and email, or neither".to_string()),
+    };
+
+    if let (Some(identity), true) = (&identity, write_git_config.unwrap_or(false)) {
+        let repo = git::open_repo(&working_dir)?;
+        let mut config = repo
+            .config()
+            .and_then(|config| config.open_level(git2::ConfigLevel::Local))
+            .map_err(|e| format!("Failed to open repository config: {}", e))?;
+        config
+            .set_str("user.name", &identity.name)
+            .and_then(|_| config.set_str("user.email", &identity.email))
+            .map_err(|e| format!("Failed to write repository config: {}", e))?;
+    }
+
+    let value = serde_json::to_value(&identity).map_err(|e| format!("Failed to serialize identity: {}", e))?;
+    let mut patch = Map::new();
+    patch.insert("commitIdentity".to_string(), value);
+    workspace::patch_state(&app, &state, root, patch).await?;
+    effective(&app, &state, &working_dir).await
+}
+
+/// Compare the identity commits would use with the authors of the repository's recent commits,
+/// warning when it matches none of them (often a work repo cloned onto a personal machine)
+#[tauri::command]
+pub async fn detect_identity_mismatch(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+) -> Result<IdentityMismatch, String> {
+    let identity = effective(&app, &state, &working_dir).await?;
+    let dir = working_dir.clone();
+    let recent_authors = tokio::task::spawn_blocking(move || recent_authors(&dir))
+        .await
+        .map_err(|e| format!("Author scan failed: {}", e))??;
+    Ok(mismatch(identity, recent_authors))
+}
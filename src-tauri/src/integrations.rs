@@ -0,0 +1,404 @@
+// mensa - Integrations Module
+// Hands backend events (query completed, commit created, PR opened) to user-configured commands or an NDJSON feed
+
+use crate::{settings, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Manager, State};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Bumped when a field of `IntegrationEvent` changes meaning or goes away
+const EVENT_SCHEMA_VERSION: u32 = 1;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+pub const MAX_TIMEOUT_SECS: u64 = 300;
+
+/// Size at which a feed file is rotated, unless the hook sets its own
+const DEFAULT_FEED_MAX_BYTES: u64 = 10 * 1024 * 1024;
+/// Rotated feed files kept next to the live one (`feed.ndjson.1` is the newest)
+const FEED_ROTATIONS: usize = 3;
+
+/// Characters of a command's stderr kept in its invocation record
+const STDERR_TAIL_CHARS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegrationEventKind {
+    QueryCompleted,
+    CommitCreated,
+    PrOpened,
+    /// Synthetic, sent by `test_integration_hook` only
+    Test,
+}
+
+/// A hook from settings: events it listens for and where they go.
+/// Exactly one of `command` and `append_to_feed` is set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct IntegrationHook {
+    pub event_kinds: Vec<IntegrationEventKind>,
+    /// Program and arguments, run without a shell; the event is written to its stdin as JSON
+    pub command: Option<Vec<String>>,
+    /// File the events are appended to as NDJSON instead
+    pub append_to_feed: Option<String>,
+    /// Seconds a command may run before it's killed (default 10)
+    pub timeout_secs: Option<u64>,
+    /// Size at which the feed is rotated (default 10 MB)
+    pub feed_max_bytes: Option<u64>,
+}
+
+/// What a hook receives: one feed line, or a command's stdin
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrationEvent {
+    pub version: u32,
+    pub id: String,
+    pub kind: IntegrationEventKind,
+    /// Unix seconds
+    pub timestamp: i64,
+    pub data: Value,
+}
+
+/// The outcome of handing one event to one hook
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookInvocation {
+    pub index: usize,
+    pub event_id: String,
+    pub kind: IntegrationEventKind,
+    /// None for feeds, and for commands that were killed or never started
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+    /// The end of the command's stderr
+    pub stderr: String,
+}
+
+struct Job {
+    index: usize,
+    event: Arc<IntegrationEvent>,
+    reply: Option<oneshot::Sender<HookInvocation>>,
+}
+
+/// One worker per configured hook, so a hook sees its events one at a time and in order.
+/// Keyed by the hook's serialized config; editing a hook gets it a fresh worker.
+#[derive(Default)]
+pub struct IntegrationHooks {
+    workers: Mutex<HashMap<String, mpsc::UnboundedSender<Job>>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Check a hook from a settings patch
+pub fn validate_hook(hook: &IntegrationHook) -> Result<(), String> {
+    if hook.event_kinds.is_empty() {
+        return Err("eventKinds must not be empty".to_string());
+    }
+    match (&hook.command, &hook.append_to_feed) {
+        (Some(command), None) => {
+            if command.first().is_none_or(|program| program.trim().is_empty()) {
+                return Err("command must start with a program".to_string());
+            }
+        }
+        (None, Some(feed)) => {
+            if !Path::new(feed).is_absolute() {
+                return Err("appendToFeed must be an absolute path".to_string());
+            }
+        }
+        _ => return Err("set exactly one of command and appendToFeed".to_string()),
+    }
+    if let Some(secs) = hook.timeout_secs {
+        if secs == 0 || secs > MAX_TIMEOUT_SECS {
+            return Err(format!("timeoutSecs must be between 1 and {}", MAX_TIMEOUT_SECS));
+        }
+    }
+    if hook.feed_max_bytes == Some(0) {
+        return Err("feedMaxBytes must be positive".to_string());
+    }
+    Ok(())
+}
+
+impl IntegrationEventKind {
+    /// The serialized name, also set as `MENSA_EVENT_KIND` for commands
+    fn as_str(self) -> &'static str {
+        match self {
+            IntegrationEventKind::QueryCompleted => "queryCompleted",
+            IntegrationEventKind::CommitCreated => "commitCreated",
+            IntegrationEventKind::PrOpened => "prOpened",
+            IntegrationEventKind::Test => "test",
+        }
+    }
+}
+
+fn hook_key(hook: &IntegrationHook) -> String {
+    serde_json::to_string(hook).unwrap_or_default()
+}
+
+fn describe(hook: &IntegrationHook) -> String {
+    match (&hook.command, &hook.append_to_feed) {
+        (Some(command), _) => command.first().cloned().unwrap_or_default(),
+        (_, Some(feed)) => feed.clone(),
+        _ => String::new(),
+    }
+}
+
+fn new_event(kind: IntegrationEventKind, data: Value) -> IntegrationEvent {
+    IntegrationEvent {
+        version: EVENT_SCHEMA_VERSION,
+        id: uuid::Uuid::new_v4().to_string(),
+        kind,
+        timestamp: crate::history::now_secs(),
+        data,
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Shift `feed.N` up by one (dropping the oldest) and move the live file to `feed.1`
+fn rotate_feed(path: &Path) {
+    let _ = std::fs::remove_file(rotated_path(path, FEED_ROTATIONS));
+    for n in (1..FEED_ROTATIONS).rev() {
+        let _ = std::fs::rename(rotated_path(path, n), rotated_path(path, n + 1));
+    }
+    let _ = std::fs::rename(path, rotated_path(path, 1));
+}
+
+fn append_line(path: &Path, line: &[u8], max_bytes: u64) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create feed directory: {}", e))?;
+    }
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 > max_bytes {
+        rotate_feed(path);
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open feed: {}", e))?;
+    file.write_all(line).map_err(|e| format!("Failed to write feed: {}", e))
+}
+
+fn stderr_tail(stderr: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stderr);
+    let skip = text.chars().count().saturating_sub(STDERR_TAIL_CHARS);
+    text.chars().skip(skip).collect()
+}
+
+/// Run the command with the event on stdin, killing it at the timeout
+async fn run_command(
+    argv: &[String],
+    event: &IntegrationEvent,
+    payload: Vec<u8>,
+    timeout: Duration,
+    proxy: Vec<(String, String)>,
+) -> (Option<i32>, bool, Option<String>, String) {
+    let mut cmd = tokio::process::Command::new(&argv[0]);
+    cmd.args(&argv[1..])
+        .envs(proxy)
+        .env("MENSA_EVENT_KIND", event.kind.as_str())
+        .env("MENSA_EVENT_ID", &event.id)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => return (None, false, Some(format!("Failed to start {}: {}", argv[0], e)), String::new()),
+    };
+
+    let run = async move {
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that ignores stdin may close it early; that's not a failure
+            let _ = stdin.write_all(&payload).await;
+            let _ = stdin.write_all(b"\n").await;
+        }
+        child.wait_with_output().await
+    };
+    match tokio::time::timeout(timeout, run).await {
+        Ok(Ok(output)) => (output.status.code(), false, None, stderr_tail(&output.stderr)),
+        Ok(Err(e)) => (None, false, Some(format!("Failed to wait for {}: {}", argv[0], e)), String::new()),
+        Err(_) => (None, true, Some(format!("Killed after {}s", timeout.as_secs())), String::new()),
+    }
+}
+
+async fn deliver(app: &tauri::AppHandle, hook: &IntegrationHook, job: &Job) -> HookInvocation {
+    let started = Instant::now();
+    let mut invocation = HookInvocation {
+        index: job.index,
+        event_id: job.event.id.clone(),
+        kind: job.event.kind,
+        exit_code: None,
+        timed_out: false,
+        duration_ms: 0,
+        error: None,
+        stderr: String::new(),
+    };
+    let payload = match serde_json::to_vec(job.event.as_ref()) {
+        Ok(payload) => payload,
+        Err(e) => {
+            invocation.error = Some(format!("Failed to serialize event: {}", e));
+            return invocation;
+        }
+    };
+
+    if let Some(argv) = &hook.command {
+        let timeout = Duration::from_secs(hook.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+        let proxy = app.state::<AppState>().proxy.vars();
+        let (exit_code, timed_out, error, stderr) = run_command(argv, &job.event, payload, timeout, proxy).await;
+        invocation.exit_code = exit_code;
+        invocation.timed_out = timed_out;
+        invocation.error = error;
+        invocation.stderr = stderr;
+    } else if let Some(feed) = &hook.append_to_feed {
+        let path = PathBuf::from(feed);
+        let max_bytes = hook.feed_max_bytes.unwrap_or(DEFAULT_FEED_MAX_BYTES);
+        let mut line = payload;
+        line.push(b'\n');
+        invocation.error = match tokio::task::spawn_blocking(move || append_line(&path, &line, max_bytes)).await {
+            Ok(result) => result.err(),
+            Err(e) => Some(format!("Failed to write feed: {}", e)),
+        };
+    }
+    invocation.duration_ms = started.elapsed().as_millis() as u64;
+    invocation
+}
+
+fn log_invocation(hook: &IntegrationHook, invocation: &HookInvocation) {
+    let outcome = match (&invocation.error, invocation.exit_code) {
+        (Some(error), _) => error.clone(),
+        (None, Some(code)) => format!("exit {}", code),
+        (None, None) => "ok".to_string(),
+    };
+    eprintln!(
+        "[mensa] integration hook #{} ({}) {} {}: {} in {}ms",
+        invocation.index,
+        describe(hook),
+        invocation.kind.as_str(),
+        invocation.event_id,
+        outcome,
+        invocation.duration_ms
+    );
+}
+
+fn spawn_worker(app: &tauri::AppHandle, hook: IntegrationHook) -> mpsc::UnboundedSender<Job> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Job>();
+    let app = app.clone();
+    let tasks = app.state::<AppState>().tasks.clone();
+    tasks.spawn(format!("integration hook {}", describe(&hook)), |token| async move {
+        loop {
+            let job = tokio::select! {
+                job = receiver.recv() => job,
+                _ = token.cancelled() => return,
+            };
+            // The sender is dropped once the hook leaves the settings
+            let Some(job) = job else { return };
+            let invocation = deliver(&app, &hook, &job).await;
+            log_invocation(&hook, &invocation);
+            if let Some(reply) = job.reply {
+                let _ = reply.send(invocation);
+            }
+        }
+    });
+    sender
+}
+
+/// Queue a job on the hook's worker, starting it if needed, and stop the workers of removed hooks
+fn enqueue(app: &tauri::AppHandle, hooks: &[IntegrationHook], jobs: Vec<(usize, Job)>) {
+    let state = app.state::<AppState>();
+    let mut workers = state.integrations.workers.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let current: Vec<String> = hooks.iter().map(hook_key).collect();
+    workers.retain(|key, _| current.contains(key));
+    for (index, job) in jobs {
+        let hook = &hooks[index];
+        let key = &current[index];
+        let sender = workers
+            .entry(key.clone())
+            .or_insert_with(|| spawn_worker(app, hook.clone()));
+        if let Err(mpsc::error::SendError(job)) = sender.send(job) {
+            // The worker was stopped (shutdown); start over once
+            let fresh = spawn_worker(app, hook.clone());
+            let _ = fresh.send(job);
+            workers.insert(key.clone(), fresh);
+        }
+    }
+}
+
+/// Hand an event to every hook listening for its kind. Never fails: delivery happens
+/// in the background and problems only reach the log.
+pub async fn notify(app: &tauri::AppHandle, kind: IntegrationEventKind, data: Value) {
+    let state = app.state::<AppState>();
+    let hooks = match settings::load(app, &state.settings).await {
+        Ok(settings) => settings.integration_hooks.clone(),
+        Err(e) => {
+            eprintln!("[mensa] {}", e);
+            return;
+        }
+    };
+    let event = Arc::new(new_event(kind, data));
+    let jobs: Vec<(usize, Job)> = hooks
+        .iter()
+        .enumerate()
+        .filter(|(_, hook)| hook.event_kinds.contains(&kind))
+        .map(|(index, _)| {
+            (index, Job {
+                index,
+                event: event.clone(),
+                reply: None,
+            })
+        })
+        .collect();
+    if jobs.is_empty() {
+        return;
+    }
+    enqueue(app, &hooks, jobs);
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Send a synthetic `test` event to the hook at `index` (after anything already queued for it)
+/// and report how it went
+#[tauri::command]
+pub async fn test_integration_hook(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    index: usize,
+) -> Result<HookInvocation, String> {
+    let hooks = settings::load(&app, &state.settings).await?.integration_hooks.clone();
+    if index >= hooks.len() {
+        return Err(format!("No integration hook at index {}", index));
+    }
+    let event = new_event(
+        IntegrationEventKind::Test,
+        serde_json::json!({ "message": "Test event from mensa" }),
+    );
+    let (reply, received) = oneshot::channel();
+    enqueue(&app, &hooks, vec![(index, Job {
+        index,
+        event: Arc::new(event),
+        reply: Some(reply),
+    })]);
+    received
+        .await
+        .map_err(|_| "Integration hook stopped before handling the test event".to_string())
+}
@@ -0,0 +1,59 @@
+// mensa - Launcher Module
+// Starts the query script's process, behind a trait so queries can run against a stand-in
+
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::process::{Child, Command};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// What to run for a query: its stdout and stderr are read by the caller
+#[derive(Debug, Clone)]
+pub struct LaunchSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub envs: Vec<(String, String)>,
+    pub working_dir: String,
+}
+
+/// Starts a query's process with piped stdout and stderr
+pub trait ProcessLauncher: Send + Sync {
+    fn launch(&self, spec: &LaunchSpec) -> std::io::Result<Child>;
+}
+
+/// Runs the spec as given
+struct CommandLauncher;
+
+/// The launcher queries start through (a `CommandLauncher` by default)
+#[derive(Clone)]
+pub struct QueryLauncher(pub Arc<dyn ProcessLauncher>);
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl ProcessLauncher for CommandLauncher {
+    fn launch(&self, spec: &LaunchSpec) -> std::io::Result<Child> {
+        Command::new(&spec.program)
+            .args(&spec.args)
+            .envs(spec.envs.clone())
+            .current_dir(&spec.working_dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+    }
+}
+
+impl Default for QueryLauncher {
+    fn default() -> Self {
+        Self(Arc::new(CommandLauncher))
+    }
+}
+
+impl QueryLauncher {
+    pub fn launch(&self, spec: &LaunchSpec) -> std::io::Result<Child> {
+        self.0.launch(spec)
+    }
+}
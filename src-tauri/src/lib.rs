@@ -1,31 +1,278 @@
 // mensa - Tauri backend
 
+mod annotations;
+mod app_data;
+mod attachments;
+mod bookmarks;
+mod cancel;
+mod checkpoints;
+mod cli;
+mod compare;
+mod context;
+mod context_usage;
+mod control;
+mod cost;
+mod diff_attributes;
+mod digest;
+mod disk;
+mod editor;
+mod export;
+mod file_index;
+mod follow;
+mod forge;
+mod fsutil;
+mod gh_auth;
+mod gh_json;
 mod git;
-
-use std::collections::HashMap;
+mod history;
+mod identity;
+mod integrations;
+mod launcher;
+mod markdown;
+mod models;
+mod patch;
+mod overview;
+mod pagination;
+mod paths;
+mod permissions;
+mod plan_approval;
+mod pr_batch;
+mod pr_context;
+mod presets;
+mod progress;
+mod proxy;
+mod query_cancel;
+mod query_group;
+mod quick_question;
+mod read_only;
+mod references;
+mod prompt_history;
+mod replay;
+mod review_drafts;
+mod review_fixes;
+mod runtime;
+mod script;
+mod secrets;
+mod sensitive;
+mod session_guard;
+mod session_cache;
+mod session_index;
+mod session_migration;
+mod session_search;
+mod settings;
+mod status_filters;
+mod stderr;
+mod store;
+mod stream;
+mod tasks;
+mod templates;
+#[cfg(test)]
+mod test_support;
+mod timeline;
+mod titles;
+mod tool_output;
+mod tool_policies;
+mod transcript;
+mod updates;
+mod usage;
+mod workspace;
+mod workspace_health;
+mod workspace_templates;
+
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
 use std::sync::Arc;
-use tauri::{Emitter, Manager, State};
+use tauri::{Manager, State};
 use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
 use tokio::sync::Mutex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
+use transcript::{parse_session_messages, parse_session_messages_until, truncate_tool_outputs, SessionMessage};
 
 /// Active query tracking for cancellation support
 pub struct ActiveQuery {
     pub child: tokio::process::Child,
+    pub phase: QueryPhase,
     pub started_at: std::time::Instant,
+    pub working_dir: String,
+    /// Files the agent has touched so far, relative to the working directory
+    pub changed_files: HashSet<PathBuf>,
+    /// Prompt to send automatically once this query finishes successfully
+    pub followup: Option<QueuedFollowup>,
+    /// Full text of tool outputs cut down to a preview in the stream, by tool_use_id
+    pub tool_outputs: HashMap<String, String>,
+    /// Latest context-window usage, once an assistant message reported tokens
+    pub context_usage: Option<context_usage::ContextUsage>,
+}
+
+/// Where a query is in its teardown. It leaves `active_queries` when it finishes, and its
+/// replay buffer takes nothing after the terminal event (see `replay::emit`). Cancelling moves
+/// it along as `ActiveQuery::begin_cancel` decides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryPhase {
+    Running,
+    /// Stdout closed; stderr is being drained before the terminal event goes out
+    Draining,
+    /// `cancel_query` stopped the agent; the run ends as cancelled
+    Cancelling,
+}
+
+/// A follow-up prompt waiting on its predecessor query
+pub struct QueuedFollowup {
+    pub query_id: String,
+    pub prompt: String,
+    pub config: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActiveQueryInfo {
+    query_id: String,
+    status: String, // "running" | "draining" | "cancelling" | "queued"
+    predecessor: Option<String>,
 }
 
 /// Application state for managing concurrent queries
 #[derive(Default)]
 pub struct AppState {
     pub active_queries: Arc<Mutex<HashMap<String, ActiveQuery>>>,
+    /// Canonical paths of workspaces the user picked in the UI
+    pub selected_workspaces: Arc<Mutex<HashSet<PathBuf>>>,
+    /// Remembered per-workspace UI state (last session, branch, PR, layout)
+    pub workspace_states: workspace::WorkspaceStateCache,
+    /// Where the query script was last found (reported by the health check)
+    pub script_location: Arc<Mutex<Option<script::ScriptLocation>>>,
+    /// Files currently followed by a live view
+    pub followed_files: follow::FollowRegistry,
+    /// Where tool results sit in session transcripts, for lazy output fetches
+    pub tool_outputs: tool_output::ToolOutputIndex,
+    /// App-wide preferences
+    pub settings: settings::SettingsStore,
+    /// Sequenced events of running and recently finished queries
+    pub replay: replay::ReplayBuffers,
+    /// Parsed transcripts, reused while their files are unchanged
+    pub session_cache: session_cache::SessionCache,
+    /// Long-running operations currently reporting progress
+    pub operations: progress::OperationRegistry,
+    /// Workspace file lists for @-mention matching
+    pub file_index: file_index::FileIndexCache,
+    /// TODO/FIXME scans per workspace
+    pub annotations: annotations::AnnotationCache,
+    /// Workspace overviews by the HEAD they were computed at
+    pub overviews: overview::OverviewCache,
+    /// Cancellation tokens of in-flight session loads
+    pub cancellations: cancel::CancelRegistry,
+    /// Query groups started by `query_claude_multi`
+    pub groups: query_group::QueryGroups,
+    /// Quick questions whose session is still to be deleted
+    pub quick_sessions: quick_question::QuickSessions,
+    /// Long-lived background tasks, stopped together at exit
+    pub tasks: tasks::TaskRegistry,
+    /// Resolved proxy variables set on every child process
+    pub proxy: proxy::ProxyEnv,
+    /// Delivery workers of the configured integration hooks
+    pub integrations: integrations::IntegrationHooks,
+    /// Update found by the last check
+    pub updates: updates::UpdateState,
+    /// Starts query processes
+    pub launcher: launcher::QueryLauncher,
+}
+
+/// Optional backend behaviours for a query
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct QueryOptions {
+    /// Mask values from sensitive files (.env etc.) in streamed tool results
+    redact_sensitive: bool,
+    /// Names of keychain secrets to expose to the query as environment variables
+    secret_env: Vec<String>,
+    /// Longest edge image attachments are downscaled to (default 1568px, at least 64px)
+    max_image_edge: Option<u32>,
+    /// Resume a session even though it was recorded in a different directory
+    allow_remap: bool,
+    /// Longest tool output sent inline in the stream; the rest is fetched on demand
+    tool_output_preview: Option<usize>,
+    /// Stop the query once its estimated cost reaches this many USD (default: the config's maxCostUsd)
+    max_cost_usd: Option<f64>,
+    /// Fraction of the ceiling at which `claude-cost-warning` fires once
+    cost_warning_fraction: Option<f64>,
+    /// Resume from a copy of the session with large images replaced by placeholders
+    trim_images_on_resume: bool,
+    /// Check the files the prompt refers to and emit `prompt-reference-warnings` for missing ones
+    validate_references: bool,
+    /// Refuse to start when the prompt refers to files that don't exist (implies validate_references)
+    strict_references: bool,
+    /// Don't check free disk space before starting, or watch it while running
+    skip_disk_check: bool,
+    /// Resume even though another Claude Code is using the session's project
+    force_resume: bool,
+}
+
+/// Error returned by `query_claude`; mismatches are typed so the UI can offer to remap
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+enum QueryError {
+    SessionWorkspaceMismatch { recorded_cwd: String, working_dir: String },
+    /// `strictReferences` was set and the prompt names files that don't exist
+    UnknownReferences { references: Vec<references::ReferenceCheck> },
+    /// The node binary is older than the agent SDK supports; `alternative` is a newer one found elsewhere
+    NodeTooOld {
+        found: String,
+        required: String,
+        path: String,
+        alternative: Option<runtime::NodeCandidate>,
+    },
+    /// The workspace's or ~/.claude's volume has less free space than the configured floor
+    LowDiskSpace { path: String, free_bytes: u64, floor_bytes: u64 },
+    /// Another Claude Code is writing to the resumed session's project; `forceResume` goes ahead anyway
+    SessionInUse {
+        session_id: String,
+        by: session_guard::SessionHolder,
+        hint: String,
+    },
+    Failed { message: String },
+}
+
+impl From<String> for QueryError {
+    fn from(message: String) -> Self {
+        QueryError::Failed { message }
+    }
+}
+
+/// Everything needed to start one agent run
+struct QueryRequest {
+    prompt: String,
+    working_dir: String,
+    config: Option<String>,
+    resume_session: Option<String>,
+    has_attachments: Option<bool>,
+    /// Image blocks of staged clipboard attachments, already processed
+    staged_images: Vec<Value>,
+    tool_result: Option<String>,
+    options: QueryOptions,
+    /// Name of the preset the config was resolved from, for history
+    preset: Option<String>,
+    /// Recorded cwd of a resumed session that was allowed to run in a different directory
+    remapped_from: Option<String>,
+    /// The plan approval that started this run, for history
+    plan_approval: Option<history::PlanApproval>,
+    /// A quick question: one tool-less turn, time-boxed, its session deleted afterwards
+    quick: bool,
 }
 
+/// How long a stopped agent gets to exit on SIGTERM before it is killed
+const TERMINATE_GRACE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// How long newly touched files are coalesced before `query-files-changed` is emitted
+const FILES_CHANGED_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How long a finished query waits for its stderr reader to deliver the last lines
+const STDERR_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// How long a query whose output has closed may take to exit before it is stopped
+const CHILD_EXIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 /// Payload wrapper for stream events with query ID
 #[derive(Clone, Serialize)]
 struct StreamPayload {
@@ -33,11 +280,12 @@ struct StreamPayload {
     data: String,
 }
 
-/// Find the node binary in common macOS installation locations.
+/// Node binaries in common macOS installation locations, in preference order.
 /// When launched from Finder/Launchpad, macOS apps don't inherit shell PATH,
 /// so we need to check common locations directly.
-fn find_node_binary() -> String {
-    let home = std::env::var("HOME").unwrap_or_default();
+fn node_candidates() -> Vec<String> {
+    let home = paths::home_dir().ok();
+    let mut candidates = Vec::new();
 
     // Common node installation paths on macOS
     let common_paths = [
@@ -52,13 +300,13 @@ fn find_node_binary() -> String {
     // Check common paths first
     for path in &common_paths {
         if Path::new(path).exists() {
-            return path.to_string();
+            candidates.push(path.to_string());
         }
     }
 
     // Check nvm installations (common versions)
-    if !home.is_empty() {
-        let nvm_base = PathBuf::from(&home).join(".nvm/versions/node");
+    if let Some(home) = home {
+        let nvm_base = home.join(".nvm/versions/node");
         if nvm_base.exists() {
             // Try to find any installed node version
             if let Ok(entries) = std::fs::read_dir(&nvm_base) {
@@ -67,19 +315,29 @@ fn find_node_binary() -> String {
                     .filter(|e| e.path().is_dir())
                     .collect();
                 // Sort by name descending to get latest version first
-                versions.sort_by(|a, b| b.file_name().cmp(&a.file_name()));
+                versions.sort_by_key(|e| std::cmp::Reverse(e.file_name()));
                 for entry in versions {
                     let node_path = entry.path().join("bin/node");
                     if node_path.exists() {
-                        return node_path.to_string_lossy().to_string();
+                        candidates.push(node_path.to_string_lossy().to_string());
                     }
                 }
             }
         }
     }
 
+    candidates
+}
+
+/// The first node binary found in the usual locations
+fn find_node_binary() -> String {
     // Fallback to PATH-based resolution
-    "node".to_string()
+    node_candidates().into_iter().next().unwrap_or_else(|| "node".to_string())
+}
+
+/// Claude Code's per-project directory (~/.claude/projects/<sanitized workspace path>)
+fn project_dir_for_workspace(workspace_path: &str) -> Result<PathBuf, String> {
+    Ok(paths::ClaudeHome::current()?.project_dir(workspace_path))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -90,6 +348,18 @@ struct SessionEntry {
     message_count: u32,
     created: String,
     modified: String,
+    /// Set with rename_session or generate_session_title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    custom_title: Option<String>,
+    /// Working directory the session was recorded in, when it differs from the workspace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    original_cwd: Option<String>,
+    /// The index was behind the transcript; message_count and modified come from the file
+    #[serde(default)]
+    index_stale: bool,
+    /// The index lists the session but its transcript is gone, so it can't be opened
+    #[serde(default)]
+    missing_file: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -98,404 +368,735 @@ struct SessionsIndex {
 }
 
 #[tauri::command]
-async fn delete_session(workspace_path: String, session_id: String) -> Result<bool, String> {
-    let sanitized = workspace_path.replace("/", "-");
-    let home = std::env::var("HOME").map_err(|e| e.to_string())?;
-    let sessions_index_path = format!("{}/.claude/projects/{}/sessions-index.json", home, sanitized);
-    let session_file_path = format!("{}/.claude/projects/{}/{}.jsonl", home, sanitized, session_id);
+async fn delete_session(
+    app: tauri::AppHandle,
+    workspace_path: String,
+    session_id: String,
+) -> Result<bool, permissions::SessionError> {
+    remove_session(&app, &workspace_path, &session_id).await
+}
+
+/// Delete a session's transcript, index entry and bookmarks
+async fn remove_session(app: &tauri::AppHandle, workspace_path: &str, session_id: &str) -> Result<bool, permissions::SessionError> {
+    let project_dir = project_dir_for_workspace(workspace_path)?;
+    let session_path = project_dir.join(format!("{}.jsonl", session_id));
+    permissions::require(&project_dir, true)?;
 
     // Remove from sessions-index.json
-    let index_path = Path::new(&sessions_index_path);
-    if index_path.exists() {
-        let content = tokio::fs::read_to_string(index_path)
+    let removed_id = session_id.to_string();
+    session_index::update_index(&project_dir, move |entries| {
+        entries.retain(|e| e.get("sessionId").and_then(|v| v.as_str()) != Some(removed_id.as_str()));
+    })
+    .await?;
+
+    // Its bookmarks go with it
+    bookmarks::remove_session_bookmarks(app, &project_dir, session_id).await?;
+
+    // Delete the session file
+    if session_path.exists() {
+        tokio::fs::remove_file(&session_path)
             .await
-            .map_err(|e| format!("Failed to read sessions index: {}", e))?;
+            .map_err(|e| permissions::io_error("delete session file", &project_dir, &e))?;
+    }
+    app.state::<AppState>().session_cache.evict(&session_path);
+
+    Ok(true)
+}
+
+/// Sessions of a workspace, newest first. Entries the index has fallen behind on take their
+/// count and modified time from the transcript (`indexStale`), and `healIndex` writes those back;
+/// entries whose transcript is gone come back with `missingFile`. Unusable first prompts are
+/// re-derived from the transcript, and without an index the transcripts themselves are listed.
+/// Sessions of quick questions are left out until they are deleted.
+#[tauri::command]
+async fn list_sessions(
+    state: State<'_, AppState>,
+    workspace_path: String,
+    heal_index: Option<bool>,
+) -> Result<Vec<SessionEntry>, permissions::SessionError> {
+    session_entries(workspace_path, heal_index, &state.quick_sessions.hidden()).await
+}
 
-        let mut index: SessionsIndex = serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to parse sessions index: {}", e))?;
+/// `list_sessions` a page at a time. Pages follow modified time then id, newest first, so
+/// sessions started during a walk don't shift it; one modified mid-walk moves to the top.
+#[tauri::command]
+async fn list_sessions_paged(
+    state: State<'_, AppState>,
+    workspace_path: String,
+    heal_index: Option<bool>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<pagination::Page<SessionEntry>, pagination::PageError> {
+    let size = pagination::page_size(limit)?;
+    let scope = pagination::CursorScope::new("sessions", &[&pagination::path_filter(&workspace_path)]);
+    let after: Option<SessionKey> = scope.decode(cursor.as_deref())?;
+    let hidden = state.quick_sessions.hidden();
+    let (entries, total, next) = session_page(&workspace_path, heal_index, &hidden, after, size).await?;
+    Ok(scope.page(entries, Some(total), next))
+}
 
-        // Filter out the session to delete
-        index.entries.retain(|e| e.session_id != session_id);
+/// Where a session sits in the session list: modified time, then id
+type SessionKey = (String, String);
 
-        // Write back
-        let updated_content = serde_json::to_string_pretty(&index)
-            .map_err(|e| format!("Failed to serialize sessions index: {}", e))?;
+/// What `list_sessions` returns (the first page), without the sessions in `hidden`
+async fn session_entries(
+    workspace_path: String,
+    heal_index: Option<bool>,
+    hidden: &HashSet<String>,
+) -> Result<Vec<SessionEntry>, permissions::SessionError> {
+    let (entries, _, _) = session_page(&workspace_path, heal_index, hidden, None, pagination::DEFAULT_PAGE_SIZE).await?;
+    Ok(entries)
+}
 
-        tokio::fs::write(index_path, updated_content)
+/// The `size` sessions after `after`, newest first, with how many sessions there are and the
+/// key to continue from
+async fn session_page(
+    workspace_path: &str,
+    heal_index: Option<bool>,
+    hidden: &HashSet<String>,
+    after: Option<SessionKey>,
+    size: usize,
+) -> Result<(Vec<SessionEntry>, u64, Option<SessionKey>), permissions::SessionError> {
+    let project_dir = project_dir_for_workspace(workspace_path)?;
+    let path = project_dir.join(session_index::INDEX_FILE);
+    permissions::require(&project_dir, heal_index.unwrap_or(false))?;
+    let mut entries = if path.exists() {
+        let content = tokio::fs::read_to_string(&path)
             .await
-            .map_err(|e| format!("Failed to write sessions index: {}", e))?;
+            .map_err(|e| permissions::io_error("read sessions", &path, &e))?;
+
+        let index: SessionsIndex = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse sessions: {}", e))?;
+
+        session_index::check_entries(&project_dir, index.entries, heal_index.unwrap_or(false)).await?
+    } else {
+        session_index::entries_from_transcripts(&project_dir).await?
+    };
+
+    entries.retain(|entry| !hidden.contains(&entry.session_id));
+    let total = entries.len() as u64;
+
+    // Sort by modified date descending and take the page
+    entries.sort_by(|a, b| (&b.modified, &b.session_id).cmp(&(&a.modified, &a.session_id)));
+    let session_key = |e: &SessionEntry| (e.modified.clone(), e.session_id.clone());
+    let (mut entries, next) = pagination::keyset_page(entries, session_key, after.as_ref(), size);
+
+    // Flag sessions recorded before the project was moved or renamed
+    let canonical = canonical_or_raw(workspace_path);
+    for entry in &mut entries {
+        if let Ok(Some(cwd)) = recorded_session_cwd(workspace_path, &entry.session_id).await {
+            if !fsutil::same_path_nfc(&canonical_or_raw(&cwd), &canonical) {
+                entry.original_cwd = Some(cwd);
+            }
+        }
     }
 
-    // Delete the session file
-    let session_path = Path::new(&session_file_path);
-    if session_path.exists() {
-        tokio::fs::remove_file(session_path)
-            .await
-            .map_err(|e| format!("Failed to delete session file: {}", e))?;
+    Ok((entries, total, next))
+}
+
+fn canonical_or_raw(path: &str) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Locate a session transcript: under the workspace's project dir first, then under
+/// any project dir (the workspace may have been moved since the session was recorded)
+fn find_session_path(workspace_path: &str, session_id: &str) -> Result<Option<PathBuf>, String> {
+    let direct = project_dir_for_workspace(workspace_path)?.join(format!("{}.jsonl", session_id));
+    if direct.exists() {
+        return Ok(Some(direct));
     }
+    find_session_in_any_project(session_id)
+}
 
-    Ok(true)
+/// Search every Claude Code project directory for a session transcript
+fn find_session_in_any_project(session_id: &str) -> Result<Option<PathBuf>, String> {
+    let file_name = format!("{}.jsonl", session_id);
+    let projects = paths::ClaudeHome::current()?.projects();
+    let found = std::fs::read_dir(&projects)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path().join(&file_name))
+        .find(|path| path.exists());
+    Ok(found)
 }
 
-#[tauri::command]
-async fn list_sessions(workspace_path: String) -> Result<Vec<SessionEntry>, String> {
-    // Convert workspace path to Claude's project directory name
-    let sanitized = workspace_path.replace("/", "-");
-    let home = std::env::var("HOME").map_err(|e| e.to_string())?;
-    let sessions_path = format!("{}/.claude/projects/{}/sessions-index.json", home, sanitized);
+/// The cwd recorded in a session transcript (the first entry that carries one)
+async fn recorded_session_cwd(workspace_path: &str, session_id: &str) -> Result<Option<String>, String> {
+    if session_id.is_empty() || session_id.contains(['/', '\\']) || session_id.contains("..") {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    let path = match find_session_path(workspace_path, session_id)? {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+    let mut lines = BufReader::new(file).lines();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| format!("Failed to read session: {}", e))?
+    {
+        if !line.contains("\"cwd\"") {
+            continue;
+        }
+        if let Some(cwd) = serde_json::from_str::<Value>(&line)
+            .ok()
+            .and_then(|v| v.get("cwd").and_then(|c| c.as_str()).map(String::from))
+        {
+            return Ok(Some(cwd));
+        }
+    }
+    Ok(None)
+}
+
+/// Where a session's jsonl transcript lives under the workspace's project dir
+fn session_file_path(workspace_path: &str, session_id: &str) -> Result<PathBuf, String> {
+    Ok(project_dir_for_workspace(workspace_path)?.join(format!("{}.jsonl", session_id)))
+}
 
-    let path = Path::new(&sessions_path);
+/// Read a session's jsonl transcript, returning None when the file doesn't exist
+async fn read_session_file(workspace_path: &str, session_id: &str) -> Result<Option<String>, String> {
+    let path = session_file_path(workspace_path, session_id)?;
     if !path.exists() {
-        return Ok(vec![]);
+        return Ok(None);
     }
 
-    let content = tokio::fs::read_to_string(path)
+    // Interleaved writes can split a character; that line is lost, not the whole transcript
+    let bytes = tokio::fs::read(&path)
         .await
-        .map_err(|e| format!("Failed to read sessions: {}", e))?;
+        .map_err(|e| permissions::io_error("read session", &path, &e))?;
+    Ok(Some(match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    }))
+}
 
-    let index: SessionsIndex = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse sessions: {}", e))?;
+/// A session's grouped messages, from the cache while the transcript is unchanged since it was
+/// parsed. None when `token` stopped the parse; a missing transcript has no messages.
+async fn parsed_session(
+    app: &tauri::AppHandle,
+    workspace_path: &str,
+    session_id: &str,
+    token: cancel::CancellationToken,
+    include_source_spans: bool,
+) -> Result<Option<Arc<session_cache::ParsedSession>>, String> {
+    let state = app.state::<AppState>();
+    let capacity = session_cache::capacity(app, &state).await;
+    parsed_session_in(&state.session_cache, capacity, workspace_path, session_id, token, include_source_spans).await
+}
 
-    // Sort by modified date descending and take recent ones
-    let mut entries = index.entries;
-    entries.sort_by(|a, b| b.modified.cmp(&a.modified));
-    entries.truncate(50);
+/// `parsed_session` against `cache`, keeping what it parses within `capacity` bytes
+async fn parsed_session_in(
+    cache: &session_cache::SessionCache,
+    capacity: u64,
+    workspace_path: &str,
+    session_id: &str,
+    token: cancel::CancellationToken,
+    include_source_spans: bool,
+) -> Result<Option<Arc<session_cache::ParsedSession>>, String> {
+    let path = session_file_path(workspace_path, session_id)?;
+    // Stamped before reading, so a write during the read leaves a stamp the next load won't match
+    let stamp = tokio::fs::metadata(&path).await.ok().map(|m| (m.modified().ok(), m.len()));
+    if let Some((modified, len)) = stamp {
+        if let Some(session) = cache.get(&path, include_source_spans, modified, len) {
+            return Ok(Some(session));
+        }
+    }
 
-    Ok(entries)
+    let Some(content) = read_session_file(workspace_path, session_id).await? else {
+        return Ok(Some(Arc::default()));
+    };
+    let parsed = tokio::task::spawn_blocking(move || {
+        let messages = parse_session_messages_until(content.as_bytes(), &token, include_source_spans)?;
+        let health = session_guard::health(&content);
+        Ok::<_, String>(messages.map(|messages| session_cache::ParsedSession {
+            messages,
+            write_in_progress: health.write_in_progress,
+            malformed_lines: health.malformed_lines.len(),
+        }))
+    })
+    .await
+    .map_err(|e| format!("Failed to load session: {}", e))??;
+    let Some(parsed) = parsed else {
+        return Ok(None);
+    };
+
+    let session = Arc::new(parsed);
+    if let Some((modified, len)) = stamp {
+        cache.insert(&path, include_source_spans, modified, len, session.clone(), capacity);
+    }
+    Ok(Some(session))
 }
 
+/// Grouped messages of a session, tagged with the load that produced them
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct SessionMessage {
-    role: String,
-    content: String,
-    timestamp: String,
-    tools: Option<Vec<SessionToolExecution>>,
-    blocks: Option<Vec<SessionBlock>>,
+struct LoadedSessionMessages {
+    /// The caller's operation id, so a response from a superseded load can be dropped
+    operation_id: Option<String>,
+    messages: Vec<SessionMessage>,
+    /// The last line is still being written (by a running query or another Claude Code)
+    write_in_progress: bool,
+    /// Complete lines that don't parse and were skipped; repair_session_file fixes them
+    malformed_lines: usize,
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "type", rename_all = "camelCase")]
-enum SessionBlock {
-    Text { content: String, order: u64 },
-    Tool {
-        #[serde(rename = "toolId")]
-        tool_id: String,
-        order: u64
-    },
-    Image {
-        #[serde(rename = "mediaType")]
-        media_type: String,
-        data: String,
-        order: u64
-    },
+/// Load a session's grouped messages; tool outputs longer than `preview_bytes` are cut to a preview.
+/// With an `operation_id` the load can be stopped by `cancel_operation`, failing with `cancelled`.
+/// `include_source_spans` adds each message's transcript line and byte ranges (`sourceSpan`).
+#[tauri::command]
+async fn load_session_messages(
+    app: tauri::AppHandle,
+    workspace_path: String,
+    session_id: String,
+    preview_bytes: Option<usize>,
+    operation_id: Option<String>,
+    include_source_spans: Option<bool>,
+) -> Result<LoadedSessionMessages, cancel::ReadError> {
+    let registration = operation_id.as_deref().map(|id| cancel::register(&app, id));
+    if let Some(ref registration) = registration {
+        registration.check()?;
+    }
+    let token = registration.as_ref().map(|r| r.token().clone()).unwrap_or_default();
+    let parsed = parsed_session(&app, &workspace_path, &session_id, token, include_source_spans.unwrap_or(false)).await?;
+    let session = match (parsed, &registration) {
+        (Some(session), _) => session,
+        (None, Some(registration)) => {
+            return Err(cancel::ReadError::Cancelled {
+                operation_id: registration.operation_id().to_string(),
+            })
+        }
+        (None, None) => Arc::default(),
+    };
+    let mut messages = session.messages.clone();
+    truncate_tool_outputs(&mut messages, preview_bytes.unwrap_or(tool_output::DEFAULT_PREVIEW_BYTES));
+    Ok(LoadedSessionMessages {
+        operation_id,
+        messages,
+        write_in_progress: session.write_in_progress,
+        malformed_lines: session.malformed_lines,
+    })
 }
 
+/// One window of a session's grouped messages
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct SessionToolExecution {
-    id: String,
-    tool: String,
-    tool_use_id: Option<String>,
-    status: String,
-    input: Option<String>,
-    output: Option<String>,
-    started_at: String,
-    completed_at: Option<String>,
+struct SessionMessagesPage {
+    messages: Vec<SessionMessage>,
+    offset: usize,
+    total: usize,
+    /// Bookmarks whose resolved index falls inside this window
+    bookmarks: Vec<bookmarks::BookmarkView>,
+    /// See `LoadedSessionMessages`
+    write_in_progress: bool,
+    malformed_lines: usize,
 }
 
+/// Load `limit` grouped messages starting at `offset`, with the bookmarks that land in them.
+/// Source spans (`include_source_spans`) are offsets into the whole transcript, not the page.
+/// Served from the cached parse of the whole session when there is one.
 #[tauri::command]
-async fn load_session_messages(
+async fn load_session_messages_page(
+    app: tauri::AppHandle,
     workspace_path: String,
     session_id: String,
-) -> Result<Vec<SessionMessage>, String> {
-    // Convert workspace path to Claude's project directory name
-    let sanitized = workspace_path.replace("/", "-");
-    let home = std::env::var("HOME").map_err(|e| e.to_string())?;
-    let session_path = format!("{}/.claude/projects/{}/{}.jsonl", home, sanitized, session_id);
+    offset: usize,
+    limit: usize,
+    preview_bytes: Option<usize>,
+    include_source_spans: Option<bool>,
+) -> Result<SessionMessagesPage, permissions::SessionError> {
+    let token = cancel::CancellationToken::default();
+    let session = parsed_session(&app, &workspace_path, &session_id, token, include_source_spans.unwrap_or(false))
+        .await?
+        .unwrap_or_default();
+    let total = session.messages.len();
+    let end = offset.saturating_add(limit).min(total);
+
+    let bookmarks = match bookmarks::session_project_dir(Some(&workspace_path), &session_id) {
+        Ok(project_dir) => bookmarks::session_bookmarks(&app, &project_dir, &session_id, &session.messages)
+            .await?
+            .into_iter()
+            .filter(|b| b.resolved_index.is_some_and(|i| i >= offset && i < end))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
 
-    let path = Path::new(&session_path);
-    if !path.exists() {
-        return Ok(vec![]);
+    let mut messages: Vec<SessionMessage> = session.messages.iter().skip(offset).take(limit).cloned().collect();
+    truncate_tool_outputs(&mut messages, preview_bytes.unwrap_or(tool_output::DEFAULT_PREVIEW_BYTES));
+    Ok(SessionMessagesPage {
+        messages,
+        offset,
+        total,
+        bookmarks,
+        write_in_progress: session.write_in_progress,
+        malformed_lines: session.malformed_lines,
+    })
+}
+
+/// Export a session transcript as Markdown or self-contained HTML, optionally writing it to disk
+#[tauri::command]
+async fn export_session(
+    workspace_path: String,
+    session_id: String,
+    format: String,
+    destination: Option<String>,
+) -> Result<export::SessionExport, permissions::SessionError> {
+    let content = read_session_file(&workspace_path, &session_id)
+        .await?
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    let messages = parse_session_messages(&content)?;
+
+    let title = format!("Session {}", session_id);
+    let exported = export::export_messages(&messages, &title, &format)?;
+
+    if let Some(destination) = destination {
+        tokio::fs::write(&destination, &exported.content)
+            .await
+            .map_err(|e| format!("Failed to write export: {}", e))?;
     }
 
-    let content = tokio::fs::read_to_string(path)
-        .await
-        .map_err(|e| format!("Failed to read session: {}", e))?;
+    Ok(exported)
+}
+
+/// What a caller (the UI or the command line) asks `start_query` to run
+#[derive(Default)]
+struct QueryInput {
+    prompt: String,
+    working_dir: String,
+    config: Option<String>,
+    resume_session: Option<String>,
+    has_attachments: Option<bool>,
+    /// Ids returned by `ingest_clipboard_image`
+    attachment_ids: Option<Vec<String>>,
+    tool_result: Option<String>,
+    options: Option<QueryOptions>,
+    preset: Option<String>,
+    template: Option<templates::TemplateRef>,
+    sensitive: Option<bool>,
+    /// Label of the window the query's events go to; None holds them until a window subscribes
+    owner: Option<String>,
+    /// Set by `approve_plan_and_continue`
+    plan_approval: Option<history::PlanApproval>,
+    /// "quick" for a quick question (see `quick_question`); None for a normal query
+    mode: Option<String>,
+}
 
-    let mut messages: Vec<SessionMessage> = Vec::new();
-    let mut tool_index: HashMap<String, (usize, usize)> = HashMap::new();
-    let mut anonymous_tool_counter: u32 = 0;
-    let mut global_block_order: u64 = 0;
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn query_claude(
+    app: tauri::AppHandle,
+    window: tauri::Window,
+    prompt: String,
+    working_dir: String,
+    config: Option<String>,
+    resume_session: Option<String>,
+    has_attachments: Option<bool>,
+    attachment_ids: Option<Vec<String>>,
+    tool_result: Option<String>,
+    options: Option<QueryOptions>,
+    preset: Option<String>,
+    template: Option<templates::TemplateRef>,
+    sensitive: Option<bool>,
+    mode: Option<String>,
+) -> Result<String, QueryError> {
+    let input = QueryInput {
+        prompt,
+        working_dir,
+        config,
+        resume_session,
+        has_attachments,
+        attachment_ids,
+        tool_result,
+        options,
+        preset,
+        template,
+        sensitive,
+        owner: Some(window.label().to_string()),
+        plan_approval: None,
+        mode,
+    };
+    start_query(app, Uuid::new_v4().to_string(), input).await
+}
 
-    for line in content.lines() {
-        if line.is_empty() {
-            continue;
+/// Expand, check and run one query under `query_id`, then hand over to any queued follow-up.
+/// Returns once the run has finished.
+async fn start_query(app: tauri::AppHandle, query_id: String, mut input: QueryInput) -> Result<String, QueryError> {
+    // Events go to the window that started the query; one started elsewhere waits for a subscriber
+    if let Some(owner) = input.owner.take() {
+        replay::subscribe(&app, &query_id, &owner);
+    }
+    let request = match prepare_query(&app, &query_id, input).await {
+        Ok(request) => request,
+        Err(e) => {
+            replay::finish(&app, &query_id).await;
+            return Err(e);
         }
+    };
+    let active_queries = app.state::<AppState>().active_queries.clone();
 
-        let parsed: Value = match serde_json::from_str(line) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-
-        let msg_type = parsed.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let result = run_query(&app, &active_queries, &query_id, request).await;
+    quick_question::finish(&app, &query_id).await;
+    replay::finish(&app, &query_id).await;
+    if let Some(followup) = result? {
+        spawn_followups(app, active_queries, query_id.clone(), followup);
+    }
 
-        // Only process user/assistant messages
-        if msg_type != "user" && msg_type != "assistant" {
-            continue;
-        }
+    Ok(query_id)
+}
 
-        let timestamp = parsed.get("timestamp")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
+/// Expand the template, check the session's workspace and resolve the config of a query about to run
+async fn prepare_query(app: &tauri::AppHandle, query_id: &str, input: QueryInput) -> Result<QueryRequest, QueryError> {
+    let QueryInput {
+        prompt,
+        working_dir,
+        config,
+        resume_session,
+        has_attachments,
+        attachment_ids,
+        tool_result,
+        options,
+        preset,
+        template,
+        sensitive,
+        owner: _,
+        plan_approval,
+        mode,
+    } = input;
+    let options = options.unwrap_or_default();
+
+    // A quick question starts nothing that outlives it, so it can't continue a session either
+    let quick = quick_question::is_quick(mode.as_deref())?;
+    if quick && resume_session.is_some() {
+        return Err("A quick question can't resume a session".to_string().into());
+    }
+    if quick && tool_result.is_some() {
+        return Err("A quick question can't answer a tool call".to_string().into());
+    }
 
-        let message = match parsed.get("message") {
-            Some(m) => m,
-            None => continue,
-        };
+    // An old node fails deep inside the SDK with syntax errors; say so before spawning it
+    let node = runtime::check_node(app, None).await;
+    match node.status {
+        runtime::NodeStatus::TooOld => {
+            return Err(QueryError::NodeTooOld {
+                found: node.version.unwrap_or_default(),
+                required: node.required,
+                path: node.path,
+                alternative: node.alternative,
+            });
+        }
+        runtime::NodeStatus::Unrecognized => {
+            replay::emit(app, query_id, "claude-stderr", stderr::StderrEvent {
+                query_id: query_id.to_string(),
+                data: format!(
+                    "Could not tell whether node {} at {} meets the required {}; running anyway",
+                    node.version.unwrap_or_default(),
+                    node.path,
+                    node.required
+                ),
+                severity: stderr::Severity::Warning,
+            })
+            .await;
+        }
+        runtime::NodeStatus::Ok | runtime::NodeStatus::Missing => {}
+    }
 
-        let role = message.get("role")
-            .and_then(|v| v.as_str())
-            .unwrap_or(msg_type)
-            .to_string();
-
-        let content_value = message.get("content");
-        let mut content_texts: Vec<String> = Vec::new();
-        let mut tools: Vec<SessionToolExecution> = Vec::new();
-        let mut blocks: Vec<SessionBlock> = Vec::new();
-        let mut tool_id_mappings: Vec<(String, usize)> = Vec::new();
-
-        match content_value {
-            Some(Value::String(s)) => {
-                if !s.trim().is_empty() {
-                    content_texts.push(s.clone());
-                    global_block_order += 1;
-                    blocks.push(SessionBlock::Text { content: s.clone(), order: global_block_order });
-                }
-            }
-            Some(Value::Array(arr)) => {
-                for block in arr {
-                    let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                    match block_type {
-                        "text" => {
-                            if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
-                                if !text.trim().is_empty() {
-                                    content_texts.push(text.to_string());
-                                    global_block_order += 1;
-                                    blocks.push(SessionBlock::Text { content: text.to_string(), order: global_block_order });
-                                }
-                            }
-                        }
-                        "image" => {
-                            // Handle image blocks with base64 data
-                            if let Some(source) = block.get("source") {
-                                let media_type = source.get("media_type")
-                                    .and_then(|v| v.as_str())
-                                    .unwrap_or("image/png")
-                                    .to_string();
-                                if let Some(data) = source.get("data").and_then(|v| v.as_str()) {
-                                    global_block_order += 1;
-                                    blocks.push(SessionBlock::Image {
-                                        media_type,
-                                        data: data.to_string(),
-                                        order: global_block_order
-                                    });
-                                }
-                            }
-                        }
-                        "tool_use" if msg_type == "assistant" => {
-                            let name = block.get("name")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("unknown")
-                                .to_string();
-                            let tool_use_id = block.get("id").and_then(|v| v.as_str()).map(|s| s.to_string());
-                            let input_value = block.get("input");
-                            let input = match input_value {
-                                Some(Value::String(s)) => Some(s.clone()),
-                                Some(v) => serde_json::to_string_pretty(v).ok(),
-                                None => None,
-                            };
-
-                            let tool_id = tool_use_id.clone().unwrap_or_else(|| {
-                                anonymous_tool_counter += 1;
-                                format!("tool-{}", anonymous_tool_counter)
-                            });
-
-                            let tool_entry = SessionToolExecution {
-                                id: tool_id.clone(),
-                                tool: name,
-                                tool_use_id: tool_use_id.clone(),
-                                status: "running".to_string(),
-                                input,
-                                output: None,
-                                started_at: timestamp.clone(),
-                                completed_at: None,
-                            };
-
-                            tools.push(tool_entry);
-                            global_block_order += 1;
-                            blocks.push(SessionBlock::Tool { tool_id: tool_id.clone(), order: global_block_order });
-                            if let Some(id) = tool_use_id {
-                                tool_id_mappings.push((id, tools.len() - 1));
-                            }
-                        }
-                        "tool_result" if msg_type == "user" => {
-                            if let Some(tool_use_id) = block.get("tool_use_id").and_then(|v| v.as_str()) {
-                                if let Some((msg_idx, tool_idx)) = tool_index.get(tool_use_id).cloned() {
-                                    if let Some(message) = messages.get_mut(msg_idx) {
-                                        if let Some(message_tools) = message.tools.as_mut() {
-                                            if let Some(tool) = message_tools.get_mut(tool_idx) {
-                                                let output_value = block.get("content");
-                                                let output = match output_value {
-                                                    Some(Value::String(s)) => Some(s.clone()),
-                                                    Some(Value::Array(arr)) => {
-                                                        let texts: Vec<String> = arr.iter()
-                                                            .filter_map(|b| {
-                                                                let t = b.get("type").and_then(|v| v.as_str());
-                                                                if t == Some("text") {
-                                                                    b.get("text").and_then(|v| v.as_str()).map(|s| s.to_string())
-                                                                } else {
-                                                                    None
-                                                                }
-                                                            })
-                                                            .collect();
-                                                        if texts.is_empty() {
-                                                            serde_json::to_string_pretty(output_value.unwrap()).ok()
-                                                        } else {
-                                                            Some(texts.join("\n"))
-                                                        }
-                                                    }
-                                                    Some(v) => serde_json::to_string_pretty(v).ok(),
-                                                    None => None,
-                                                };
-
-                                                let is_error = block.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false);
-                                                tool.status = if is_error { "error".to_string() } else { "completed".to_string() };
-                                                tool.output = output;
-                                                tool.completed_at = Some(timestamp.clone());
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+    // A run that fills the disk can corrupt the session on its last write; don't start one that would
+    if !options.skip_disk_check {
+        match disk::check(app, &working_dir).await {
+            Ok(check) if check.level == disk::DiskLevel::Critical => {
+                return Err(QueryError::LowDiskSpace {
+                    path: check.volume.path,
+                    free_bytes: check.volume.free_bytes,
+                    floor_bytes: check.floor_bytes,
+                });
             }
-            _ => {}
+            Ok(check) if check.level == disk::DiskLevel::Low => disk::emit_warning(app, query_id, &check, false).await,
+            Ok(_) => {}
+            Err(e) => eprintln!("[mensa] Disk space check skipped: {}", e),
         }
+    }
 
-        let content_text = content_texts.join("\n");
-        let has_tools = !tools.is_empty();
-        let has_blocks = !blocks.is_empty();
-
-        // Skip empty messages (no text, no tools, no blocks like images)
-        if content_text.trim().is_empty() && !has_tools && !has_blocks {
-            continue;
+    // A template stands in for the raw prompt; never send one with placeholders left over
+    let prompt = match template {
+        Some(template) => {
+            let expanded = templates::expand(app, &working_dir, &template.name, template.vars).await?;
+            if !expanded.unresolved.is_empty() {
+                return Err(format!("Unresolved template placeholders: {}", expanded.unresolved.join(", ")).into());
+            }
+            expanded.prompt
         }
+        None => prompt,
+    };
 
-        // Group consecutive assistant messages
-        if role == "assistant" && !messages.is_empty() {
-            let last_idx = messages.len() - 1;
-            if messages[last_idx].role == "assistant" {
-                let last = messages
-                    .get_mut(last_idx)
-                    .ok_or_else(|| "Failed to read previous assistant message".to_string())?;
-
-                if !content_text.trim().is_empty() {
-                    if !last.content.is_empty() {
-                        last.content.push('\n');
-                    }
-                    last.content.push_str(&content_text);
-                }
-
-                if has_tools {
-                    let existing_len = last.tools.as_ref().map(|t| t.len()).unwrap_or(0);
-                    if last.tools.is_none() {
-                        last.tools = Some(Vec::new());
-                    }
-                    if let Some(last_tools) = last.tools.as_mut() {
-                        last_tools.extend(tools);
-                    }
-                    for (id, idx) in tool_id_mappings {
-                        tool_index.insert(id, (last_idx, existing_len + idx));
-                    }
-                }
-
-                if has_blocks {
-                    if last.blocks.is_none() {
-                        last.blocks = Some(Vec::new());
-                    }
-                    if let Some(last_blocks) = last.blocks.as_mut() {
-                        last_blocks.extend(blocks);
-                    }
+    // A resumed transcript holds absolute paths; refuse to run it somewhere else unless asked to
+    let mut remapped_from = None;
+    if let Some(ref session_id) = resume_session {
+        if let Some(recorded_cwd) = recorded_session_cwd(&working_dir, session_id).await? {
+            if !fsutil::same_path_nfc(&canonical_or_raw(&recorded_cwd), &canonical_or_raw(&working_dir)) {
+                if !options.allow_remap {
+                    return Err(QueryError::SessionWorkspaceMismatch {
+                        recorded_cwd,
+                        working_dir,
+                    });
                 }
-
-                last.timestamp = timestamp;
-                continue;
+                replay::emit(app, query_id, "session-workspace-remapped", serde_json::json!({
+                    "query_id": query_id,
+                    "session_id": session_id,
+                    "recorded_cwd": recorded_cwd,
+                    "working_dir": working_dir
+                }))
+                .await;
+                remapped_from = Some(recorded_cwd);
             }
         }
+        // Two writers appending to one transcript can interleave their lines
+        if !options.force_resume {
+            if let Some(by) = session_guard::holder(app, &working_dir, session_id).await? {
+                return Err(QueryError::SessionInUse {
+                    session_id: session_id.clone(),
+                    by,
+                    hint: session_guard::SESSION_IN_USE_HINT.to_string(),
+                });
+            }
+        }
+    }
 
-        // Group consecutive user messages
-        if role == "user" && !messages.is_empty() {
-            let last_idx = messages.len() - 1;
-            if messages[last_idx].role == "user" {
-                let last = messages
-                    .get_mut(last_idx)
-                    .ok_or_else(|| "Failed to read previous user message".to_string())?;
-
-                if !content_text.trim().is_empty() {
-                    if !last.content.is_empty() {
-                        last.content.push('\n');
-                    }
-                    last.content.push_str(&content_text);
-                }
-
-                if has_blocks {
-                    if last.blocks.is_none() {
-                        last.blocks = Some(Vec::new());
-                    }
-                    if let Some(last_blocks) = last.blocks.as_mut() {
-                        last_blocks.extend(blocks);
-                    }
-                }
-
-                last.timestamp = timestamp;
-                continue;
+    // Missing files are reported before the agent spends a turn finding out
+    if tool_result.is_none() && (options.validate_references || options.strict_references) {
+        let text = prompt_history::prompt_text(&prompt, has_attachments == Some(true));
+        let report = references::validate(app, &working_dir, &text).await?;
+        let missing: Vec<references::ReferenceCheck> =
+            report.references.into_iter().filter(|r| !r.exists).collect();
+        if !missing.is_empty() {
+            if options.strict_references {
+                return Err(QueryError::UnknownReferences { references: missing });
             }
+            replay::emit(app, query_id, "prompt-reference-warnings", serde_json::json!({
+                "query_id": query_id,
+                "references": missing
+            }))
+            .await;
         }
+    }
 
-        let msg_idx = messages.len();
-        messages.push(SessionMessage {
-            role,
-            content: content_text,
-            timestamp,
-            tools: if has_tools { Some(tools) } else { None },
-            blocks: if blocks.is_empty() { None } else { Some(blocks) },
-        });
-        for (id, idx) in tool_id_mappings {
-            tool_index.insert(id, (msg_idx, idx));
+    let config = presets::resolve_query_config(app, &working_dir, preset.as_deref(), config).await?;
+    let config = match quick {
+        true => Some(quick_question::quick_config(config.as_deref())?),
+        false => config,
+    };
+    models::reload_if_changed(app).await;
+    if let Some(warning) = models::unknown_model_warning(config.as_deref()) {
+        replay::emit(app, query_id, "claude-stderr", stderr::StderrEvent {
+            query_id: query_id.to_string(),
+            data: warning,
+            severity: stderr::Severity::Warning,
+        })
+        .await;
+    }
+
+    // Tool results aren't prompts the user typed, and the caller can keep secrets out of the history
+    if tool_result.is_none() && sensitive != Some(true) {
+        let text = prompt_history::prompt_text(&prompt, has_attachments == Some(true));
+        if let Err(e) = prompt_history::record(app, &working_dir, &text, query_id).await {
+            eprintln!("[mensa] {}", e);
         }
     }
 
-    Ok(messages)
+    // Staged images are consumed only once the query is certain to be sent
+    let staged_images = match attachment_ids {
+        Some(ids) if !ids.is_empty() => attachments::take_staged(app, &ids).await?,
+        _ => Vec::new(),
+    };
+
+    Ok(QueryRequest {
+        prompt,
+        working_dir,
+        config,
+        resume_session,
+        has_attachments,
+        staged_images,
+        tool_result,
+        options,
+        preset,
+        remapped_from,
+        plan_approval,
+        quick,
+    })
 }
 
-#[tauri::command]
-async fn query_claude(
+/// Run queued follow-ups one after another in the background, each resuming its predecessor's session
+fn spawn_followups(
     app: tauri::AppHandle,
-    state: State<'_, AppState>,
-    prompt: String,
-    working_dir: String,
-    config: Option<String>,
-    resume_session: Option<String>,
-    has_attachments: Option<bool>,
-    tool_result: Option<String>,
-) -> Result<String, String> {
-    // Generate unique query ID
-    let query_id = Uuid::new_v4().to_string();
+    active_queries: Arc<Mutex<HashMap<String, ActiveQuery>>>,
+    predecessor: String,
+    first: (String, QueryRequest),
+) {
+    let tasks = app.state::<AppState>().tasks.clone();
+    tasks.spawn(format!("follow-ups of query {}", predecessor), |token| async move {
+        let mut predecessor = predecessor;
+        let mut next = Some(first);
+
+        while let Some((query_id, request)) = next.take() {
+            if token.is_cancelled() {
+                break;
+            }
+            replay::inherit_subscribers(&app, &predecessor, &query_id);
+            replay::emit(&app, &query_id, "claude-followup-started", serde_json::json!({
+                "query_id": query_id,
+                "predecessor": predecessor
+            }))
+            .await;
+
+            next = match run_query(&app, &active_queries, &query_id, request).await {
+                Ok(followup) => followup,
+                Err(e) => {
+                    replay::emit(&app, &query_id, "claude-stderr", stderr::StderrEvent {
+                        query_id: query_id.clone(),
+                        data: e,
+                        severity: stderr::Severity::Error,
+                    })
+                    .await;
+                    replay::emit(&app, &query_id, "claude-done", serde_json::json!({
+                        "query_id": query_id,
+                        "code": -1
+                    }))
+                    .await;
+                    None
+                }
+            };
+            replay::finish(&app, &query_id).await;
+            predecessor = query_id;
+        }
+    });
+}
+
+/// Spawn the node query script and stream its output until it exits.
+/// Returns the follow-up to start next, if one was queued and this run succeeded.
+async fn run_query(
+    app: &tauri::AppHandle,
+    active_queries: &Arc<Mutex<HashMap<String, ActiveQuery>>>,
+    query_id: &str,
+    request: QueryRequest,
+) -> Result<Option<(String, QueryRequest)>, String> {
+    let query_id = query_id.to_string();
+    let QueryRequest {
+        mut prompt,
+        working_dir,
+        config,
+        resume_session,
+        mut has_attachments,
+        staged_images,
+        tool_result,
+        options,
+        preset,
+        remapped_from,
+        plan_approval,
+        quick,
+    } = request;
 
     // Validate working directory exists
     let path = Path::new(&working_dir);
@@ -506,36 +1107,62 @@ async fn query_claude(
         return Err(format!("Path is not a directory: {}", working_dir));
     }
 
-    // Use Node.js script with Claude Agent SDK
-    // Try multiple locations for the script
-    let mut possible_paths: Vec<PathBuf> = vec![];
+    let tool_output_preview = options.tool_output_preview.unwrap_or(tool_output::DEFAULT_PREVIEW_BYTES);
 
-    // 1. Tauri resource directory (for bundled app)
-    if let Ok(resource_dir) = app.path().resource_dir() {
-        // Tauri v2 puts "../scripts" into "_up_/scripts" to preserve relative paths
-        possible_paths.push(resource_dir.join("_up_/scripts/claude-query.mjs"));
-        possible_paths.push(resource_dir.join("scripts/claude-query.mjs"));
-    }
+    // Whatever the caller asked for, a read-only machine only plans
+    let read_only = read_only::enabled(app);
+    let config = if read_only {
+        Some(read_only::constrain_config(config.as_deref())?)
+    } else {
+        config
+    };
 
-    // 2. Relative to executable (for development/bundled)
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(parent) = exe_path.parent() {
-            // macOS .app bundle structure: Contents/MacOS/binary -> Contents/Resources
-            // Tauri v2 puts "../scripts" into "_up_/scripts"
-            possible_paths.push(parent.join("../Resources/_up_/scripts/claude-query.mjs"));
-            possible_paths.push(parent.join("../Resources/scripts/claude-query.mjs"));
+    let max_cost_usd = options
+        .max_cost_usd
+        .or_else(|| config.as_deref().and_then(config_max_cost));
+    if let Some(limit) = max_cost_usd {
+        if !limit.is_finite() || limit <= 0.0 {
+            return Err(format!("Cost limit must be a positive amount, got {}", limit));
         }
     }
+    let warning_fraction = options.cost_warning_fraction.unwrap_or(cost::DEFAULT_WARNING_FRACTION);
+    if !(warning_fraction > 0.0 && warning_fraction <= 1.0) {
+        return Err(format!("Cost warning fraction must be between 0 and 1, got {}", warning_fraction));
+    }
+    let max_image_edge = attachments::max_image_edge(options.max_image_edge)?;
+
+    // Collect secret values up front so streamed tool output can be masked
+    let redactor = if options.redact_sensitive {
+        let patterns = sensitive::load_patterns(app).await?;
+        let matcher = sensitive::build_matcher(&patterns)?;
+        let files = sensitive::find_sensitive_files(path, &matcher);
+        sensitive::Redactor::from_files(path, &files)
+    } else {
+        None
+    };
 
-    // 3. Current working directory (for development)
-    if let Ok(cwd) = std::env::current_dir() {
-        possible_paths.push(cwd.join("scripts/claude-query.mjs"));
+    // Downscale and re-encode images so oversized photos and EXIF/GPS data never leave the machine
+    if has_attachments == Some(true) {
+        let (processed_prompt, processed) =
+            tokio::task::spawn_blocking(move || attachments::preprocess_prompt(&prompt, max_image_edge))
+                .await
+                .map_err(|e| format!("Attachment processing failed: {}", e))??;
+        prompt = processed_prompt;
+        if !processed.is_empty() {
+            replay::emit(app, &query_id, "attachments-processed", serde_json::json!({
+                "query_id": query_id,
+                "attachments": processed
+            }))
+            .await;
+        }
+    }
+    if !staged_images.is_empty() {
+        prompt = attachments::append_image_blocks(&prompt, has_attachments == Some(true), staged_images)?;
+        has_attachments = Some(true);
     }
 
-    let script = possible_paths
-        .into_iter()
-        .find(|p| p.exists())
-        .ok_or_else(|| "Could not find claude-query.mjs script. Please ensure the app is installed correctly.".to_string())?;
+    // Use Node.js script with Claude Agent SDK
+    let script = script::resolve_script(app).await?.path;
 
     let mut args = vec![
         script.to_string_lossy().to_string(),
@@ -552,6 +1179,32 @@ async fn query_claude(
         args.push(config_json);
     }
 
+    // An image-heavy session resumes from a trimmed companion copy; the original stays as it was
+    let mut trimmed_resume = None;
+    let resume_session = match resume_session {
+        Some(session_id) if options.trim_images_on_resume => {
+            match context::prepare_trimmed_resume(app, &working_dir, &session_id).await? {
+                Some(trimmed) => {
+                    replay::emit(app, &query_id, "session-trimmed", serde_json::json!({
+                        "query_id": query_id,
+                        "trimmed": trimmed
+                    }))
+                    .await;
+                    let trimmed_id = trimmed.trimmed_session_id.clone();
+                    trimmed_resume = Some(trimmed);
+                    Some(trimmed_id)
+                }
+                None => Some(session_id),
+            }
+        }
+        other => other,
+    };
+
+    // Tell other mensa processes which session this query writes to (again once the agent reports it)
+    let mut session_hold = resume_session
+        .as_deref()
+        .and_then(|session_id| session_guard::hold(app, session_id, &query_id, &working_dir));
+
     if let Some(session_id) = resume_session {
         args.push("--resume".to_string());
         args.push(session_id);
@@ -566,20 +1219,44 @@ async fn query_claude(
         args.push(tr);
     }
 
-    let node_binary = find_node_binary();
-    let mut child = Command::new(&node_binary)
-        .args(&args)
-        .current_dir(&working_dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Failed to spawn node at '{}': {}. Make sure Node.js is installed.", node_binary, e))?;
+    // Secrets are read from the keychain only now, right before they're needed
+    let mut secret_env: Vec<(String, String)> = Vec::new();
+    for name in &options.secret_env {
+        let valid_name = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(format!("Secret '{}' is not a valid environment variable name", name));
+        }
+        secret_env.push((name.clone(), secrets::read_secret(app, name).await?));
+    }
+
+    // Read before the agent starts, so settings that fail to load fail the query instead of
+    // leaving a launched agent with nobody reading its output
+    let classifier = stderr::Classifier::new(&settings::load(app, &app.state::<AppState>().settings).await?.stderr_rules);
+
+    let node_binary = settings::node_binary(app).await;
+    let state = app.state::<AppState>();
+    let mut envs = state.proxy.vars();
+    envs.extend(secret_env);
+    let spec = launcher::LaunchSpec {
+        program: node_binary,
+        args,
+        envs,
+        working_dir: working_dir.clone(),
+    };
+    let mut child = state
+        .launcher
+        .launch(&spec)
+        .map_err(|e| format!("Failed to spawn node at '{}': {}. Make sure Node.js is installed.", spec.program, e))?;
+
+    if quick {
+        quick_question::begin(app, &query_id, &working_dir);
+    }
 
     // Store the child process for potential cancellation
     let query_id_for_storage = query_id.clone();
-    let active_queries = state.active_queries.clone();
 
-    // Read stderr in background for error messages
     let stderr = child.stderr.take();
     let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
 
@@ -588,132 +1265,598 @@ async fn query_claude(
         let mut queries = active_queries.lock().await;
         queries.insert(query_id_for_storage.clone(), ActiveQuery {
             child,
+            phase: QueryPhase::Running,
             started_at: std::time::Instant::now(),
+            working_dir: working_dir.clone(),
+            changed_files: HashSet::new(),
+            followup: None,
+            tool_outputs: HashMap::new(),
+            context_usage: None,
         });
     }
 
-    let app_clone = app.clone();
-    let query_id_for_stderr = query_id.clone();
-    if let Some(stderr) = stderr {
-        tokio::spawn(async move {
-            let mut reader = BufReader::new(stderr).lines();
-            while let Ok(Some(line)) = reader.next_line().await {
-                if !line.is_empty() {
-                    let payload = StreamPayload {
-                        query_id: query_id_for_stderr.clone(),
-                        data: line,
-                    };
-                    let _ = app_clone.emit("claude-stderr", payload);
-                }
-            }
-        });
+    if let Some(ref redactor) = redactor {
+        replay::emit(app, &query_id, "claude-redaction-active", serde_json::json!({
+            "query_id": query_id,
+            "files": redactor.files,
+            "notice": "Tool output is redacted in mensa's stream only; the session file written by Claude Code still contains the original values."
+        }))
+        .await;
     }
 
+    // Read stderr in the background, classified by the built-in and configured rules
+    let mut stderr_task = match stderr {
+        Some(stderr) => {
+            let tasks = &app.state::<AppState>().tasks;
+            let (app, query_id) = (app.clone(), query_id.clone());
+            let name = format!("query {} stderr", query_id);
+            Some(tasks.spawn(name, |token| stderr::pump(app, query_id, stderr, classifier, token)))
+        }
+        None => None,
+    };
+
+    // Free space can run out mid-run (cloned datasets, build output)
+    let disk_watch = (!options.skip_disk_check).then(|| {
+        let tasks = &app.state::<AppState>().tasks;
+        let (app, query_id, working_dir) = (app.clone(), query_id.clone(), working_dir.clone());
+        let name = format!("query {} disk watch", query_id);
+        tasks.spawn(name, |token| disk::watch(app, query_id, working_dir, token)).token
+    });
+
     let mut reader = BufReader::new(stdout).lines();
     let query_id_for_stream = query_id.clone();
+    let mut history_base = history::QueryRecord {
+        query_id: query_id.clone(),
+        working_dir: working_dir.clone(),
+        preset,
+        remapped_from,
+        started_at: history::now_secs(),
+        finished_at: 0,
+        exit_code: None,
+        cancelled: false,
+        changed_files: Vec::new(),
+        hooks: None,
+        cost_usd: None,
+        terminal_reason: None,
+        trimmed_resume,
+        group_id: query_group::group_of(app, &query_id),
+        stderr_suppressed: None,
+        session_id: None,
+        model: None,
+        plan_approval,
+        read_only: read_only.then_some(true),
+        ephemeral: quick.then_some(true),
+    };
+    let mut changed_files: HashSet<PathBuf> = HashSet::new();
+    let mut files_flush_at: Option<tokio::time::Instant> = None;
+    let mut session_id: Option<String> = None;
+    let mut result_failed = false;
+    let mut costs = cost::CostTracker::default();
+    let mut cost_warned = false;
+    let mut cost_limit_hit = false;
+    let mut quick_deadline = quick.then(|| tokio::time::Instant::now() + quick_question::QUICK_TIMEOUT);
+    // Why this run stopped its own agent (out of time, over budget); None for a user's cancel
+    let mut stop_reason: Option<&'static str> = None;
+    let mut read_error: Option<String> = None;
+    let mut context = {
+        let settings = settings::load(app, &app.state::<AppState>().settings).await.unwrap_or_default();
+        context_usage::ContextTracker::new(&query_id, settings.context_limits.clone(), &settings.context_warning_percents)
+    };
 
-    while let Some(line) = reader.next_line().await.map_err(|e| e.to_string())? {
-        if !line.is_empty() {
-            let payload = StreamPayload {
-                query_id: query_id_for_stream.clone(),
-                data: line,
-            };
-            app.emit("claude-stream", payload).map_err(|e| e.to_string())?;
+    loop {
+        let line = tokio::select! {
+            // Output that can't be read ends the run as failed, through the same steps as any end
+            line = reader.next_line() => line.unwrap_or_else(|e| {
+                read_error = Some(format!("Failed to read the agent's output: {}", e));
+                None
+            }),
+            _ = sleep_until_deadline(files_flush_at) => {
+                files_flush_at = None;
+                emit_changed_files(app, &query_id, &changed_files).await;
+                continue;
+            }
+            // A quick question out of time is cancelled; stdout closes once the agent is stopped
+            _ = sleep_until_deadline(quick_deadline) => {
+                quick_deadline = None;
+                if query_cancel::cancel(app, &query_id, false).await.action == query_cancel::CancelAction::Stopped {
+                    stop_reason = Some(quick_question::TIMED_OUT);
+                }
+                continue;
+            }
+        };
+        let line = match line {
+            Some(line) => line,
+            None => break,
+        };
+        if line.is_empty() {
+            continue;
+        }
+
+        // Track files touched by tool calls so the UI can show them live
+        if let Some(message) = stream::parse_line(&line) {
+            if let Some(id) = message.session_id() {
+                if session_hold.as_ref().map(|hold| hold.session_id()) != Some(id) {
+                    session_hold = session_guard::hold(app, id, &query_id, &working_dir);
+                }
+                if quick && session_id.as_deref() != Some(id) {
+                    quick_question::record_session(app, &query_id, id);
+                }
+                session_id = Some(id.to_string());
+            }
+            result_failed |= message.is_error_result();
+
+            if let Some(total) = message.result_cost() {
+                costs.set_reported(total);
+            }
+            // Only assistant turns can push the run over; a result message means it already ended
+            if let Some(body) = message.assistant_usage() {
+                if body.model.is_some() {
+                    history_base.model = body.model.clone();
+                }
+                costs.record(body);
+                let update = context.record(body);
+                if let Some(active) = active_queries.lock().await.get_mut(&query_id) {
+                    active.context_usage = Some(context.latest().clone());
+                }
+                if let Some(usage) = update.usage {
+                    replay::emit(app, &query_id, "claude-context-usage", usage).await;
+                }
+                for warning in update.warnings {
+                    replay::emit(app, &query_id, "claude-context-warning", warning).await;
+                }
+                if let Some(limit) = max_cost_usd {
+                    let spent = costs.total();
+                    let cost_payload = serde_json::json!({
+                        "query_id": query_id,
+                        "cost_usd": spent,
+                        "max_cost_usd": limit
+                    });
+                    if !cost_warned && spent >= limit * warning_fraction {
+                        cost_warned = true;
+                        replay::emit(app, &query_id, "claude-cost-warning", &cost_payload).await;
+                    }
+                    // Stopped like a cancel, so it stays listed (and cancellable) until the run ends
+                    if !cost_limit_hit && spent >= limit {
+                        cost_limit_hit = true;
+                        if query_cancel::stop_over_budget(app, &query_id).await.action == query_cancel::CancelAction::Stopped {
+                            stop_reason = Some(query_cancel::COST_LIMIT_EXCEEDED);
+                        }
+                        replay::emit(app, &query_id, "claude-cost-limit", &cost_payload).await;
+                    }
+                }
+            }
+
+            for hook in message.hook_events() {
+                if hook.decision != "started" {
+                    let summary = history_base.hooks.get_or_insert_with(Default::default);
+                    summary.run += 1;
+                    if hook.decision == "blocked" {
+                        summary.blocked += 1;
+                    }
+                }
+                let mut payload = serde_json::to_value(&hook).unwrap_or_default();
+                payload["query_id"] = Value::String(query_id.clone());
+                replay::emit(app, &query_id, "claude-hook-event", payload).await;
+            }
+
+            let touched: Vec<PathBuf> = message
+                .tool_uses()
+                .flat_map(|(tool, input)| stream::touched_paths(path, tool, input))
+                .filter(|p| !changed_files.contains(p))
+                .collect();
+            if !touched.is_empty() {
+                if let Some(active) = active_queries.lock().await.get_mut(&query_id) {
+                    active.changed_files.extend(touched.iter().cloned());
+                }
+                changed_files.extend(touched);
+                files_flush_at.get_or_insert_with(|| tokio::time::Instant::now() + FILES_CHANGED_DEBOUNCE);
+            }
+        }
+
+        let mut data = match redactor {
+            Some(ref redactor) => redactor.redact_stream_line(&line),
+            None => line,
+        };
+        // Long tool results go out as a preview; the full (redacted) text waits in the query's buffer
+        if data.contains("\"tool_result\"") {
+            if let Some((preview, outputs)) = tool_output::truncate_stream_line(&data, tool_output_preview) {
+                if let Some(active) = active_queries.lock().await.get_mut(&query_id) {
+                    active.tool_outputs.extend(outputs);
+                }
+                data = preview;
+            }
+        }
+        let payload = StreamPayload {
+            query_id: query_id_for_stream.clone(),
+            data,
+        };
+        replay::emit(app, &query_id, "claude-stream", payload).await;
+    }
+
+    // A quick question's session is deleted as it finishes; history doesn't point at it
+    history_base.session_id = session_id.clone().filter(|_| !quick);
+    if files_flush_at.is_some() {
+        emit_changed_files(app, &query_id, &changed_files).await;
+    }
+    if let Some(usage) = context.take_unsent() {
+        replay::emit(app, &query_id, "claude-context-usage", usage).await;
+    }
+
+    if let Some(token) = &disk_watch {
+        token.cancel();
+    }
+    // Stdout closed, but the agent may still be writing stderr (a crash explaining itself).
+    // Its last lines go out before anything terminal does.
+    if let Some(active) = active_queries.lock().await.get_mut(&query_id_for_storage) {
+        if active.phase == QueryPhase::Running {
+            active.phase = QueryPhase::Draining;
         }
     }
+    history_base.stderr_suppressed = finish_stderr(stderr_task.take()).await;
 
     // Wait for process completion and clean up
-    let status = {
-        let mut queries = active_queries.lock().await;
-        if let Some(mut active_query) = queries.remove(&query_id_for_storage) {
-            active_query.child.wait().await.map_err(|e| e.to_string())?
-        } else {
-            // Query was cancelled, return early
-            return Ok(query_id);
+    let removed = active_queries.lock().await.remove(&query_id_for_storage);
+    let (status, followup) = match removed {
+        Some(mut active_query) if active_query.phase == QueryPhase::Cancelling => {
+            // Cancelled (or stopped out of time or over budget): the agent is being stopped, and
+            // only a kept follow-up is still queued
+            let _ = wait_for_exit(&mut active_query.child).await;
+            let reason = stop_reason.unwrap_or(query_cancel::CANCELLED);
+            history_base.cost_usd = Some(costs.total());
+            history_base.terminal_reason = Some(reason.to_string());
+            record_query_history(app, history_base, None, &changed_files).await;
+            replay::emit(app, &query_id, "claude-done", serde_json::json!({
+                "query_id": query_id,
+                "code": -1,
+                "reason": reason
+            }))
+            .await;
+            let next = match (active_query.followup.take(), session_id) {
+                (Some(followup), Some(session_id)) => Some(followup_request(followup, working_dir, session_id, options)),
+                (Some(followup), None) => {
+                    query_cancel::finish_unstarted(app, &followup.query_id, Some(&query_id), query_cancel::PREDECESSOR_FAILED).await;
+                    None
+                }
+                (None, _) => None,
+            };
+            return Ok(next);
+        }
+        Some(mut active_query) => {
+            let followup = active_query.followup.take();
+            // An agent whose output can't be read isn't left running
+            if read_error.is_some() {
+                terminate_child(&mut active_query.child).await;
+            }
+            (wait_for_exit(&mut active_query.child).await, followup)
         }
+        // Only the run removes its own entry, so this is a bug; it still ends with a terminal event
+        None => (Err("The query was no longer tracked".to_string()), None),
     };
 
-    let done_payload = serde_json::json!({
+    // A run whose output or exit couldn't be read failed, whatever the agent itself did
+    let (status, failure) = match status {
+        Ok(status) => (Some(status), read_error),
+        Err(e) => (None, Some(read_error.unwrap_or(format!("Failed to wait for the agent: {}", e)))),
+    };
+    let succeeded = failure.is_none() && status.is_some_and(|status| status.success()) && !result_failed;
+    let exit_code = match &failure {
+        Some(_) => status.and_then(|status| status.code()).or(Some(-1)),
+        None => status.and_then(|status| status.code()),
+    };
+    if let Some(error) = &failure {
+        replay::emit(app, &query_id, "claude-stderr", stderr::StderrEvent {
+            query_id: query_id.clone(),
+            data: error.clone(),
+            severity: stderr::Severity::Error,
+        })
+        .await;
+    }
+
+    history_base.cost_usd = Some(costs.total());
+    history_base.terminal_reason = Some(if succeeded { "completed" } else { "failed" }.to_string());
+    let completed = serde_json::json!({
         "query_id": query_id,
-        "code": status.code().unwrap_or(-1)
+        "working_dir": working_dir,
+        "session_id": session_id,
+        "exit_code": exit_code,
+        "status": history_base.terminal_reason,
+        "cost_usd": history_base.cost_usd,
+        "changed_files": sorted_paths(&changed_files),
     });
-    app.emit("claude-done", done_payload)
-        .map_err(|e| e.to_string())?;
+    record_query_history(app, history_base, exit_code, &changed_files).await;
 
-    Ok(query_id)
+    let mut done_payload = serde_json::json!({
+        "query_id": query_id,
+        "code": exit_code.unwrap_or(-1)
+    });
+    if failure.is_some() {
+        done_payload["reason"] = Value::from("failed");
+    }
+    replay::emit(app, &query_id, "claude-done", done_payload).await;
+    integrations::notify(app, integrations::IntegrationEventKind::QueryCompleted, completed).await;
+
+    // Only a clean finish with a known session hands over to the queued follow-up
+    let next = match (followup, session_id) {
+        (Some(followup), Some(session_id)) if succeeded => {
+            Some(followup_request(followup, working_dir, session_id, options))
+        }
+        (Some(followup), _) => {
+            query_cancel::finish_unstarted(app, &followup.query_id, Some(&query_id), query_cancel::PREDECESSOR_FAILED).await;
+            None
+        }
+        (None, _) => None,
+    };
+
+    Ok(next)
 }
 
-#[tauri::command]
-async fn cancel_query(state: State<'_, AppState>, query_id: String) -> Result<bool, String> {
-    let mut queries = state.active_queries.lock().await;
+/// The run of a follow-up, resuming the session its predecessor left
+fn followup_request(followup: QueuedFollowup, working_dir: String, session_id: String, options: QueryOptions) -> (String, QueryRequest) {
+    (
+        followup.query_id,
+        QueryRequest {
+            prompt: followup.prompt,
+            working_dir,
+            config: followup.config,
+            resume_session: Some(session_id),
+            has_attachments: None,
+            staged_images: Vec::new(),
+            tool_result: None,
+            options,
+            preset: None,
+            remapped_from: None,
+            plan_approval: None,
+            quick: false,
+        },
+    )
+}
 
-    if let Some(mut active_query) = queries.remove(&query_id) {
-        // Try to kill the process
-        #[cfg(unix)]
-        {
-            use nix::sys::signal::{kill, Signal};
-            use nix::unistd::Pid;
+/// The `maxCostUsd` key of a resolved query config, if set
+fn config_max_cost(config: &str) -> Option<f64> {
+    serde_json::from_str::<Value>(config)
+        .ok()?
+        .get("maxCostUsd")
+        .and_then(|v| v.as_f64())
+}
 
-            if let Some(pid) = active_query.child.id() {
-                // Send SIGTERM first for graceful shutdown
-                let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+/// Stop a query's process: SIGTERM first, then a kill if it hasn't exited shortly after
+async fn terminate_child(child: &mut tokio::process::Child) {
+    #[cfg(unix)]
+    {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
 
-                // Wait a bit then force kill if still running
-                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        if let Some(pid) = child.id() {
+            // Send SIGTERM first for graceful shutdown
+            let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
 
-                // Check if still running and force kill
-                match active_query.child.try_wait() {
-                    Ok(None) => {
-                        // Still running, force kill
-                        let _ = active_query.child.kill().await;
-                    }
-                    _ => {}
-                }
-            } else {
-                // No PID, just try to kill
-                let _ = active_query.child.kill().await;
+            // Wait a bit then force kill if still running
+            tokio::time::sleep(TERMINATE_GRACE).await;
+
+            // Check if still running and force kill
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill().await;
             }
+        } else {
+            // No PID, just try to kill
+            let _ = child.kill().await;
         }
+    }
 
-        #[cfg(not(unix))]
-        {
-            // On non-Unix systems, just kill directly
-            let _ = active_query.child.kill().await;
+    #[cfg(not(unix))]
+    {
+        // On non-Unix systems, just kill directly
+        let _ = child.kill().await;
+    }
+}
+
+/// Sleep until the deadline, or forever when there is none (for use in select!)
+async fn sleep_until_deadline(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+fn sorted_paths(paths: &HashSet<PathBuf>) -> Vec<String> {
+    let mut sorted: Vec<String> = paths.iter().map(|p| p.to_string_lossy().to_string()).collect();
+    sorted.sort();
+    sorted
+}
+
+async fn emit_changed_files(app: &tauri::AppHandle, query_id: &str, files: &HashSet<PathBuf>) {
+    file_index::mark_dirty(app, files);
+    annotations::mark_dirty(app, files);
+    replay::emit(app, query_id, "query-files-changed", serde_json::json!({
+        "query_id": query_id,
+        "files": sorted_paths(files)
+    }))
+    .await;
+}
+
+/// Let a query's stderr reader drain briefly, then stop it. It's joined either way, so none of
+/// its lines can follow the query's terminal event. The count of benign lines it saw, if any.
+async fn finish_stderr(task: Option<tasks::SpawnedTask<u32>>) -> Option<u32> {
+    let mut task = task?;
+    let joined = match tokio::time::timeout(STDERR_DRAIN_TIMEOUT, &mut task.handle).await {
+        Ok(joined) => joined,
+        Err(_) => {
+            task.token.cancel();
+            task.handle.await
         }
+    };
+    joined.ok().filter(|benign| *benign > 0)
+}
 
-        Ok(true)
-    } else {
-        Ok(false)
+/// Wait for a query's process to exit, stopping it if it lingers after closing its output
+async fn wait_for_exit(child: &mut tokio::process::Child) -> Result<std::process::ExitStatus, String> {
+    if let Ok(status) = tokio::time::timeout(CHILD_EXIT_TIMEOUT, child.wait()).await {
+        return status.map_err(|e| e.to_string());
+    }
+    terminate_child(child).await;
+    child.wait().await.map_err(|e| e.to_string())
+}
+
+/// Append the finished query to the history file; failures are logged, not surfaced
+async fn record_query_history(
+    app: &tauri::AppHandle,
+    base: history::QueryRecord,
+    exit_code: Option<i32>,
+    changed_files: &HashSet<PathBuf>,
+) {
+    let record = history::QueryRecord {
+        finished_at: history::now_secs(),
+        exit_code,
+        cancelled: exit_code.is_none(),
+        changed_files: sorted_paths(changed_files),
+        ..base
+    };
+    if let Err(e) = history::append_record(app, &record).await {
+        eprintln!("[mensa] {}", e);
+    }
+    if let Some(reason) = &record.terminal_reason {
+        if let Err(e) = prompt_history::set_outcome(app, &record.query_id, reason).await {
+            eprintln!("[mensa] {}", e);
+        }
     }
 }
 
 #[tauri::command]
-async fn list_active_queries(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+async fn list_active_queries(state: State<'_, AppState>) -> Result<Vec<ActiveQueryInfo>, String> {
     let queries = state.active_queries.lock().await;
-    Ok(queries.keys().cloned().collect())
+    let mut infos = Vec::new();
+    for (query_id, active) in queries.iter() {
+        let status = match active.phase {
+            QueryPhase::Running => "running",
+            QueryPhase::Draining => "draining",
+            QueryPhase::Cancelling => "cancelling",
+        };
+        infos.push(ActiveQueryInfo {
+            query_id: query_id.clone(),
+            status: status.to_string(),
+            predecessor: None,
+        });
+        if let Some(ref followup) = active.followup {
+            infos.push(ActiveQueryInfo {
+                query_id: followup.query_id.clone(),
+                status: "queued".to_string(),
+                predecessor: Some(query_id.clone()),
+            });
+        }
+    }
+    Ok(infos)
+}
+
+/// Queue a prompt to send automatically when a running query finishes successfully.
+/// Replaces any follow-up already queued; returns the follow-up's query id.
+#[tauri::command]
+async fn queue_followup(
+    state: State<'_, AppState>,
+    query_id: String,
+    prompt: String,
+    config: Option<String>,
+) -> Result<String, String> {
+    // Its session is deleted as it finishes, so there is nothing to continue
+    if state.quick_sessions.contains(&query_id) {
+        return Err("A quick question can't take a follow-up".to_string());
+    }
+    let mut queries = state.active_queries.lock().await;
+    let active = queries
+        .get_mut(&query_id)
+        .ok_or_else(|| format!("Query is not running: {}", query_id))?;
+
+    let followup_id = Uuid::new_v4().to_string();
+    active.followup = Some(QueuedFollowup {
+        query_id: followup_id.clone(),
+        prompt,
+        config,
+    });
+    Ok(followup_id)
 }
 
+/// Remove the follow-up queued on a query
 #[tauri::command]
-async fn read_plan_file(_workspace_path: String, plan_filename: String) -> Result<String, String> {
+async fn clear_followup(state: State<'_, AppState>, query_id: String) -> Result<bool, String> {
+    let mut queries = state.active_queries.lock().await;
+    Ok(queries
+        .get_mut(&query_id)
+        .and_then(|active| active.followup.take())
+        .is_some())
+}
+
+/// Files touched so far by a running query, or the final set of a finished one
+#[tauri::command]
+async fn get_query_changed_files(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    query_id: String,
+) -> Result<Vec<String>, String> {
+    if let Some(active) = state.active_queries.lock().await.get(&query_id) {
+        return Ok(sorted_paths(&active.changed_files));
+    }
+
+    history::find_record(&app, &query_id)
+        .await?
+        .map(|record| record.changed_files)
+        .ok_or_else(|| format!("No query found with id {}", query_id))
+}
+
+/// A plan file's raw text, or the text plus sanitized HTML when `rendered` was requested
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum PlanFileContent {
+    Raw(String),
+    Rendered {
+        content: String,
+        #[serde(flatten)]
+        rendered: markdown::RenderedMarkdown,
+    },
+}
+
+#[tauri::command]
+async fn read_plan_file(
+    _workspace_path: String,
+    plan_filename: String,
+    rendered: Option<bool>,
+) -> Result<PlanFileContent, permissions::SessionError> {
     // Claude Code writes plan files to ~/.claude/plans/ (user's home directory)
-    let home = std::env::var("HOME").map_err(|_| "Could not determine home directory")?;
-    let plan_path = Path::new(&home)
-        .join(".claude")
-        .join("plans")
-        .join(&plan_filename);
+    let plan_path = paths::ClaudeHome::current()?.plans().join(&plan_filename);
 
-    tokio::fs::read_to_string(&plan_path)
+    let content = tokio::fs::read_to_string(&plan_path)
         .await
-        .map_err(|e| format!("Failed to read plan file: {}", e))
+        .map_err(|e| permissions::io_error("read plan file", &plan_path, &e))?;
+
+    if rendered != Some(true) {
+        return Ok(PlanFileContent::Raw(content));
+    }
+    let rendered = markdown::render(&content, &markdown::MarkdownOptions::default());
+    Ok(PlanFileContent::Rendered { content, rendered })
+}
+
+#[tauri::command]
+async fn list_plan_files(_workspace_path: String) -> Result<Vec<String>, permissions::SessionError> {
+    Ok(plan_files().await?.into_iter().map(|(_, name)| name).collect())
 }
 
+/// `list_plan_files` a page at a time, most recently modified first
 #[tauri::command]
-async fn list_plan_files(_workspace_path: String) -> Result<Vec<String>, String> {
+async fn list_plan_files_paged(
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<pagination::Page<String>, pagination::PageError> {
+    let size = pagination::page_size(limit)?;
+    let scope = pagination::CursorScope::new("plan_files", &[]);
+    let after: Option<(i64, String)> = scope.decode(cursor.as_deref())?;
+    let files = plan_files().await?;
+    let total = files.len() as u64;
+    let (files, next) = pagination::keyset_page(files, |file| file.clone(), after.as_ref(), size);
+    Ok(scope.page(files.into_iter().map(|(_, name)| name).collect(), Some(total), next))
+}
+
+/// Plan files with their modification time (unix millis), most recent first
+async fn plan_files() -> Result<Vec<(i64, String)>, permissions::SessionError> {
     // Claude Code writes plan files to ~/.claude/plans/ (user's home directory)
-    let home = std::env::var("HOME").map_err(|_| "Could not determine home directory")?;
-    let plans_dir = Path::new(&home)
-        .join(".claude")
-        .join("plans");
+    let plans_dir = paths::ClaudeHome::current()?.plans();
 
     if !plans_dir.exists() {
         return Ok(vec![]);
@@ -721,17 +1864,18 @@ async fn list_plan_files(_workspace_path: String) -> Result<Vec<String>, String>
 
     let mut entries = tokio::fs::read_dir(&plans_dir)
         .await
-        .map_err(|e| format!("Failed to read plans directory: {}", e))?;
+        .map_err(|e| permissions::io_error("read plans directory", &plans_dir, &e))?;
 
     // Collect files with their modification times
-    let mut plan_files_with_time: Vec<(String, std::time::SystemTime)> = Vec::new();
+    let mut plan_files_with_time: Vec<(i64, String)> = Vec::new();
     while let Some(entry) = entries.next_entry().await.map_err(|e| e.to_string())? {
         let path = entry.path();
         if path.extension().map(|e| e == "md").unwrap_or(false) {
             if let Some(name) = path.file_name() {
                 if let Ok(metadata) = entry.metadata().await {
                     if let Ok(modified) = metadata.modified() {
-                        plan_files_with_time.push((name.to_string_lossy().to_string(), modified));
+                        let millis = modified.duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_millis() as i64);
+                        plan_files_with_time.push((millis, name.to_string_lossy().to_string()));
                     }
                 }
             }
@@ -739,51 +1883,421 @@ async fn list_plan_files(_workspace_path: String) -> Result<Vec<String>, String>
     }
 
     // Sort by modification time (most recent first)
-    plan_files_with_time.sort_by(|a, b| b.1.cmp(&a.1));
+    plan_files_with_time.sort_by(|a, b| b.cmp(a));
 
-    Ok(plan_files_with_time.into_iter().map(|(name, _)| name).collect())
+    Ok(plan_files_with_time)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let context = tauri::generate_context!();
+    let args: Vec<String> = std::env::args().collect();
+    match cli::parse(&args) {
+        Ok(None) => {}
+        Ok(Some(command)) => std::process::exit(cli::run(command, context)),
+        Err(e) => std::process::exit(cli::usage_error(&e)),
+    }
+
+    app_builder()
+        .build(context)
+        .expect("error while running tauri application")
+        .run(|handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                tauri::async_runtime::block_on(shutdown(handle));
+            }
+        });
+}
+
+/// Longest exit waits for background tasks to finish after cancelling them
+const SHUTDOWN_DEADLINE: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Stop every running query, then cancel the background tasks and wait (bounded) for them
+pub async fn shutdown(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    query_cancel::stop_all_queries(app).await;
+    let report = state.tasks.shutdown_all(SHUTDOWN_DEADLINE).await;
+    if !report.aborted.is_empty() {
+        eprintln!("[mensa] Aborted background tasks still running at exit: {}", report.aborted.join(", "));
+    }
+}
+
+/// The app with its plugins, state and commands; shared by the window app and headless runs
+fn app_builder() -> tauri::Builder<tauri::Wry> {
+    let app_state = AppState::default();
+    let git_state = git::GitState {
+        tasks: app_state.tasks.clone(),
+        proxy: app_state.proxy.clone(),
+        ..Default::default()
+    };
     tauri::Builder::default()
+        // Must come first: a second `mensa` hands its command line to this one and exits
+        .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| cli::forwarded(app, argv)))
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_pty::init())
-        .manage(AppState::default())
-        .invoke_handler(tauri::generate_handler![
-            query_claude,
-            cancel_query,
-            list_active_queries,
-            list_sessions,
-            delete_session,
-            load_session_messages,
-            read_plan_file,
-            list_plan_files,
-            // Git commands
-            git::git_status,
-            git::git_diff,
-            git::git_stage,
-            git::git_unstage,
-            git::git_branch_info,
-            git::git_commit,
-            git::git_push,
-            git::git_log,
-            git::git_fetch,
-            git::git_pull,
-            git::git_discard,
-            git::check_gh_cli_available,
-            git::create_pull_request,
-            git::git_list_branches,
-            git::git_diff_commits,
-            // PR Review commands
-            git::list_prs,
-            git::fetch_pr_info,
-            git::fetch_pr_diff,
-            git::post_pr_review
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .manage(app_state)
+        .manage(git_state)
+        .setup(|app| {
+            // The read-only guard checks the settings synchronously; have them loaded before any command
+            if let Err(e) = tauri::async_runtime::block_on(settings::load(app.handle(), &app.state::<AppState>().settings)) {
+                eprintln!("[mensa] {}", e);
+            }
+            if let Err(e) = script::cleanup_old_extractions(app.handle()) {
+                eprintln!("[mensa] {}", e);
+            }
+            if let Err(e) = attachments::cleanup_staged(app.handle()) {
+                eprintln!("[mensa] {}", e);
+            }
+
+            app.state::<git::GitState>().gh_token.bind(app.handle());
+
+            // Move any plaintext secrets left by older builds into the keychain, then resolve
+            // the proxy (whose credentials may be among them) for child processes
+            let handle = app.handle().clone();
+            app.state::<AppState>().tasks.spawn("secrets migration and proxy setup", |_| async move {
+                if let Err(e) = secrets::migrate_plaintext(&handle).await {
+                    eprintln!("[mensa] {}", e);
+                }
+                if let Err(e) = proxy::refresh(&handle).await {
+                    eprintln!("[mensa] {}", e);
+                }
+            });
+            let handle = app.handle().clone();
+            app.state::<AppState>().tasks.spawn("model catalog", |_| models::init(handle));
+            app.state::<AppState>().tasks.spawn("claude permissions check", |_| permissions::log_startup_issues());
+            let handle = app.handle().clone();
+            app.state::<AppState>().tasks.spawn("update checks", |token| updates::run_background_checks(handle, token));
+            let handle = app.handle().clone();
+            app.state::<AppState>().tasks.spawn("control endpoint", |token| control::serve(handle, token));
+            Ok(())
+        })
+        .invoke_handler(move |invoke| match read_only::guard(invoke) {
+            Some(invoke) => commands(invoke),
+            None => true,
+        })
+}
+
+/// Every command the frontend can invoke; `read_only::guard` sees each call first
+fn commands(invoke: tauri::ipc::Invoke) -> bool {
+    let handler: fn(tauri::ipc::Invoke) -> bool = tauri::generate_handler![
+        query_claude,
+        attachments::ingest_clipboard_image,
+        query_group::query_claude_multi,
+        query_group::cancel_group,
+        query_cancel::cancel_query,
+        list_active_queries,
+        queue_followup,
+        clear_followup,
+        get_query_changed_files,
+        follow::follow_file,
+        follow::unfollow_file,
+        patch::apply_patch_text,
+        history::list_query_history,
+        history::list_query_history_paged,
+        prompt_history::search_prompt_history,
+        prompt_history::search_prompt_history_paged,
+        prompt_history::delete_prompt_history_entry,
+        prompt_history::clear_prompt_history,
+        replay::replay_query_events,
+        replay::list_replay_buffers,
+        replay::subscribe_query,
+        proxy::test_proxy,
+        proxy::set_proxy_credentials,
+        integrations::test_integration_hook,
+        titles::rename_session,
+        session_guard::repair_session_file,
+        permissions::check_claude_permissions,
+        titles::generate_session_title,
+        titles::generate_missing_titles,
+        session_migration::migrate_workspace_sessions,
+        editor::open_session_at,
+        replay::ack_query_events,
+        replay::get_last_seq,
+        session_cache::get_session_cache_stats,
+        progress::list_running_operations,
+        compare::compare_sessions,
+        context::analyze_session_context,
+        context::get_trimmed_session_origin,
+        file_index::fuzzy_match_files,
+        file_index::invalidate_file_index,
+        references::validate_prompt_references,
+        annotations::scan_code_annotations,
+        annotations::annotations_to_prompt,
+        script::check_runtime_health,
+        script::reveal_app_location,
+        runtime::set_preferred_runtime_path,
+        disk::check_disk_space,
+        timeline::get_query_timeline,
+        usage::get_usage_analytics,
+        updates::check_for_updates,
+        updates::install_update,
+        markdown::render_markdown,
+        presets::list_query_presets,
+        presets::save_query_preset,
+        presets::delete_query_preset,
+        presets::set_query_preset_hidden,
+        presets::set_workspace_query_defaults,
+        templates::list_prompt_templates,
+        templates::save_prompt_template,
+        templates::delete_prompt_template,
+        templates::expand_prompt_template,
+        list_sessions,
+        list_sessions_paged,
+        session_search::search_sessions,
+        delete_session,
+        load_session_messages,
+        cancel::cancel_operation,
+        load_session_messages_page,
+        bookmarks::add_message_bookmark,
+        bookmarks::list_message_bookmarks,
+        bookmarks::remove_message_bookmark,
+        export_session,
+        read_plan_file,
+        list_plan_files,
+        list_plan_files_paged,
+        plan_approval::plan_file_hash,
+        plan_approval::approve_plan_and_continue,
+        plan_approval::reject_plan,
+        // Git commands
+        git::git_status,
+        git::get_repo_capabilities,
+        pr_context::collect_pr_context,
+        git::git_status_summary,
+        git::git_is_dirty,
+        git::git_diff,
+        git::git_diff_stats,
+        git::git_diff_stats_range,
+        git::git_stage,
+        git::git_unstage,
+        git::git_branch_info,
+        git::git_commit,
+        identity::get_commit_identity,
+        identity::set_commit_identity,
+        identity::detect_identity_mismatch,
+        git::git_push,
+        git::git_log,
+        git::git_log_paged,
+        git::git_fetch,
+        git::git_gc,
+        git::git_fetch_deepen,
+        git::git_set_upstream,
+        digest::workspace_digest,
+        overview::workspace_overview,
+        git::git_pull,
+        git::git_discard,
+        git::git_check_attr,
+        git::check_gh_cli_available,
+        gh_auth::get_gh_auth_status,
+        gh_auth::preflight_gh_feature,
+        git::create_pull_request,
+        git::git_list_branches,
+        git::git_diff_commits,
+        git::git_precommit_scan,
+        git::git_rebase_plan,
+        git::git_rebase_execute,
+        git::git_rebase_continue,
+        git::git_rebase_abort,
+        git::cancel_git_operation,
+        git::get_branch_protection,
+        git::git_create_branch_and_move_changes,
+        // Checkpoint commands
+        checkpoints::list_file_checkpoints,
+        checkpoints::read_file_checkpoint,
+        checkpoints::restore_file_checkpoint,
+        // Workspace commands
+        workspace::register_workspace,
+        workspace::bootstrap_workspace,
+        workspace::gitignore_add,
+        workspace_health::analyze_workspace_health,
+        workspace_templates::list_workspace_templates,
+        workspace_templates::create_workspace_from_template,
+        workspace::get_workspace_state,
+        workspace::set_workspace_state,
+        // Secret storage commands
+        secrets::list_stored_secrets,
+        secrets::set_stored_secret,
+        secrets::delete_stored_secret,
+        // Sensitive file commands
+        sensitive::detect_sensitive_files,
+        sensitive::get_sensitive_patterns,
+        sensitive::set_sensitive_patterns,
+        settings::get_settings,
+        settings::update_settings,
+        app_data::export_app_data,
+        app_data::import_app_data,
+        tool_output::get_tool_output,
+        tool_output::get_query_tool_output,
+        context_usage::get_query_context_usage,
+        tasks::list_background_tasks,
+        status_filters::get_status_filters,
+        status_filters::set_status_filters,
+        tool_policies::get_tool_policies,
+        tool_policies::set_tool_policy,
+        tool_policies::evaluate_tool_policy,
+        tool_policies::export_tool_policies,
+        // PR Review commands
+        git::list_prs,
+        git::list_prs_paged,
+        git::fetch_pr_info,
+        git::fetch_pr_diff,
+        pr_batch::fetch_pr_batch,
+        git::post_pr_review,
+        forge::get_repo_info,
+        review_drafts::save_review_draft,
+        review_drafts::get_review_draft,
+        review_drafts::delete_review_draft,
+        review_fixes::build_review_fix_prompt,
+        review_fixes::match_threads_to_changes,
+        models::list_models,
+        read_only::get_read_only_status,
+        read_only::set_read_only
+    ];
+    handler(invoke)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch_home, write};
+
+    // macOS file systems look names up normalization-insensitively; there both spellings exist
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn sessions_of_an_accented_workspace_are_listed_by_either_spelling() {
+        let home = scratch_home();
+        let workspace = home.dir.path().join("Proje\u{301}t");
+        std::fs::create_dir_all(&workspace).unwrap();
+        let recorded = workspace.to_string_lossy().to_string();
+        let project_dir = home.claude().projects().join(paths::sanitize_workspace_path(&recorded));
+        let transcript = format!(
+            "{}\n",
+            serde_json::json!({
+                "type": "user",
+                "cwd": recorded,
+                "sessionId": "s1",
+                "timestamp": "2026-01-05T10:00:00.000Z",
+                "message": { "role": "user", "content": "Hello from an accented directory" },
+            })
+        );
+        write(&project_dir, "s1.jsonl", transcript);
+
+        let composed = fsutil::nfc(&recorded);
+        assert_ne!(composed, recorded);
+        for workspace_path in [recorded.clone(), composed] {
+            let entries = session_entries(workspace_path.clone(), None, &HashSet::new()).await.unwrap();
+            assert_eq!(entries.len(), 1, "listing {}", workspace_path);
+            assert_eq!(entries[0].session_id, "s1");
+            // Recorded in this workspace, just spelled differently: not a moved session
+            assert_eq!(entries[0].original_cwd, None);
+        }
+    }
+
+    /// Events a harness query emitted, as (event, data)
+    type Emitted = std::sync::Arc<std::sync::Mutex<Vec<(String, String)>>>;
+
+    /// Run `script` under sh as a query's agent, through the steps `query_claude` takes: stdout
+    /// lines as `claude-stream` until it closes, stderr through its reader task, then the
+    /// drain, the wait and `claude-done`. Keeps listening a while after that, so anything
+    /// emitted late would be caught.
+    #[cfg(unix)]
+    async fn run_agent(script: &str) -> Vec<(String, String)> {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", script])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let emitted: Emitted = Default::default();
+        let emit = |emitted: &Emitted, event: &str, data: String| emitted.lock().unwrap().push((event.to_string(), data));
+
+        let stderr = child.stderr.take().unwrap();
+        let tasks = tasks::TaskRegistry::default();
+        let sink = emitted.clone();
+        let stderr_task = tasks.spawn("query stderr", move |token| {
+            stderr::pump_into(stderr, stderr::Classifier::new(&[]), token, move |data, _| {
+                emit(&sink, "claude-stderr", data);
+                std::future::ready(())
+            })
+        });
+
+        let mut reader = BufReader::new(child.stdout.take().unwrap()).lines();
+        while let Some(line) = reader.next_line().await.unwrap() {
+            emit(&emitted, "claude-stream", line);
+        }
+        finish_stderr(Some(stderr_task)).await;
+        let status = wait_for_exit(&mut child).await.unwrap();
+        emit(&emitted, "claude-done", status.code().unwrap_or(-1).to_string());
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let emitted = emitted.lock().unwrap().clone();
+        emitted
+    }
+
+    fn assert_done_is_last(emitted: &[(String, String)]) {
+        let done = emitted.iter().position(|(event, _)| event == "claude-done");
+        assert_eq!(done, Some(emitted.len() - 1), "{:?}", emitted);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stderr_after_stdout_closes_goes_out_before_done() {
+        let emitted = run_agent(
+            r#"echo '{"type":"system","subtype":"init"}'
+               exec 1>&-
+               sleep 0.1
+               echo 'Error: socket hang up' >&2
+               echo '    at TLSSocket.onClose (node:_tls_wrap:1:1)' >&2
+               sleep 0.2
+               echo 'fatal: agent crashed' >&2
+               exit 3"#,
+        )
+        .await;
+        let events: Vec<(&str, &str)> = emitted.iter().map(|(e, d)| (e.as_str(), d.as_str())).collect();
+        assert_eq!(
+            events,
+            [
+                ("claude-stream", r#"{"type":"system","subtype":"init"}"#),
+                ("claude-stderr", "Error: socket hang up\n    at TLSSocket.onClose (node:_tls_wrap:1:1)"),
+                ("claude-stderr", "fatal: agent crashed"),
+                ("claude-done", "3"),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn endless_stderr_is_cut_off_before_done() {
+        // Closes stdout, then writes stderr well past the drain timeout before exiting
+        let emitted = run_agent(
+            r#"exec 1>&-
+               i=0
+               while [ $i -lt 30 ]; do echo "warning: tick $i" >&2; sleep 0.05; i=$((i+1)); done
+               exit 0"#,
+        )
+        .await;
+        assert_done_is_last(&emitted);
+        // With its reader stopped, the agent's next write fails (SIGPIPE) and it exits
+        assert_eq!(emitted.last().unwrap().1, "-1");
+        let ticks = emitted.iter().filter(|(event, _)| event == "claude-stderr").count();
+        assert!(ticks > 0 && ticks < 30, "{} ticks", ticks);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stderr_closing_with_stdout_is_read_to_the_end() {
+        let emitted = run_agent(
+            r#"echo '{"type":"result"}'
+               for i in 1 2 3 4 5; do echo "warning: line $i" >&2; done
+               exit 1"#,
+        )
+        .await;
+        assert_done_is_last(&emitted);
+        let stderr: Vec<&str> = emitted.iter().filter(|(e, _)| e == "claude-stderr").map(|(_, d)| d.as_str()).collect();
+        assert_eq!(stderr, ["warning: line 1", "warning: line 2", "warning: line 3", "warning: line 4", "warning: line 5"]);
+        assert_eq!(emitted.last().unwrap().1, "1");
+    }
 }
@@ -0,0 +1,239 @@
+// mensa - Markdown Module
+// Renders agent-produced markdown (plans, PR bodies) to sanitized HTML with highlighted code
+
+use crate::export;
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Prefix ammonia puts on every id so rendered headings can't clobber app DOM ids
+const ID_PREFIX: &str = "md-";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MarkdownOptions {
+    /// Syntax-highlight fenced code blocks (plain <pre><code> otherwise)
+    pub highlight_code: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self { highlight_code: true }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutlineHeading {
+    pub level: u8,
+    pub text: String,
+    /// Element id of the heading in the rendered HTML
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub outline: Vec<OutlineHeading>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+        // Task list checkboxes; anything but a checkbox loses its type below
+        builder
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["type", "checked", "disabled"])
+            .id_prefix(Some(ID_PREFIX))
+            .attribute_filter(|element, attribute, value| {
+                if element == "input" && attribute == "type" && value != "checkbox" {
+                    return None;
+                }
+                Some(Cow::Borrowed(value))
+            });
+        for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+            builder.add_tag_attributes(heading, ["id"]);
+        }
+        builder
+    })
+}
+
+/// GitHub-style heading anchor: lowercase words joined by dashes, de-duplicated with a suffix
+fn heading_slug(text: &str, used: &mut HashMap<String, u32>) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || c == '_' || c == '-' {
+            slug.push(c);
+        } else if c.is_whitespace() {
+            slug.push('-');
+        }
+    }
+    if slug.is_empty() {
+        slug.push_str("section");
+    }
+
+    let count = used.entry(slug.clone()).or_insert(0);
+    *count += 1;
+    if *count > 1 {
+        slug = format!("{}-{}", slug, *count - 1);
+    }
+    slug
+}
+
+/// Render markdown to sanitized HTML and collect its headings.
+///
+/// Highlighted code is swapped in for random placeholders after sanitizing, since
+/// syntect's inline styles would otherwise be stripped; its output is already escaped.
+pub fn render(content: &str, options: &MarkdownOptions) -> RenderedMarkdown {
+    let parser_options =
+        Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_FOOTNOTES;
+    let events: Vec<Event> = Parser::new_ext(content, parser_options).collect();
+
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let mut code_blocks: Vec<String> = Vec::new();
+    let mut outline = Vec::new();
+    let mut used_slugs = HashMap::new();
+    let mut output: Vec<Event> = Vec::with_capacity(events.len());
+
+    let mut i = 0;
+    while i < events.len() {
+        match &events[i] {
+            Event::Start(Tag::Heading { level, classes, attrs, .. }) => {
+                let text: String = events[i + 1..]
+                    .iter()
+                    .take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))))
+                    .filter_map(|e| match e {
+                        Event::Text(t) | Event::Code(t) => Some(t.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                let slug = heading_slug(&text, &mut used_slugs);
+                outline.push(OutlineHeading {
+                    level: *level as u8,
+                    text,
+                    id: format!("{}{}", ID_PREFIX, slug),
+                });
+                output.push(Event::Start(Tag::Heading {
+                    level: *level,
+                    id: Some(CowStr::from(slug)),
+                    classes: classes.clone(),
+                    attrs: attrs.clone(),
+                }));
+                i += 1;
+            }
+            Event::Start(Tag::CodeBlock(kind)) if options.highlight_code => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                let mut code = String::new();
+                i += 1;
+                while i < events.len() && !matches!(events[i], Event::End(TagEnd::CodeBlock)) {
+                    if let Event::Text(text) = &events[i] {
+                        code.push_str(text);
+                    }
+                    i += 1;
+                }
+                output.push(Event::Html(
+                    format!("<div>mensa-code-{}-{}</div>\n", nonce, code_blocks.len()).into(),
+                ));
+                code_blocks.push(export::highlight_code(&code, &lang));
+                i += 1;
+            }
+            event => {
+                output.push(event.clone());
+                i += 1;
+            }
+        }
+    }
+
+    let mut raw_html = String::new();
+    html::push_html(&mut raw_html, output.into_iter());
+
+    let mut html = sanitizer().clean(&raw_html).to_string();
+    for (index, block) in code_blocks.iter().enumerate() {
+        html = html.replacen(&format!("<div>mensa-code-{}-{}</div>", nonce, index), block, 1);
+    }
+
+    RenderedMarkdown { html, outline }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Render markdown to sanitized HTML plus a heading outline for a table of contents
+#[tauri::command]
+pub async fn render_markdown(content: String, options: Option<MarkdownOptions>) -> Result<RenderedMarkdown, String> {
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || render(&content, &options))
+        .await
+        .map_err(|e| format!("Markdown rendering failed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn render_html(content: &str) -> String {
+        render(content, &MarkdownOptions::default()).html
+    }
+
+    #[test]
+    fn malicious_markup_is_stripped() {
+        let html = render_html(&fixture("markdown/malicious.md"));
+        let lower = html.to_lowercase();
+        for forbidden in ["<script", "onerror", "onclick", "onmouseover", "javascript:", "<iframe", "type=\"text\""] {
+            assert!(!lower.contains(forbidden), "{} survived in {}", forbidden, html);
+        }
+        // What's harmless is kept
+        assert!(html.contains("Hover me"));
+        assert!(html.contains("type=\"checkbox\""));
+        assert!(html.contains("id=\"md-plan\""));
+    }
+
+    #[test]
+    fn highlighted_code_stays_escaped() {
+        for highlight_code in [true, false] {
+            let html = render(&fixture("markdown/malicious.md"), &MarkdownOptions { highlight_code }).html;
+            // Highlighting splits the tag into spans; either way it is text, not markup
+            assert!(html.contains("&lt;/"), "highlight_code={}: {}", highlight_code, html);
+            assert!(!html.to_lowercase().contains("<script"));
+            assert!(!html.contains("</code></pre><"));
+        }
+    }
+
+    #[test]
+    fn placeholder_in_the_content_is_not_replaced() {
+        // A forged placeholder can't name this render's nonce, so it stays text
+        let forged = "<div>mensa-code-00000000000000000000000000000000-0</div>\n\n```rust\nfn main() {}\n```\n";
+        let html = render_html(forged);
+        assert!(html.contains("mensa-code-00000000000000000000000000000000-0"));
+        assert_eq!(html.matches("main").count(), 1);
+        // The real placeholder was swapped for the code; only the forged one is left
+        assert_eq!(html.matches("mensa-code-").count(), 1);
+    }
+
+    #[test]
+    fn headings_get_prefixed_unique_ids() {
+        let rendered = render("# Setup\n\n## Setup\n\n### C'est *fini*!\n", &MarkdownOptions::default());
+        let ids: Vec<&str> = rendered.outline.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, ["md-setup", "md-setup-1", "md-cest-fini"]);
+        for id in ids {
+            assert!(rendered.html.contains(&format!("id=\"{}\"", id)));
+        }
+    }
+}
@@ -0,0 +1,628 @@
+// mensa - Models Module
+// The model catalog: built-in entries, overlaid by a cached remote manifest and then a user file
+// in app data. Cost estimates, context usage and config checks all read it.
+
+use crate::store::JsonStore;
+use crate::{history, proxy, settings, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Context window assumed for a model the catalog doesn't know
+pub const FALLBACK_CONTEXT_WINDOW: u64 = 200_000;
+
+/// Model ids with this suffix run with a 1M-token context window
+const LONG_CONTEXT_SUFFIX: &str = "[1m]";
+const LONG_CONTEXT_WINDOW: u64 = 1_000_000;
+
+/// Cache writes and reads cost these multiples of the input price unless an entry says otherwise
+const CACHE_WRITE_MULTIPLIER: f64 = 1.25;
+const CACHE_READ_MULTIPLIER: f64 = 0.1;
+
+/// The remote manifest is fetched again once the cached copy is this old (seconds)
+const REMOTE_MAX_AGE_SECS: i64 = 24 * 60 * 60;
+
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest remote manifest accepted
+const MAX_REMOTE_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CatalogSource {
+    Builtin,
+    Remote,
+    User,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub id: String,
+    pub display_name: String,
+    /// Substrings of model names this entry also stands for (e.g. "sonnet"); the longest match wins
+    pub matches: Vec<String>,
+    pub context_window: u64,
+    pub supports_thinking: bool,
+    /// USD per million tokens
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    pub cache_write_per_mtok: f64,
+    pub cache_read_per_mtok: f64,
+    /// The last layer that set this entry
+    pub source: CatalogSource,
+}
+
+/// One entry of the remote manifest or the user file: a new model, or fields to change on a known one
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ModelOverride {
+    id: String,
+    display_name: Option<String>,
+    matches: Option<Vec<String>>,
+    context_window: Option<u64>,
+    supports_thinking: Option<bool>,
+    input_per_mtok: Option<f64>,
+    output_per_mtok: Option<f64>,
+    cache_write_per_mtok: Option<f64>,
+    cache_read_per_mtok: Option<f64>,
+}
+
+/// The remote manifest as last fetched
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteCache {
+    url: String,
+    fetched_at: i64,
+    models: Vec<Value>,
+}
+
+static REMOTE_FILE: JsonStore<RemoteCache> = JsonStore::new("remote model catalog");
+
+/// The merged catalog and what was skipped building it
+#[derive(Debug, Default)]
+struct Catalog {
+    models: Vec<ModelInfo>,
+    warnings: Vec<String>,
+    /// Modification time and size of the user file it was built from
+    user_stamp: Option<(SystemTime, u64)>,
+    remote_fetched_at: Option<i64>,
+}
+
+static CATALOG: RwLock<Option<Arc<Catalog>>> = RwLock::new(None);
+
+/// A remote fetch is in flight
+static REFRESHING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelList {
+    pub models: Vec<ModelInfo>,
+    /// Entries or files that were skipped, and why
+    pub warnings: Vec<String>,
+    /// Unix seconds of the remote manifest in use, if any
+    pub remote_fetched_at: Option<i64>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn builtin_entry(id: &str, display_name: &str, matches: &[&str], thinking: bool, input: f64, output: f64) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        display_name: display_name.to_string(),
+        matches: matches.iter().map(|m| m.to_string()).collect(),
+        context_window: 200_000,
+        supports_thinking: thinking,
+        input_per_mtok: input,
+        output_per_mtok: output,
+        cache_write_per_mtok: input * CACHE_WRITE_MULTIPLIER,
+        cache_read_per_mtok: input * CACHE_READ_MULTIPLIER,
+        source: CatalogSource::Builtin,
+    }
+}
+
+fn builtin_models() -> Vec<ModelInfo> {
+    vec![
+        builtin_entry("claude-opus-4-5", "Claude Opus 4.5", &["opus-4-5"], true, 5.0, 25.0),
+        builtin_entry("claude-opus-4-1", "Claude Opus 4.1", &["opus"], true, 15.0, 75.0),
+        builtin_entry("claude-sonnet-4-5", "Claude Sonnet 4.5", &["sonnet"], true, 3.0, 15.0),
+        builtin_entry("claude-haiku-4-5", "Claude Haiku 4.5", &["haiku-4"], true, 1.0, 5.0),
+        builtin_entry("claude-3-5-haiku", "Claude Haiku 3.5", &["haiku-3-5"], false, 0.8, 4.0),
+        builtin_entry("claude-3-haiku", "Claude Haiku 3", &["haiku"], false, 0.25, 1.25),
+    ]
+}
+
+fn check_override(entry: &ModelOverride) -> Result<(), String> {
+    if entry.id.trim().is_empty() {
+        return Err("id must not be empty".to_string());
+    }
+    if entry.context_window == Some(0) {
+        return Err("contextWindow must be at least 1".to_string());
+    }
+    let prices = [
+        ("inputPerMtok", entry.input_per_mtok),
+        ("outputPerMtok", entry.output_per_mtok),
+        ("cacheWritePerMtok", entry.cache_write_per_mtok),
+        ("cacheReadPerMtok", entry.cache_read_per_mtok),
+    ];
+    for (name, price) in prices {
+        if price.is_some_and(|p| !p.is_finite() || p < 0.0) {
+            return Err(format!("{} must be a non-negative number", name));
+        }
+    }
+    if entry.matches.as_ref().is_some_and(|m| m.iter().any(|s| s.trim().is_empty())) {
+        return Err("matches must not contain empty strings".to_string());
+    }
+    Ok(())
+}
+
+/// The most expensive known prices, for a model the catalog lacks, so a cost ceiling still holds
+fn fallback_prices(models: &[ModelInfo]) -> (f64, f64) {
+    models.iter().fold((0.0, 0.0), |(input, output), m| {
+        (input.max(m.input_per_mtok), output.max(m.output_per_mtok))
+    })
+}
+
+/// Apply one layer's entries: an id already in the catalog is updated field by field, a new id
+/// is added with conservative defaults for what it leaves out. Bad entries are skipped.
+fn apply_layer(models: &mut Vec<ModelInfo>, entries: &[Value], source: CatalogSource, label: &str, warnings: &mut Vec<String>) {
+    for (index, raw) in entries.iter().enumerate() {
+        let entry: ModelOverride = match serde_json::from_value(raw.clone()) {
+            Ok(entry) => entry,
+            Err(e) => {
+                warnings.push(format!("{} entry {}: {}", label, index, e));
+                continue;
+            }
+        };
+        if let Err(e) = check_override(&entry) {
+            warnings.push(format!("{} entry {} ({}): {}", label, index, entry.id, e));
+            continue;
+        }
+
+        let existing = models.iter().position(|m| m.id.eq_ignore_ascii_case(&entry.id));
+        let model = match existing {
+            Some(i) => &mut models[i],
+            None => {
+                let (input, output) = fallback_prices(models);
+                models.push(ModelInfo {
+                    id: entry.id.clone(),
+                    display_name: entry.id.clone(),
+                    matches: Vec::new(),
+                    context_window: FALLBACK_CONTEXT_WINDOW,
+                    supports_thinking: false,
+                    input_per_mtok: input,
+                    output_per_mtok: output,
+                    cache_write_per_mtok: input * CACHE_WRITE_MULTIPLIER,
+                    cache_read_per_mtok: input * CACHE_READ_MULTIPLIER,
+                    source,
+                });
+                models.last_mut().expect("just pushed")
+            }
+        };
+        model.source = source;
+        if let Some(name) = entry.display_name {
+            model.display_name = name;
+        }
+        if let Some(matches) = entry.matches {
+            model.matches = matches;
+        }
+        if let Some(window) = entry.context_window {
+            model.context_window = window;
+        }
+        if let Some(thinking) = entry.supports_thinking {
+            model.supports_thinking = thinking;
+        }
+        // Cache prices follow a changed input price unless they're given too
+        if let Some(input) = entry.input_per_mtok {
+            model.input_per_mtok = input;
+            model.cache_write_per_mtok = input * CACHE_WRITE_MULTIPLIER;
+            model.cache_read_per_mtok = input * CACHE_READ_MULTIPLIER;
+        }
+        if let Some(output) = entry.output_per_mtok {
+            model.output_per_mtok = output;
+        }
+        if let Some(price) = entry.cache_write_per_mtok {
+            model.cache_write_per_mtok = price;
+        }
+        if let Some(price) = entry.cache_read_per_mtok {
+            model.cache_read_per_mtok = price;
+        }
+    }
+}
+
+/// The `models` array of a catalog file (`{"models": [...]}`); None with a warning when the
+/// file isn't shaped like one
+fn layer_entries(bytes: &[u8], label: &str, warnings: &mut Vec<String>) -> Option<Vec<Value>> {
+    let parsed: Value = match serde_json::from_slice(bytes) {
+        Ok(value) => value,
+        Err(e) => {
+            warnings.push(format!("{} is not valid JSON and was ignored: {}", label, e));
+            return None;
+        }
+    };
+    match parsed.get("models").and_then(|m| m.as_array()) {
+        Some(models) => Some(models.clone()),
+        None => {
+            warnings.push(format!("{} has no \"models\" array and was ignored", label));
+            None
+        }
+    }
+}
+
+/// Built-ins, then the remote manifest, then the user file; later layers win
+fn build(remote: Option<&[Value]>, user: Option<&[u8]>) -> (Vec<ModelInfo>, Vec<String>) {
+    let mut models = builtin_models();
+    let mut warnings = Vec::new();
+    if let Some(entries) = remote {
+        apply_layer(&mut models, entries, CatalogSource::Remote, "Remote model catalog", &mut warnings);
+    }
+    if let Some(entries) = user.and_then(|bytes| layer_entries(bytes, "models.json", &mut warnings)) {
+        apply_layer(&mut models, &entries, CatalogSource::User, "models.json", &mut warnings);
+    }
+    (models, warnings)
+}
+
+/// The catalog entry standing for `model`: its id exactly, else the entry with the longest id
+/// or `matches` substring contained in it
+fn find<'a>(models: &'a [ModelInfo], model: &str) -> Option<&'a ModelInfo> {
+    let model = model.to_ascii_lowercase();
+    let model = model.strip_suffix(LONG_CONTEXT_SUFFIX).unwrap_or(&model);
+    if let Some(exact) = models.iter().find(|m| m.id.eq_ignore_ascii_case(model)) {
+        return Some(exact);
+    }
+    models
+        .iter()
+        .flat_map(|m| std::iter::once(&m.id).chain(&m.matches).map(move |needle| (m, needle.to_ascii_lowercase())))
+        .filter(|(_, needle)| model.contains(needle.as_str()))
+        .max_by_key(|(_, needle)| needle.len())
+        .map(|(m, _)| m)
+}
+
+fn current() -> Arc<Catalog> {
+    if let Some(catalog) = CATALOG.read().ok().and_then(|c| c.clone()) {
+        return catalog;
+    }
+    let (models, warnings) = build(None, None);
+    Arc::new(Catalog {
+        models,
+        warnings,
+        ..Default::default()
+    })
+}
+
+/// The catalog entry for a model, None when the catalog doesn't know it
+pub fn lookup(model: &str) -> Option<ModelInfo> {
+    find(&current().models, model).cloned()
+}
+
+/// Prices for a model: its entry's, or the most expensive known for an unknown model
+pub fn prices(model: Option<&str>) -> (f64, f64, f64, f64) {
+    let catalog = current();
+    match model.and_then(|model| find(&catalog.models, model)) {
+        Some(m) => (m.input_per_mtok, m.output_per_mtok, m.cache_write_per_mtok, m.cache_read_per_mtok),
+        None => {
+            let (input, output) = fallback_prices(&catalog.models);
+            (input, output, input * CACHE_WRITE_MULTIPLIER, input * CACHE_READ_MULTIPLIER)
+        }
+    }
+}
+
+/// Context window of a model in tokens, FALLBACK_CONTEXT_WINDOW for an unknown one
+pub fn context_window(model: Option<&str>) -> u64 {
+    let model = model.unwrap_or_default();
+    if model.to_ascii_lowercase().ends_with(LONG_CONTEXT_SUFFIX) {
+        return LONG_CONTEXT_WINDOW;
+    }
+    find(&current().models, model)
+        .map(|m| m.context_window)
+        .unwrap_or(FALLBACK_CONTEXT_WINDOW)
+}
+
+/// A warning when the query config names a model the catalog doesn't know, so its cost and
+/// context figures are estimates
+pub fn unknown_model_warning(config: Option<&str>) -> Option<String> {
+    let config: Value = serde_json::from_str(config?).ok()?;
+    let model = config.get("model")?.as_str()?;
+    if lookup(model).is_some() {
+        return None;
+    }
+    let (input, output, _, _) = prices(None);
+    Some(format!(
+        "Model '{}' is not in the model catalog; assuming a {}-token context window and ${}/${} per MTok. Add it to models.json for accurate figures",
+        model,
+        context_window(Some(model)),
+        input,
+        output
+    ))
+}
+
+fn app_data_file(app: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join(name))
+}
+
+/// Where the user's catalog overrides live
+pub fn user_file_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_data_file(app, "models.json")
+}
+
+fn stamp_of(path: &std::path::Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Rebuild the catalog from the cached remote manifest (when it came from the configured URL)
+/// and the user file
+async fn rebuild(app: &tauri::AppHandle) -> Result<(), String> {
+    let url = settings::load(app, &app.state::<AppState>().settings).await?.model_catalog_url.clone();
+    let remote = match url {
+        Some(url) => Some(REMOTE_FILE.read(app, app_data_file(app, "model-catalog-remote.json")?).await?)
+            .filter(|cache| cache.url == url && cache.fetched_at > 0),
+        None => None,
+    };
+    let user_path = user_file_path(app)?;
+    let user_stamp = stamp_of(&user_path);
+    let user = match user_stamp {
+        Some(_) => tokio::fs::read(&user_path).await.ok(),
+        None => None,
+    };
+
+    let (models, warnings) = build(remote.as_ref().map(|r| r.models.as_slice()), user.as_deref());
+    for warning in &warnings {
+        eprintln!("[mensa] {}", warning);
+    }
+    let catalog = Arc::new(Catalog {
+        models,
+        warnings,
+        user_stamp,
+        remote_fetched_at: remote.map(|r| r.fetched_at),
+    });
+    if let Ok(mut slot) = CATALOG.write() {
+        *slot = Some(catalog);
+    }
+    Ok(())
+}
+
+/// Rebuild when the user file changed since the catalog was built
+pub async fn reload_if_changed(app: &tauri::AppHandle) {
+    let loaded = CATALOG.read().ok().and_then(|c| c.clone());
+    let stamp = user_file_path(app).ok().and_then(|path| stamp_of(&path));
+    if loaded.is_some_and(|catalog| catalog.user_stamp == stamp) {
+        return;
+    }
+    if let Err(e) = rebuild(app).await {
+        eprintln!("[mensa] Failed to load the model catalog: {}", e);
+    }
+}
+
+async fn fetch_remote(app: &tauri::AppHandle, url: &str) -> Result<(), String> {
+    let client = proxy::http_client(proxy::resolve(app).await?.as_ref(), REMOTE_TIMEOUT)?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch the model catalog: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch the model catalog: HTTP {}", response.status()));
+    }
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to fetch the model catalog: {}", e))?;
+    if body.len() > MAX_REMOTE_BYTES {
+        return Err(format!("Model catalog at {} is larger than {} bytes", url, MAX_REMOTE_BYTES));
+    }
+    let mut warnings = Vec::new();
+    let models = layer_entries(&body, "Remote model catalog", &mut warnings)
+        .ok_or_else(|| warnings.join("; "))?;
+
+    let cache = RemoteCache {
+        url: url.to_string(),
+        fetched_at: history::now_secs(),
+        models,
+    };
+    REMOTE_FILE.write(app, app_data_file(app, "model-catalog-remote.json")?, &cache).await?;
+    rebuild(app).await
+}
+
+/// Fetch the configured remote manifest in the background, unless one is already being fetched
+pub fn spawn_refresh(app: &tauri::AppHandle) {
+    if REFRESHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let url = match settings::load(&app, &app.state::<AppState>().settings).await {
+            Ok(settings) => settings.model_catalog_url.clone(),
+            Err(e) => {
+                eprintln!("[mensa] {}", e);
+                None
+            }
+        };
+        let result = match url {
+            Some(url) => fetch_remote(&app, &url).await,
+            // Dropping the URL drops the remote layer
+            None => rebuild(&app).await,
+        };
+        if let Err(e) = result {
+            eprintln!("[mensa] {}", e);
+        }
+        REFRESHING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Load the catalog at startup and refresh the remote manifest if it's due
+pub async fn init(app: tauri::AppHandle) {
+    if let Err(e) = rebuild(&app).await {
+        eprintln!("[mensa] Failed to load the model catalog: {}", e);
+    }
+    refresh_if_stale(&app).await;
+}
+
+async fn refresh_if_stale(app: &tauri::AppHandle) {
+    let configured = settings::load(app, &app.state::<AppState>().settings)
+        .await
+        .is_ok_and(|s| s.model_catalog_url.is_some());
+    let fetched_at = current().remote_fetched_at;
+    if configured && fetched_at.is_none_or(|at| history::now_secs() - at >= REMOTE_MAX_AGE_SECS) {
+        spawn_refresh(app);
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// The model catalog, with warnings about skipped overrides. A stale remote manifest is
+/// refreshed in the background; this never waits for it.
+#[tauri::command]
+pub async fn list_models(app: tauri::AppHandle) -> Result<ModelList, String> {
+    reload_if_changed(&app).await;
+    refresh_if_stale(&app).await;
+    let catalog = current();
+    Ok(ModelList {
+        models: catalog.models.clone(),
+        warnings: catalog.warnings.clone(),
+        remote_fetched_at: catalog.remote_fetched_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    fn remote() -> Vec<Value> {
+        let mut warnings = Vec::new();
+        let entries = layer_entries(fixture("models/remote.json").as_bytes(), "Remote model catalog", &mut warnings).unwrap();
+        assert!(warnings.is_empty());
+        entries
+    }
+
+    fn model<'a>(models: &'a [ModelInfo], id: &str) -> &'a ModelInfo {
+        models.iter().find(|m| m.id == id).unwrap_or_else(|| panic!("{} not in the catalog", id))
+    }
+
+    /// Input, output, cache write and cache read prices
+    fn prices_of(m: &ModelInfo) -> [f64; 4] {
+        [m.input_per_mtok, m.output_per_mtok, m.cache_write_per_mtok, m.cache_read_per_mtok]
+    }
+
+    fn assert_prices(m: &ModelInfo, expected: [f64; 4]) {
+        let actual = prices_of(m);
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-9), "{}: {:?} != {:?}", m.id, actual, expected);
+    }
+
+    #[test]
+    fn later_layers_win_field_by_field() {
+        let user = fixture("models/user.json");
+        let (models, warnings) = build(Some(&remote()), Some(user.as_bytes()));
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(&ids[6..], ["claude-sonnet-5", "claude-preview", "internal-llm"]);
+
+        // Remote lowered the prices, the user (matching the id in any case) lowered input again;
+        // cache prices follow input, output keeps the remote's
+        let sonnet = model(&models, "claude-sonnet-4-5");
+        assert_eq!((sonnet.display_name.as_str(), sonnet.source), ("Sonnet (team rate)", CatalogSource::User));
+        assert_prices(sonnet, [2.0, 12.5, 2.5, 0.2]);
+
+        // Only the remote touched it; an explicit cache price isn't recomputed
+        let opus = model(&models, "claude-opus-4-5");
+        assert_eq!((opus.context_window, opus.source), (500_000, CatalogSource::Remote));
+        assert_prices(opus, [5.0, 25.0, 6.25, 0.4]);
+
+        let sonnet_5 = model(&models, "claude-sonnet-5");
+        assert_eq!(sonnet_5.source, CatalogSource::User);
+        assert!(sonnet_5.supports_thinking);
+        assert_prices(sonnet_5, [3.0, 15.0, 3.5, 0.3]);
+
+        // New ids without prices get the most expensive known, and the fallback window
+        let preview = model(&models, "claude-preview");
+        assert_eq!((preview.context_window, preview.supports_thinking), (FALLBACK_CONTEXT_WINDOW, false));
+        assert_prices(preview, [15.0, 75.0, 18.75, 1.5]);
+
+        let internal = model(&models, "internal-llm");
+        assert_eq!((internal.context_window, internal.source), (32_000, CatalogSource::User));
+        assert_prices(internal, [0.0; 4]);
+
+        assert_eq!(model(&models, "claude-3-haiku").source, CatalogSource::Builtin);
+    }
+
+    #[test]
+    fn malformed_overrides_are_skipped_with_a_warning() {
+        let user = fixture("models/user_malformed.json");
+        let (models, warnings) = build(None, Some(user.as_bytes()));
+        let expected_prefixes = [
+            "models.json entry 1: unknown field `inputPerMTok`",
+            "models.json entry 2: invalid type: string \"large\", expected u64",
+            "models.json entry 3: missing field `id`",
+            "models.json entry 4 (  ): id must not be empty",
+            "models.json entry 5 (claude-opus-4-1): contextWindow must be at least 1",
+            "models.json entry 6 (claude-3-haiku): outputPerMtok must be a non-negative number",
+            "models.json entry 7 (claude-3-5-haiku): matches must not contain empty strings",
+            "models.json entry 8: invalid type: string \"claude-sonnet-5\"",
+        ];
+        assert_eq!(warnings.len(), expected_prefixes.len(), "{:#?}", warnings);
+        for (warning, prefix) in warnings.iter().zip(expected_prefixes) {
+            assert!(warning.starts_with(prefix), "{} doesn't start with {}", warning, prefix);
+        }
+
+        // The valid entries around them still apply; the skipped ones change nothing
+        let haiku = model(&models, "claude-haiku-4-5");
+        assert_eq!(haiku.source, CatalogSource::User);
+        assert_prices(haiku, [0.9, 4.5, 1.125, 0.09]);
+        let builtins = builtin_models();
+        for id in ["claude-sonnet-4-5", "claude-opus-4-5", "claude-opus-4-1", "claude-3-haiku", "claude-3-5-haiku"] {
+            let (now, before) = (model(&models, id), model(&builtins, id));
+            assert_eq!((prices_of(now), now.context_window, &now.matches), (prices_of(before), before.context_window, &before.matches));
+            assert_eq!(now.source, CatalogSource::Builtin);
+        }
+        assert_eq!(models.len(), builtins.len());
+    }
+
+    #[test]
+    fn unreadable_user_files_leave_the_lower_layers() {
+        let files: [(&[u8], &str); 3] = [
+            (b"{\"models\": [", "models.json is not valid JSON and was ignored: "),
+            (b"{\"model\": []}", "models.json has no \"models\" array and was ignored"),
+            (b"[{\"id\": \"x\"}]", "models.json has no \"models\" array and was ignored"),
+        ];
+        for (content, warning) in files {
+            let (models, warnings) = build(Some(&remote()), Some(content));
+            assert_eq!(warnings.len(), 1);
+            assert!(warnings[0].starts_with(warning), "{}", warnings[0]);
+            assert_eq!(model(&models, "claude-sonnet-4-5").source, CatalogSource::Remote);
+        }
+    }
+
+    #[test]
+    fn lookup_prefers_exact_ids_then_the_longest_match() {
+        let user = fixture("models/user.json");
+        let (models, _) = build(Some(&remote()), Some(user.as_bytes()));
+        let found = |name: &str| find(&models, name).map(|m| m.id.as_str());
+        assert_eq!(found("claude-sonnet-4-5-20250929"), Some("claude-sonnet-4-5"));
+        assert_eq!(found("us.anthropic.claude-sonnet-5-v1:0"), Some("claude-sonnet-5"));
+        assert_eq!(found("sonnet"), Some("claude-sonnet-4-5"));
+        assert_eq!(found("claude-opus-4-5[1m]"), Some("claude-opus-4-5"));
+        assert_eq!(found("claude-opus-4-20250514"), Some("claude-opus-4-1"));
+        assert_eq!(found("INTERNAL-LLM"), Some("internal-llm"));
+        assert_eq!(found("team-internal-7b"), Some("internal-llm"));
+        assert_eq!(found("claude-haiku-4-5-20251001"), Some("claude-haiku-4-5"));
+        assert_eq!(found("claude-3-haiku-20240307"), Some("claude-3-haiku"));
+        assert_eq!(found("gpt-4o"), None);
+    }
+}
@@ -0,0 +1,525 @@
+// mensa - Workspace Overview Module
+// A quick fingerprint of a workspace for its header: languages by size, largest directories, owners
+
+use crate::{file_index, git, pr_context, workspace, AppState};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// How long the overview may wait for a cold file index
+const INDEX_BUDGET: Duration = Duration::from_secs(1);
+
+/// How long sizing the indexed files may take; what's left over is reported as partial
+const SIZE_BUDGET: Duration = Duration::from_secs(2);
+
+/// Files sized between deadline checks
+const DEADLINE_CHECK_FILES: usize = 256;
+
+/// Languages listed by name; the rest are summed into `other_percent`
+const TOP_LANGUAGES: usize = 5;
+
+/// Top-level directories listed by size
+const TOP_DIRECTORIES: usize = 6;
+
+/// Files per top-level path checked against CODEOWNERS to find who owns it
+const OWNERSHIP_SAMPLE: usize = 200;
+
+/// Language of a file by extension; files with none of these don't count towards the languages
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("Rust", &["rs"]),
+    ("TypeScript", &["ts", "tsx", "mts", "cts"]),
+    ("JavaScript", &["js", "jsx", "mjs", "cjs"]),
+    ("Svelte", &["svelte"]),
+    ("Vue", &["vue"]),
+    ("Python", &["py", "pyi"]),
+    ("Go", &["go"]),
+    ("Java", &["java"]),
+    ("Kotlin", &["kt", "kts"]),
+    ("Swift", &["swift"]),
+    ("C", &["c", "h"]),
+    ("C++", &["cc", "cpp", "cxx", "hpp", "hh", "hxx"]),
+    ("C#", &["cs"]),
+    ("Objective-C", &["m", "mm"]),
+    ("Ruby", &["rb"]),
+    ("PHP", &["php"]),
+    ("Scala", &["scala"]),
+    ("Elixir", &["ex", "exs"]),
+    ("Erlang", &["erl", "hrl"]),
+    ("Haskell", &["hs"]),
+    ("OCaml", &["ml", "mli"]),
+    ("Clojure", &["clj", "cljs", "cljc"]),
+    ("Dart", &["dart"]),
+    ("Lua", &["lua"]),
+    ("Zig", &["zig"]),
+    ("Nim", &["nim"]),
+    ("R", &["r"]),
+    ("Julia", &["jl"]),
+    ("Shell", &["sh", "bash", "zsh", "fish"]),
+    ("PowerShell", &["ps1", "psm1"]),
+    ("SQL", &["sql"]),
+    ("HTML", &["html", "htm"]),
+    ("CSS", &["css", "scss", "sass", "less"]),
+    ("Markdown", &["md", "mdx"]),
+    ("Protocol Buffers", &["proto"]),
+    ("GraphQL", &["graphql", "gql"]),
+    ("Terraform", &["tf"]),
+    ("Nix", &["nix"]),
+];
+
+/// Languages of files known by name rather than extension
+const LANGUAGE_FILE_NAMES: &[(&str, &str)] = &[
+    ("Dockerfile", "Dockerfile"),
+    ("Makefile", "Makefile"),
+    ("CMakeLists.txt", "CMake"),
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageShare {
+    pub language: String,
+    pub bytes: u64,
+    pub files: usize,
+    /// Of the bytes in recognized languages, one decimal
+    pub percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectorySize {
+    /// Top-level directory name
+    pub path: String,
+    pub bytes: u64,
+    pub files: usize,
+}
+
+/// Owners of a top-level path: those of most of its files
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathOwners {
+    pub path: String,
+    pub owners: Vec<String>,
+}
+
+/// One owner and the top-level paths they own, for a chip in the header
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnerChip {
+    pub owner: String,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceOverview {
+    /// The largest languages by bytes, at most TOP_LANGUAGES
+    pub languages: Vec<LanguageShare>,
+    /// Share of the recognized bytes in languages not listed
+    pub other_percent: f64,
+    pub top_directories: Vec<DirectorySize>,
+    /// Files in the workspace's file index (gitignored files excluded)
+    pub file_count: usize,
+    pub total_bytes: u64,
+    /// Top-level paths by owner; None when the repository has no CODEOWNERS
+    pub ownership: Option<Vec<PathOwners>>,
+    /// Owners with the most top-level paths first
+    pub owners: Vec<OwnerChip>,
+    /// HEAD commit the overview was computed at; None outside a repository
+    pub head: Option<String>,
+    /// The file index was still being built or the time budget ran out; sizes cover what was reached
+    pub partial: bool,
+    /// The workspace has more files than are indexed
+    pub truncated: bool,
+    /// Served from the cache for this HEAD
+    pub cached: bool,
+}
+
+/// Complete overviews by workspace root, valid while HEAD stays where it was
+#[derive(Default)]
+pub struct OverviewCache {
+    entries: Mutex<HashMap<PathBuf, (String, Arc<WorkspaceOverview>)>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn head_commit(root: &Path) -> Option<String> {
+    let repo = git::open_repo(&root.to_string_lossy()).ok()?;
+    let head = repo.head().ok()?.target()?;
+    Some(head.to_string())
+}
+
+fn language_of(path: &str) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if let Some((_, language)) = LANGUAGE_FILE_NAMES.iter().find(|(file, _)| *file == name) {
+        return Some(*language);
+    }
+    let (_, extension) = name.rsplit_once('.')?;
+    let extension = extension.to_ascii_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        .map(|(language, _)| *language)
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    (part as f64 * 1000.0 / total as f64).round() / 10.0
+}
+
+/// Owners of each top-level path by the most common owner set among a sample of its files
+fn top_level_ownership(rules: &[pr_context::CodeownersRule], paths: &[String]) -> Vec<PathOwners> {
+    let mut samples: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for path in paths {
+        let top = path.split('/').next().unwrap_or(path);
+        let sample = samples.entry(top).or_default();
+        if sample.len() < OWNERSHIP_SAMPLE {
+            sample.push(path);
+        }
+    }
+
+    samples
+        .into_iter()
+        .filter_map(|(top, files)| {
+            let mut counts: HashMap<&[String], usize> = HashMap::new();
+            for file in files {
+                if let Some(owners) = pr_context::owners_of(rules, file) {
+                    *counts.entry(owners).or_default() += 1;
+                }
+            }
+            let (owners, _) = counts
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))?;
+            Some(PathOwners {
+                path: top.to_string(),
+                owners: owners.to_vec(),
+            })
+        })
+        .collect()
+}
+
+fn owner_chips(ownership: &[PathOwners]) -> Vec<OwnerChip> {
+    let mut by_owner: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for entry in ownership {
+        for owner in &entry.owners {
+            by_owner.entry(owner).or_default().push(entry.path.clone());
+        }
+    }
+    let mut chips: Vec<OwnerChip> = by_owner
+        .into_iter()
+        .map(|(owner, paths)| OwnerChip {
+            owner: owner.to_string(),
+            paths,
+        })
+        .collect();
+    chips.sort_by(|a, b| b.paths.len().cmp(&a.paths.len()).then_with(|| a.owner.cmp(&b.owner)));
+    chips
+}
+
+/// Size the indexed files (until `budget` runs out) and summarize them
+fn compute(root: &Path, snapshot: file_index::IndexSnapshot, head: Option<String>, budget: Duration) -> WorkspaceOverview {
+    let deadline = Instant::now() + budget;
+    let mut out_of_time = false;
+    let mut languages: HashMap<&'static str, (u64, usize)> = HashMap::new();
+    let mut directories: HashMap<&str, (u64, usize)> = HashMap::new();
+    let mut total_bytes = 0;
+
+    for (i, path) in snapshot.paths.iter().enumerate() {
+        if i % DEADLINE_CHECK_FILES == 0 && Instant::now() >= deadline {
+            out_of_time = true;
+            break;
+        }
+        let Ok(metadata) = std::fs::symlink_metadata(root.join(path)) else {
+            continue;
+        };
+        let bytes = metadata.len();
+        total_bytes += bytes;
+        if let Some(language) = language_of(path) {
+            let entry = languages.entry(language).or_default();
+            entry.0 += bytes;
+            entry.1 += 1;
+        }
+        if let Some((top, _)) = path.split_once('/') {
+            let entry = directories.entry(top).or_default();
+            entry.0 += bytes;
+            entry.1 += 1;
+        }
+    }
+
+    let recognized: u64 = languages.values().map(|(bytes, _)| bytes).sum();
+    let mut languages: Vec<LanguageShare> = languages
+        .into_iter()
+        .map(|(language, (bytes, files))| LanguageShare {
+            language: language.to_string(),
+            bytes,
+            files,
+            percent: percent(bytes, recognized),
+        })
+        .collect();
+    languages.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.language.cmp(&b.language)));
+    let other_bytes: u64 = languages.iter().skip(TOP_LANGUAGES).map(|l| l.bytes).sum();
+    languages.truncate(TOP_LANGUAGES);
+
+    let mut top_directories: Vec<DirectorySize> = directories
+        .into_iter()
+        .map(|(path, (bytes, files))| DirectorySize {
+            path: path.to_string(),
+            bytes,
+            files,
+        })
+        .collect();
+    top_directories.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+    top_directories.truncate(TOP_DIRECTORIES);
+
+    // CODEOWNERS only means something in a repository
+    let ownership = head
+        .is_some()
+        .then(|| pr_context::read_codeowners(root))
+        .flatten()
+        .map(|rules| top_level_ownership(&rules, &snapshot.paths));
+    let owners = ownership.as_deref().map(owner_chips).unwrap_or_default();
+
+    WorkspaceOverview {
+        languages,
+        other_percent: percent(other_bytes, recognized),
+        top_directories,
+        file_count: snapshot.paths.len(),
+        total_bytes,
+        ownership,
+        owners,
+        head,
+        partial: out_of_time || !snapshot.complete,
+        truncated: snapshot.truncated,
+        cached: false,
+    }
+}
+
+impl OverviewCache {
+    /// The overview computed at `head`, marked as cached
+    fn get(&self, root: &Path, head: &str) -> Option<WorkspaceOverview> {
+        let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (cached_head, overview) = entries.get(root)?;
+        (cached_head == head).then(|| WorkspaceOverview {
+            cached: true,
+            ..overview.as_ref().clone()
+        })
+    }
+
+    /// Keep an overview for its HEAD; partial ones and those outside a repository aren't kept
+    fn store(&self, root: PathBuf, overview: &WorkspaceOverview) {
+        let Some(head) = overview.head.clone().filter(|_| !overview.partial) else {
+            return;
+        };
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.insert(root, (head, Arc::new(overview.clone())));
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Languages by size, the largest top-level directories, the file count and (with CODEOWNERS)
+/// who owns each top-level path. Runs on the cached file index under a time budget; a complete
+/// result is cached until HEAD moves. Directories outside git get everything but ownership.
+#[tauri::command]
+pub async fn workspace_overview(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+) -> Result<WorkspaceOverview, String> {
+    let root = workspace::canonical_dir(&working_dir)?;
+    let head_root = root.clone();
+    let head = tokio::task::spawn_blocking(move || head_commit(&head_root))
+        .await
+        .map_err(|e| format!("Workspace overview failed: {}", e))?;
+
+    if let Some(overview) = head.as_deref().and_then(|head| state.overviews.get(&root, head)) {
+        return Ok(overview);
+    }
+
+    let snapshot = file_index::snapshot(&app, &root, INDEX_BUDGET).await;
+    let compute_root = root.clone();
+    let overview = tokio::task::spawn_blocking(move || compute(&compute_root, snapshot, head, SIZE_BUDGET))
+        .await
+        .map_err(|e| format!("Workspace overview failed: {}", e))?;
+
+    state.overviews.store(root, &overview);
+    Ok(overview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_all, repo, write};
+
+    /// A small polyglot workspace: file sizes by path, a gitignored bundle that mustn't count,
+    /// and CODEOWNERS splitting the top-level paths between teams
+    fn polyglot(root: &Path) {
+        let files: [(&str, usize); 11] = [
+            ("src/main.rs", 4000),
+            ("src/lib.rs", 2000),
+            ("web/app.ts", 3000),
+            ("web/index.html", 500),
+            ("web/style.css", 500),
+            ("scripts/build.py", 1000),
+            ("scripts/deploy.sh", 400),
+            ("docs/guide.md", 300),
+            ("Dockerfile", 200),
+            ("Makefile", 100),
+            ("README", 50),
+        ];
+        for (path, bytes) in files {
+            write(root, path, "x".repeat(bytes));
+        }
+        write(root, ".gitignore", "dist/\n");
+        write(root, "dist/bundle.js", "x".repeat(50_000));
+        write(root, ".github/CODEOWNERS", "* @core\n/web/ @frontend\n/scripts/ @ops @core\ndocs/ @writers\n");
+    }
+
+    fn overview_of(root: &Path, head: Option<String>) -> WorkspaceOverview {
+        compute(root, file_index::walked(root), head, SIZE_BUDGET)
+    }
+
+    fn languages(overview: &WorkspaceOverview) -> Vec<(&str, u64, usize, f64)> {
+        overview.languages.iter().map(|l| (l.language.as_str(), l.bytes, l.files, l.percent)).collect()
+    }
+
+    fn directories(overview: &WorkspaceOverview) -> Vec<(&str, u64, usize)> {
+        overview.top_directories.iter().map(|d| (d.path.as_str(), d.bytes, d.files)).collect()
+    }
+
+    #[test]
+    fn polyglot_repository_is_fingerprinted() {
+        let (dir, repo) = repo();
+        polyglot(dir.path());
+        commit_all(&repo, "polyglot");
+        let head = head_commit(dir.path());
+        assert!(head.is_some());
+
+        let overview = overview_of(dir.path(), head.clone());
+        // 12000 recognized bytes; Shell, Markdown, Dockerfile and Makefile share the rest
+        assert_eq!(
+            languages(&overview),
+            [
+                ("Rust", 6000, 2, 50.0),
+                ("TypeScript", 3000, 1, 25.0),
+                ("Python", 1000, 1, 8.3),
+                ("CSS", 500, 1, 4.2),
+                ("HTML", 500, 1, 4.2),
+            ]
+        );
+        assert_eq!(overview.other_percent, 8.3);
+
+        let codeowners = std::fs::metadata(dir.path().join(".github/CODEOWNERS")).unwrap().len();
+        assert_eq!(
+            directories(&overview),
+            [("src", 6000, 2), ("web", 4000, 3), ("scripts", 1400, 2), ("docs", 300, 1), (".github", codeowners, 1)]
+        );
+        // The gitignored bundle is neither indexed nor sized
+        assert_eq!(overview.file_count, 13);
+        assert_eq!(overview.total_bytes, 12_050 + 6 + codeowners);
+        assert_eq!(overview.head, head);
+        assert!(!overview.partial && !overview.truncated && !overview.cached);
+
+        let ownership: Vec<(&str, Vec<&str>)> = overview
+            .ownership
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|o| (o.path.as_str(), o.owners.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            ownership,
+            [
+                (".github", vec!["@core"]),
+                (".gitignore", vec!["@core"]),
+                ("Dockerfile", vec!["@core"]),
+                ("Makefile", vec!["@core"]),
+                ("README", vec!["@core"]),
+                ("docs", vec!["@writers"]),
+                ("scripts", vec!["@ops", "@core"]),
+                ("src", vec!["@core"]),
+                ("web", vec!["@frontend"]),
+            ]
+        );
+        let chips: Vec<(&str, usize)> = overview.owners.iter().map(|c| (c.owner.as_str(), c.paths.len())).collect();
+        assert_eq!(chips, [("@core", 7), ("@frontend", 1), ("@ops", 1), ("@writers", 1)]);
+        assert_eq!(overview.owners[2].paths, ["scripts"]);
+    }
+
+    #[test]
+    fn directory_outside_git_gets_languages_and_layout_but_no_owners() {
+        let dir = tempfile::tempdir().unwrap();
+        polyglot(dir.path());
+        let head = head_commit(dir.path());
+        assert_eq!(head, None);
+
+        let overview = overview_of(dir.path(), head);
+        assert_eq!(overview.languages[0].language, "Rust");
+        assert_eq!(overview.languages.len(), TOP_LANGUAGES);
+        assert_eq!(overview.top_directories[0].path, "src");
+        // .gitignore is honoured without a repository too
+        assert_eq!(overview.file_count, 13);
+        assert!(overview.ownership.is_none() && overview.owners.is_empty());
+    }
+
+    #[test]
+    fn running_out_of_time_reports_a_partial_overview() {
+        let dir = tempfile::tempdir().unwrap();
+        polyglot(dir.path());
+
+        let overview = compute(dir.path(), file_index::walked(dir.path()), None, Duration::ZERO);
+        assert!(overview.partial);
+        assert_eq!(overview.file_count, 13);
+        assert_eq!(overview.total_bytes, 0);
+
+        // An index still being built is partial as well
+        let mut snapshot = file_index::walked(dir.path());
+        snapshot.complete = false;
+        assert!(compute(dir.path(), snapshot, None, SIZE_BUDGET).partial);
+    }
+
+    #[test]
+    fn overview_is_cached_until_head_moves() {
+        let (dir, repo) = repo();
+        polyglot(dir.path());
+        commit_all(&repo, "polyglot");
+        let first = head_commit(dir.path()).unwrap();
+        let cache = OverviewCache::default();
+        let root = dir.path().to_path_buf();
+
+        assert!(cache.get(&root, &first).is_none());
+        cache.store(root.clone(), &overview_of(&root, Some(first.clone())));
+        let hit = cache.get(&root, &first).unwrap();
+        assert!(hit.cached);
+        assert_eq!(hit.languages[0].bytes, 6000);
+
+        // A new commit misses, and its overview replaces the old one
+        write(&root, "src/extra.rs", "x".repeat(1000));
+        commit_all(&repo, "more rust");
+        let second = head_commit(&root).unwrap();
+        assert!(cache.get(&root, &second).is_none());
+        cache.store(root.clone(), &overview_of(&root, Some(second.clone())));
+        assert_eq!(cache.get(&root, &second).unwrap().languages[0].bytes, 7000);
+        assert!(cache.get(&root, &first).is_none());
+
+        // Partial overviews and those outside a repository are never kept
+        let other = tempfile::tempdir().unwrap();
+        polyglot(other.path());
+        let mut partial = overview_of(other.path(), Some(first.clone()));
+        partial.partial = true;
+        cache.store(other.path().to_path_buf(), &partial);
+        cache.store(other.path().to_path_buf(), &overview_of(other.path(), None));
+        assert!(cache.get(other.path(), &first).is_none());
+    }
+}
@@ -0,0 +1,395 @@
+// mensa - Pagination Module
+// One page envelope for every list command, continued through opaque, tamper-checked cursors
+
+use crate::{fsutil, history, permissions};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Items per page when the caller names no limit
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// Largest page a caller may ask for
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// How long a cursor stays valid after it was issued
+const CURSOR_TTL_SECS: i64 = 60 * 60;
+
+/// Format of the cursor payload; older cursors are refused after it changes
+const CURSOR_VERSION: u32 = 1;
+
+/// Bytes of the HMAC-SHA256 tag kept in a cursor
+const SIGNATURE_BYTES: usize = 16;
+
+/// One page of a list. `next_cursor` continues the same walk (same list and filters) where this
+/// page ended; None on the last page.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items in the whole list, when the source can tell without reading it all
+    pub total: Option<u64>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+/// Error of the paged list commands
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum PageError {
+    /// The cursor wasn't issued by this mensa, or was altered
+    InvalidCursor { message: String },
+    /// The cursor outlived CURSOR_TTL_SECS or a restart of mensa; start the walk again
+    ExpiredCursor { message: String },
+    /// The cursor belongs to another list, or to the same list with other filters
+    CursorMismatch { message: String },
+    /// mensa's user may not read `path`; `suggested_fix` is the command that fixes it
+    PermissionDenied {
+        path: String,
+        suggested_fix: String,
+        message: String,
+    },
+    Failed { message: String },
+}
+
+impl From<String> for PageError {
+    fn from(message: String) -> Self {
+        permissions::SessionError::from(message).into()
+    }
+}
+
+impl From<permissions::SessionError> for PageError {
+    fn from(error: permissions::SessionError) -> Self {
+        match error {
+            permissions::SessionError::PermissionDenied {
+                path,
+                suggested_fix,
+                message,
+            } => PageError::PermissionDenied {
+                path,
+                suggested_fix,
+                message,
+            },
+            other => PageError::Failed {
+                message: other.to_string(),
+            },
+        }
+    }
+}
+
+/// What a cursor carries: the walk it continues and where that walk stood
+#[derive(Debug, Serialize, Deserialize)]
+struct CursorPayload {
+    #[serde(rename = "v")]
+    version: u32,
+    /// Name of the list, e.g. "sessions"
+    #[serde(rename = "l")]
+    list: String,
+    /// Hash of the filters the walk started with
+    #[serde(rename = "f")]
+    filters: String,
+    /// Process that issued the cursor
+    #[serde(rename = "e")]
+    epoch: String,
+    #[serde(rename = "t")]
+    issued_at: i64,
+    /// List-specific position (last key seen, git tip and offset, ...)
+    #[serde(rename = "p")]
+    position: Value,
+}
+
+/// The cursors of one list walk: the list's name and the filters it was started with.
+/// A cursor decodes only under the scope that encoded it.
+pub struct CursorScope {
+    list: &'static str,
+    filters: String,
+}
+
+/// Signing key and epoch of this process; cursors from before a restart are expired
+struct CursorKey {
+    secret: [u8; 32],
+    epoch: String,
+}
+
+static CURSOR_KEY: OnceLock<CursorKey> = OnceLock::new();
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl CursorKey {
+    fn generate() -> Self {
+        let mut secret = [0u8; 32];
+        secret[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+        secret[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+        CursorKey {
+            secret,
+            epoch: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    fn mac(&self, encoded_payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC takes keys of any length");
+        mac.update(encoded_payload.as_bytes());
+        mac
+    }
+
+    fn sign(&self, encoded_payload: &str) -> String {
+        BASE64_URL.encode(&self.mac(encoded_payload).finalize().into_bytes()[..SIGNATURE_BYTES])
+    }
+
+    /// Whether `signature` is this key's tag of the payload, compared in constant time
+    fn verify(&self, encoded_payload: &str, signature: &str) -> bool {
+        match BASE64_URL.decode(signature) {
+            Ok(tag) if tag.len() == SIGNATURE_BYTES => self.mac(encoded_payload).verify_truncated_left(&tag).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+fn cursor_key() -> &'static CursorKey {
+    CURSOR_KEY.get_or_init(CursorKey::generate)
+}
+
+fn invalid(message: &str) -> PageError {
+    PageError::InvalidCursor {
+        message: message.to_string(),
+    }
+}
+
+/// The page size a caller asked for, DEFAULT_PAGE_SIZE when none
+pub fn page_size(limit: Option<usize>) -> Result<usize, PageError> {
+    match limit {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(limit) if (1..=MAX_PAGE_SIZE).contains(&limit) => Ok(limit),
+        Some(limit) => Err(format!("Page size must be between 1 and {}, got {}", MAX_PAGE_SIZE, limit).into()),
+    }
+}
+
+/// One page of `items`, which are sorted by a unique `key`, descending: the first `size` items
+/// whose key comes after `after` (from the top without one), and the key to continue from
+/// when more remain. Items added above the cursor while a walk runs don't shift its pages.
+pub fn keyset_page<T, K: Ord>(items: Vec<T>, key: impl Fn(&T) -> K, after: Option<&K>, size: usize) -> (Vec<T>, Option<K>) {
+    let mut rest = items.into_iter().filter(|item| after.is_none_or(|after| key(item) < *after));
+    let page: Vec<T> = rest.by_ref().take(size).collect();
+    let next = match rest.next() {
+        Some(_) => page.last().map(&key),
+        None => None,
+    };
+    (page, next)
+}
+
+impl CursorScope {
+    pub fn new(list: &'static str, filters: &[&str]) -> Self {
+        CursorScope {
+            list,
+            filters: fsutil::sha256_hex(filters.join("\u{1f}").as_bytes())[..16].to_string(),
+        }
+    }
+
+    /// An opaque cursor continuing this walk at `position`
+    pub fn encode<P: Serialize>(&self, position: &P) -> String {
+        self.encode_with(cursor_key(), history::now_secs(), position)
+    }
+
+    fn encode_with<P: Serialize>(&self, key: &CursorKey, issued_at: i64, position: &P) -> String {
+        let payload = CursorPayload {
+            version: CURSOR_VERSION,
+            list: self.list.to_string(),
+            filters: self.filters.clone(),
+            epoch: key.epoch.clone(),
+            issued_at,
+            position: serde_json::to_value(position).unwrap_or(Value::Null),
+        };
+        let encoded = BASE64_URL.encode(serde_json::to_vec(&payload).unwrap_or_default());
+        let signature = key.sign(&encoded);
+        format!("{}.{}", encoded, signature)
+    }
+
+    /// The position a cursor of this walk stands at; None without a cursor (the first page).
+    /// Refuses altered cursors, cursors of other walks and expired ones.
+    pub fn decode<P: DeserializeOwned>(&self, cursor: Option<&str>) -> Result<Option<P>, PageError> {
+        self.decode_with(cursor_key(), history::now_secs(), cursor)
+    }
+
+    fn decode_with<P: DeserializeOwned>(&self, key: &CursorKey, now: i64, cursor: Option<&str>) -> Result<Option<P>, PageError> {
+        let Some(cursor) = cursor else {
+            return Ok(None);
+        };
+        let (encoded, signature) = cursor.split_once('.').ok_or_else(|| invalid("The cursor is malformed"))?;
+        let bytes = BASE64_URL.decode(encoded).map_err(|_| invalid("The cursor is malformed"))?;
+        let payload: CursorPayload = serde_json::from_slice(&bytes).map_err(|_| invalid("The cursor is malformed"))?;
+
+        if !key.verify(encoded, signature) {
+            // Signed by an earlier run of mensa (the key changes on restart), or altered
+            if payload.epoch != key.epoch {
+                return Err(PageError::ExpiredCursor {
+                    message: "The cursor was issued before mensa restarted; start from the first page".to_string(),
+                });
+            }
+            return Err(invalid("The cursor was altered"));
+        }
+        if payload.version != CURSOR_VERSION {
+            return Err(PageError::ExpiredCursor {
+                message: "The cursor is from an older version of mensa; start from the first page".to_string(),
+            });
+        }
+        if payload.list != self.list || payload.filters != self.filters {
+            return Err(PageError::CursorMismatch {
+                message: format!("The cursor belongs to another list or other filters than this {} list", self.list),
+            });
+        }
+        if now - payload.issued_at > CURSOR_TTL_SECS {
+            return Err(PageError::ExpiredCursor {
+                message: "The cursor has expired; start from the first page".to_string(),
+            });
+        }
+        serde_json::from_value(payload.position)
+            .map(Some)
+            .map_err(|_| invalid("The cursor's position doesn't fit this list"))
+    }
+
+    /// A page whose walk continues at `next`, if anything is left
+    pub fn page<T, P: Serialize>(&self, items: Vec<T>, total: Option<u64>, next: Option<P>) -> Page<T> {
+        let next_cursor = next.map(|position| self.encode(&position));
+        Page {
+            items,
+            total,
+            has_more: next_cursor.is_some(),
+            next_cursor,
+        }
+    }
+}
+
+/// Filters that name a path compare by their text; keep the caller's spelling out of the scope
+pub fn path_filter(path: &str) -> String {
+    std::fs::canonicalize(Path::new(path))
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn scope() -> CursorScope {
+        CursorScope::new("sessions", &["/repo", "main"])
+    }
+
+    fn decode(scope: &CursorScope, key: &CursorKey, now: i64, cursor: &str) -> Result<Option<u64>, PageError> {
+        scope.decode_with(key, now, Some(cursor))
+    }
+
+    #[test]
+    fn cursor_round_trips_within_its_walk() {
+        let key = CursorKey::generate();
+        let cursor = scope().encode_with(&key, NOW, &42u64);
+        assert_eq!(decode(&scope(), &key, NOW + 10, &cursor).unwrap(), Some(42));
+        assert_eq!(scope().decode_with::<u64>(&key, NOW, None).unwrap(), None);
+    }
+
+    #[test]
+    fn altered_payload_or_signature_is_refused() {
+        let key = CursorKey::generate();
+        let cursor = scope().encode_with(&key, NOW, &42u64);
+        let (encoded, signature) = cursor.split_once('.').unwrap();
+
+        // Same signature over a payload pointing elsewhere
+        let mut payload: CursorPayload = serde_json::from_slice(&BASE64_URL.decode(encoded).unwrap()).unwrap();
+        payload.position = serde_json::json!(7);
+        let forged = format!("{}.{}", BASE64_URL.encode(serde_json::to_vec(&payload).unwrap()), signature);
+        assert!(matches!(decode(&scope(), &key, NOW, &forged), Err(PageError::InvalidCursor { .. })));
+
+        // A flipped signature byte, a shortened one and none at all
+        let mut tag = BASE64_URL.decode(signature).unwrap();
+        tag[0] ^= 1;
+        for bad in [BASE64_URL.encode(&tag), signature[..signature.len() - 4].to_string(), String::new()] {
+            let cursor = format!("{}.{}", encoded, bad);
+            assert!(matches!(decode(&scope(), &key, NOW, &cursor), Err(PageError::InvalidCursor { .. })));
+        }
+
+        for malformed in ["no-dot", "!!!.abc", "e30.abc"] {
+            assert!(matches!(decode(&scope(), &key, NOW, malformed), Err(PageError::InvalidCursor { .. })));
+        }
+    }
+
+    #[test]
+    fn cursor_of_another_list_or_filters_is_a_mismatch() {
+        let key = CursorKey::generate();
+        let cursor = scope().encode_with(&key, NOW, &42u64);
+        let other_list = CursorScope::new("history", &["/repo", "main"]);
+        let other_filters = CursorScope::new("sessions", &["/repo", "dev"]);
+        // The separator keeps ["/repo", "main"] apart from ["/repomain"]
+        let joined_filters = CursorScope::new("sessions", &["/repomain"]);
+        for scope in [other_list, other_filters, joined_filters] {
+            assert!(matches!(decode(&scope, &key, NOW, &cursor), Err(PageError::CursorMismatch { .. })));
+        }
+    }
+
+    #[test]
+    fn cursor_expires_after_its_ttl() {
+        let key = CursorKey::generate();
+        let cursor = scope().encode_with(&key, NOW, &42u64);
+        assert_eq!(decode(&scope(), &key, NOW + CURSOR_TTL_SECS, &cursor).unwrap(), Some(42));
+        assert!(matches!(
+            decode(&scope(), &key, NOW + CURSOR_TTL_SECS + 1, &cursor),
+            Err(PageError::ExpiredCursor { .. })
+        ));
+    }
+
+    #[test]
+    fn cursor_from_before_a_restart_is_expired_not_altered() {
+        let before_restart = CursorKey::generate();
+        let cursor = scope().encode_with(&before_restart, NOW, &42u64);
+        let after_restart = CursorKey::generate();
+        assert!(matches!(decode(&scope(), &after_restart, NOW, &cursor), Err(PageError::ExpiredCursor { .. })));
+    }
+
+    #[test]
+    fn position_of_the_wrong_shape_is_refused() {
+        let key = CursorKey::generate();
+        let cursor = scope().encode_with(&key, NOW, &"not a number");
+        assert!(matches!(decode(&scope(), &key, NOW, &cursor), Err(PageError::InvalidCursor { .. })));
+    }
+
+    #[test]
+    fn keyset_page_boundaries() {
+        let items = || vec![9, 7, 5, 3, 1];
+        let page = |after: Option<i32>, size| keyset_page(items(), |n| *n, after.as_ref(), size);
+
+        assert_eq!(keyset_page(Vec::<i32>::new(), |n| *n, None, 2), (vec![], None));
+        assert_eq!(page(None, 2), (vec![9, 7], Some(7)));
+        assert_eq!(page(Some(7), 2), (vec![5, 3], Some(3)));
+        // The last page is exactly full: nothing to continue from
+        assert_eq!(page(Some(3), 1), (vec![1], None));
+        assert_eq!(page(None, 5), (items(), None));
+        assert_eq!(page(None, 6), (items(), None));
+        assert_eq!(page(Some(1), 2), (vec![], None));
+        // A key that was deleted from under the walk still continues below it
+        assert_eq!(page(Some(6), 2), (vec![5, 3], Some(3)));
+        // An item added above the cursor doesn't shift the next page
+        let mut grown = items();
+        grown.insert(0, 11);
+        assert_eq!(keyset_page(grown, |n| *n, Some(&7), 2), (vec![5, 3], Some(3)));
+    }
+
+    #[test]
+    fn page_size_bounds() {
+        assert_eq!(page_size(None).unwrap(), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(MAX_PAGE_SIZE)).unwrap(), MAX_PAGE_SIZE);
+        assert!(page_size(Some(0)).is_err());
+        assert!(page_size(Some(MAX_PAGE_SIZE + 1)).is_err());
+    }
+}
@@ -0,0 +1,794 @@
+// mensa - Patch Module
+// Applies unified diffs proposed by the agent: hunk-level dry run, then all-or-nothing writes
+
+use crate::git::{DiffFileStat, DiffStats};
+use crate::{fsutil, workspace, AppState};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WhitespaceMode {
+    /// Context and removed lines must match exactly
+    #[default]
+    Exact,
+    /// Ignore trailing whitespace (and CR) when matching
+    IgnoreTrailing,
+    /// Ignore all whitespace differences when matching
+    IgnoreAll,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApplyPatchOptions {
+    /// Validate and report only; nothing is written
+    pub dry_run: bool,
+    /// Stage the applied files in the index
+    pub stage: bool,
+    pub whitespace: WhitespaceMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkMismatch {
+    /// 1-based line in the current file where the hunk expected `expected`
+    pub line: usize,
+    pub expected: String,
+    /// None when the file ends before this line
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkReport {
+    pub header: String,
+    pub status: String, // "clean" | "offset" | "conflict"
+    /// 1-based line the hunk applied at (in the file as patched so far)
+    pub applied_at: Option<usize>,
+    /// Lines between where the hunk said it belongs and where it matched
+    pub offset: isize,
+    pub mismatch: Option<HunkMismatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilePatchReport {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    pub status: String, // "added" | "modified" | "deleted" | "renamed"
+    pub hunks: Vec<HunkReport>,
+    /// Problem with the file as a whole (missing, already exists, binary, outside the workspace)
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchApplyResult {
+    /// Every file validated and (unless dry run) was written
+    pub applied: bool,
+    pub dry_run: bool,
+    pub staged: bool,
+    pub stage_error: Option<String>,
+    pub files: Vec<FilePatchReport>,
+    /// Same shape as the diff stats used for branch/workspace changes
+    pub summary: DiffStats,
+}
+
+#[derive(Debug, Clone)]
+struct HunkLine {
+    kind: char, // ' ' | '-' | '+'
+    text: String,
+    /// Followed by "\ No newline at end of file"
+    no_newline: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Hunk {
+    header: String,
+    old_start: usize,
+    lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct FilePatch {
+    old_path: Option<String>,
+    new_path: Option<String>,
+    is_new: bool,
+    is_deleted: bool,
+    new_mode: Option<u32>,
+    hunks: Vec<Hunk>,
+}
+
+/// File content as lines without terminators
+struct TextFile {
+    lines: Vec<String>,
+    trailing_newline: bool,
+    crlf: bool,
+}
+
+enum FileOp {
+    Write { path: PathBuf, content: Vec<u8>, mode: Option<u32> },
+    Remove { path: PathBuf },
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Byte length of the C-style quoted string `raw` starts with (quotes included), if it starts with one
+fn quoted_len(raw: &str) -> Option<usize> {
+    let bytes = raw.as_bytes();
+    if bytes.first() != Some(&b'"') {
+        return None;
+    }
+    let mut i = 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Undo git's quoting of unusual paths: `"caf\303\251 \"x\".txt"` is `café "x".txt`.
+/// Unquoted text comes back unchanged.
+fn unquote_path(raw: &str) -> String {
+    let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+        return raw.to_string();
+    };
+    let bytes = inner.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            out.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let escape = bytes[i + 1];
+        i += 2;
+        out.push(match escape {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b't' => b'\t',
+            b'n' => b'\n',
+            b'v' => 0x0b,
+            b'f' => 0x0c,
+            b'r' => b'\r',
+            b'0'..=b'7' => {
+                // Up to three octal digits, one byte
+                let mut value = u32::from(escape - b'0');
+                let mut digits = 1;
+                while digits < 3 && i < bytes.len() && (b'0'..=b'7').contains(&bytes[i]) {
+                    value = value * 8 + u32::from(bytes[i] - b'0');
+                    i += 1;
+                    digits += 1;
+                }
+                value as u8
+            }
+            other => other,
+        });
+    }
+    String::from_utf8(out).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+/// Path from a ---/+++ or diff --git header, without the a/ b/ prefix; None for /dev/null
+fn header_path(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    // A quoted path keeps its tabs escaped; an unquoted one may be followed by a tab and a timestamp
+    let raw = match quoted_len(raw) {
+        Some(len) => unquote_path(&raw[..len]),
+        None => raw.split('\t').next().unwrap_or(raw).trim().to_string(),
+    };
+    if raw == "/dev/null" {
+        return None;
+    }
+    let stripped = raw.strip_prefix("a/").or_else(|| raw.strip_prefix("b/")).unwrap_or(&raw);
+    Some(stripped.to_string())
+}
+
+/// The two paths of a `diff --git a/x b/x` line, either of which may be quoted
+fn git_header_paths(rest: &str) -> (Option<String>, Option<String>) {
+    if let Some(len) = quoted_len(rest) {
+        return (header_path(&rest[..len]), header_path(&rest[len..]));
+    }
+    let split = rest.rfind(" \"b/").or_else(|| rest.rfind(" b/"));
+    match split {
+        Some(split) => (header_path(&rest[..split]), header_path(&rest[split + 1..])),
+        None => (None, None),
+    }
+}
+
+fn parse_range(range: &str) -> Result<(usize, usize), String> {
+    let (start, len) = range.split_once(',').unwrap_or((range, "1"));
+    let parse = |n: &str| n.parse::<usize>().map_err(|_| format!("Invalid hunk range: {}", range));
+    Ok((parse(start)?, parse(len)?))
+}
+
+fn parse_mode(raw: &str) -> Option<u32> {
+    u32::from_str_radix(raw.trim(), 8).ok()
+}
+
+/// Parse a unified diff (git extended headers included); prose around the diff is ignored
+fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            files.extend(current.take());
+            let (old, new) = git_header_paths(rest);
+            current = Some(FilePatch {
+                old_path: old,
+                new_path: new,
+                ..Default::default()
+            });
+            i += 1;
+            continue;
+        }
+
+        if line.starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ ")) {
+            if current.as_ref().is_none_or(|c| !c.hunks.is_empty()) {
+                files.extend(current.take());
+                current = Some(FilePatch::default());
+            }
+            let file = current.as_mut().expect("file patch started above");
+            match header_path(&line[4..]) {
+                Some(path) => file.old_path = Some(path),
+                None => file.is_new = true,
+            }
+            match header_path(&lines[i + 1][4..]) {
+                Some(path) => file.new_path = Some(path),
+                None => file.is_deleted = true,
+            }
+            i += 2;
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("@@ ") {
+            let file = current
+                .as_mut()
+                .ok_or_else(|| format!("Hunk without a file header: {}", line))?;
+            let ranges = header.split(" @@").next().unwrap_or("");
+            let mut parts = ranges.split_whitespace();
+            let (old_start, mut old_left) = parse_range(parts.next().and_then(|p| p.strip_prefix('-')).unwrap_or(""))?;
+            let (_, mut new_left) = parse_range(parts.next().and_then(|p| p.strip_prefix('+')).unwrap_or(""))?;
+
+            let mut hunk = Hunk {
+                header: line.to_string(),
+                old_start,
+                lines: Vec::new(),
+            };
+            i += 1;
+            while i < lines.len() && (old_left > 0 || new_left > 0 || lines[i].starts_with('\\')) {
+                let body = lines[i];
+                let kind = match body.chars().next() {
+                    Some('\\') => {
+                        if let Some(last) = hunk.lines.last_mut() {
+                            last.no_newline = true;
+                        }
+                        i += 1;
+                        continue;
+                    }
+                    // Some tools strip the space from empty context lines
+                    None => ' ',
+                    Some(c @ (' ' | '-' | '+')) => c,
+                    Some(_) => break,
+                };
+                match kind {
+                    ' ' => {
+                        old_left = old_left.saturating_sub(1);
+                        new_left = new_left.saturating_sub(1);
+                    }
+                    '-' => old_left = old_left.saturating_sub(1),
+                    _ => new_left = new_left.saturating_sub(1),
+                }
+                hunk.lines.push(HunkLine {
+                    kind,
+                    text: body.get(1..).unwrap_or("").to_string(),
+                    no_newline: false,
+                });
+                i += 1;
+            }
+            if old_left > 0 || new_left > 0 {
+                return Err(format!("Truncated hunk: {}", hunk.header));
+            }
+            file.hunks.push(hunk);
+            continue;
+        }
+
+        if let Some(file) = current.as_mut().filter(|c| c.hunks.is_empty()) {
+            if let Some(mode) = line.strip_prefix("new file mode ") {
+                file.is_new = true;
+                file.new_mode = parse_mode(mode);
+            } else if line.starts_with("deleted file mode ") {
+                file.is_deleted = true;
+            } else if let Some(mode) = line.strip_prefix("new mode ") {
+                file.new_mode = parse_mode(mode);
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                file.old_path = Some(unquote_path(path));
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.new_path = Some(unquote_path(path));
+            } else if line.starts_with("copy from ") || line.starts_with("copy to ") {
+                return Err("Copy patches are not supported".to_string());
+            } else if line.starts_with("GIT binary patch") || line.starts_with("Binary files ") {
+                return Err(format!(
+                    "Binary patches are not supported: {}",
+                    file.new_path.as_deref().or(file.old_path.as_deref()).unwrap_or("?")
+                ));
+            }
+        }
+        i += 1;
+    }
+    files.extend(current);
+
+    files.retain(|f| f.old_path.is_some() || f.new_path.is_some());
+    if files.is_empty() {
+        return Err("No file changes found in the patch".to_string());
+    }
+    Ok(files)
+}
+
+fn lines_match(file_line: &str, patch_line: &str, mode: WhitespaceMode) -> bool {
+    match mode {
+        WhitespaceMode::Exact => file_line == patch_line,
+        WhitespaceMode::IgnoreTrailing => file_line.trim_end() == patch_line.trim_end(),
+        WhitespaceMode::IgnoreAll => file_line.split_whitespace().eq(patch_line.split_whitespace()),
+    }
+}
+
+fn split_text(content: &str) -> TextFile {
+    let crlf = content.contains("\r\n");
+    let trailing_newline = content.ends_with('\n');
+    let body = content.strip_suffix('\n').unwrap_or(content);
+    let lines = if content.is_empty() {
+        Vec::new()
+    } else {
+        body.split('\n')
+            .map(|l| l.strip_suffix('\r').unwrap_or(l).to_string())
+            .collect()
+    };
+    TextFile {
+        lines,
+        trailing_newline,
+        crlf,
+    }
+}
+
+fn join_text(file: &TextFile) -> String {
+    let eol = if file.crlf { "\r\n" } else { "\n" };
+    let mut text = file.lines.join(eol);
+    if file.trailing_newline && !file.lines.is_empty() {
+        text.push_str(eol);
+    }
+    text
+}
+
+/// Apply hunks in order, searching outward from the expected line when the file has drifted.
+/// Returns per-hunk reports; `file` is only meaningful if none conflicted.
+fn apply_hunks(file: &mut TextFile, hunks: &[Hunk], mode: WhitespaceMode) -> Vec<HunkReport> {
+    let mut reports = Vec::new();
+    let mut offset: isize = 0;
+    let mut min_pos = 0usize;
+
+    for hunk in hunks {
+        let old: Vec<&HunkLine> = hunk.lines.iter().filter(|l| l.kind != '+').collect();
+        let base = if old.is_empty() { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (base as isize + offset).max(0) as usize;
+
+        let fits = |pos: usize| {
+            pos >= min_pos
+                && pos + old.len() <= file.lines.len()
+                && old.iter().enumerate().all(|(k, l)| lines_match(&file.lines[pos + k], &l.text, mode))
+        };
+        let span = file.lines.len().max(expected) + 1;
+        let found = (0..=span).find_map(|d| {
+            if fits(expected + d) {
+                Some(expected + d)
+            } else if d > 0 && d <= expected && fits(expected - d) {
+                Some(expected - d)
+            } else {
+                None
+            }
+        });
+
+        let pos = match found {
+            Some(pos) => pos,
+            None => {
+                let mismatch = old
+                    .iter()
+                    .enumerate()
+                    .find(|(k, l)| {
+                        file.lines
+                            .get(expected + k)
+                            .is_none_or(|actual| !lines_match(actual, &l.text, mode))
+                    })
+                    .map(|(k, l)| HunkMismatch {
+                        line: expected + k + 1,
+                        expected: l.text.clone(),
+                        actual: file.lines.get(expected + k).cloned(),
+                    });
+                reports.push(HunkReport {
+                    header: hunk.header.clone(),
+                    status: "conflict".to_string(),
+                    applied_at: None,
+                    offset: 0,
+                    mismatch,
+                });
+                continue;
+            }
+        };
+
+        // Keep the file's own context lines (they may differ in whitespace from the patch)
+        let mut replacement = Vec::new();
+        let mut cursor = pos;
+        for line in &hunk.lines {
+            match line.kind {
+                ' ' => {
+                    replacement.push(file.lines[cursor].clone());
+                    cursor += 1;
+                }
+                '-' => cursor += 1,
+                _ => replacement.push(line.text.clone()),
+            }
+        }
+
+        let reaches_end = pos + old.len() == file.lines.len();
+        let new_len = replacement.len();
+        file.lines.splice(pos..pos + old.len(), replacement);
+        if reaches_end {
+            let new_side_last = hunk.lines.iter().rev().find(|l| l.kind != '-');
+            let old_side_last = old.last();
+            if new_side_last.is_some_and(|l| l.no_newline) {
+                file.trailing_newline = false;
+            } else if old_side_last.is_some_and(|l| l.no_newline) || old.is_empty() {
+                file.trailing_newline = true;
+            }
+        }
+
+        let drift = pos as isize - expected as isize;
+        offset += drift + new_len as isize - old.len() as isize;
+        min_pos = pos + new_len;
+        reports.push(HunkReport {
+            header: hunk.header.clone(),
+            status: if drift == 0 { "clean" } else { "offset" }.to_string(),
+            applied_at: Some(pos + 1),
+            offset: drift,
+            mismatch: None,
+        });
+    }
+    reports
+}
+
+/// Resolve a patch path inside the workspace, refusing absolute paths, `..`, .git and symlink escapes
+fn confine(root: &Path, rel: &str) -> Result<PathBuf, String> {
+    let path = Path::new(rel);
+    let mut components = path.components();
+    if !components.all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("Path escapes the workspace: {}", rel));
+    }
+    if path.components().next().is_some_and(|c| c.as_os_str() == ".git") {
+        return Err(format!("Refusing to patch inside .git: {}", rel));
+    }
+
+    let target = root.join(path);
+    let existing = target
+        .ancestors()
+        .find(|p| p.exists())
+        .and_then(|p| std::fs::canonicalize(p).ok())
+        .ok_or_else(|| format!("Failed to resolve {}", rel))?;
+    if !existing.starts_with(root) {
+        return Err(format!("Path escapes the workspace: {}", rel));
+    }
+    Ok(target)
+}
+
+fn file_status(patch: &FilePatch) -> &'static str {
+    if patch.is_new {
+        "added"
+    } else if patch.is_deleted {
+        "deleted"
+    } else if patch.old_path.is_some() && patch.new_path.is_some() && patch.old_path != patch.new_path {
+        "renamed"
+    } else {
+        "modified"
+    }
+}
+
+/// Validate one file patch and work out the writes it needs
+fn plan_file(root: &Path, patch: &FilePatch, mode: WhitespaceMode) -> (FilePatchReport, Vec<FileOp>) {
+    let status = file_status(patch);
+    let path = patch.new_path.clone().or_else(|| patch.old_path.clone()).unwrap_or_default();
+    let mut report = FilePatchReport {
+        path: path.clone(),
+        old_path: (status == "renamed").then(|| patch.old_path.clone()).flatten(),
+        status: status.to_string(),
+        hunks: Vec::new(),
+        error: None,
+    };
+
+    let result = (|| -> Result<Vec<FileOp>, String> {
+        let target = confine(root, &path)?;
+        let source = match &report.old_path {
+            Some(old) => confine(root, old)?,
+            None => target.clone(),
+        };
+
+        let original = if patch.is_new {
+            if target.exists() {
+                return Err(format!("{} already exists", path));
+            }
+            String::new()
+        } else {
+            let bytes = std::fs::read(&source).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            String::from_utf8(bytes).map_err(|_| format!("{} is not a text file", path))?
+        };
+        if status == "renamed" && target.exists() {
+            return Err(format!("Rename target {} already exists", path));
+        }
+
+        let mut text = split_text(&original);
+        report.hunks = apply_hunks(&mut text, &patch.hunks, mode);
+        if report.hunks.iter().any(|h| h.status == "conflict") {
+            return Ok(Vec::new());
+        }
+
+        if patch.is_deleted {
+            if !text.lines.is_empty() {
+                return Err(format!("{} has content the deletion patch doesn't account for", path));
+            }
+            return Ok(vec![FileOp::Remove { path: source }]);
+        }
+
+        let mut ops = vec![FileOp::Write {
+            path: target,
+            content: join_text(&text).into_bytes(),
+            mode: patch.new_mode,
+        }];
+        if status == "renamed" {
+            ops.push(FileOp::Remove { path: source });
+        }
+        Ok(ops)
+    })();
+
+    match result {
+        Ok(ops) => (report, ops),
+        Err(e) => {
+            report.error = Some(e);
+            (report, Vec::new())
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    let perms = if mode & 0o111 != 0 { 0o755 } else { 0o644 };
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(perms))
+        .map_err(|e| format!("Failed to set mode on {}: {}", path.display(), e))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<(), String> {
+    Ok(())
+}
+
+/// Run every write, restoring the original contents of already-touched files if one fails
+fn execute_ops(ops: &[FileOp]) -> Result<(), String> {
+    let mut done: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+
+    let result = ops.iter().try_for_each(|op| {
+        let path = match op {
+            FileOp::Write { path, .. } | FileOp::Remove { path } => path,
+        };
+        done.push((path.clone(), std::fs::read(path).ok()));
+        match op {
+            FileOp::Write { path, content, mode } => {
+                fsutil::write_atomic(path, content)?;
+                mode.map_or(Ok(()), |mode| set_mode(path, mode))
+            }
+            FileOp::Remove { path } => {
+                std::fs::remove_file(path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))
+            }
+        }
+    });
+
+    if result.is_err() {
+        for (path, original) in done.into_iter().rev() {
+            let _ = match original {
+                Some(content) => fsutil::write_atomic(&path, &content),
+                None => std::fs::remove_file(&path).map_err(|e| e.to_string()),
+            };
+        }
+    }
+    result
+}
+
+fn stage_files(root: &Path, files: &[FilePatchReport]) -> Result<(), String> {
+    let repo = Repository::open(root).map_err(|e| format!("Failed to open repository: {}", e))?;
+    let mut index = repo.index().map_err(|e| format!("Failed to get index: {}", e))?;
+    for file in files {
+        if let Some(old) = &file.old_path {
+            index
+                .remove_path(Path::new(old))
+                .map_err(|e| format!("Failed to unstage {}: {}", old, e))?;
+        }
+        if file.status == "deleted" {
+            index
+                .remove_path(Path::new(&file.path))
+                .map_err(|e| format!("Failed to stage removal of {}: {}", file.path, e))?;
+        } else {
+            index
+                .add_path(Path::new(&file.path))
+                .map_err(|e| format!("Failed to stage {}: {}", file.path, e))?;
+        }
+    }
+    index.write().map_err(|e| format!("Failed to write index: {}", e))
+}
+
+/// Body of `apply_patch_text`: plan every file, then write them all only if each one is clean
+fn apply_patch(root: &Path, patch_text: &str, options: &ApplyPatchOptions) -> Result<PatchApplyResult, String> {
+    let patches = parse_patch(patch_text)?;
+
+    let mut files = Vec::new();
+    let mut ops = Vec::new();
+    for patch in &patches {
+        let (report, file_ops) = plan_file(root, patch, options.whitespace);
+        files.push(report);
+        ops.extend(file_ops);
+    }
+    let summary = summarize(&patches, &files);
+    let valid = files
+        .iter()
+        .all(|f| f.error.is_none() && f.hunks.iter().all(|h| h.status != "conflict"));
+
+    let mut result = PatchApplyResult {
+        applied: false,
+        dry_run: options.dry_run,
+        staged: false,
+        stage_error: None,
+        files,
+        summary,
+    };
+    if !valid || options.dry_run {
+        return Ok(result);
+    }
+
+    execute_ops(&ops)?;
+    result.applied = true;
+
+    if options.stage {
+        match stage_files(root, &result.files) {
+            Ok(()) => result.staged = true,
+            Err(e) => result.stage_error = Some(e),
+        }
+    }
+    Ok(result)
+}
+
+fn summarize(patches: &[FilePatch], reports: &[FilePatchReport]) -> DiffStats {
+    let mut summary = DiffStats {
+        files: Vec::new(),
+        additions: 0,
+        deletions: 0,
+        filtered: Vec::new(),
+    };
+    for (patch, report) in patches.iter().zip(reports) {
+        let count = |kind: char| patch.hunks.iter().flat_map(|h| &h.lines).filter(|l| l.kind == kind).count() as u32;
+        let entry = DiffFileStat {
+            path: report.path.clone(),
+            old_path: report.old_path.clone(),
+            status: report.status.clone(),
+            additions: count('+'),
+            deletions: count('-'),
+            binary: false,
+            old_size_bytes: None,
+            new_size_bytes: None,
+            #[cfg(feature = "legacy_fields")]
+            old_size: None,
+            #[cfg(feature = "legacy_fields")]
+            new_size: None,
+            line_endings_only: false,
+            generated: false,
+            vendored: false,
+            diff_suppressed: false,
+            diff_driver: None,
+        };
+        summary.additions += entry.additions;
+        summary.deletions += entry.deletions;
+        summary.files.push(entry);
+    }
+    summary
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Validate a unified diff hunk by hunk and, if every hunk applies, write all files
+/// (or none). Use `dryRun` for a preview.
+#[tauri::command]
+pub async fn apply_patch_text(
+    state: State<'_, AppState>,
+    working_dir: String,
+    patch_text: String,
+    options: Option<ApplyPatchOptions>,
+) -> Result<PatchApplyResult, String> {
+    let root = workspace::validate_selected_workspace(&state, &working_dir).await?;
+    let options = options.unwrap_or_default();
+
+    tokio::task::spawn_blocking(move || apply_patch(&root, &patch_text, &options))
+        .await
+        .map_err(|e| format!("Patch apply failed: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, write};
+
+    fn workspace() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn quoted_paths_are_unescaped() {
+        assert_eq!(unquote_path(r#""caf\303\251.txt""#), "café.txt");
+        assert_eq!(unquote_path(r#""say \"hi\"\\now\tthen""#), "say \"hi\"\\now\tthen");
+        assert_eq!(unquote_path("plain name.txt"), "plain name.txt");
+        assert_eq!(header_path(r#""a/caf\303\251.txt""#).as_deref(), Some("café.txt"));
+        assert_eq!(header_path("b/notes.txt\t2024-01-01 00:00:00").as_deref(), Some("notes.txt"));
+        assert_eq!(header_path("/dev/null"), None);
+        assert_eq!(
+            git_header_paths(r#""a/x y.txt" b/x\ty.txt"#),
+            (Some("x y.txt".to_string()), Some("x\\ty.txt".to_string()))
+        );
+    }
+
+    #[test]
+    fn patch_with_quoted_paths_applies() {
+        let (_dir, root) = workspace();
+        write(&root, "café notes.txt", "first\nsecond\n");
+        write(&root, "tab\there.txt", "moved\n");
+
+        let result = apply_patch(&root, &fixture("patch/quoted_paths.diff"), &ApplyPatchOptions::default()).unwrap();
+        assert!(result.applied, "{:?}", result.files);
+        let paths: Vec<&str> = result.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["café notes.txt", "plain.txt"]);
+        assert_eq!(std::fs::read_to_string(root.join("café notes.txt")).unwrap(), "first\nsecond, edited\n");
+        assert_eq!(std::fs::read_to_string(root.join("plain.txt")).unwrap(), "moved\n");
+        assert!(!root.join("tab\there.txt").exists());
+    }
+
+    #[test]
+    fn one_failing_hunk_writes_nothing() {
+        let (_dir, root) = workspace();
+        write(&root, "one.txt", "alpha\nbeta\n");
+        write(&root, "two.txt", "gamma\nsomething else\n");
+
+        let result = apply_patch(&root, &fixture("patch/one_conflict.diff"), &ApplyPatchOptions::default()).unwrap();
+        assert!(!result.applied);
+        assert_eq!(result.files[0].hunks[0].status, "clean");
+        assert_eq!(result.files[1].hunks[0].status, "conflict");
+        let mismatch = result.files[1].hunks[0].mismatch.as_ref().unwrap();
+        assert_eq!((mismatch.line, mismatch.actual.as_deref()), (2, Some("something else")));
+
+        assert_eq!(std::fs::read_to_string(root.join("one.txt")).unwrap(), "alpha\nbeta\n");
+        assert_eq!(std::fs::read_to_string(root.join("two.txt")).unwrap(), "gamma\nsomething else\n");
+    }
+}
@@ -0,0 +1,124 @@
+// mensa - Paths Module
+// Where Claude Code keeps its files, resolved under a home directory given to it
+
+use crate::fsutil;
+use std::path::{Path, PathBuf};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Claude Code's data directory (~/.claude) under some home directory
+#[derive(Debug, Clone)]
+pub struct ClaudeHome {
+    root: PathBuf,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The user's home directory ($HOME)
+pub fn home_dir() -> Result<PathBuf, String> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| "Could not determine home directory".to_string())
+}
+
+/// Claude Code's name for a workspace's project directory (its path with `/` as `-`)
+pub fn sanitize_workspace_path(workspace_path: &str) -> String {
+    workspace_path.replace('/', "-")
+}
+
+impl ClaudeHome {
+    /// .claude under `home`
+    pub fn under(home: &Path) -> Self {
+        Self { root: home.join(".claude") }
+    }
+
+    /// .claude under the user's home directory
+    pub fn current() -> Result<Self, String> {
+        Ok(Self::under(&home_dir()?))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Per-project session transcripts
+    pub fn projects(&self) -> PathBuf {
+        self.root.join("projects")
+    }
+
+    pub fn plans(&self) -> PathBuf {
+        self.root.join("plans")
+    }
+
+    /// File snapshots taken before edits, per session
+    pub fn file_history(&self) -> PathBuf {
+        self.root.join("file-history")
+    }
+
+    /// A workspace's project directory. The workspace path is matched NFC-normalized, but an
+    /// existing directory is returned in the form it was created with, so composed and
+    /// decomposed spellings map to one project.
+    pub fn project_dir(&self, workspace_path: &str) -> PathBuf {
+        let projects = self.projects();
+        let sanitized = sanitize_workspace_path(workspace_path);
+        let direct = projects.join(&sanitized);
+        if sanitized.is_ascii() || direct.exists() {
+            return direct;
+        }
+        match fsutil::find_entry_nfc(&projects, &sanitized) {
+            Some(existing) => projects.join(existing),
+            None => projects.join(fsutil::nfc(&sanitized)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_replaces_every_separator() {
+        assert_eq!(sanitize_workspace_path("/Users/me/code/app"), "-Users-me-code-app");
+        assert_eq!(sanitize_workspace_path("relative/dir/"), "relative-dir-");
+        assert_eq!(sanitize_workspace_path("no-separators"), "no-separators");
+    }
+
+    #[test]
+    fn resolves_directories_under_the_given_home() {
+        let home = ClaudeHome::under(Path::new("/home/test"));
+        assert_eq!(home.root(), Path::new("/home/test/.claude"));
+        assert_eq!(home.projects(), Path::new("/home/test/.claude/projects"));
+        assert_eq!(home.plans(), Path::new("/home/test/.claude/plans"));
+        assert_eq!(home.file_history(), Path::new("/home/test/.claude/file-history"));
+        assert_eq!(
+            home.project_dir("/work/app"),
+            Path::new("/home/test/.claude/projects/-work-app")
+        );
+    }
+
+    #[test]
+    fn new_non_ascii_project_dirs_are_nfc() {
+        let home = tempfile::tempdir().unwrap();
+        let claude = ClaudeHome::under(home.path());
+        let decomposed = "/work/cafe\u{301}";
+        assert_eq!(claude.project_dir(decomposed), claude.projects().join("-work-caf\u{e9}"));
+    }
+
+    // macOS file systems look names up normalization-insensitively, so either spelling exists
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn existing_project_dir_is_found_in_its_stored_form() {
+        let home = tempfile::tempdir().unwrap();
+        let claude = ClaudeHome::under(home.path());
+        let stored = claude.projects().join("-work-cafe\u{301}");
+        std::fs::create_dir_all(&stored).unwrap();
+        // Typed composed, stored decomposed: both spellings land on the one directory
+        assert_eq!(claude.project_dir("/work/caf\u{e9}"), stored);
+        assert_eq!(claude.project_dir("/work/cafe\u{301}"), stored);
+    }
+}
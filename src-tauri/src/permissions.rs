@@ -0,0 +1,445 @@
+// mensa - Permissions Module
+// Finds paths under ~/.claude that mensa can't read or write (often left root-owned by a
+// sudo run of Claude Code) and explains how to fix them; nothing is ever changed
+
+use crate::{paths, project_dir_for_workspace, session_index, AppState};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Prefix of every error caused by a path mensa lacks permission for; the rest is the path
+pub const PERMISSION_DENIED_PREFIX: &str = "Permission denied: ";
+
+/// Most issues one check reports
+const MAX_ISSUES: usize = 200;
+
+/// Files next to the transcripts that mensa reads and rewrites
+const PROJECT_FILES: &[&str] = &[session_index::INDEX_FILE, crate::bookmarks::META_FILE];
+
+/// Error of the session and plan commands
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SessionError {
+    /// mensa's user may not read or write `path`; `suggested_fix` is the command that fixes it
+    PermissionDenied {
+        path: String,
+        suggested_fix: String,
+        message: String,
+    },
+    /// Another Claude Code is writing to the session's project; `hint` says what mensa can't do about it
+    SessionInUse {
+        session_id: String,
+        by: crate::session_guard::SessionHolder,
+        hint: String,
+    },
+    Failed { message: String },
+}
+
+impl From<String> for SessionError {
+    fn from(message: String) -> Self {
+        match message.strip_prefix(PERMISSION_DENIED_PREFIX) {
+            Some(path) => SessionError::PermissionDenied {
+                path: path.to_string(),
+                suggested_fix: suggested_fix(Path::new(path)),
+                message,
+            },
+            None => SessionError::Failed { message },
+        }
+    }
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::PermissionDenied { message, .. } | SessionError::Failed { message } => f.write_str(message),
+            SessionError::SessionInUse { session_id, hint, .. } => {
+                write!(f, "Session {} is in use by another Claude Code. {}", session_id, hint)
+            }
+        }
+    }
+}
+
+impl From<&str> for SessionError {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PermissionProblem {
+    Unreadable,
+    /// Readable, but mensa can't create, replace or delete files in it
+    Unwritable,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionIssue {
+    pub path: String,
+    pub is_dir: bool,
+    pub problem: PermissionProblem,
+    /// Owner's uid (Unix)
+    pub owner_uid: Option<u32>,
+    /// Whether someone other than mensa's user owns it (root, after a sudo run)
+    pub foreign_owner: bool,
+    /// Permission bits in octal, e.g. "755" (Unix)
+    pub mode: Option<String>,
+    /// Shell command that fixes it; mensa never runs it
+    pub suggested_fix: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionReport {
+    pub claude_dir: String,
+    /// Paths looked at
+    pub checked: u32,
+    pub issues: Vec<PermissionIssue>,
+    /// More issues were found than reported
+    pub truncated: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The error for a path mensa lacks permission for (see `PERMISSION_DENIED_PREFIX`)
+pub fn denied(path: &Path) -> String {
+    format!("{}{}", PERMISSION_DENIED_PREFIX, path.display())
+}
+
+/// Describe an I/O error on `path`: `denied` for a permission error, else "Failed to <action>: ..."
+pub fn io_error(action: &str, path: &Path, e: &std::io::Error) -> String {
+    match e.kind() {
+        std::io::ErrorKind::PermissionDenied => denied(path),
+        _ => format!("Failed to {}: {}", action, e),
+    }
+}
+
+/// Fail fast when mensa may not read (or, with `write`, change the entries of) an existing path
+pub fn require(path: &Path, write: bool) -> Result<(), String> {
+    match std::fs::metadata(path) {
+        Ok(metadata) => match problem(path, metadata.is_dir(), write) {
+            Some(_) => Err(denied(path)),
+            None => Ok(()),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Err(denied(path)),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Quote a path for a POSIX shell
+#[cfg(unix)]
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+/// What's wrong with a path, if anything. Directories need search permission to be read and
+/// write permission to have entries replaced; files only need to be readable, since every
+/// write is a rename in the directory.
+#[cfg(unix)]
+fn problem(path: &Path, is_dir: bool, write: bool) -> Option<PermissionProblem> {
+    use nix::unistd::{access, AccessFlags};
+    let read = match is_dir {
+        true => AccessFlags::R_OK | AccessFlags::X_OK,
+        false => AccessFlags::R_OK,
+    };
+    if access(path, read).is_err() {
+        return Some(PermissionProblem::Unreadable);
+    }
+    (write && is_dir && access(path, AccessFlags::W_OK).is_err()).then_some(PermissionProblem::Unwritable)
+}
+
+/// Windows ACLs aren't inspected; a failed open or a read-only directory is what's reported
+#[cfg(windows)]
+fn problem(path: &Path, is_dir: bool, write: bool) -> Option<PermissionProblem> {
+    let readable = match is_dir {
+        true => std::fs::read_dir(path).is_ok(),
+        false => std::fs::File::open(path).is_ok(),
+    };
+    if !readable {
+        return Some(PermissionProblem::Unreadable);
+    }
+    let read_only = std::fs::metadata(path).is_ok_and(|m| m.permissions().readonly());
+    (write && read_only).then_some(PermissionProblem::Unwritable)
+}
+
+/// Command that gives mensa's user access: a chown when someone else owns the path, else a chmod
+#[cfg(unix)]
+pub fn suggested_fix(path: &Path) -> String {
+    use std::os::unix::fs::MetadataExt;
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return format!("sudo chown -R \"$(id -un)\" {}", shell_quote(path));
+    };
+    let quoted = shell_quote(path);
+    let recursive = if metadata.is_dir() { "-R " } else { "" };
+    if metadata.uid() != nix::unistd::geteuid().as_raw() {
+        return format!("sudo chown {}\"$(id -un)\" {}", recursive, quoted);
+    }
+    match metadata.is_dir() {
+        true => format!("chmod -R u+rwX {}", quoted),
+        false => format!("chmod u+rw {}", quoted),
+    }
+}
+
+#[cfg(windows)]
+pub fn suggested_fix(path: &Path) -> String {
+    format!("icacls \"{}\" /grant \"%USERNAME%\":(OI)(CI)F /T", path.display())
+}
+
+#[cfg(unix)]
+fn owner_details(metadata: &std::fs::Metadata) -> (Option<u32>, bool, Option<String>) {
+    use std::os::unix::fs::MetadataExt;
+    let uid = metadata.uid();
+    (Some(uid), uid != nix::unistd::geteuid().as_raw(), Some(format!("{:o}", metadata.mode() & 0o7777)))
+}
+
+#[cfg(windows)]
+fn owner_details(_metadata: &std::fs::Metadata) -> (Option<u32>, bool, Option<String>) {
+    (None, false, None)
+}
+
+/// The issue with one path, if it has one
+fn inspect(path: &Path, write: bool) -> Option<PermissionIssue> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    let problem = problem(path, metadata.is_dir(), write)?;
+    let (owner_uid, foreign_owner, mode) = owner_details(&metadata);
+    Some(PermissionIssue {
+        path: path.to_string_lossy().to_string(),
+        is_dir: metadata.is_dir(),
+        problem,
+        owner_uid,
+        foreign_owner,
+        mode,
+        suggested_fix: suggested_fix(path),
+    })
+}
+
+/// Check ~/.claude, its projects and plans directories, and each workspace's project directory
+/// with its transcripts and the files mensa rewrites
+pub fn scan(workspaces: &[String]) -> Result<PermissionReport, String> {
+    let claude = paths::ClaudeHome::current()?;
+    let plans = claude.plans();
+    let mut paths: Vec<(PathBuf, bool)> = vec![
+        (claude.root().to_path_buf(), true),
+        (claude.projects(), true),
+        (plans.clone(), true),
+    ];
+    if let Ok(entries) = std::fs::read_dir(&plans) {
+        paths.extend(entries.flatten().map(|e| (e.path(), false)));
+    }
+    for workspace in workspaces {
+        let project_dir = project_dir_for_workspace(workspace)?;
+        if let Ok(entries) = std::fs::read_dir(&project_dir) {
+            paths.extend(
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        let name = p.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                        name.ends_with(".jsonl") || PROJECT_FILES.contains(&name)
+                    })
+                    .map(|p| (p, false)),
+            );
+        }
+        paths.push((project_dir, true));
+    }
+
+    let mut issues: Vec<PermissionIssue> = paths.iter().filter_map(|(path, write)| inspect(path, *write)).collect();
+    let truncated = issues.len() > MAX_ISSUES;
+    issues.truncate(MAX_ISSUES);
+    Ok(PermissionReport {
+        claude_dir: claude.root().to_string_lossy().to_string(),
+        checked: paths.len() as u32,
+        issues,
+        truncated,
+    })
+}
+
+/// Log what a startup check of ~/.claude finds (workspaces aren't known yet)
+pub async fn log_startup_issues() {
+    let Ok(Ok(report)) = tokio::task::spawn_blocking(|| scan(&[])).await else {
+        return;
+    };
+    for issue in &report.issues {
+        eprintln!("[mensa] {}{} ({:?}); fix with: {}", PERMISSION_DENIED_PREFIX, issue.path, issue.problem, issue.suggested_fix);
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Paths under ~/.claude mensa can't read or write, for the given workspaces (the selected
+/// ones when None), each with the chown/chmod command that fixes it
+#[tauri::command]
+pub async fn check_claude_permissions(
+    state: State<'_, AppState>,
+    workspaces: Option<Vec<String>>,
+) -> Result<PermissionReport, String> {
+    let workspaces = match workspaces {
+        Some(workspaces) => workspaces,
+        None => state
+            .selected_workspaces
+            .lock()
+            .await
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect(),
+    };
+    tokio::task::spawn_blocking(move || scan(&workspaces))
+        .await
+        .map_err(|e| format!("Failed to check permissions: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch_home, write};
+
+    /// Root passes every access check, so mode-bit simulations only mean something without it
+    #[cfg(unix)]
+    fn as_root() -> bool {
+        nix::unistd::geteuid().is_root()
+    }
+
+    /// Permission bits set on a path until dropped, so the scratch directory can be removed
+    #[cfg(unix)]
+    struct Mode(PathBuf);
+
+    #[cfg(unix)]
+    impl Mode {
+        fn set(path: &Path, mode: u32) -> Self {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+            Mode(path.to_path_buf())
+        }
+    }
+
+    #[cfg(unix)]
+    impl Drop for Mode {
+        fn drop(&mut self) {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&self.0, std::fs::Permissions::from_mode(0o755));
+        }
+    }
+
+    #[test]
+    fn denied_errors_become_permission_errors() {
+        let path = Path::new("/home/me/.claude/projects/-work-app/s1.jsonl");
+        let error = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        match SessionError::from(io_error("read session", path, &error)) {
+            SessionError::PermissionDenied { path: denied, suggested_fix, message } => {
+                assert_eq!(denied, path.to_string_lossy());
+                assert_eq!(message, format!("{}{}", PERMISSION_DENIED_PREFIX, path.display()));
+                assert!(suggested_fix.contains("-work-app/s1.jsonl"));
+            }
+            other => panic!("expected PermissionDenied, got {:?}", other),
+        }
+
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert!(matches!(SessionError::from(io_error("read session", path, &missing)), SessionError::Failed { .. }));
+        // A path that doesn't exist yet is left for the operation itself to create
+        assert_eq!(require(Path::new("/nonexistent/mensa/project"), true), Ok(()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fixes_quote_awkward_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "it's here/s1.jsonl", "");
+        let file = dir.path().join("it's here/s1.jsonl");
+        assert_eq!(suggested_fix(&file), format!("chmod u+rw '{}'", file.display().to_string().replace('\'', r"'\''")));
+        assert!(suggested_fix(file.parent().unwrap()).starts_with("chmod -R u+rwX '"));
+        // Gone by the time the fix is suggested: take ownership of whatever is there
+        assert!(suggested_fix(&dir.path().join("missing")).starts_with("sudo chown -R \"$(id -un)\" '"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_project_dir_is_readable_but_not_writable() {
+        if as_root() {
+            return;
+        }
+        let home = scratch_home();
+        let workspace = "/work/app";
+        let project_dir = home.claude().project_dir(workspace);
+        write(&project_dir, "s1.jsonl", "{}\n");
+        write(&project_dir, session_index::INDEX_FILE, "{\"entries\":[]}");
+        let _mode = Mode::set(&project_dir, 0o555);
+
+        assert_eq!(require(&project_dir, false), Ok(()));
+        assert_eq!(require(&project_dir, true), Err(denied(&project_dir)));
+        // Files need only be readable: every write is a rename in the directory
+        assert_eq!(require(&project_dir.join("s1.jsonl"), true), Ok(()));
+
+        let report = scan(&[workspace.to_string()]).unwrap();
+        assert_eq!(report.checked, 6);
+        assert_eq!(report.issues.len(), 1, "{:?}", report.issues);
+        let issue = &report.issues[0];
+        assert_eq!(issue.path, project_dir.to_string_lossy());
+        assert!(issue.is_dir && !issue.foreign_owner);
+        assert_eq!((issue.problem, issue.mode.as_deref()), (PermissionProblem::Unwritable, Some("555")));
+        assert_eq!(issue.suggested_fix, format!("chmod -R u+rwX '{}'", project_dir.display()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_files_and_unsearchable_dirs_are_reported() {
+        if as_root() {
+            return;
+        }
+        let home = scratch_home();
+        let project_dir = home.claude().project_dir("/work/app");
+        write(&project_dir, "s1.jsonl", "{}\n");
+        write(&project_dir, "s2.jsonl", "{}\n");
+        write(&home.claude().plans(), "plan.md", "# Plan\n");
+        let _transcript = Mode::set(&project_dir.join("s1.jsonl"), 0o200);
+        // Listable without search permission, so its entries are found but can't be opened
+        let _plans = Mode::set(&home.claude().plans(), 0o644);
+
+        let transcript = project_dir.join("s1.jsonl");
+        let error = std::fs::read_to_string(&transcript).unwrap_err();
+        assert_eq!(io_error("read session", &transcript, &error), denied(&transcript));
+
+        let report = scan(&["/work/app".to_string()]).unwrap();
+        let issues: Vec<(String, PermissionProblem, &str)> = report
+            .issues
+            .iter()
+            .map(|i| (i.path.clone(), i.problem, i.suggested_fix.split(' ').next().unwrap()))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                (home.claude().plans().to_string_lossy().to_string(), PermissionProblem::Unreadable, "chmod"),
+                (transcript.to_string_lossy().to_string(), PermissionProblem::Unreadable, "chmod"),
+            ]
+        );
+        assert_eq!(report.issues[1].mode.as_deref(), Some("200"));
+        assert!(!report.truncated);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_owned_by_someone_else_get_a_chown() {
+        if !as_root() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "sub/s1.jsonl", "");
+        let other = Some(nix::unistd::Uid::from_raw(4242));
+        nix::unistd::chown(&dir.path().join("sub"), other, None).unwrap();
+        nix::unistd::chown(&dir.path().join("sub/s1.jsonl"), other, None).unwrap();
+
+        let quoted = |p: &str| format!("'{}'", dir.path().join(p).display());
+        assert_eq!(suggested_fix(&dir.path().join("sub")), format!("sudo chown -R \"$(id -un)\" {}", quoted("sub")));
+        assert_eq!(suggested_fix(&dir.path().join("sub/s1.jsonl")), format!("sudo chown \"$(id -un)\" {}", quoted("sub/s1.jsonl")));
+        // Root can still use them, so there is nothing to report
+        assert!(inspect(&dir.path().join("sub"), true).is_none());
+    }
+}
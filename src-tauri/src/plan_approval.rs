@@ -0,0 +1,269 @@
+// mensa - Plan Approval Module
+// Approve a plan written in plan mode and resume its session in execution mode, or reject it
+
+use crate::history::{self, PlanApproval, QueryRecord};
+use crate::{fsutil, identity, paths, presets, AppState, QueryInput};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// A transcript written this long after its query finished was resumed by something else
+const CONTINUED_SLACK_SECS: i64 = 5;
+
+/// Tools whose `file_path` input marks the plan as written by the session
+const WRITE_TOOLS: &[&str] = &["Write", "Edit", "MultiEdit"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlanContinuation {
+    /// The resumed query, already started
+    pub query_id: String,
+    pub session_id: String,
+    /// Permission mode the plan is executed in
+    pub permission_mode: String,
+    pub approval: PlanApproval,
+    /// E.g. the session was continued after the plan was written
+    pub warnings: Vec<String>,
+}
+
+/// The planning run a plan belongs to
+struct PlanSession {
+    record: QueryRecord,
+    session_id: String,
+    plan_path: PathBuf,
+    /// Hash of the plan as it is on disk now
+    current_hash: String,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// A plan's path in ~/.claude/plans; the name must be a bare file name
+fn plan_path(plan_filename: &str) -> Result<PathBuf, String> {
+    let name = Path::new(plan_filename);
+    if plan_filename.is_empty() || name.file_name().and_then(|n| n.to_str()) != Some(plan_filename) {
+        return Err(format!("Invalid plan file name: {}", plan_filename));
+    }
+    Ok(paths::ClaudeHome::current()?.plans().join(plan_filename))
+}
+
+/// The last recorded run with this query id, else the last run that wrote to this session
+fn find_run(records: Vec<QueryRecord>, query_or_session_id: &str) -> Option<QueryRecord> {
+    let by_query = records.iter().rposition(|r| r.query_id == query_or_session_id);
+    let index = by_query.or_else(|| {
+        records
+            .iter()
+            .rposition(|r| r.session_id.as_deref() == Some(query_or_session_id))
+    })?;
+    records.into_iter().nth(index)
+}
+
+/// Whether an assistant tool_use in the transcript wrote or edited `plan_filename` in a plans directory
+fn transcript_wrote_plan(transcript: &str, plan_filename: &str) -> bool {
+    transcript.lines().filter(|line| line.contains(plan_filename)).any(|line| {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            return false;
+        };
+        let blocks = entry
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array())
+            .map(|b| b.as_slice())
+            .unwrap_or_default();
+        blocks.iter().any(|block| {
+            let is_write = block.get("type").and_then(|v| v.as_str()) == Some("tool_use")
+                && block
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|name| WRITE_TOOLS.contains(&name));
+            let path = block.get("input").and_then(|i| i.get("file_path")).and_then(|p| p.as_str());
+            is_write
+                && path.is_some_and(|path| {
+                    let path = Path::new(path);
+                    path.file_name().and_then(|n| n.to_str()) == Some(plan_filename)
+                        && path.parent().and_then(|p| p.file_name()).and_then(|n| n.to_str()) == Some("plans")
+                })
+        })
+    })
+}
+
+fn transcript_path(session: &PlanSession) -> Result<PathBuf, String> {
+    Ok(paths::ClaudeHome::current()?
+        .project_dir(&session.record.working_dir)
+        .join(format!("{}.jsonl", session.session_id)))
+}
+
+/// Check the plan exists, was written by the identified run's session, and (when given) still
+/// hashes to what the caller read
+async fn resolve(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    query_or_session_id: &str,
+    plan_filename: &str,
+    expected_hash: Option<&str>,
+) -> Result<PlanSession, String> {
+    if state.active_queries.lock().await.contains_key(query_or_session_id) {
+        return Err(format!("Query {} is still running; wait for it to finish", query_or_session_id));
+    }
+    let record = find_run(history::load_records(app).await?, query_or_session_id)
+        .ok_or_else(|| format!("No query or session found with id {}", query_or_session_id))?;
+    let session_id = record
+        .session_id
+        .clone()
+        .ok_or_else(|| format!("Query {} has no recorded session", record.query_id))?;
+
+    let plan_path = plan_path(plan_filename)?;
+    let current_hash = fsutil::file_hash(&plan_path)?
+        .ok_or_else(|| format!("Plan file not found: {}", plan_filename))?;
+    if expected_hash.is_some_and(|expected| expected != current_hash) {
+        return Err(format!("Conflict: {} changed since it was last read", plan_filename));
+    }
+
+    let session = PlanSession {
+        record,
+        session_id,
+        plan_path,
+        current_hash,
+    };
+    let transcript_path = transcript_path(&session)?;
+    let transcript = tokio::fs::read_to_string(&transcript_path)
+        .await
+        .map_err(|e| format!("Failed to read session {}: {}", session.session_id, e))?;
+    if !transcript_wrote_plan(&transcript, plan_filename) {
+        return Err(format!("Plan {} was not written in session {}", plan_filename, session.session_id));
+    }
+    Ok(session)
+}
+
+/// A warning when the session's transcript changed after its last recorded run finished
+fn continued_warning(session: &PlanSession) -> Option<String> {
+    let modified = std::fs::metadata(transcript_path(session).ok()?).ok()?.modified().ok()?;
+    let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    (modified > session.record.finished_at + CONTINUED_SLACK_SECS).then(|| {
+        format!(
+            "Session {} was continued after query {} wrote the plan; the approved plan may be out of date",
+            session.session_id, session.record.query_id
+        )
+    })
+}
+
+async fn approver(app: &tauri::AppHandle, state: &AppState, working_dir: &str) -> String {
+    match identity::effective(app, state, working_dir).await {
+        Ok(identity) => format!("{} <{}>", identity.name, identity.email),
+        Err(_) => std::env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
+    }
+}
+
+fn continuation_prompt(plan_path: &Path, edited: bool) -> String {
+    let edited = if edited { " (with my edits)" } else { "" };
+    format!(
+        "I approve the plan in {}{}. Implement it now, following the plan as written there.",
+        plan_path.display(),
+        edited
+    )
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// SHA-256 of a plan file, to pass back as `expected_hash` when approving or rejecting it
+#[tauri::command]
+pub async fn plan_file_hash(plan_filename: String) -> Result<Option<String>, String> {
+    fsutil::file_hash(&plan_path(&plan_filename)?)
+}
+
+/// Approve a plan written by a plan-mode run (named by query or session id): write back any
+/// edits, then resume the session in the workspace's execution mode. Returns once the resumed
+/// query has been started; the approval is kept in that query's history record.
+#[tauri::command]
+pub async fn approve_plan_and_continue(
+    app: tauri::AppHandle,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    query_or_session_id: String,
+    plan_filename: String,
+    edits: Option<String>,
+    expected_hash: Option<String>,
+) -> Result<PlanContinuation, String> {
+    let session = resolve(&app, &state, &query_or_session_id, &plan_filename, expected_hash.as_deref()).await?;
+    let warnings: Vec<String> = continued_warning(&session).into_iter().collect();
+
+    let edited = edits.is_some();
+    let plan_hash = match edits {
+        Some(edits) => {
+            fsutil::write_checked(&session.plan_path, edits.as_bytes(), Some(&session.current_hash))?;
+            fsutil::sha256_hex(edits.as_bytes())
+        }
+        None => session.current_hash.clone(),
+    };
+
+    let working_dir = session.record.working_dir.clone();
+    let permission_mode = presets::execution_mode(&app, &working_dir).await?;
+    let approval = PlanApproval {
+        plan_filename,
+        plan_hash,
+        approved_by: approver(&app, &state, &working_dir).await,
+        approved_at: history::now_secs(),
+        edited,
+        planned_in: session.record.query_id.clone(),
+    };
+    let config = serde_json::json!({ "permissionMode": permission_mode }).to_string();
+    let input = QueryInput {
+        prompt: continuation_prompt(&session.plan_path, edited),
+        working_dir,
+        config: Some(config),
+        resume_session: Some(session.session_id.clone()),
+        preset: session.record.preset.clone(),
+        owner: Some(window.label().to_string()),
+        plan_approval: Some(approval.clone()),
+        ..Default::default()
+    };
+
+    let query_id = uuid::Uuid::new_v4().to_string();
+    let (handle, id) = (app.clone(), query_id.clone());
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = crate::start_query(handle, id, input).await {
+            eprintln!("[mensa] Plan continuation failed: {}", serde_json::to_string(&e).unwrap_or_default());
+        }
+    });
+
+    Ok(PlanContinuation {
+        query_id,
+        session_id: session.session_id,
+        permission_mode,
+        approval,
+        warnings,
+    })
+}
+
+/// Reject a plan: append a rejection note (with who rejected it) to the plan file. Nothing is run.
+#[tauri::command]
+pub async fn reject_plan(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    query_or_session_id: String,
+    plan_filename: String,
+    note: Option<String>,
+    expected_hash: Option<String>,
+) -> Result<(), String> {
+    let session = resolve(&app, &state, &query_or_session_id, &plan_filename, expected_hash.as_deref()).await?;
+    let content = tokio::fs::read_to_string(&session.plan_path)
+        .await
+        .map_err(|e| format!("Failed to read plan file: {}", e))?;
+    let by = approver(&app, &state, &session.record.working_dir).await;
+    let reason = note
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .map(|n| format!(": {}", n))
+        .unwrap_or_default();
+    let updated = format!("{}\n\n---\n\n> **Rejected** by {}{}\n", content.trim_end(), by, reason);
+    fsutil::write_checked(&session.plan_path, updated.as_bytes(), Some(&session.current_hash))
+}
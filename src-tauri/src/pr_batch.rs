@@ -0,0 +1,384 @@
+// mensa - PR Batch Module
+// Fetches several PRs' info, diffs, checks, files and comments concurrently for a review
+// queue, streaming each PR as it arrives
+
+use crate::cancel::CancellationToken;
+use crate::forge::{self, Provider, RepoInfo};
+use crate::git::{self, GhPRInfo, GitState};
+use crate::gh_json;
+use crate::progress::ProgressReporter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{Emitter, Manager, State};
+use tokio::sync::{oneshot, Semaphore};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// PRs fetched at once; each runs up to three gh processes
+const PR_BATCH_CONCURRENCY: usize = 4;
+
+/// Most PRs one batch accepts
+const MAX_BATCH_PRS: usize = 50;
+
+/// Error of a PR the batch stopped before it finished
+const CANCELLED: &str = "Cancelled";
+
+/// Error of a PR not fetched because GitHub's API rate limit was hit
+const RATE_LIMITED: &str = "Skipped: the GitHub API rate limit was reached";
+
+/// What to fetch besides each PR's info
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PrBatchPart {
+    Diff,
+    /// CI check runs (`gh pr checks`)
+    Checks,
+    /// Changed files with their line counts
+    Files,
+    /// Conversation comments and reviews
+    Comments,
+}
+
+/// What was fetched for one PR; parts not asked for (or not available on the host) are None
+#[derive(Debug, Clone, Default)]
+struct FetchedPr {
+    info: Option<GhPRInfo>,
+    diff: Option<String>,
+    checks: Option<Value>,
+    files: Option<Value>,
+    comments: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrBatchFailure {
+    /// The number or URL as requested
+    pub pr: String,
+    pub error: String,
+}
+
+/// Result of `fetch_pr_batch`, once every PR finished or was skipped
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrBatchSummary {
+    pub operation_id: String,
+    pub requested: u32,
+    pub succeeded: u32,
+    pub failed: Vec<PrBatchFailure>,
+    pub cancelled: bool,
+    /// GitHub's rate limit was hit; PRs not started by then were skipped
+    pub rate_limited: bool,
+}
+
+/// Shared by every PR of one batch
+struct Batch {
+    app: tauri::AppHandle,
+    parts: HashSet<PrBatchPart>,
+    /// Prefix of the operation ids of the batch's gh processes
+    op_prefix: String,
+    token: CancellationToken,
+    rate_limited: AtomicBool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The URL of a requested PR: a PR or merge request URL as is, or a number ("123", "#123")
+/// on the workspace's remote
+fn resolve(pr: &str, repo: &RepoInfo) -> Result<String, String> {
+    let pr = pr.trim();
+    if forge::parse_review_url(pr).is_ok() {
+        return Ok(pr.to_string());
+    }
+    let number: u64 = pr
+        .trim_start_matches('#')
+        .parse()
+        .map_err(|_| format!("Not a pull request number or URL: {}", pr))?;
+    let (Some(host), Some(project)) = (&repo.host, &repo.project) else {
+        return Err("The workspace has no remote to resolve pull request numbers against".to_string());
+    };
+    match repo.provider {
+        Provider::Github => Ok(format!("https://{}/{}/pull/{}", host, project, number)),
+        Provider::Gitlab => Ok(format!("https://{}/{}/-/merge_requests/{}", host, project, number)),
+        provider => Err(forge::ReviewError::unsupported(provider, "Loading pull requests").to_string()),
+    }
+}
+
+fn is_rate_limit(error: &str) -> bool {
+    error.to_lowercase().contains("rate limit")
+}
+
+/// Run gh under an operation id of the batch. Cancelling the batch drops the run, which kills
+/// the process; `cancel_children` also stops its process group.
+async fn gh(batch: &Batch, args: &[&str], part: &str) -> Result<git::ExternalOutput, String> {
+    if batch.token.is_cancelled() {
+        return Err(CANCELLED.to_string());
+    }
+    let state = batch.app.state::<GitState>();
+    let op_id = format!("{}{}", batch.op_prefix, part);
+    tokio::select! {
+        output = git::run_external(&state, "gh", args, None, &[], None, Some(op_id)) => output.map_err(String::from),
+        _ = batch.token.cancelled() => Err(CANCELLED.to_string()),
+    }
+}
+
+fn stderr_of(output: &git::ExternalOutput) -> String {
+    String::from_utf8_lossy(&output.stderr).trim().to_string()
+}
+
+/// Info plus the asked-for parts of a GitHub PR: one `gh pr view` covers info, files and
+/// comments; the diff and checks take a call each
+async fn fetch_github(batch: &Batch, url: &str, index: usize) -> Result<FetchedPr, String> {
+    let (owner, repo, number) = git::parse_pr_url(url)?;
+    let repo_arg = format!("{}/{}", owner, repo);
+    let mut fields = git::PR_INFO_FIELDS.to_string();
+    if batch.parts.contains(&PrBatchPart::Files) {
+        fields.push_str(",files");
+    }
+    if batch.parts.contains(&PrBatchPart::Comments) {
+        fields.push_str(",comments,reviews");
+    }
+
+    let view = async {
+        let args = ["pr", "view", &number, "--repo", &repo_arg, "--json", &fields];
+        let output = gh(batch, &args, &format!("{}:view", index)).await?;
+        if !output.status.success() {
+            return Err(format!("Failed to fetch PR info: {}", stderr_of(&output)));
+        }
+        serde_json::from_slice::<Value>(&output.stdout).map_err(|e| format!("Failed to parse PR info JSON: {}", e))
+    };
+    let diff = async {
+        if !batch.parts.contains(&PrBatchPart::Diff) {
+            return Ok(None);
+        }
+        let args = ["pr", "diff", &number, "--repo", &repo_arg];
+        let output = gh(batch, &args, &format!("{}:diff", index)).await?;
+        if !output.status.success() {
+            return Err(format!("Failed to fetch PR diff: {}", stderr_of(&output)));
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).to_string()))
+    };
+    let checks = async {
+        if !batch.parts.contains(&PrBatchPart::Checks) {
+            return Ok(None);
+        }
+        let args = ["pr", "checks", &number, "--repo", &repo_arg, "--json", "name,state,bucket,link,workflow"];
+        let output = gh(batch, &args, &format!("{}:checks", index)).await?;
+        // gh exits non-zero while checks fail or are pending, and when there are none
+        match serde_json::from_slice::<Value>(&output.stdout) {
+            Ok(checks) => Ok(Some(checks)),
+            Err(_) if stderr_of(&output).contains("no checks reported") => Ok(Some(Value::Array(Vec::new()))),
+            Err(_) => Err(format!("Failed to fetch PR checks: {}", stderr_of(&output))),
+        }
+    };
+    let (view, diff, checks) = tokio::join!(view, diff, checks);
+
+    // Files and comments come out of the view first, so the info's `extra` holds only drift
+    let mut view = view?;
+    let mut take = |field: &str| view.as_object_mut().and_then(|v| v.remove(field)).unwrap_or(Value::Null);
+    let files = take("files");
+    let (comments, reviews) = (take("comments"), take("reviews"));
+    Ok(FetchedPr {
+        info: Some(gh_json::pr_info(view)?),
+        diff: diff?,
+        checks: checks?,
+        files: batch.parts.contains(&PrBatchPart::Files).then_some(files),
+        comments: batch
+            .parts
+            .contains(&PrBatchPart::Comments)
+            .then(|| serde_json::json!({ "comments": comments, "reviews": reviews })),
+    })
+}
+
+/// GitLab merge requests get info and diff; checks, files and comments stay None
+async fn fetch_gitlab(batch: &Batch, target: &forge::ReviewTarget) -> Result<FetchedPr, String> {
+    let info = forge::fetch_mr_info(&batch.app, target).await?;
+    let diff = match batch.parts.contains(&PrBatchPart::Diff) {
+        true => Some(forge::fetch_mr_diff(&batch.app, target).await?),
+        false => None,
+    };
+    Ok(FetchedPr {
+        info: Some(info),
+        diff,
+        ..Default::default()
+    })
+}
+
+/// Fetch one PR and keep its info and diff for the single-PR commands
+async fn fetch_one(batch: &Batch, url: &str, index: usize) -> Result<FetchedPr, String> {
+    let target = forge::parse_review_url(url)?;
+    let fetched = match target.provider {
+        Provider::Github => fetch_github(batch, url, index).await?,
+        Provider::Gitlab => {
+            tokio::select! {
+                fetched = fetch_gitlab(batch, &target) => fetched?,
+                _ = batch.token.cancelled() => return Err(CANCELLED.to_string()),
+            }
+        }
+        provider => return Err(forge::ReviewError::unsupported(provider, "Loading pull requests").to_string()),
+    };
+
+    let state = batch.app.state::<GitState>();
+    let key = target.key();
+    if let Some(info) = &fetched.info {
+        state.pr_info_cache.lock().await.insert(key.clone(), (Instant::now(), info.clone()));
+    }
+    state
+        .pr_prefetch
+        .lock()
+        .await
+        .insert(key, (Instant::now(), fetched.info.clone(), fetched.diff.clone()));
+    Ok(fetched)
+}
+
+/// Stop every gh process of the batch that's still running, with its children
+async fn cancel_children(state: &GitState, op_prefix: &str) {
+    let mut operations = state.operations.lock().await;
+    let children: Vec<String> = operations.keys().filter(|id| id.starts_with(op_prefix)).cloned().collect();
+    for id in children {
+        if let Some(cancel) = operations.remove(&id) {
+            let _ = cancel.send(());
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Fetch several PRs (numbers on the workspace's remote, or URLs) at once: info always, plus
+/// the `include`d parts. Each PR is emitted as `pr-batch-item` when it's done and lands in the
+/// caches `fetch_pr_info`/`fetch_pr_diff` read, so opening it afterwards is instant. Reported
+/// as a `pr-batch` operation; `cancel_git_operation` with its id stops the gh processes still
+/// running. After a rate limit error, PRs not yet started are skipped.
+#[tauri::command]
+pub async fn fetch_pr_batch(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    prs: Vec<String>,
+    include: Option<Vec<PrBatchPart>>,
+    op_id: Option<String>,
+) -> Result<PrBatchSummary, String> {
+    if prs.is_empty() {
+        return Err("No pull requests to fetch".to_string());
+    }
+    if prs.len() > MAX_BATCH_PRS {
+        return Err(format!("At most {} pull requests can be fetched at once", MAX_BATCH_PRS));
+    }
+    let repo = forge::get_repo_info(working_dir.clone()).await?;
+
+    let reporter = ProgressReporter::start(&app, "pr-batch", op_id, Some(&working_dir), true);
+    let operation_id = reporter.operation_id().to_string();
+    let batch = Arc::new(Batch {
+        app: app.clone(),
+        parts: include.unwrap_or_default().into_iter().collect(),
+        op_prefix: format!("{}:", operation_id),
+        token: CancellationToken::default(),
+        rate_limited: AtomicBool::new(false),
+    });
+
+    // cancel_git_operation(operation_id) lands here
+    let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+    state.operations.lock().await.insert(operation_id.clone(), cancel_tx);
+    let watcher = {
+        let (batch, app) = (batch.clone(), app.clone());
+        tauri::async_runtime::spawn(async move {
+            if cancel_rx.await.is_ok() {
+                batch.token.cancel();
+                cancel_children(&app.state::<GitState>(), &batch.op_prefix).await;
+            }
+        })
+    };
+
+    let total = prs.len() as u64;
+    reporter.update("fetching", Some(0), Some(total), None);
+    let permits = Arc::new(Semaphore::new(PR_BATCH_CONCURRENCY));
+    let mut running = tokio::task::JoinSet::new();
+    for (index, pr) in prs.iter().cloned().enumerate() {
+        let url = resolve(&pr, &repo);
+        let (batch, permits) = (batch.clone(), permits.clone());
+        running.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let result = match url {
+                Ok(_) if batch.token.is_cancelled() => Err(CANCELLED.to_string()),
+                Ok(_) if batch.rate_limited.load(Ordering::SeqCst) => Err(RATE_LIMITED.to_string()),
+                Ok(ref url) => fetch_one(&batch, url, index).await,
+                Err(ref e) => Err(e.clone()),
+            };
+            if let Err(e) = &result {
+                if is_rate_limit(e) {
+                    batch.rate_limited.store(true, Ordering::SeqCst);
+                }
+            }
+            (index, pr, url.ok(), result)
+        });
+    }
+
+    let mut summary = PrBatchSummary {
+        operation_id: operation_id.clone(),
+        requested: prs.len() as u32,
+        succeeded: 0,
+        failed: Vec::new(),
+        cancelled: false,
+        rate_limited: false,
+    };
+    let mut done = 0;
+    while let Some(joined) = running.join_next().await {
+        let Ok((index, pr, url, result)) = joined else {
+            continue;
+        };
+        done += 1;
+        reporter.update("fetching", Some(done), Some(total), None);
+        let payload = match result {
+            Ok(fetched) => {
+                summary.succeeded += 1;
+                serde_json::json!({
+                    "operation_id": operation_id,
+                    "index": index,
+                    "pr": pr,
+                    "url": url,
+                    "info": fetched.info,
+                    "diff": fetched.diff,
+                    "checks": fetched.checks,
+                    "files": fetched.files,
+                    "comments": fetched.comments,
+                    "error": null,
+                })
+            }
+            Err(error) => {
+                summary.failed.push(PrBatchFailure {
+                    pr: pr.clone(),
+                    error: error.clone(),
+                });
+                serde_json::json!({
+                    "operation_id": operation_id,
+                    "index": index,
+                    "pr": pr,
+                    "url": url,
+                    "error": error,
+                })
+            }
+        };
+        let _ = app.emit("pr-batch-item", payload);
+    }
+
+    watcher.abort();
+    state.operations.lock().await.remove(&operation_id);
+    // Runs dropped by the cancellation never unregistered themselves
+    cancel_children(&state, &batch.op_prefix).await;
+    summary.cancelled = batch.token.is_cancelled();
+    summary.rate_limited = batch.rate_limited.load(Ordering::SeqCst);
+    match summary.cancelled {
+        true => reporter.fail(CANCELLED),
+        false => reporter.complete(Some(format!("Fetched {} of {} pull requests", summary.succeeded, summary.requested))),
+    }
+    Ok(summary)
+}
@@ -0,0 +1,358 @@
+// mensa - PR Context Module
+// Gathers what's needed to draft a PR in one call: commits, linked issues, diff summary, template, owners
+
+use crate::git::{self, DiffStats, GitCommit, GitState};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// `#123` and Jira-style `ABC-456` references
+const DEFAULT_ISSUE_PATTERNS: &[&str] = &[r"#\d+", r"\b[A-Z][A-Z0-9]+-\d+\b"];
+
+/// Cached PR contexts kept at most (one per head/base pair)
+const MAX_CACHED_CONTEXTS: usize = 32;
+
+/// Commits listed at most; long-lived branches can be far ahead
+const MAX_COMMITS: usize = 250;
+
+const PR_TEMPLATE_PATHS: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+
+const CODEOWNERS_PATHS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueReference {
+    pub reference: String,
+    /// "branch" or the short hashes of the commits mentioning it
+    pub sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrTemplate {
+    pub path: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestedReviewer {
+    pub owner: String,
+    /// Changed files this owner is responsible for
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SectionError {
+    pub section: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrContext {
+    pub branch: String,
+    pub base: String,
+    pub head_sha: String,
+    pub base_sha: String,
+    pub commits: Option<Vec<GitCommit>>,
+    pub issues: Option<Vec<IssueReference>>,
+    pub changes: Option<DiffStats>,
+    /// None when the repository has no PR template
+    pub template: Option<PrTemplate>,
+    pub reviewers: Option<Vec<SuggestedReviewer>>,
+    /// Sections that couldn't be collected; the others are still filled in
+    pub errors: Vec<SectionError>,
+}
+
+pub struct CodeownersRule {
+    matcher: GlobMatcher,
+    owners: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// The base to compare against: the given ref, else the remote default branch, else main/master
+fn resolve_base(repo: &git2::Repository, base: Option<String>) -> Result<String, String> {
+    if let Some(base) = base {
+        return Ok(base);
+    }
+    let exists = |rev: &str| repo.revparse_single(rev).is_ok();
+    if let Some(default) = git::remote_default_branch(repo) {
+        return Ok(format!("origin/{}", default));
+    }
+    ["origin/main", "origin/master", "main", "master"]
+        .into_iter()
+        .find(|rev| exists(rev))
+        .map(|rev| rev.to_string())
+        .ok_or_else(|| "Could not determine a base branch; pass one explicitly".to_string())
+}
+
+fn commits_ahead(working_dir: &str, head: git2::Oid, base: git2::Oid) -> Result<Vec<GitCommit>, String> {
+    let repo = git::open_repo(working_dir)?;
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk.push(head).map_err(|e| format!("Failed to push HEAD: {}", e))?;
+    revwalk.hide(base).map_err(|e| format!("Failed to hide base: {}", e))?;
+
+    let mut commits = Vec::new();
+    for oid in revwalk.take(MAX_COMMITS) {
+        let oid = oid.map_err(|e| format!("Failed to get OID: {}", e))?;
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        commits.push(git::commit_summary(&repo, &commit));
+    }
+    Ok(commits)
+}
+
+fn extract_issues(patterns: &[String], branch: &str, commits: &[GitCommit]) -> Result<Vec<IssueReference>, String> {
+    let regexes = patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| format!("Invalid issue pattern '{}': {}", p, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut found: Vec<IssueReference> = Vec::new();
+    let mut record = |reference: &str, source: &str| match found.iter_mut().find(|i| i.reference == reference) {
+        Some(issue) if !issue.sources.iter().any(|s| s == source) => issue.sources.push(source.to_string()),
+        Some(_) => {}
+        None => found.push(IssueReference {
+            reference: reference.to_string(),
+            sources: vec![source.to_string()],
+        }),
+    };
+
+    for regex in &regexes {
+        for m in regex.find_iter(branch) {
+            record(m.as_str(), "branch");
+        }
+        for commit in commits {
+            for m in regex.find_iter(&commit.message) {
+                record(m.as_str(), &commit.short_hash);
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn read_pr_template(root: &Path) -> Option<PrTemplate> {
+    let candidates = PR_TEMPLATE_PATHS.iter().map(|p| p.to_string()).chain(
+        // Multiple-template directory: take the first one alphabetically
+        std::fs::read_dir(root.join(".github/PULL_REQUEST_TEMPLATE"))
+            .ok()
+            .map(|entries| {
+                let mut names: Vec<String> = entries
+                    .flatten()
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .filter(|n| n.ends_with(".md"))
+                    .collect();
+                names.sort();
+                names.into_iter().map(|n| format!(".github/PULL_REQUEST_TEMPLATE/{}", n)).collect::<Vec<_>>()
+            })
+            .unwrap_or_default(),
+    );
+    candidates
+        .into_iter()
+        .find_map(|path| std::fs::read_to_string(root.join(&path)).ok().map(|content| PrTemplate { path, content }))
+}
+
+/// Translate a CODEOWNERS pattern (gitignore-style) into a glob over repo-relative paths
+fn codeowners_glob(pattern: &str) -> String {
+    let anchored = pattern.starts_with('/');
+    let trimmed = pattern.trim_start_matches('/');
+    let mut glob = if anchored || trimmed.trim_end_matches('/').contains('/') {
+        trimmed.to_string()
+    } else {
+        format!("**/{}", trimmed)
+    };
+    if glob.ends_with('/') {
+        glob.push_str("**");
+    } else if !trimmed.contains('*') {
+        // A plain name matches the file itself or everything under a directory of that name
+        glob = format!("{{{},{}/**}}", glob, glob);
+    }
+    glob
+}
+
+fn parse_codeowners(content: &str) -> Vec<CodeownersRule> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let pattern = parts.next()?;
+            let owners: Vec<String> = parts.take_while(|p| !p.starts_with('#')).map(|p| p.to_string()).collect();
+            let matcher = GlobBuilder::new(&codeowners_glob(pattern))
+                .literal_separator(true)
+                .build()
+                .ok()?
+                .compile_matcher();
+            Some(CodeownersRule { matcher, owners })
+        })
+        .collect()
+}
+
+/// The repository's CODEOWNERS rules, if it has the file in one of the places GitHub looks
+pub fn read_codeowners(root: &Path) -> Option<Vec<CodeownersRule>> {
+    let content = CODEOWNERS_PATHS.iter().find_map(|p| std::fs::read_to_string(root.join(p)).ok())?;
+    Some(parse_codeowners(&content))
+}
+
+/// Owners of a repo-relative path: the last matching rule wins, as on GitHub
+pub fn owners_of<'a>(rules: &'a [CodeownersRule], path: &str) -> Option<&'a [String]> {
+    rules.iter().rev().find(|r| r.matcher.is_match(path)).map(|r| r.owners.as_slice())
+}
+
+/// Owners of the changed files by CODEOWNERS
+fn suggest_reviewers(root: &Path, files: &[String]) -> Vec<SuggestedReviewer> {
+    let Some(rules) = read_codeowners(root) else {
+        return Vec::new();
+    };
+
+    let mut reviewers: Vec<SuggestedReviewer> = Vec::new();
+    for file in files {
+        let Some(owners) = owners_of(&rules, file) else {
+            continue;
+        };
+        for owner in owners {
+            match reviewers.iter_mut().find(|r| &r.owner == owner) {
+                Some(reviewer) => reviewer.files.push(file.clone()),
+                None => reviewers.push(SuggestedReviewer {
+                    owner: owner.clone(),
+                    files: vec![file.clone()],
+                }),
+            }
+        }
+    }
+    reviewers.sort_by(|a, b| b.files.len().cmp(&a.files.len()).then_with(|| a.owner.cmp(&b.owner)));
+    reviewers
+}
+
+fn section_error(errors: &mut Vec<SectionError>, section: &str, message: String) {
+    errors.push(SectionError {
+        section: section.to_string(),
+        message,
+    });
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Collect commits ahead of the base, referenced issues, the change summary, the PR
+/// template and CODEOWNERS reviewers. Sections fail independently.
+#[tauri::command]
+pub async fn collect_pr_context(
+    state: State<'_, GitState>,
+    working_dir: String,
+    base: Option<String>,
+    issue_patterns: Option<Vec<String>>,
+) -> Result<PrContext, String> {
+    let (branch, base, head_oid, base_oid) = {
+        let repo = git::open_repo(&working_dir)?;
+        let base = resolve_base(&repo, base)?;
+        let head = repo.head().map_err(|e| format!("Failed to get HEAD: {}", e))?;
+        let branch = head.shorthand().unwrap_or("HEAD").to_string();
+        let head_oid = head
+            .peel_to_commit()
+            .map_err(|e| format!("Failed to resolve HEAD: {}", e))?
+            .id();
+        let base_oid = repo
+            .revparse_single(&base)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|e| format!("Failed to resolve '{}': {}", base, e))?
+            .id();
+        (branch, base, head_oid, base_oid)
+    };
+    let mut errors = Vec::new();
+
+    let cache_key = format!("{}|{}|{}", working_dir, head_oid, base_oid);
+    let cached = state.pr_context_cache.lock().await.get(&cache_key).cloned();
+    let root = Path::new(&working_dir).to_path_buf();
+
+    let (commits, changes) = match cached {
+        Some((commits, changes)) => (Some(commits), Some(changes)),
+        None => {
+            let commits_dir = working_dir.clone();
+            let stats_dir = working_dir.clone();
+            let (commits, changes) = tokio::join!(
+                tokio::task::spawn_blocking(move || commits_ahead(&commits_dir, head_oid, base_oid)),
+                tokio::task::spawn_blocking(move || {
+                    git::diff_stats_range(&stats_dir, &base_oid.to_string(), &head_oid.to_string())
+                }),
+            );
+            let commits = commits
+                .map_err(|e| format!("Commit listing failed: {}", e))
+                .and_then(|r| r)
+                .map_err(|e| section_error(&mut errors, "commits", e))
+                .ok();
+            let changes = changes
+                .map_err(|e| format!("Diff stats failed: {}", e))
+                .and_then(|r| r)
+                .map_err(|e| section_error(&mut errors, "changes", e))
+                .ok();
+
+            if let (Some(commits), Some(changes)) = (&commits, &changes) {
+                let mut cache = state.pr_context_cache.lock().await;
+                if cache.len() >= MAX_CACHED_CONTEXTS {
+                    cache.clear();
+                }
+                cache.insert(cache_key, (commits.clone(), changes.clone()));
+            }
+            (commits, changes)
+        }
+    };
+
+    let patterns = issue_patterns.unwrap_or_else(|| DEFAULT_ISSUE_PATTERNS.iter().map(|p| p.to_string()).collect());
+    let issues = extract_issues(&patterns, &branch, commits.as_deref().unwrap_or_default())
+        .map_err(|e| section_error(&mut errors, "issues", e))
+        .ok();
+
+    let changed_files: Vec<String> = changes
+        .as_ref()
+        .map(|c| c.files.iter().map(|f| f.path.clone()).collect())
+        .unwrap_or_default();
+    let files_root = root.clone();
+    let (template, reviewers) = tokio::join!(
+        tokio::task::spawn_blocking(move || read_pr_template(&root)),
+        tokio::task::spawn_blocking(move || suggest_reviewers(&files_root, &changed_files)),
+    );
+    let template = template
+        .map_err(|e| section_error(&mut errors, "template", format!("Template lookup failed: {}", e)))
+        .ok()
+        .flatten();
+    let reviewers = reviewers
+        .map_err(|e| section_error(&mut errors, "reviewers", format!("CODEOWNERS lookup failed: {}", e)))
+        .ok();
+
+    Ok(PrContext {
+        branch,
+        base,
+        head_sha: head_oid.to_string(),
+        base_sha: base_oid.to_string(),
+        commits,
+        issues,
+        changes,
+        template,
+        reviewers,
+        errors,
+    })
+}
@@ -0,0 +1,348 @@
+// mensa - Query Presets Module
+// Named QueryConfig fragments (model, permission mode, ...) stored globally or per workspace
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::{Emitter, Manager};
+
+use crate::store::JsonStore;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PresetScope {
+    Builtin,
+    Global,
+    Workspace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryPreset {
+    pub name: String,
+    /// Partial QueryConfig merged under the query's own config
+    pub config: Value,
+    /// Display-only hint, e.g. "Mod+Shift+P"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shortcut: Option<String>,
+    pub scope: PresetScope,
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// The keys a preset may set, with their expected types
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+#[allow(dead_code)]
+struct PresetConfigFragment {
+    model: Option<String>,
+    permission_mode: Option<String>,
+    max_turns: Option<u32>,
+    allowed_tools: Option<Vec<String>>,
+    enable_skills: Option<bool>,
+    setting_sources: Option<Vec<String>>,
+    mcp_servers: Option<Vec<Value>>,
+    max_cost_usd: Option<f64>,
+}
+
+const PERMISSION_MODES: &[&str] = &["default", "acceptEdits", "bypassPermissions", "plan"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct WorkspacePresets {
+    presets: Vec<QueryPreset>,
+    /// Applied on top of whichever preset is chosen, below the explicit config
+    default_config: Option<Value>,
+}
+
+static PRESETS_FILE: JsonStore<PresetsFile> = JsonStore::new("query presets");
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PresetsFile {
+    global: Vec<QueryPreset>,
+    workspaces: HashMap<String, WorkspacePresets>,
+    hidden_builtins: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn builtin_presets() -> Vec<QueryPreset> {
+    vec![
+        QueryPreset {
+            name: "Plan".to_string(),
+            config: serde_json::json!({ "model": "opus", "permissionMode": "plan" }),
+            shortcut: Some("Mod+Shift+P".to_string()),
+            scope: PresetScope::Builtin,
+            hidden: false,
+        },
+        QueryPreset {
+            name: "Fast edit".to_string(),
+            config: serde_json::json!({ "model": "sonnet", "permissionMode": "acceptEdits" }),
+            shortcut: Some("Mod+Shift+E".to_string()),
+            scope: PresetScope::Builtin,
+            hidden: false,
+        },
+    ]
+}
+
+pub fn presets_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("query-presets.json"))
+}
+
+fn notify_changed(app: &tauri::AppHandle, working_dir: Option<&str>) {
+    let _ = app.emit("presets-changed", serde_json::json!({ "working_dir": working_dir }));
+}
+
+/// Check that a config fragment is an object containing only known, well-typed keys
+fn validate_fragment(config: &Value) -> Result<(), String> {
+    if !config.is_object() {
+        return Err("Preset config must be a JSON object".to_string());
+    }
+    let fragment: PresetConfigFragment = serde_json::from_value(config.clone())
+        .map_err(|e| format!("Invalid preset config: {}", e))?;
+    if let Some(mode) = fragment.permission_mode.as_deref() {
+        if !PERMISSION_MODES.contains(&mode) {
+            return Err(format!("Invalid preset config: unknown permission mode '{}'", mode));
+        }
+    }
+    if fragment.max_turns == Some(0) {
+        return Err("Invalid preset config: maxTurns must be at least 1".to_string());
+    }
+    if fragment.max_cost_usd.is_some_and(|cost| !cost.is_finite() || cost <= 0.0) {
+        return Err("Invalid preset config: maxCostUsd must be a positive amount".to_string());
+    }
+    Ok(())
+}
+
+/// Shallow-merge `overlay` onto `base`; later layers win key by key
+fn merge_into(base: &mut Map<String, Value>, overlay: &Value) {
+    if let Some(overlay) = overlay.as_object() {
+        for (key, value) in overlay {
+            base.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+/// Builtins, then global, then workspace presets; a later preset with the same name replaces an earlier one
+fn effective_presets(file: &PresetsFile, working_dir: Option<&str>) -> Vec<QueryPreset> {
+    let mut presets = builtin_presets();
+    for preset in &mut presets {
+        preset.hidden = file.hidden_builtins.contains(&preset.name);
+    }
+
+    let workspace = working_dir.and_then(|dir| file.workspaces.get(dir));
+    let overrides = file
+        .global
+        .iter()
+        .chain(workspace.map(|w| w.presets.iter()).into_iter().flatten());
+    for preset in overrides {
+        match presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset.clone(),
+            None => presets.push(preset.clone()),
+        }
+    }
+    presets
+}
+
+/// Resolve the config a query runs with: preset < workspace default < explicit config.
+/// Returns the config JSON unchanged when there is nothing to merge.
+pub async fn resolve_query_config(
+    app: &tauri::AppHandle,
+    working_dir: &str,
+    preset: Option<&str>,
+    config: Option<String>,
+) -> Result<Option<String>, String> {
+    let file = PRESETS_FILE.read(app, presets_path(app)?).await?;
+    let workspace_default = file
+        .workspaces
+        .get(working_dir)
+        .and_then(|w| w.default_config.clone());
+    if preset.is_none() && workspace_default.is_none() {
+        return Ok(config);
+    }
+
+    let mut merged = Map::new();
+    if let Some(name) = preset {
+        let preset = effective_presets(&file, Some(working_dir))
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("Query preset not found: {}", name))?;
+        merge_into(&mut merged, &preset.config);
+    }
+    if let Some(default) = workspace_default {
+        merge_into(&mut merged, &default);
+    }
+    if let Some(config) = config {
+        let explicit: Value = serde_json::from_str(&config)
+            .map_err(|e| format!("Failed to parse query config: {}", e))?;
+        merge_into(&mut merged, &explicit);
+    }
+
+    serde_json::to_string(&Value::Object(merged))
+        .map(Some)
+        .map_err(|e| format!("Failed to serialize query config: {}", e))
+}
+
+/// The permission mode a workspace executes in: its default config's, or "default" when that is
+/// unset or is plan mode itself
+pub async fn execution_mode(app: &tauri::AppHandle, working_dir: &str) -> Result<String, String> {
+    let file = PRESETS_FILE.read(app, presets_path(app)?).await?;
+    let configured = file
+        .workspaces
+        .get(working_dir)
+        .and_then(|w| w.default_config.as_ref())
+        .and_then(|c| c.get("permissionMode"))
+        .and_then(|m| m.as_str())
+        .filter(|&m| m != "plan" && PERMISSION_MODES.contains(&m));
+    Ok(configured.unwrap_or("default").to_string())
+}
+
+fn require_working_dir(scope: PresetScope, working_dir: Option<String>) -> Result<Option<String>, String> {
+    match (scope, working_dir) {
+        (PresetScope::Workspace, None) => Err("Workspace presets need a working directory".to_string()),
+        (PresetScope::Workspace, dir) => Ok(dir),
+        _ => Ok(None),
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List presets visible in a workspace (hidden built-ins are included but flagged)
+#[tauri::command]
+pub async fn list_query_presets(
+    app: tauri::AppHandle,
+    working_dir: Option<String>,
+) -> Result<Vec<QueryPreset>, String> {
+    let file = PRESETS_FILE.read(&app, presets_path(&app)?).await?;
+    Ok(effective_presets(&file, working_dir.as_deref()))
+}
+
+/// Create or replace a global or workspace preset
+#[tauri::command]
+pub async fn save_query_preset(
+    app: tauri::AppHandle,
+    name: String,
+    config: Value,
+    scope: PresetScope,
+    working_dir: Option<String>,
+    shortcut: Option<String>,
+) -> Result<QueryPreset, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Preset name cannot be empty".to_string());
+    }
+    if scope == PresetScope::Builtin {
+        return Err("Built-in presets cannot be modified".to_string());
+    }
+    validate_fragment(&config)?;
+    let working_dir = require_working_dir(scope, working_dir)?;
+
+    let preset = QueryPreset {
+        name,
+        config,
+        shortcut,
+        scope,
+        hidden: false,
+    };
+
+    let store = PRESETS_FILE.lock(&app, presets_path(&app)?).await;
+    let mut file = store.load().await?;
+    let list = match working_dir.as_deref() {
+        Some(dir) => &mut file.workspaces.entry(dir.to_string()).or_default().presets,
+        None => &mut file.global,
+    };
+    match list.iter_mut().find(|p| p.name == preset.name) {
+        Some(existing) => *existing = preset.clone(),
+        None => list.push(preset.clone()),
+    }
+    store.save(&file).await?;
+
+    notify_changed(&app, working_dir.as_deref());
+    Ok(preset)
+}
+
+/// Delete a global or workspace preset. Built-ins can't be deleted, only hidden.
+#[tauri::command]
+pub async fn delete_query_preset(
+    app: tauri::AppHandle,
+    name: String,
+    scope: PresetScope,
+    working_dir: Option<String>,
+) -> Result<bool, String> {
+    if scope == PresetScope::Builtin {
+        return Err("Built-in presets can be hidden but not deleted".to_string());
+    }
+    let working_dir = require_working_dir(scope, working_dir)?;
+
+    let store = PRESETS_FILE.lock(&app, presets_path(&app)?).await;
+    let mut file = store.load().await?;
+    let list = match working_dir.as_deref() {
+        Some(dir) => match file.workspaces.get_mut(dir) {
+            Some(workspace) => &mut workspace.presets,
+            None => return Ok(false),
+        },
+        None => &mut file.global,
+    };
+    let before = list.len();
+    list.retain(|p| p.name != name);
+    if list.len() == before {
+        return Ok(false);
+    }
+    store.save(&file).await?;
+
+    notify_changed(&app, working_dir.as_deref());
+    Ok(true)
+}
+
+/// Hide or show a built-in preset
+#[tauri::command]
+pub async fn set_query_preset_hidden(app: tauri::AppHandle, name: String, hidden: bool) -> Result<bool, String> {
+    if !builtin_presets().iter().any(|p| p.name == name) {
+        return Err(format!("Not a built-in preset: {}", name));
+    }
+
+    let store = PRESETS_FILE.lock(&app, presets_path(&app)?).await;
+    let mut file = store.load().await?;
+    file.hidden_builtins.retain(|n| n != &name);
+    if hidden {
+        file.hidden_builtins.push(name);
+    }
+    store.save(&file).await?;
+
+    notify_changed(&app, None);
+    Ok(hidden)
+}
+
+/// Set (or clear with null) the config fragment applied to every query in a workspace
+#[tauri::command]
+pub async fn set_workspace_query_defaults(
+    app: tauri::AppHandle,
+    working_dir: String,
+    config: Option<Value>,
+) -> Result<(), String> {
+    if let Some(ref config) = config {
+        validate_fragment(config)?;
+    }
+
+    let store = PRESETS_FILE.lock(&app, presets_path(&app)?).await;
+    let mut file = store.load().await?;
+    file.workspaces.entry(working_dir.clone()).or_default().default_config = config;
+    store.save(&file).await?;
+
+    notify_changed(&app, Some(&working_dir));
+    Ok(())
+}
@@ -0,0 +1,228 @@
+// mensa - Progress Module
+// One progress schema for every long-running backend operation, plus the list of what's running
+
+use crate::AppState;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{Emitter, Manager, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Payload of `progress`, `progress-complete` and `progress-failed`.
+/// `current`/`total` are set when the step count is known; otherwise the phase is indeterminate.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    pub operation_id: String,
+    pub kind: String,
+    pub phase: String,
+    pub current: Option<u64>,
+    pub total: Option<u64>,
+    pub message: Option<String>,
+}
+
+/// An operation currently reporting progress
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningOperation {
+    pub operation_id: String,
+    pub kind: String,
+    pub working_dir: Option<String>,
+    pub started_at: i64,
+    /// Whether `cancel_git_operation` can stop it
+    pub cancellable: bool,
+    /// Latest progress reported
+    pub last: ProgressEvent,
+}
+
+/// Operations that have started and not yet completed or failed
+#[derive(Default)]
+pub struct OperationRegistry {
+    running: Mutex<HashMap<String, RunningOperation>>,
+}
+
+struct ReporterInner {
+    app: tauri::AppHandle,
+    operation_id: String,
+    kind: String,
+    finished: Mutex<bool>,
+}
+
+/// Emits progress for one operation. Cloning shares the operation; dropping the last
+/// clone without `complete`/`fail` reports the operation as failed.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    inner: Arc<ReporterInner>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl ProgressReporter {
+    /// Register an operation and emit its first (indeterminate) "started" phase
+    pub fn start(
+        app: &tauri::AppHandle,
+        kind: &str,
+        operation_id: Option<String>,
+        working_dir: Option<&str>,
+        cancellable: bool,
+    ) -> ProgressReporter {
+        let operation_id = operation_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let event = ProgressEvent {
+            operation_id: operation_id.clone(),
+            kind: kind.to_string(),
+            phase: "started".to_string(),
+            current: None,
+            total: None,
+            message: None,
+        };
+        if let Ok(mut running) = app.state::<AppState>().operations.running.lock() {
+            running.insert(operation_id.clone(), RunningOperation {
+                operation_id: operation_id.clone(),
+                kind: kind.to_string(),
+                working_dir: working_dir.map(String::from),
+                started_at: crate::history::now_secs(),
+                cancellable,
+                last: event.clone(),
+            });
+        }
+        let _ = app.emit("progress", &event);
+
+        ProgressReporter {
+            inner: Arc::new(ReporterInner {
+                app: app.clone(),
+                operation_id,
+                kind: kind.to_string(),
+                finished: Mutex::new(false),
+            }),
+        }
+    }
+
+    pub fn operation_id(&self) -> &str {
+        &self.inner.operation_id
+    }
+
+    fn event(&self, phase: &str, current: Option<u64>, total: Option<u64>, message: Option<String>) -> ProgressEvent {
+        ProgressEvent {
+            operation_id: self.inner.operation_id.clone(),
+            kind: self.inner.kind.clone(),
+            phase: phase.to_string(),
+            current,
+            total,
+            message,
+        }
+    }
+
+    /// Report a step; pass `total` when the amount of work is known
+    pub fn update(&self, phase: &str, current: Option<u64>, total: Option<u64>, message: Option<String>) {
+        let event = self.event(phase, current, total, message);
+        if let Ok(mut running) = self.inner.app.state::<AppState>().operations.running.lock() {
+            if let Some(operation) = running.get_mut(&self.inner.operation_id) {
+                operation.last = event.clone();
+            }
+        }
+        let _ = self.inner.app.emit("progress", &event);
+    }
+
+    /// Enter an indeterminate phase
+    pub fn phase(&self, phase: &str, message: Option<String>) {
+        self.update(phase, None, None, message);
+    }
+
+    fn finish(&self, event_name: &str, phase: &str, message: Option<String>) {
+        {
+            let Ok(mut finished) = self.inner.finished.lock() else {
+                return;
+            };
+            if *finished {
+                return;
+            }
+            *finished = true;
+        }
+        if let Ok(mut running) = self.inner.app.state::<AppState>().operations.running.lock() {
+            running.remove(&self.inner.operation_id);
+        }
+        let _ = self.inner.app.emit(event_name, self.event(phase, None, None, message));
+    }
+
+    pub fn complete(&self, message: Option<String>) {
+        self.finish("progress-complete", "complete", message);
+    }
+
+    pub fn fail(&self, error: &str) {
+        self.finish("progress-failed", "failed", Some(error.to_string()));
+    }
+
+    /// Finish according to a result, passing it through
+    pub fn settle<T, E: std::fmt::Display>(&self, result: Result<T, E>) -> Result<T, E> {
+        match &result {
+            Ok(_) => self.complete(None),
+            Err(e) => self.fail(&e.to_string()),
+        }
+        result
+    }
+
+    /// Sink for git's stderr: "Receiving objects:  45% (123/456)" lines become determinate
+    /// steps of that phase, anything else is passed on as an indeterminate message
+    pub fn git_sink(&self) -> crate::git::ProgressSink {
+        let reporter = self.clone();
+        Box::new(move |line: &str| match parse_git_progress(line) {
+            Some((phase, current, total)) => reporter.update(&phase, Some(current), Some(total), Some(line.to_string())),
+            None => reporter.phase("running", Some(line.to_string())),
+        })
+    }
+}
+
+impl Drop for ReporterInner {
+    fn drop(&mut self) {
+        let finished = self.finished.lock().map(|f| *f).unwrap_or(true);
+        if finished {
+            return;
+        }
+        if let Ok(mut running) = self.app.state::<AppState>().operations.running.lock() {
+            running.remove(&self.operation_id);
+        }
+        let _ = self.app.emit("progress-failed", ProgressEvent {
+            operation_id: self.operation_id.clone(),
+            kind: self.kind.clone(),
+            phase: "failed".to_string(),
+            current: None,
+            total: None,
+            message: Some("Operation ended without reporting completion".to_string()),
+        });
+    }
+}
+
+/// (phase, current, total) of a git progress line such as "remote: Counting objects:  9% (1/11)"
+fn parse_git_progress(line: &str) -> Option<(String, u64, u64)> {
+    static GIT_PROGRESS: OnceLock<Regex> = OnceLock::new();
+    let re = GIT_PROGRESS
+        .get_or_init(|| Regex::new(r"^(?:remote:\s*)?([A-Za-z][A-Za-z ]*?):\s+\d+% \((\d+)/(\d+)\)").expect("valid regex"));
+    let caps = re.captures(line)?;
+    Some((
+        caps[1].to_lowercase().replace(' ', "_"),
+        caps[2].parse().ok()?,
+        caps[3].parse().ok()?,
+    ))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Everything currently reporting progress, oldest first
+#[tauri::command]
+pub async fn list_running_operations(state: State<'_, AppState>) -> Result<Vec<RunningOperation>, String> {
+    let running = state
+        .operations
+        .running
+        .lock()
+        .map_err(|_| "Operation registry is unavailable".to_string())?;
+    let mut operations: Vec<RunningOperation> = running.values().cloned().collect();
+    operations.sort_by(|a, b| a.started_at.cmp(&b.started_at).then_with(|| a.operation_id.cmp(&b.operation_id)));
+    Ok(operations)
+}
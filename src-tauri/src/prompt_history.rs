@@ -0,0 +1,222 @@
+// mensa - Prompt History Module
+// Every submitted prompt, kept apart from sessions for fuzzy recall in the composer
+
+use crate::store::JsonStore;
+use crate::pagination::{self, CursorScope, Page, PageError};
+use crate::{history, settings, AppState};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// The prompt history file; its lock serializes read-modify-write cycles
+static PROMPTS_FILE: JsonStore<Vec<PromptEntry>> = JsonStore::new("prompt history");
+
+/// Characters of a prompt that are kept
+const MAX_PROMPT_CHARS: usize = 2000;
+
+/// Entries kept; the oldest are dropped past this
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptEntry {
+    pub id: String,
+    pub prompt: String,
+    pub workspace: String,
+    pub created_at: i64,
+    /// Query the prompt was last sent with
+    pub query_id: String,
+    /// Terminal reason of that query once it finished ("completed", "failed", ...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptMatch {
+    #[serde(flatten)]
+    pub entry: PromptEntry,
+    pub score: i64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn prompt_history_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("prompt-history.json"))
+}
+
+/// The text of a prompt: as-is, or the text blocks of an attachment prompt's content blocks
+pub fn prompt_text(prompt: &str, has_attachments: bool) -> String {
+    if !has_attachments {
+        return prompt.to_string();
+    }
+    match serde_json::from_str::<Value>(prompt) {
+        Ok(Value::Array(blocks)) => blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|v| v.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => prompt.to_string(),
+    }
+}
+
+async fn recording_enabled(app: &tauri::AppHandle) -> bool {
+    let state = app.state::<AppState>();
+    settings::load(app, &state.settings)
+        .await
+        .map(|s| s.prompt_history_enabled)
+        .unwrap_or(false)
+}
+
+/// Record a submitted prompt. A repeat of the newest entry (same text and workspace)
+/// refreshes that entry instead of adding another.
+pub async fn record(app: &tauri::AppHandle, workspace: &str, prompt: &str, query_id: &str) -> Result<(), String> {
+    let prompt: String = prompt.trim().chars().take(MAX_PROMPT_CHARS).collect();
+    if prompt.is_empty() || !recording_enabled(app).await {
+        return Ok(());
+    }
+
+    let store = PROMPTS_FILE.lock(app, prompt_history_path(app)?).await;
+    let mut entries = store.load().await?;
+    match entries.last_mut() {
+        Some(last) if last.prompt == prompt && last.workspace == workspace => {
+            last.created_at = history::now_secs();
+            last.query_id = query_id.to_string();
+            last.outcome = None;
+        }
+        _ => entries.push(PromptEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            prompt,
+            workspace: workspace.to_string(),
+            created_at: history::now_secs(),
+            query_id: query_id.to_string(),
+            outcome: None,
+        }),
+    }
+    if entries.len() > MAX_ENTRIES {
+        let excess = entries.len() - MAX_ENTRIES;
+        entries.drain(..excess);
+    }
+    store.save(&entries).await
+}
+
+/// Attach a finished query's terminal reason to the prompt that started it
+pub async fn set_outcome(app: &tauri::AppHandle, query_id: &str, outcome: &str) -> Result<(), String> {
+    let store = PROMPTS_FILE.lock(app, prompt_history_path(app)?).await;
+    let mut entries = store.load().await?;
+    let Some(entry) = entries.iter_mut().rev().find(|e| e.query_id == query_id) else {
+        return Ok(());
+    };
+    entry.outcome = Some(outcome.to_string());
+    store.save(&entries).await
+}
+
+/// Delete every recorded prompt
+pub async fn purge(app: &tauri::AppHandle) -> Result<(), String> {
+    PROMPTS_FILE.lock(app, prompt_history_path(app)?).await.remove().await
+}
+
+/// Rank entries against `query` (best score, then newest), one per distinct prompt text.
+/// An empty query lists the newest prompts.
+fn rank(entries: Vec<PromptEntry>, query: &str, workspace: Option<&str>) -> Vec<PromptMatch> {
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let query = query.trim();
+
+    let mut matches: Vec<PromptMatch> = entries
+        .into_iter()
+        .filter(|e| workspace.is_none_or(|w| e.workspace == w))
+        .filter_map(|entry| {
+            let score = if query.is_empty() {
+                0
+            } else {
+                matcher.fuzzy_match(&entry.prompt, query)?
+            };
+            Some(PromptMatch { entry, score })
+        })
+        .collect();
+    matches.sort_by_key(|m| std::cmp::Reverse(prompt_key(m)));
+
+    let mut seen = std::collections::HashSet::new();
+    matches.retain(|m| seen.insert(m.entry.prompt.clone()));
+    matches
+}
+
+/// Where a match sits in the ranking: score, then recency, then id
+type PromptKey = (i64, i64, String);
+
+fn prompt_key(m: &PromptMatch) -> PromptKey {
+    (m.score, m.entry.created_at, m.entry.id.clone())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Fuzzy-search recorded prompts, optionally within one workspace
+#[tauri::command]
+pub async fn search_prompt_history(
+    app: tauri::AppHandle,
+    query: String,
+    workspace: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<PromptMatch>, String> {
+    let entries = PROMPTS_FILE.read(&app, prompt_history_path(&app)?).await?;
+    let mut matches = rank(entries, &query, workspace.as_deref());
+    matches.truncate(limit.unwrap_or(20));
+    Ok(matches)
+}
+
+/// `search_prompt_history` a page at a time, in ranking order. Prompts recorded during a walk
+/// rank above or below the cursor by their score and never repeat a page.
+#[tauri::command]
+pub async fn search_prompt_history_paged(
+    app: tauri::AppHandle,
+    query: String,
+    workspace: Option<String>,
+    cursor: Option<String>,
+    limit: Option<usize>,
+) -> Result<Page<PromptMatch>, PageError> {
+    let size = pagination::page_size(limit)?;
+    let workspace_filter = workspace.as_deref().map(pagination::path_filter).unwrap_or_default();
+    let scope = CursorScope::new("prompt_history", &[query.trim(), &workspace_filter]);
+    let after: Option<PromptKey> = scope.decode(cursor.as_deref())?;
+    let entries = PROMPTS_FILE.read(&app, prompt_history_path(&app)?).await?;
+    let matches = rank(entries, &query, workspace.as_deref());
+    let total = matches.len() as u64;
+    let (matches, next) = pagination::keyset_page(matches, prompt_key, after.as_ref(), size);
+    Ok(scope.page(matches, Some(total), next))
+}
+
+/// Delete one recorded prompt; returns whether it existed
+#[tauri::command]
+pub async fn delete_prompt_history_entry(app: tauri::AppHandle, id: String) -> Result<bool, String> {
+    let store = PROMPTS_FILE.lock(&app, prompt_history_path(&app)?).await;
+    let mut entries = store.load().await?;
+    let before = entries.len();
+    entries.retain(|e| e.id != id);
+    if entries.len() == before {
+        return Ok(false);
+    }
+    store.save(&entries).await?;
+    Ok(true)
+}
+
+/// Delete every recorded prompt
+#[tauri::command]
+pub async fn clear_prompt_history(app: tauri::AppHandle) -> Result<(), String> {
+    purge(&app).await
+}
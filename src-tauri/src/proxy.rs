@@ -0,0 +1,391 @@
+// mensa - Proxy Module
+// Resolves the HTTP(S) proxy (configured, or the system's) and hands it to every child process
+
+use crate::{secrets, settings, AppState};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Keychain secret holding the proxy's `user:password`
+pub const PROXY_CREDENTIALS_SECRET: &str = "proxy_credentials";
+
+/// Checked by `test_proxy` when no URL is given
+const DEFAULT_TEST_URL: &str = "https://api.anthropic.com";
+
+const TEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Proxy preferences as stored in settings; credentials never appear here
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProxySettings {
+    /// "none" leaves the environment alone, "manual" uses the URLs below,
+    /// "system" reads the OS proxy settings (macOS) or the environment (elsewhere)
+    pub mode: ProxyMode,
+    pub http: Option<String>,
+    pub https: Option<String>,
+    /// Comma-separated hosts and domains that bypass the proxy
+    pub no_proxy: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProxyMode {
+    #[default]
+    None,
+    Manual,
+    System,
+}
+
+/// The proxy in effect after resolving the settings
+#[derive(Debug, Clone)]
+pub struct ResolvedProxy {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub no_proxy: Option<String>,
+    /// `user:password` from the keychain
+    credentials: Option<String>,
+}
+
+/// Proxy environment variables for child processes, shared by AppState and GitState.
+/// Kept resolved so spawning a process never waits on the keychain or the system settings.
+#[derive(Clone, Default)]
+pub struct ProxyEnv {
+    vars: Arc<RwLock<Vec<(String, String)>>>,
+}
+
+/// Outcome of `test_proxy`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyTestResult {
+    pub ok: bool,
+    pub url: String,
+    /// The proxy the request went through (without credentials); None when it went direct
+    pub proxy: Option<String>,
+    pub status: Option<u16>,
+    pub latency_ms: Option<u64>,
+    pub failure: Option<ProxyFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ProxyFailure {
+    /// Nothing is listening at the proxy's address
+    ConnectRefused { message: String },
+    /// The proxy wants credentials (or rejected the stored ones)
+    AuthRequired { message: String },
+    /// The certificate presented isn't trusted: usually a proxy re-signing TLS traffic
+    TlsInterception { message: String },
+    Timeout { message: String },
+    Other { message: String },
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl ProxyEnv {
+    /// Variables to set on a child process
+    pub fn vars(&self) -> Vec<(String, String)> {
+        self.vars.read().map(|vars| vars.clone()).unwrap_or_default()
+    }
+
+    fn set(&self, vars: Vec<(String, String)>) {
+        if let Ok(mut current) = self.vars.write() {
+            *current = vars;
+        }
+    }
+}
+
+impl ResolvedProxy {
+    /// Proxy URL for HTTPS requests, with the stored credentials in it
+    pub fn https_url(&self) -> Option<String> {
+        let url = self.https.as_ref().or(self.http.as_ref())?;
+        Some(with_credentials(url, self.credentials.as_deref()))
+    }
+}
+
+/// Prefix `http://` onto a bare `host:port`
+fn with_scheme(url: &str) -> String {
+    let url = url.trim();
+    if url.contains("://") {
+        url.to_string()
+    } else {
+        format!("http://{}", url)
+    }
+}
+
+/// Check a proxy URL from settings: it must parse and must not carry credentials
+pub fn validate_url(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(&with_scheme(url)).map_err(|e| format!("is not a valid URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https" | "socks5" | "socks5h") {
+        return Err(format!("unsupported proxy scheme '{}'", parsed.scheme()));
+    }
+    if parsed.host_str().is_none() {
+        return Err("has no host".to_string());
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err("must not contain credentials; store them with set_proxy_credentials".to_string());
+    }
+    Ok(())
+}
+
+/// The URL with the stored credentials filled in
+fn with_credentials(url: &str, credentials: Option<&str>) -> String {
+    let url = with_scheme(url);
+    let (Some(credentials), Ok(mut parsed)) = (credentials, reqwest::Url::parse(&url)) else {
+        return url;
+    };
+    let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+    if parsed.set_username(user).is_err() {
+        return url;
+    }
+    let _ = parsed.set_password((!password.is_empty()).then_some(password));
+    parsed.to_string()
+}
+
+/// The URL without any credentials, for display
+fn redacted(url: &str) -> String {
+    match reqwest::Url::parse(&with_scheme(url)) {
+        Ok(mut parsed) => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| std::env::var(name).ok())
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// Proxies from `scutil --proxy` output (macOS System Settings > Network > Proxies)
+fn parse_scutil(output: &str) -> ResolvedProxy {
+    let mut values = std::collections::HashMap::new();
+    let mut exceptions = Vec::new();
+    let mut in_exceptions = false;
+    for line in output.lines() {
+        let line = line.trim();
+        if line.starts_with("ExceptionsList") {
+            in_exceptions = true;
+            continue;
+        }
+        if in_exceptions {
+            if line == "}" {
+                in_exceptions = false;
+            } else if let Some((_, host)) = line.split_once(" : ") {
+                // `*.corp.example` is written `.corp.example` in NO_PROXY
+                exceptions.push(host.trim().trim_start_matches('*').to_string());
+            }
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(" : ") {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    let proxy = |prefix: &str| {
+        if values.get(&format!("{}Enable", prefix)).map(String::as_str) != Some("1") {
+            return None;
+        }
+        let host = values.get(&format!("{}Proxy", prefix))?;
+        Some(match values.get(&format!("{}Port", prefix)) {
+            Some(port) => format!("http://{}:{}", host, port),
+            None => format!("http://{}", host),
+        })
+    };
+    if values.get("ExcludeSimpleHostnames").map(String::as_str) == Some("1") {
+        exceptions.push("localhost".to_string());
+    }
+    ResolvedProxy {
+        http: proxy("HTTP"),
+        https: proxy("HTTPS"),
+        no_proxy: (!exceptions.is_empty()).then(|| exceptions.join(",")),
+        credentials: None,
+    }
+}
+
+/// The OS proxy: SystemConfiguration on macOS, the environment the app was started with elsewhere
+async fn system_proxy() -> ResolvedProxy {
+    if cfg!(target_os = "macos") {
+        if let Ok(output) = tokio::process::Command::new("scutil").arg("--proxy").output().await {
+            if output.status.success() {
+                return parse_scutil(&String::from_utf8_lossy(&output.stdout));
+            }
+        }
+    }
+    ResolvedProxy {
+        http: env_var(&["http_proxy", "HTTP_PROXY"]),
+        https: env_var(&["https_proxy", "HTTPS_PROXY"]),
+        no_proxy: env_var(&["no_proxy", "NO_PROXY"]),
+        credentials: None,
+    }
+}
+
+/// Resolve the configured proxy, reading credentials from the keychain only if some were stored
+pub async fn resolve(app: &tauri::AppHandle) -> Result<Option<ResolvedProxy>, String> {
+    let state = app.state::<AppState>();
+    let config = settings::load(app, &state.settings).await?.proxy.clone();
+    let mut resolved = match config.mode {
+        ProxyMode::None => return Ok(None),
+        ProxyMode::Manual => ResolvedProxy {
+            http: config.http.clone(),
+            https: config.https.clone().or_else(|| config.http.clone()),
+            no_proxy: config.no_proxy.clone(),
+            credentials: None,
+        },
+        ProxyMode::System => system_proxy().await,
+    };
+    if secrets::has_secret(app, PROXY_CREDENTIALS_SECRET).await? {
+        resolved.credentials = Some(secrets::read_secret(app, PROXY_CREDENTIALS_SECRET).await?);
+    }
+    Ok(Some(resolved))
+}
+
+/// Environment variables for a resolved proxy, in both cases (curl only reads lowercase http_proxy)
+fn env_vars(proxy: &ResolvedProxy) -> Vec<(String, String)> {
+    let credentials = proxy.credentials.as_deref();
+    let mut vars = Vec::new();
+    let entries = [
+        ("HTTP_PROXY", proxy.http.as_ref().map(|url| with_credentials(url, credentials))),
+        ("HTTPS_PROXY", proxy.https.as_ref().map(|url| with_credentials(url, credentials))),
+        ("NO_PROXY", proxy.no_proxy.clone()),
+    ];
+    for (name, value) in entries {
+        if let Some(value) = value {
+            vars.push((name.to_string(), value.clone()));
+            vars.push((name.to_lowercase(), value));
+        }
+    }
+    vars
+}
+
+/// Re-resolve the proxy and update the environment handed to child processes
+pub async fn refresh(app: &tauri::AppHandle) -> Result<(), String> {
+    let vars = resolve(app).await?.map(|proxy| env_vars(&proxy)).unwrap_or_default();
+    app.state::<AppState>().proxy.set(vars);
+    Ok(())
+}
+
+/// An HTTP client that goes through the resolved proxy (or direct when there is none)
+pub fn http_client(proxy: Option<&ResolvedProxy>, timeout: Duration) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().no_proxy().timeout(timeout);
+    if let Some(proxy) = proxy {
+        let no_proxy = proxy.no_proxy.as_deref().and_then(reqwest::NoProxy::from_string);
+        let credentials = proxy.credentials.as_deref().map(|c| c.split_once(':').unwrap_or((c, "")));
+        let targets = [
+            (proxy.http.as_deref(), reqwest::Proxy::http as fn(String) -> reqwest::Result<reqwest::Proxy>),
+            (proxy.https.as_deref(), reqwest::Proxy::https),
+        ];
+        for (url, make) in targets {
+            let Some(url) = url else { continue };
+            let mut configured = make(with_scheme(url)).map_err(|e| format!("Invalid proxy {}: {}", url, e))?;
+            if let Some((user, password)) = credentials {
+                configured = configured.basic_auth(user, password);
+            }
+            builder = builder.proxy(configured.no_proxy(no_proxy.clone()));
+        }
+    }
+    builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Sort a request error into the failure classes the UI explains
+fn classify(error: &reqwest::Error) -> ProxyFailure {
+    let mut chain = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        chain.push_str(": ");
+        chain.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    let lower = chain.to_lowercase();
+    if error.is_timeout() {
+        ProxyFailure::Timeout { message: chain }
+    } else if lower.contains("proxy authentication required") || lower.contains("407") {
+        ProxyFailure::AuthRequired { message: chain }
+    } else if lower.contains("certificate") || lower.contains("unknownissuer") || lower.contains("invalid peer") {
+        ProxyFailure::TlsInterception { message: chain }
+    } else if lower.contains("connection refused") {
+        ProxyFailure::ConnectRefused { message: chain }
+    } else {
+        ProxyFailure::Other { message: chain }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Send a HEAD request through the configured proxy (to `url`, or the Anthropic API) and report
+/// the latency, or why it failed: connection refused, proxy auth required, TLS interception
+#[tauri::command]
+pub async fn test_proxy(app: tauri::AppHandle, url: Option<String>) -> Result<ProxyTestResult, String> {
+    let url = url.unwrap_or_else(|| DEFAULT_TEST_URL.to_string());
+    let target = reqwest::Url::parse(&url).map_err(|e| format!("Invalid URL {}: {}", url, e))?;
+    let proxy = resolve(&app).await?;
+    let client = http_client(proxy.as_ref(), TEST_TIMEOUT)?;
+    let used = proxy.as_ref().and_then(|p| match target.scheme() {
+        "https" => p.https.as_deref().map(redacted),
+        _ => p.http.as_deref().map(redacted),
+    });
+
+    let started = Instant::now();
+    let mut result = ProxyTestResult {
+        ok: false,
+        url: url.clone(),
+        proxy: used,
+        status: None,
+        latency_ms: None,
+        failure: None,
+    };
+    match client.head(target).send().await {
+        Ok(response) => {
+            let status = response.status();
+            result.status = Some(status.as_u16());
+            result.latency_ms = Some(started.elapsed().as_millis() as u64);
+            if status == reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED {
+                result.failure = Some(ProxyFailure::AuthRequired {
+                    message: "The proxy answered 407 Proxy Authentication Required".to_string(),
+                });
+            } else {
+                // Any answer from the far end means the proxy let the request through
+                result.ok = true;
+            }
+        }
+        Err(e) => result.failure = Some(classify(&e)),
+    }
+    // A refreshed resolution (e.g. the system proxy changed) also reaches later child processes
+    if let Err(e) = refresh(&app).await {
+        eprintln!("[mensa] {}", e);
+    }
+    Ok(result)
+}
+
+/// Store the proxy's username and password in the keychain, or remove them when `username` is None
+#[tauri::command]
+pub async fn set_proxy_credentials(
+    app: tauri::AppHandle,
+    username: Option<String>,
+    password: Option<String>,
+) -> Result<(), String> {
+    match username.filter(|u| !u.trim().is_empty()) {
+        Some(username) => {
+            if username.contains(':') {
+                return Err("Proxy username must not contain ':'".to_string());
+            }
+            let value = format!("{}:{}", username.trim(), password.unwrap_or_default());
+            secrets::store_secret(&app, PROXY_CREDENTIALS_SECRET, value, "proxy").await?;
+        }
+        None => {
+            secrets::remove_secret(&app, PROXY_CREDENTIALS_SECRET).await?;
+        }
+    }
+    refresh(&app).await
+}
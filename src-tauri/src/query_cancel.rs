@@ -0,0 +1,384 @@
+// mensa - Query Cancel Module
+// One cancellation path for running, draining and queued queries; each ends with exactly one terminal event
+
+use crate::{query_group, replay, ActiveQuery, AppState, QueryPhase, QueuedFollowup, TERMINATE_GRACE};
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Terminal reason of a query stopped while it ran
+pub const CANCELLED: &str = "cancelled";
+
+/// Terminal reason of a queued query (follow-up or group member) cancelled before it started
+pub const CANCELLED_BEFORE_START: &str = "cancelled_before_start";
+
+/// Terminal reason of a follow-up dropped because its predecessor didn't finish cleanly
+pub const PREDECESSOR_FAILED: &str = "predecessor_failed";
+
+/// Terminal reason of a query stopped at its cost ceiling
+pub const COST_LIMIT_EXCEEDED: &str = "cost_limit_exceeded";
+
+/// The state a cancel found the query in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum CancelFound {
+    Running,
+    /// Output had closed; the process was finishing on its own
+    Draining,
+    /// An earlier cancel already stopped the agent
+    Cancelling,
+    /// A follow-up waiting on its predecessor
+    QueuedFollowup { predecessor: String },
+    /// A group member waiting for a free slot
+    QueuedInGroup { group_id: String },
+    /// Not running or queued (finished, or never started)
+    NotFound,
+}
+
+/// What the cancel did about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CancelAction {
+    /// The agent was stopped; the run emits `claude-done` with reason "cancelled"
+    Stopped,
+    /// The run had already produced all its output and finishes with its own outcome
+    LeftToFinish,
+    /// The agent was already being stopped
+    AlreadyStopping,
+    /// Taken off its queue; `claude-done` went out with reason "cancelled_before_start"
+    RemovedFromQueue,
+    Nothing,
+}
+
+/// Returned by `cancel_query`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelOutcome {
+    pub query_id: String,
+    pub found: CancelFound,
+    pub action: CancelAction,
+    /// Follow-up cancelled along with the query
+    pub cancelled_followup: Option<String>,
+    /// Follow-up left queued (`keep_followup`); it resumes the cancelled run's session
+    pub kept_followup: Option<String>,
+}
+
+/// One transition of an active query's cancellation; the caller stops the process and
+/// ends the dropped follow-up
+pub struct CancelStep {
+    pub found: CancelFound,
+    pub action: CancelAction,
+    pub dropped_followup: Option<QueuedFollowup>,
+    pub kept_followup: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl ActiveQuery {
+    /// Running moves to Cancelling and its process must be stopped; Draining is left to finish
+    /// and Cancelling stays as it is. In every phase the queued follow-up is taken out unless
+    /// `keep_followup`; a kept one stays for the run to hand over when it ends.
+    pub fn begin_cancel(&mut self, keep_followup: bool) -> CancelStep {
+        let (found, action) = match self.phase {
+            QueryPhase::Running => {
+                self.phase = QueryPhase::Cancelling;
+                (CancelFound::Running, CancelAction::Stopped)
+            }
+            QueryPhase::Draining => (CancelFound::Draining, CancelAction::LeftToFinish),
+            QueryPhase::Cancelling => (CancelFound::Cancelling, CancelAction::AlreadyStopping),
+        };
+        let dropped_followup = if keep_followup { None } else { self.followup.take() };
+        CancelStep {
+            found,
+            action,
+            dropped_followup,
+            kept_followup: self.followup.as_ref().map(|f| f.query_id.clone()),
+        }
+    }
+}
+
+impl CancelOutcome {
+    fn not_found(query_id: &str) -> Self {
+        CancelOutcome {
+            query_id: query_id.to_string(),
+            found: CancelFound::NotFound,
+            action: CancelAction::Nothing,
+            cancelled_followup: None,
+            kept_followup: None,
+        }
+    }
+}
+
+/// Emit the terminal event of a query that never started and close its buffer. A follow-up's
+/// event goes to the windows following its predecessor.
+pub async fn finish_unstarted(app: &tauri::AppHandle, query_id: &str, predecessor: Option<&str>, reason: &str) {
+    if let Some(predecessor) = predecessor {
+        replay::inherit_subscribers(app, predecessor, query_id);
+    }
+    replay::emit(app, query_id, replay::TERMINAL_EVENT, serde_json::json!({
+        "query_id": query_id,
+        "code": -1,
+        "reason": reason
+    }))
+    .await;
+    replay::finish(app, query_id).await;
+}
+
+/// Stop the agent of a query just moved to Cancelling: SIGTERM, then a kill if it's still
+/// running after TERMINATE_GRACE. The query lock is only taken to reach the child, so other
+/// queries' runs and cancels don't wait out the grace period.
+async fn stop_agent(app: &tauri::AppHandle, query_id: &str, pid: Option<u32>) {
+    #[cfg(unix)]
+    if let Some(pid) = pid {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+
+        let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+        tokio::time::sleep(TERMINATE_GRACE).await;
+    }
+    #[cfg(not(unix))]
+    let _ = pid;
+
+    let state = app.state::<AppState>();
+    let mut queries = state.active_queries.lock().await;
+    if let Some(active) = queries.get_mut(query_id) {
+        if let Ok(None) = active.child.try_wait() {
+            let _ = active.child.start_kill();
+        }
+    }
+}
+
+/// Cancel a query with a process: stop a running agent and end its follow-up unless kept,
+/// with `followup_reason`. None if no query by that id has one.
+async fn cancel_active(app: &tauri::AppHandle, query_id: &str, keep_followup: bool, followup_reason: &str) -> Option<CancelOutcome> {
+    let (step, pid) = {
+        let state = app.state::<AppState>();
+        let mut queries = state.active_queries.lock().await;
+        let active = queries.get_mut(query_id)?;
+        (active.begin_cancel(keep_followup), active.child.id())
+    };
+    if step.action == CancelAction::Stopped {
+        stop_agent(app, query_id, pid).await;
+    }
+    let cancelled_followup = match step.dropped_followup {
+        Some(followup) => {
+            finish_unstarted(app, &followup.query_id, Some(query_id), followup_reason).await;
+            Some(followup.query_id)
+        }
+        None => None,
+    };
+    Some(CancelOutcome {
+        query_id: query_id.to_string(),
+        found: step.found,
+        action: step.action,
+        cancelled_followup,
+        kept_followup: step.kept_followup,
+    })
+}
+
+/// Take the follow-up `query_id` off the query it waits on; that query's id, if it was queued
+fn take_followup(queries: &mut HashMap<String, ActiveQuery>, query_id: &str) -> Option<String> {
+    let (predecessor, active) = queries
+        .iter_mut()
+        .find(|(_, active)| active.followup.as_ref().is_some_and(|f| f.query_id == query_id))?;
+    active.followup = None;
+    Some(predecessor.clone())
+}
+
+/// Take a queued follow-up off the query it waits on; its predecessor, if it was queued
+async fn cancel_followup(app: &tauri::AppHandle, query_id: &str) -> Option<String> {
+    let predecessor = {
+        let state = app.state::<AppState>();
+        let mut queries = state.active_queries.lock().await;
+        take_followup(&mut queries, query_id)?
+    };
+    finish_unstarted(app, query_id, Some(&predecessor), CANCELLED_BEFORE_START).await;
+    Some(predecessor)
+}
+
+/// Cancel a query in whatever state it is: running or finishing, queued as a follow-up, or
+/// waiting in a group. A query's follow-up is cancelled with it unless `keep_followup`.
+pub async fn cancel(app: &tauri::AppHandle, query_id: &str, keep_followup: bool) -> CancelOutcome {
+    if let Some(outcome) = cancel_active(app, query_id, keep_followup, CANCELLED_BEFORE_START).await {
+        return outcome;
+    }
+    if let Some(predecessor) = cancel_followup(app, query_id).await {
+        return CancelOutcome {
+            found: CancelFound::QueuedFollowup { predecessor },
+            action: CancelAction::RemovedFromQueue,
+            ..CancelOutcome::not_found(query_id)
+        };
+    }
+    // The group emits the member's terminal event as it takes it off its queue
+    if let Some(group_id) = query_group::cancel_queued(app, query_id) {
+        return CancelOutcome {
+            found: CancelFound::QueuedInGroup { group_id },
+            action: CancelAction::RemovedFromQueue,
+            ..CancelOutcome::not_found(query_id)
+        };
+    }
+    CancelOutcome::not_found(query_id)
+}
+
+/// Stop a query that ran past its cost ceiling. Its run ends as cancelled runs do (reporting
+/// COST_LIMIT_EXCEEDED), and its follow-up ends as one whose predecessor failed.
+pub async fn stop_over_budget(app: &tauri::AppHandle, query_id: &str) -> CancelOutcome {
+    cancel_active(app, query_id, false, PREDECESSOR_FAILED)
+        .await
+        .unwrap_or_else(|| CancelOutcome::not_found(query_id))
+}
+
+/// Stop a running query's agent, cancelling its follow-up; false if it wasn't running
+pub async fn stop_query(app: &tauri::AppHandle, query_id: &str) -> bool {
+    cancel_active(app, query_id, false, CANCELLED_BEFORE_START)
+        .await
+        .is_some_and(|outcome| outcome.action == CancelAction::Stopped)
+}
+
+/// Stop every running query; returns how many were stopped
+pub async fn stop_all_queries(app: &tauri::AppHandle) -> usize {
+    let query_ids: Vec<String> = app.state::<AppState>().active_queries.lock().await.keys().cloned().collect();
+    // The agents get their grace period together rather than one after another
+    let mut stops = tokio::task::JoinSet::new();
+    for query_id in query_ids {
+        let app = app.clone();
+        stops.spawn(async move { stop_query(&app, &query_id).await });
+    }
+    stops.join_all().await.into_iter().filter(|stopped| *stopped).count()
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Cancel a running, finishing or queued query. Reports the state it was found in and what was
+/// done; every cancelled query still gets exactly one `claude-done` carrying its reason.
+#[tauri::command]
+pub async fn cancel_query(
+    app: tauri::AppHandle,
+    query_id: String,
+    keep_followup: Option<bool>,
+) -> Result<CancelOutcome, String> {
+    Ok(cancel(&app, &query_id, keep_followup.unwrap_or(false)).await)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn active(phase: QueryPhase, followup: Option<&str>) -> ActiveQuery {
+        let child = tokio::process::Command::new("sleep").arg("60").kill_on_drop(true).spawn().unwrap();
+        ActiveQuery {
+            child,
+            phase,
+            started_at: std::time::Instant::now(),
+            working_dir: "/work".to_string(),
+            changed_files: Default::default(),
+            followup: followup.map(|query_id| QueuedFollowup {
+                query_id: query_id.to_string(),
+                prompt: "and then?".to_string(),
+                config: None,
+            }),
+            tool_outputs: HashMap::new(),
+            context_usage: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn begin_cancel_in_every_phase() {
+        use CancelAction::*;
+        use QueryPhase::*;
+        let cases = [
+            (Running, Cancelling, CancelFound::Running, Stopped),
+            (Draining, Draining, CancelFound::Draining, LeftToFinish),
+            (Cancelling, Cancelling, CancelFound::Cancelling, AlreadyStopping),
+        ];
+        for (before, after, found, action) in cases {
+            for keep_followup in [false, true] {
+                for followup in [None, Some("f1")] {
+                    let case = format!("{:?} keep_followup={} followup={:?}", before, keep_followup, followup);
+                    let mut query = active(before, followup);
+                    let step = query.begin_cancel(keep_followup);
+                    assert_eq!(query.phase, after, "{}", case);
+                    assert_eq!((&step.found, step.action), (&found, action), "{}", case);
+
+                    let dropped = step.dropped_followup.map(|f| f.query_id);
+                    let remaining = query.followup.as_ref().map(|f| f.query_id.as_str());
+                    if keep_followup {
+                        assert_eq!((dropped.as_deref(), step.kept_followup.as_deref(), remaining), (None, followup, followup), "{}", case);
+                    } else {
+                        assert_eq!((dropped.as_deref(), step.kept_followup, remaining), (followup, None, None), "{}", case);
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelling_again_stops_nothing_and_drops_nothing() {
+        let mut query = active(QueryPhase::Running, Some("f1"));
+        let first = query.begin_cancel(false);
+        assert_eq!(first.action, CancelAction::Stopped);
+        assert_eq!(first.dropped_followup.map(|f| f.query_id).as_deref(), Some("f1"));
+
+        let second = query.begin_cancel(false);
+        assert_eq!((second.found, second.action), (CancelFound::Cancelling, CancelAction::AlreadyStopping));
+        assert!(second.dropped_followup.is_none() && second.kept_followup.is_none());
+
+        // A kept follow-up can still be dropped by a later cancel, once
+        let mut query = active(QueryPhase::Running, Some("f2"));
+        let kept = query.begin_cancel(true);
+        assert_eq!((kept.action, kept.kept_followup.as_deref()), (CancelAction::Stopped, Some("f2")));
+        let dropped = query.begin_cancel(false);
+        assert_eq!(dropped.action, CancelAction::AlreadyStopping);
+        assert_eq!(dropped.dropped_followup.map(|f| f.query_id).as_deref(), Some("f2"));
+        assert!(query.begin_cancel(false).dropped_followup.is_none());
+    }
+
+    /// Every way a follow-up can end (cancelled on its own, dropped with its predecessor, or
+    /// handed over when the run ends) takes it off the predecessor, so only one of them gets
+    /// to emit its terminal event
+    #[tokio::test]
+    async fn a_queued_followup_is_ended_by_one_path_only() {
+        // Cancelled on its own first
+        let mut queries = HashMap::from([("p1".to_string(), active(QueryPhase::Running, Some("f1")))]);
+        assert_eq!(take_followup(&mut queries, "f1").as_deref(), Some("p1"));
+        assert_eq!(take_followup(&mut queries, "f1"), None);
+        let step = queries.get_mut("p1").unwrap().begin_cancel(false);
+        assert!(step.dropped_followup.is_none());
+
+        // Dropped with its predecessor first
+        let mut queries = HashMap::from([("p1".to_string(), active(QueryPhase::Running, Some("f1")))]);
+        let step = queries.get_mut("p1").unwrap().begin_cancel(false);
+        assert_eq!(step.dropped_followup.map(|f| f.query_id).as_deref(), Some("f1"));
+        assert_eq!(take_followup(&mut queries, "f1"), None);
+
+        // Kept, then cancelled on its own before the run hands it over
+        let mut queries = HashMap::from([("p1".to_string(), active(QueryPhase::Draining, Some("f1")))]);
+        let step = queries.get_mut("p1").unwrap().begin_cancel(true);
+        assert!(step.dropped_followup.is_none());
+        assert_eq!(take_followup(&mut queries, "f1").as_deref(), Some("p1"));
+        assert!(queries.get_mut("p1").unwrap().followup.take().is_none());
+
+        // Handed over first: nothing is left to cancel
+        let mut queries = HashMap::from([("p1".to_string(), active(QueryPhase::Draining, Some("f1")))]);
+        assert!(queries.get_mut("p1").unwrap().followup.take().is_some());
+        assert_eq!(take_followup(&mut queries, "f1"), None);
+        assert!(queries.get_mut("p1").unwrap().begin_cancel(false).dropped_followup.is_none());
+
+        // Only the query it waits on is touched
+        let mut queries = HashMap::from([
+            ("p1".to_string(), active(QueryPhase::Running, Some("f1"))),
+            ("p2".to_string(), active(QueryPhase::Running, Some("f2"))),
+        ]);
+        assert_eq!(take_followup(&mut queries, "f2").as_deref(), Some("p2"));
+        assert_eq!(queries["p1"].followup.as_ref().map(|f| f.query_id.as_str()), Some("f1"));
+        assert_eq!(take_followup(&mut queries, "p1"), None);
+    }
+}
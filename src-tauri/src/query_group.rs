@@ -0,0 +1,567 @@
+// mensa - Query Group Module
+// Runs one prompt per workspace as a group: queued under the concurrency limit, reported together
+
+use crate::cancel::CancellationToken;
+use crate::session_guard::SessionHolder;
+use crate::{history, query_cancel, replay, settings, workspace, AppState, QueryError, QueryInput, StreamPayload};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager, State};
+use tokio::sync::mpsc;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// How often a running group checks for free slots and pending cancellation
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// One member of a group
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupQuery {
+    pub working_dir: String,
+    pub prompt: String,
+    pub config: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GroupOptions {
+    /// Run at most this many members at once, on top of the global limit
+    pub max_parallel: Option<usize>,
+}
+
+/// Returned by `query_claude_multi` as soon as the group is set up
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupStarted {
+    pub group_id: String,
+    /// Query ids in the order the members were given
+    pub query_ids: Vec<String>,
+}
+
+/// One member's outcome in `claude-group-done`
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupResult {
+    pub query_id: String,
+    pub working_dir: String,
+    /// The history record's terminal reason; "cancelled" for members cancelled while queued
+    pub exit_reason: String,
+    pub cost: Option<f64>,
+    /// Why the member couldn't start
+    pub error: Option<String>,
+}
+
+struct GroupHandle {
+    token: CancellationToken,
+    query_ids: Vec<String>,
+    /// Members not started yet; one taken out before it starts is cancelled
+    queued: HashSet<String>,
+}
+
+/// Groups still running, and the group of each member query
+#[derive(Default)]
+pub struct QueryGroups {
+    groups: Mutex<HashMap<String, GroupHandle>>,
+    by_query: Mutex<HashMap<String, String>>,
+}
+
+struct Member {
+    index: usize,
+    query_id: String,
+    input: QueryInput,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl QueryGroups {
+    /// Track a new group whose members are all queued
+    fn register(&self, group_id: &str, query_ids: &[String], token: CancellationToken) {
+        {
+            let mut by_query = self.by_query.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            for query_id in query_ids {
+                by_query.insert(query_id.clone(), group_id.to_string());
+            }
+        }
+        self.groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(
+            group_id.to_string(),
+            GroupHandle {
+                token,
+                query_ids: query_ids.to_vec(),
+                queued: query_ids.iter().cloned().collect(),
+            },
+        );
+    }
+
+    fn group_of(&self, query_id: &str) -> Option<String> {
+        let by_query = self.by_query.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        by_query.get(query_id).cloned()
+    }
+
+    /// See `cancel_queued`
+    fn cancel_queued(&self, query_id: &str) -> Option<String> {
+        let group_id = self.group_of(query_id)?;
+        let mut groups = self.groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let group = groups.get_mut(&group_id)?;
+        group.queued.remove(query_id).then_some(group_id)
+    }
+
+    /// Take a member off the group's queue to start it; false if it was cancelled meanwhile
+    fn claim_start(&self, group_id: &str, query_id: &str) -> bool {
+        let mut groups = self.groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        groups.get_mut(group_id).is_some_and(|group| group.queued.remove(query_id))
+    }
+
+    /// Split off the queued members no longer on the group's queue
+    fn take_cancelled(&self, group_id: &str, queued: &mut VecDeque<Member>) -> Vec<Member> {
+        let groups = self.groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let waiting = groups.get(group_id).map(|group| &group.queued);
+        let (kept, cancelled): (Vec<Member>, Vec<Member>) = queued
+            .drain(..)
+            .partition(|m| waiting.is_some_and(|waiting| waiting.contains(&m.query_id)));
+        *queued = kept.into();
+        cancelled
+    }
+}
+
+/// Group a query belongs to, if it was started by `query_claude_multi`
+pub fn group_of(app: &tauri::AppHandle, query_id: &str) -> Option<String> {
+    app.state::<AppState>().groups.group_of(query_id)
+}
+
+/// Cancel every running group so none starts another member; returns how many there were
+pub fn cancel_all(groups: &QueryGroups) -> usize {
+    let mut groups = groups.groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    for group in groups.values_mut() {
+        group.token.cancel();
+        group.queued.clear();
+    }
+    groups.len()
+}
+
+/// Take a member still waiting for a slot off its group's queue; the group ends it with
+/// `cancelled_before_start` on its next pass. The member's group, if it was waiting.
+pub fn cancel_queued(app: &tauri::AppHandle, query_id: &str) -> Option<String> {
+    app.state::<AppState>().groups.cancel_queued(query_id)
+}
+
+/// End a member cancelled before it started: its one terminal event, its history and its result
+async fn finish_cancelled(app: &tauri::AppHandle, group_id: &str, member: Member) -> GroupResult {
+    if let Some(owner) = &member.input.owner {
+        replay::subscribe(app, &member.query_id, owner);
+    }
+    query_cancel::finish_unstarted(app, &member.query_id, None, query_cancel::CANCELLED_BEFORE_START).await;
+    let working_dir = member.input.working_dir;
+    record_unstarted(app, group_id, &member.query_id, &working_dir, query_cancel::CANCELLED).await;
+    GroupResult {
+        query_id: member.query_id,
+        working_dir,
+        exit_reason: query_cancel::CANCELLED.to_string(),
+        cost: None,
+        error: None,
+    }
+}
+
+fn error_message(error: &QueryError) -> String {
+    match error {
+        QueryError::Failed { message } => message.clone(),
+        QueryError::SessionWorkspaceMismatch { recorded_cwd, working_dir } => {
+            format!("Session was recorded in {}, not {}", recorded_cwd, working_dir)
+        }
+        QueryError::UnknownReferences { references } => {
+            let paths: Vec<&str> = references.iter().map(|r| r.reference.as_str()).collect();
+            format!("Prompt refers to files that don't exist: {}", paths.join(", "))
+        }
+        QueryError::NodeTooOld { found, required, path, .. } => {
+            format!("Node {} at {} is too old; the agent SDK needs {}", found, path, required)
+        }
+        QueryError::LowDiskSpace { path, free_bytes, floor_bytes } => format!(
+            "Only {} MB free on the volume of {} (at least {} MB needed)",
+            free_bytes / (1024 * 1024),
+            path,
+            floor_bytes / (1024 * 1024)
+        ),
+        QueryError::SessionInUse { session_id, by, .. } => match by {
+            SessionHolder::Mensa { query_id, .. } => format!("Session {} is in use by query {}", session_id, query_id),
+            SessionHolder::ClaudeCli { pid, .. } => format!("Session {} is in use by Claude Code (pid {})", session_id, pid),
+        },
+    }
+}
+
+/// Record a member that never produced a run of its own, so the group's history stays complete
+async fn record_unstarted(app: &tauri::AppHandle, group_id: &str, query_id: &str, working_dir: &str, reason: &str) {
+    let now = history::now_secs();
+    let record = history::QueryRecord {
+        query_id: query_id.to_string(),
+        working_dir: working_dir.to_string(),
+        preset: None,
+        remapped_from: None,
+        started_at: now,
+        finished_at: now,
+        exit_code: None,
+        cancelled: reason == "cancelled",
+        changed_files: Vec::new(),
+        hooks: None,
+        cost_usd: None,
+        terminal_reason: Some(reason.to_string()),
+        trimmed_resume: None,
+        group_id: Some(group_id.to_string()),
+        stderr_suppressed: None,
+        session_id: None,
+        model: None,
+        plan_approval: None,
+        read_only: None,
+        ephemeral: None,
+    };
+    if let Err(e) = history::append_record(app, &record).await {
+        eprintln!("[mensa] {}", e);
+    }
+}
+
+/// Slots a group may fill now: the global limit minus queries outside the group, capped by the group's own
+async fn free_slots(app: &tauri::AppHandle, group_ids: &HashSet<String>, running: usize, max_parallel: Option<usize>) -> usize {
+    let state = app.state::<AppState>();
+    let limit = settings::load(app, &state.settings)
+        .await
+        .map(|s| s.max_concurrent_queries)
+        .unwrap_or(settings::DEFAULT_MAX_CONCURRENT_QUERIES) as usize;
+    let others = state
+        .active_queries
+        .lock()
+        .await
+        .keys()
+        .filter(|id| !group_ids.contains(*id))
+        .count();
+    let global = limit.saturating_sub(others + running);
+    match max_parallel {
+        Some(max) => global.min(max.saturating_sub(running)),
+        None => global,
+    }
+}
+
+/// Start members as slots free up, collect their outcomes and emit `claude-group-done`.
+/// `shutdown` (the app exiting) cancels the group like `token` does.
+async fn run_group(
+    app: tauri::AppHandle,
+    group_id: String,
+    members: Vec<Member>,
+    options: GroupOptions,
+    token: CancellationToken,
+    shutdown: CancellationToken,
+) {
+    let group_ids: HashSet<String> = members.iter().map(|m| m.query_id.clone()).collect();
+    let mut results: Vec<Option<GroupResult>> = members.iter().map(|_| None).collect();
+    let mut queued: VecDeque<Member> = members.into();
+    let mut running: HashMap<usize, (String, String)> = HashMap::new();
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel::<(usize, Option<String>)>();
+
+    loop {
+        if token.is_cancelled() || shutdown.is_cancelled() {
+            if let Some(group) = app
+                .state::<AppState>()
+                .groups
+                .groups
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .get_mut(&group_id)
+            {
+                group.queued.clear();
+            }
+            // A member may register its process only after cancel_group looked
+            for (query_id, _) in running.values() {
+                query_cancel::stop_query(&app, query_id).await;
+            }
+        }
+        // Members cancelled while queued, one by one or with the group, never start
+        let cancelled = app.state::<AppState>().groups.take_cancelled(&group_id, &mut queued);
+        for member in cancelled {
+            let index = member.index;
+            results[index] = Some(finish_cancelled(&app, &group_id, member).await);
+        }
+
+        let mut slots = if queued.is_empty() {
+            0
+        } else {
+            free_slots(&app, &group_ids, running.len(), options.max_parallel).await
+        };
+        while slots > 0 {
+            let Some(member) = queued.pop_front() else {
+                break;
+            };
+            if !app.state::<AppState>().groups.claim_start(&group_id, &member.query_id) {
+                let index = member.index;
+                results[index] = Some(finish_cancelled(&app, &group_id, member).await);
+                continue;
+            }
+            slots -= 1;
+            running.insert(member.index, (member.query_id.clone(), member.input.working_dir.clone()));
+            let _ = app.emit("claude-group-query-started", serde_json::json!({
+                "group_id": group_id,
+                "query_id": member.query_id,
+                "working_dir": member.input.working_dir
+            }));
+            let app = app.clone();
+            let done_tx = done_tx.clone();
+            let tasks = app.state::<AppState>().tasks.clone();
+            tasks.spawn(format!("query group {} member {}", group_id, member.query_id), |_| async move {
+                let query_id = member.query_id.clone();
+                let error = match crate::start_query(app.clone(), query_id.clone(), member.input).await {
+                    Ok(_) => None,
+                    Err(e) => {
+                        let message = error_message(&e);
+                        replay::emit(&app, &query_id, "claude-stderr", StreamPayload {
+                            query_id: query_id.clone(),
+                            data: message.clone(),
+                        })
+                        .await;
+                        replay::emit(&app, &query_id, "claude-done", serde_json::json!({
+                            "query_id": query_id,
+                            "code": -1
+                        }))
+                        .await;
+                        replay::finish(&app, &query_id).await;
+                        Some(message)
+                    }
+                };
+                let _ = done_tx.send((member.index, error));
+            });
+        }
+
+        if running.is_empty() && queued.is_empty() {
+            break;
+        }
+        // Wake up regularly too: slots free up elsewhere, and a cancel has to reach late starters
+        let finished = tokio::select! {
+            finished = done_rx.recv() => finished,
+            _ = tokio::time::sleep(SLOT_POLL_INTERVAL) => continue,
+        };
+        let Some((index, error)) = finished else {
+            break;
+        };
+        let Some((query_id, working_dir)) = running.remove(&index) else {
+            continue;
+        };
+        let result = match error {
+            Some(error) => {
+                record_unstarted(&app, &group_id, &query_id, &working_dir, "failed").await;
+                GroupResult {
+                    query_id,
+                    working_dir,
+                    exit_reason: "failed".to_string(),
+                    cost: None,
+                    error: Some(error),
+                }
+            }
+            None => {
+                let record = history::find_record(&app, &query_id).await.ok().flatten();
+                GroupResult {
+                    exit_reason: record
+                        .as_ref()
+                        .and_then(|r| r.terminal_reason.clone())
+                        .unwrap_or_else(|| "completed".to_string()),
+                    cost: record.and_then(|r| r.cost_usd),
+                    query_id,
+                    working_dir,
+                    error: None,
+                }
+            }
+        };
+        results[index] = Some(result);
+    }
+
+    let state = app.state::<AppState>();
+    state.groups.groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&group_id);
+    state
+        .groups
+        .by_query
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .retain(|_, group| *group != group_id);
+    let _ = app.emit("claude-group-done", serde_json::json!({
+        "group_id": group_id,
+        "results": results.into_iter().flatten().collect::<Vec<_>>()
+    }));
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Run one prompt per workspace as a group. Every workspace is checked before anything starts;
+/// members then start through the normal query pipeline as the concurrency limit allows, each
+/// with its own session. Their events carry `group_id`, and `claude-group-done` reports every
+/// member once the last one finishes. Returns right away with the ids.
+#[tauri::command]
+pub async fn query_claude_multi(
+    app: tauri::AppHandle,
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    prompts: Vec<GroupQuery>,
+    group_options: Option<GroupOptions>,
+) -> Result<GroupStarted, String> {
+    if prompts.is_empty() {
+        return Err("A query group needs at least one prompt".to_string());
+    }
+    let options = group_options.unwrap_or_default();
+    if options.max_parallel == Some(0) {
+        return Err("maxParallel must be at least 1".to_string());
+    }
+
+    let problems: Vec<String> = prompts
+        .iter()
+        .filter_map(|query| {
+            if query.prompt.trim().is_empty() {
+                return Some(format!("{}: prompt is empty", query.working_dir));
+            }
+            workspace::canonical_dir(&query.working_dir).err()
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(format!("Failed to start query group: {}", problems.join("; ")));
+    }
+
+    let group_id = uuid::Uuid::new_v4().to_string();
+    let members: Vec<Member> = prompts
+        .into_iter()
+        .enumerate()
+        .map(|(index, query)| Member {
+            index,
+            query_id: uuid::Uuid::new_v4().to_string(),
+            input: QueryInput {
+                prompt: query.prompt,
+                working_dir: query.working_dir,
+                config: query.config,
+                owner: Some(window.label().to_string()),
+                ..Default::default()
+            },
+        })
+        .collect();
+    let query_ids: Vec<String> = members.iter().map(|m| m.query_id.clone()).collect();
+
+    let token = CancellationToken::default();
+    state.groups.register(&group_id, &query_ids, token.clone());
+
+    let name = format!("query group {}", group_id);
+    state.tasks.spawn(name, |shutdown| run_group(app, group_id.clone(), members, options, token, shutdown));
+    Ok(GroupStarted { group_id, query_ids })
+}
+
+/// Cancel a group: queued members never start and running ones are stopped.
+/// False if the group already finished.
+#[tauri::command]
+pub async fn cancel_group(app: tauri::AppHandle, state: State<'_, AppState>, group_id: String) -> Result<bool, String> {
+    let query_ids = {
+        let mut groups = state.groups.groups.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(group) = groups.get_mut(&group_id) else {
+            return Ok(false);
+        };
+        group.token.cancel();
+        group.queued.clear();
+        group.query_ids.clone()
+    };
+    for query_id in &query_ids {
+        query_cancel::stop_query(&app, query_id).await;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn members(names: &[&str]) -> VecDeque<Member> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| Member {
+                index,
+                query_id: name.to_string(),
+                input: QueryInput::default(),
+            })
+            .collect()
+    }
+
+    fn query_ids(members: &[Member]) -> Vec<&str> {
+        members.iter().map(|m| m.query_id.as_str()).collect()
+    }
+
+    #[test]
+    fn members_are_found_by_query_id() {
+        let groups = QueryGroups::default();
+        groups.register("g1", &ids(&["a", "b"]), CancellationToken::default());
+        groups.register("g2", &ids(&["c"]), CancellationToken::default());
+
+        assert_eq!(groups.group_of("a").as_deref(), Some("g1"));
+        assert_eq!(groups.group_of("c").as_deref(), Some("g2"));
+        assert_eq!(groups.group_of("z"), None);
+        assert_eq!(groups.cancel_queued("z"), None);
+        assert!(!groups.claim_start("missing", "a"));
+    }
+
+    /// A queued member is ended by whichever of cancel_queued, take_cancelled and claim_start
+    /// reaches it first; the others find it gone, so it gets a single terminal event
+    #[test]
+    fn a_queued_member_is_ended_by_one_path_only() {
+        let groups = QueryGroups::default();
+        groups.register("g1", &ids(&["a", "b", "c"]), CancellationToken::default());
+        let mut queued = members(&["a", "b", "c"]);
+
+        // b is cancelled while waiting: once
+        assert_eq!(groups.cancel_queued("b").as_deref(), Some("g1"));
+        assert_eq!(groups.cancel_queued("b"), None);
+        assert!(!groups.claim_start("g1", "b"));
+
+        // The group's next pass ends it and keeps the rest in order
+        let cancelled = groups.take_cancelled("g1", &mut queued);
+        assert_eq!(query_ids(&cancelled), ["b"]);
+        assert_eq!(query_ids(queued.make_contiguous()), ["a", "c"]);
+        assert!(groups.take_cancelled("g1", &mut queued).is_empty());
+
+        // a starts: it can no longer be cancelled as queued, nor started twice
+        assert!(groups.claim_start("g1", "a"));
+        assert_eq!(groups.cancel_queued("a"), None);
+        assert!(!groups.claim_start("g1", "a"));
+
+        // c is cancelled between the pass and its start: the start loses
+        assert_eq!(groups.cancel_queued("c").as_deref(), Some("g1"));
+        assert!(!groups.claim_start("g1", "c"));
+    }
+
+    #[test]
+    fn cancelling_every_group_ends_all_queued_members_once() {
+        let groups = QueryGroups::default();
+        let token = CancellationToken::default();
+        groups.register("g1", &ids(&["a", "b"]), token.clone());
+        groups.register("g2", &ids(&["c"]), CancellationToken::default());
+        assert!(groups.claim_start("g1", "a"));
+
+        assert_eq!(cancel_all(&groups), 2);
+        assert!(token.is_cancelled());
+
+        // Nothing is left to cancel one by one, and nothing starts
+        assert_eq!(groups.cancel_queued("b"), None);
+        assert!(!groups.claim_start("g2", "c"));
+
+        // Each group's pass takes all of its queued members
+        let mut queued = members(&["b"]);
+        assert_eq!(query_ids(&groups.take_cancelled("g1", &mut queued)), ["b"]);
+        assert!(queued.is_empty());
+    }
+
+    #[test]
+    fn a_finished_group_keeps_nothing_queued() {
+        let groups = QueryGroups::default();
+        let mut queued = members(&["a"]);
+        // A group already removed: every member it still holds counts as cancelled
+        assert_eq!(query_ids(&groups.take_cancelled("gone", &mut queued)), ["a"]);
+    }
+}
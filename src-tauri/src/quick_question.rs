@@ -0,0 +1,296 @@
+// mensa - Quick Question Module
+// One-turn, tool-less queries that leave no session behind
+
+use crate::AppState;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// `mode` of `query_claude` that asks a quick question
+pub const QUICK_MODE: &str = "quick";
+
+/// How long a quick question may run before it is stopped
+pub const QUICK_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Terminal reason of a quick question stopped at QUICK_TIMEOUT
+pub const TIMED_OUT: &str = "timed_out";
+
+/// Every tool the query script knows by default; a quick question may use none of them
+const ALL_TOOLS: &[&str] = &[
+    "Read", "Write", "Edit", "MultiEdit", "NotebookEdit", "Bash", "Glob", "Grep", "WebSearch", "WebFetch",
+    "Task", "TodoWrite", "Skill",
+];
+
+/// Quick questions by query id, from start until their session is deleted
+#[derive(Default)]
+pub struct QuickSessions {
+    entries: Mutex<HashMap<String, QuickRun>>,
+}
+
+struct QuickRun {
+    working_dir: String,
+    /// Known once the agent reports it
+    session_id: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Whether a `query_claude` mode asks for a quick question; None is a normal query
+pub fn is_quick(mode: Option<&str>) -> Result<bool, String> {
+    match mode {
+        None => Ok(false),
+        Some(QUICK_MODE) => Ok(true),
+        Some(other) => Err(format!("Unknown query mode: {}", other)),
+    }
+}
+
+/// The config of a quick question: one turn, no tools, skills or MCP servers, and no session
+/// written where the SDK supports it. Only the model and cost ceiling carry over.
+pub fn quick_config(config: Option<&str>) -> Result<String, String> {
+    let config: Map<String, Value> = match config {
+        Some(config) => serde_json::from_str(config).map_err(|e| format!("Failed to parse query config: {}", e))?,
+        None => Map::new(),
+    };
+    let mut quick = serde_json::json!({
+        "permissionMode": "default",
+        "maxTurns": 1,
+        "allowedTools": [],
+        "disallowedTools": ALL_TOOLS,
+        "mcpServers": [],
+        "enableSkills": false,
+        "settingSources": [],
+        "persistSession": false
+    });
+    for key in ["model", "maxCostUsd"] {
+        if let Some(value) = config.get(key) {
+            quick[key] = value.clone();
+        }
+    }
+    serde_json::to_string(&quick).map_err(|e| format!("Failed to serialize query config: {}", e))
+}
+
+impl QuickSessions {
+    pub fn contains(&self, query_id: &str) -> bool {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).contains_key(query_id)
+    }
+
+    /// Session ids of quick questions, kept out of session lists until they are deleted
+    pub fn hidden(&self) -> HashSet<String> {
+        let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.values().filter_map(|run| run.session_id.clone()).collect()
+    }
+
+    fn begin(&self, query_id: &str, working_dir: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        entries.insert(query_id.to_string(), QuickRun {
+            working_dir: working_dir.to_string(),
+            session_id: None,
+        });
+    }
+
+    fn record_session(&self, query_id: &str, session_id: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(run) = entries.get_mut(query_id) {
+            run.session_id = Some(session_id.to_string());
+        }
+    }
+
+    /// Delete the session a quick question left with `delete` (working dir, session id) and
+    /// stop tracking it. One that can't be deleted stays tracked, and so hidden.
+    async fn finish_with<F, Fut>(&self, query_id: &str, delete: F)
+    where
+        F: FnOnce(String, String) -> Fut,
+        Fut: Future<Output = Result<(), String>>,
+    {
+        let run = {
+            let entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            match entries.get(query_id) {
+                Some(run) => (run.working_dir.clone(), run.session_id.clone()),
+                None => return,
+            }
+        };
+        if let (working_dir, Some(session_id)) = run {
+            if let Err(e) = delete(working_dir, session_id).await {
+                eprintln!("[mensa] Could not delete the session of quick question {}: {}", query_id, e);
+                return;
+            }
+        }
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(query_id);
+    }
+}
+
+/// Track a quick question about to start
+pub fn begin(app: &tauri::AppHandle, query_id: &str, working_dir: &str) {
+    app.state::<AppState>().quick_sessions.begin(query_id, working_dir);
+}
+
+/// Note the session a quick question writes to, so it is hidden from here on
+pub fn record_session(app: &tauri::AppHandle, query_id: &str, session_id: &str) {
+    app.state::<AppState>().quick_sessions.record_session(query_id, session_id);
+}
+
+/// Delete whatever session a finished (or cancelled, or failed) quick question left, through
+/// the same path as `delete_session`. A session that can't be deleted stays hidden.
+pub async fn finish(app: &tauri::AppHandle, query_id: &str) {
+    let state = app.state::<AppState>();
+    state
+        .quick_sessions
+        .finish_with(query_id, |working_dir, session_id| async move {
+            crate::remove_session(app, &working_dir, &session_id).await.map(|_| ()).map_err(|e| e.to_string())
+        })
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch_home, write, ScratchHome};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    fn transcript(session_id: &str, cwd: &str, prompt: &str) -> String {
+        format!(
+            "{}\n",
+            serde_json::json!({
+                "type": "user",
+                "cwd": cwd,
+                "sessionId": session_id,
+                "timestamp": "2026-01-05T10:00:00.000Z",
+                "message": { "role": "user", "content": prompt },
+            })
+        )
+    }
+
+    /// A workspace under the scratch home holding one normal session, and its project dir
+    fn workspace(home: &ScratchHome) -> (String, PathBuf) {
+        let workspace = home.dir.path().join("app");
+        std::fs::create_dir_all(&workspace).unwrap();
+        let workspace = workspace.to_string_lossy().to_string();
+        let project_dir = home.claude().project_dir(&workspace);
+        write(&project_dir, "normal.jsonl", transcript("normal", &workspace, "Refactor the parser"));
+        (workspace, project_dir)
+    }
+
+    async fn listed(workspace: &str, sessions: &QuickSessions) -> Vec<String> {
+        let mut ids: Vec<String> = crate::session_entries(workspace.to_string(), None, &sessions.hidden())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.session_id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Deletes the transcript as `remove_session` does, noting each (working dir, session id)
+    fn deleter(
+        project_dir: &Path,
+        calls: &Arc<Mutex<Vec<(String, String)>>>,
+    ) -> impl FnOnce(String, String) -> std::future::Ready<Result<(), String>> {
+        let (project_dir, calls) = (project_dir.to_path_buf(), calls.clone());
+        move |working_dir, session_id| {
+            let removed = std::fs::remove_file(project_dir.join(format!("{}.jsonl", session_id))).map_err(|e| e.to_string());
+            calls.lock().unwrap().push((working_dir, session_id));
+            std::future::ready(removed)
+        }
+    }
+
+    #[test]
+    fn only_the_quick_mode_is_known() {
+        assert_eq!(is_quick(None), Ok(false));
+        assert_eq!(is_quick(Some(QUICK_MODE)), Ok(true));
+        assert!(is_quick(Some("agentic")).is_err());
+
+        let config: Value = serde_json::from_str(&quick_config(Some(r#"{"model":"opus","maxTurns":40,"allowedTools":["Bash"]}"#)).unwrap()).unwrap();
+        assert_eq!((config["model"].as_str(), config["maxTurns"].as_u64()), (Some("opus"), Some(1)));
+        assert_eq!(config["allowedTools"], serde_json::json!([]));
+        assert_eq!(config["persistSession"], false);
+    }
+
+    #[tokio::test]
+    async fn quick_session_is_hidden_until_deleted() {
+        let home = scratch_home();
+        let (workspace, project_dir) = workspace(&home);
+        let sessions = QuickSessions::default();
+
+        // Started, but the agent hasn't named its session yet
+        sessions.begin("q1", &workspace);
+        assert!(sessions.contains("q1"));
+        write(&project_dir, "quick.jsonl", transcript("quick", &workspace, "What does parse_line return?"));
+        assert_eq!(listed(&workspace, &sessions).await, ["normal", "quick"]);
+
+        // Once it does, the session is left out of the list
+        sessions.record_session("q1", "quick");
+        assert_eq!(sessions.hidden(), HashSet::from(["quick".to_string()]));
+        assert_eq!(listed(&workspace, &sessions).await, ["normal"]);
+
+        // Finishing deletes it and forgets the question
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        sessions.finish_with("q1", deleter(&project_dir, &calls)).await;
+        assert_eq!(calls.lock().unwrap().clone(), [(workspace.clone(), "quick".to_string())]);
+        assert!(!project_dir.join("quick.jsonl").exists());
+        assert!(!sessions.contains("q1") && sessions.hidden().is_empty());
+        assert_eq!(listed(&workspace, &sessions).await, ["normal"]);
+
+        // Only the question's own session goes, and only once
+        sessions.finish_with("q1", deleter(&project_dir, &calls)).await;
+        sessions.finish_with("unknown", deleter(&project_dir, &calls)).await;
+        assert_eq!(calls.lock().unwrap().len(), 1);
+        assert!(project_dir.join("normal.jsonl").exists());
+    }
+
+    #[tokio::test]
+    async fn cancelled_quick_question_still_deletes_its_partial_session() {
+        let home = scratch_home();
+        let (workspace, project_dir) = workspace(&home);
+        let sessions = QuickSessions::default();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        // Cancelled mid-run: the agent had named its session and written part of it when stopped;
+        // the run ends through `finish` either way
+        sessions.begin("q1", &workspace);
+        sessions.record_session("q1", "partial");
+        write(&project_dir, "partial.jsonl", &transcript("partial", &workspace, "Why is this slow?")[..40]);
+        sessions.finish_with("q1", deleter(&project_dir, &calls)).await;
+        assert!(!project_dir.join("partial.jsonl").exists());
+        assert!(!sessions.contains("q1"));
+        assert_eq!(listed(&workspace, &sessions).await, ["normal"]);
+
+        // Cancelled before the agent named a session: nothing to delete, nothing left tracked
+        sessions.begin("q2", &workspace);
+        sessions.finish_with("q2", deleter(&project_dir, &calls)).await;
+        assert_eq!(calls.lock().unwrap().len(), 1);
+        assert!(!sessions.contains("q2"));
+    }
+
+    #[tokio::test]
+    async fn session_that_cant_be_deleted_stays_hidden() {
+        let home = scratch_home();
+        let (workspace, project_dir) = workspace(&home);
+        let sessions = QuickSessions::default();
+
+        sessions.begin("q1", &workspace);
+        sessions.record_session("q1", "stuck");
+        write(&project_dir, "stuck.jsonl", transcript("stuck", &workspace, "Is this cached?"));
+        sessions
+            .finish_with("q1", |_, _| std::future::ready(Err("permission denied".to_string())))
+            .await;
+        assert!(sessions.contains("q1"));
+        assert_eq!(listed(&workspace, &sessions).await, ["normal"]);
+
+        // A later attempt that succeeds lets it go
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        sessions.finish_with("q1", deleter(&project_dir, &calls)).await;
+        assert!(!sessions.contains("q1"));
+        assert_eq!(listed(&workspace, &sessions).await, ["normal"]);
+    }
+}
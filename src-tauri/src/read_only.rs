@@ -0,0 +1,472 @@
+// mensa - Read-Only Module
+// Demo lock for shared machines: mutating commands are refused and queries run in plan mode
+
+use crate::store::JsonStore;
+use crate::{fsutil, settings, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Commands refused while read-only mode is on: they change repos, sessions, plans or
+/// workspaces, post to a forge, replace the app's data, or change stored credentials and
+/// what queries may do
+pub const MUTATING_COMMANDS: &[&str] = &[
+    "apply_patch_text",
+    "approve_plan_and_continue",
+    "bootstrap_workspace",
+    "create_pull_request",
+    "create_workspace_from_template",
+    "delete_session",
+    "delete_stored_secret",
+    "export_app_data",
+    "generate_missing_titles",
+    "generate_session_title",
+    "git_commit",
+    "git_create_branch_and_move_changes",
+    "git_discard",
+    "git_fetch",
+    "git_fetch_deepen",
+    "git_gc",
+    "git_pull",
+    "git_push",
+    "git_rebase_abort",
+    "git_rebase_continue",
+    "git_rebase_execute",
+    "git_set_upstream",
+    "git_stage",
+    "git_unstage",
+    "gitignore_add",
+    "import_app_data",
+    "install_update",
+    "migrate_workspace_sessions",
+    "post_pr_review",
+    "reject_plan",
+    "rename_session",
+    "repair_session_file",
+    "restore_file_checkpoint",
+    "save_query_preset",
+    "set_commit_identity",
+    "set_preferred_runtime_path",
+    "set_proxy_credentials",
+    "set_sensitive_patterns",
+    "set_stored_secret",
+    "set_tool_policy",
+    "set_workspace_query_defaults",
+    "test_integration_hook",
+];
+
+/// Commands refused while read-only mode is on when the named argument is given (not null or
+/// false): they only write a file then, in the workspace or at a path the caller picks
+pub const MUTATING_WITH_ARGUMENT: &[(&str, &str)] = &[("export_session", "destination"), ("export_tool_policies", "write")];
+
+/// Settings `update_settings` refuses to change while read-only mode is on: they pick what
+/// mensa runs (hooks, the node binary) and where its traffic goes
+pub const LOCKED_SETTINGS: &[&str] = &["integrationHooks", "nodePath", "proxy"];
+
+/// Commands that change nothing read-only mode protects. A command in neither list is refused
+/// like a mutating one, so a new command stays locked until it is classified here.
+pub const SAFE_COMMANDS: &[&str] = &[
+    "ack_query_events",
+    "add_message_bookmark",
+    "analyze_session_context",
+    "analyze_workspace_health",
+    "annotations_to_prompt",
+    "build_review_fix_prompt",
+    "cancel_git_operation",
+    "cancel_group",
+    "cancel_operation",
+    "cancel_query",
+    "check_claude_permissions",
+    "check_disk_space",
+    "check_for_updates",
+    "check_gh_cli_available",
+    "check_runtime_health",
+    "clear_followup",
+    "clear_prompt_history",
+    "collect_pr_context",
+    "compare_sessions",
+    "delete_prompt_history_entry",
+    "delete_prompt_template",
+    "delete_query_preset",
+    "delete_review_draft",
+    "detect_identity_mismatch",
+    "detect_sensitive_files",
+    "evaluate_tool_policy",
+    "expand_prompt_template",
+    "export_session",
+    "export_tool_policies",
+    "fetch_pr_batch",
+    "fetch_pr_diff",
+    "fetch_pr_info",
+    "follow_file",
+    "fuzzy_match_files",
+    "get_branch_protection",
+    "get_commit_identity",
+    "get_gh_auth_status",
+    "get_last_seq",
+    "get_query_changed_files",
+    "get_query_context_usage",
+    "get_query_timeline",
+    "get_query_tool_output",
+    "get_read_only_status",
+    "get_repo_capabilities",
+    "get_repo_info",
+    "get_review_draft",
+    "get_sensitive_patterns",
+    "get_session_cache_stats",
+    "get_settings",
+    "get_status_filters",
+    "get_tool_output",
+    "get_tool_policies",
+    "get_trimmed_session_origin",
+    "get_usage_analytics",
+    "get_workspace_state",
+    "git_branch_info",
+    "git_check_attr",
+    "git_diff",
+    "git_diff_commits",
+    "git_diff_stats",
+    "git_diff_stats_range",
+    "git_is_dirty",
+    "git_list_branches",
+    "git_log",
+    "git_log_paged",
+    "git_precommit_scan",
+    "git_rebase_plan",
+    "git_status",
+    "git_status_summary",
+    "ingest_clipboard_image",
+    "invalidate_file_index",
+    "list_active_queries",
+    "list_background_tasks",
+    "list_file_checkpoints",
+    "list_message_bookmarks",
+    "list_models",
+    "list_plan_files",
+    "list_plan_files_paged",
+    "list_prompt_templates",
+    "list_prs",
+    "list_prs_paged",
+    "list_query_history",
+    "list_query_history_paged",
+    "list_query_presets",
+    "list_replay_buffers",
+    "list_running_operations",
+    "list_sessions",
+    "list_sessions_paged",
+    "list_stored_secrets",
+    "list_workspace_templates",
+    "load_session_messages",
+    "load_session_messages_page",
+    "match_threads_to_changes",
+    "open_session_at",
+    "plan_file_hash",
+    "preflight_gh_feature",
+    "query_claude",
+    "query_claude_multi",
+    "queue_followup",
+    "read_file_checkpoint",
+    "read_plan_file",
+    "register_workspace",
+    "remove_message_bookmark",
+    "render_markdown",
+    "replay_query_events",
+    "reveal_app_location",
+    "save_prompt_template",
+    "save_review_draft",
+    "scan_code_annotations",
+    "search_prompt_history",
+    "search_prompt_history_paged",
+    "search_sessions",
+    "set_query_preset_hidden",
+    "set_read_only",
+    "set_status_filters",
+    "set_workspace_state",
+    "subscribe_query",
+    "test_proxy",
+    "unfollow_file",
+    "update_settings",
+    "validate_prompt_references",
+    "workspace_digest",
+    "workspace_overview",
+];
+
+/// Tools that write files, run commands or hand work to subagents; stripped from every query
+const WRITE_TOOLS: &[&str] = &["Write", "Edit", "MultiEdit", "NotebookEdit", "Bash", "Task"];
+
+/// What the query script allows when a config names no tools (see `scripts/claude-query.mjs`)
+const DEFAULT_TOOLS: &[&str] = &[
+    "Read", "Write", "Edit", "Bash", "Glob", "Grep", "WebSearch", "WebFetch", "Task", "TodoWrite", "Skill",
+];
+
+/// The passphrase that unlocks read-only mode, salted and hashed
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ReadOnlyLock {
+    salt: String,
+    hash: String,
+}
+
+static LOCK_FILE: JsonStore<ReadOnlyLock> = JsonStore::new("read-only lock");
+
+/// Returned by `get_read_only_status` and `set_read_only`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadOnlyStatus {
+    pub enabled: bool,
+    /// Commands refused while enabled, so the UI can hide their controls
+    pub mutating_commands: Vec<String>,
+}
+
+/// Error returned by commands refused in read-only mode, and by `set_read_only`
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ReadOnlyError {
+    /// Read-only mode is on and `command` would change something
+    ReadOnlyMode { command: String, message: String },
+    /// The passphrase doesn't match the one set when read-only mode was enabled
+    WrongPassphrase { message: String },
+    Failed { message: String },
+}
+
+impl From<String> for ReadOnlyError {
+    fn from(message: String) -> Self {
+        ReadOnlyError::Failed { message }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn lock_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("read-only-lock.json"))
+}
+
+fn passphrase_hash(salt: &str, passphrase: &str) -> String {
+    fsutil::sha256_hex(format!("{}:{}", salt, passphrase).as_bytes())
+}
+
+/// Whether read-only mode is on (the settings are loaded at startup, before any command runs)
+pub fn enabled(app: &tauri::AppHandle) -> bool {
+    settings::read_only(&app.state::<AppState>().settings)
+}
+
+/// Whether `command` is refused in read-only mode: listed as mutating, or not classified at all
+pub fn is_mutating(command: &str) -> bool {
+    MUTATING_COMMANDS.contains(&command) || !SAFE_COMMANDS.contains(&command)
+}
+
+/// Whether a call of `command` with `args` (its JSON arguments) is refused in read-only mode
+fn is_mutating_call(command: &str, args: Option<&Value>) -> bool {
+    if let Some((_, argument)) = MUTATING_WITH_ARGUMENT.iter().find(|(name, _)| *name == command) {
+        return match args.and_then(|args| args.get(*argument)) {
+            None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+            Some(_) => true,
+        };
+    }
+    is_mutating(command)
+}
+
+/// Refuse a mutating command while read-only mode is on; hands every other invoke back to run
+pub fn guard(invoke: tauri::ipc::Invoke) -> Option<tauri::ipc::Invoke> {
+    let command = invoke.message.command().to_string();
+    let args = match invoke.message.payload() {
+        tauri::ipc::InvokeBody::Json(args) => Some(args),
+        _ => None,
+    };
+    if !is_mutating_call(&command, args) || !enabled(invoke.message.webview_ref().app_handle()) {
+        return Some(invoke);
+    }
+    invoke.resolver.reject(ReadOnlyError::ReadOnlyMode {
+        message: format!("{} is disabled while mensa is in read-only mode", command),
+        command,
+    });
+    None
+}
+
+/// A query config narrowed to read-only use: plan mode, write-capable tools removed from
+/// allowedTools (the script's default set when none are named), and no MCP servers, whose
+/// tools can't be vetted
+pub fn constrain_config(config: Option<&str>) -> Result<String, String> {
+    let mut config: Map<String, Value> = match config {
+        Some(config) => serde_json::from_str(config).map_err(|e| format!("Failed to parse query config: {}", e))?,
+        None => Map::new(),
+    };
+    let tools: Vec<String> = match config.get("allowedTools").and_then(|t| t.as_array()) {
+        Some(tools) => tools.iter().filter_map(|t| t.as_str().map(str::to_string)).collect(),
+        None => DEFAULT_TOOLS.iter().map(|t| t.to_string()).collect(),
+    };
+    let tools: Vec<String> = tools
+        .into_iter()
+        .filter(|t| !WRITE_TOOLS.contains(&t.as_str()) && !t.starts_with("mcp__"))
+        .collect();
+    config.insert("permissionMode".to_string(), Value::from("plan"));
+    config.insert("allowedTools".to_string(), Value::from(tools));
+    config.remove("mcpServers");
+    serde_json::to_string(&Value::Object(config)).map_err(|e| format!("Failed to serialize query config: {}", e))
+}
+
+fn status(enabled: bool) -> ReadOnlyStatus {
+    ReadOnlyStatus {
+        enabled,
+        mutating_commands: MUTATING_COMMANDS.iter().map(|c| c.to_string()).collect(),
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+#[tauri::command]
+pub async fn get_read_only_status(app: tauri::AppHandle) -> Result<ReadOnlyStatus, String> {
+    let settings = settings::load(&app, &app.state::<AppState>().settings).await?;
+    Ok(status(settings.read_only))
+}
+
+/// Turn read-only mode on or off. Enabling sets the passphrase; disabling (or enabling again)
+/// needs the same one. Update_settings can't change the flag.
+#[tauri::command]
+pub async fn set_read_only(app: tauri::AppHandle, enabled: bool, passphrase: String) -> Result<ReadOnlyStatus, ReadOnlyError> {
+    let path = lock_path(&app)?;
+    let guard = LOCK_FILE.lock(&app, path).await;
+    let current = settings::load(&app, &app.state::<AppState>().settings).await?;
+
+    if current.read_only {
+        let lock = guard.load().await?;
+        if lock.hash.is_empty() || passphrase_hash(&lock.salt, &passphrase) != lock.hash {
+            return Err(ReadOnlyError::WrongPassphrase {
+                message: "The passphrase does not match the one set when read-only mode was enabled".to_string(),
+            });
+        }
+        if enabled {
+            return Ok(status(true));
+        }
+        settings::store_read_only(&app, false).await?;
+        guard.remove().await?;
+        return Ok(status(false));
+    }
+
+    if !enabled {
+        return Ok(status(false));
+    }
+    if passphrase.trim().is_empty() {
+        return Err("Enabling read-only mode needs a passphrase".to_string().into());
+    }
+    let salt = uuid::Uuid::new_v4().to_string();
+    let hash = passphrase_hash(&salt, &passphrase);
+    guard.save(&ReadOnlyLock { salt, hash }).await?;
+    settings::store_read_only(&app, true).await?;
+    Ok(status(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    /// Command names in `fn commands`' generate_handler! list, as the frontend invokes them
+    fn registered_commands() -> Vec<String> {
+        let source = include_str!("lib.rs");
+        let start = source.find("tauri::generate_handler![").expect("generate_handler! in lib.rs");
+        let list = &source[start + "tauri::generate_handler![".len()..];
+        let list = &list[..list.find("];").expect("end of generate_handler!")];
+        list.lines()
+            .map(|line| line.split("//").next().unwrap_or_default().trim().trim_end_matches(','))
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| entry.rsplit("::").next().unwrap_or(entry).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn every_command_is_classified_exactly_once() {
+        let commands = registered_commands();
+        assert!(commands.len() > 100, "parsed only {} commands", commands.len());
+        for command in &commands {
+            let mutating = MUTATING_COMMANDS.contains(&command.as_str());
+            let safe = SAFE_COMMANDS.contains(&command.as_str());
+            assert!(mutating != safe, "{} must be in exactly one of MUTATING_COMMANDS and SAFE_COMMANDS", command);
+        }
+
+        let registered: HashSet<&str> = commands.iter().map(String::as_str).collect();
+        for listed in MUTATING_COMMANDS.iter().chain(SAFE_COMMANDS) {
+            assert!(registered.contains(listed), "{} is classified but not registered", listed);
+        }
+        for (command, _) in MUTATING_WITH_ARGUMENT {
+            assert!(SAFE_COMMANDS.contains(command), "{} must be listed as safe without its argument", command);
+        }
+    }
+
+    #[test]
+    fn unclassified_commands_are_refused() {
+        assert!(is_mutating("git_commit"));
+        assert!(!is_mutating("git_status"));
+        assert!(is_mutating("some_new_command"));
+
+        // Credentials, presets and policies change what a later session gets to do
+        for command in [
+            "delete_stored_secret",
+            "save_query_preset",
+            "set_proxy_credentials",
+            "set_sensitive_patterns",
+            "set_stored_secret",
+            "set_tool_policy",
+            "set_workspace_query_defaults",
+        ] {
+            assert!(is_mutating(command), "{}", command);
+        }
+    }
+
+    #[test]
+    fn writes_through_an_argument_are_refused() {
+        let write = json!({ "workingDir": "/repo", "write": true });
+        assert!(is_mutating_call("export_tool_policies", Some(&write)));
+        assert!(!is_mutating_call("export_tool_policies", Some(&json!({ "workingDir": "/repo", "write": false }))));
+        assert!(!is_mutating_call("export_tool_policies", Some(&json!({ "workingDir": "/repo" }))));
+
+        assert!(is_mutating_call("export_session", Some(&json!({ "destination": "/tmp/out.md" }))));
+        assert!(!is_mutating_call("export_session", Some(&json!({ "destination": null }))));
+        assert!(!is_mutating_call("export_session", None));
+
+        for command in ["export_app_data", "set_preferred_runtime_path", "test_integration_hook"] {
+            assert!(is_mutating_call(command, Some(&json!({}))), "{}", command);
+        }
+    }
+
+    #[test]
+    fn locked_settings_are_real_settings() {
+        let settings = serde_json::to_value(settings::Settings::default()).unwrap();
+        for key in LOCKED_SETTINGS {
+            assert!(settings.get(*key).is_some(), "{} is not a setting", key);
+        }
+    }
+
+    #[test]
+    fn constrained_queries_plan_without_write_tools_or_mcp() {
+        let config = json!({
+            "permissionMode": "bypassPermissions",
+            "allowedTools": ["Read", "Edit", "Bash", "mcp__github__create_issue", "Grep"],
+            "mcpServers": [{ "name": "github" }],
+            "model": "sonnet"
+        });
+        let constrained: Value = serde_json::from_str(&constrain_config(Some(&config.to_string())).unwrap()).unwrap();
+        assert_eq!(constrained["permissionMode"], "plan");
+        assert_eq!(constrained["allowedTools"], json!(["Read", "Grep"]));
+        assert!(constrained.get("mcpServers").is_none());
+        assert_eq!(constrained["model"], "sonnet");
+
+        let defaults: Value = serde_json::from_str(&constrain_config(None).unwrap()).unwrap();
+        let tools: Vec<&str> = defaults["allowedTools"].as_array().unwrap().iter().filter_map(|t| t.as_str()).collect();
+        assert!(tools.contains(&"Read") && !tools.iter().any(|t| WRITE_TOOLS.contains(t)));
+    }
+}
@@ -0,0 +1,448 @@
+// mensa - References Module
+// Finds the files a prompt mentions and checks that they exist before the agent goes looking
+
+use crate::{file_index, workspace};
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Suggestions offered per missing reference
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Extensions that make a bare `name.ext` token count as a file reference
+const KNOWN_EXTENSIONS: &[&str] = &[
+    "rs", "ts", "tsx", "js", "jsx", "mjs", "cjs", "svelte", "vue", "py", "rb", "go", "java", "kt", "swift", "c",
+    "h", "cc", "cpp", "hpp", "cs", "php", "md", "mdx", "json", "jsonl", "toml", "yaml", "yml", "lock", "txt",
+    "sh", "css", "scss", "html", "sql", "proto", "xml", "gradle", "ini", "cfg", "conf", "env", "csv", "lua",
+];
+
+/// Names written like files that are products, not paths
+const NOT_FILES: &[&str] = &[
+    "node.js", "vue.js", "next.js", "nuxt.js", "react.js", "three.js", "d3.js", "express.js", "chart.js", "ember.js",
+    "nest.js", "deno.js", "p5.js",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ReferenceKind {
+    /// `@path`, as inserted by the mention picker
+    Mention,
+    /// A path-looking token in the text
+    Path,
+}
+
+/// A file reference found in a prompt, and what's on disk for it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferenceCheck {
+    /// As written in the prompt
+    pub reference: String,
+    pub kind: ReferenceKind,
+    /// The path checked (a `:line` suffix removed)
+    pub path: String,
+    pub exists: bool,
+    pub is_dir: bool,
+    /// The on-disk casing, when it differs from what was written
+    pub canonical_path: Option<String>,
+    /// Close matches from the file index, for a reference that doesn't exist
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptReferenceReport {
+    pub references: Vec<ReferenceCheck>,
+    pub missing: usize,
+}
+
+/// What the filesystem says about one path
+struct Lookup {
+    exists: bool,
+    is_dir: bool,
+    /// The path with each component in its on-disk casing, if every component was found
+    canonical: Option<PathBuf>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn version_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^[vV^~=<>]*\d+(\.\d+)+([-+][\w.]*)?$").expect("valid regex"))
+}
+
+fn line_suffix_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(.+?)(?::\d+){1,2}$").expect("valid regex"))
+}
+
+fn code_span_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"`([^`\n]+)`").expect("valid regex"))
+}
+
+/// Punctuation around a token that isn't part of the path
+fn trim_token(token: &str) -> &str {
+    token
+        .trim_start_matches(['(', '[', '{', '"', '\'', '<'])
+        .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}', '>', '"', '\''])
+}
+
+/// `name.ext` with a plausible extension: alphanumeric, with a letter, at most 10 chars
+fn has_extension(name: &str) -> bool {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => {
+            !stem.is_empty()
+                && (1..=10).contains(&ext.len())
+                && ext.chars().all(|c| c.is_ascii_alphanumeric())
+                && ext.chars().any(|c| c.is_ascii_alphabetic())
+        }
+        None => false,
+    }
+}
+
+/// The path in a token, if it looks like one. Inside backticks (`in_code`) a slash is enough;
+/// in prose a path needs a file extension or an explicit `./`, `../`, `~/` or `/` start, so
+/// "and/or" or "TCP/IP" don't count. URLs, versions and globs never do.
+fn path_like(token: &str, in_code: bool) -> Option<String> {
+    if token.is_empty()
+        || token.contains("://")
+        || token.starts_with("www.")
+        || token.starts_with("//")
+        || token.contains(['@', '*', '?', '{', '}', '[', ']', '<', '>', '|', '$', '=', '(', ')', '"', '`', ','])
+        || !token.chars().any(|c| c.is_alphabetic())
+        || version_pattern().is_match(token)
+    {
+        return None;
+    }
+    // `src/main.rs:42` or `src/main.rs:42:7` points into a file
+    let path = line_suffix_pattern()
+        .captures(token)
+        .map_or(token, |c| c.get(1).map_or(token, |m| m.as_str()));
+    if path.contains(':') && !cfg!(windows) {
+        return None;
+    }
+
+    let explicit = ["./", "../", "~/"].iter().any(|p| path.starts_with(p)) || (path.starts_with('/') && path.len() > 1);
+    let last = path.trim_end_matches('/').rsplit('/').next().unwrap_or(path);
+    let looks_like = if path.contains('/') {
+        explicit || in_code || has_extension(last)
+    } else {
+        let ext = last.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+        has_extension(last)
+            && ext.is_some_and(|ext| KNOWN_EXTENSIONS.contains(&ext.as_str()))
+            && !NOT_FILES.contains(&last.to_ascii_lowercase().as_str())
+    };
+    looks_like.then(|| path.to_string())
+}
+
+/// Explicit @-mentions and path-looking tokens in a prompt, first occurrence of each.
+/// Fenced code blocks are skipped; inline code spans are checked with the looser rule
+/// (and `@` in them isn't a mention). Returns (as written, path, kind).
+pub fn extract_references(prompt: &str) -> Vec<(String, String, ReferenceKind)> {
+    let mut found: Vec<(String, String, ReferenceKind)> = Vec::new();
+    let mut push = |reference: &str, path: String, kind: ReferenceKind| {
+        if !found.iter().any(|(_, p, _)| *p == path) {
+            found.push((reference.to_string(), path, kind));
+        }
+    };
+
+    let mut in_fence = false;
+    for line in prompt.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        for span in code_span_pattern().captures_iter(line) {
+            let code = span[1].trim();
+            if code.split_whitespace().count() != 1 {
+                continue;
+            }
+            // `@scope/package` in code is a package name, not a mention
+            if let Some(path) = path_like(code, true) {
+                push(code, path, ReferenceKind::Path);
+            }
+        }
+
+        let prose = code_span_pattern().replace_all(line, " ");
+        for raw in prose.split_whitespace() {
+            let token = trim_token(raw);
+            if let Some(mention) = token.strip_prefix('@') {
+                // Like a path in code: a directory part is enough, no extension needed
+                if !mention.is_empty() && !mention.contains('@') {
+                    if let Some(path) = path_like(mention, true) {
+                        push(token, path, ReferenceKind::Mention);
+                    }
+                }
+            } else if let Some(path) = path_like(token, false) {
+                push(token, path, ReferenceKind::Path);
+            }
+        }
+    }
+    found
+}
+
+/// The entry of `dir` named `name`, matched exactly or else ignoring case
+fn find_entry(dir: &Path, name: &str) -> Option<String> {
+    let entries: Vec<String> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    if entries.iter().any(|e| e == name) {
+        return Some(name.to_string());
+    }
+    let lower = name.to_lowercase();
+    entries.into_iter().find(|e| e.to_lowercase() == lower)
+}
+
+/// Check a path, resolving each component's on-disk casing. On a case-insensitive volume a
+/// wrongly cased path exists and gets its canonical casing; on a case-sensitive one it doesn't,
+/// and the casing found is offered as a suggestion.
+fn lookup(path: &Path) -> Lookup {
+    let mut canonical = PathBuf::new();
+    let mut resolved = true;
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => {
+                let name = name.to_string_lossy();
+                match resolved.then(|| find_entry(&canonical, &name)).flatten() {
+                    Some(entry) => canonical.push(entry),
+                    None => {
+                        resolved = false;
+                        canonical.push(name.as_ref());
+                    }
+                }
+            }
+            Component::ParentDir => {
+                canonical.pop();
+            }
+            Component::CurDir => {}
+            other => canonical.push(other.as_os_str()),
+        }
+    }
+    let metadata = std::fs::metadata(path).ok();
+    Lookup {
+        exists: metadata.is_some(),
+        is_dir: metadata.is_some_and(|m| m.is_dir()),
+        canonical: resolved.then_some(canonical),
+    }
+}
+
+/// Where a reference points: absolute and `~/` paths as they are, anything else in the workspace
+fn resolve(root: &Path, path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+            return PathBuf::from(home).join(rest);
+        }
+    }
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    }
+}
+
+/// A path for display: workspace-relative and `/`-separated when it's inside the workspace
+fn display_path(root: &Path, path: &Path) -> String {
+    match path.strip_prefix(root) {
+        Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+        Err(_) => path.to_string_lossy().to_string(),
+    }
+}
+
+/// Check every file reference in `prompt` against the workspace
+pub async fn validate(app: &tauri::AppHandle, working_dir: &str, prompt: &str) -> Result<PromptReferenceReport, String> {
+    let root = workspace::canonical_dir(working_dir)?;
+    let references = extract_references(prompt);
+    let lookup_root = root.clone();
+    let checked = tokio::task::spawn_blocking(move || {
+        references
+            .into_iter()
+            .map(|(reference, path, kind)| {
+                let full = resolve(&lookup_root, &path);
+                let found = lookup(&full);
+                let canonical = found
+                    .canonical
+                    .as_deref()
+                    .map(|c| display_path(&lookup_root, c))
+                    .filter(|c| *c != display_path(&lookup_root, &full));
+                ReferenceCheck {
+                    reference,
+                    kind,
+                    path,
+                    exists: found.exists,
+                    is_dir: found.is_dir,
+                    canonical_path: canonical,
+                    suggestions: Vec::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| format!("Failed to check prompt references: {}", e))?;
+
+    let mut references = Vec::with_capacity(checked.len());
+    for mut check in checked {
+        if !check.exists {
+            // A case-only difference on a case-sensitive volume is the best suggestion there is
+            check.suggestions.extend(check.canonical_path.take());
+            let query = check.path.trim_start_matches("./");
+            let mut close = file_index::closest_paths(app, &root, query, MAX_SUGGESTIONS).await;
+            if close.is_empty() {
+                // Moved to another directory: look for the file name alone
+                let name = query.rsplit('/').next().unwrap_or(query);
+                close = file_index::closest_paths(app, &root, name, MAX_SUGGESTIONS).await;
+            }
+            for path in close {
+                if check.suggestions.len() < MAX_SUGGESTIONS && !check.suggestions.contains(&path) {
+                    check.suggestions.push(path);
+                }
+            }
+        }
+        references.push(check);
+    }
+    let missing = references.iter().filter(|r| !r.exists).count();
+    Ok(PromptReferenceReport { references, missing })
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Find the files a prompt refers to (@-mentions and path-looking tokens) and check each one:
+/// whether it exists, its on-disk casing, and close matches from the file index when it doesn't
+#[tauri::command]
+pub async fn validate_prompt_references(
+    app: tauri::AppHandle,
+    working_dir: String,
+    prompt: String,
+) -> Result<PromptReferenceReport, String> {
+    validate(&app, &working_dir, &prompt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write;
+
+    fn paths(prompt: &str) -> Vec<String> {
+        extract_references(prompt)
+            .into_iter()
+            .map(|(_, path, _)| path)
+            .collect()
+    }
+
+    #[test]
+    fn urls_are_not_file_references() {
+        for prompt in [
+            "See https://github.com/owner/repo/blob/main/src/lib.rs for the original",
+            "Fetch http://localhost:3000/api/users.json and compare",
+            "The docs at www.example.com/guide/setup.md are out of date",
+            "Clone git://example.com/project.git first",
+            "Protocol-relative //cdn.example.com/app.js breaks on file://",
+            "Open <https://example.com/index.html>",
+            "Email me at dev@example.com about it",
+            "ssh into git@github.com:owner/repo.git",
+            "Files under s3://bucket/data/export.csv",
+        ] {
+            assert_eq!(paths(prompt), Vec::<String>::new(), "{}", prompt);
+        }
+    }
+
+    #[test]
+    fn version_strings_are_not_file_references() {
+        for prompt in [
+            "Upgrade from 1.2.3 to 2.0.0-beta.1",
+            "Pin tokio to v1.38.0, and serde ^1.0.200",
+            "Requires Python 3.12 or node >=18.17.1",
+            "Release v2.1.0+build.5 broke ~0.9.4 users",
+            "It regressed in 10.0.19045 (Windows)",
+        ] {
+            assert_eq!(paths(prompt), Vec::<String>::new(), "{}", prompt);
+        }
+    }
+
+    #[test]
+    fn prose_needs_an_extension_or_an_explicit_start() {
+        for prompt in [
+            "Support TCP/IP and/or UDP",
+            "Port the client/server split",
+            "Built with Node.js and Vue.js on Next.js",
+            "Use e.g. the i.e. form",
+            "Match *.rs and src/**/*.ts globs",
+            "Set PATH=$HOME/bin:$PATH",
+        ] {
+            assert_eq!(paths(prompt), Vec::<String>::new(), "{}", prompt);
+        }
+
+        assert_eq!(
+            paths(
+                "Fix src/main.rs:42:7, then ./scripts/build and ../shared/README; also Cargo.toml."
+            ),
+            [
+                "src/main.rs",
+                "./scripts/build",
+                "../shared/README",
+                "Cargo.toml"
+            ]
+        );
+        // The first spelling of a path is kept
+        assert_eq!(paths("Edit lib.rs, then test lib.rs again"), ["lib.rs"]);
+    }
+
+    #[test]
+    fn mentions_and_code_spans_use_the_looser_rule() {
+        let found = extract_references(
+            "Look at @src/components and `src/routes` but not `npm run build` or `@scope/pkg`",
+        );
+        let found: Vec<(&str, &str, ReferenceKind)> = found
+            .iter()
+            .map(|(r, p, k)| (r.as_str(), p.as_str(), *k))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("src/routes", "src/routes", ReferenceKind::Path),
+                ("@src/components", "src/components", ReferenceKind::Mention),
+            ]
+        );
+    }
+
+    #[test]
+    fn fenced_code_is_skipped() {
+        let prompt = "Run this:\n```sh\ncat src/secret.rs\n```\nthen open docs/guide.md";
+        assert_eq!(paths(prompt), ["docs/guide.md"]);
+    }
+
+    #[test]
+    fn lookup_finds_the_on_disk_casing() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "Src/Main.rs", "");
+
+        let exact = lookup(&dir.path().join("Src/Main.rs"));
+        assert!(exact.exists && !exact.is_dir);
+        assert_eq!(exact.canonical.unwrap(), dir.path().join("Src/Main.rs"));
+
+        let folded = lookup(&dir.path().join("src/main.rs"));
+        assert_eq!(folded.canonical.unwrap(), dir.path().join("Src/Main.rs"));
+        // Whether the wrong casing opens depends on the volume
+        assert_eq!(folded.exists, dir.path().join("src/main.rs").exists());
+
+        let missing = lookup(&dir.path().join("src/mian.rs"));
+        assert!(!missing.exists && missing.canonical.is_none());
+        assert!(lookup(&dir.path().join("Src")).is_dir);
+    }
+}
@@ -0,0 +1,728 @@
+// mensa - Replay Module
+// Sequenced copies of a query's events, so a reloaded frontend can rebuild its state,
+// delivered in order (one emitter per query) only to the windows following the query
+
+use crate::AppState;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tauri::{EventTarget, Emitter, Manager, State};
+use tokio::sync::{oneshot, Notify};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Buffered payload bytes kept per query before the oldest events are evicted
+const MAX_BUFFER_BYTES: usize = 4 * 1024 * 1024;
+
+/// Finished queries whose buffers are kept until acknowledged; older ones are dropped first
+const MAX_FINISHED_BUFFERS: usize = 8;
+
+/// The last event of a query; anything emitted for it afterwards is dropped
+pub const TERMINAL_EVENT: &str = "claude-done";
+
+/// Events queued for a query's emitter beyond which superseded samples are discarded, and
+/// producers of other events wait for room
+const MAX_PENDING_EVENTS: usize = 256;
+
+/// Events whose next copy supersedes the last; discarded first when the emitter falls behind
+const COALESCIBLE_EVENTS: &[&str] = &["claude-context-usage", "query-files-changed"];
+
+/// Something queued for a query's emitter
+enum Outgoing {
+    Event { event: String, payload: Value },
+    /// Mark the buffer finished, then signal that everything before it went out
+    Finish(oneshot::Sender<()>),
+}
+
+/// What became of something handed to a query's emitter
+enum Queued {
+    /// Queued or discarded; carries the wake handle for a new emitter when none is running
+    Accepted(Option<Arc<Notify>>),
+    /// The queue is full and nothing in it can be discarded; queue it again once `room` is notified
+    Full(Outgoing, Arc<Notify>),
+}
+
+/// One event as it was emitted, with its position in the query's sequence
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayEvent {
+    pub seq: u64,
+    pub event: String,
+    pub payload: Value,
+    /// Unix milliseconds when it was emitted
+    pub at_ms: i64,
+}
+
+#[derive(Default)]
+struct QueryBuffer {
+    next_seq: u64,
+    events: VecDeque<ReplayEvent>,
+    bytes: usize,
+    /// Events below this sequence number were evicted
+    evicted_before_seq: u64,
+    finished: bool,
+    /// The terminal event was queued; later events are dropped
+    terminated: bool,
+    /// Labels of the windows that receive the query's events live
+    subscribers: Vec<String>,
+    /// Waiting for the emitter, oldest first
+    pending: VecDeque<Outgoing>,
+    /// Coalescible events discarded because the emitter fell behind
+    dropped: u64,
+    /// Wakes the emitter when something is queued
+    wake: Arc<Notify>,
+    /// Wakes a producer waiting on a full queue when the emitter takes something from it
+    room: Arc<Notify>,
+    /// An emitter task is draining `pending`
+    emitting: bool,
+}
+
+/// Where a query's events go once sequenced
+pub trait EventSink: Send + Sync {
+    /// Send `event` to the windows in `subscribers` (and to backend listeners)
+    fn deliver(&self, event: &str, payload: Value, subscribers: &[String]);
+}
+
+/// Replay buffers of running and recently finished queries
+#[derive(Default)]
+pub struct ReplayBuffers {
+    queries: Mutex<HashMap<String, QueryBuffer>>,
+    /// Finished query ids, oldest first
+    finished: Mutex<VecDeque<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayPage {
+    pub query_id: String,
+    pub events: Vec<ReplayEvent>,
+    /// Set when events at or after `from_seq` were evicted; load the session transcript for that prefix
+    pub evicted_before_seq: Option<u64>,
+    /// Sequence number the next live event will carry
+    pub next_seq: u64,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayBufferInfo {
+    pub query_id: String,
+    pub next_seq: u64,
+    pub finished: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn payload_size(payload: &Value) -> usize {
+    serde_json::to_string(payload).map(|s| s.len()).unwrap_or(0)
+}
+
+/// Whether a query event goes to `target`. Windows get it when they follow the query; backend
+/// listeners (the command line relay) listen on any target and get everything. A query no
+/// window follows yet (started from the command line) is only buffered for the UI.
+fn is_routed_to(subscribers: &[String], target: &EventTarget) -> bool {
+    match target {
+        EventTarget::Window { label }
+        | EventTarget::Webview { label }
+        | EventTarget::WebviewWindow { label }
+        | EventTarget::AnyLabel { label } => subscribers.contains(label),
+        _ => false,
+    }
+}
+
+fn add_subscriber(buffer: &mut QueryBuffer, label: &str) {
+    if !buffer.subscribers.iter().any(|s| s == label) {
+        buffer.subscribers.push(label.to_string());
+    }
+}
+
+/// Route a query's events to a window from now on
+pub fn subscribe(app: &tauri::AppHandle, query_id: &str, label: &str) {
+    let state = app.state::<AppState>();
+    let Ok(mut queries) = state.replay.queries.lock() else {
+        return;
+    };
+    add_subscriber(queries.entry(query_id.to_string()).or_default(), label);
+}
+
+/// Route a follow-up's events to the windows that followed its predecessor
+pub fn inherit_subscribers(app: &tauri::AppHandle, from_query_id: &str, to_query_id: &str) {
+    let state = app.state::<AppState>();
+    let Ok(mut queries) = state.replay.queries.lock() else {
+        return;
+    };
+    let Some(subscribers) = queries.get(from_query_id).map(|b| b.subscribers.clone()) else {
+        return;
+    };
+    let buffer = queries.entry(to_query_id.to_string()).or_default();
+    for label in &subscribers {
+        add_subscriber(buffer, label);
+    }
+}
+
+fn is_coalescible(outgoing: &Outgoing) -> bool {
+    matches!(outgoing, Outgoing::Event { event, .. } if COALESCIBLE_EVENTS.contains(&event.as_str()))
+}
+
+/// Queue for a query's emitter. Past MAX_PENDING_EVENTS the oldest queued coalescible event
+/// is discarded (or the new one, if it's coalescible and nothing else is); other events are
+/// never discarded, so with nothing to discard the queue is reported full.
+fn queue(replay: &ReplayBuffers, query_id: &str, outgoing: Outgoing) -> Queued {
+    let Ok(mut queries) = replay.queries.lock() else {
+        return Queued::Accepted(None);
+    };
+    let buffer = queries.entry(query_id.to_string()).or_default();
+    match &outgoing {
+        Outgoing::Finish(_) if !buffer.emitting => {
+            mark_finished(replay, &mut queries, query_id);
+            if let Outgoing::Finish(done) = outgoing {
+                let _ = done.send(());
+            }
+            return Queued::Accepted(None);
+        }
+        Outgoing::Finish(_) => {}
+        Outgoing::Event { event, .. } if buffer.terminated => {
+            eprintln!("[mensa] Dropped {} for query {}: emitted after {}", event, query_id, TERMINAL_EVENT);
+            return Queued::Accepted(None);
+        }
+        Outgoing::Event { event, .. } => {
+            if buffer.pending.len() >= MAX_PENDING_EVENTS {
+                match buffer.pending.iter().position(is_coalescible) {
+                    Some(oldest) => {
+                        buffer.pending.remove(oldest);
+                        buffer.dropped += 1;
+                    }
+                    None if is_coalescible(&outgoing) => {
+                        buffer.dropped += 1;
+                        return Queued::Accepted(None);
+                    }
+                    None => return Queued::Full(outgoing, buffer.room.clone()),
+                }
+            }
+            buffer.terminated = event == TERMINAL_EVENT;
+        }
+    }
+    buffer.pending.push_back(outgoing);
+    if buffer.emitting {
+        buffer.wake.notify_one();
+        return Queued::Accepted(None);
+    }
+    buffer.emitting = true;
+    Queued::Accepted(Some(buffer.wake.clone()))
+}
+
+/// `queue`, waiting for the emitter to make room while the queue is full
+async fn queue_when_room(replay: &ReplayBuffers, query_id: &str, mut outgoing: Outgoing) -> Option<Arc<Notify>> {
+    loop {
+        match queue(replay, query_id, outgoing) {
+            Queued::Accepted(wake) => return wake,
+            Queued::Full(returned, room) => {
+                outgoing = returned;
+                room.notified().await;
+            }
+        }
+    }
+}
+
+/// Queue for a query's emitter, starting one if none is running
+async fn enqueue(app: &tauri::AppHandle, query_id: &str, outgoing: Outgoing) {
+    let state = app.state::<AppState>();
+    let Some(wake) = queue_when_room(&state.replay, query_id, outgoing).await else {
+        return;
+    };
+    let (app, query_id) = (app.clone(), query_id.to_string());
+    state
+        .tasks
+        .spawn(format!("events of query {}", query_id), |token| run_emitter(app, query_id, wake, token));
+}
+
+/// Emit a query event. The query's emitter adds its sequence number (and group, if any) to the
+/// payload and keeps a copy for replay; events go out in the order they were emitted, only to
+/// the windows following the query, and not at all once its terminal event was emitted.
+/// Waits while the emitter is too far behind to take it.
+pub async fn emit<S: Serialize>(app: &tauri::AppHandle, query_id: &str, event: &str, payload: S) {
+    let mut payload = serde_json::to_value(payload).unwrap_or(Value::Null);
+    if let (Some(group_id), Value::Object(map)) = (crate::query_group::group_of(app, query_id), &mut payload) {
+        map.insert("group_id".to_string(), Value::from(group_id));
+    }
+    enqueue(app, query_id, Outgoing::Event {
+        event: event.to_string(),
+        payload,
+    })
+    .await;
+}
+
+/// Number the next queued event and copy it into the replay buffer; the terminal event also
+/// reports how many coalescible events were discarded
+fn record(buffer: &mut QueryBuffer, event: &str, mut payload: Value) -> Value {
+    let seq = buffer.next_seq;
+    buffer.next_seq += 1;
+    if let Value::Object(map) = &mut payload {
+        map.insert("seq".to_string(), Value::from(seq));
+        if event == TERMINAL_EVENT {
+            map.insert("dropped_events".to_string(), Value::from(buffer.dropped));
+        }
+    }
+
+    let size = payload_size(&payload);
+    buffer.events.push_back(ReplayEvent {
+        seq,
+        event: event.to_string(),
+        payload: payload.clone(),
+        at_ms: crate::history::now_millis(),
+    });
+    buffer.bytes += size;
+    while buffer.bytes > MAX_BUFFER_BYTES {
+        let Some(oldest) = buffer.events.pop_front() else {
+            break;
+        };
+        buffer.bytes = buffer.bytes.saturating_sub(payload_size(&oldest.payload));
+        buffer.evicted_before_seq = oldest.seq + 1;
+    }
+    payload
+}
+
+impl EventSink for tauri::AppHandle {
+    fn deliver(&self, event: &str, payload: Value, subscribers: &[String]) {
+        let _ = self.emit_filter(event, payload, |target| is_routed_to(subscribers, target));
+    }
+}
+
+async fn run_emitter(app: tauri::AppHandle, query_id: String, wake: Arc<Notify>, token: crate::cancel::CancellationToken) {
+    let state = app.state::<AppState>();
+    drain(&state.replay, &app, &query_id, &wake, &token).await;
+}
+
+/// The only sender of a query's events: drains its queue in order into `sink` until it's
+/// empty, then waits for more. Stops once the queue is empty after the terminal event or a finish.
+async fn drain(
+    replay: &ReplayBuffers,
+    sink: &dyn EventSink,
+    query_id: &str,
+    wake: &Notify,
+    token: &crate::cancel::CancellationToken,
+) {
+    loop {
+        let next = {
+            let Ok(mut queries) = replay.queries.lock() else {
+                return;
+            };
+            let Some(buffer) = queries.get_mut(query_id) else {
+                return;
+            };
+            let popped = buffer.pending.pop_front();
+            if popped.is_some() {
+                buffer.room.notify_one();
+            }
+            match popped {
+                Some(Outgoing::Event { event, payload }) => {
+                    let payload = record(buffer, &event, payload);
+                    Some((event, payload, buffer.subscribers.clone()))
+                }
+                Some(Outgoing::Finish(done)) => {
+                    buffer.emitting = !buffer.pending.is_empty();
+                    let stop = !buffer.emitting;
+                    mark_finished(replay, &mut queries, query_id);
+                    let _ = done.send(());
+                    if stop {
+                        return;
+                    }
+                    None
+                }
+                None if buffer.terminated || token.is_cancelled() => {
+                    buffer.emitting = false;
+                    return;
+                }
+                None => None,
+            }
+        };
+        match next {
+            Some((event, payload, subscribers)) => sink.deliver(&event, payload, &subscribers),
+            None => tokio::select! {
+                _ = wake.notified() => {}
+                _ = token.cancelled() => {}
+            },
+        }
+    }
+}
+
+fn mark_finished(replay: &ReplayBuffers, queries: &mut HashMap<String, QueryBuffer>, query_id: &str) {
+    let Ok(mut finished) = replay.finished.lock() else {
+        return;
+    };
+    match queries.get_mut(query_id) {
+        Some(buffer) if !buffer.finished => buffer.finished = true,
+        _ => return,
+    }
+    finished.push_back(query_id.to_string());
+    while finished.len() > MAX_FINISHED_BUFFERS {
+        if let Some(oldest) = finished.pop_front() {
+            queries.remove(&oldest);
+        }
+    }
+}
+
+/// Mark a query's buffer finished once its queued events went out; it stays available until
+/// acknowledged or pushed out. Returns after those events were emitted.
+pub async fn finish(app: &tauri::AppHandle, query_id: &str) {
+    let (done, emitted) = oneshot::channel();
+    enqueue(app, query_id, Outgoing::Finish(done)).await;
+    let _ = emitted.await;
+}
+
+/// A copy of a query's buffered events, and whether earlier ones were evicted. None when
+/// the query has no buffer (never ran here, or acknowledged and dropped).
+pub fn snapshot(app: &tauri::AppHandle, query_id: &str) -> Option<(Vec<ReplayEvent>, bool)> {
+    let state = app.state::<AppState>();
+    let queries = state.replay.queries.lock().ok()?;
+    let buffer = queries.get(query_id)?;
+    Some((buffer.events.iter().cloned().collect(), buffer.evicted_before_seq > 0))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+fn page(query_id: &str, buffer: &QueryBuffer, from_seq: u64) -> ReplayPage {
+    ReplayPage {
+        query_id: query_id.to_string(),
+        events: buffer.events.iter().filter(|e| e.seq >= from_seq).cloned().collect(),
+        evicted_before_seq: (from_seq < buffer.evicted_before_seq).then_some(buffer.evicted_before_seq),
+        next_seq: buffer.next_seq,
+        finished: buffer.finished,
+    }
+}
+
+/// Buffered events of a query from `from_seq` on, in order
+#[tauri::command]
+pub async fn replay_query_events(
+    state: State<'_, AppState>,
+    query_id: String,
+    from_seq: Option<u64>,
+) -> Result<ReplayPage, String> {
+    let queries = state
+        .replay
+        .queries
+        .lock()
+        .map_err(|_| "Replay buffer is unavailable".to_string())?;
+    let buffer = queries
+        .get(&query_id)
+        .ok_or_else(|| format!("No buffered events for query {}", query_id))?;
+    Ok(page(&query_id, buffer, from_seq.unwrap_or(0)))
+}
+
+/// Follow a query from the calling window: its events arrive live from `nextSeq` on, and the
+/// returned page holds everything buffered before that, so nothing is missed or seen twice
+#[tauri::command]
+pub async fn subscribe_query(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    query_id: String,
+    from_seq: Option<u64>,
+) -> Result<ReplayPage, String> {
+    let mut queries = state
+        .replay
+        .queries
+        .lock()
+        .map_err(|_| "Replay buffer is unavailable".to_string())?;
+    let buffer = queries
+        .get_mut(&query_id)
+        .ok_or_else(|| format!("No buffered events for query {}", query_id))?;
+    add_subscriber(buffer, window.label());
+    Ok(page(&query_id, buffer, from_seq.unwrap_or(0)))
+}
+
+/// Queries with buffered events, so a reloaded frontend knows what it can restore
+#[tauri::command]
+pub async fn list_replay_buffers(state: State<'_, AppState>) -> Result<Vec<ReplayBufferInfo>, String> {
+    let queries = state
+        .replay
+        .queries
+        .lock()
+        .map_err(|_| "Replay buffer is unavailable".to_string())?;
+    let mut infos: Vec<ReplayBufferInfo> = queries
+        .iter()
+        .map(|(query_id, buffer)| ReplayBufferInfo {
+            query_id: query_id.clone(),
+            next_seq: buffer.next_seq,
+            finished: buffer.finished,
+        })
+        .collect();
+    infos.sort_by(|a, b| a.query_id.cmp(&b.query_id));
+    Ok(infos)
+}
+
+/// Drop a finished query's buffer once the frontend has rebuilt its state
+#[tauri::command]
+pub async fn ack_query_events(state: State<'_, AppState>, query_id: String) -> Result<bool, String> {
+    let (Ok(mut queries), Ok(mut finished)) = (state.replay.queries.lock(), state.replay.finished.lock()) else {
+        return Err("Replay buffer is unavailable".to_string());
+    };
+    if !queries.get(&query_id).is_some_and(|buffer| buffer.finished) {
+        return Ok(false);
+    }
+    queries.remove(&query_id);
+    finished.retain(|id| id != &query_id);
+    Ok(true)
+}
+
+/// Sequence number of the last event a query emitted, so a window can tell whether it missed
+/// any; None when nothing went out yet
+#[tauri::command]
+pub async fn get_last_seq(state: State<'_, AppState>, query_id: String) -> Result<Option<u64>, String> {
+    let queries = state
+        .replay
+        .queries
+        .lock()
+        .map_err(|_| "Replay buffer is unavailable".to_string())?;
+    let buffer = queries
+        .get(&query_id)
+        .ok_or_else(|| format!("No buffered events for query {}", query_id))?;
+    Ok(buffer.next_seq.checked_sub(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancellationToken;
+    use crate::test_support::RecordingSink;
+    use serde_json::json;
+
+    fn event(name: &str, payload: Value) -> Outgoing {
+        Outgoing::Event {
+            event: name.to_string(),
+            payload,
+        }
+    }
+
+    fn accepted(queued: Queued) -> Option<Arc<Notify>> {
+        match queued {
+            Queued::Accepted(wake) => wake,
+            Queued::Full(..) => panic!("the queue was full"),
+        }
+    }
+
+    /// Queue `events` for one query, then drain them into a recording sink as its emitter would
+    async fn run(replay: &ReplayBuffers, query_id: &str, events: Vec<Outgoing>) -> RecordingSink {
+        let mut wake = None;
+        for outgoing in events {
+            wake = wake.or(accepted(queue(replay, query_id, outgoing)));
+        }
+        let sink = RecordingSink::default();
+        let token = CancellationToken::default();
+        // Stop once the queue is empty, as at shutdown, when no terminal event ends the drain
+        token.cancel();
+        drain(replay, &sink, query_id, &wake.unwrap(), &token).await;
+        sink
+    }
+
+    #[tokio::test]
+    async fn events_go_out_in_order_with_sequence_numbers() {
+        let replay = ReplayBuffers::default();
+        let sink = run(&replay, "q1", vec![
+            event("claude-stream", json!({ "n": 0 })),
+            event("claude-stream", json!({ "n": 1 })),
+            event(TERMINAL_EVENT, json!({ "code": 0 })),
+        ])
+        .await;
+
+        let delivered = sink.delivered();
+        let seqs: Vec<u64> = delivered.iter().map(|d| d.payload["seq"].as_u64().unwrap()).collect();
+        assert_eq!(seqs, [0, 1, 2]);
+        assert_eq!(delivered[1].payload["n"], 1);
+        assert_eq!(delivered[2].payload["dropped_events"], 0);
+
+        let queries = replay.queries.lock().unwrap();
+        let buffer = &queries["q1"];
+        assert_eq!(buffer.next_seq, 3);
+        assert_eq!(buffer.events.len(), 3);
+        assert!(!buffer.emitting);
+    }
+
+    #[tokio::test]
+    async fn nothing_follows_the_terminal_event() {
+        let replay = ReplayBuffers::default();
+        let sink = run(&replay, "q1", vec![
+            event("claude-stream", json!({})),
+            event(TERMINAL_EVENT, json!({})),
+            event("claude-stream", json!({ "late": true })),
+            event(TERMINAL_EVENT, json!({})),
+        ])
+        .await;
+        assert_eq!(sink.events(), ["claude-stream", TERMINAL_EVENT]);
+
+        // Nor later, once the emitter stopped
+        assert!(accepted(queue(&replay, "q1", event("claude-stream", json!({})))).is_none());
+        assert_eq!(replay.queries.lock().unwrap()["q1"].pending.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_backlog_discards_superseded_samples_only() {
+        let replay = ReplayBuffers::default();
+        let mut events = vec![event("claude-context-usage", json!({ "sample": 0 }))];
+        events.extend((0..MAX_PENDING_EVENTS - 2).map(|n| event("claude-stream", json!({ "n": n }))));
+        events.push(event("claude-context-usage", json!({ "sample": 1 })));
+        // Queued on a full queue: the oldest sample makes room for it
+        events.push(event(TERMINAL_EVENT, json!({})));
+        let sink = run(&replay, "q1", events).await;
+
+        let delivered = sink.delivered();
+        let streams = delivered.iter().filter(|d| d.event == "claude-stream").count();
+        assert_eq!(streams, MAX_PENDING_EVENTS - 2);
+        let samples: Vec<&Value> = delivered
+            .iter()
+            .filter(|d| d.event == "claude-context-usage")
+            .map(|d| &d.payload["sample"])
+            .collect();
+        assert_eq!(samples, [&json!(1)]);
+        assert_eq!(delivered.last().unwrap().payload["dropped_events"], 1);
+    }
+
+    #[tokio::test]
+    async fn a_full_queue_of_other_events_makes_producers_wait() {
+        let replay = Arc::new(ReplayBuffers::default());
+        let mut wake = None;
+        for n in 0..MAX_PENDING_EVENTS {
+            wake = wake.or(accepted(queue(&replay, "q1", event("claude-stream", json!({ "n": n })))));
+        }
+        assert!(matches!(queue(&replay, "q1", event(TERMINAL_EVENT, json!({}))), Queued::Full(..)));
+
+        // Nothing is discarded and the queue stays at its limit while the producer waits
+        let producer = {
+            let replay = replay.clone();
+            tokio::spawn(async move {
+                for n in MAX_PENDING_EVENTS..MAX_PENDING_EVENTS + 3 {
+                    queue_when_room(&replay, "q1", event("claude-stream", json!({ "n": n }))).await;
+                }
+                queue_when_room(&replay, "q1", event(TERMINAL_EVENT, json!({}))).await;
+            })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!producer.is_finished());
+        assert_eq!(replay.queries.lock().unwrap()["q1"].pending.len(), MAX_PENDING_EVENTS);
+
+        let sink = RecordingSink::default();
+        drain(&replay, &sink, "q1", &wake.unwrap(), &CancellationToken::default()).await;
+        producer.await.unwrap();
+
+        let delivered = sink.delivered();
+        let ns: Vec<u64> = delivered.iter().filter_map(|d| d.payload["n"].as_u64()).collect();
+        assert_eq!(ns, (0..MAX_PENDING_EVENTS as u64 + 3).collect::<Vec<_>>());
+        let seqs: Vec<u64> = delivered.iter().map(|d| d.payload["seq"].as_u64().unwrap()).collect();
+        assert_eq!(seqs, (0..MAX_PENDING_EVENTS as u64 + 4).collect::<Vec<_>>());
+        assert_eq!(delivered.last().unwrap().payload["dropped_events"], 0);
+    }
+
+    #[tokio::test]
+    async fn events_go_to_the_subscribers_at_emit_time() {
+        let replay = ReplayBuffers::default();
+        add_subscriber(replay.queries.lock().unwrap().entry("q1".to_string()).or_default(), "main");
+        let sink = run(&replay, "q1", vec![event(TERMINAL_EVENT, json!({}))]).await;
+        assert_eq!(sink.delivered()[0].subscribers, ["main"]);
+    }
+
+    #[tokio::test]
+    async fn finish_waits_for_queued_events() {
+        let replay = ReplayBuffers::default();
+        let (done, mut finished) = oneshot::channel();
+        let sink = run(&replay, "q1", vec![
+            event("claude-stream", json!({})),
+            Outgoing::Finish(done),
+        ])
+        .await;
+        assert_eq!(sink.events(), ["claude-stream"]);
+        assert!(finished.try_recv().is_ok());
+        assert!(replay.queries.lock().unwrap()["q1"].finished);
+        assert_eq!(replay.finished.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn finish_without_an_emitter_completes_at_once() {
+        let replay = ReplayBuffers::default();
+        let (done, mut finished) = oneshot::channel();
+        assert!(accepted(queue(&replay, "idle", Outgoing::Finish(done))).is_none());
+        assert!(finished.try_recv().is_ok());
+    }
+
+    #[test]
+    fn oldest_finished_buffers_are_dropped() {
+        let replay = ReplayBuffers::default();
+        for n in 0..=MAX_FINISHED_BUFFERS {
+            let (done, _finished) = oneshot::channel();
+            queue(&replay, &format!("q{}", n), Outgoing::Finish(done));
+        }
+        let queries = replay.queries.lock().unwrap();
+        assert_eq!(queries.len(), MAX_FINISHED_BUFFERS);
+        assert!(!queries.contains_key("q0"));
+    }
+
+    fn window(label: &str) -> EventTarget {
+        EventTarget::Window { label: label.to_string() }
+    }
+
+    #[test]
+    fn events_reach_only_the_windows_following_the_query() {
+        let subscribers = ["main".to_string(), "workspace-2".to_string()];
+        let label = |l: &str| l.to_string();
+
+        assert!(is_routed_to(&subscribers, &window("main")));
+        assert!(is_routed_to(&subscribers, &EventTarget::WebviewWindow { label: label("workspace-2") }));
+        assert!(is_routed_to(&subscribers, &EventTarget::Webview { label: label("main") }));
+        assert!(is_routed_to(&subscribers, &EventTarget::AnyLabel { label: label("workspace-2") }));
+        assert!(!is_routed_to(&subscribers, &window("workspace-3")));
+        assert!(!is_routed_to(&subscribers, &EventTarget::Webview { label: label("Main") }));
+        // Broadcast-style targets are never chosen; backend listeners on Any get every event regardless
+        assert!(!is_routed_to(&subscribers, &EventTarget::App));
+        assert!(!is_routed_to(&subscribers, &EventTarget::Any));
+
+        // A query nobody follows goes to no window at all
+        assert!(!is_routed_to(&[], &window("main")));
+    }
+
+    #[test]
+    fn subscribing_twice_routes_events_once() {
+        let mut buffer = QueryBuffer::default();
+        add_subscriber(&mut buffer, "main");
+        add_subscriber(&mut buffer, "workspace-2");
+        add_subscriber(&mut buffer, "main");
+        assert_eq!(buffer.subscribers, ["main", "workspace-2"]);
+    }
+
+    #[tokio::test]
+    async fn headless_query_is_buffered_until_a_window_subscribes() {
+        let replay = ReplayBuffers::default();
+        // Started from the command line: no window follows it yet
+        let sink = run(&replay, "cli", vec![
+            event("claude-stream", json!({ "n": 0 })),
+            event("claude-stream", json!({ "n": 1 })),
+        ])
+        .await;
+        assert_eq!(sink.delivered().len(), 2);
+        assert!(sink.delivered().iter().all(|d| d.subscribers.is_empty()));
+
+        // The subscribing window gets what was buffered, then the rest live
+        let replayed = {
+            let mut queries = replay.queries.lock().unwrap();
+            let buffer = queries.get_mut("cli").unwrap();
+            add_subscriber(buffer, "main");
+            page("cli", buffer, 0)
+        };
+        assert_eq!(replayed.events.iter().map(|e| e.seq).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(replayed.events[1].payload["n"], 1);
+        assert_eq!((replayed.next_seq, replayed.evicted_before_seq, replayed.finished), (2, None, false));
+
+        let sink = run(&replay, "cli", vec![
+            event("claude-stream", json!({ "n": 2 })),
+            event(TERMINAL_EVENT, json!({ "code": 0 })),
+        ])
+        .await;
+        let live = sink.delivered();
+        assert_eq!(live.iter().map(|d| d.payload["seq"].as_u64().unwrap()).collect::<Vec<_>>(), [2, 3]);
+        assert!(live.iter().all(|d| d.subscribers == ["main"]));
+    }
+}
@@ -0,0 +1,197 @@
+// mensa - Review Drafts Module
+// Persists in-progress PR reviews (verdict, body, line comments) in app data until they're posted
+
+use crate::store::JsonStore;
+use crate::{git, history};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tauri::{Manager, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Drafts keyed by normalized PR URL; the store's lock serializes read-modify-write cycles
+static DRAFTS_FILE: JsonStore<HashMap<String, ReviewDraft>> = JsonStore::new("review drafts");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewComment {
+    pub path: String,
+    pub line: u32,
+    /// First line of a multi-line comment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<u32>,
+    /// "LEFT" (old) or "RIGHT" (new) side of the diff
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewDraft {
+    pub pr_url: String,
+    pub verdict: String,
+    pub body: String,
+    pub comments: Vec<ReviewComment>,
+    /// PR head commit the line anchors refer to (None if it couldn't be fetched)
+    pub head_sha: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewDraftView {
+    #[serde(flatten)]
+    pub draft: ReviewDraft,
+    /// The PR head moved since the draft was saved; line anchors need revalidating
+    pub outdated: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn drafts_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("review-drafts.json"))
+}
+
+/// The head SHA to keep on a saved draft and whether the PR moved past it. An existing draft's
+/// anchors stay tied to the head they were written against until `acknowledge_head` says the
+/// comments were checked against the current one.
+fn draft_head(saved: Option<&ReviewDraft>, current: Option<String>, acknowledge_head: bool) -> (Option<String>, bool) {
+    match saved.and_then(|draft| draft.head_sha.clone()) {
+        Some(saved) if !acknowledge_head => {
+            let outdated = current.is_some_and(|current| current != saved);
+            (Some(saved), outdated)
+        }
+        _ => (current, false),
+    }
+}
+
+/// Remove a PR's draft; returns whether one existed
+pub async fn remove_draft(app: &tauri::AppHandle, pr_url: &str) -> Result<bool, String> {
+    let key = git::normalize_pr_url(pr_url)?;
+    let guard = DRAFTS_FILE.lock(app, drafts_path(app)?).await;
+    let mut drafts = guard.load().await?;
+    if drafts.remove(&key).is_none() {
+        return Ok(false);
+    }
+    guard.save(&drafts).await?;
+    Ok(true)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Save (or replace) the review draft for a PR. The draft keeps the head SHA it was first saved
+/// against; `acknowledge_head` moves it to the PR's current head.
+#[tauri::command]
+pub async fn save_review_draft(
+    app: tauri::AppHandle,
+    state: State<'_, git::GitState>,
+    pr_url: String,
+    verdict: String,
+    body: String,
+    comments: Vec<ReviewComment>,
+    acknowledge_head: Option<bool>,
+) -> Result<ReviewDraftView, String> {
+    let key = git::normalize_pr_url(&pr_url)?;
+    // The head SHA is best effort; an offline save still keeps the text
+    let head_sha = git::load_pr_info(&app, &state, &pr_url, false)
+        .await
+        .ok()
+        .map(|info| info.head_sha)
+        .filter(|sha| !sha.is_empty());
+
+    let guard = DRAFTS_FILE.lock(&app, drafts_path(&app)?).await;
+    let mut drafts = guard.load().await?;
+    let now = history::now_secs();
+    let created_at = drafts.get(&key).map(|d| d.created_at).unwrap_or(now);
+    let (head_sha, outdated) = draft_head(drafts.get(&key), head_sha, acknowledge_head.unwrap_or(false));
+    let draft = ReviewDraft {
+        pr_url,
+        verdict,
+        body,
+        comments,
+        head_sha,
+        created_at,
+        updated_at: now,
+    };
+    drafts.insert(key, draft.clone());
+    guard.save(&drafts).await?;
+
+    Ok(ReviewDraftView { draft, outdated })
+}
+
+/// Get a PR's review draft, flagged outdated when the PR head changed since it was saved
+#[tauri::command]
+pub async fn get_review_draft(
+    app: tauri::AppHandle,
+    state: State<'_, git::GitState>,
+    pr_url: String,
+) -> Result<Option<ReviewDraftView>, String> {
+    let key = git::normalize_pr_url(&pr_url)?;
+    let draft = match DRAFTS_FILE.read(&app, drafts_path(&app)?).await?.remove(&key) {
+        Some(draft) => draft,
+        None => return Ok(None),
+    };
+
+    let outdated = match (&draft.head_sha, git::load_pr_info(&app, &state, &pr_url, false).await) {
+        (Some(saved), Ok(info)) => !info.head_sha.is_empty() && &info.head_sha != saved,
+        _ => false,
+    };
+    Ok(Some(ReviewDraftView { draft, outdated }))
+}
+
+/// Discard a PR's review draft
+#[tauri::command]
+pub async fn delete_review_draft(app: tauri::AppHandle, pr_url: String) -> Result<bool, String> {
+    remove_draft(&app, &pr_url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft(head_sha: Option<&str>) -> ReviewDraft {
+        ReviewDraft {
+            pr_url: "https://github.com/o/r/pull/1".to_string(),
+            verdict: "comment".to_string(),
+            body: String::new(),
+            comments: Vec::new(),
+            head_sha: head_sha.map(String::from),
+            created_at: 1,
+            updated_at: 1,
+        }
+    }
+
+    #[test]
+    fn update_keeps_the_head_the_comments_were_written_against() {
+        let saved = draft(Some("aaa"));
+        assert_eq!(draft_head(Some(&saved), Some("bbb".into()), false), (Some("aaa".into()), true));
+        assert_eq!(draft_head(Some(&saved), Some("aaa".into()), false), (Some("aaa".into()), false));
+        // Offline: nothing to compare against
+        assert_eq!(draft_head(Some(&saved), None, false), (Some("aaa".into()), false));
+    }
+
+    #[test]
+    fn acknowledging_moves_the_draft_to_the_current_head() {
+        let saved = draft(Some("aaa"));
+        assert_eq!(draft_head(Some(&saved), Some("bbb".into()), true), (Some("bbb".into()), false));
+    }
+
+    #[test]
+    fn first_save_takes_the_current_head() {
+        assert_eq!(draft_head(None, Some("bbb".into()), false), (Some("bbb".into()), false));
+        assert_eq!(draft_head(Some(&draft(None)), Some("bbb".into()), false), (Some("bbb".into()), false));
+    }
+}
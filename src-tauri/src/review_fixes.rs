@@ -0,0 +1,550 @@
+// mensa - Review Fixes Module
+// Turns a PR's unresolved review threads into one prompt, then checks which ones a query touched
+
+use crate::git::{self, GitState};
+use crate::history;
+use git2::{DiffOptions, Repository, Sort};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Lines either side of a thread's line a change may land and still count as addressing it
+const LINE_WINDOW: u32 = 10;
+
+/// Diff hunk lines quoted per thread; GitHub's hunk ends at the commented line
+const MAX_HUNK_LINES: usize = 20;
+
+const THREADS_QUERY: &str = "query($owner: String!, $name: String!, $number: Int!) { \
+    repository(owner: $owner, name: $name) { pullRequest(number: $number) { \
+    reviewThreads(first: 100) { nodes { id isResolved isOutdated path line originalLine \
+    comments(first: 50) { nodes { author { login } body diffHunk createdAt } } } } } } }";
+
+const REPLY_MUTATION: &str = "mutation($thread: ID!, $body: String!) { \
+    addPullRequestReviewThreadReply(input: { pullRequestReviewThreadId: $thread, body: $body }) \
+    { comment { id } } }";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewThreadComment {
+    pub author: String,
+    pub body: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewThread {
+    pub id: String,
+    pub path: String,
+    /// Line in the PR head, or for an outdated thread the line it was left on
+    pub line: Option<u32>,
+    pub is_resolved: bool,
+    pub is_outdated: bool,
+    pub diff_hunk: String,
+    pub comments: Vec<ReviewThreadComment>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewFixPrompt {
+    pub prompt: String,
+    pub threads: Vec<ReviewThread>,
+    /// Requested ids that are resolved or not on the PR
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThreadStatus {
+    /// A change landed within LINE_WINDOW lines of the thread
+    LikelyAddressed,
+    /// The thread's file changed, but not near its line
+    FileTouched,
+    Untouched,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadMatch {
+    pub thread_id: String,
+    pub path: String,
+    pub line: Option<u32>,
+    pub status: ThreadStatus,
+    /// Short hashes of commits since the query started that changed the thread's file
+    pub commits: Vec<String>,
+    /// Whether a reply was posted to the thread
+    pub replied: bool,
+}
+
+/// Changes to one file since a query started
+#[derive(Debug, Default)]
+struct TouchedFile {
+    /// Inclusive line ranges in the current version
+    ranges: Vec<(u32, u32)>,
+    commits: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn thread_from_json(node: &Value) -> Option<ReviewThread> {
+    let comments: Vec<&Value> = node["comments"]["nodes"].as_array()?.iter().collect();
+    let line = node["line"].as_u64().or_else(|| node["originalLine"].as_u64());
+    Some(ReviewThread {
+        id: node["id"].as_str()?.to_string(),
+        path: node["path"].as_str().unwrap_or("").to_string(),
+        line: line.map(|l| l as u32),
+        is_resolved: node["isResolved"].as_bool().unwrap_or(false),
+        is_outdated: node["isOutdated"].as_bool().unwrap_or(false),
+        diff_hunk: comments
+            .first()
+            .and_then(|c| c["diffHunk"].as_str())
+            .unwrap_or("")
+            .to_string(),
+        comments: comments
+            .iter()
+            .map(|c| ReviewThreadComment {
+                author: c["author"]["login"].as_str().unwrap_or("ghost").to_string(),
+                body: c["body"].as_str().unwrap_or("").to_string(),
+                created_at: c["createdAt"].as_str().unwrap_or("").to_string(),
+            })
+            .collect(),
+    })
+}
+
+/// Every review thread on a GitHub PR
+async fn fetch_review_threads(state: &GitState, pr_url: &str) -> Result<Vec<ReviewThread>, String> {
+    let (owner, repo, number) = git::parse_pr_url(pr_url)?;
+    let args = [
+        "api".to_string(),
+        "graphql".to_string(),
+        "-f".to_string(),
+        format!("query={}", THREADS_QUERY),
+        "-f".to_string(),
+        format!("owner={}", owner),
+        "-f".to_string(),
+        format!("name={}", repo),
+        "-F".to_string(),
+        format!("number={}", number),
+    ];
+    let output = git::run_external(state, "gh", &args, None, &[], None, None).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to fetch review threads: {}", stderr.trim()));
+    }
+
+    let json: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse review threads JSON: {}", e))?;
+    threads_from_response(&json, pr_url)
+}
+
+/// The review threads in a THREADS_QUERY response
+fn threads_from_response(json: &Value, pr_url: &str) -> Result<Vec<ReviewThread>, String> {
+    let nodes = json["data"]["repository"]["pullRequest"]["reviewThreads"]["nodes"]
+        .as_array()
+        .ok_or_else(|| format!("No pull request found at {}", pr_url))?;
+    Ok(nodes.iter().filter_map(thread_from_json).collect())
+}
+
+/// The last `MAX_HUNK_LINES` lines of a hunk, which end at the commented line
+fn hunk_tail(hunk: &str) -> String {
+    let lines: Vec<&str> = hunk.lines().collect();
+    lines[lines.len().saturating_sub(MAX_HUNK_LINES)..].join("\n")
+}
+
+fn format_prompt(pr_url: &str, threads: &[ReviewThread]) -> String {
+    let mut prompt = format!(
+        "Address these review comments on {}. Each one quotes the code it was left on; \
+         make the change the reviewer asked for, or explain why it shouldn't be made.\n",
+        pr_url
+    );
+    for (i, thread) in threads.iter().enumerate() {
+        let location = match thread.line {
+            Some(line) => format!("{}:{}", thread.path, line),
+            None => thread.path.clone(),
+        };
+        let outdated = if thread.is_outdated { " (outdated: the code has moved since)" } else { "" };
+        prompt.push_str(&format!("\n## {}. {}{}\n", i + 1, location, outdated));
+        if !thread.diff_hunk.is_empty() {
+            prompt.push_str(&format!("```diff\n{}\n```\n", hunk_tail(&thread.diff_hunk)));
+        }
+        for comment in &thread.comments {
+            prompt.push_str(&format!("\n@{} wrote:\n", comment.author));
+            for line in comment.body.lines() {
+                prompt.push_str(&format!("> {}\n", line));
+            }
+        }
+    }
+    prompt
+}
+
+/// Inclusive new-side line ranges of the hunks in `diff`, by path
+fn diff_ranges(diff: &git2::Diff, files: &mut HashMap<String, TouchedFile>) -> Result<(), String> {
+    diff.foreach(
+        &mut |_, _| true,
+        None,
+        Some(&mut |delta, hunk| {
+            if let Some(path) = delta.new_file().path().and_then(|p| p.to_str()) {
+                // A pure deletion has no new lines; it sits just before new_start
+                let start = hunk.new_start().max(1);
+                let end = start + hunk.new_lines().max(1) - 1;
+                files.entry(path.to_string()).or_default().ranges.push((start, end));
+            }
+            true
+        }),
+        None,
+    )
+    .map_err(|e| format!("Failed to read diff hunks: {}", e))
+}
+
+/// What changed in the repository since `since` (Unix seconds): commits on HEAD's
+/// first-parent line made since then, plus staged and unstaged changes
+fn collect_changes(repo: &Repository, since: i64) -> Result<HashMap<String, TouchedFile>, String> {
+    let mut files: HashMap<String, TouchedFile> = HashMap::new();
+    let mut base = None;
+
+    if let Ok(head) = repo.head().and_then(|h| h.peel_to_commit()) {
+        let mut walk = repo.revwalk().map_err(|e| format!("Failed to walk history: {}", e))?;
+        walk.set_sorting(Sort::TOPOLOGICAL).map_err(|e| format!("Failed to walk history: {}", e))?;
+        walk.simplify_first_parent().map_err(|e| format!("Failed to walk history: {}", e))?;
+        walk.push(head.id()).map_err(|e| format!("Failed to walk history: {}", e))?;
+
+        for oid in walk {
+            let oid = oid.map_err(|e| format!("Failed to walk history: {}", e))?;
+            let commit = repo.find_commit(oid).map_err(|e| format!("Failed to read commit: {}", e))?;
+            if commit.time().seconds() < since {
+                base = Some(commit);
+                break;
+            }
+            let tree = commit.tree().map_err(|e| format!("Failed to read commit tree: {}", e))?;
+            let parent_tree = commit.parent(0).ok().and_then(|p| p.tree().ok());
+            let diff = repo
+                .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+                .map_err(|e| format!("Failed to diff commit: {}", e))?;
+            let hash = git::short_hash(repo, oid);
+            for delta in diff.deltas() {
+                if let Some(path) = delta.new_file().path().and_then(|p| p.to_str()) {
+                    files.entry(path.to_string()).or_default().commits.push(hash.clone());
+                }
+            }
+        }
+    }
+
+    // One diff from before the query to the working tree covers both the new commits
+    // and what's not committed yet
+    let base_tree = base.as_ref().and_then(|c| c.tree().ok());
+    let mut opts = DiffOptions::new();
+    opts.context_lines(0);
+    let diff = repo
+        .diff_tree_to_workdir_with_index(base_tree.as_ref(), Some(&mut opts))
+        .map_err(|e| format!("Failed to diff working tree: {}", e))?;
+    diff_ranges(&diff, &mut files)?;
+    Ok(files)
+}
+
+/// How likely the changes addressed a thread on `line` of a file with `touched` changes;
+/// `in_query` says the query itself reported editing the file
+fn classify(line: Option<u32>, touched: Option<&TouchedFile>, in_query: bool) -> ThreadStatus {
+    let near = |line: u32| {
+        touched.is_some_and(|t| {
+            t.ranges
+                .iter()
+                .any(|&(start, end)| line + LINE_WINDOW >= start && line <= end + LINE_WINDOW)
+        })
+    };
+    match line {
+        Some(line) if near(line) => ThreadStatus::LikelyAddressed,
+        _ if in_query || touched.is_some_and(|t| !t.ranges.is_empty() || !t.commits.is_empty()) => {
+            ThreadStatus::FileTouched
+        }
+        _ => ThreadStatus::Untouched,
+    }
+}
+
+/// The query's changed files (absolute) as paths relative to the repository root
+fn relative_paths(repo: &Repository, changed: &[String]) -> HashSet<String> {
+    let Some(root) = repo.workdir() else {
+        return HashSet::new();
+    };
+    changed
+        .iter()
+        .filter_map(|p| Path::new(p).strip_prefix(root).ok())
+        .filter_map(|p| p.to_str())
+        .map(|p| p.replace('\\', "/"))
+        .collect()
+}
+
+/// Judge each unresolved thread against what changed in `repo` since `since`, with the
+/// query's own `changed_files` (absolute)
+fn match_threads(repo: &Repository, threads: Vec<ReviewThread>, since: i64, changed_files: &[String]) -> Result<Vec<ThreadMatch>, String> {
+    let changes = collect_changes(repo, since)?;
+    let in_query = relative_paths(repo, changed_files);
+    Ok(threads
+        .into_iter()
+        .filter(|t| !t.is_resolved)
+        .map(|thread| {
+            let touched = changes.get(&thread.path);
+            ThreadMatch {
+                status: classify(thread.line, touched, in_query.contains(&thread.path)),
+                commits: touched.map(|t| t.commits.clone()).unwrap_or_default(),
+                thread_id: thread.id,
+                path: thread.path,
+                line: thread.line,
+                replied: false,
+            }
+        })
+        .collect())
+}
+
+async fn post_reply(state: &GitState, thread_id: &str, body: &str) -> Result<(), String> {
+    let args = [
+        "api".to_string(),
+        "graphql".to_string(),
+        "-f".to_string(),
+        format!("query={}", REPLY_MUTATION),
+        "-f".to_string(),
+        format!("thread={}", thread_id),
+        "-f".to_string(),
+        format!("body={}", body),
+    ];
+    let output = git::run_external(state, "gh", &args, None, &[], None, None).await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Failed to reply to review thread: {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// A prompt asking to address the given unresolved review threads of a GitHub PR (all
+/// unresolved ones when `thread_ids` is empty), with the threads it was built from
+#[tauri::command]
+pub async fn build_review_fix_prompt(
+    state: State<'_, GitState>,
+    pr_url: String,
+    thread_ids: Vec<String>,
+) -> Result<ReviewFixPrompt, String> {
+    let all = fetch_review_threads(&state, &pr_url).await?;
+    let (threads, skipped) = if thread_ids.is_empty() {
+        (all.into_iter().filter(|t| !t.is_resolved).collect::<Vec<_>>(), Vec::new())
+    } else {
+        let by_id: HashMap<&str, &ReviewThread> = all.iter().map(|t| (t.id.as_str(), t)).collect();
+        let mut threads = Vec::new();
+        let mut skipped = Vec::new();
+        for id in thread_ids {
+            match by_id.get(id.as_str()) {
+                Some(thread) if !thread.is_resolved => threads.push((*thread).clone()),
+                _ => skipped.push(id),
+            }
+        }
+        (threads, skipped)
+    };
+
+    if threads.is_empty() {
+        return Err("No unresolved review threads to address".to_string());
+    }
+
+    Ok(ReviewFixPrompt {
+        prompt: format_prompt(&pr_url, &threads),
+        threads,
+        skipped,
+    })
+}
+
+/// Which unresolved threads of a PR a finished query likely addressed, judged by the files
+/// it changed and commits made since it started. With `post_replies`, likely-addressed
+/// threads with a commit get a reply naming it; threads are never resolved.
+#[tauri::command]
+pub async fn match_threads_to_changes(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    pr_url: String,
+    query_id: String,
+    post_replies: Option<bool>,
+) -> Result<Vec<ThreadMatch>, String> {
+    let record = history::find_record(&app, &query_id)
+        .await?
+        .ok_or_else(|| format!("No finished query found with id {}", query_id))?;
+    let threads = fetch_review_threads(&state, &pr_url).await?;
+
+    let mut matches = {
+        let repo = Repository::discover(&record.working_dir)
+            .map_err(|e| format!("Failed to open repository: {}", e))?;
+        match_threads(&repo, threads, record.started_at, &record.changed_files)?
+    };
+
+    if post_replies.unwrap_or(false) {
+        // Uncommitted fixes aren't on GitHub yet, so only threads with a commit get a reply
+        for m in matches.iter_mut() {
+            if m.status != ThreadStatus::LikelyAddressed || m.commits.is_empty() {
+                continue;
+            }
+            let body = format!("Addressed in {}.", m.commits.join(", "));
+            post_reply(&state, &m.thread_id, &body).await?;
+            m.replied = true;
+        }
+    }
+
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, fixture, write};
+
+    const PR: &str = "https://github.com/acme/app/pull/7";
+
+    /// The query started between the base commit and the fix commit
+    const STARTED_AT: i64 = 2_000_000_000;
+
+    fn threads() -> Vec<ReviewThread> {
+        let json: Value = serde_json::from_str(&fixture("review/threads.json")).unwrap();
+        threads_from_response(&json, PR).unwrap()
+    }
+
+    fn numbered(count: u32) -> String {
+        (1..=count).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    /// Commit everything with author and committer time `seconds`
+    fn commit_at(repo: &Repository, message: &str, seconds: i64) -> git2::Oid {
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::new("Test", "test@example.com", &git2::Time::new(seconds, 0)).unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+    }
+
+    /// A base commit from before the query, a fix commit from during it (line 30 of src/lib.rs,
+    /// docs/old.md deleted) and an uncommitted edit to line 5 of src/other.rs
+    fn worked_on_repo() -> (tempfile::TempDir, Repository, String) {
+        let (dir, repo) = test_support::repo();
+        write(dir.path(), "src/lib.rs", numbered(60));
+        write(dir.path(), "src/other.rs", numbered(10));
+        write(dir.path(), "README.md", "# app\n");
+        write(dir.path(), "docs/old.md", numbered(5));
+        commit_at(&repo, "base", STARTED_AT - 3600);
+
+        write(dir.path(), "src/lib.rs", numbered(60).replace("line 30\n", "fixed 30\n"));
+        std::fs::remove_file(dir.path().join("docs/old.md")).unwrap();
+        let fix = commit_at(&repo, "fix overflow", STARTED_AT + 60);
+        let hash = git::short_hash(&repo, fix);
+
+        write(dir.path(), "src/other.rs", numbered(10).replace("line 5\n", "edited 5\n"));
+        (dir, repo, hash)
+    }
+
+    fn statuses(matches: &[ThreadMatch]) -> Vec<(&str, ThreadStatus, Vec<&str>)> {
+        matches
+            .iter()
+            .map(|m| (m.thread_id.as_str(), m.status, m.commits.iter().map(String::as_str).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn threads_are_read_from_the_graphql_response() {
+        let threads = threads();
+        // The node without an id or comments is skipped
+        let ids: Vec<&str> = threads.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["RT_near", "RT_far", "RT_outdated", "RT_wip", "RT_readme", "RT_resolved", "RT_deleted"]);
+
+        let outdated = &threads[2];
+        assert_eq!(outdated.line, Some(24));
+        assert!(outdated.is_outdated && !outdated.is_resolved);
+        assert_eq!(threads[6].comments[0].author, "ghost");
+        assert_eq!(threads[0].comments.len(), 2);
+        assert!(threads[5].is_resolved);
+
+        let missing = threads_from_response(&serde_json::json!({ "data": { "repository": { "pullRequest": null } } }), PR);
+        assert_eq!(missing.unwrap_err(), format!("No pull request found at {}", PR));
+    }
+
+    #[test]
+    fn prompt_quotes_the_hunk_tail_and_every_comment() {
+        let threads = threads();
+        let prompt = format_prompt(PR, &threads[..3]);
+        assert!(prompt.starts_with(&format!("Address these review comments on {}.", PR)));
+        assert!(prompt.contains("\n## 1. src/lib.rs:35\n```diff\n line 16\n"), "{}", prompt);
+        assert!(!prompt.contains(" line 15\n") && !prompt.contains("@@ -10,25"));
+        assert!(prompt.contains("+    let total = a + b;\n```\n"));
+        assert!(prompt.contains("\n@alice wrote:\n> This can overflow.\n> Use checked_add.\n\n@bob wrote:\n> +1\n"));
+        assert!(prompt.contains("\n## 3. src/lib.rs:24 (outdated: the code has moved since)\n"));
+    }
+
+    #[test]
+    fn threads_are_matched_to_nearby_changes() {
+        let (_dir, repo, hash) = worked_on_repo();
+        let matches = match_threads(&repo, threads(), STARTED_AT, &[]).unwrap();
+        use ThreadStatus::*;
+        assert_eq!(
+            statuses(&matches),
+            [
+                // Five lines from the fix
+                ("RT_near", LikelyAddressed, vec![hash.as_str()]),
+                // Same file, 25 lines away
+                ("RT_far", FileTouched, vec![hash.as_str()]),
+                // Outdated: judged by the line it was left on
+                ("RT_outdated", LikelyAddressed, vec![hash.as_str()]),
+                // Not committed yet, so no commit to name
+                ("RT_wip", LikelyAddressed, vec![]),
+                ("RT_readme", Untouched, vec![]),
+                // The deleted file's only hunk sits at line 1
+                ("RT_deleted", LikelyAddressed, vec![hash.as_str()]),
+            ]
+        );
+        assert!(matches.iter().all(|m| !m.replied));
+    }
+
+    #[test]
+    fn files_the_query_reported_count_as_touched() {
+        let (dir, repo, _) = worked_on_repo();
+        let readme = dir.path().join("README.md").to_string_lossy().to_string();
+        let elsewhere = "/somewhere/else/README.md".to_string();
+        let matches = match_threads(&repo, threads(), STARTED_AT, &[readme, elsewhere]).unwrap();
+        let readme = matches.iter().find(|m| m.thread_id == "RT_readme").unwrap();
+        assert_eq!(readme.status, ThreadStatus::FileTouched);
+        assert!(readme.commits.is_empty());
+    }
+
+    #[test]
+    fn commits_before_the_query_are_the_base() {
+        let (_dir, repo, _) = worked_on_repo();
+        // Started after the fix commit: only the uncommitted edit is the query's
+        let matches = match_threads(&repo, threads(), STARTED_AT + 3600, &[]).unwrap();
+        let touched: Vec<(&str, ThreadStatus)> = matches
+            .iter()
+            .filter(|m| m.status != ThreadStatus::Untouched)
+            .map(|m| (m.thread_id.as_str(), m.status))
+            .collect();
+        assert_eq!(touched, [("RT_wip", ThreadStatus::LikelyAddressed)]);
+    }
+
+    #[test]
+    fn nearness_is_line_window_either_side_of_a_hunk() {
+        let touched = TouchedFile { ranges: vec![(30, 32)], commits: Vec::new() };
+        let status = |line| classify(Some(line), Some(&touched), false);
+        assert_eq!(status(30 - LINE_WINDOW), ThreadStatus::LikelyAddressed);
+        assert_eq!(status(30 - LINE_WINDOW - 1), ThreadStatus::FileTouched);
+        assert_eq!(status(32 + LINE_WINDOW), ThreadStatus::LikelyAddressed);
+        assert_eq!(status(32 + LINE_WINDOW + 1), ThreadStatus::FileTouched);
+        // A thread without a line is never near, only on a touched file
+        assert_eq!(classify(None, Some(&touched), false), ThreadStatus::FileTouched);
+        assert_eq!(classify(None, None, false), ThreadStatus::Untouched);
+        assert_eq!(classify(Some(1), Some(&TouchedFile::default()), false), ThreadStatus::Untouched);
+    }
+}
@@ -0,0 +1,201 @@
+// mensa - Node Runtime Module
+// Checks the node binary's version against what the agent SDK needs, and finds a newer one when it's too old
+
+use crate::{node_candidates, settings, AppState};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tauri::{Manager, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Oldest node the Claude Agent SDK runs on; `minNodeVersion` in settings overrides it
+pub const DEFAULT_MIN_NODE_VERSION: &str = "18.0.0";
+
+/// How long `node --version` may take before the binary counts as unreadable
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `node --version` output by binary, valid while the binary's mtime matches
+type VersionCache = HashMap<String, (Option<SystemTime>, Option<String>)>;
+
+static VERSION_CACHE: Mutex<Option<VersionCache>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeStatus {
+    Ok,
+    /// Older than required; queries are refused
+    TooOld,
+    /// Ran, but the version wasn't recognizable (nightly builds, forks); queries run with a warning
+    Unrecognized,
+    /// Couldn't be run at all; the spawn reports why
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeCandidate {
+    pub path: String,
+    pub version: String,
+}
+
+/// The node binary queries will use, checked against the required version
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeCheck {
+    pub path: String,
+    /// As printed by `node --version`
+    pub version: Option<String>,
+    pub required: String,
+    pub status: NodeStatus,
+    /// The newest binary in the usual locations that is new enough, when this one isn't
+    pub alternative: Option<NodeCandidate>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// (major, minor, patch) of "v22.3.0", "18", "v23.0.0-nightly2024..."; None for anything
+/// else (the caller warns instead of blocking)
+pub fn parse_version(raw: &str) -> Option<(u64, u64, u64)> {
+    let raw = raw.trim();
+    let raw = raw.strip_prefix('v').unwrap_or(raw);
+    let core = raw.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let major = parts.next()??;
+    let minor = parts.next().unwrap_or(Some(0))?;
+    let patch = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch))
+}
+
+/// `node --version` of a binary, run once per path (again only if the binary changed)
+async fn node_version(path: &str) -> Option<String> {
+    let mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let cached = VERSION_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .as_ref()
+        .and_then(|cache| cache.get(path).cloned());
+    if let Some((cached_mtime, version)) = cached {
+        if cached_mtime == mtime {
+            return version;
+        }
+    }
+
+    let output = tokio::time::timeout(
+        VERSION_TIMEOUT,
+        tokio::process::Command::new(path).arg("--version").kill_on_drop(true).output(),
+    )
+    .await;
+    let version = match output {
+        Ok(Ok(output)) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|v| !v.is_empty())
+        }
+        _ => None,
+    };
+    VERSION_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(path.to_string(), (mtime, version.clone()));
+    version
+}
+
+/// The required version from settings, or the built-in one
+async fn required_version(app: &tauri::AppHandle) -> String {
+    let state = app.state::<AppState>();
+    settings::load(app, &state.settings)
+        .await
+        .ok()
+        .and_then(|s| s.min_node_version.clone())
+        .filter(|v| parse_version(v).is_some())
+        .unwrap_or_else(|| DEFAULT_MIN_NODE_VERSION.to_string())
+}
+
+/// Newest binary in the usual locations that meets `required`, other than `current`
+async fn newer_candidate(current: &str, required: (u64, u64, u64)) -> Option<NodeCandidate> {
+    let mut best: Option<((u64, u64, u64), NodeCandidate)> = None;
+    for path in node_candidates() {
+        if path == current {
+            continue;
+        }
+        let Some(version) = node_version(&path).await else {
+            continue;
+        };
+        let Some(parsed) = parse_version(&version).filter(|v| *v >= required) else {
+            continue;
+        };
+        if best.as_ref().is_none_or(|(found, _)| parsed > *found) {
+            best = Some((parsed, NodeCandidate { path, version }));
+        }
+    }
+    best.map(|(_, candidate)| candidate)
+}
+
+/// Check a node binary against the required version (the one queries would use when None)
+pub async fn check_node(app: &tauri::AppHandle, path: Option<String>) -> NodeCheck {
+    let path = match path {
+        Some(path) => path,
+        None => settings::node_binary(app).await,
+    };
+    let required = required_version(app).await;
+    let version = node_version(&path).await;
+    let minimum = parse_version(&required).unwrap_or_default();
+    let status = match version.as_deref().map(parse_version) {
+        None => NodeStatus::Missing,
+        Some(None) => NodeStatus::Unrecognized,
+        Some(Some(found)) if found < minimum => NodeStatus::TooOld,
+        Some(Some(_)) => NodeStatus::Ok,
+    };
+    let alternative = match status {
+        NodeStatus::TooOld => newer_candidate(&path, minimum).await,
+        _ => None,
+    };
+    NodeCheck {
+        path,
+        version,
+        required,
+        status,
+        alternative,
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Run queries with this node binary from now on (saved as `nodePath`), after checking its version
+#[tauri::command]
+pub async fn set_preferred_runtime_path(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<NodeCheck, settings::SettingsError> {
+    if !Path::new(&path).is_file() {
+        return Err(format!("{} does not exist", path).into());
+    }
+    let check = check_node(&app, Some(path.clone())).await;
+    match check.status {
+        NodeStatus::TooOld => {
+            return Err(format!(
+                "{} is node {}, older than the required {}",
+                path,
+                check.version.as_deref().unwrap_or_default(),
+                check.required
+            )
+            .into())
+        }
+        NodeStatus::Missing => return Err(format!("{} could not be run", path).into()),
+        NodeStatus::Ok | NodeStatus::Unrecognized => {}
+    }
+    settings::update_settings(app.clone(), state, serde_json::json!({ "nodePath": path })).await?;
+    Ok(check)
+}
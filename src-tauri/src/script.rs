@@ -0,0 +1,530 @@
+// mensa - Query Script Module
+// Locates claude-query.mjs, falling back to a copy embedded in the binary when packaging lost it
+// or the packaged copy can't be used where it is (read-only, or translocated by macOS)
+
+use crate::{fsutil, git, runtime, settings, AppState};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{Manager, State};
+use tokio::sync::Mutex;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+const EMBEDDED_SCRIPT: &str = include_str!("../../scripts/claude-query.mjs");
+const EMBEDDED_MANIFEST: &str = include_str!("../../scripts/package.json");
+const EMBEDDED_LOCKFILE: &str = include_str!("../../scripts/package-lock.json");
+
+const SCRIPT_NAME: &str = "claude-query.mjs";
+
+/// Written next to an extracted script once `npm ci` has finished; holds `install_stamp()`
+const INSTALLED_MARKER: &str = ".mensa-installed";
+
+/// Installing the agent SDK can take a while on a slow connection
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Serializes extraction so concurrent queries don't run two installs in one directory
+static EXTRACT_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScriptSource {
+    ResourceDir,
+    Executable,
+    DevCwd,
+    Embedded,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptLocation {
+    pub path: PathBuf,
+    pub source: ScriptSource,
+}
+
+/// A packaged copy of the script and what its directory allows
+#[derive(Debug, Clone)]
+struct Candidate {
+    path: PathBuf,
+    source: ScriptSource,
+    /// Under macOS App Translocation's randomized read-only mount
+    translocated: bool,
+    writable: bool,
+    /// node_modules sits next to the script
+    has_dependencies: bool,
+}
+
+/// Where `resolve_script` takes the script from
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Resolution {
+    Packaged(PathBuf, ScriptSource),
+    /// The embedded copy in app data, extracted (and its dependencies installed) if needed
+    Extracted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AppLocationKind {
+    /// Run straight from a download or disk image; macOS moved it to a random read-only path
+    Translocated,
+    /// The app's resources can't be written (a disk image, a read-only mount)
+    ReadOnly,
+}
+
+/// Why the packaged script isn't used where it is; the script runs from app data instead
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppLocationIssue {
+    pub kind: AppLocationKind,
+    pub resource_dir: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeHealth {
+    pub node_binary: String,
+    pub node_found: bool,
+    /// Version of node_binary against the required one, with a newer binary when it's too old
+    pub node_check: runtime::NodeCheck,
+    pub script: Option<ScriptLocation>,
+    pub script_error: Option<String>,
+    pub app_location: Option<AppLocationIssue>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Places a packaged or development copy of the script may live, in lookup order
+fn candidate_paths(app: &tauri::AppHandle) -> Vec<(PathBuf, ScriptSource)> {
+    let mut candidates = Vec::new();
+
+    // 1. Tauri resource directory (for bundled app)
+    if let Ok(resource_dir) = app.path().resource_dir() {
+        // Tauri v2 puts "../scripts" into "_up_/scripts" to preserve relative paths
+        candidates.push((resource_dir.join("_up_/scripts").join(SCRIPT_NAME), ScriptSource::ResourceDir));
+        candidates.push((resource_dir.join("scripts").join(SCRIPT_NAME), ScriptSource::ResourceDir));
+    }
+
+    // 2. Relative to executable (for development/bundled)
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(parent) = exe_path.parent() {
+            // macOS .app bundle structure: Contents/MacOS/binary -> Contents/Resources
+            candidates.push((parent.join("../Resources/_up_/scripts").join(SCRIPT_NAME), ScriptSource::Executable));
+            candidates.push((parent.join("../Resources/scripts").join(SCRIPT_NAME), ScriptSource::Executable));
+        }
+    }
+
+    // 3. Current working directory (for development)
+    if let Ok(cwd) = std::env::current_dir() {
+        candidates.push((cwd.join("scripts").join(SCRIPT_NAME), ScriptSource::DevCwd));
+    }
+
+    candidates
+}
+
+/// macOS runs quarantined apps opened from where they were downloaded from a randomized
+/// read-only mount under /private/var/folders/.../AppTranslocation/
+fn is_translocated(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == "AppTranslocation")
+}
+
+/// Whether files can be created in `dir` (a read-only mount counts as not writable)
+fn is_writable(dir: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        nix::unistd::access(dir, nix::unistd::AccessFlags::W_OK).is_ok()
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::metadata(dir).is_ok_and(|m| !m.permissions().readonly())
+    }
+}
+
+fn inspect(path: PathBuf, source: ScriptSource) -> Candidate {
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    Candidate {
+        translocated: is_translocated(&path),
+        writable: is_writable(&dir),
+        has_dependencies: dir.join("node_modules").is_dir(),
+        path,
+        source,
+    }
+}
+
+/// Pick the script among existing packaged copies, in lookup order. A translocated copy is
+/// never used: its path changes every launch. A read-only copy is only used when it ships its
+/// dependencies (none can be installed next to it) and no extracted copy is ready to use.
+fn choose(candidates: &[Candidate], extracted_ready: bool) -> Resolution {
+    for candidate in candidates {
+        if candidate.translocated {
+            continue;
+        }
+        if !candidate.writable {
+            if extracted_ready {
+                return Resolution::Extracted;
+            }
+            if !candidate.has_dependencies {
+                continue;
+            }
+        }
+        return Resolution::Packaged(candidate.path.clone(), candidate.source);
+    }
+    Resolution::Extracted
+}
+
+/// Why the resource directory's script can't be used in place, if it can't
+fn location_issue(app: &tauri::AppHandle) -> Option<AppLocationIssue> {
+    let resource_dir = app.path().resource_dir().ok()?;
+    let resource_copy = candidate_paths(app)
+        .into_iter()
+        .filter(|(path, source)| *source == ScriptSource::ResourceDir && path.exists())
+        .map(|(path, source)| inspect(path, source))
+        .next()?;
+    let (kind, message) = if resource_copy.translocated {
+        (
+            AppLocationKind::Translocated,
+            "macOS is running mensa from a temporary read-only copy because it was opened where it was downloaded. \
+             Move mensa to the Applications folder and open it from there."
+                .to_string(),
+        )
+    } else if !resource_copy.writable {
+        (
+            AppLocationKind::ReadOnly,
+            "mensa's resources are read-only (e.g. it runs from a disk image), so the query script runs from a copy \
+             in the app data directory. Move mensa to the Applications folder to use the bundled copy."
+                .to_string(),
+        )
+    } else {
+        return None;
+    };
+    Some(AppLocationIssue {
+        kind,
+        resource_dir: resource_dir.to_string_lossy().to_string(),
+        message,
+    })
+}
+
+fn extraction_root(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("scripts"))
+}
+
+/// npm is installed next to node by every common installer; fall back to PATH
+pub fn npm_binary(node_binary: &str) -> String {
+    let name = if cfg!(windows) { "npm.cmd" } else { "npm" };
+    let node = Path::new(node_binary);
+    if node.is_absolute() {
+        let sibling = node.with_file_name(name);
+        if sibling.exists() {
+            return sibling.to_string_lossy().to_string();
+        }
+    }
+    name.to_string()
+}
+
+fn which_on_path(binary: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(binary).is_file()))
+        .unwrap_or(false)
+}
+
+/// Identifies the dependency set an install was made from
+fn install_stamp() -> String {
+    fsutil::sha256_hex(format!("{}\n{}", EMBEDDED_MANIFEST, EMBEDDED_LOCKFILE).as_bytes())
+}
+
+/// Whether an install of the embedded lockfile finished in `dir`. node_modules alone doesn't
+/// say so: an interrupted install leaves it half populated.
+fn dependencies_installed(dir: &Path) -> bool {
+    std::fs::read_to_string(dir.join(INSTALLED_MARKER)).is_ok_and(|stamp| stamp.trim() == install_stamp())
+}
+
+/// The extracted copy's directory for this app version
+fn extraction_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(extraction_root(app)?.join(app.package_info().version.to_string()))
+}
+
+/// An extracted copy of this version with its dependencies installed, ready without any writes
+fn extracted_ready(app: &tauri::AppHandle) -> bool {
+    let Ok(dir) = extraction_dir(app) else {
+        return false;
+    };
+    let expected_hash = fsutil::sha256_hex(EMBEDDED_SCRIPT.as_bytes());
+    dependencies_installed(&dir)
+        && fsutil::file_hash(&dir.join(SCRIPT_NAME)).ok().flatten().as_deref() == Some(expected_hash.as_str())
+}
+
+/// Write the embedded script, manifest and lockfile to app data (versioned by app version),
+/// install the locked SDK if needed, and check the script on disk matches the embedded copy
+async fn extract_embedded(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let _guard = EXTRACT_LOCK.lock().await;
+
+    let dir = extraction_dir(app)?;
+    let script_path = dir.join(SCRIPT_NAME);
+    let expected_hash = fsutil::sha256_hex(EMBEDDED_SCRIPT.as_bytes());
+
+    if fsutil::file_hash(&script_path)?.as_deref() != Some(expected_hash.as_str()) {
+        fsutil::write_atomic(&script_path, EMBEDDED_SCRIPT.as_bytes())?;
+    }
+    if fsutil::file_hash(&dir.join("package.json"))?.as_deref()
+        != Some(fsutil::sha256_hex(EMBEDDED_MANIFEST.as_bytes()).as_str())
+    {
+        fsutil::write_atomic(&dir.join("package.json"), EMBEDDED_MANIFEST.as_bytes())?;
+    }
+    if fsutil::file_hash(&dir.join("package-lock.json"))?.as_deref()
+        != Some(fsutil::sha256_hex(EMBEDDED_LOCKFILE.as_bytes()).as_str())
+    {
+        fsutil::write_atomic(&dir.join("package-lock.json"), EMBEDDED_LOCKFILE.as_bytes())?;
+    }
+
+    if !dependencies_installed(&dir) {
+        let _ = std::fs::remove_file(dir.join(INSTALLED_MARKER));
+        let npm = npm_binary(&settings::node_binary(app).await);
+        let git_state = app.state::<git::GitState>();
+        // `npm ci` installs exactly the locked versions, replacing whatever node_modules holds
+        let output = git::run_external(
+            &git_state,
+            &npm,
+            &["ci", "--omit=dev", "--no-audit", "--no-fund"],
+            Some(&dir.to_string_lossy()),
+            &[],
+            Some(INSTALL_TIMEOUT),
+            None,
+        )
+        .await
+        .map_err(|e| format!("Failed to install query script dependencies: {}", e))?;
+        if !output.status.success() {
+            let _ = std::fs::remove_dir_all(dir.join("node_modules"));
+            return Err(format!(
+                "Failed to install query script dependencies: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        fsutil::write_atomic(&dir.join(INSTALLED_MARKER), install_stamp().as_bytes())?;
+    }
+
+    // Verify right before use; a tampered or half-written file is never executed
+    if fsutil::file_hash(&script_path)?.as_deref() != Some(expected_hash.as_str()) {
+        return Err(format!("Integrity check failed for {}", script_path.display()));
+    }
+    Ok(script_path)
+}
+
+/// Where the script would be taken from right now
+fn resolution(app: &tauri::AppHandle) -> Resolution {
+    let candidates: Vec<Candidate> = candidate_paths(app)
+        .into_iter()
+        .filter(|(path, _)| path.exists())
+        .map(|(path, source)| inspect(path, source))
+        .collect();
+    choose(&candidates, extracted_ready(app))
+}
+
+/// Find the query script, extracting the embedded copy when no packaged one is usable
+pub async fn resolve_script(app: &tauri::AppHandle) -> Result<ScriptLocation, String> {
+    let location = match resolution(app) {
+        Resolution::Packaged(path, source) => ScriptLocation { path, source },
+        Resolution::Extracted => ScriptLocation {
+            path: extract_embedded(app).await?,
+            source: ScriptSource::Embedded,
+        },
+    };
+
+    let state = app.state::<AppState>();
+    *state.script_location.lock().await = Some(location.clone());
+    Ok(location)
+}
+
+/// Remove scripts extracted by other app versions
+pub fn cleanup_old_extractions(app: &tauri::AppHandle) -> Result<(), String> {
+    let root = extraction_root(app)?;
+    let current = app.package_info().version.to_string();
+    let entries = match std::fs::read_dir(&root) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(()),
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy() != current && entry.path().is_dir() {
+            std::fs::remove_dir_all(entry.path())
+                .map_err(|e| format!("Failed to remove old script directory {}: {}", entry.path().display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Report the node binary and query script the app will use (and where the script came from).
+/// Only looks: an extraction or install still to do is reported, left to the next query.
+#[tauri::command]
+pub async fn check_runtime_health(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<RuntimeHealth, String> {
+    let node_binary = settings::node_binary(&app).await;
+    let node_found = Path::new(&node_binary).is_absolute() || which_on_path(&node_binary);
+    let node_check = runtime::check_node(&app, Some(node_binary.clone())).await;
+
+    let last_used = state.script_location.lock().await.clone();
+    let (script, script_error) = match last_used {
+        Some(location) if location.path.exists() => (Some(location), None),
+        _ => match resolution(&app) {
+            Resolution::Packaged(path, source) => (Some(ScriptLocation { path, source }), None),
+            Resolution::Extracted if extracted_ready(&app) => (
+                extraction_dir(&app).ok().map(|dir| ScriptLocation {
+                    path: dir.join(SCRIPT_NAME),
+                    source: ScriptSource::Embedded,
+                }),
+                None,
+            ),
+            Resolution::Extracted => (
+                None,
+                Some(
+                    "No usable packaged query script; the first query extracts the bundled copy and installs its dependencies"
+                        .to_string(),
+                ),
+            ),
+        },
+    };
+
+    Ok(RuntimeHealth {
+        node_binary,
+        node_found,
+        node_check,
+        script,
+        script_error,
+        app_location: location_issue(&app),
+    })
+}
+
+/// Show the running app in the file manager (the .app bundle on macOS), so it can be moved
+/// to the Applications folder; returns the path shown
+#[tauri::command]
+pub async fn reveal_app_location() -> Result<String, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to locate the app: {}", e))?;
+    // Contents/MacOS/mensa inside the bundle
+    let bundle = exe
+        .ancestors()
+        .find(|dir| dir.extension().is_some_and(|ext| ext == "app"))
+        .map(Path::to_path_buf)
+        .unwrap_or(exe);
+    tauri_plugin_opener::reveal_item_in_dir(&bundle).map_err(|e| format!("Failed to reveal the app: {}", e))?;
+    Ok(bundle.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_modules_without_the_marker_is_not_an_install() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("node_modules")).unwrap();
+        assert!(!dependencies_installed(dir.path()));
+
+        std::fs::write(dir.path().join(INSTALLED_MARKER), "stale").unwrap();
+        assert!(!dependencies_installed(dir.path()));
+
+        std::fs::write(dir.path().join(INSTALLED_MARKER), install_stamp()).unwrap();
+        assert!(dependencies_installed(dir.path()));
+    }
+
+    #[test]
+    fn embedded_lockfile_locks_the_manifest_dependencies() {
+        let manifest: serde_json::Value = serde_json::from_str(EMBEDDED_MANIFEST).unwrap();
+        let lockfile: serde_json::Value = serde_json::from_str(EMBEDDED_LOCKFILE).unwrap();
+        assert_eq!(lockfile["packages"][""]["dependencies"], manifest["dependencies"]);
+        for name in manifest["dependencies"].as_object().unwrap().keys() {
+            assert!(lockfile["packages"][format!("node_modules/{}", name)]["version"].is_string());
+        }
+    }
+
+    fn candidate(name: &str, source: ScriptSource, writable: bool, has_dependencies: bool) -> Candidate {
+        Candidate {
+            path: PathBuf::from(name).join(SCRIPT_NAME),
+            source,
+            translocated: is_translocated(Path::new(name)),
+            writable,
+            has_dependencies,
+        }
+    }
+
+    fn packaged(name: &str, source: ScriptSource) -> Resolution {
+        Resolution::Packaged(PathBuf::from(name).join(SCRIPT_NAME), source)
+    }
+
+    #[test]
+    fn first_writable_candidate_wins_in_lookup_order() {
+        let candidates = [
+            candidate("/app/Resources/_up_/scripts", ScriptSource::ResourceDir, true, false),
+            candidate("/app/MacOS/../Resources/scripts", ScriptSource::Executable, true, true),
+            candidate("/src/mensa/scripts", ScriptSource::DevCwd, true, true),
+        ];
+        for extracted_ready in [false, true] {
+            assert_eq!(choose(&candidates, extracted_ready), packaged("/app/Resources/_up_/scripts", ScriptSource::ResourceDir));
+            assert_eq!(choose(&candidates[1..], extracted_ready), packaged("/app/MacOS/../Resources/scripts", ScriptSource::Executable));
+        }
+        assert_eq!(choose(&[], false), Resolution::Extracted);
+    }
+
+    #[test]
+    fn read_only_candidates_defer_to_a_ready_extraction() {
+        let read_only = candidate("/Volumes/mensa/mensa.app/Contents/Resources/scripts", ScriptSource::ResourceDir, false, true);
+        let dev = candidate("/src/mensa/scripts", ScriptSource::DevCwd, true, false);
+
+        // Shipping its dependencies, a read-only copy runs in place until an extraction is ready
+        assert_eq!(choose(std::slice::from_ref(&read_only), false), packaged("/Volumes/mensa/mensa.app/Contents/Resources/scripts", ScriptSource::ResourceDir));
+        assert_eq!(choose(std::slice::from_ref(&read_only), true), Resolution::Extracted);
+        // ...and the ready extraction is preferred even over a writable copy later in the order
+        assert_eq!(choose(&[read_only.clone(), dev.clone()], true), Resolution::Extracted);
+
+        // Without dependencies nothing can be installed next to it: the next candidate is tried
+        let bare = candidate("/Volumes/mensa/mensa.app/Contents/Resources/scripts", ScriptSource::ResourceDir, false, false);
+        assert_eq!(choose(&[bare.clone(), dev.clone()], false), packaged("/src/mensa/scripts", ScriptSource::DevCwd));
+        assert_eq!(choose(std::slice::from_ref(&bare), false), Resolution::Extracted);
+    }
+
+    #[test]
+    fn translocated_candidates_are_never_used() {
+        let translocated = candidate(
+            "/private/var/folders/xy/T/AppTranslocation/6F2A/d/mensa.app/Contents/Resources/scripts",
+            ScriptSource::ResourceDir,
+            true,
+            true,
+        );
+        assert!(translocated.translocated);
+        assert_eq!(choose(std::slice::from_ref(&translocated), false), Resolution::Extracted);
+        let dev = candidate("/src/mensa/scripts", ScriptSource::DevCwd, true, true);
+        assert_eq!(choose(&[translocated, dev], false), packaged("/src/mensa/scripts", ScriptSource::DevCwd));
+        assert!(!is_translocated(Path::new("/Applications/mensa.app/Contents/Resources/scripts")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inspect_reads_the_directory_a_copy_sits_in() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let scripts = dir.path().join("scripts");
+        std::fs::create_dir(&scripts).unwrap();
+        let found = inspect(scripts.join(SCRIPT_NAME), ScriptSource::ResourceDir);
+        assert!(found.writable && !found.has_dependencies && !found.translocated);
+
+        std::fs::create_dir(scripts.join("node_modules")).unwrap();
+        assert!(inspect(scripts.join(SCRIPT_NAME), ScriptSource::ResourceDir).has_dependencies);
+
+        // Root may write anywhere, so the read-only directory is only read-only to other users
+        std::fs::set_permissions(&scripts, std::fs::Permissions::from_mode(0o555)).unwrap();
+        let read_only = inspect(scripts.join(SCRIPT_NAME), ScriptSource::ResourceDir);
+        std::fs::set_permissions(&scripts, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(read_only.writable, nix::unistd::geteuid().is_root());
+        assert_eq!(choose(&[read_only], false), packaged(scripts.to_str().unwrap(), ScriptSource::ResourceDir));
+    }
+}
@@ -0,0 +1,345 @@
+// mensa - Secrets Module
+// Keeps secret values in the OS keychain; only names and metadata live on disk
+
+use crate::store::JsonStore;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Keychain service name all mensa secrets are stored under
+const KEYCHAIN_SERVICE: &str = "com.samihindi.mensa";
+
+/// Secret name used for the GitHub token fallback
+pub const GITHUB_TOKEN_SECRET: &str = "github_token";
+
+/// Secret name of the GitLab API token; `gitlab_token@<host>` takes precedence for that host
+pub const GITLAB_TOKEN_SECRET: &str = "gitlab_token";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredSecretInfo {
+    pub name: String,
+    pub kind: String, // "env" | "github_token" | "gitlab_token" | "credential" | "proxy"
+    pub created_at: i64,
+    /// Plaintext value from older builds; kept in the file only until it's in the keychain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SecretsIndex {
+    secrets: Vec<StoredSecretInfo>,
+}
+
+static INDEX_FILE: JsonStore<SecretsIndex> = JsonStore::new("secrets index");
+
+/// Supplies the stored GitHub token to `gh` runs (shared with GitState). Bound to the app
+/// at setup; the keychain is only read when a `gh` process is about to start.
+#[derive(Clone, Default)]
+pub struct GhTokenSource {
+    app: Arc<OnceLock<tauri::AppHandle>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn index_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("secrets.json"))
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Map keyring errors to messages that tell the user what to do.
+/// A missing secret service is an error, never a reason to fall back to plaintext.
+fn keychain_error(err: keyring::Error) -> String {
+    match err {
+        keyring::Error::NoStorageAccess(e) => {
+            if cfg!(target_os = "linux") {
+                format!(
+                    "No keychain available ({}). Install and unlock a Secret Service provider such as gnome-keyring or KWallet.",
+                    e
+                )
+            } else {
+                format!("Keychain is locked or inaccessible: {}", e)
+            }
+        }
+        keyring::Error::PlatformFailure(e) => format!("Keychain error: {}", e),
+        keyring::Error::NoEntry => "Secret not found in keychain".to_string(),
+        other => format!("Keychain error: {}", other),
+    }
+}
+
+async fn keychain_set(name: String, value: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        keyring::Entry::new(KEYCHAIN_SERVICE, &name)
+            .and_then(|entry| entry.set_password(&value))
+            .map_err(keychain_error)
+    })
+    .await
+    .map_err(|e| format!("Keychain task failed: {}", e))?
+}
+
+async fn keychain_get(name: String) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        keyring::Entry::new(KEYCHAIN_SERVICE, &name)
+            .and_then(|entry| entry.get_password())
+            .map_err(keychain_error)
+    })
+    .await
+    .map_err(|e| format!("Keychain task failed: {}", e))?
+}
+
+async fn keychain_delete(name: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        match keyring::Entry::new(KEYCHAIN_SERVICE, &name).and_then(|entry| entry.delete_credential()) {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keychain_error(e)),
+        }
+    })
+    .await
+    .map_err(|e| format!("Keychain task failed: {}", e))?
+}
+
+async fn load_index(app: &tauri::AppHandle) -> Result<SecretsIndex, String> {
+    INDEX_FILE.read(app, index_path(app)?).await
+}
+
+/// Record `name` as stored in the keychain. Its keychain value is now current, so a
+/// plaintext value still waiting for migration is dropped.
+fn record(index: &mut SecretsIndex, name: &str, kind: &str) -> StoredSecretInfo {
+    match index.secrets.iter_mut().find(|s| s.name == name) {
+        Some(existing) => {
+            existing.kind = kind.to_string();
+            existing.value = None;
+            existing.clone()
+        }
+        None => {
+            let info = StoredSecretInfo {
+                name: name.to_string(),
+                kind: kind.to_string(),
+                created_at: now_secs(),
+                value: None,
+            };
+            index.secrets.push(info.clone());
+            info
+        }
+    }
+}
+
+/// Whether a child process's environment already names a GitHub token
+fn has_github_token_env(envs: &[(&str, &str)]) -> bool {
+    let named = |key: &str| envs.iter().any(|(k, _)| *k == key) || std::env::var_os(key).is_some();
+    named("GH_TOKEN") || named("GITHUB_TOKEN")
+}
+
+impl GhTokenSource {
+    pub fn bind(&self, app: &tauri::AppHandle) {
+        let _ = self.app.set(app.clone());
+    }
+
+    /// The stored GitHub token for a `gh` run with `envs`; None when the environment
+    /// already has one, none is stored, or the keychain can't be read (gh uses its own login)
+    pub async fn token(&self, envs: &[(&str, &str)]) -> Option<String> {
+        let app = self.app.get()?;
+        if has_github_token_env(envs) || !has_secret(app, GITHUB_TOKEN_SECRET).await.unwrap_or(false) {
+            return None;
+        }
+        match read_secret(app, GITHUB_TOKEN_SECRET).await {
+            Ok(token) => Some(token),
+            Err(e) => {
+                eprintln!("[mensa] {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Read a secret from the keychain. Called only when the value is actually
+/// needed (e.g. at spawn time) so unlock prompts don't appear eagerly.
+pub async fn read_secret(app: &tauri::AppHandle, name: &str) -> Result<String, String> {
+    let index = load_index(app).await?;
+    if !index.secrets.iter().any(|s| s.name == name) {
+        return Err(format!("No stored secret named '{}'", name));
+    }
+    keychain_get(name.to_string())
+        .await
+        .map_err(|e| format!("Failed to read secret '{}': {}", name, e))
+}
+
+/// Whether a secret by this name was stored (without touching the keychain)
+pub async fn has_secret(app: &tauri::AppHandle, name: &str) -> Result<bool, String> {
+    Ok(load_index(app).await?.secrets.iter().any(|s| s.name == name))
+}
+
+/// Store a secret value in the keychain and record its name with `kind`
+pub async fn store_secret(app: &tauri::AppHandle, name: &str, value: String, kind: &str) -> Result<StoredSecretInfo, String> {
+    keychain_set(name.to_string(), value).await?;
+
+    let index_file = INDEX_FILE.lock(app, index_path(app)?).await;
+    let mut index = index_file.load().await?;
+    let info = record(&mut index, name, kind);
+    index_file.save(&index).await?;
+    Ok(info)
+}
+
+/// Delete a secret from the keychain and the metadata file; false if it wasn't recorded
+pub async fn remove_secret(app: &tauri::AppHandle, name: &str) -> Result<bool, String> {
+    let index_file = INDEX_FILE.lock(app, index_path(app)?).await;
+    let mut index = index_file.load().await?;
+    let before = index.secrets.len();
+    index.secrets.retain(|s| s.name != name);
+
+    keychain_delete(name.to_string()).await?;
+
+    if index.secrets.len() == before {
+        return Ok(false);
+    }
+
+    index_file.save(&index).await?;
+    Ok(true)
+}
+
+/// Move plaintext values left by older builds into the keychain and scrub the file.
+/// Values that fail to migrate stay in place so nothing is lost.
+pub async fn migrate_plaintext(app: &tauri::AppHandle) -> Result<usize, String> {
+    let path = index_path(app)?;
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let index_file = INDEX_FILE.lock(app, path).await;
+    let mut index = index_file.load().await?;
+    let mut migrated = 0;
+    let mut first_error = None;
+
+    for secret in index.secrets.iter_mut() {
+        if let Some(value) = secret.value.take() {
+            match keychain_set(secret.name.clone(), value.clone()).await {
+                Ok(()) => migrated += 1,
+                Err(e) => {
+                    secret.value = Some(value);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    if migrated > 0 {
+        // Saving twice makes the backup (the previous content, plaintext included) scrubbed too
+        index_file.save(&index).await?;
+        index_file.save(&index).await?;
+    }
+
+    if let Some(err) = first_error {
+        return Err(format!("Failed to migrate secrets into the keychain: {}", err));
+    }
+
+    Ok(migrated)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List stored secrets (names and metadata only, never values)
+#[tauri::command]
+pub async fn list_stored_secrets(app: tauri::AppHandle) -> Result<Vec<StoredSecretInfo>, String> {
+    let mut secrets = load_index(&app).await?.secrets;
+    for secret in secrets.iter_mut() {
+        secret.value = None;
+    }
+    Ok(secrets)
+}
+
+/// Store a secret value in the keychain and record its name
+#[tauri::command]
+pub async fn set_stored_secret(
+    app: tauri::AppHandle,
+    name: String,
+    value: String,
+    kind: Option<String>,
+) -> Result<StoredSecretInfo, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Secret name cannot be empty".to_string());
+    }
+
+    let kind = kind.unwrap_or_else(|| {
+        if name == GITHUB_TOKEN_SECRET {
+            "github_token".to_string()
+        } else if name == GITLAB_TOKEN_SECRET || name.starts_with(&format!("{}@", GITLAB_TOKEN_SECRET)) {
+            "gitlab_token".to_string()
+        } else {
+            "env".to_string()
+        }
+    });
+    store_secret(&app, &name, value, &kind).await
+}
+
+/// Delete a secret from the keychain and the metadata file
+#[tauri::command]
+pub async fn delete_stored_secret(app: tauri::AppHandle, name: String) -> Result<bool, String> {
+    remove_secret(&app, &name).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy_index() -> SecretsIndex {
+        serde_json::from_str(
+            r#"{"secrets": [
+                {"name": "OPENAI_KEY", "kind": "env", "createdAt": 1, "value": "sk-old"},
+                {"name": "github_token", "kind": "github_token", "createdAt": 2}
+            ]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn unmigrated_values_survive_a_rewrite() {
+        let mut index = legacy_index();
+        record(&mut index, "NEW_KEY", "env");
+
+        let written = serde_json::to_string(&index).unwrap();
+        let reread: SecretsIndex = serde_json::from_str(&written).unwrap();
+        assert_eq!(reread.secrets[0].value.as_deref(), Some("sk-old"));
+        assert!(!written.contains("\"value\":null"));
+        assert_eq!(reread.secrets.len(), 3);
+    }
+
+    #[test]
+    fn storing_a_secret_supersedes_its_plaintext_value() {
+        let mut index = legacy_index();
+        let info = record(&mut index, "OPENAI_KEY", "credential");
+
+        assert_eq!(info.kind, "credential");
+        assert_eq!(info.created_at, 1);
+        assert!(info.value.is_none());
+        assert!(!serde_json::to_string(&index).unwrap().contains("sk-old"));
+    }
+
+    #[test]
+    fn github_token_in_the_environment_wins_over_the_stored_one() {
+        assert!(has_github_token_env(&[("GH_TOKEN", "abc")]));
+        assert!(has_github_token_env(&[("GITHUB_TOKEN", "abc")]));
+        let inherited = std::env::var_os("GH_TOKEN").is_some() || std::env::var_os("GITHUB_TOKEN").is_some();
+        assert_eq!(has_github_token_env(&[("GH_HOST", "github.com")]), inherited);
+    }
+}
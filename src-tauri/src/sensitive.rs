@@ -0,0 +1,353 @@
+// mensa - Sensitive File Module
+// Detects secret-bearing files in a workspace and redacts their values from streamed output
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Patterns used when the user hasn't configured their own set
+pub const DEFAULT_SENSITIVE_PATTERNS: &[&str] = &[".env*", "*.pem", "credentials.json", "id_*"];
+
+/// Directories never descended into while scanning for sensitive files
+const SKIPPED_DIRS: &[&str] = &[".git", "node_modules", "target", ".venv", "venv"];
+
+/// Upper bound on directory entries visited per scan
+const MAX_SCAN_ENTRIES: usize = 50_000;
+
+/// Files larger than this are listed but not read for redaction values
+const MAX_REDACTION_SOURCE_BYTES: u64 = 256 * 1024;
+
+/// Values shorter than this are too ambiguous to mask by literal match
+const MIN_REDACTED_VALUE_LEN: usize = 4;
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SensitiveFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SensitivePatternsFile {
+    patterns: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn patterns_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("sensitive-patterns.json"))
+}
+
+/// Load the configured sensitive patterns, falling back to the defaults
+pub async fn load_patterns(app: &tauri::AppHandle) -> Result<Vec<String>, String> {
+    let path = patterns_path(app)?;
+    if !path.exists() {
+        return Ok(DEFAULT_SENSITIVE_PATTERNS.iter().map(|p| p.to_string()).collect());
+    }
+
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read sensitive patterns: {}", e))?;
+    let file: SensitivePatternsFile = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse sensitive patterns: {}", e))?;
+
+    Ok(file.patterns)
+}
+
+/// Compile patterns into a matcher. Each pattern is tested against both the
+/// file name and the workspace-relative path.
+pub fn build_matcher(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| format!("Invalid sensitive pattern '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to compile sensitive patterns: {}", e))
+}
+
+/// Check a workspace-relative path against the matcher
+pub fn is_sensitive(matcher: &GlobSet, relative_path: &Path) -> bool {
+    let by_name = relative_path
+        .file_name()
+        .map(|name| matcher.is_match(name))
+        .unwrap_or(false);
+    by_name || matcher.is_match(relative_path)
+}
+
+/// Walk the workspace (including ignored files, since that's where secrets live)
+/// and collect files matching the sensitive patterns
+pub fn find_sensitive_files(root: &Path, matcher: &GlobSet) -> Vec<SensitiveFile> {
+    let mut found = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    let mut visited = 0usize;
+
+    while let Some(dir) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            visited += 1;
+            if visited > MAX_SCAN_ENTRIES {
+                return found;
+            }
+
+            let file_type = match entry.file_type() {
+                Ok(t) => t,
+                Err(_) => continue,
+            };
+            let path = entry.path();
+
+            if file_type.is_dir() {
+                let skipped = entry
+                    .file_name()
+                    .to_str()
+                    .map(|name| SKIPPED_DIRS.contains(&name))
+                    .unwrap_or(false);
+                if !skipped {
+                    stack.push(path);
+                }
+                continue;
+            }
+
+            if !file_type.is_file() {
+                continue;
+            }
+
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            if is_sensitive(matcher, relative) {
+                found.push(SensitiveFile {
+                    path: relative.to_string_lossy().to_string(),
+                    size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                });
+            }
+        }
+    }
+
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+/// Masks `KEY=value` lines and literal secret values taken from sensitive files
+pub struct Redactor {
+    key_lines: Option<Regex>,
+    values: Vec<String>,
+    pub files: Vec<String>,
+}
+
+impl Redactor {
+    /// Build a redactor from the sensitive files in a workspace.
+    /// Returns None when none of the files contain `KEY=value` pairs.
+    pub fn from_files(root: &Path, files: &[SensitiveFile]) -> Option<Redactor> {
+        let mut keys: Vec<String> = Vec::new();
+        let mut values: Vec<String> = Vec::new();
+
+        for file in files {
+            if file.size > MAX_REDACTION_SOURCE_BYTES {
+                continue;
+            }
+            let content = match std::fs::read_to_string(root.join(&file.path)) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            for line in content.lines() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let line = line.strip_prefix("export ").unwrap_or(line);
+                if let Some((key, value)) = line.split_once('=') {
+                    let key = key.trim();
+                    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+                    if key.is_empty() || key.contains(char::is_whitespace) {
+                        continue;
+                    }
+                    if !keys.iter().any(|k| k == key) {
+                        keys.push(key.to_string());
+                    }
+                    if value.len() >= MIN_REDACTED_VALUE_LEN && !values.iter().any(|v| v == value) {
+                        values.push(value.to_string());
+                    }
+                }
+            }
+        }
+
+        if keys.is_empty() {
+            return None;
+        }
+
+        // Longest values first so a value containing another is masked whole
+        values.sort_by_key(|v| std::cmp::Reverse(v.len()));
+
+        let alternation = keys.iter().map(|k| regex::escape(k)).collect::<Vec<_>>().join("|");
+        let key_lines = Regex::new(&format!(
+            r#"(?m)^(\s*(?:export\s+)?(?:{})\s*=\s*).+$"#,
+            alternation
+        ))
+        .ok();
+
+        Some(Redactor {
+            key_lines,
+            values,
+            files: files.iter().map(|f| f.path.clone()).collect(),
+        })
+    }
+
+    /// Mask secret values in a block of text
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut result = match &self.key_lines {
+            Some(re) => re.replace_all(text, format!("${{1}}{}", REDACTED)).to_string(),
+            None => text.to_string(),
+        };
+        for value in &self.values {
+            if result.contains(value.as_str()) {
+                result = result.replace(value.as_str(), REDACTED);
+            }
+        }
+        result
+    }
+
+    /// Redact tool_result content inside one streamed JSON line.
+    /// Lines that aren't JSON are treated as plain text output.
+    pub fn redact_stream_line(&self, line: &str) -> String {
+        let mut parsed: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(_) => return self.redact_text(line),
+        };
+
+        if parsed.get("type").and_then(|v| v.as_str()) != Some("user") {
+            return line.to_string();
+        }
+
+        let mut changed = false;
+
+        if let Some(Value::Array(blocks)) = parsed.pointer_mut("/message/content") {
+            for block in blocks.iter_mut() {
+                if block.get("type").and_then(|v| v.as_str()) == Some("tool_result") {
+                    if let Some(content) = block.get_mut("content") {
+                        changed |= self.redact_value(content);
+                    }
+                }
+            }
+        }
+
+        // The SDK mirrors tool output on user messages as tool_use_result
+        if let Some(result) = parsed.get_mut("tool_use_result") {
+            changed |= self.redact_value(result);
+        }
+
+        if changed {
+            serde_json::to_string(&parsed).unwrap_or_else(|_| line.to_string())
+        } else {
+            line.to_string()
+        }
+    }
+
+    /// Recursively redact every string in a JSON value, reporting whether anything changed
+    fn redact_value(&self, value: &mut Value) -> bool {
+        match value {
+            Value::String(s) => {
+                let redacted = self.redact_text(s);
+                if redacted != *s {
+                    *s = redacted;
+                    true
+                } else {
+                    false
+                }
+            }
+            Value::Array(items) => {
+                let mut changed = false;
+                for item in items.iter_mut() {
+                    changed |= self.redact_value(item);
+                }
+                changed
+            }
+            Value::Object(map) => {
+                let mut changed = false;
+                for item in map.values_mut() {
+                    changed |= self.redact_value(item);
+                }
+                changed
+            }
+            _ => false,
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List files in the workspace matching the sensitive patterns
+#[tauri::command]
+pub async fn detect_sensitive_files(
+    app: tauri::AppHandle,
+    working_dir: String,
+) -> Result<Vec<SensitiveFile>, String> {
+    let root = Path::new(&working_dir);
+    if !root.is_dir() {
+        return Err(format!("Path is not a directory: {}", working_dir));
+    }
+
+    let patterns = load_patterns(&app).await?;
+    let matcher = build_matcher(&patterns)?;
+    Ok(find_sensitive_files(root, &matcher))
+}
+
+/// Get the configured sensitive file patterns
+#[tauri::command]
+pub async fn get_sensitive_patterns(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+    load_patterns(&app).await
+}
+
+/// Replace the sensitive file patterns (validated before saving)
+#[tauri::command]
+pub async fn set_sensitive_patterns(
+    app: tauri::AppHandle,
+    patterns: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let patterns: Vec<String> = patterns
+        .into_iter()
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    build_matcher(&patterns)?;
+
+    let path = patterns_path(&app)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+
+    let content = serde_json::to_string_pretty(&SensitivePatternsFile {
+        patterns: patterns.clone(),
+    })
+    .map_err(|e| format!("Failed to serialize sensitive patterns: {}", e))?;
+
+    tokio::fs::write(&path, content)
+        .await
+        .map_err(|e| format!("Failed to write sensitive patterns: {}", e))?;
+
+    Ok(patterns)
+}
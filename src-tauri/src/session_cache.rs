@@ -0,0 +1,271 @@
+// mensa - Session Cache Module
+// Parsed transcripts kept in memory, so switching back to a session doesn't parse it again
+
+use crate::transcript::SessionMessage;
+use crate::{settings, AppState};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// A transcript's grouped messages and what its health check found
+#[derive(Debug, Default)]
+pub struct ParsedSession {
+    pub(crate) messages: Vec<SessionMessage>,
+    pub(crate) write_in_progress: bool,
+    pub(crate) malformed_lines: usize,
+}
+
+struct CacheEntry {
+    modified: Option<SystemTime>,
+    len: u64,
+    session: Arc<ParsedSession>,
+    /// Tick of the last hit or insert; the smallest is evicted first
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    /// By transcript path and whether source spans were parsed
+    entries: HashMap<(PathBuf, bool), CacheEntry>,
+    /// Sum of the entries' transcript sizes, the estimate of what they hold in memory
+    bytes: u64,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+/// Parsed sessions, least recently used evicted first once their transcripts add up to more
+/// than the `sessionCacheMb` setting
+#[derive(Default)]
+pub struct SessionCache {
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub capacity_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    /// Hits over all lookups, 0 before the first
+    pub hit_rate: f64,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl CacheInner {
+    fn remove(&mut self, key: &(PathBuf, bool)) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.len;
+        }
+    }
+
+    /// Drop least recently used entries until they fit in `capacity` bytes
+    fn shrink_to(&mut self, capacity: u64) {
+        while self.bytes > capacity {
+            let Some(oldest) = self.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+impl SessionCache {
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheInner> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The parse of `path` if it was cached at this modification time and size
+    pub fn get(&self, path: &Path, source_spans: bool, modified: Option<SystemTime>, len: u64) -> Option<Arc<ParsedSession>> {
+        let mut inner = self.lock();
+        let key = (path.to_path_buf(), source_spans);
+        let fresh = inner.entries.get(&key).map(|e| e.modified == modified && e.len == len);
+        match fresh {
+            Some(true) => {
+                inner.tick += 1;
+                inner.hits += 1;
+                let tick = inner.tick;
+                let entry = inner.entries.get_mut(&key)?;
+                entry.last_used = tick;
+                Some(entry.session.clone())
+            }
+            stale => {
+                // The transcript changed since; its old parse is no use to anyone
+                if stale.is_some() {
+                    inner.remove(&key);
+                }
+                inner.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Keep a parse of `path`, stamped with the metadata read before parsing it.
+    /// A transcript larger than `capacity` alone isn't kept.
+    pub fn insert(&self, path: &Path, source_spans: bool, modified: Option<SystemTime>, len: u64, session: Arc<ParsedSession>, capacity: u64) {
+        let mut inner = self.lock();
+        let key = (path.to_path_buf(), source_spans);
+        inner.remove(&key);
+        if len > capacity {
+            inner.shrink_to(capacity);
+            return;
+        }
+        inner.tick += 1;
+        let last_used = inner.tick;
+        inner.bytes += len;
+        inner.entries.insert(key, CacheEntry { modified, len, session, last_used });
+        inner.shrink_to(capacity);
+    }
+
+    /// Forget every parse of `path` (the transcript was deleted or rewritten)
+    pub fn evict(&self, path: &Path) {
+        let mut inner = self.lock();
+        for source_spans in [false, true] {
+            inner.remove(&(path.to_path_buf(), source_spans));
+        }
+    }
+
+    fn stats(&self, capacity_bytes: u64) -> SessionCacheStats {
+        let inner = self.lock();
+        let lookups = inner.hits + inner.misses;
+        SessionCacheStats {
+            entries: inner.entries.len(),
+            bytes: inner.bytes,
+            capacity_bytes,
+            hits: inner.hits,
+            misses: inner.misses,
+            hit_rate: if lookups == 0 { 0.0 } else { inner.hits as f64 / lookups as f64 },
+        }
+    }
+}
+
+/// The cache's memory bound from settings, in bytes
+pub async fn capacity(app: &tauri::AppHandle, state: &AppState) -> u64 {
+    let mb = settings::load(app, &state.settings)
+        .await
+        .map(|s| s.session_cache_mb)
+        .unwrap_or(settings::DEFAULT_SESSION_CACHE_MB);
+    mb * 1024 * 1024
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Entries, estimated bytes and hit rate of the parsed session cache, for the debug panel
+#[tauri::command]
+pub async fn get_session_cache_stats(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<SessionCacheStats, String> {
+    let capacity_bytes = capacity(&app, &state).await;
+    Ok(state.session_cache.stats(capacity_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cancel::CancellationToken;
+    use crate::test_support::{fixture, scratch_home, write};
+
+    const WORKSPACE: &str = "/work/app";
+    const CAPACITY: u64 = 1024 * 1024;
+
+    async fn load(cache: &SessionCache, capacity: u64, session_id: &str, source_spans: bool) -> Arc<ParsedSession> {
+        crate::parsed_session_in(cache, capacity, WORKSPACE, session_id, CancellationToken::default(), source_spans)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn second_load_is_a_hit() {
+        let home = scratch_home();
+        let project = home.claude().project_dir(WORKSPACE);
+        write(&project, "s1.jsonl", fixture("sessions/basic.jsonl"));
+        let cache = SessionCache::default();
+
+        let first = load(&cache, CAPACITY, "s1", false).await;
+        assert!(!first.messages.is_empty());
+        let second = load(&cache, CAPACITY, "s1", false).await;
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Source spans are parsed separately
+        let spans = load(&cache, CAPACITY, "s1", true).await;
+        assert!(!Arc::ptr_eq(&first, &spans));
+        let stats = cache.stats(CAPACITY);
+        let len = std::fs::metadata(project.join("s1.jsonl")).unwrap().len();
+        assert_eq!((stats.entries, stats.bytes, stats.hits, stats.misses), (2, 2 * len, 1, 2));
+        assert!((stats.hit_rate - 1.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn appending_to_the_transcript_invalidates_its_parse() {
+        let home = scratch_home();
+        let project = home.claude().project_dir(WORKSPACE);
+        write(&project, "s1.jsonl", fixture("sessions/basic.jsonl"));
+        let cache = SessionCache::default();
+        let before = load(&cache, CAPACITY, "s1", false).await;
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(project.join("s1.jsonl")).unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            br#"{"type":"user","timestamp":"2026-01-05T10:05:00.000Z","message":{"role":"user","content":"One more thing"}}
+"#,
+        )
+        .unwrap();
+        drop(file);
+
+        let after = load(&cache, CAPACITY, "s1", false).await;
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert_eq!(after.messages.len(), before.messages.len() + 1);
+        // The stale parse was replaced, not kept alongside
+        let stats = cache.stats(CAPACITY);
+        let len = std::fs::metadata(project.join("s1.jsonl")).unwrap().len();
+        assert_eq!((stats.entries, stats.bytes, stats.hits, stats.misses), (1, len, 0, 2));
+        assert!(Arc::ptr_eq(&after, &load(&cache, CAPACITY, "s1", false).await));
+
+        // A rewrite the app reports is forgotten without waiting for the next load
+        cache.evict(&project.join("s1.jsonl"));
+        assert_eq!(cache.stats(CAPACITY).entries, 0);
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted_under_the_cap() {
+        let cache = SessionCache::default();
+        let insert = |name: &str, len: u64| {
+            cache.insert(Path::new(name), false, None, len, Arc::default(), 300);
+        };
+        let hit = |name: &str, len: u64| cache.get(Path::new(name), false, None, len).is_some();
+
+        insert("a", 100);
+        insert("b", 100);
+        insert("c", 100);
+        assert!(hit("a", 100));
+        // "b" is now the least recently used
+        insert("d", 100);
+        assert!(!hit("b", 100));
+        assert!(hit("a", 100) && hit("c", 100) && hit("d", 100));
+        assert_eq!(cache.stats(300).bytes, 300);
+
+        // One bigger entry pushes out as many as it needs
+        insert("e", 250);
+        assert!(!hit("a", 100) && !hit("c", 100) && !hit("d", 100));
+        assert!(hit("e", 250));
+
+        // A transcript bigger than the whole cache isn't kept, and doesn't flush the others
+        insert("huge", 301);
+        assert!(!hit("huge", 301));
+        assert!(hit("e", 250));
+        assert_eq!(cache.stats(300).bytes, 250);
+    }
+}
@@ -0,0 +1,480 @@
+// mensa - Session Guard Module
+// Notices another Claude Code writing to the same project before a session is resumed, and
+// repairs transcripts whose lines two writers interleaved
+
+use crate::cancel::CancellationToken;
+use crate::{canonical_or_raw, find_session_path, fsutil, history, permissions, project_dir_for_workspace, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// What a SessionInUse error tells the user; nothing can keep the CLI itself out
+pub const SESSION_IN_USE_HINT: &str = "mensa can't lock other Claude Code processes out of a project. \
+Close the other one (or let it finish) before resuming here; resuming anyway can interleave both \
+writers' lines, which repair_session_file can then quarantine. Processes of other users, on other \
+machines or (on Windows) any CLI process aren't detected.";
+
+/// How often a running query refreshes its heartbeat
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Age after which a heartbeat is left over from a run that didn't clean up
+const HEARTBEAT_STALE_SECS: i64 = 30;
+
+/// Most `{"` positions a malformed line is re-parsed from when salvaging entries
+const MAX_SALVAGE_STARTS: usize = 256;
+
+/// Sidecar (next to the transcript) that repaired-away lines are appended to
+const QUARANTINE_SUFFIX: &str = ".jsonl.quarantine";
+
+/// Who else is writing to a session's project
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SessionHolder {
+    /// A query of this or another mensa process (e.g. a headless run) on the same session
+    Mensa { pid: u32, query_id: String },
+    /// A Claude Code CLI process whose working directory belongs to the same project
+    ClaudeCli { pid: u32, cwd: String, command: String },
+}
+
+/// Heartbeat file of a mensa query working on a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Heartbeat {
+    pid: u32,
+    query_id: String,
+    session_id: String,
+    working_dir: String,
+    updated_at: i64,
+}
+
+/// Keeps a session's heartbeat fresh while held; dropping it removes the heartbeat
+pub struct SessionHold {
+    session_id: String,
+    path: PathBuf,
+    token: CancellationToken,
+    /// Set on release, so a refresh racing the drop doesn't write the file back
+    released: Arc<Mutex<bool>>,
+}
+
+struct ProcessInfo {
+    pid: u32,
+    ppid: u32,
+    args: Vec<String>,
+}
+
+/// Lines of a transcript that don't parse
+#[derive(Debug, Clone, Default)]
+pub struct TranscriptHealth {
+    /// 1-based numbers of complete lines that aren't valid JSON
+    pub malformed_lines: Vec<usize>,
+    /// The last line has no newline yet and doesn't parse: a writer is still appending it
+    pub write_in_progress: bool,
+}
+
+/// Result of `repair_session_file`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RepairReport {
+    pub session_id: String,
+    pub path: String,
+    /// 1-based lines that held whole entries glued to other writes; the entries were kept
+    pub salvaged_lines: Vec<usize>,
+    /// 1-based lines nothing could be recovered from, dropped from the transcript
+    pub quarantined_lines: Vec<usize>,
+    /// Where every changed line was kept as it was; None when nothing changed
+    pub quarantine_file: Option<String>,
+    /// The unfinished last line was left alone
+    pub write_in_progress: bool,
+    /// Whether the transcript was rewritten
+    pub changed: bool,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn heartbeat_path(app: &tauri::AppHandle, session_id: &str) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("session-locks").join(format!("{}.json", session_id)))
+}
+
+impl SessionHold {
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+}
+
+impl Drop for SessionHold {
+    fn drop(&mut self) {
+        self.token.cancel();
+        if let Ok(mut released) = self.released.lock() {
+            *released = true;
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Announce that `query_id` works on `session_id` until the returned hold is dropped
+pub fn hold(app: &tauri::AppHandle, session_id: &str, query_id: &str, working_dir: &str) -> Option<SessionHold> {
+    let path = heartbeat_path(app, session_id).ok()?;
+    let heartbeat = Heartbeat {
+        pid: std::process::id(),
+        query_id: query_id.to_string(),
+        session_id: session_id.to_string(),
+        working_dir: working_dir.to_string(),
+        updated_at: 0,
+    };
+    let released = Arc::new(Mutex::new(false));
+    let task_path = path.clone();
+    let task_released = released.clone();
+    let spawned = app.state::<AppState>().tasks.spawn(format!("heartbeat of session {}", session_id), |token| async move {
+        let mut heartbeat = heartbeat;
+        loop {
+            if let Ok(released) = task_released.lock() {
+                if *released {
+                    break;
+                }
+                heartbeat.updated_at = history::now_secs();
+                if let Ok(content) = serde_json::to_vec(&heartbeat) {
+                    if let Err(e) = fsutil::write_atomic(&task_path, &content) {
+                        eprintln!("[mensa] {}", e);
+                    }
+                }
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(HEARTBEAT_INTERVAL) => {}
+                _ = token.cancelled() => break,
+            }
+        }
+    });
+    Some(SessionHold {
+        session_id: session_id.to_string(),
+        path,
+        token: spawned.token,
+        released,
+    })
+}
+
+#[cfg(unix)]
+fn pid_alive(pid: u32) -> bool {
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
+    // EPERM means it exists but belongs to someone else
+    !matches!(kill(Pid::from_raw(pid as i32), None), Err(nix::errno::Errno::ESRCH))
+}
+
+#[cfg(windows)]
+fn pid_alive(_pid: u32) -> bool {
+    true
+}
+
+/// The live mensa query holding `session_id`, removing a heartbeat its run left behind
+fn heartbeat_holder(app: &tauri::AppHandle, session_id: &str) -> Option<SessionHolder> {
+    let path = heartbeat_path(app, session_id).ok()?;
+    let heartbeat: Heartbeat = serde_json::from_slice(&std::fs::read(&path).ok()?).ok()?;
+    if history::now_secs() - heartbeat.updated_at > HEARTBEAT_STALE_SECS || !pid_alive(heartbeat.pid) {
+        let _ = std::fs::remove_file(&path);
+        return None;
+    }
+    Some(SessionHolder::Mensa {
+        pid: heartbeat.pid,
+        query_id: heartbeat.query_id,
+    })
+}
+
+/// Whether a command line runs the Claude Code CLI (the native binary or the npm package)
+fn is_claude_cli(args: &[String]) -> bool {
+    let Some(program) = args.first() else {
+        return false;
+    };
+    let name = Path::new(program).file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    matches!(name, "claude" | "claude.exe")
+        || args.iter().take(3).any(|arg| arg.replace('\\', "/").contains("@anthropic-ai/claude-code/cli"))
+}
+
+#[cfg(target_os = "linux")]
+fn processes() -> Vec<ProcessInfo> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir("/proc").into_iter().flatten().flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        // "pid (comm) state ppid ...", where comm may itself contain spaces and parentheses
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        let ppid = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+            .and_then(|ppid| ppid.parse().ok())
+            .unwrap_or(0);
+        let args = std::fs::read(entry.path().join("cmdline"))
+            .unwrap_or_default()
+            .split(|b| *b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+        found.push(ProcessInfo { pid, ppid, args });
+    }
+    found
+}
+
+#[cfg(target_os = "macos")]
+fn processes() -> Vec<ProcessInfo> {
+    let Ok(output) = std::process::Command::new("ps").args(["-axo", "pid=,ppid=,args="]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            Some(ProcessInfo { pid, ppid, args: fields.map(String::from).collect() })
+        })
+        .collect()
+}
+
+/// Other processes' working directories can't be read here; nothing is detected
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn processes() -> Vec<ProcessInfo> {
+    Vec::new()
+}
+
+#[cfg(target_os = "linux")]
+fn working_dirs(pids: &[u32]) -> HashMap<u32, PathBuf> {
+    pids.iter()
+        .filter_map(|pid| Some((*pid, std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()?)))
+        .collect()
+}
+
+/// `lsof -Fn` prints "p<pid>", then "fcwd" and "n<path>" for each process
+#[cfg(target_os = "macos")]
+fn working_dirs(pids: &[u32]) -> HashMap<u32, PathBuf> {
+    let list = pids.iter().map(|pid| pid.to_string()).collect::<Vec<_>>().join(",");
+    let Ok(output) = std::process::Command::new("lsof").args(["-a", "-d", "cwd", "-Fn", "-p", &list]).output() else {
+        return HashMap::new();
+    };
+    let mut dirs = HashMap::new();
+    let mut current = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(pid) = line.strip_prefix('p') {
+            current = pid.parse::<u32>().ok();
+        } else if let (Some(path), Some(pid)) = (line.strip_prefix('n'), current) {
+            dirs.insert(pid, PathBuf::from(path));
+        }
+    }
+    dirs
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn working_dirs(_pids: &[u32]) -> HashMap<u32, PathBuf> {
+    HashMap::new()
+}
+
+/// Whether `pid` is `ancestor` or runs beneath it
+fn descends_from(mut pid: u32, ancestor: u32, parents: &HashMap<u32, u32>) -> bool {
+    for _ in 0..64 {
+        if pid == ancestor {
+            return true;
+        }
+        match parents.get(&pid) {
+            Some(&parent) if parent != 0 && parent != pid => pid = parent,
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// A Claude Code CLI outside mensa's own process tree whose cwd maps to `project_dir`
+fn cli_holder(project_dir: &Path) -> Option<SessionHolder> {
+    let processes = processes();
+    let parents: HashMap<u32, u32> = processes.iter().map(|p| (p.pid, p.ppid)).collect();
+    let own = std::process::id();
+    let candidates: Vec<&ProcessInfo> = processes
+        .iter()
+        .filter(|p| is_claude_cli(&p.args) && !descends_from(p.pid, own, &parents))
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    let dirs = working_dirs(&candidates.iter().map(|p| p.pid).collect::<Vec<_>>());
+    candidates.into_iter().find_map(|process| {
+        let cwd = dirs.get(&process.pid)?.to_string_lossy().to_string();
+        let same_project = project_dir_for_workspace(&cwd).is_ok_and(|dir| dir == project_dir);
+        same_project.then(|| SessionHolder::ClaudeCli {
+            pid: process.pid,
+            cwd,
+            command: process.args.join(" "),
+        })
+    })
+}
+
+/// Who else is working on `session_id` in `working_dir`'s project, if anyone
+pub async fn holder(app: &tauri::AppHandle, working_dir: &str, session_id: &str) -> Result<Option<SessionHolder>, String> {
+    if let Some(holder) = heartbeat_holder(app, session_id) {
+        return Ok(Some(holder));
+    }
+    let canonical = canonical_or_raw(working_dir).to_string_lossy().to_string();
+    let project_dir = project_dir_for_workspace(&canonical)?;
+    tokio::task::spawn_blocking(move || cli_holder(&project_dir))
+        .await
+        .map_err(|e| format!("Failed to look for other Claude Code processes: {}", e))
+}
+
+/// Check every line of a transcript without building values
+pub fn health(content: &str) -> TranscriptHealth {
+    let mut health = TranscriptHealth::default();
+    for (index, raw_line) in content.split_inclusive('\n').enumerate() {
+        let line = raw_line.trim_end_matches(['\n', '\r']);
+        if line.trim().is_empty() || serde_json::from_str::<serde::de::IgnoredAny>(line).is_ok() {
+            continue;
+        }
+        if raw_line.ends_with('\n') {
+            health.malformed_lines.push(index + 1);
+        } else {
+            health.write_in_progress = true;
+        }
+    }
+    health
+}
+
+/// Top-level transcript entries carry a type and a uuid (summaries a leafUuid)
+fn is_entry(value: &Value) -> bool {
+    value.get("type").is_some_and(Value::is_string)
+        && ["uuid", "leafUuid", "sessionId"].iter().any(|key| value.get(key).is_some())
+}
+
+/// The whole entries inside a malformed line: writes glued together without a newline, or a
+/// write cut short with another one after it. Parsing restarts at each `{"` from the left.
+fn salvage(line: &str) -> Vec<&str> {
+    for (start, _) in line.match_indices("{\"").take(MAX_SALVAGE_STARTS) {
+        let rest = &line[start..];
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+        let mut entries = Vec::new();
+        loop {
+            let begin = stream.byte_offset();
+            match stream.next() {
+                Some(Ok(value)) if is_entry(&value) => entries.push(rest[begin..stream.byte_offset()].trim()),
+                _ => break,
+            }
+        }
+        if !entries.is_empty() {
+            return entries;
+        }
+    }
+    Vec::new()
+}
+
+fn repair(path: &Path, session_id: &str) -> Result<RepairReport, String> {
+    let bytes = std::fs::read(path).map_err(|e| permissions::io_error("read session", path, &e))?;
+    let original_hash = fsutil::sha256_hex(&bytes);
+    let mut report = RepairReport {
+        session_id: session_id.to_string(),
+        path: path.to_string_lossy().to_string(),
+        salvaged_lines: Vec::new(),
+        quarantined_lines: Vec::new(),
+        quarantine_file: None,
+        write_in_progress: false,
+        changed: false,
+    };
+
+    let mut repaired: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut quarantined = Vec::new();
+    for (index, raw_line) in bytes.split_inclusive(|b| *b == b'\n').enumerate() {
+        if !raw_line.ends_with(b"\n") {
+            // Still being appended: keep it for its writer to finish
+            report.write_in_progress = serde_json::from_slice::<serde::de::IgnoredAny>(raw_line).is_err();
+            repaired.extend_from_slice(raw_line);
+            continue;
+        }
+        let text = String::from_utf8_lossy(raw_line);
+        let line = text.trim_end_matches(['\n', '\r']);
+        if line.trim().is_empty() || serde_json::from_slice::<serde::de::IgnoredAny>(raw_line).is_ok() {
+            repaired.extend_from_slice(raw_line);
+            continue;
+        }
+        let entries = salvage(line);
+        if entries.is_empty() {
+            report.quarantined_lines.push(index + 1);
+        } else {
+            report.salvaged_lines.push(index + 1);
+            for entry in entries {
+                repaired.extend_from_slice(entry.as_bytes());
+                repaired.push(b'\n');
+            }
+        }
+        quarantined.push(serde_json::json!({
+            "line": index + 1,
+            "quarantinedAt": history::now_secs(),
+            "content": line,
+        }));
+    }
+    if quarantined.is_empty() {
+        return Ok(report);
+    }
+
+    // The removed text is kept before the transcript changes, in case the rewrite is lost
+    let sidecar = path.with_file_name(format!("{}{}", session_id, QUARANTINE_SUFFIX));
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&sidecar)
+        .map_err(|e| permissions::io_error("open quarantine file", &sidecar, &e))?;
+    for record in &quarantined {
+        writeln!(file, "{}", record).map_err(|e| format!("Failed to write quarantine file: {}", e))?;
+    }
+    report.quarantine_file = Some(sidecar.to_string_lossy().to_string());
+
+    // Refuses if anything was appended since the read
+    fsutil::write_checked(path, &repaired, Some(&original_hash))?;
+    report.changed = true;
+    Ok(report)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Rewrite a transcript without the lines interleaved writes broke: entries glued together are
+/// split back apart, anything else is moved to `<session>.jsonl.quarantine`. An unfinished last
+/// line is left alone. Refuses while another Claude Code uses the project unless `force`.
+#[tauri::command]
+pub async fn repair_session_file(
+    app: tauri::AppHandle,
+    workspace_path: String,
+    session_id: String,
+    force: Option<bool>,
+) -> Result<RepairReport, permissions::SessionError> {
+    let path = find_session_path(&workspace_path, &session_id)?
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    if force != Some(true) {
+        if let Some(by) = holder(&app, &workspace_path, &session_id).await? {
+            return Err(permissions::SessionError::SessionInUse {
+                session_id,
+                by,
+                hint: SESSION_IN_USE_HINT.to_string(),
+            });
+        }
+    }
+    if let Some(dir) = path.parent() {
+        permissions::require(dir, true)?;
+    }
+    app.state::<AppState>().session_cache.evict(&path);
+    let repaired = tokio::task::spawn_blocking(move || repair(&path, &session_id))
+        .await
+        .map_err(|e| format!("Failed to repair session: {}", e))??;
+    Ok(repaired)
+}
@@ -0,0 +1,520 @@
+// mensa - Session Index Module
+// Cross-checks Claude Code's sessions-index.json against the transcripts it describes
+
+use crate::{digest, fsutil, permissions, SessionEntry};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+pub const INDEX_FILE: &str = "sessions-index.json";
+
+/// A transcript modified this much later than its index entry says makes the entry stale
+const MTIME_TOLERANCE_SECS: i64 = 2;
+
+/// Longest first prompt derived from a transcript
+const MAX_FIRST_PROMPT_CHARS: usize = 200;
+
+/// Letters or digits a user turn needs before it counts as the first prompt
+const MIN_PROMPT_ALNUM: usize = 2;
+
+/// What Claude Code writes as firstPrompt when it found none
+const NO_PROMPT: &str = "No prompt";
+
+/// Context Claude Code injects into user turns, dropped from a first prompt
+const INJECTED_TAGS: &[&str] = &["system-reminder", "local-command-stdout", "local-command-stderr"];
+
+/// Serializes mensa's own rewrites of the index
+static INDEX_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Message counts of transcripts, keyed by path and valid while (mtime, size) match
+type CountCache = HashMap<PathBuf, (SystemTime, u64, u32)>;
+
+static COUNT_CACHE: Mutex<Option<CountCache>> = Mutex::new(None);
+
+/// Just the top-level type of a transcript line
+#[derive(Deserialize)]
+struct LineKind<'a> {
+    #[serde(rename = "type", borrow)]
+    kind: Option<&'a str>,
+}
+
+/// Count and time read from a transcript the index has fallen behind on
+struct Counts {
+    message_count: u32,
+    modified: String,
+    mtime_millis: i64,
+}
+
+/// Values read from a transcript that replace its index entry's
+struct Corrected {
+    session_id: String,
+    counts: Option<Counts>,
+    first_prompt: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// User and assistant messages in a transcript (what the index's messageCount counts)
+fn count_messages(content: &str) -> u32 {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<LineKind>(line).ok())
+        .filter(|line| matches!(line.kind, Some("user") | Some("assistant")))
+        .count() as u32
+}
+
+/// Collapse whitespace and cut at a word boundary so the prompt fits, marking the cut
+fn tidy_prompt(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= MAX_FIRST_PROMPT_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(MAX_FIRST_PROMPT_CHARS - 1).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) if space > MAX_FIRST_PROMPT_CHARS / 2 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/// Text between `<tag>` and `</tag>`, when the tag is present
+fn tag_text<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = text.find(&open)? + open.len();
+    let end = text[start..].find(&format!("</{}>", tag)).map_or(text.len(), |i| start + i);
+    Some(text[start..end].trim())
+}
+
+/// Remove the `<tag>...</tag>` blocks of `INJECTED_TAGS` (an unclosed one runs to the end)
+pub fn strip_injected(text: &str) -> String {
+    let mut text = text.to_string();
+    for tag in INJECTED_TAGS {
+        let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+        while let Some(start) = text.find(&open) {
+            let end = text[start..].find(&close).map_or(text.len(), |i| start + i + close.len());
+            text.replace_range(start..end, "");
+        }
+    }
+    text
+}
+
+/// The prompt a user turn stands for: "/name args" for a slash command, else its text when it
+/// has enough letters to say something. None for tool results, images and meta entries.
+fn turn_prompt(entry: &Value) -> Option<String> {
+    if entry.get("type").and_then(|v| v.as_str()) != Some("user")
+        || entry.get("isMeta").and_then(|v| v.as_bool()).unwrap_or(false)
+    {
+        return None;
+    }
+    let texts: Vec<&str> = match entry.get("message")?.get("content")? {
+        Value::String(text) => vec![text.as_str()],
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|v| v.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect(),
+        _ => return None,
+    };
+    texts.into_iter().find_map(|text| {
+        if let Some(name) = tag_text(text, "command-name").filter(|name| !name.is_empty()) {
+            let name = format!("/{}", name.trim_start_matches('/'));
+            let args = tag_text(text, "command-args").unwrap_or_default();
+            return Some(tidy_prompt(&format!("{} {}", name, args)));
+        }
+        let text = strip_injected(text);
+        let text = text.trim();
+        if text.starts_with("Caveat:") || text.starts_with("[Request interrupted") {
+            return None;
+        }
+        let alnum = text.chars().filter(|c| c.is_alphanumeric()).count();
+        (alnum >= MIN_PROMPT_ALNUM).then(|| tidy_prompt(text))
+    })
+}
+
+/// The first prompt of a transcript, as the sessions index should show it: the first user
+/// turn with text of its own (tool_result-only and image-only turns are skipped), a slash
+/// command as "/name args", whitespace collapsed and the length capped. None when no turn has any.
+pub fn first_prompt(content: &str) -> Option<String> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find_map(|entry| turn_prompt(&entry))
+}
+
+/// An index firstPrompt that shows no prompt: empty, Claude Code's placeholder, serialized
+/// content blocks, or a raw command wrapper
+fn is_unusable_prompt(prompt: &str) -> bool {
+    let prompt = prompt.trim();
+    prompt.is_empty()
+        || prompt == NO_PROMPT
+        || (prompt.starts_with('[') && prompt.contains("\"type\""))
+        || prompt.contains("tool_use_id")
+        || prompt.contains("<command-name>")
+        || prompt.starts_with("<command-message>")
+}
+
+/// Message count of a transcript, re-read only when its mtime or size changed
+fn cached_message_count(path: &Path, mtime: SystemTime, size: u64) -> Option<u32> {
+    let cached = COUNT_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .as_ref()
+        .and_then(|cache| cache.get(path).copied());
+    if let Some((cached_mtime, cached_size, count)) = cached {
+        if cached_mtime == mtime && cached_size == size {
+            return Some(count);
+        }
+    }
+    let count = count_messages(&std::fs::read_to_string(path).ok()?);
+    COUNT_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(path.to_path_buf(), (mtime, size, count));
+    Some(count)
+}
+
+/// Check one entry against its transcript. Sets `missing_file`; when the transcript changed
+/// after the entry was written, takes count and modified time from the file; and replaces a
+/// firstPrompt that shows nothing with one derived from the transcript.
+fn reconcile_entry(project_dir: &Path, entry: &mut SessionEntry) -> Option<Corrected> {
+    let path = project_dir.join(format!("{}.jsonl", entry.session_id));
+    let Ok(metadata) = std::fs::metadata(&path) else {
+        entry.missing_file = true;
+        return None;
+    };
+    let first_prompt = match is_unusable_prompt(&entry.first_prompt) {
+        true => std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| first_prompt(&content))
+            .filter(|prompt| *prompt != entry.first_prompt),
+        false => None,
+    };
+    if let Some(prompt) = &first_prompt {
+        entry.first_prompt = prompt.clone();
+    }
+    let counts = stale_counts(&path, &metadata, entry);
+    (counts.is_some() || first_prompt.is_some()).then(|| Corrected {
+        session_id: entry.session_id.clone(),
+        counts,
+        first_prompt,
+    })
+}
+
+/// Count and modified time from the transcript, when it changed after the entry was written
+fn stale_counts(path: &Path, metadata: &std::fs::Metadata, entry: &mut SessionEntry) -> Option<Counts> {
+    let mtime = metadata.modified().ok()?;
+    let mtime_millis = mtime.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64;
+    let indexed = digest::parse_utc_timestamp(&entry.modified);
+    if indexed.is_some_and(|secs| mtime_millis / 1000 <= secs + MTIME_TOLERANCE_SECS) {
+        return None;
+    }
+
+    let message_count = cached_message_count(path, mtime, metadata.len())?;
+    entry.index_stale = true;
+    entry.message_count = message_count;
+    entry.modified = digest::format_utc_timestamp(mtime_millis);
+    Some(Counts {
+        message_count,
+        modified: entry.modified.clone(),
+        mtime_millis,
+    })
+}
+
+/// Flag index entries whose transcript is missing and correct those the transcript has
+/// outgrown or whose first prompt is unusable. Returns the corrections so they can be written back.
+fn reconcile(project_dir: &Path, entries: &mut [SessionEntry]) -> Vec<Corrected> {
+    entries
+        .iter_mut()
+        .filter_map(|entry| reconcile_entry(project_dir, entry))
+        .collect()
+}
+
+/// An entry for a transcript the index doesn't have, built from the file alone
+fn entry_from_transcript(path: &Path) -> Option<SessionEntry> {
+    let session_id = path.file_stem()?.to_str()?.to_string();
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime_millis = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_millis() as i64;
+    let content = std::fs::read_to_string(path).ok()?;
+    let created = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find_map(|entry| entry.get("timestamp").and_then(|v| v.as_str()).map(str::to_string));
+    let modified = digest::format_utc_timestamp(mtime_millis);
+    Some(SessionEntry {
+        session_id,
+        first_prompt: first_prompt(&content).unwrap_or_else(|| NO_PROMPT.to_string()),
+        message_count: count_messages(&content),
+        created: created.unwrap_or_else(|| modified.clone()),
+        modified,
+        custom_title: None,
+        original_cwd: None,
+        index_stale: false,
+        missing_file: false,
+    })
+}
+
+/// Entries built from a project's transcripts, for when Claude Code hasn't written an index
+/// (subagent transcripts, `agent-*.jsonl`, are left out)
+pub async fn entries_from_transcripts(project_dir: &Path) -> Result<Vec<SessionEntry>, String> {
+    let dir = project_dir.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let read_dir = match std::fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read sessions: {}", e)),
+        };
+        Ok(read_dir
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .filter(|path| !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("agent-")))
+            .filter_map(|path| entry_from_transcript(&path))
+            .collect())
+    })
+    .await
+    .map_err(|e| format!("Failed to read sessions: {}", e))?
+}
+
+/// Rewrite the index's entries in place (unknown fields kept) and save it atomically.
+/// Does nothing when the index doesn't exist.
+pub async fn update_index<F>(project_dir: &Path, edit: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Vec<Value>) + Send + 'static,
+{
+    let _guard = INDEX_LOCK.lock().await;
+    let path = project_dir.join(INDEX_FILE);
+    tokio::task::spawn_blocking(move || {
+        if !path.exists() {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| permissions::io_error("read sessions index", &path, &e))?;
+        let mut index: Value =
+            serde_json::from_str(&content).map_err(|e| format!("Failed to parse sessions index: {}", e))?;
+        let Some(entries) = index.get_mut("entries").and_then(|e| e.as_array_mut()) else {
+            return Err("Failed to parse sessions index: no entries".to_string());
+        };
+        edit(entries);
+        let updated = serde_json::to_vec_pretty(&index)
+            .map_err(|e| format!("Failed to serialize sessions index: {}", e))?;
+        fsutil::write_atomic(&path, &updated)
+    })
+    .await
+    .map_err(|e| format!("Failed to update sessions index: {}", e))?
+}
+
+/// Add entries to an index, creating it from `skeleton` (another project's index, whose
+/// other top-level fields are kept) when the project has none yet
+pub async fn append_entries(project_dir: &Path, skeleton: Value, added: Vec<Value>) -> Result<(), String> {
+    let _guard = INDEX_LOCK.lock().await;
+    let path = project_dir.join(INDEX_FILE);
+    tokio::task::spawn_blocking(move || {
+        let mut index = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| format!("Failed to parse sessions index: {}", e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut index = skeleton;
+                index["entries"] = Value::Array(Vec::new());
+                index
+            }
+            Err(e) => return Err(permissions::io_error("read sessions index", &path, &e)),
+        };
+        let Some(entries) = index.get_mut("entries").and_then(|e| e.as_array_mut()) else {
+            return Err("Failed to parse sessions index: no entries".to_string());
+        };
+        entries.extend(added);
+        let updated = serde_json::to_vec_pretty(&index)
+            .map_err(|e| format!("Failed to serialize sessions index: {}", e))?;
+        fsutil::write_atomic(&path, &updated)
+    })
+    .await
+    .map_err(|e| format!("Failed to update sessions index: {}", e))?
+}
+
+/// Cross-check entries against their transcripts (see `reconcile_entry`); with `heal`, write
+/// the corrected counts, times and first prompts back to the index
+pub async fn check_entries(project_dir: &Path, mut entries: Vec<SessionEntry>, heal: bool) -> Result<Vec<SessionEntry>, String> {
+    let dir = project_dir.to_path_buf();
+    let (entries, corrections) = tokio::task::spawn_blocking(move || {
+        let corrections = reconcile(&dir, &mut entries);
+        (entries, corrections)
+    })
+    .await
+    .map_err(|e| format!("Failed to check sessions index: {}", e))?;
+
+    if heal && !corrections.is_empty() {
+        update_index(project_dir, move |raw| {
+            let by_id: HashMap<&str, &Corrected> = corrections.iter().map(|c| (c.session_id.as_str(), c)).collect();
+            for entry in raw.iter_mut() {
+                let Some(corrected) = entry.get("sessionId").and_then(|v| v.as_str()).and_then(|id| by_id.get(id)) else {
+                    continue;
+                };
+                if let Some(counts) = &corrected.counts {
+                    entry["messageCount"] = Value::from(counts.message_count);
+                    entry["modified"] = Value::from(counts.modified.clone());
+                    if entry.get("fileMtime").is_some() {
+                        entry["fileMtime"] = Value::from(counts.mtime_millis);
+                    }
+                }
+                if let Some(prompt) = &corrected.first_prompt {
+                    entry["firstPrompt"] = Value::from(prompt.clone());
+                }
+            }
+        })
+        .await?;
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, write};
+
+    /// Messages in sessions/basic.jsonl (user and assistant lines)
+    const BASIC_MESSAGES: u32 = 7;
+
+    fn raw_index(project_dir: &Path) -> Value {
+        serde_json::from_str(&std::fs::read_to_string(project_dir.join(INDEX_FILE)).unwrap()).unwrap()
+    }
+
+    /// A project with the basic transcript as "stale" and "fresh", and an index that is
+    /// behind on the first, current on the second and lists a third whose transcript is gone
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "stale.jsonl", fixture("sessions/basic.jsonl"));
+        write(dir.path(), "fresh.jsonl", fixture("sessions/basic.jsonl"));
+        let index = serde_json::json!({
+            "version": 1,
+            "entries": [
+                { "sessionId": "stale", "firstPrompt": "No prompt", "messageCount": 2, "created": "2026-01-05T10:00:00.000Z", "modified": "2026-01-05T10:00:02.000Z",
+                  "fileMtime": 1767607202000_i64, "gitBranch": "main" },
+                { "sessionId": "fresh", "firstPrompt": "Why does the parser skip blank lines?", "messageCount": BASIC_MESSAGES,
+                  "created": "2026-01-05T10:00:00.000Z", "modified": "2999-01-01T00:00:00.000Z" },
+                { "sessionId": "gone", "firstPrompt": "Deleted by hand", "messageCount": 4, "created": "2026-01-04T08:00:00.000Z", "modified": "2026-01-04T09:00:00.000Z" },
+            ],
+        });
+        write(dir.path(), INDEX_FILE, serde_json::to_vec_pretty(&index).unwrap());
+        dir
+    }
+
+    fn entries_of(project_dir: &Path) -> Vec<SessionEntry> {
+        serde_json::from_value(raw_index(project_dir)["entries"].clone()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stale_counts_and_missing_files_are_flagged_without_touching_the_index() {
+        let dir = project();
+        let before = std::fs::read(dir.path().join(INDEX_FILE)).unwrap();
+
+        let entries = check_entries(dir.path(), entries_of(dir.path()), false).await.unwrap();
+        let [stale, fresh, gone] = &entries[..] else { panic!("{:?}", entries) };
+
+        assert!(stale.index_stale && !stale.missing_file);
+        assert_eq!(stale.message_count, BASIC_MESSAGES);
+        assert!(stale.modified.as_str() > "2026-01-05T10:00:02.000Z", "{}", stale.modified);
+        assert_eq!(stale.first_prompt, "Why does the parser skip blank lines?");
+
+        assert!(!fresh.index_stale && !fresh.missing_file);
+        assert_eq!(fresh.modified, "2999-01-01T00:00:00.000Z");
+
+        assert!(gone.missing_file && !gone.index_stale);
+        assert_eq!(gone.message_count, 4);
+
+        assert_eq!(std::fs::read(dir.path().join(INDEX_FILE)).unwrap(), before);
+    }
+
+    #[tokio::test]
+    async fn healing_writes_the_transcript_values_back() {
+        let dir = project();
+        let checked = check_entries(dir.path(), entries_of(dir.path()), true).await.unwrap();
+
+        let index = raw_index(dir.path());
+        let healed = &index["entries"][0];
+        assert_eq!(healed["messageCount"], BASIC_MESSAGES);
+        assert_eq!(healed["modified"], checked[0].modified.as_str());
+        assert_eq!(healed["fileMtime"], digest::parse_utc_timestamp_ms(&checked[0].modified).unwrap());
+        assert_eq!(healed["firstPrompt"], "Why does the parser skip blank lines?");
+        // Fields mensa doesn't know about survive, and the missing entry is left for the user
+        assert_eq!(healed["gitBranch"], "main");
+        assert_eq!(index["version"], 1);
+        assert_eq!(index["entries"][2]["sessionId"], "gone");
+
+        let again = check_entries(dir.path(), entries_of(dir.path()), false).await.unwrap();
+        assert!(!again[0].index_stale);
+        assert!(again[2].missing_file);
+    }
+
+    #[tokio::test]
+    async fn appended_transcript_makes_a_healed_entry_stale_again() {
+        let dir = project();
+        check_entries(dir.path(), entries_of(dir.path()), true).await.unwrap();
+
+        // The index now matches the file's mtime; a later write must move it past the tolerance
+        let transcript = dir.path().join("stale.jsonl");
+        let mut content = fixture("sessions/basic.jsonl");
+        content.push_str("{\"type\":\"user\",\"message\":{\"role\":\"user\",\"content\":\"One more\"}}\n");
+        std::fs::write(&transcript, content).unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        std::fs::File::options().write(true).open(&transcript).unwrap().set_modified(later).unwrap();
+
+        let entries = check_entries(dir.path(), entries_of(dir.path()), false).await.unwrap();
+        assert!(entries[0].index_stale);
+        assert_eq!(entries[0].message_count, BASIC_MESSAGES + 1);
+    }
+
+    #[test]
+    fn first_prompt_corpus() {
+        let prompt = |name: &str| first_prompt(&fixture(&format!("sessions/first_prompt/{}.jsonl", name)));
+        // The first command counts even with no args; its stdout and the meta caveat don't
+        assert_eq!(prompt("slash_command").as_deref(), Some("/model"));
+        assert_eq!(prompt("image_only").as_deref(), Some("Make the header match this mockup"));
+        assert_eq!(prompt("tool_result_resume").as_deref(), Some("continue with the retry backoff"));
+        assert_eq!(prompt("no_prompt"), None);
+
+        let diff = prompt("pasted_diff").unwrap();
+        assert!(diff.starts_with("diff --git a/src/lib.rs b/src/lib.rs index 3b18e51..a9c2f04 100644 --- a/src/lib.rs"), "{}", diff);
+        assert!(!diff.contains("system-reminder") && !diff.contains("  ") && !diff.contains('\n'));
+        assert!(diff.ends_with("why does this break the build?"));
+
+        // Args follow the name with their whitespace collapsed, wherever the tags sit
+        let review = fixture("sessions/first_prompt/slash_command.jsonl").lines().skip(3).collect::<Vec<_>>().join("\n");
+        assert_eq!(first_prompt(&review).as_deref(), Some("/review-pr 482 --strict"));
+    }
+
+    #[test]
+    fn long_first_prompts_are_cut_at_a_word() {
+        let line = serde_json::json!({ "type": "user", "message": { "content": "word ".repeat(100) } }).to_string();
+        let prompt = first_prompt(&line).unwrap();
+        assert!(prompt.chars().count() <= MAX_FIRST_PROMPT_CHARS);
+        assert!(prompt.ends_with("word…"));
+
+        let line = serde_json::json!({ "type": "user", "message": { "content": "x".repeat(500) } }).to_string();
+        assert_eq!(first_prompt(&line).unwrap().chars().count(), MAX_FIRST_PROMPT_CHARS);
+    }
+
+    #[test]
+    fn placeholder_prompts_are_unusable() {
+        for prompt in [
+            "",
+            "   ",
+            "No prompt",
+            r#"[{"type":"tool_result","tool_use_id":"toolu_1","content":"ok"}]"#,
+            r#"[{"type":"image","source":{}}]"#,
+            "<command-message>model</command-message>\n<command-name>/model</command-name>",
+        ] {
+            assert!(is_unusable_prompt(prompt), "{:?}", prompt);
+        }
+        for prompt in ["/model", "[draft] fix the parser", "Why does the parser skip blank lines?"] {
+            assert!(!is_unusable_prompt(prompt), "{:?}", prompt);
+        }
+    }
+}
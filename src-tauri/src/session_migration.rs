@@ -0,0 +1,513 @@
+// mensa - Session Migration Module
+// Moves or copies a workspace's Claude Code sessions to the project directory of its new path
+
+use crate::{bookmarks, canonical_or_raw, context, fsutil, permissions, project_dir_for_workspace, session_index};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationMode {
+    /// Leave the old project's sessions in place
+    Copy,
+    /// Remove each session from the old project once it's written to the new one
+    Move,
+}
+
+/// What happened (or, in a dry run, would happen) to one session
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMigration {
+    pub session_id: String,
+    /// Id in the new project; differs from session_id when the new project already had it
+    pub target_session_id: String,
+    pub reassigned: bool,
+    /// Transcript lines whose cwd or sessionId was rewritten
+    pub lines_rewritten: usize,
+    pub bytes: u64,
+    /// Set when this session couldn't be migrated; the others still are
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    pub old_project_dir: String,
+    pub new_project_dir: String,
+    pub mode: MigrationMode,
+    pub dry_run: bool,
+    pub sessions: Vec<SessionMigration>,
+    /// sessions-index.json entries carried over (titles included)
+    pub index_entries: usize,
+    /// Bookmarks and trimmed-copy mappings carried over in mensa-meta.json
+    pub bookmarks: usize,
+    pub trimmed_sessions: usize,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn trim_separator(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    }
+}
+
+/// `cwd` moved from under any spelling of the old path to the same place under the new one
+fn remap_cwd(cwd: &str, old_paths: &[String], new_path: &str) -> Option<String> {
+    let cwd = fsutil::nfc(cwd);
+    old_paths.iter().find_map(|old| {
+        let rest = cwd.strip_prefix(old.as_str())?;
+        (rest.is_empty() || rest.starts_with('/')).then(|| format!("{}{}", new_path, rest))
+    })
+}
+
+/// Rewrite a transcript's cwd fields to the new path and, for a reassigned session, its
+/// sessionId fields to the new id. Lines that need no change are kept byte for byte.
+/// Returns the new content and how many lines changed.
+fn rewrite_transcript(
+    content: &str,
+    old_paths: &[String],
+    new_path: &str,
+    renamed: Option<(&str, &str)>,
+) -> (String, usize) {
+    let mut lines = Vec::new();
+    let mut rewritten = 0;
+    for line in content.lines() {
+        let may_change = line.contains("\"cwd\"") || renamed.is_some_and(|(old_id, _)| line.contains(old_id));
+        let parsed = may_change.then(|| serde_json::from_str::<Value>(line).ok()).flatten();
+        let Some(mut parsed) = parsed else {
+            lines.push(line.to_string());
+            continue;
+        };
+        let mut changed = false;
+        if let Some(cwd) = parsed
+            .get("cwd")
+            .and_then(|v| v.as_str())
+            .and_then(|cwd| remap_cwd(cwd, old_paths, new_path))
+        {
+            parsed["cwd"] = Value::from(cwd);
+            changed = true;
+        }
+        if let Some((old_id, new_id)) = renamed {
+            if parsed.get("sessionId").and_then(|v| v.as_str()) == Some(old_id) {
+                parsed["sessionId"] = Value::from(new_id);
+                changed = true;
+            }
+        }
+        if changed {
+            rewritten += 1;
+            lines.push(parsed.to_string());
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    let mut updated = lines.join("\n");
+    if content.ends_with('\n') {
+        updated.push('\n');
+    }
+    (updated, rewritten)
+}
+
+/// Session ids of a project: its transcripts and its index entries
+fn project_session_ids(project_dir: &Path, index: &Value) -> HashSet<String> {
+    let mut ids: HashSet<String> = transcript_ids(project_dir).into_iter().collect();
+    ids.extend(index_entries(index).filter_map(|e| e.get("sessionId")?.as_str().map(String::from)));
+    ids
+}
+
+fn transcript_ids(project_dir: &Path) -> Vec<String> {
+    let mut ids: Vec<String> = std::fs::read_dir(project_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .collect();
+    ids.sort();
+    ids
+}
+
+fn read_index(project_dir: &Path) -> Result<Value, String> {
+    match std::fs::read_to_string(project_dir.join(session_index::INDEX_FILE)) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| format!("Failed to parse sessions index: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Value::Null),
+        Err(e) => Err(format!("Failed to read sessions index: {}", e)),
+    }
+}
+
+fn index_entries(index: &Value) -> impl Iterator<Item = &Value> {
+    index.get("entries").and_then(|e| e.as_array()).into_iter().flatten()
+}
+
+/// An old index entry as it belongs in the new project
+fn migrated_entry(entry: &Value, target_id: &str, new_dir: &Path, new_path: &str) -> Value {
+    let mut entry = entry.clone();
+    entry["sessionId"] = Value::from(target_id);
+    if entry.get("fullPath").is_some() {
+        entry["fullPath"] = Value::from(new_dir.join(format!("{}.jsonl", target_id)).to_string_lossy().to_string());
+    }
+    if entry.get("projectPath").is_some() {
+        entry["projectPath"] = Value::from(new_path);
+    }
+    entry
+}
+
+/// Read and rewrite every transcript of the old project, reassigning the id of any session the
+/// new project already has. Returns the reports and the rewritten transcripts (None if unreadable).
+fn prepare_sessions(
+    old_dir: &Path,
+    new_dir: &Path,
+    old_paths: &[String],
+    new_path: &str,
+) -> Result<(Vec<SessionMigration>, Vec<Option<String>>), String> {
+    let taken = project_session_ids(new_dir, &read_index(new_dir)?);
+    let mut reports = Vec::new();
+    let mut prepared = Vec::new();
+    for session_id in transcript_ids(old_dir) {
+        let reassigned = taken.contains(&session_id);
+        let target_session_id = match reassigned {
+            true => uuid::Uuid::new_v4().to_string(),
+            false => session_id.clone(),
+        };
+        let source = old_dir.join(format!("{}.jsonl", session_id));
+        let renamed = reassigned.then_some((session_id.as_str(), target_session_id.as_str()));
+        let mut report = SessionMigration {
+            session_id: session_id.clone(),
+            target_session_id: target_session_id.clone(),
+            reassigned,
+            lines_rewritten: 0,
+            bytes: 0,
+            error: None,
+        };
+        match std::fs::read_to_string(&source) {
+            Ok(content) => {
+                let (content, rewritten) = rewrite_transcript(&content, old_paths, new_path, renamed);
+                report.lines_rewritten = rewritten;
+                report.bytes = content.len() as u64;
+                prepared.push(Some(content));
+            }
+            Err(e) => {
+                report.error = Some(format!("Failed to read session: {}", e));
+                prepared.push(None);
+            }
+        }
+        reports.push(report);
+    }
+    Ok((reports, prepared))
+}
+
+/// Write the prepared transcripts into the new project, never over an existing file
+fn write_sessions(new_dir: &Path, reports: &mut [SessionMigration], prepared: &[Option<String>]) {
+    for (report, prepared) in reports.iter_mut().zip(prepared) {
+        let Some(content) = prepared else {
+            continue;
+        };
+        let target = new_dir.join(format!("{}.jsonl", report.target_session_id));
+        match fsutil::create_new_atomic(&target, content.as_bytes()) {
+            Ok(true) => {}
+            Ok(false) => report.error = Some(format!("{} appeared during the migration", target.display())),
+            Err(e) => report.error = Some(e),
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Carry a workspace's sessions from the project directory of `old_path` to that of `new_path`:
+/// transcripts (cwd rewritten so they resume in the new location), their index entries and
+/// titles, and their bookmarks and trimmed-copy mappings. A session id the new project already
+/// has is given a fresh one, so both are kept. With `dry_run`, only reports what would happen.
+#[tauri::command]
+pub async fn migrate_workspace_sessions(
+    app: tauri::AppHandle,
+    old_path: String,
+    new_path: String,
+    mode: MigrationMode,
+    dry_run: Option<bool>,
+) -> Result<MigrationReport, permissions::SessionError> {
+    let dry_run = dry_run.unwrap_or(false);
+    let old_paths: Vec<String> = {
+        let raw = fsutil::nfc(trim_separator(&old_path));
+        let canonical = fsutil::nfc(&canonical_or_raw(&old_path).to_string_lossy());
+        let mut paths = vec![raw];
+        if !paths.contains(&canonical) {
+            paths.push(canonical);
+        }
+        paths
+    };
+    let new_path = canonical_or_raw(trim_separator(&new_path)).to_string_lossy().to_string();
+    let old_dir = project_dir_for_workspace(trim_separator(&old_path))?;
+    let new_dir = project_dir_for_workspace(&new_path)?;
+    if !old_dir.is_dir() {
+        return Err(format!("No sessions recorded for {}", old_path).into());
+    }
+    if fsutil::same_path_nfc(&old_dir, &new_dir) {
+        return Err("The old and new paths share a project directory".into());
+    }
+    permissions::require(&old_dir, mode == MigrationMode::Move && !dry_run)?;
+    if !dry_run {
+        permissions::require(&new_dir, true)?;
+    }
+
+    let (old_index, (mut sessions, prepared)) = {
+        let (old_dir, new_dir, old_paths, new_path) = (old_dir.clone(), new_dir.clone(), old_paths.clone(), new_path.clone());
+        tokio::task::spawn_blocking(move || {
+            Ok::<_, String>((read_index(&old_dir)?, prepare_sessions(&old_dir, &new_dir, &old_paths, &new_path)?))
+        })
+        .await
+        .map_err(|e| format!("Failed to read sessions: {}", e))??
+    };
+
+    if !dry_run {
+        let new_dir = new_dir.clone();
+        sessions = tokio::task::spawn_blocking(move || {
+            write_sessions(&new_dir, &mut sessions, &prepared);
+            sessions
+        })
+        .await
+        .map_err(|e| format!("Failed to write sessions: {}", e))?;
+    }
+    // Only sessions whose transcript made it carry their index entry and metadata
+    let renames: HashMap<String, String> = sessions
+        .iter()
+        .filter(|s| s.error.is_none())
+        .map(|s| (s.session_id.clone(), s.target_session_id.clone()))
+        .collect();
+
+    let entries: Vec<Value> = index_entries(&old_index)
+        .filter_map(|entry| {
+            let target = renames.get(entry.get("sessionId")?.as_str()?)?;
+            Some(migrated_entry(entry, target, &new_dir, &new_path))
+        })
+        .collect();
+    let index_count = entries.len();
+
+    // The sidecars share one lock, so each is updated on its own: the new project first
+    let remove = mode == MigrationMode::Move;
+    let mut old_meta = bookmarks::load_meta(&app, &old_dir).await?;
+    let (bookmark_count, trimmed_count) = {
+        let guard = bookmarks::lock_meta(&app, &new_dir).await;
+        let mut new_meta = guard.load().await?;
+        let bookmark_count = bookmarks::carry_bookmarks(&mut old_meta, &mut new_meta, &renames, false);
+        let trimmed_count = context::carry_trimmed_sessions(&mut old_meta, &mut new_meta, &renames, false);
+        if !dry_run && bookmark_count + trimmed_count > 0 {
+            guard.save(&new_meta).await?;
+        }
+        (bookmark_count, trimmed_count)
+    };
+
+    if !dry_run {
+        if !entries.is_empty() {
+            let skeleton = match old_index {
+                Value::Object(_) => old_index.clone(),
+                _ => Value::Object(Default::default()),
+            };
+            session_index::append_entries(&new_dir, skeleton, entries).await?;
+        }
+
+        // The new project has everything; only now does a move take it from the old one
+        if remove && !renames.is_empty() {
+            if bookmark_count + trimmed_count > 0 {
+                let guard = bookmarks::lock_meta(&app, &old_dir).await;
+                let mut old_meta = guard.load().await?;
+                let mut carried = bookmarks::ProjectMeta::default();
+                bookmarks::carry_bookmarks(&mut old_meta, &mut carried, &renames, true);
+                context::carry_trimmed_sessions(&mut old_meta, &mut carried, &renames, true);
+                guard.save(&old_meta).await?;
+            }
+            let moved: HashSet<String> = renames.keys().cloned().collect();
+            session_index::update_index(&old_dir, move |entries| {
+                entries.retain(|e| !e.get("sessionId").and_then(|v| v.as_str()).is_some_and(|id| moved.contains(id)));
+            })
+            .await?;
+            for session in sessions.iter_mut().filter(|s| s.error.is_none()) {
+                let source = old_dir.join(format!("{}.jsonl", session.session_id));
+                if let Err(e) = tokio::fs::remove_file(&source).await {
+                    session.error = Some(format!("Copied, but failed to remove the original: {}", e));
+                }
+            }
+        }
+    }
+
+    Ok(MigrationReport {
+        old_project_dir: old_dir.to_string_lossy().to_string(),
+        new_project_dir: new_dir.to_string_lossy().to_string(),
+        mode,
+        dry_run,
+        sessions,
+        index_entries: index_count,
+        bookmarks: bookmark_count,
+        trimmed_sessions: trimmed_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{scratch_home, write};
+    use serde_json::json;
+
+    fn transcript(session_id: &str, cwd: &str, prompt: &str) -> String {
+        let lines = [
+            json!({ "type": "summary", "summary": prompt, "leafUuid": "u2" }),
+            json!({ "type": "user", "uuid": "u1", "cwd": cwd, "sessionId": session_id, "timestamp": "2026-02-01T09:00:00.000Z",
+                    "message": { "role": "user", "content": prompt } }),
+            json!({ "type": "assistant", "uuid": "u2", "cwd": format!("{}/src", cwd), "sessionId": session_id,
+                    "timestamp": "2026-02-01T09:00:05.000Z", "message": { "role": "assistant", "content": [{ "type": "text", "text": "Done." }] } }),
+        ];
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+
+    fn index(project_dir: &Path, workspace: &str, ids: &[&str]) -> String {
+        let entries: Vec<Value> = ids
+            .iter()
+            .map(|id| {
+                json!({
+                    "sessionId": id,
+                    "fullPath": project_dir.join(format!("{}.jsonl", id)),
+                    "projectPath": workspace,
+                    "firstPrompt": format!("prompt of {}", id),
+                    "messageCount": 2,
+                    "created": "2026-02-01T09:00:00.000Z",
+                    "modified": "2026-02-01T09:00:05.000Z",
+                })
+            })
+            .collect();
+        json!({ "version": 1, "entries": entries }).to_string()
+    }
+
+    /// Transcripts and index entries from `old` into `new`, as `migrate_workspace_sessions` copies them
+    async fn migrate(old_dir: &Path, new_dir: &Path, old_path: &str, new_path: &str) -> Vec<SessionMigration> {
+        let (mut sessions, prepared) = prepare_sessions(old_dir, new_dir, &[old_path.to_string()], new_path).unwrap();
+        write_sessions(new_dir, &mut sessions, &prepared);
+        let renames: HashMap<&str, &str> = sessions
+            .iter()
+            .filter(|s| s.error.is_none())
+            .map(|s| (s.session_id.as_str(), s.target_session_id.as_str()))
+            .collect();
+        let old_index = read_index(old_dir).unwrap();
+        let entries = index_entries(&old_index)
+            .filter_map(|entry| {
+                let target = renames.get(entry.get("sessionId")?.as_str()?)?;
+                Some(migrated_entry(entry, target, new_dir, new_path))
+            })
+            .collect();
+        session_index::append_entries(new_dir, old_index.clone(), entries).await.unwrap();
+        sessions
+    }
+
+    #[test]
+    fn cwd_is_remapped_only_under_the_old_path() {
+        let old = ["/work/app".to_string()];
+        assert_eq!(remap_cwd("/work/app", &old, "/new/app").as_deref(), Some("/new/app"));
+        assert_eq!(remap_cwd("/work/app/src/ui", &old, "/new/app").as_deref(), Some("/new/app/src/ui"));
+        assert_eq!(remap_cwd("/work/app-old", &old, "/new/app"), None);
+        assert_eq!(remap_cwd("/work", &old, "/new/app"), None);
+
+        let content = format!("{}not json\n", transcript("s1", "/work/app", "Fix it"));
+        let (updated, rewritten) = rewrite_transcript(&content, &old, "/new/app", None);
+        assert_eq!(rewritten, 2);
+        assert!(updated.ends_with("not json\n"));
+        // Lines without a cwd are kept byte for byte
+        assert_eq!(updated.lines().next(), content.lines().next());
+        assert!(updated.contains(r#""cwd":"/new/app/src""#) && !updated.contains("/work/app"));
+    }
+
+    #[tokio::test]
+    async fn colliding_session_is_merged_under_a_new_id() {
+        let home = scratch_home();
+        let (old_path, new_path) = ("/work/old-app", "/work/new-app");
+        let old_dir = home.claude().project_dir(old_path);
+        let new_dir = home.claude().project_dir(new_path);
+        write(&old_dir, "shared.jsonl", transcript("shared", old_path, "Old side of the shared id"));
+        write(&old_dir, "only-old.jsonl", transcript("only-old", old_path, "Only in the old project"));
+        write(&old_dir, session_index::INDEX_FILE, index(&old_dir, old_path, &["shared", "only-old"]));
+        let existing = transcript("shared", new_path, "New side of the shared id");
+        write(&new_dir, "shared.jsonl", &existing);
+        write(&new_dir, session_index::INDEX_FILE, index(&new_dir, new_path, &["shared"]));
+
+        let sessions = migrate(&old_dir, &new_dir, old_path, new_path).await;
+        let by_id: HashMap<&str, &SessionMigration> = sessions.iter().map(|s| (s.session_id.as_str(), s)).collect();
+        let (shared, only_old) = (by_id["shared"], by_id["only-old"]);
+        assert!(shared.reassigned && shared.error.is_none());
+        assert_ne!(shared.target_session_id, "shared");
+        assert!(!only_old.reassigned && only_old.target_session_id == "only-old");
+        // Both cwd lines, plus the sessionId that moved with them
+        assert_eq!((shared.lines_rewritten, only_old.lines_rewritten), (2, 2));
+
+        // The new project's own session is untouched; the old one sits beside it
+        assert_eq!(std::fs::read_to_string(new_dir.join("shared.jsonl")).unwrap(), existing);
+        let merged = std::fs::read_to_string(new_dir.join(format!("{}.jsonl", shared.target_session_id))).unwrap();
+        assert!(merged.contains("Old side of the shared id"));
+        assert!(!merged.contains(r#""sessionId":"shared""#));
+        assert_eq!(merged.matches(&shared.target_session_id).count(), 2);
+
+        let index = read_index(&new_dir).unwrap();
+        let ids: Vec<&str> = index_entries(&index).filter_map(|e| e["sessionId"].as_str()).collect();
+        assert_eq!(ids, ["shared", shared.target_session_id.as_str(), "only-old"]);
+        let moved = index_entries(&index).find(|e| e["sessionId"] == shared.target_session_id.as_str()).unwrap();
+        assert_eq!(moved["projectPath"], new_path);
+        assert_eq!(moved["firstPrompt"], "prompt of shared");
+        assert_eq!(
+            moved["fullPath"].as_str().map(Path::new),
+            Some(new_dir.join(format!("{}.jsonl", shared.target_session_id)).as_path())
+        );
+
+        // A second run collides on every id and still keeps everything
+        let again = migrate(&old_dir, &new_dir, old_path, new_path).await;
+        assert!(again.iter().all(|s| s.reassigned && s.error.is_none()));
+        assert_eq!(transcript_ids(&new_dir).len(), 5);
+    }
+
+    #[test]
+    fn target_that_appears_mid_migration_is_not_overwritten() {
+        let home = scratch_home();
+        let old_dir = home.claude().project_dir("/a");
+        let new_dir = home.claude().project_dir("/b");
+        write(&old_dir, "s1.jsonl", transcript("s1", "/a", "Hello there everyone"));
+
+        let (mut sessions, prepared) = prepare_sessions(&old_dir, &new_dir, &["/a".to_string()], "/b").unwrap();
+        assert!(!sessions[0].reassigned);
+        write(&new_dir, "s1.jsonl", "written meanwhile\n");
+        write_sessions(&new_dir, &mut sessions, &prepared);
+        assert!(sessions[0].error.as_deref().unwrap().contains("appeared during the migration"));
+        assert_eq!(std::fs::read_to_string(new_dir.join("s1.jsonl")).unwrap(), "written meanwhile\n");
+    }
+
+    #[tokio::test]
+    async fn migrated_sessions_resume_in_the_new_workspace() {
+        let home = scratch_home();
+        let old_path = home.dir.path().join("old-app").to_string_lossy().to_string();
+        let new_workspace = home.dir.path().join("new-app");
+        std::fs::create_dir_all(&new_workspace).unwrap();
+        let new_path = new_workspace.to_string_lossy().to_string();
+        let old_dir = home.claude().project_dir(&old_path);
+        let new_dir = home.claude().project_dir(&new_path);
+        write(&old_dir, "s1.jsonl", transcript("s1", &old_path, "Resume me after the move"));
+        write(&old_dir, session_index::INDEX_FILE, index(&old_dir, &old_path, &["s1"]));
+
+        let sessions = migrate(&old_dir, &new_dir, &old_path, &new_path).await;
+        assert!(sessions[0].error.is_none());
+
+        // Found where Claude Code looks for the new path, recorded there, and listed as
+        // belonging to it rather than as a moved session
+        let found = crate::find_session_path(&new_path, "s1").unwrap();
+        assert_eq!(found, Some(new_dir.join("s1.jsonl")));
+        assert_eq!(crate::recorded_session_cwd(&new_path, "s1").await.unwrap(), Some(new_path.clone()));
+        let listed = crate::session_entries(new_path.clone(), None, &HashSet::new()).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!((listed[0].session_id.as_str(), &listed[0].original_cwd), ("s1", &None));
+    }
+}
@@ -0,0 +1,331 @@
+// mensa - Session Search Module
+// Full-text search over the transcripts of a workspace's sessions
+
+use crate::{digest, permissions, project_dir_for_workspace, session_index, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tauri::State;
+use tokio::sync::Semaphore;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Transcripts read and searched at once
+const SEARCH_CONCURRENCY: usize = 16;
+
+/// Snippets returned per session unless the caller asks for another number
+const DEFAULT_SNIPPETS_PER_SESSION: usize = 3;
+
+/// Sessions returned unless the caller asks for another number
+const DEFAULT_SESSION_LIMIT: usize = 50;
+
+/// Characters of context kept on each side of a match
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Optional behaviours of `search_sessions`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SessionSearchOptions {
+    /// Also search tool results (file contents, command output); off by default as they are mostly noise
+    pub include_tool_results: bool,
+    /// Snippets returned per session (default 3); match_count still counts every match
+    pub max_snippets_per_session: Option<usize>,
+    /// Sessions returned, most recently modified first (default 50)
+    pub limit: Option<usize>,
+}
+
+/// Where a matched text block came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SnippetSource {
+    User,
+    Assistant,
+    ToolResult,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSnippet {
+    pub source: SnippetSource,
+    /// The match with some context, whitespace collapsed and cut marked with "…"
+    pub text: String,
+    /// Timestamp of the transcript entry holding the match
+    pub timestamp: Option<String>,
+}
+
+/// A session with at least one match
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSearchHit {
+    pub session_id: String,
+    pub first_prompt: Option<String>,
+    /// First timestamp in the transcript
+    pub created: Option<String>,
+    /// Modification time of the transcript
+    pub modified: String,
+    /// Text blocks that matched, including those beyond the snippet cap
+    pub match_count: usize,
+    pub snippets: Vec<SearchSnippet>,
+}
+
+/// Returned by `search_sessions`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSearchResults {
+    pub hits: Vec<SessionSearchHit>,
+    /// Transcripts searched
+    pub scanned: usize,
+    /// More sessions matched than `limit` allowed
+    pub truncated: bool,
+}
+
+/// What is searched for: every term must appear in one text block, in any case
+struct SearchTerms(Vec<String>);
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl SearchTerms {
+    fn parse(query: &str) -> Option<Self> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        (!terms.is_empty()).then_some(SearchTerms(terms))
+    }
+
+    /// Byte offset of the first term in `lowered` when every term is in it
+    fn find(&self, lowered: &str) -> Option<usize> {
+        let mut first: Option<usize> = None;
+        for term in &self.0 {
+            let at = lowered.find(term.as_str())?;
+            first = Some(first.map_or(at, |first| first.min(at)));
+        }
+        first
+    }
+
+    /// Whether the file could hold a match at all, before any line is parsed. Terms JSON would
+    /// escape can't be looked for in the raw file and are left to the parsed text.
+    fn may_match(&self, lowered_file: &str) -> bool {
+        self.0
+            .iter()
+            .filter(|term| !term.contains(['"', '\\']))
+            .all(|term| lowered_file.contains(term.as_str()))
+    }
+}
+
+/// Text blocks of one transcript entry, with where they came from. Meta entries and injected
+/// context (system reminders, command output) are left out.
+fn entry_texts(entry: &Value, include_tool_results: bool) -> Vec<(SnippetSource, String)> {
+    let source = match entry.get("type").and_then(|v| v.as_str()) {
+        Some("user") => SnippetSource::User,
+        Some("assistant") => SnippetSource::Assistant,
+        _ => return Vec::new(),
+    };
+    if entry.get("isMeta").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return Vec::new();
+    }
+    let blocks = match entry.get("message").and_then(|m| m.get("content")) {
+        Some(Value::String(text)) => return vec![(source, session_index::strip_injected(text))],
+        Some(Value::Array(blocks)) => blocks,
+        _ => return Vec::new(),
+    };
+    let mut texts = Vec::new();
+    for block in blocks {
+        match block.get("type").and_then(|v| v.as_str()) {
+            Some("text") => {
+                if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+                    texts.push((source, session_index::strip_injected(text)));
+                }
+            }
+            Some("tool_result") if include_tool_results => match block.get("content") {
+                Some(Value::String(text)) => texts.push((SnippetSource::ToolResult, text.clone())),
+                Some(Value::Array(parts)) => texts.extend(
+                    parts
+                        .iter()
+                        .filter_map(|p| p.get("text").and_then(|v| v.as_str()))
+                        .map(|text| (SnippetSource::ToolResult, text.to_string())),
+                ),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    texts
+}
+
+/// Lowercase `text` keeping byte offsets in step with the original, so a match found in the
+/// lowered text can be cut out of the original. Characters whose lowercase changes length are
+/// kept as they are.
+fn lowercase_in_place(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(l), None) if l.len_utf8() == c.len_utf8() => l,
+                _ => c,
+            }
+        })
+        .collect()
+}
+
+/// The text around byte offset `at`, whitespace collapsed, with "…" where it was cut
+fn snippet(text: &str, at: usize) -> String {
+    let before: Vec<char> = text[..at].chars().rev().take(SNIPPET_CONTEXT_CHARS + 1).collect();
+    let after: Vec<char> = text[at..].chars().take(SNIPPET_CONTEXT_CHARS * 2 + 1).collect();
+    let cut_before = before.len() > SNIPPET_CONTEXT_CHARS;
+    let cut_after = after.len() > SNIPPET_CONTEXT_CHARS * 2;
+    let window: String = before
+        .into_iter()
+        .take(SNIPPET_CONTEXT_CHARS)
+        .rev()
+        .chain(after.into_iter().take(SNIPPET_CONTEXT_CHARS * 2))
+        .collect();
+    let window = window.split_whitespace().collect::<Vec<_>>().join(" ");
+    format!(
+        "{}{}{}",
+        if cut_before { "…" } else { "" },
+        window,
+        if cut_after { "…" } else { "" }
+    )
+}
+
+/// Search one transcript; None when it doesn't match or can't be read
+fn search_transcript(
+    path: &Path,
+    terms: &SearchTerms,
+    include_tool_results: bool,
+    max_snippets: usize,
+) -> Option<SessionSearchHit> {
+    let content = std::fs::read(path).ok()?;
+    let content = String::from_utf8_lossy(&content);
+    if !terms.may_match(&content.to_lowercase()) {
+        return None;
+    }
+
+    let mut created = None;
+    let mut match_count = 0;
+    let mut snippets = Vec::new();
+    for line in content.lines() {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let timestamp = entry.get("timestamp").and_then(|v| v.as_str()).map(str::to_string);
+        if created.is_none() {
+            created = timestamp.clone();
+        }
+        for (source, text) in entry_texts(&entry, include_tool_results) {
+            let Some(at) = terms.find(&lowercase_in_place(&text)) else {
+                continue;
+            };
+            match_count += 1;
+            if snippets.len() < max_snippets {
+                snippets.push(SearchSnippet {
+                    source,
+                    text: snippet(&text, at),
+                    timestamp: timestamp.clone(),
+                });
+            }
+        }
+    }
+    if match_count == 0 {
+        return None;
+    }
+
+    let mtime_millis = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as i64);
+    Some(SessionSearchHit {
+        session_id: path.file_stem()?.to_str()?.to_string(),
+        first_prompt: session_index::first_prompt(&content),
+        created,
+        modified: digest::format_utc_timestamp(mtime_millis),
+        match_count,
+        snippets,
+    })
+}
+
+/// Session transcripts of a project dir (subagent transcripts, `agent-*.jsonl`, left out)
+fn transcripts(project_dir: &Path, hidden: &HashSet<String>) -> Result<Vec<PathBuf>, String> {
+    let read_dir = match std::fs::read_dir(project_dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(permissions::io_error("read sessions", project_dir, &e)),
+    };
+    Ok(read_dir
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+        .filter(|path| {
+            path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| !stem.starts_with("agent-") && !hidden.contains(stem))
+        })
+        .collect())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Search the text of every session in a workspace: user and assistant messages, and with
+/// `includeToolResults` tool output too. Sessions come back most recently modified first, each
+/// with up to `maxSnippetsPerSession` snippets. Sessions of quick questions are left out.
+#[tauri::command]
+pub async fn search_sessions(
+    state: State<'_, AppState>,
+    workspace_path: String,
+    query: String,
+    options: Option<SessionSearchOptions>,
+) -> Result<SessionSearchResults, permissions::SessionError> {
+    let options = options.unwrap_or_default();
+    let Some(terms) = SearchTerms::parse(&query) else {
+        return Err("Search query is empty".to_string().into());
+    };
+    let project_dir = project_dir_for_workspace(&workspace_path)?;
+    permissions::require(&project_dir, false)?;
+    let paths = transcripts(&project_dir, &state.quick_sessions.hidden())?;
+
+    let terms = Arc::new(terms);
+    let max_snippets = options.max_snippets_per_session.unwrap_or(DEFAULT_SNIPPETS_PER_SESSION);
+    let permits = Arc::new(Semaphore::new(SEARCH_CONCURRENCY));
+    let scanned = paths.len();
+    let handles: Vec<_> = paths
+        .into_iter()
+        .map(|path| {
+            let (permits, terms) = (permits.clone(), terms.clone());
+            let include_tool_results = options.include_tool_results;
+            tauri::async_runtime::spawn(async move {
+                let _permit = permits.acquire_owned().await.ok()?;
+                tokio::task::spawn_blocking(move || {
+                    search_transcript(&path, &terms, include_tool_results, max_snippets)
+                })
+                .await
+                .ok()?
+            })
+        })
+        .collect();
+
+    let mut hits = Vec::new();
+    for handle in handles {
+        if let Ok(Some(hit)) = handle.await {
+            hits.push(hit);
+        }
+    }
+    hits.sort_by_key(|hit| Reverse(hit.modified.clone()));
+    let limit = options.limit.unwrap_or(DEFAULT_SESSION_LIMIT);
+    let truncated = hits.len() > limit;
+    hits.truncate(limit);
+
+    Ok(SessionSearchResults {
+        hits,
+        scanned,
+        truncated,
+    })
+}
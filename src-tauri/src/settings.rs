@@ -0,0 +1,474 @@
+// mensa - Settings Module
+// App-wide preferences: one JSON file, cheap concurrent reads, validated partial updates
+
+use crate::store::JsonStore;
+use crate::AppState;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tauri::{Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Longest accepted batching interval
+const MAX_BATCH_INTERVAL_MS: u64 = 60_000;
+
+/// Longest accepted minimum interval between git status walks
+const MAX_STATUS_INTERVAL_MS: u64 = 10_000;
+
+/// Longest accepted default timeout (one day)
+const MAX_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+/// Largest accepted context window override
+const MAX_CONTEXT_LIMIT: u64 = 100_000_000;
+
+/// Queries a query group may have running at once (counting every other running query)
+pub const DEFAULT_MAX_CONCURRENT_QUERIES: u32 = 4;
+
+/// Highest accepted concurrency limit
+const MAX_CONCURRENT_QUERIES: u64 = 32;
+
+/// Highest accepted disk space threshold (1 TB)
+const MAX_DISK_THRESHOLD_MB: u64 = 1024 * 1024;
+
+/// Memory parsed sessions may take by default
+pub const DEFAULT_SESSION_CACHE_MB: u64 = 100;
+
+/// Largest accepted parsed session cache
+const MAX_SESSION_CACHE_MB: u64 = 4096;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// Show a system notification when a query finishes in the background
+    pub notifications_enabled: bool,
+    /// Command used to open files in an external editor (e.g. "code", "zed")
+    pub preferred_editor: Option<String>,
+    /// Node binary to run queries with, instead of searching the usual install locations
+    pub node_path: Option<String>,
+    /// Oldest node version queries run with (e.g. "20.0.0"); None uses the built-in minimum
+    pub min_node_version: Option<String>,
+    /// Keep a tray icon while the app runs
+    pub tray_enabled: bool,
+    /// How long UI updates (file changes, status refreshes) are coalesced
+    pub batch_interval_ms: u64,
+    /// How soon after a git status walk another one may run; calls in between reuse the result
+    pub git_status_min_interval_ms: u64,
+    /// Timeout applied to long-running operations that don't set their own
+    pub default_timeout_secs: u64,
+    /// Record submitted prompts for recall; turning it off deletes what was recorded
+    pub prompt_history_enabled: bool,
+    /// Log finished queries for the usage analytics; turning it off deletes the log
+    pub usage_analytics_enabled: bool,
+    /// Check for a newer mensa once a day in the background
+    pub auto_update_check: bool,
+    /// Context window in tokens by model-name substring, ahead of the model catalog
+    /// (e.g. {"opus-5": 500000}); set a key to null to drop it
+    pub context_limits: HashMap<String, u64>,
+    /// Percentages of the context window at which a running query warns
+    pub context_warning_percents: Vec<u8>,
+    /// Running queries a query group waits for before starting its next member
+    pub max_concurrent_queries: u32,
+    /// HTTP(S) proxy for the agent, git and gh; credentials live in the keychain
+    pub proxy: crate::proxy::ProxySettings,
+    /// Commands run, or NDJSON feeds appended to, on query, commit and PR events
+    pub integration_hooks: Vec<crate::integrations::IntegrationHook>,
+    /// Free space (MB) below which a query warns with `low-disk-warning`, before starting or while running
+    pub low_disk_warning_mb: u64,
+    /// Free space (MB) below which a query refuses to start
+    pub min_free_disk_mb: u64,
+    /// Extra stderr classifications (regex → severity), checked before the built-in rules
+    pub stderr_rules: Vec<crate::stderr::StderrRule>,
+    /// Accept requests from editor extensions on a local socket (see `control`); applies at next launch
+    pub control_server_enabled: bool,
+    /// Memory (MB, estimated from transcript sizes) parsed sessions are kept in; 0 turns the cache off
+    pub session_cache_mb: u64,
+    /// http(s) URL of a model catalog manifest layered between the built-in models and models.json
+    pub model_catalog_url: Option<String>,
+    /// Refuse mutating commands and run queries in plan mode; only `set_read_only` changes it
+    pub read_only: bool,
+    /// Keys written by a newer version, kept so a downgrade doesn't drop them
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            notifications_enabled: true,
+            preferred_editor: None,
+            node_path: None,
+            min_node_version: None,
+            tray_enabled: false,
+            batch_interval_ms: 250,
+            git_status_min_interval_ms: 300,
+            default_timeout_secs: 300,
+            prompt_history_enabled: true,
+            usage_analytics_enabled: true,
+            auto_update_check: true,
+            context_limits: HashMap::new(),
+            context_warning_percents: crate::context_usage::DEFAULT_WARNING_PERCENTS.to_vec(),
+            max_concurrent_queries: DEFAULT_MAX_CONCURRENT_QUERIES,
+            proxy: crate::proxy::ProxySettings::default(),
+            integration_hooks: Vec::new(),
+            low_disk_warning_mb: 2048,
+            min_free_disk_mb: 500,
+            stderr_rules: Vec::new(),
+            control_server_enabled: false,
+            session_cache_mb: DEFAULT_SESSION_CACHE_MB,
+            model_catalog_url: None,
+            read_only: false,
+            extra: Map::new(),
+        }
+    }
+}
+
+static SETTINGS_FILE: JsonStore<Settings> = JsonStore::new("settings");
+
+/// Loaded settings behind a read-mostly lock; writers also hold `write_lock` across the disk write
+#[derive(Default)]
+pub struct SettingsStore {
+    current: RwLock<Option<Arc<Settings>>>,
+    write_lock: Mutex<()>,
+    /// `read_only` of the last settings loaded, readable without awaiting
+    read_only: AtomicBool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldError {
+    pub key: String,
+    pub message: String,
+}
+
+/// Error returned by update_settings
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SettingsError {
+    /// The patch was rejected; nothing was saved
+    Invalid { errors: Vec<FieldError> },
+    Failed { message: String },
+}
+
+impl From<String> for SettingsError {
+    fn from(message: String) -> Self {
+        SettingsError::Failed { message }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SettingsChanged {
+    keys: Vec<String>,
+    settings: Settings,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn settings_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("settings.json"))
+}
+
+fn cached(store: &SettingsStore) -> Option<Arc<Settings>> {
+    store.current.read().ok().and_then(|current| current.clone())
+}
+
+fn replace_cached(store: &SettingsStore, settings: Arc<Settings>) {
+    store.read_only.store(settings.read_only, Ordering::Relaxed);
+    if let Ok(mut current) = store.current.write() {
+        *current = Some(settings);
+    }
+}
+
+/// Drop the cached copy (after the file was replaced underneath the store)
+pub fn invalidate(store: &SettingsStore) {
+    if let Ok(mut current) = store.current.write() {
+        *current = None;
+    }
+}
+
+/// Whether read-only mode was on in the settings last loaded or saved
+pub fn read_only(store: &SettingsStore) -> bool {
+    store.read_only.load(Ordering::Relaxed)
+}
+
+/// Current settings, read from disk on first use
+pub async fn load(app: &tauri::AppHandle, store: &SettingsStore) -> Result<Arc<Settings>, String> {
+    if let Some(settings) = cached(store) {
+        return Ok(settings);
+    }
+    let _guard = store.write_lock.lock().await;
+    if let Some(settings) = cached(store) {
+        return Ok(settings);
+    }
+    let settings = Arc::new(SETTINGS_FILE.read(app, settings_path(app)?).await?);
+    replace_cached(store, settings.clone());
+    Ok(settings)
+}
+
+/// Node binary to run: the configured one if it exists, otherwise the usual search
+pub async fn node_binary(app: &tauri::AppHandle) -> String {
+    let state = app.state::<AppState>();
+    let configured = load(app, &state.settings)
+        .await
+        .ok()
+        .and_then(|s| s.node_path.clone())
+        .filter(|path| Path::new(path).is_file());
+    configured.unwrap_or_else(crate::find_node_binary)
+}
+
+/// Merge `patch` into `target`: objects merge key by key, null removes a key, anything else replaces
+fn deep_merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    _ if value.is_null() => {
+                        target.remove(&key);
+                    }
+                    Some(existing) if existing.is_object() && value.is_object() => deep_merge(existing, value),
+                    _ => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+fn expect<T: serde::de::DeserializeOwned>(value: &Value) -> Result<T, String> {
+    serde_json::from_value(value.clone()).map_err(|e| e.to_string())
+}
+
+fn in_range(value: &Value, min: u64, max: u64) -> Result<(), String> {
+    let n: u64 = expect(value)?;
+    if n < min || n > max {
+        return Err(format!("must be between {} and {}", min, max));
+    }
+    Ok(())
+}
+
+/// Check one patched key against its type and allowed values
+fn validate_field(key: &str, value: &Value) -> Result<(), String> {
+    match key {
+        "notificationsEnabled" | "trayEnabled" | "promptHistoryEnabled" | "usageAnalyticsEnabled"
+        | "autoUpdateCheck" | "controlServerEnabled" => expect::<bool>(value).map(|_| ()),
+        "preferredEditor" => match expect::<Option<String>>(value)? {
+            Some(editor) if editor.trim().is_empty() => Err("must not be empty (use null to clear)".to_string()),
+            _ => Ok(()),
+        },
+        "nodePath" => match expect::<Option<String>>(value)? {
+            Some(path) if !Path::new(&path).is_absolute() => Err("must be an absolute path".to_string()),
+            Some(path) if !Path::new(&path).is_file() => Err(format!("{} does not exist", path)),
+            _ => Ok(()),
+        },
+        "minNodeVersion" => match expect::<Option<String>>(value)? {
+            Some(version) if crate::runtime::parse_version(&version).is_none() => {
+                Err(format!("'{}' is not a version like 18.0.0", version))
+            }
+            _ => Ok(()),
+        },
+        "batchIntervalMs" => in_range(value, 0, MAX_BATCH_INTERVAL_MS),
+        "gitStatusMinIntervalMs" => in_range(value, 0, MAX_STATUS_INTERVAL_MS),
+        "defaultTimeoutSecs" => in_range(value, 1, MAX_TIMEOUT_SECS),
+        "maxConcurrentQueries" => in_range(value, 1, MAX_CONCURRENT_QUERIES),
+        "lowDiskWarningMb" | "minFreeDiskMb" => in_range(value, 0, MAX_DISK_THRESHOLD_MB),
+        "sessionCacheMb" => in_range(value, 0, MAX_SESSION_CACHE_MB),
+        "contextLimits" => {
+            for (model, limit) in expect::<HashMap<String, Option<u64>>>(value)? {
+                if model.trim().is_empty() {
+                    return Err("model names must not be empty".to_string());
+                }
+                if let Some(limit) = limit {
+                    in_range(&Value::from(limit), 1, MAX_CONTEXT_LIMIT).map_err(|e| format!("{}: {}", model, e))?;
+                }
+            }
+            Ok(())
+        }
+        "proxy" => {
+            let Value::Object(fields) = value else {
+                return Err("must be an object".to_string());
+            };
+            for (field, value) in fields {
+                match field.as_str() {
+                    "mode" => expect::<crate::proxy::ProxyMode>(value)
+                        .map(|_| ())
+                        .map_err(|_| "mode must be \"none\", \"manual\" or \"system\"".to_string())?,
+                    "http" | "https" => {
+                        if let Some(url) = expect::<Option<String>>(value)? {
+                            crate::proxy::validate_url(&url).map_err(|e| format!("{} {}", field, e))?;
+                        }
+                    }
+                    "noProxy" => {
+                        expect::<Option<String>>(value)?;
+                    }
+                    other => return Err(format!("unknown proxy field '{}'", other)),
+                }
+            }
+            Ok(())
+        }
+        "integrationHooks" => {
+            let hooks: Vec<crate::integrations::IntegrationHook> = expect(value)?;
+            for (index, hook) in hooks.iter().enumerate() {
+                crate::integrations::validate_hook(hook).map_err(|e| format!("hook {}: {}", index, e))?;
+            }
+            Ok(())
+        }
+        "modelCatalogUrl" => match expect::<Option<String>>(value)? {
+            Some(url) => match reqwest::Url::parse(&url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.host_str().is_some() => Ok(()),
+                Ok(_) => Err("must be an http(s) URL".to_string()),
+                Err(e) => Err(format!("is not a valid URL: {}", e)),
+            },
+            None => Ok(()),
+        },
+        "readOnly" => Err("can only be changed with set_read_only".to_string()),
+        "stderrRules" => crate::stderr::validate_rules(&expect::<Vec<crate::stderr::StderrRule>>(value)?),
+        "contextWarningPercents" => {
+            let percents: Vec<u64> = expect(value)?;
+            match percents.iter().find(|&&p| p == 0 || p > 100) {
+                Some(p) => Err(format!("{} is not a percentage between 1 and 100", p)),
+                None => Ok(()),
+            }
+        }
+        _ => Err("unknown setting".to_string()),
+    }
+}
+
+/// Top-level keys whose values differ between two serialized settings
+fn changed_keys(before: &Value, after: &Value) -> Vec<String> {
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return Vec::new();
+    };
+    let mut keys: Vec<String> = after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .chain(before.keys().filter(|key| !after.contains_key(*key)).cloned())
+        .collect();
+    keys.sort();
+    keys
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Get the current settings
+#[tauri::command]
+pub async fn get_settings(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<Settings, String> {
+    Ok(load(&app, &state.settings).await?.as_ref().clone())
+}
+
+/// Apply a partial settings object. Every key is validated before anything is saved;
+/// listeners get `settings-changed` with the keys that actually changed.
+#[tauri::command]
+pub async fn update_settings(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    patch: Value,
+) -> Result<Settings, SettingsError> {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => return Err("Settings patch must be an object".to_string().into()),
+    };
+
+    let locked = read_only(&state.settings);
+    let errors: Vec<FieldError> = patch
+        .iter()
+        .filter_map(|(key, value)| {
+            let checked = match locked && crate::read_only::LOCKED_SETTINGS.contains(&key.as_str()) {
+                true => Err("can't be changed while mensa is in read-only mode".to_string()),
+                false => validate_field(key, value),
+            };
+            checked.err().map(|message| FieldError {
+                key: key.clone(),
+                message,
+            })
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(SettingsError::Invalid { errors });
+    }
+
+    let current = load(&app, &state.settings).await?;
+    let _guard = state.settings.write_lock.lock().await;
+    // Another writer may have landed while we waited for the lock
+    let current = cached(&state.settings).unwrap_or(current);
+
+    let before = serde_json::to_value(current.as_ref())
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let mut merged = before.clone();
+    deep_merge(&mut merged, Value::Object(patch));
+    let updated: Settings =
+        serde_json::from_value(merged).map_err(|e| format!("Failed to apply settings: {}", e))?;
+    let after = serde_json::to_value(&updated).map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    let keys = changed_keys(&before, &after);
+    if keys.is_empty() {
+        return Ok(updated);
+    }
+
+    SETTINGS_FILE.write(&app, settings_path(&app)?, &updated).await?;
+
+    replace_cached(&state.settings, Arc::new(updated.clone()));
+    if keys.iter().any(|k| k == "promptHistoryEnabled") && !updated.prompt_history_enabled {
+        crate::prompt_history::purge(&app).await?;
+    }
+    if keys.iter().any(|k| k == "usageAnalyticsEnabled") && !updated.usage_analytics_enabled {
+        crate::usage::purge(&app).await?;
+    }
+    if keys.iter().any(|k| k == "proxy") {
+        crate::proxy::refresh(&app).await?;
+    }
+    if keys.iter().any(|k| k == "modelCatalogUrl") {
+        crate::models::spawn_refresh(&app);
+    }
+    let _ = app.emit(
+        "settings-changed",
+        SettingsChanged {
+            keys,
+            settings: updated.clone(),
+        },
+    );
+    Ok(updated)
+}
+
+/// Save the read-only flag, which `update_settings` refuses to change (see `read_only::set_read_only`)
+pub async fn store_read_only(app: &tauri::AppHandle, enabled: bool) -> Result<Settings, String> {
+    let store = &app.state::<AppState>().settings;
+    let current = load(app, store).await?;
+    let _guard = store.write_lock.lock().await;
+    let current = cached(store).unwrap_or(current);
+    if current.read_only == enabled {
+        return Ok(current.as_ref().clone());
+    }
+
+    let updated = Settings {
+        read_only: enabled,
+        ..current.as_ref().clone()
+    };
+    SETTINGS_FILE.write(app, settings_path(app)?, &updated).await?;
+    replace_cached(store, Arc::new(updated.clone()));
+    let _ = app.emit(
+        "settings-changed",
+        SettingsChanged {
+            keys: vec!["readOnly".to_string()],
+            settings: updated.clone(),
+        },
+    );
+    Ok(updated)
+}
@@ -0,0 +1,151 @@
+// mensa - Status Filters Module
+// Per-workspace display-ignore globs that group noisy files (lockfiles, generated code) out of status and diff views
+
+use crate::git::{DiffStats, GitState, GitStatus};
+use crate::store::JsonStore;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{Manager, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+static FILTERS_FILE: JsonStore<StatusFiltersFile> = JsonStore::new("status filters");
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StatusFiltersFile {
+    /// Glob patterns keyed by canonical workspace path
+    workspaces: HashMap<String, Vec<String>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn filters_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("status-filters.json"))
+}
+
+/// Stable key for a workspace, so "repo" and "repo/" share one filter list
+fn workspace_key(working_dir: &str) -> String {
+    std::fs::canonicalize(working_dir)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| working_dir.trim_end_matches('/').to_string())
+}
+
+async fn load_filters(app: &tauri::AppHandle) -> Result<StatusFiltersFile, String> {
+    FILTERS_FILE.read(app, filters_path(app)?).await
+}
+
+/// Compile globs into a matcher. Each glob is tested against both the file
+/// name and the repository-relative path.
+fn build_matcher(globs: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in globs {
+        let glob = Glob::new(pattern).map_err(|e| format!("Invalid status filter '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to compile status filters: {}", e))
+}
+
+fn is_filtered(matcher: &GlobSet, path: &str) -> bool {
+    let path = Path::new(path);
+    let by_name = path.file_name().map(|name| matcher.is_match(name)).unwrap_or(false);
+    by_name || matcher.is_match(path)
+}
+
+/// The compiled filters for a workspace (None when it has none), compiled once and cached
+pub async fn matcher_for(
+    app: &tauri::AppHandle,
+    state: &GitState,
+    working_dir: &str,
+) -> Result<Option<Arc<GlobSet>>, String> {
+    let key = workspace_key(working_dir);
+    if let Some(matcher) = state.status_filters.lock().await.get(&key) {
+        return Ok((!matcher.is_empty()).then_some(matcher.clone()));
+    }
+
+    let globs = load_filters(app).await?.workspaces.remove(&key).unwrap_or_default();
+    let matcher = Arc::new(build_matcher(&globs)?);
+    state.status_filters.lock().await.insert(key, matcher.clone());
+    Ok((!matcher.is_empty()).then_some(matcher))
+}
+
+/// Move matching files out of the status lists into `filtered`
+pub fn partition_status(status: &mut GitStatus, matcher: &GlobSet) {
+    for list in [
+        &mut status.staged,
+        &mut status.modified,
+        &mut status.untracked,
+        &mut status.deleted,
+    ] {
+        let (hidden, kept) = std::mem::take(list)
+            .into_iter()
+            .partition(|file| is_filtered(matcher, &file.path));
+        *list = kept;
+        status.filtered.extend::<Vec<_>>(hidden);
+    }
+    status.files.retain(|file| !is_filtered(matcher, &file.path));
+}
+
+/// Move matching files into `filtered`; the totals then cover only the remaining files
+pub fn partition_stats(stats: &mut DiffStats, matcher: &GlobSet) {
+    let (hidden, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut stats.files)
+        .into_iter()
+        .partition(|file| is_filtered(matcher, &file.path));
+    stats.files = kept;
+    stats.additions = stats.files.iter().map(|f| f.additions).sum();
+    stats.deletions = stats.files.iter().map(|f| f.deletions).sum();
+    stats.filtered.extend(hidden);
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Get the display-ignore globs for a workspace
+#[tauri::command]
+pub async fn get_status_filters(app: tauri::AppHandle, working_dir: String) -> Result<Vec<String>, String> {
+    let mut file = load_filters(&app).await?;
+    Ok(file.workspaces.remove(&workspace_key(&working_dir)).unwrap_or_default())
+}
+
+/// Replace the display-ignore globs for a workspace (validated before saving).
+/// An empty list removes the workspace's filters.
+#[tauri::command]
+pub async fn set_status_filters(
+    app: tauri::AppHandle,
+    state: State<'_, GitState>,
+    working_dir: String,
+    globs: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let globs: Vec<String> = globs
+        .into_iter()
+        .map(|g| g.trim().to_string())
+        .filter(|g| !g.is_empty())
+        .collect();
+    let matcher = build_matcher(&globs)?;
+
+    let key = workspace_key(&working_dir);
+    let guard = FILTERS_FILE.lock(&app, filters_path(&app)?).await;
+    let mut file = guard.load().await?;
+    if globs.is_empty() {
+        file.workspaces.remove(&key);
+    } else {
+        file.workspaces.insert(key.clone(), globs.clone());
+    }
+    guard.save(&file).await?;
+
+    state.status_filters.lock().await.insert(key, Arc::new(matcher));
+    Ok(globs)
+}
@@ -0,0 +1,195 @@
+// mensa - Stderr Module
+// Tags the agent's stderr lines with a severity, groups stack traces under their error and counts benign noise
+
+use crate::cancel::CancellationToken;
+use crate::replay;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// How long a line waits for stack frames that may follow it before it's emitted
+const FRAME_WAIT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Known-harmless output the UI keeps out of error messages
+    pub fn is_benign(self) -> bool {
+        matches!(self, Severity::Debug | Severity::Info)
+    }
+}
+
+/// A user rule from settings, checked before the built-in ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StderrRule {
+    pub pattern: String,
+    pub severity: Severity,
+}
+
+/// A `claude-stderr` event: one line, or an error line with the stack frames below it
+#[derive(Debug, Clone, Serialize)]
+pub struct StderrEvent {
+    pub query_id: String,
+    pub data: String,
+    pub severity: Severity,
+}
+
+/// Compiled rules for one query: the user's from settings, then the built-in set
+pub struct Classifier {
+    rules: Vec<(Regex, Severity)>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Known node and SDK noise, then generic heuristics; first match wins
+fn builtin_rules() -> &'static [(Regex, Severity)] {
+    static RULES: OnceLock<Vec<(Regex, Severity)>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        [
+            (r"DeprecationWarning: The `?punycode`? module is deprecated", Severity::Debug),
+            (r"ExperimentalWarning:", Severity::Debug),
+            (r"^\(Use `node --trace-(deprecation|warnings) \.\.\.` to show where the warning was created\)", Severity::Debug),
+            (r"^\(node:\d+\) \[DEP\d+\] DeprecationWarning:", Severity::Debug),
+            (r"^(Debugger attached|Waiting for the debugger to disconnect)", Severity::Debug),
+            (r"^\[claude-query\]", Severity::Debug),
+            (r"^npm (notice|WARN)\b", Severity::Info),
+            (r"^(Uncaught )?([A-Z][A-Za-z]*)?Error( \[[A-Z_0-9]+\])?:", Severity::Error),
+            (r"(?i)^\s*(fatal|error|panic)\b", Severity::Error),
+            (r"\b(ECONNREFUSED|ECONNRESET|ENOTFOUND|ETIMEDOUT|EACCES|EPERM|ENOENT)\b", Severity::Error),
+            (r"(?i)^\s*warn(ing)?\b", Severity::Warning),
+        ]
+        .into_iter()
+        .map(|(pattern, severity)| (Regex::new(pattern).unwrap(), severity))
+        .collect()
+    })
+}
+
+/// A frame of a JS stack trace ("    at fn (file:1:2)", "    ... 3 more lines")
+fn is_stack_frame(line: &str) -> bool {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN
+        .get_or_init(|| Regex::new(r"^\s+(at\s|\.\.\. \d+ more)").unwrap())
+        .is_match(line)
+}
+
+pub fn validate_rules(rules: &[StderrRule]) -> Result<(), String> {
+    for rule in rules {
+        Regex::new(&rule.pattern).map_err(|e| format!("invalid pattern '{}': {}", rule.pattern, e))?;
+    }
+    Ok(())
+}
+
+impl Classifier {
+    pub fn new(user_rules: &[StderrRule]) -> Self {
+        // Rules are validated when saved; one that stops compiling is skipped, not fatal
+        let mut rules: Vec<(Regex, Severity)> = user_rules
+            .iter()
+            .filter_map(|rule| Regex::new(&rule.pattern).ok().map(|re| (re, rule.severity)))
+            .collect();
+        rules.extend(builtin_rules().iter().cloned());
+        Classifier { rules }
+    }
+
+    /// The first matching rule's severity; anything unrecognized is a warning, so nothing
+    /// real is hidden by a gap in the rules
+    pub fn classify(&self, line: &str) -> Severity {
+        self.rules
+            .iter()
+            .find(|(re, _)| re.is_match(line))
+            .map(|(_, severity)| *severity)
+            .unwrap_or(Severity::Warning)
+    }
+}
+
+/// Forward a query's stderr as classified `claude-stderr` events until it closes or `token`
+/// is cancelled. Returns how many benign (debug/info) lines there were.
+pub async fn pump<R: AsyncRead + Unpin>(
+    app: tauri::AppHandle,
+    query_id: String,
+    stderr: R,
+    classifier: Classifier,
+    token: CancellationToken,
+) -> u32 {
+    pump_into(stderr, classifier, token, |data, severity| {
+        let (app, query_id) = (app.clone(), query_id.clone());
+        async move {
+            replay::emit(&app, &query_id, "claude-stderr", StderrEvent {
+                query_id: query_id.clone(),
+                data,
+                severity,
+            })
+            .await
+        }
+    })
+    .await
+}
+
+/// `pump`, handing each event's text and severity to `emit` and waiting for it
+pub async fn pump_into<R: AsyncRead + Unpin, F: Future<Output = ()>>(
+    stderr: R,
+    classifier: Classifier,
+    token: CancellationToken,
+    mut emit: impl FnMut(String, Severity) -> F,
+) -> u32 {
+    let mut reader = BufReader::new(stderr).lines();
+    let mut pending: Option<(String, Severity)> = None;
+    let mut benign = 0;
+    let mut flush = async |pending: &mut Option<(String, Severity)>| {
+        if let Some((data, severity)) = pending.take() {
+            if severity.is_benign() {
+                benign += data.lines().count() as u32;
+            }
+            emit(data, severity).await;
+        }
+    };
+
+    loop {
+        let line = tokio::select! {
+            line = reader.next_line() => line,
+            _ = tokio::time::sleep(FRAME_WAIT), if pending.is_some() => {
+                flush(&mut pending).await;
+                continue;
+            }
+            _ = token.cancelled() => break,
+        };
+        let Ok(Some(line)) = line else {
+            break;
+        };
+        if line.is_empty() {
+            continue;
+        }
+        if is_stack_frame(&line) {
+            if let Some((data, _)) = pending.as_mut() {
+                data.push('\n');
+                data.push_str(&line);
+                continue;
+            }
+        }
+        flush(&mut pending).await;
+        // A frame with nothing above it still belongs to some error
+        let severity = match is_stack_frame(&line) {
+            true => Severity::Error,
+            false => classifier.classify(&line),
+        };
+        pending = Some((line, severity));
+    }
+    flush(&mut pending).await;
+    benign
+}
@@ -0,0 +1,350 @@
+// mensa - Store Module
+// Crash-safe JSON files in app data: atomic writes, a backup of the last good version, recovery on read
+
+use crate::fsutil;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use tauri::Emitter;
+use tokio::sync::{Mutex, MutexGuard};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// One kind of app-data JSON file (settings, presets, ...). Every read and write goes through
+/// the store's lock, so racing tasks can't interleave a read-modify-write or a repair.
+///
+/// Next to `name.json` live `name.json.bak`, the previous good content, and while a write
+/// is in flight `name.json.dirty`; a dirty marker found on read means that write never finished.
+pub struct JsonStore<T> {
+    /// Used in messages and the `store-recovered` event ("settings", "query presets", ...)
+    name: &'static str,
+    lock: Mutex<()>,
+    _value: PhantomData<fn() -> T>,
+}
+
+/// Exclusive access to one file of a store, for a read-modify-write
+pub struct StoreGuard<'a, T> {
+    store: &'a JsonStore<T>,
+    app: tauri::AppHandle,
+    path: PathBuf,
+    _lock: MutexGuard<'a, ()>,
+}
+
+/// What a read had to fall back on
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RecoverySource {
+    Backup,
+    Default,
+}
+
+/// A repair made while reading
+struct Recovery {
+    source: RecoverySource,
+    /// Where the unreadable file was moved
+    corrupt_copy: Option<PathBuf>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    sibling(path, ".bak")
+}
+
+fn dirty_path(path: &Path) -> PathBuf {
+    sibling(path, ".dirty")
+}
+
+fn parse<T: DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    serde_json::from_slice(bytes).ok()
+}
+
+/// Flush the directory entry of a rename, so it survives a power cut along with the content
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = std::fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Temp files an interrupted `fsutil::write_atomic` of this path left behind
+fn remove_stale_temps(path: &Path) {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return;
+    };
+    let prefix = format!(".{}.mensa-", name.to_string_lossy());
+    for entry in std::fs::read_dir(parent).into_iter().flatten().flatten() {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with(&prefix) && file_name.ends_with(".tmp") {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Keep an unreadable file next to the store for inspection instead of overwriting it
+fn set_aside(path: &Path) -> Option<PathBuf> {
+    let aside = sibling(path, &format!(".corrupt-{}", crate::history::now_secs()));
+    std::fs::rename(path, &aside).ok().map(|_| aside)
+}
+
+/// Read a store file, repairing it from the backup (or resetting it) when it's missing or
+/// unreadable. Returns the value and what was recovered, if anything.
+fn read_blocking<T: Serialize + DeserializeOwned + Default>(
+    path: &Path,
+) -> Result<(T, Option<Recovery>), String> {
+    let dirty = dirty_path(path);
+    let interrupted = dirty.exists();
+    if interrupted {
+        remove_stale_temps(path);
+    }
+
+    let main = match std::fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    if let Some(value) = main.as_deref().and_then(parse::<T>) {
+        if interrupted {
+            let _ = std::fs::remove_file(&dirty);
+        }
+        return Ok((value, None));
+    }
+
+    let backup = std::fs::read(backup_path(path)).ok();
+    let from_backup = backup.as_deref().and_then(|bytes| parse::<T>(bytes).map(|value| (value, bytes.to_vec())));
+    if main.is_none() && from_backup.is_none() {
+        // Never written (or both gone): a fresh store, not a recovery
+        let _ = std::fs::remove_file(&dirty);
+        return Ok((T::default(), None));
+    }
+
+    let corrupt_copy = main.as_ref().and_then(|_| set_aside(path));
+    let (value, source) = match from_backup {
+        Some((value, bytes)) => {
+            fsutil::write_atomic(path, &bytes)?;
+            (value, RecoverySource::Backup)
+        }
+        None => {
+            let value = T::default();
+            let bytes = serde_json::to_vec_pretty(&value).map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+            fsutil::write_atomic(path, &bytes)?;
+            (value, RecoverySource::Default)
+        }
+    };
+    sync_dir(path);
+    let _ = std::fs::remove_file(&dirty);
+    Ok((value, Some(Recovery { source, corrupt_copy })))
+}
+
+/// Replace a store file: mark it dirty, back up the current content if it's good,
+/// write the new content atomically, then clear the mark
+fn write_blocking<T: DeserializeOwned>(path: &Path, content: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+    }
+    let dirty = dirty_path(path);
+    std::fs::write(&dirty, b"").map_err(|e| format!("Failed to mark {} dirty: {}", path.display(), e))?;
+
+    // A corrupt current file must not replace the last good backup
+    if let Ok(current) = std::fs::read(path) {
+        if parse::<T>(&current).is_some() {
+            fsutil::write_atomic(&backup_path(path), &current)?;
+        }
+    }
+    fsutil::write_atomic(path, content)?;
+    sync_dir(path);
+    std::fs::remove_file(&dirty).map_err(|e| format!("Failed to clear {}: {}", dirty.display(), e))
+}
+
+impl<T> JsonStore<T>
+where
+    T: Serialize + DeserializeOwned + Default + Send + 'static,
+{
+    pub const fn new(name: &'static str) -> Self {
+        JsonStore {
+            name,
+            lock: Mutex::const_new(()),
+            _value: PhantomData,
+        }
+    }
+
+    /// Take the store's lock for a file; hold the guard across a load and save
+    pub async fn lock(&self, app: &tauri::AppHandle, path: PathBuf) -> StoreGuard<'_, T> {
+        StoreGuard {
+            store: self,
+            app: app.clone(),
+            path,
+            _lock: self.lock.lock().await,
+        }
+    }
+
+    /// Read a file of the store (recovering it if needed)
+    pub async fn read(&self, app: &tauri::AppHandle, path: PathBuf) -> Result<T, String> {
+        self.lock(app, path).await.load().await
+    }
+
+    /// Replace a file of the store
+    pub async fn write(&self, app: &tauri::AppHandle, path: PathBuf, value: &T) -> Result<(), String> {
+        self.lock(app, path).await.save(value).await
+    }
+}
+
+impl<T> StoreGuard<'_, T>
+where
+    T: Serialize + DeserializeOwned + Default + Send + 'static,
+{
+    /// The file's value: the default when it was never written, the backup when it's
+    /// unreadable (reported as `store-recovered`), the default again when both are
+    pub async fn load(&self) -> Result<T, String> {
+        let path = self.path.clone();
+        let (value, recovery) = tokio::task::spawn_blocking(move || read_blocking::<T>(&path))
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", self.store.name, e))??;
+        if let Some(recovery) = recovery {
+            let recovered_from = match recovery.source {
+                RecoverySource::Backup => "backup",
+                RecoverySource::Default => "default",
+            };
+            eprintln!(
+                "[mensa] Recovered {} ({}) from {}",
+                self.store.name,
+                self.path.display(),
+                recovered_from
+            );
+            let _ = self.app.emit(
+                "store-recovered",
+                serde_json::json!({
+                    "store": self.store.name,
+                    "path": self.path.to_string_lossy(),
+                    "recovered_from": recovered_from,
+                    "corrupt_copy": recovery.corrupt_copy.map(|p| p.to_string_lossy().to_string()),
+                }),
+            );
+        }
+        Ok(value)
+    }
+
+    /// Delete the file along with its backup (nothing of it should survive, e.g. a history purge)
+    pub async fn remove(&self) -> Result<(), String> {
+        for path in [self.path.clone(), backup_path(&self.path), dirty_path(&self.path)] {
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to delete {}: {}", path.display(), e)),
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn save(&self, value: &T) -> Result<(), String> {
+        let content = serde_json::to_vec_pretty(value)
+            .map_err(|e| format!("Failed to serialize {}: {}", self.store.name, e))?;
+        let path = self.path.clone();
+        tokio::task::spawn_blocking(move || write_blocking::<T>(&path, &content))
+            .await
+            .map_err(|e| format!("Failed to save {}: {}", self.store.name, e))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_value(path: &Path, value: &[u32]) {
+        write_blocking::<Vec<u32>>(path, &serde_json::to_vec_pretty(value).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn truncated_file_is_recovered_from_the_backup_and_repaired() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        write_value(&path, &[1, 2]);
+        write_value(&path, &[1, 2, 3]);
+
+        // A crash mid-write leaves half a file
+        let content = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &content[..content.len() / 2]).unwrap();
+
+        let (value, recovery) = read_blocking::<Vec<u32>>(&path).unwrap();
+        assert_eq!(value, vec![1, 2]);
+        let recovery = recovery.expect("the read should report a recovery");
+        assert_eq!(recovery.source, RecoverySource::Backup);
+        let corrupt = recovery.corrupt_copy.expect("the truncated file should be kept aside");
+        assert_eq!(std::fs::read(&corrupt).unwrap(), &content[..content.len() / 2]);
+
+        // The main file is whole again, so the next read needs no recovery
+        assert_eq!(serde_json::from_slice::<Vec<u32>>(&std::fs::read(&path).unwrap()).unwrap(), vec![1, 2]);
+        let (value, recovery) = read_blocking::<Vec<u32>>(&path).unwrap();
+        assert_eq!(value, vec![1, 2]);
+        assert!(recovery.is_none());
+    }
+
+    #[test]
+    fn unreadable_file_without_a_backup_resets_to_the_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        std::fs::write(&path, b"{not json").unwrap();
+
+        let (value, recovery) = read_blocking::<Vec<u32>>(&path).unwrap();
+        assert!(value.is_empty());
+        let recovery = recovery.unwrap();
+        assert_eq!(recovery.source, RecoverySource::Default);
+        assert!(recovery.corrupt_copy.is_some_and(|p| p.exists()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), "[]");
+    }
+
+    #[test]
+    fn interrupted_write_clears_its_marker_and_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        write_value(&path, &[7]);
+        std::fs::write(dirty_path(&path), b"").unwrap();
+        let temp = dir.path().join(".store.json.mensa-1234.tmp");
+        std::fs::write(&temp, b"[7, 8").unwrap();
+
+        let (value, recovery) = read_blocking::<Vec<u32>>(&path).unwrap();
+        assert_eq!(value, vec![7]);
+        assert!(recovery.is_none());
+        assert!(!dirty_path(&path).exists());
+        assert!(!temp.exists());
+    }
+
+    #[test]
+    fn corrupt_file_never_replaces_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        write_value(&path, &[1]);
+        write_value(&path, &[2]);
+        std::fs::write(&path, b"[2,").unwrap();
+        write_value(&path, &[3]);
+
+        let backup: Vec<u32> = serde_json::from_slice(&std::fs::read(backup_path(&path)).unwrap()).unwrap();
+        assert_eq!(backup, vec![1]);
+        assert!(!dirty_path(&path).exists());
+    }
+
+    #[test]
+    fn missing_file_is_a_fresh_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let (value, recovery) = read_blocking::<Vec<u32>>(&path).unwrap();
+        assert!(value.is_empty());
+        assert!(recovery.is_none());
+        assert!(!path.exists());
+    }
+}
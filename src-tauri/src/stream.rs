@@ -0,0 +1,508 @@
+// mensa - Stream Module
+// Typed view of the JSON lines emitted by claude-query.mjs
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Tools whose input names the file they modify
+const FILE_EDIT_TOOLS: &[(&str, &str)] = &[
+    ("Edit", "file_path"),
+    ("Write", "file_path"),
+    ("MultiEdit", "file_path"),
+    ("NotebookEdit", "notebook_path"),
+];
+
+/// Input field naming what a tool works on (file, command, pattern, URL, ...)
+const SUBJECT_FIELDS: &[(&str, &str)] = &[
+    ("Bash", "command"),
+    ("Read", "file_path"),
+    ("Write", "file_path"),
+    ("Edit", "file_path"),
+    ("MultiEdit", "file_path"),
+    ("NotebookEdit", "notebook_path"),
+    ("Glob", "pattern"),
+    ("Grep", "pattern"),
+    ("WebFetch", "url"),
+    ("WebSearch", "query"),
+];
+
+/// Shell commands whose operands are files they create, modify or delete
+const MUTATING_COMMANDS: &[&str] = &["rm", "mv", "touch", "mkdir", "rmdir", "tee", "truncate"];
+
+/// One line of the agent stream. Only the message types mensa inspects are typed;
+/// everything else is kept as `Other` and forwarded untouched.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamMessage {
+    Assistant { message: MessageBody },
+    User { message: MessageBody },
+    System {
+        #[serde(default)]
+        session_id: Option<String>,
+        #[serde(default)]
+        subtype: Option<String>,
+        /// Remaining fields, read by subtype (e.g. hook responses)
+        #[serde(flatten)]
+        fields: Map<String, Value>,
+    },
+    Result {
+        #[serde(default)]
+        session_id: Option<String>,
+        #[serde(default)]
+        is_error: bool,
+        /// Cost of the whole run as computed by the SDK
+        #[serde(default)]
+        total_cost_usd: Option<f64>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessageBody {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+    #[serde(default)]
+    pub content: Vec<ContentBlock>,
+}
+
+/// Token counts reported on an assistant message
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    ToolUse {
+        name: String,
+        #[serde(default)]
+        input: Value,
+    },
+    ToolResult {
+        #[serde(default)]
+        tool_use_id: Option<String>,
+        #[serde(default)]
+        content: Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+/// Parse one stream line; lines that aren't recognisable JSON messages yield None
+pub fn parse_line(line: &str) -> Option<StreamMessage> {
+    serde_json::from_str(line).ok()
+}
+
+/// What a tool use works on: the file, command, pattern or URL of the known tools
+pub fn tool_subject<'a>(tool: &str, input: &'a Value) -> Option<&'a str> {
+    let field = SUBJECT_FIELDS.iter().find(|(name, _)| *name == tool)?.1;
+    input.get(field).and_then(Value::as_str)
+}
+
+impl StreamMessage {
+    /// (tool name, input) for every tool_use block in an assistant message
+    pub fn tool_uses(&self) -> impl Iterator<Item = (&str, &Value)> {
+        let blocks: &[ContentBlock] = match self {
+            StreamMessage::Assistant { message } => &message.content,
+            _ => &[],
+        };
+        blocks.iter().filter_map(|block| match block {
+            ContentBlock::ToolUse { name, input } => Some((name.as_str(), input)),
+            _ => None,
+        })
+    }
+
+    /// Session id announced by the system init or result message
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            StreamMessage::System { session_id, .. } | StreamMessage::Result { session_id, .. } => session_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Assistant message body when it reports token usage
+    pub fn assistant_usage(&self) -> Option<&MessageBody> {
+        match self {
+            StreamMessage::Assistant { message } if message.usage.is_some() => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Run cost reported by the result message
+    pub fn result_cost(&self) -> Option<f64> {
+        match self {
+            StreamMessage::Result { total_cost_usd, .. } => *total_cost_usd,
+            _ => None,
+        }
+    }
+
+    /// Whether this is a result message reporting a failed run
+    pub fn is_error_result(&self) -> bool {
+        matches!(self, StreamMessage::Result { is_error: true, .. })
+    }
+
+    /// Hook activity reported by this message: hook system messages, and tool results
+    /// whose text says a hook blocked or failed the call
+    pub fn hook_events(&self) -> Vec<HookEvent> {
+        match self {
+            StreamMessage::System {
+                subtype: Some(subtype),
+                fields,
+                ..
+            } if subtype.contains("hook") => vec![hook_event_from_fields(subtype, fields)],
+            StreamMessage::User { message } => message
+                .content
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::ToolResult { tool_use_id, content } => hook_event_from_tool_result(tool_use_id.as_deref(), content),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+// ============================================================================
+// Hook Events
+// ============================================================================
+
+/// One hook execution (or failure) seen in the stream or a transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookEvent {
+    /// Hook event name, e.g. "PostToolUse" ("unknown" if the payload didn't say)
+    pub hook: String,
+    pub tool: Option<String>,
+    pub tool_use_id: Option<String>,
+    /// "started" | "ran" | "blocked" | "error" | "unknown"
+    pub decision: String,
+    pub output: Option<String>,
+    /// The original payload when its shape wasn't recognised
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<Value>,
+}
+
+fn field_str<'a>(fields: &'a Map<String, Value>, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().find_map(|key| fields.get(*key).and_then(|v| v.as_str())).filter(|s| !s.is_empty())
+}
+
+/// Read a hook payload from either the SDK stream (snake_case) or a transcript (camelCase).
+/// `kind` is the system subtype or attachment type, e.g. "hook_response" or "hook_blocking_error".
+pub fn hook_event_from_fields(kind: &str, fields: &Map<String, Value>) -> HookEvent {
+    let hook_name = field_str(fields, &["hook_name", "hookName"]);
+    let (name_event, name_tool) = match hook_name.map(|n| n.split_once(':')) {
+        Some(Some((event, tool))) => (Some(event), Some(tool)),
+        _ => (hook_name, None),
+    };
+    let hook = field_str(fields, &["hook_event", "hookEvent", "hook_event_name"])
+        .or(name_event)
+        .map(|s| s.to_string());
+    let tool = field_str(fields, &["tool_name", "toolName"]).or(name_tool).map(|s| s.to_string());
+    let tool_use_id = field_str(fields, &["tool_use_id", "toolUseID", "toolUseId"]).map(|s| s.to_string());
+
+    let exit_code = fields
+        .get("exit_code")
+        .or_else(|| fields.get("exitCode"))
+        .and_then(|v| v.as_i64());
+    let explicit = field_str(fields, &["decision", "outcome"]);
+    let decision = if kind.contains("started") {
+        "started"
+    } else if kind.contains("blocking") || matches!(explicit, Some("block" | "blocked")) || exit_code == Some(2) {
+        "blocked"
+    } else if kind.contains("error") || matches!(explicit, Some("error" | "failed")) || exit_code.is_some_and(|c| c != 0) {
+        "error"
+    } else if kind.contains("success") || matches!(explicit, Some("success" | "approve")) || exit_code == Some(0) {
+        "ran"
+    } else {
+        "unknown"
+    };
+
+    let blocking = fields
+        .get("blockingError")
+        .and_then(|v| v.get("blockingError").or(Some(v)))
+        .and_then(|v| v.as_str());
+    let output: Vec<&str> = [
+        field_str(fields, &["output", "content"]),
+        blocking,
+        field_str(fields, &["stdout"]),
+        field_str(fields, &["stderr"]),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let recognised = hook.is_some() && decision != "unknown";
+    HookEvent {
+        hook: hook.unwrap_or_else(|| "unknown".to_string()),
+        tool,
+        tool_use_id,
+        decision: decision.to_string(),
+        output: (!output.is_empty()).then(|| output.join("\n")),
+        raw: (!recognised).then(|| Value::Object(fields.clone())),
+    }
+}
+
+/// Hook entry in a session transcript: a hook system message or a hook attachment
+pub fn hook_event_from_transcript(entry: &Value) -> Option<HookEvent> {
+    let (kind, fields) = match entry.get("type")?.as_str()? {
+        "system" => (entry.get("subtype")?.as_str()?, entry.as_object()?),
+        "attachment" => {
+            let attachment = entry.get("attachment")?;
+            (attachment.get("type")?.as_str()?, attachment.as_object()?)
+        }
+        _ => return None,
+    };
+    if !kind.contains("hook") {
+        return None;
+    }
+    let mut event = hook_event_from_fields(kind, fields);
+    // Transcripts may carry the tool use id on the entry rather than the payload
+    if event.tool_use_id.is_none() {
+        event.tool_use_id = entry.get("toolUseID").and_then(|v| v.as_str()).map(|s| s.to_string());
+    }
+    Some(event)
+}
+
+/// Tool results that were replaced by a hook's message, e.g.
+/// "PostToolUse:Edit hook blocking error from command: ..."
+pub fn hook_event_from_tool_result(tool_use_id: Option<&str>, content: &Value) -> Option<HookEvent> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        Regex::new(r"(?m)^(PreToolUse|PostToolUseFailure|PostToolUse|Stop|SubagentStop|UserPromptSubmit):?(\w*)\s+hook\s+(blocking error|error)")
+            .expect("valid hook pattern")
+    });
+
+    let text = match content {
+        Value::String(s) => s.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    let caps = pattern.captures(&text)?;
+    Some(HookEvent {
+        hook: caps[1].to_string(),
+        tool: Some(caps[2].to_string()).filter(|t| !t.is_empty()),
+        tool_use_id: tool_use_id.map(|s| s.to_string()),
+        decision: if &caps[3] == "blocking error" { "blocked" } else { "error" }.to_string(),
+        output: Some(text),
+        raw: None,
+    })
+}
+
+// ============================================================================
+// Touched File Detection
+// ============================================================================
+
+/// Resolve `.` and `..` without touching the filesystem
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Express a path relative to the working directory when it lies inside it.
+/// Paths outside the workspace stay absolute.
+pub fn normalize_path(working_dir: &Path, raw: &str) -> Option<PathBuf> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    let absolute = normalize_lexically(&working_dir.join(raw));
+    let root = normalize_lexically(working_dir);
+    match absolute.strip_prefix(&root) {
+        Ok(relative) if relative.as_os_str().is_empty() => None,
+        Ok(relative) => Some(relative.to_path_buf()),
+        Err(_) => Some(absolute),
+    }
+}
+
+/// Split a command line into words, honouring simple quoting.
+/// Returns None for anything we can't read literally (substitutions, globs, variables).
+fn shell_words(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for q in chars.by_ref() {
+                    if q == '\'' {
+                        break;
+                    }
+                    current.push(q);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(q) = chars.next() {
+                    match q {
+                        '"' => break,
+                        '$' | '`' => return None,
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                current.push(escaped);
+                            }
+                        }
+                        _ => current.push(q),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            '$' | '`' | '*' | '?' | '[' | '{' | '(' | ')' => return None,
+            ';' | '|' | '&' | '\n' => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+                // Collapse && and || into a single separator
+                if matches!(c, '|' | '&') && chars.peek() == Some(&c) {
+                    chars.next();
+                }
+                words.push(";".to_string());
+            }
+            '>' => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+                if chars.peek() == Some(&'>') {
+                    chars.next();
+                }
+                // 2>&1 style redirects target a descriptor, not a file
+                if chars.peek() == Some(&'&') {
+                    chars.next();
+                    continue;
+                }
+                words.push(">".to_string());
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            _ => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    Some(words)
+}
+
+/// Best-effort list of files a Bash command writes to. Commands that can't be
+/// read literally contribute nothing rather than a guess.
+fn bash_touched_paths(command: &str) -> Vec<String> {
+    let words = match shell_words(command) {
+        Some(words) => words,
+        None => return Vec::new(),
+    };
+
+    let mut paths = Vec::new();
+    for simple in words.split(|w| w == ";") {
+        let mut operands: Vec<&str> = Vec::new();
+        let mut iter = simple.iter().peekable();
+        let program = iter.next().map(|w| w.as_str());
+
+        while let Some(word) = iter.next() {
+            if word == ">" {
+                if let Some(target) = iter.next() {
+                    if target != "/dev/null" {
+                        paths.push(target.clone());
+                    }
+                }
+                continue;
+            }
+            if word.starts_with('-') {
+                continue;
+            }
+            operands.push(word);
+        }
+
+        // Drop a descriptor number that preceded a redirect (`cmd 2> file`)
+        operands.retain(|w| w.parse::<u32>().is_err());
+
+        match program {
+            Some("cp") if operands.len() >= 2 => {
+                paths.push(operands[operands.len() - 1].to_string());
+            }
+            Some("sed") if simple.iter().any(|w| w == "-i" || w.starts_with("-i")) => {
+                // First operand is the script, the rest are edited in place
+                paths.extend(operands.iter().skip(1).map(|w| w.to_string()));
+            }
+            Some(program) if MUTATING_COMMANDS.contains(&program) => {
+                paths.extend(operands.iter().map(|w| w.to_string()));
+            }
+            _ => {}
+        }
+    }
+    paths
+}
+
+/// Files a single tool call is expected to modify, relative to the working directory
+pub fn touched_paths(working_dir: &Path, tool: &str, input: &Value) -> Vec<PathBuf> {
+    let raw_paths: Vec<String> = if let Some((_, key)) = FILE_EDIT_TOOLS.iter().find(|(name, _)| *name == tool) {
+        input
+            .get(*key)
+            .and_then(|v| v.as_str())
+            .map(|p| vec![p.to_string()])
+            .unwrap_or_default()
+    } else if tool == "Bash" {
+        input
+            .get("command")
+            .and_then(|v| v.as_str())
+            .map(bash_touched_paths)
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    raw_paths
+        .iter()
+        .filter_map(|raw| normalize_path(working_dir, raw))
+        .collect()
+}
@@ -0,0 +1,307 @@
+// mensa - Tasks Module
+// Registry of long-lived background tasks, so they can be listed and stopped together at exit
+
+use crate::cancel::CancellationToken;
+use crate::AppState;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::async_runtime::JoinHandle;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// How often `shutdown_all` checks whether the cancelled tasks have ended
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+struct TaskEntry {
+    name: String,
+    started_at: Instant,
+    started_at_secs: i64,
+    token: CancellationToken,
+    /// Set right after spawning; None only in that instant
+    abort: Option<tokio::task::AbortHandle>,
+}
+
+#[derive(Default)]
+struct RegistryInner {
+    tasks: Mutex<HashMap<u64, TaskEntry>>,
+    next_id: AtomicU64,
+    /// Set by `shutdown_all`; tasks spawned afterwards start out cancelled
+    closed: AtomicBool,
+}
+
+/// Background tasks that are still running, by id. Clones share the registry.
+#[derive(Clone, Default)]
+pub struct TaskRegistry {
+    inner: Arc<RegistryInner>,
+}
+
+/// A task started through the registry
+pub struct SpawnedTask<T> {
+    /// Cancel to ask the task to stop; it decides when it's safe to
+    pub token: CancellationToken,
+    pub handle: JoinHandle<T>,
+}
+
+/// Removes a task's entry when its future ends, completes or is aborted
+struct Deregister {
+    inner: Arc<RegistryInner>,
+    id: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackgroundTaskInfo {
+    pub id: u64,
+    pub name: String,
+    pub started_at: i64,
+    pub uptime_ms: u64,
+    /// Previous form of `uptime_ms`
+    #[cfg(feature = "legacy_fields")]
+    pub uptime_secs: f64,
+    /// False once the task's future ended (its entry goes away right after)
+    pub alive: bool,
+    /// Asked to stop and not yet done
+    pub cancelled: bool,
+}
+
+/// What `shutdown_all` had to do
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShutdownReport {
+    /// Tasks that stopped on their own after being cancelled
+    pub stopped: Vec<String>,
+    /// Tasks still running at the deadline, aborted
+    pub aborted: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl Drop for Deregister {
+    fn drop(&mut self) {
+        self.inner
+            .tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&self.id);
+    }
+}
+
+impl TaskRegistry {
+    /// Spawn a named task. It gets a token to watch (cancelled on shutdown or through the
+    /// returned `SpawnedTask`) and is listed until its future ends.
+    pub fn spawn<T, F, Fut>(&self, name: impl Into<String>, task: F) -> SpawnedTask<T>
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = CancellationToken::default();
+        if self.inner.closed.load(Ordering::SeqCst) {
+            token.cancel();
+        }
+        self.inner
+            .tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(id, TaskEntry {
+                name: name.into(),
+                started_at: Instant::now(),
+                started_at_secs: crate::history::now_secs(),
+                token: token.clone(),
+                abort: None,
+            });
+
+        let guard = Deregister {
+            inner: self.inner.clone(),
+            id,
+        };
+        let future = task(token.clone());
+        let handle = tauri::async_runtime::spawn(async move {
+            let _guard = guard;
+            future.await
+        });
+        // A task that already ended has removed its entry; nothing to fill in then
+        if let Some(entry) = self
+            .inner
+            .tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get_mut(&id)
+        {
+            entry.abort = Some(handle.inner().abort_handle());
+        }
+        SpawnedTask { token, handle }
+    }
+
+    pub fn list(&self) -> Vec<BackgroundTaskInfo> {
+        let tasks = self.inner.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut infos: Vec<BackgroundTaskInfo> = tasks
+            .iter()
+            .map(|(id, entry)| BackgroundTaskInfo {
+                id: *id,
+                name: entry.name.clone(),
+                started_at: entry.started_at_secs,
+                uptime_ms: entry.started_at.elapsed().as_millis() as u64,
+                #[cfg(feature = "legacy_fields")]
+                uptime_secs: entry.started_at.elapsed().as_secs_f64(),
+                alive: entry.abort.as_ref().is_none_or(|abort| !abort.is_finished()),
+                cancelled: entry.token.is_cancelled(),
+            })
+            .collect();
+        infos.sort_by_key(|info| info.id);
+        infos
+    }
+
+    /// Cancel every task, wait until `deadline` for them to end, then abort the rest.
+    /// Tasks spawned afterwards start out cancelled.
+    pub async fn shutdown_all(&self, deadline: Duration) -> ShutdownReport {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        let pending: Vec<(String, Option<tokio::task::AbortHandle>)> = {
+            let tasks = self.inner.tasks.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            tasks
+                .values()
+                .map(|entry| {
+                    entry.token.cancel();
+                    (entry.name.clone(), entry.abort.clone())
+                })
+                .collect()
+        };
+
+        let until = Instant::now() + deadline;
+        while Instant::now() < until
+            && pending
+                .iter()
+                .any(|(_, abort)| abort.as_ref().is_some_and(|a| !a.is_finished()))
+        {
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+
+        let mut report = ShutdownReport::default();
+        for (name, abort) in pending {
+            match abort {
+                Some(abort) if !abort.is_finished() => {
+                    abort.abort();
+                    report.aborted.push(name);
+                }
+                _ => report.stopped.push(name),
+            }
+        }
+        report
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Background tasks still running, oldest first, for the debug panel
+#[tauri::command]
+pub async fn list_background_tasks(state: State<'_, AppState>) -> Result<Vec<BackgroundTaskInfo>, String> {
+    Ok(state.tasks.list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sets its flag when dropped, i.e. when the task holding it ends or is aborted
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn names(mut names: Vec<String>) -> Vec<String> {
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn no_task_outlives_the_shutdown_deadline() {
+        let registry = TaskRegistry::default();
+        let mut handles = Vec::new();
+        let mut dropped = Vec::new();
+
+        // Cooperative tasks stop (after a little cleanup) once cancelled
+        for i in 0..3 {
+            let flag = Arc::new(AtomicBool::new(false));
+            dropped.push(flag.clone());
+            handles.push(registry.spawn(format!("watcher-{}", i), move |token| async move {
+                let _flag = DropFlag(flag);
+                token.cancelled().await;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }).handle);
+        }
+        // Stubborn ones never look at their token
+        for i in 0..2 {
+            let flag = Arc::new(AtomicBool::new(false));
+            dropped.push(flag.clone());
+            handles.push(registry.spawn(format!("reader-{}", i), move |_token| async move {
+                let _flag = DropFlag(flag);
+                loop {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }).handle);
+        }
+        assert_eq!(registry.list().len(), 5);
+
+        let deadline = Duration::from_millis(300);
+        let started = Instant::now();
+        let report = registry.shutdown_all(deadline).await;
+        assert!(started.elapsed() < deadline + Duration::from_secs(1), "shutdown took {:?}", started.elapsed());
+        assert_eq!(names(report.stopped), ["watcher-0", "watcher-1", "watcher-2"]);
+        assert_eq!(names(report.aborted), ["reader-0", "reader-1"]);
+
+        // Aborted tasks end at their next await; none is still running shortly after
+        for handle in handles {
+            let _ = tokio::time::timeout(Duration::from_secs(1), handle).await.expect("a task outlived shutdown");
+        }
+        assert!(dropped.iter().all(|flag| flag.load(Ordering::SeqCst)));
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn tasks_spawned_after_shutdown_start_cancelled() {
+        let registry = TaskRegistry::default();
+        registry.shutdown_all(Duration::from_millis(10)).await;
+
+        let late = registry.spawn("late", |token| async move {
+            token.cancelled().await;
+            "stopped"
+        });
+        assert!(late.token.is_cancelled());
+        assert_eq!(late.handle.await.unwrap(), "stopped");
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn listing_shows_running_and_cancelled_tasks_in_start_order() {
+        let registry = TaskRegistry::default();
+        let first = registry.spawn("first", |token| async move { token.cancelled().await });
+        let second = registry.spawn("second", |token| async move { token.cancelled().await });
+        let listed: Vec<(String, bool, bool)> = registry.list().into_iter().map(|t| (t.name, t.alive, t.cancelled)).collect();
+        assert_eq!(listed, [("first".to_string(), true, false), ("second".to_string(), true, false)]);
+
+        first.token.cancel();
+        first.handle.await.unwrap();
+        let listed: Vec<String> = registry.list().into_iter().map(|t| t.name).collect();
+        assert_eq!(listed, ["second"]);
+        assert!(registry.list()[0].alive);
+
+        second.token.cancel();
+        second.handle.await.unwrap();
+        assert!(registry.list().is_empty());
+    }
+}
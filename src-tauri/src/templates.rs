@@ -0,0 +1,535 @@
+// mensa - Prompt Templates Module
+// Shared prompt templates with typed {{variables}}, stored globally or per workspace and expanded here
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use tauri::{Emitter, Manager};
+
+use crate::store::JsonStore;
+use crate::{forge, git};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TemplateScope {
+    Global,
+    Workspace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableKind {
+    String,
+    /// Must name an existing local or remote branch
+    Branch,
+    /// Must be an existing path inside the workspace
+    File,
+    /// Must be a GitHub pull request URL
+    PrUrl,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateVariable {
+    pub name: String,
+    pub kind: VariableKind,
+    #[serde(default = "default_required")]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub name: String,
+    /// Text with {{variable}} placeholders; `\{{` is a literal `{{`
+    pub body: String,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    pub scope: TemplateScope,
+}
+
+/// A template reference passed to `query_claude` instead of a raw prompt
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateRef {
+    pub name: String,
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandedPrompt {
+    pub prompt: String,
+    /// Placeholders left in the prompt because nothing supplied a value
+    pub unresolved: Vec<String>,
+}
+
+/// Placeholders filled from backend state when the caller doesn't pass them
+const SPECIAL_VARIABLES: &[&str] = &["current_branch", "default_branch", "changed_files", "latest_session"];
+
+static TEMPLATES_FILE: JsonStore<TemplatesFile> = JsonStore::new("prompt templates");
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct TemplatesFile {
+    global: Vec<PromptTemplate>,
+    workspaces: HashMap<String, Vec<PromptTemplate>>,
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn default_required() -> bool {
+    true
+}
+
+pub fn templates_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("prompt-templates.json"))
+}
+
+async fn load_file(app: &tauri::AppHandle) -> Result<TemplatesFile, String> {
+    TEMPLATES_FILE.read(app, templates_path(app)?).await
+}
+
+fn notify_changed(app: &tauri::AppHandle, working_dir: Option<&str>) {
+    let _ = app.emit("templates-changed", serde_json::json!({ "working_dir": working_dir }));
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Split a template body into text and placeholder names; `\{{` stays literal text
+fn segments(body: &str) -> Vec<Segment<'_>> {
+    let mut out = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 && rest.as_bytes()[start - 1] == b'\\' {
+            out.push(Segment::Text(&rest[..start - 1]));
+            out.push(Segment::Text("{{"));
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let end = match rest[start + 2..].find("}}") {
+            Some(end) => start + 2 + end,
+            None => break,
+        };
+        let name = rest[start + 2..end].trim();
+        out.push(Segment::Text(&rest[..start]));
+        if is_identifier(name) {
+            out.push(Segment::Placeholder(name));
+        } else {
+            out.push(Segment::Text(&rest[start..end + 2]));
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push(Segment::Text(rest));
+    out
+}
+
+/// Substitute placeholders from `values`; unknown ones are kept verbatim and reported
+fn substitute(body: &str, values: &HashMap<String, String>) -> ExpandedPrompt {
+    let mut prompt = String::with_capacity(body.len());
+    let mut unresolved: Vec<String> = Vec::new();
+    for segment in segments(body) {
+        match segment {
+            Segment::Text(text) => prompt.push_str(text),
+            Segment::Placeholder(name) => match values.get(name) {
+                Some(value) => prompt.push_str(value),
+                None => {
+                    prompt.push_str(&format!("{{{{{}}}}}", name));
+                    if !unresolved.iter().any(|u| u == name) {
+                        unresolved.push(name.to_string());
+                    }
+                }
+            },
+        }
+    }
+    ExpandedPrompt { prompt, unresolved }
+}
+
+fn validate_template(template: &PromptTemplate) -> Result<(), String> {
+    if template.name.trim().is_empty() {
+        return Err("Template name cannot be empty".to_string());
+    }
+    for (i, variable) in template.variables.iter().enumerate() {
+        if !is_identifier(&variable.name) {
+            return Err(format!("Invalid variable name '{}': use letters, digits and _", variable.name));
+        }
+        if template.variables[..i].iter().any(|v| v.name == variable.name) {
+            return Err(format!("Variable '{}' is declared twice", variable.name));
+        }
+    }
+    Ok(())
+}
+
+/// Workspace templates shadow global ones with the same name
+fn effective_templates(file: &TemplatesFile, working_dir: Option<&str>) -> Vec<PromptTemplate> {
+    let mut templates = file.global.clone();
+    let workspace = working_dir.and_then(|dir| file.workspaces.get(dir));
+    for template in workspace.into_iter().flatten() {
+        match templates.iter_mut().find(|t| t.name == template.name) {
+            Some(existing) => *existing = template.clone(),
+            None => templates.push(template.clone()),
+        }
+    }
+    templates
+}
+
+/// Check a supplied value against its declared kind
+fn validate_value(working_dir: &str, variable: &TemplateVariable, value: &str) -> Result<(), String> {
+    let invalid = |reason: String| format!("Invalid value for '{}': {}", variable.name, reason);
+    match variable.kind {
+        VariableKind::String => Ok(()),
+        VariableKind::PrUrl => forge::parse_review_url(value).map(|_| ()).map_err(invalid),
+        VariableKind::Branch => {
+            let repo = git::open_repo(working_dir)?;
+            let exists = repo.find_branch(value, git2::BranchType::Local).is_ok()
+                || repo.find_branch(value, git2::BranchType::Remote).is_ok();
+            if exists {
+                Ok(())
+            } else {
+                Err(invalid(format!("no branch named '{}'", value)))
+            }
+        }
+        VariableKind::File => {
+            let path = Path::new(value);
+            if !path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+                return Err(invalid(format!("'{}' is not a path inside the workspace", value)));
+            }
+            if Path::new(working_dir).join(path).exists() {
+                Ok(())
+            } else {
+                Err(invalid(format!("'{}' does not exist", value)))
+            }
+        }
+    }
+}
+
+/// Value of a special variable from the repository and session state
+async fn resolve_special(working_dir: &str, name: &str) -> Option<String> {
+    match name {
+        "current_branch" => {
+            let repo = git::open_repo(working_dir).ok()?;
+            let head = repo.head().ok()?;
+            head.shorthand().map(|s| s.to_string())
+        }
+        "default_branch" => {
+            let repo = git::open_repo(working_dir).ok()?;
+            git::remote_default_branch(&repo).or_else(|| {
+                ["main", "master"]
+                    .into_iter()
+                    .find(|b| repo.find_branch(b, git2::BranchType::Local).is_ok())
+                    .map(|b| b.to_string())
+            })
+        }
+        "changed_files" => {
+            let repo = git::open_repo(working_dir).ok()?;
+            let mut opts = git2::StatusOptions::new();
+            opts.include_untracked(true).recurse_untracked_dirs(true);
+            let statuses = repo.statuses(Some(&mut opts)).ok()?;
+            let paths: Vec<String> = statuses.iter().filter_map(|e| e.path().map(|p| p.to_string())).collect();
+            Some(paths.join("\n"))
+        }
+        "latest_session" => crate::session_entries(working_dir.to_string(), None, &Default::default())
+            .await
+            .ok()?
+            .into_iter()
+            .next()
+            .map(|s| s.session_id),
+        _ => None,
+    }
+}
+
+/// Expand a named template: caller values, then backend state for special names, then defaults.
+/// Missing required variables are an error; undeclared placeholders are reported as unresolved.
+pub async fn expand(
+    app: &tauri::AppHandle,
+    working_dir: &str,
+    name: &str,
+    vars: HashMap<String, String>,
+) -> Result<ExpandedPrompt, String> {
+    expand_from(&load_file(app).await?, working_dir, name, vars).await
+}
+
+async fn expand_from(
+    file: &TemplatesFile,
+    working_dir: &str,
+    name: &str,
+    vars: HashMap<String, String>,
+) -> Result<ExpandedPrompt, String> {
+    let template = effective_templates(file, Some(working_dir))
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| format!("Prompt template not found: {}", name))?;
+
+    let mut values = vars;
+    for placeholder in segments(&template.body) {
+        if let Segment::Placeholder(name) = placeholder {
+            if !values.contains_key(name) && SPECIAL_VARIABLES.contains(&name) {
+                if let Some(value) = resolve_special(working_dir, name).await {
+                    values.insert(name.to_string(), value);
+                }
+            }
+        }
+    }
+
+    let mut missing = Vec::new();
+    for variable in &template.variables {
+        match values.get(&variable.name) {
+            Some(value) => validate_value(working_dir, variable, value)?,
+            None => match &variable.default {
+                Some(default) => {
+                    values.insert(variable.name.clone(), default.clone());
+                }
+                None if variable.required => missing.push(variable.name.clone()),
+                None => {
+                    values.insert(variable.name.clone(), String::new());
+                }
+            },
+        }
+    }
+    if !missing.is_empty() {
+        return Err(format!("Missing required template variables: {}", missing.join(", ")));
+    }
+
+    Ok(substitute(&template.body, &values))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// List templates visible in a workspace (workspace templates shadow global ones by name)
+#[tauri::command]
+pub async fn list_prompt_templates(
+    app: tauri::AppHandle,
+    working_dir: Option<String>,
+) -> Result<Vec<PromptTemplate>, String> {
+    let file = load_file(&app).await?;
+    Ok(effective_templates(&file, working_dir.as_deref()))
+}
+
+/// Create or replace a global or workspace template
+#[tauri::command]
+pub async fn save_prompt_template(
+    app: tauri::AppHandle,
+    template: PromptTemplate,
+    working_dir: Option<String>,
+) -> Result<PromptTemplate, String> {
+    validate_template(&template)?;
+    let working_dir = match (template.scope, working_dir) {
+        (TemplateScope::Workspace, None) => return Err("Workspace templates need a working directory".to_string()),
+        (TemplateScope::Workspace, dir) => dir,
+        (TemplateScope::Global, _) => None,
+    };
+
+    let guard = TEMPLATES_FILE.lock(&app, templates_path(&app)?).await;
+    let mut file = guard.load().await?;
+    let list = match working_dir.as_deref() {
+        Some(dir) => file.workspaces.entry(dir.to_string()).or_default(),
+        None => &mut file.global,
+    };
+    match list.iter_mut().find(|t| t.name == template.name) {
+        Some(existing) => *existing = template.clone(),
+        None => list.push(template.clone()),
+    }
+    guard.save(&file).await?;
+
+    notify_changed(&app, working_dir.as_deref());
+    Ok(template)
+}
+
+/// Delete a global or workspace template
+#[tauri::command]
+pub async fn delete_prompt_template(
+    app: tauri::AppHandle,
+    name: String,
+    scope: TemplateScope,
+    working_dir: Option<String>,
+) -> Result<bool, String> {
+    let guard = TEMPLATES_FILE.lock(&app, templates_path(&app)?).await;
+    let mut file = guard.load().await?;
+    let list = match (scope, working_dir.as_deref()) {
+        (TemplateScope::Workspace, None) => return Err("Workspace templates need a working directory".to_string()),
+        (TemplateScope::Workspace, Some(dir)) => match file.workspaces.get_mut(dir) {
+            Some(list) => list,
+            None => return Ok(false),
+        },
+        (TemplateScope::Global, _) => &mut file.global,
+    };
+    let before = list.len();
+    list.retain(|t| t.name != name);
+    if list.len() == before {
+        return Ok(false);
+    }
+    guard.save(&file).await?;
+
+    notify_changed(&app, working_dir.as_deref());
+    Ok(true)
+}
+
+/// Expand a template into the final prompt, reporting placeholders nothing could fill
+#[tauri::command]
+pub async fn expand_prompt_template(
+    app: tauri::AppHandle,
+    working_dir: String,
+    name: String,
+    vars: Option<HashMap<String, String>>,
+) -> Result<ExpandedPrompt, String> {
+    expand(&app, &working_dir, &name, vars.unwrap_or_default()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(name: &str, body: &str, scope: TemplateScope, variables: Vec<TemplateVariable>) -> PromptTemplate {
+        PromptTemplate {
+            name: name.to_string(),
+            body: body.to_string(),
+            variables,
+            scope,
+        }
+    }
+
+    fn variable(name: &str, required: bool, default: Option<&str>) -> TemplateVariable {
+        TemplateVariable {
+            name: name.to_string(),
+            kind: VariableKind::String,
+            required,
+            default: default.map(String::from),
+            description: None,
+        }
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn file_with(global: Vec<PromptTemplate>, workspace: &str, local: Vec<PromptTemplate>) -> TemplatesFile {
+        TemplatesFile {
+            global,
+            workspaces: HashMap::from([(workspace.to_string(), local)]),
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_required_variables_are_all_named() {
+        let dir = tempfile::tempdir().unwrap();
+        let working_dir = dir.path().to_string_lossy().to_string();
+        let variables = vec![
+            variable("target", true, None),
+            variable("reason", true, None),
+            variable("tone", true, Some("friendly")),
+            variable("extra", false, None),
+        ];
+        let body = "Fix {{target}} because {{reason}}, {{tone}}.{{extra}}";
+        let file = file_with(vec![template("fix", body, TemplateScope::Global, variables)], &working_dir, Vec::new());
+
+        let error = expand_from(&file, &working_dir, "fix", HashMap::new()).await.unwrap_err();
+        assert_eq!(error, "Missing required template variables: target, reason");
+
+        // Defaults fill in, optional variables expand to nothing
+        let expanded = expand_from(&file, &working_dir, "fix", vars(&[("target", "the parser"), ("reason", "it panics")]))
+            .await
+            .unwrap();
+        assert_eq!(expanded.prompt, "Fix the parser because it panics, friendly.");
+        assert!(expanded.unresolved.is_empty());
+    }
+
+    #[test]
+    fn escaped_braces_stay_literal() {
+        let values = vars(&[("name", "mensa")]);
+        assert_eq!(substitute(r"Write \{{name}} for {{name}}", &values).prompt, "Write {{name}} for mensa");
+        assert_eq!(substitute(r"\{{name}}", &values).prompt, "{{name}}");
+        // Not identifiers, or never closed: left as written
+        assert_eq!(substitute("{{ not a name }} and {{name", &values).prompt, "{{ not a name }} and {{name");
+        assert_eq!(substitute("{{ name }}", &values).prompt, "mensa");
+    }
+
+    #[test]
+    fn undeclared_placeholders_are_kept_and_reported_once() {
+        let expanded = substitute("{{a}} {{b}} {{a}}", &vars(&[("b", "two")]));
+        assert_eq!(expanded.prompt, "{{a}} two {{a}}");
+        assert_eq!(expanded.unresolved, ["a"]);
+    }
+
+    #[tokio::test]
+    async fn workspace_template_shadows_the_global_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let working_dir = dir.path().to_string_lossy().to_string();
+        let file = file_with(
+            vec![
+                template("review", "global review", TemplateScope::Global, Vec::new()),
+                template("explain", "global explain", TemplateScope::Global, Vec::new()),
+            ],
+            &working_dir,
+            vec![
+                template("review", "workspace review", TemplateScope::Workspace, Vec::new()),
+                template("deploy", "workspace deploy", TemplateScope::Workspace, Vec::new()),
+            ],
+        );
+
+        let visible = effective_templates(&file, Some(&working_dir));
+        let names: Vec<(&str, &str)> = visible.iter().map(|t| (t.name.as_str(), t.body.as_str())).collect();
+        assert_eq!(names, [("review", "workspace review"), ("explain", "global explain"), ("deploy", "workspace deploy")]);
+        assert_eq!(effective_templates(&file, Some("/elsewhere")).len(), 2);
+        assert_eq!(effective_templates(&file, None)[0].body, "global review");
+
+        let expanded = expand_from(&file, &working_dir, "review", HashMap::new()).await.unwrap();
+        assert_eq!(expanded.prompt, "workspace review");
+        assert!(expand_from(&file, "/elsewhere", "deploy", HashMap::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn file_variables_must_stay_inside_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.md"), "").unwrap();
+        let working_dir = dir.path().to_string_lossy().to_string();
+        let mut file_variable = variable("file", true, None);
+        file_variable.kind = VariableKind::File;
+        let file = file_with(
+            vec![template("read", "Read {{file}}", TemplateScope::Global, vec![file_variable])],
+            &working_dir,
+            Vec::new(),
+        );
+
+        let expanded = expand_from(&file, &working_dir, "read", vars(&[("file", "notes.md")])).await.unwrap();
+        assert_eq!(expanded.prompt, "Read notes.md");
+        for outside in ["../notes.md", "/etc/passwd", "missing.md"] {
+            assert!(expand_from(&file, &working_dir, "read", vars(&[("file", outside)])).await.is_err(), "{}", outside);
+        }
+    }
+
+    #[test]
+    fn invalid_templates_are_refused() {
+        let bad_name = template(" ", "body", TemplateScope::Global, Vec::new());
+        assert!(validate_template(&bad_name).is_err());
+        let bad_variable = template("t", "body", TemplateScope::Global, vec![variable("1st", true, None)]);
+        assert!(validate_template(&bad_variable).is_err());
+        let duplicate = template("t", "body", TemplateScope::Global, vec![variable("a", true, None), variable("a", false, None)]);
+        assert!(validate_template(&duplicate).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+// mensa - Test Support Module
+// Fixtures shared by the unit tests: scratch git repositories, fixture files and a recording
+// event sink
+
+use crate::replay::EventSink;
+use git2::{Repository, Signature};
+use serde_json::Value;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tempfile::TempDir;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// One event an EventSink was handed
+#[derive(Debug, Clone)]
+pub struct Delivered {
+    pub event: String,
+    pub payload: Value,
+    pub subscribers: Vec<String>,
+}
+
+/// EventSink that keeps every delivery, in order
+#[derive(Default)]
+pub struct RecordingSink {
+    delivered: Mutex<Vec<Delivered>>,
+}
+
+/// $HOME pointed at a scratch directory (so ~/.claude lives there) until dropped.
+/// Only one test holds it at a time.
+pub struct ScratchHome {
+    pub dir: TempDir,
+    previous: Option<OsString>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+static HOME_LOCK: Mutex<()> = Mutex::new(());
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+impl EventSink for RecordingSink {
+    fn deliver(&self, event: &str, payload: Value, subscribers: &[String]) {
+        self.delivered.lock().unwrap().push(Delivered {
+            event: event.to_string(),
+            payload,
+            subscribers: subscribers.to_vec(),
+        });
+    }
+}
+
+impl RecordingSink {
+    pub fn delivered(&self) -> Vec<Delivered> {
+        self.delivered.lock().unwrap().clone()
+    }
+
+    /// Names of the delivered events, in order
+    pub fn events(&self) -> Vec<String> {
+        self.delivered().into_iter().map(|d| d.event).collect()
+    }
+}
+
+/// A file under tests/fixtures
+pub fn fixture_path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(relative)
+}
+
+pub fn fixture(relative: &str) -> String {
+    std::fs::read_to_string(fixture_path(relative)).unwrap()
+}
+
+/// Write `content` to `relative` under `root`, creating its directories
+pub fn write(root: &Path, relative: &str, content: impl AsRef<[u8]>) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+/// An empty repository in a scratch directory, with a committer identity configured
+pub fn repo() -> (TempDir, Repository) {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    {
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+    }
+    (dir, repo)
+}
+
+/// The scratch repository's working directory as the git commands take it
+pub fn working_dir(dir: &TempDir) -> String {
+    dir.path().to_string_lossy().to_string()
+}
+
+/// Stage everything in the working tree (deletions included) and commit it on HEAD
+pub fn commit_all(repo: &Repository, message: &str) -> git2::Oid {
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+    index.update_all(["*"], None).unwrap();
+    index.write().unwrap();
+    commit_index(repo, message)
+}
+
+/// Commit the index as it is on HEAD
+pub fn commit_index(repo: &Repository, message: &str) -> git2::Oid {
+    let mut index = repo.index().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+        .unwrap()
+}
+
+/// Stage one path as `git add` would
+pub fn stage(repo: &Repository, relative: &str) {
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(relative)).unwrap();
+    index.write().unwrap();
+}
+
+pub fn scratch_home() -> ScratchHome {
+    let lock = HOME_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let dir = tempfile::tempdir().unwrap();
+    let previous = std::env::var_os("HOME");
+    std::env::set_var("HOME", dir.path());
+    ScratchHome {
+        dir,
+        previous,
+        _lock: lock,
+    }
+}
+
+impl ScratchHome {
+    /// ~/.claude under the scratch home
+    pub fn claude(&self) -> crate::paths::ClaudeHome {
+        crate::paths::ClaudeHome::under(self.dir.path())
+    }
+}
+
+impl Drop for ScratchHome {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(home) => std::env::set_var("HOME", home),
+            None => std::env::remove_var("HOME"),
+        }
+    }
+}
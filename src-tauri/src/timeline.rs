@@ -0,0 +1,623 @@
+// mensa - Query Timeline Module
+// One chronological view of a run: tool executions, file changes, commits, hooks, cost and warnings
+
+use crate::{digest, find_session_path, git, history, replay, stream, AppState};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tauri::State;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Entries returned at most; repetitive tool runs are grouped before anything is cut
+const MAX_ENTRIES: usize = 400;
+
+/// Consecutive runs of one tool grouped into a single entry regardless of the cap
+const MIN_GROUPED_RUN: usize = 5;
+
+/// Commits looked at when finding those made during a run
+const MAX_COMMITS_WALKED: usize = 500;
+
+/// Slack around a finished run's recorded window (its times are whole seconds)
+const WINDOW_SLACK_MS: i64 = 2_000;
+
+/// Longest Bash command or other subject kept on a tool entry
+const MAX_SUBJECT_CHARS: usize = 120;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolStatus {
+    Ok,
+    Error,
+    /// No result yet (still running, or the run ended first)
+    Pending,
+}
+
+/// One thing that happened during a run; `kind` tells them apart
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum TimelineEntry {
+    ToolRun {
+        at_ms: i64,
+        tool_use_id: Option<String>,
+        name: String,
+        /// File, command, pattern or URL the tool worked on
+        subject: Option<String>,
+        duration_ms: Option<i64>,
+        status: ToolStatus,
+    },
+    /// Consecutive runs of one tool, e.g. "Read ×14 in src/"
+    ToolRunGroup {
+        at_ms: i64,
+        name: String,
+        count: usize,
+        /// Directory the runs' files share, relative to the workspace
+        scope: Option<String>,
+        label: String,
+        duration_ms: i64,
+        errors: usize,
+    },
+    /// Files first touched since the previous entry of this kind
+    FilesChanged { at_ms: i64, files: Vec<String> },
+    Commit { at_ms: i64, commit: git::GitCommit },
+    /// A tool use the agent wasn't allowed to make
+    PermissionDenied { at_ms: i64, tool: String, tool_use_id: Option<String> },
+    Hook { at_ms: i64, event: stream::HookEvent },
+    /// "warning" and "limit" from the cost ceiling, "final" from the run's result
+    CostCheckpoint {
+        at_ms: i64,
+        cost_usd: f64,
+        max_cost_usd: Option<f64>,
+        reason: String,
+    },
+    /// "contextUsage" | "disk" | "stderr" | "references"
+    Warning { at_ms: i64, source: String, message: String },
+}
+
+/// Counts for the run summary card
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineTotals {
+    pub tool_runs: usize,
+    pub tool_errors: usize,
+    /// Runs per tool name
+    pub tools: BTreeMap<String, usize>,
+    pub files_changed: usize,
+    pub commits: usize,
+    pub permission_denials: usize,
+    pub hooks_run: usize,
+    pub hooks_blocked: usize,
+    pub warnings: usize,
+    pub cost_usd: Option<f64>,
+    pub duration_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryTimeline {
+    pub query_id: String,
+    /// "live" (the query's event buffer), "session" (its transcript) or "history" (the record alone)
+    pub source: String,
+    pub running: bool,
+    pub started_at_ms: Option<i64>,
+    pub finished_at_ms: Option<i64>,
+    pub entries: Vec<TimelineEntry>,
+    /// Entries cut to stay under the cap, after grouping
+    pub omitted: usize,
+    /// Early events were evicted from the buffer, so the start of the run is missing
+    pub partial: bool,
+    /// Counted before grouping and cutting
+    pub totals: TimelineTotals,
+}
+
+/// Collects entries from either the event buffer or a transcript
+#[derive(Default)]
+struct Builder {
+    entries: Vec<TimelineEntry>,
+    /// Index of each tool run's entry by tool_use_id, until its result arrives
+    open_tools: HashMap<String, usize>,
+    files_seen: HashSet<String>,
+    started_at_ms: Option<i64>,
+    finished_at_ms: Option<i64>,
+    final_cost: Option<f64>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn short_subject(subject: &str) -> String {
+    let line = subject.lines().next().unwrap_or_default();
+    match line.char_indices().nth(MAX_SUBJECT_CHARS) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None if line.len() < subject.len() => format!("{}…", line),
+        None => line.to_string(),
+    }
+}
+
+impl Builder {
+    fn note_time(&mut self, at_ms: i64) {
+        self.started_at_ms.get_or_insert(at_ms);
+    }
+
+    /// Tool uses, tool results, permission denials and the final cost of one agent message
+    /// (a stream line and a transcript line have the same shape)
+    fn message(&mut self, at_ms: i64, line: &Value) {
+        let blocks = || {
+            line.get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+        };
+        match line.get("type").and_then(Value::as_str) {
+            Some("assistant") => {
+                for block in blocks().filter(|b| b.get("type").and_then(Value::as_str) == Some("tool_use")) {
+                    let name = block.get("name").and_then(Value::as_str).unwrap_or("unknown").to_string();
+                    let input = block.get("input").unwrap_or(&Value::Null);
+                    let tool_use_id = block.get("id").and_then(Value::as_str).map(String::from);
+                    if let Some(id) = &tool_use_id {
+                        // The SDK repeats a message per content block; one entry per tool use
+                        if self.open_tools.contains_key(id) {
+                            continue;
+                        }
+                        self.open_tools.insert(id.clone(), self.entries.len());
+                    }
+                    self.entries.push(TimelineEntry::ToolRun {
+                        at_ms,
+                        subject: stream::tool_subject(&name, input).map(short_subject),
+                        tool_use_id,
+                        name,
+                        duration_ms: None,
+                        status: ToolStatus::Pending,
+                    });
+                }
+            }
+            Some("user") => {
+                for block in blocks().filter(|b| b.get("type").and_then(Value::as_str) == Some("tool_result")) {
+                    let Some(index) = block
+                        .get("tool_use_id")
+                        .and_then(Value::as_str)
+                        .and_then(|id| self.open_tools.get(id))
+                    else {
+                        continue;
+                    };
+                    if let Some(TimelineEntry::ToolRun {
+                        at_ms: started,
+                        duration_ms,
+                        status,
+                        ..
+                    }) = self.entries.get_mut(*index)
+                    {
+                        if *status == ToolStatus::Pending {
+                            *duration_ms = Some((at_ms - *started).max(0));
+                            *status = match block.get("is_error").and_then(Value::as_bool) {
+                                Some(true) => ToolStatus::Error,
+                                _ => ToolStatus::Ok,
+                            };
+                        }
+                    }
+                }
+            }
+            Some("result") => {
+                for denial in line.get("permission_denials").and_then(Value::as_array).into_iter().flatten() {
+                    self.entries.push(TimelineEntry::PermissionDenied {
+                        at_ms,
+                        tool: denial.get("tool_name").and_then(Value::as_str).unwrap_or("unknown").to_string(),
+                        tool_use_id: denial.get("tool_use_id").and_then(Value::as_str).map(String::from),
+                    });
+                }
+                if let Some(cost) = line.get("total_cost_usd").and_then(Value::as_f64) {
+                    self.final_cost = Some(cost);
+                    self.entries.push(TimelineEntry::CostCheckpoint {
+                        at_ms,
+                        cost_usd: cost,
+                        max_cost_usd: None,
+                        reason: "final".to_string(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn hook(&mut self, at_ms: i64, event: stream::HookEvent) {
+        // "started" only announces the run that follows
+        if event.decision != "started" {
+            self.entries.push(TimelineEntry::Hook { at_ms, event });
+        }
+    }
+
+    fn files(&mut self, at_ms: i64, files: impl IntoIterator<Item = String>) {
+        let mut added: Vec<String> = files.into_iter().filter(|f| self.files_seen.insert(f.clone())).collect();
+        if !added.is_empty() {
+            added.sort();
+            self.entries.push(TimelineEntry::FilesChanged { at_ms, files: added });
+        }
+    }
+
+    fn warning(&mut self, at_ms: i64, source: &str, message: String) {
+        self.entries.push(TimelineEntry::Warning {
+            at_ms,
+            source: source.to_string(),
+            message,
+        });
+    }
+
+    /// One buffered query event
+    fn replay_event(&mut self, event: &replay::ReplayEvent) {
+        let (at_ms, payload) = (event.at_ms, &event.payload);
+        self.note_time(at_ms);
+        let number = |key: &str| payload.get(key).and_then(Value::as_f64);
+        match event.event.as_str() {
+            "claude-stream" => {
+                if let Some(line) = payload
+                    .get("data")
+                    .and_then(Value::as_str)
+                    .and_then(|data| serde_json::from_str::<Value>(data).ok())
+                {
+                    self.message(at_ms, &line);
+                }
+            }
+            "query-files-changed" => {
+                let files = payload.get("files").and_then(Value::as_array).into_iter().flatten();
+                self.files(at_ms, files.filter_map(|f| f.as_str().map(String::from)));
+            }
+            "claude-hook-event" => {
+                if let Ok(hook) = serde_json::from_value::<stream::HookEvent>(payload.clone()) {
+                    self.hook(at_ms, hook);
+                }
+            }
+            "claude-cost-warning" | "claude-cost-limit" => self.entries.push(TimelineEntry::CostCheckpoint {
+                at_ms,
+                cost_usd: number("cost_usd").unwrap_or(0.0),
+                max_cost_usd: number("max_cost_usd"),
+                reason: if event.event == "claude-cost-limit" { "limit" } else { "warning" }.to_string(),
+            }),
+            "claude-context-warning" => self.warning(
+                at_ms,
+                "contextUsage",
+                format!(
+                    "Context {:.0}% full ({} of {} tokens)",
+                    number("percent").unwrap_or(0.0),
+                    number("used_tokens").unwrap_or(0.0),
+                    number("context_limit").unwrap_or(0.0)
+                ),
+            ),
+            "low-disk-warning" => self.warning(
+                at_ms,
+                "disk",
+                format!(
+                    "{} MB free on the volume of {}",
+                    number("free_bytes").unwrap_or(0.0) as u64 / (1024 * 1024),
+                    payload.get("path").and_then(Value::as_str).unwrap_or_default()
+                ),
+            ),
+            "claude-stderr" if payload.get("severity").and_then(Value::as_str) == Some("error") => {
+                let data = payload.get("data").and_then(Value::as_str).unwrap_or_default();
+                self.warning(at_ms, "stderr", data.lines().next().unwrap_or_default().to_string());
+            }
+            "prompt-reference-warnings" => {
+                let references: Vec<&str> = payload
+                    .get("references")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|r| r.get("reference").and_then(Value::as_str))
+                    .collect();
+                self.warning(at_ms, "references", format!("Missing files: {}", references.join(", ")));
+            }
+            "claude-done" => self.finished_at_ms = Some(at_ms),
+            _ => {}
+        }
+    }
+
+    /// Transcript lines of one session inside the run's window
+    fn transcript(&mut self, content: &str, from_ms: i64, to_ms: i64) {
+        for line in content.lines() {
+            let Ok(entry) = serde_json::from_str::<Value>(line) else {
+                continue;
+            };
+            let Some(at_ms) = entry.get("timestamp").and_then(Value::as_str).and_then(digest::parse_utc_timestamp_ms) else {
+                continue;
+            };
+            if at_ms < from_ms || at_ms > to_ms {
+                continue;
+            }
+            if let Some(hook) = stream::hook_event_from_transcript(&entry) {
+                self.hook(at_ms, hook);
+            }
+            self.message(at_ms, &entry);
+        }
+    }
+}
+
+/// Commits on the current branch made between two times (Unix milliseconds), oldest first
+fn commits_between(working_dir: &str, from_ms: i64, to_ms: i64) -> Result<Vec<git::GitCommit>, String> {
+    let repo = git::open_repo(working_dir)?;
+    let mut revwalk = repo
+        .revwalk()
+        .map_err(|e| format!("Failed to create revwalk: {}", e))?;
+    revwalk
+        .set_sorting(git2::Sort::TIME)
+        .map_err(|e| format!("Failed to sort revwalk: {}", e))?;
+    if revwalk.push_head().is_err() {
+        // No commits yet
+        return Ok(Vec::new());
+    }
+
+    let mut commits = Vec::new();
+    for oid in revwalk.take(MAX_COMMITS_WALKED) {
+        let Ok(oid) = oid else { break };
+        let commit = repo
+            .find_commit(oid)
+            .map_err(|e| format!("Failed to find commit: {}", e))?;
+        let at_ms = commit.time().seconds() * 1000;
+        if at_ms < from_ms {
+            break;
+        }
+        if at_ms <= to_ms {
+            commits.push(git::commit_summary(&repo, &commit));
+        }
+    }
+    commits.reverse();
+    Ok(commits)
+}
+
+fn totals(entries: &[TimelineEntry]) -> TimelineTotals {
+    let mut totals = TimelineTotals::default();
+    for entry in entries {
+        match entry {
+            TimelineEntry::ToolRun { name, status, .. } => {
+                totals.tool_runs += 1;
+                *totals.tools.entry(name.clone()).or_default() += 1;
+                if *status == ToolStatus::Error {
+                    totals.tool_errors += 1;
+                }
+            }
+            TimelineEntry::FilesChanged { files, .. } => totals.files_changed += files.len(),
+            TimelineEntry::Commit { .. } => totals.commits += 1,
+            TimelineEntry::PermissionDenied { .. } => totals.permission_denials += 1,
+            TimelineEntry::Hook { event, .. } => {
+                totals.hooks_run += 1;
+                if event.decision == "blocked" {
+                    totals.hooks_blocked += 1;
+                }
+            }
+            TimelineEntry::CostCheckpoint { cost_usd, .. } => {
+                totals.cost_usd = Some(totals.cost_usd.unwrap_or(0.0).max(*cost_usd));
+            }
+            TimelineEntry::Warning { .. } => totals.warnings += 1,
+            TimelineEntry::ToolRunGroup { .. } => {}
+        }
+    }
+    totals
+}
+
+/// Directory a group of tool runs' files share, relative to the workspace ("src/")
+fn common_scope(subjects: &[&str], working_dir: &str) -> Option<String> {
+    let dirs: Vec<&Path> = subjects.iter().map(|s| Path::new(*s).parent()).collect::<Option<_>>()?;
+    let mut common = dirs.first()?.to_path_buf();
+    for dir in &dirs[1..] {
+        while !dir.starts_with(&common) {
+            if !common.pop() {
+                return None;
+            }
+        }
+    }
+    let relative = common.strip_prefix(working_dir).unwrap_or(&common);
+    let relative = relative.to_string_lossy();
+    match relative.is_empty() {
+        true => None,
+        false => Some(format!("{}/", relative.trim_end_matches('/'))),
+    }
+}
+
+/// Replace each run of at least `min_run` consecutive uses of one tool with a group entry
+fn group_runs(entries: Vec<TimelineEntry>, min_run: usize, working_dir: &str) -> Vec<TimelineEntry> {
+    let tool_name = |entry: &TimelineEntry| match entry {
+        TimelineEntry::ToolRun { name, .. } => Some(name.clone()),
+        _ => None,
+    };
+    let mut grouped = Vec::new();
+    let mut run: Vec<TimelineEntry> = Vec::new();
+    let flush = |run: &mut Vec<TimelineEntry>, grouped: &mut Vec<TimelineEntry>| {
+        if run.len() < min_run {
+            grouped.append(run);
+            return;
+        }
+        let (mut at_ms, mut name, mut duration, mut errors, mut subjects) = (0, String::new(), 0, 0, Vec::new());
+        for (i, entry) in run.iter().enumerate() {
+            if let TimelineEntry::ToolRun {
+                at_ms: at,
+                name: n,
+                subject,
+                duration_ms,
+                status,
+                ..
+            } = entry
+            {
+                if i == 0 {
+                    (at_ms, name) = (*at, n.clone());
+                }
+                duration += duration_ms.unwrap_or(0);
+                errors += usize::from(*status == ToolStatus::Error);
+                subjects.extend(subject.as_deref());
+            }
+        }
+        let scope = (subjects.len() == run.len()).then(|| common_scope(&subjects, working_dir)).flatten();
+        let label = match &scope {
+            Some(scope) => format!("{} ×{} in {}", name, run.len(), scope),
+            None => format!("{} ×{}", name, run.len()),
+        };
+        grouped.push(TimelineEntry::ToolRunGroup {
+            at_ms,
+            name,
+            count: run.len(),
+            scope,
+            label,
+            duration_ms: duration,
+            errors,
+        });
+        run.clear();
+    };
+    for entry in entries {
+        let same_tool = tool_name(&entry).is_some_and(|name| run.first().and_then(tool_name).as_ref() == Some(&name));
+        if !same_tool {
+            flush(&mut run, &mut grouped);
+        }
+        if tool_name(&entry).is_some() {
+            run.push(entry);
+        } else {
+            grouped.push(entry);
+        }
+    }
+    flush(&mut run, &mut grouped);
+    grouped
+}
+
+fn at_ms(entry: &TimelineEntry) -> i64 {
+    match entry {
+        TimelineEntry::ToolRun { at_ms, .. }
+        | TimelineEntry::ToolRunGroup { at_ms, .. }
+        | TimelineEntry::FilesChanged { at_ms, .. }
+        | TimelineEntry::Commit { at_ms, .. }
+        | TimelineEntry::PermissionDenied { at_ms, .. }
+        | TimelineEntry::Hook { at_ms, .. }
+        | TimelineEntry::CostCheckpoint { at_ms, .. }
+        | TimelineEntry::Warning { at_ms, .. } => *at_ms,
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Everything a query did, in time order. Running and recently finished queries are built from
+/// their event buffer; older ones from their session transcript (or just their history record).
+/// Repetitive tool runs are grouped and the list is capped; `totals` count everything.
+#[tauri::command]
+pub async fn get_query_timeline(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    query_id: String,
+) -> Result<QueryTimeline, String> {
+    let record = history::find_record(&app, &query_id).await?;
+    let active_dir = state
+        .active_queries
+        .lock()
+        .await
+        .get(&query_id)
+        .map(|active| active.working_dir.clone());
+    let running = active_dir.is_some();
+    let working_dir = match (active_dir, &record) {
+        (Some(dir), _) => dir,
+        (None, Some(record)) => record.working_dir.clone(),
+        (None, None) if replay::snapshot(&app, &query_id).is_none() => {
+            return Err(format!("Unknown query: {}", query_id));
+        }
+        (None, None) => String::new(),
+    };
+
+    let mut builder = Builder::default();
+    let mut partial = false;
+    let source = if let Some((events, evicted)) = replay::snapshot(&app, &query_id) {
+        partial = evicted;
+        for event in &events {
+            builder.replay_event(event);
+        }
+        "live"
+    } else if let Some(record) = &record {
+        builder.started_at_ms = Some(record.started_at * 1000);
+        builder.finished_at_ms = Some(record.finished_at * 1000);
+        let transcript = match &record.session_id {
+            Some(session_id) => match find_session_path(&record.working_dir, session_id)? {
+                Some(path) => tokio::fs::read_to_string(&path).await.ok(),
+                None => None,
+            },
+            None => None,
+        };
+        let from_ms = record.started_at * 1000 - WINDOW_SLACK_MS;
+        let to_ms = record.finished_at * 1000 + WINDOW_SLACK_MS;
+        match transcript {
+            Some(content) => {
+                builder = tokio::task::spawn_blocking(move || {
+                    builder.transcript(&content, from_ms, to_ms);
+                    builder
+                })
+                .await
+                .map_err(|e| format!("Failed to read session: {}", e))?;
+                "session"
+            }
+            None => "history",
+        }
+    } else {
+        return Err(format!("Unknown query: {}", query_id));
+    };
+
+    if let Some(record) = &record {
+        // The record knows the run's window and outcome better than a partial buffer does
+        let recorded_start = record.started_at * 1000;
+        builder.started_at_ms = Some(builder.started_at_ms.map_or(recorded_start, |start| start.min(recorded_start)));
+        builder.finished_at_ms = Some(record.finished_at * 1000);
+        let at = record.finished_at * 1000;
+        builder.files(at, record.changed_files.iter().cloned());
+        if builder.final_cost.is_none() {
+            if let Some(cost) = record.cost_usd {
+                builder.final_cost = Some(cost);
+                builder.entries.push(TimelineEntry::CostCheckpoint {
+                    at_ms: at,
+                    cost_usd: cost,
+                    max_cost_usd: None,
+                    reason: "final".to_string(),
+                });
+            }
+        }
+    }
+
+    if let (Some(from_ms), false) = (builder.started_at_ms, working_dir.is_empty()) {
+        let to_ms = builder.finished_at_ms.unwrap_or_else(history::now_millis) + WINDOW_SLACK_MS;
+        let dir = working_dir.clone();
+        let commits = tokio::task::spawn_blocking(move || commits_between(&dir, from_ms - WINDOW_SLACK_MS, to_ms))
+            .await
+            .map_err(|e| format!("Failed to read commits: {}", e))?
+            .unwrap_or_default();
+        for commit in commits {
+            builder.entries.push(TimelineEntry::Commit {
+                at_ms: commit.timestamp * 1000,
+                commit,
+            });
+        }
+    }
+
+    let mut entries = builder.entries;
+    entries.sort_by_key(at_ms);
+    let mut totals = totals(&entries);
+    totals.cost_usd = builder.final_cost.or(totals.cost_usd);
+    totals.duration_ms = builder
+        .started_at_ms
+        .zip(builder.finished_at_ms.or(running.then(history::now_millis)))
+        .map(|(start, end)| (end - start).max(0));
+
+    let mut entries = group_runs(entries, MIN_GROUPED_RUN, &working_dir);
+    if entries.len() > MAX_ENTRIES {
+        entries = group_runs(entries, 2, &working_dir);
+    }
+    let omitted = entries.len().saturating_sub(MAX_ENTRIES);
+    entries.truncate(MAX_ENTRIES);
+
+    Ok(QueryTimeline {
+        query_id,
+        source: source.to_string(),
+        running,
+        started_at_ms: builder.started_at_ms,
+        finished_at_ms: builder.finished_at_ms,
+        entries,
+        omitted,
+        partial,
+        totals,
+    })
+}
@@ -0,0 +1,648 @@
+// mensa - Session Titles Module
+// Custom session titles, and heuristic ones derived from the transcript without a model call
+
+use crate::{permissions, project_dir_for_workspace, session_index};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+const MAX_TITLE_CHARS: usize = 60;
+
+/// Transcripts read at once by `generate_missing_titles`
+const TITLE_CONCURRENCY: usize = 4;
+
+/// Words that don't make a prompt worth titling on their own ("hey", "ok continue")
+const LOW_CONTENT_WORDS: &[&str] = &[
+    "hey", "hi", "hello", "yo", "ok", "okay", "k", "thanks", "thank", "you", "please", "pls", "continue",
+    "go", "on", "ahead", "sure", "yes", "no", "yep", "nope", "hmm", "test", "testing", "start", "again",
+    "do", "it", "that", "this", "the", "a", "and", "so",
+];
+
+/// Headings too generic to tell one session from another
+const GENERIC_HEADINGS: &[&str] = &[
+    "plan", "summary", "overview", "implementation plan", "implementation", "changes", "analysis",
+    "approach", "next steps", "context", "background", "notes", "details", "steps", "todo",
+];
+
+/// Wrappers Claude Code puts around slash commands and injected context in user turns
+const INJECTED_TAGS: &[&str] = &[
+    "system-reminder",
+    "command-name",
+    "command-message",
+    "command-args",
+    "local-command-stdout",
+    "local-command-stderr",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TitleSource {
+    /// Set by the user (here or with Claude Code's /rename); left alone
+    Custom,
+    /// The first heading of a plan-mode plan
+    Plan,
+    /// The first H1/H2 the assistant wrote
+    Heading,
+    /// A line of the first substantive prompt
+    Prompt,
+    /// Nothing better: "Session from <date>"
+    Date,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTitle {
+    pub session_id: String,
+    pub title: String,
+    pub source: TitleSource,
+    /// Whether the title was written to the sessions index
+    pub applied: bool,
+}
+
+/// What a transcript offers for a title, in the order of preference
+#[derive(Debug, Default)]
+struct TitleCandidates {
+    custom: Option<String>,
+    plan_heading: Option<String>,
+    assistant_heading: Option<String>,
+    prompt_line: Option<String>,
+    /// Date part of the first timestamp
+    date: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+fn heading_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(#{1,6})\s+(.+?)\s*#*$").expect("valid regex"))
+}
+
+/// A stack frame or trace header rather than something a person wrote
+fn trace_line_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"^(at\s|File "|Traceback\b|#\d+\s|\d+:\s+0x|stack backtrace|\.\.\. \d+ more|note: run with|[\w./\\-]+:\d+(:\d+)?\b)"#)
+            .expect("valid regex")
+    })
+}
+
+/// An error message line in pasted output: "TypeError: ...", "error[E0382]: ...", a panic
+fn error_line_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^([\w.]*(Error|Exception)\b|error(\[\w+\])?:|fatal:|panic:|thread '.*' panicked)").expect("valid regex")
+    })
+}
+
+/// Conversational openers removed from the front of a prompt line
+fn filler_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"(?i)^((hey|hi|hello|ok|okay|so|please|pls)\b[,!.]?\s*|(can|could|would|will) you( please)?\s+|(i want you to|i need you to|i'd like you to|help me( to)?|let's|lets)\s+)",
+        )
+        .expect("valid regex")
+    })
+}
+
+fn list_marker_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(#+|[-*+>]|\d+[.)])\s+").expect("valid regex"))
+}
+
+/// Drop fenced code blocks (an unclosed fence runs to the end)
+fn strip_fences(text: &str) -> String {
+    let mut kept = Vec::new();
+    let mut in_fence = false;
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if !in_fence {
+            kept.push(line);
+        }
+    }
+    kept.join("\n")
+}
+
+/// Remove `<tag>...</tag>` blocks Claude Code injects into user turns
+fn strip_injected(text: &str) -> String {
+    let mut text = text.to_string();
+    for tag in INJECTED_TAGS {
+        let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+        while let Some(start) = text.find(&open) {
+            let end = text[start..]
+                .find(&close)
+                .map(|i| start + i + close.len())
+                .unwrap_or(text.len());
+            text.replace_range(start..end, "");
+        }
+    }
+    text
+}
+
+fn words(line: &str) -> impl Iterator<Item = String> + '_ {
+    line.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+
+/// Says something beyond greetings and "continue"
+fn is_substantive(text: &str) -> bool {
+    let content: Vec<String> = words(text).filter(|w| !LOW_CONTENT_WORDS.contains(&w.as_str())).collect();
+    content.iter().map(|w| w.chars().count()).sum::<usize>() >= 6
+}
+
+/// Reads like a sentence: a few words, mostly letters, not a trace frame
+fn is_prose(line: &str) -> bool {
+    if trace_line_pattern().is_match(line) || error_line_pattern().is_match(line) {
+        return false;
+    }
+    let visible: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    let letters = visible.iter().filter(|c| c.is_alphabetic()).count();
+    line.split_whitespace().count() >= 3 && letters * 10 >= visible.len() * 7 && is_substantive(line)
+}
+
+/// Cut at a word boundary so the title fits, marking the cut
+fn cap_length(text: &str) -> String {
+    if text.chars().count() <= MAX_TITLE_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(MAX_TITLE_CHARS - 1).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) if space > MAX_TITLE_CHARS / 2 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end_matches([' ', ',', ';', ':', '-']))
+}
+
+/// Tidy a line into a title: no markdown, no filler, one line of collapsed whitespace
+fn clean_title(line: &str) -> String {
+    let mut text = line.split_whitespace().collect::<Vec<_>>().join(" ");
+    text = list_marker_pattern().replace(&text, "").to_string();
+    text = text.replace(['`', '*'], "").replace("__", "");
+    loop {
+        let stripped = filler_pattern().replace(&text, "").to_string();
+        if stripped == text {
+            break;
+        }
+        text = stripped;
+    }
+    let text = text.trim().trim_end_matches(['?', '.', '!', ':', ',', ';']).trim();
+    let mut chars = text.chars();
+    let text = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    };
+    cap_length(&text)
+}
+
+/// The first heading of `max_level` or above that isn't generic, outside code fences
+fn first_heading(markdown: &str, max_level: usize) -> Option<String> {
+    strip_fences(markdown).lines().find_map(|line| {
+        let captures = heading_pattern().captures(line.trim())?;
+        if captures[1].len() > max_level {
+            return None;
+        }
+        let text = captures[2].trim();
+        let text = text
+            .strip_prefix("Plan:")
+            .or_else(|| text.strip_prefix("Plan -"))
+            .unwrap_or(text)
+            .trim();
+        let title = clean_title(text);
+        let generic = GENERIC_HEADINGS.contains(&title.to_lowercase().as_str());
+        (!title.is_empty() && !generic).then_some(title)
+    })
+}
+
+/// The line of a prompt that best says what it's about: the first sentence-like line,
+/// else the error message of a pasted trace, else the first line that isn't a frame
+fn salient_line(prompt: &str) -> Option<String> {
+    let text = strip_fences(&strip_injected(prompt));
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let line = lines
+        .iter()
+        .find(|line| is_prose(line))
+        .or_else(|| lines.iter().find(|line| error_line_pattern().is_match(line)))
+        .or_else(|| lines.iter().find(|line| !trace_line_pattern().is_match(line) && is_substantive(line)))?;
+    let title = clean_title(line);
+    (!title.is_empty()).then_some(title)
+}
+
+/// Text of a user turn the person typed (not tool results, slash command output or meta entries)
+fn typed_prompt(entry: &Value) -> Option<String> {
+    if entry.get("isMeta").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return None;
+    }
+    let text = match entry.get("message")?.get("content")? {
+        Value::String(text) => text.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|v| v.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    let text = strip_injected(&text);
+    if text.trim().is_empty() || text.starts_with("Caveat:") || text.starts_with("[Request interrupted") {
+        return None;
+    }
+    Some(text)
+}
+
+fn collect_candidates(content: &str) -> TitleCandidates {
+    let mut candidates = TitleCandidates::default();
+    for line in content.lines() {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        let kind = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        if candidates.date.is_none() {
+            candidates.date = entry
+                .get("timestamp")
+                .and_then(|v| v.as_str())
+                .and_then(|ts| ts.get(..10))
+                .map(String::from);
+        }
+        match kind {
+            // Claude Code's /rename; the latest one counts
+            "custom-title" => {
+                if let Some(title) = entry.get("customTitle").and_then(|v| v.as_str()) {
+                    candidates.custom = Some(title.to_string()).filter(|t| !t.trim().is_empty());
+                }
+            }
+            "user" if candidates.prompt_line.is_none() => {
+                if let Some(prompt) = typed_prompt(&entry).filter(|p| is_substantive(p)) {
+                    candidates.prompt_line = salient_line(&prompt);
+                }
+            }
+            "assistant" => {
+                let blocks = entry
+                    .get("message")
+                    .and_then(|m| m.get("content"))
+                    .and_then(|c| c.as_array())
+                    .map(|b| b.as_slice())
+                    .unwrap_or_default();
+                for block in blocks {
+                    match block.get("type").and_then(|v| v.as_str()) {
+                        Some("tool_use")
+                            if candidates.plan_heading.is_none()
+                                && block.get("name").and_then(|v| v.as_str()) == Some("ExitPlanMode") =>
+                        {
+                            let plan = block.get("input").and_then(|i| i.get("plan")).and_then(|p| p.as_str());
+                            candidates.plan_heading = plan.and_then(|plan| first_heading(plan, 6));
+                        }
+                        Some("text") if candidates.assistant_heading.is_none() => {
+                            let text = block.get("text").and_then(|v| v.as_str()).unwrap_or("");
+                            candidates.assistant_heading = first_heading(text, 2);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    candidates
+}
+
+/// Pick a title from a transcript, `fallback_date` standing in when it has no timestamps
+fn derive_title(content: &str, fallback_date: Option<&str>) -> (String, TitleSource) {
+    let candidates = collect_candidates(content);
+    if let Some(custom) = candidates.custom {
+        return (custom, TitleSource::Custom);
+    }
+    if let Some(title) = candidates.plan_heading {
+        return (title, TitleSource::Plan);
+    }
+    if let Some(title) = candidates.assistant_heading {
+        return (title, TitleSource::Heading);
+    }
+    if let Some(title) = candidates.prompt_line {
+        return (title, TitleSource::Prompt);
+    }
+    let date = candidates.date.as_deref().or(fallback_date).unwrap_or("an unknown date");
+    (format!("Session from {}", date), TitleSource::Date)
+}
+
+fn check_session_id(session_id: &str) -> Result<(), String> {
+    if session_id.is_empty() || session_id.contains(['/', '\\']) || session_id.contains("..") {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    Ok(())
+}
+
+/// Index entries by session id, as raw JSON
+async fn index_entries(project_dir: &Path) -> Result<HashMap<String, Value>, String> {
+    let path = project_dir.join(session_index::INDEX_FILE);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| format!("Failed to read sessions index: {}", e))?;
+    let index: Value = serde_json::from_str(&content).map_err(|e| format!("Failed to parse sessions index: {}", e))?;
+    Ok(index
+        .get("entries")
+        .and_then(|e| e.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some((entry.get("sessionId")?.as_str()?.to_string(), entry.clone())))
+        .collect())
+}
+
+fn has_custom_title(entry: &Value) -> bool {
+    entry
+        .get("customTitle")
+        .and_then(|v| v.as_str())
+        .is_some_and(|t| !t.trim().is_empty())
+}
+
+/// Set `customTitle` on index entries (None removes it). With `overwrite` false, entries that
+/// have one by the time the index is locked keep it. Returns the ids actually written.
+async fn write_titles(
+    project_dir: &Path,
+    titles: HashMap<String, Option<String>>,
+    overwrite: bool,
+) -> Result<HashSet<String>, String> {
+    let written = Arc::new(Mutex::new(HashSet::new()));
+    let record = written.clone();
+    session_index::update_index(project_dir, move |entries| {
+        let mut record = record.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for entry in entries.iter_mut() {
+            let Some(id) = entry.get("sessionId").and_then(|v| v.as_str()).map(String::from) else {
+                continue;
+            };
+            let Some(title) = titles.get(&id) else {
+                continue;
+            };
+            if !overwrite && has_custom_title(entry) {
+                continue;
+            }
+            let Some(fields) = entry.as_object_mut() else {
+                continue;
+            };
+            match title {
+                Some(title) => fields.insert("customTitle".to_string(), Value::from(title.clone())),
+                None => fields.remove("customTitle"),
+            };
+            record.insert(id);
+        }
+    })
+    .await?;
+    let written = written.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+    Ok(written)
+}
+
+/// Read a transcript and derive its title off the async runtime
+async fn title_for_file(path: PathBuf, fallback_date: Option<String>) -> Result<(String, TitleSource), String> {
+    tokio::task::spawn_blocking(move || {
+        let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read session: {}", e))?;
+        Ok(derive_title(&content, fallback_date.as_deref()))
+    })
+    .await
+    .map_err(|e| format!("Failed to read session: {}", e))?
+}
+
+/// Date part of an index entry's `created`
+fn created_date(entry: Option<&Value>) -> Option<String> {
+    entry
+        .and_then(|e| e.get("created"))
+        .and_then(|v| v.as_str())
+        .and_then(|created| created.get(..10))
+        .map(String::from)
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Set a session's title in the sessions index; an empty or null title removes it.
+/// Returns false when the index has no entry for the session.
+#[tauri::command]
+pub async fn rename_session(
+    workspace_path: String,
+    session_id: String,
+    title: Option<String>,
+) -> Result<bool, permissions::SessionError> {
+    check_session_id(&session_id)?;
+    let title = title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    let project_dir = project_dir_for_workspace(&workspace_path)?;
+    permissions::require(&project_dir, true)?;
+    let written = write_titles(&project_dir, HashMap::from([(session_id.clone(), title)]), true).await?;
+    Ok(written.contains(&session_id))
+}
+
+/// Title a session from its transcript (plan heading, assistant heading, first real prompt,
+/// else its date) and save it, unless it already has a custom title, which is returned as is
+#[tauri::command]
+pub async fn generate_session_title(workspace_path: String, session_id: String) -> Result<SessionTitle, permissions::SessionError> {
+    check_session_id(&session_id)?;
+    let project_dir = project_dir_for_workspace(&workspace_path)?;
+    permissions::require(&project_dir, true)?;
+    let entries = index_entries(&project_dir).await?;
+    let entry = entries.get(&session_id);
+    if let Some(title) = entry.filter(|e| has_custom_title(e)).and_then(|e| e["customTitle"].as_str()) {
+        return Ok(SessionTitle {
+            session_id,
+            title: title.to_string(),
+            source: TitleSource::Custom,
+            applied: false,
+        });
+    }
+
+    let path = project_dir.join(format!("{}.jsonl", session_id));
+    if !path.exists() {
+        return Err(format!("Session not found: {}", session_id).into());
+    }
+    let (title, source) = title_for_file(path, created_date(entry)).await?;
+    let applied = source != TitleSource::Custom
+        && write_titles(&project_dir, HashMap::from([(session_id.clone(), Some(title.clone()))]), false)
+            .await?
+            .contains(&session_id);
+    Ok(SessionTitle {
+        session_id,
+        title,
+        source,
+        applied,
+    })
+}
+
+/// Title every indexed session that has no custom title yet; returns the titles set
+#[tauri::command]
+pub async fn generate_missing_titles(workspace_path: String) -> Result<Vec<SessionTitle>, permissions::SessionError> {
+    let project_dir = project_dir_for_workspace(&workspace_path)?;
+    permissions::require(&project_dir, true)?;
+    let untitled: Vec<(String, Option<String>)> = index_entries(&project_dir)
+        .await?
+        .into_iter()
+        .filter(|(id, entry)| !has_custom_title(entry) && check_session_id(id).is_ok())
+        .map(|(id, entry)| (id, created_date(Some(&entry))))
+        .collect();
+
+    let permits = Arc::new(tokio::sync::Semaphore::new(TITLE_CONCURRENCY));
+    let handles: Vec<_> = untitled
+        .into_iter()
+        .map(|(session_id, fallback_date)| {
+            let permits = permits.clone();
+            let path = project_dir.join(format!("{}.jsonl", session_id));
+            tauri::async_runtime::spawn(async move {
+                let _permit = permits.acquire_owned().await.ok()?;
+                if !path.exists() {
+                    return None;
+                }
+                let (title, source) = title_for_file(path, fallback_date).await.ok()?;
+                Some(SessionTitle {
+                    session_id,
+                    title,
+                    source,
+                    applied: false,
+                })
+            })
+        })
+        .collect();
+
+    let mut generated = Vec::new();
+    for handle in handles {
+        if let Ok(Some(title)) = handle.await {
+            // Titled with /rename in Claude Code: the index just doesn't know yet
+            if title.source != TitleSource::Custom {
+                generated.push(title);
+            }
+        }
+    }
+
+    let titles = generated
+        .iter()
+        .map(|t| (t.session_id.clone(), Some(t.title.clone())))
+        .collect();
+    let written = write_titles(&project_dir, titles, false).await?;
+    let mut applied: Vec<SessionTitle> = generated
+        .into_iter()
+        .filter(|t| written.contains(&t.session_id))
+        .map(|t| SessionTitle { applied: true, ..t })
+        .collect();
+    applied.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transcript(entries: &[Value]) -> String {
+        entries.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n")
+    }
+
+    fn user(content: Value) -> Value {
+        json!({ "type": "user", "timestamp": "2025-03-04T10:00:00Z", "message": { "role": "user", "content": content } })
+    }
+
+    fn assistant(content: Value) -> Value {
+        json!({ "type": "assistant", "message": { "role": "assistant", "content": content } })
+    }
+
+    #[test]
+    fn salient_line_of_ugly_prompts() {
+        let cases = [
+            ("hey can you please fix the login redirect loop on safari??", Some("Fix the login redirect loop on safari")),
+            (
+                "Traceback (most recent call last):\n  File \"app.py\", line 12, in <module>\n    main()\nKeyError: 'user_id'",
+                Some("KeyError: 'user_id'"),
+            ),
+            (
+                "TypeError: Cannot read properties of undefined (reading 'map')\n    at render (App.jsx:14:22)\n    at commit (react-dom.js:9:1)",
+                Some("TypeError: Cannot read properties of undefined (reading…"),
+            ),
+            (
+                "```rust\nfn main() { let x = vec![1]; drop(x); println!(\"{:?}\", x); }\n```\nwhy does this not compile",
+                Some("Why does this not compile"),
+            ),
+            ("ok", None),
+            ("hi!!!\n\ncontinue", None),
+            ("   \n\n  - **Refactor**   the `settings` store\tto use   signals  \n", Some("Refactor the settings store to use signals")),
+            (
+                "<command-name>/review</command-name><command-args>123</command-args>\nlook at the flaky upload tests in ci",
+                Some("Look at the flaky upload tests in ci"),
+            ),
+            ("```\nunclosed fence with only code in it\nand more code", None),
+        ];
+        for (prompt, expected) in cases {
+            assert_eq!(salient_line(prompt).as_deref(), expected, "{:?}", prompt);
+        }
+    }
+
+    #[test]
+    fn long_prompts_are_cut_at_a_word() {
+        let title = salient_line(&format!("{} end", "please migrate the entire billing service over to the new ledger api".repeat(2))).unwrap();
+        assert!(title.chars().count() <= MAX_TITLE_CHARS, "{}", title);
+        assert!(title.ends_with('…'));
+        assert!(title.starts_with("Migrate the entire billing service"));
+        assert!(!title.trim_end_matches('…').ends_with(' '));
+
+        // One unbroken token is cut mid-word
+        let token = salient_line(&format!("Investigate {}", "x".repeat(200)));
+        assert_eq!(token.map(|t| t.chars().count()), Some(MAX_TITLE_CHARS));
+    }
+
+    #[test]
+    fn derive_title_skips_meta_and_low_content_turns() {
+        let content = transcript(&[
+            user(json!("<local-command-stdout>Set model to opus</local-command-stdout>")),
+            json!({ "type": "user", "isMeta": true, "message": { "content": "Caveat: the messages below were generated by the user while running local commands" } }),
+            user(json!("hey")),
+            user(json!([{ "type": "tool_result", "tool_use_id": "t1", "content": "Add a dark mode toggle to the header" }])),
+            user(json!([{ "type": "text", "text": "[Request interrupted by user]" }])),
+            user(json!([
+                { "type": "image", "source": { "type": "base64", "media_type": "image/png", "data": "iVBOR" } },
+                { "type": "text", "text": "the sidebar overlaps the editor on narrow windows" }
+            ])),
+        ]);
+        assert_eq!(
+            derive_title(&content, None),
+            ("The sidebar overlaps the editor on narrow windows".to_string(), TitleSource::Prompt)
+        );
+    }
+
+    #[test]
+    fn derive_title_prefers_custom_then_plan_then_heading() {
+        let prompt = user(json!("please add retries to the webhook sender"));
+        let heading = assistant(json!([{ "type": "text", "text": "## Summary\n\n## Webhook retries with backoff\nDone." }]));
+        let plan = assistant(json!([{ "type": "tool_use", "name": "ExitPlanMode", "input": { "plan": "```\n# not this\n```\n# Plan: Retry failed webhook deliveries" } }]));
+        let rename = json!({ "type": "custom-title", "customTitle": "Webhooks" });
+
+        let title = |entries: &[Value]| derive_title(&transcript(entries), Some("2025-01-01"));
+        assert_eq!(title(std::slice::from_ref(&prompt)), ("Add retries to the webhook sender".to_string(), TitleSource::Prompt));
+        assert_eq!(
+            title(&[prompt.clone(), heading.clone()]),
+            ("Webhook retries with backoff".to_string(), TitleSource::Heading)
+        );
+        assert_eq!(
+            title(&[prompt.clone(), heading.clone(), plan.clone()]),
+            ("Retry failed webhook deliveries".to_string(), TitleSource::Plan)
+        );
+        assert_eq!(title(&[prompt, heading, plan, rename]), ("Webhooks".to_string(), TitleSource::Custom));
+    }
+
+    #[test]
+    fn derive_title_falls_back_to_a_date() {
+        let content = transcript(&[user(json!("ok")), user(json!("continue"))]);
+        assert_eq!(derive_title(&content, Some("2024-12-31")).0, "Session from 2025-03-04");
+        assert_eq!(derive_title("not json\n{", Some("2024-12-31")).0, "Session from 2024-12-31");
+        assert_eq!(derive_title("", None), ("Session from an unknown date".to_string(), TitleSource::Date));
+    }
+}
@@ -0,0 +1,305 @@
+// mensa - Tool Output Module
+// Previews of long tool results, with the full text fetched lazily in slices
+
+use crate::AppState;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tauri::State;
+use tokio::sync::Mutex;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Tool output sent inline (transcripts and stream) when the caller doesn't choose a length
+pub const DEFAULT_PREVIEW_BYTES: usize = 16 * 1024;
+
+/// Largest slice returned by one fetch
+const MAX_SLICE_BYTES: usize = 1024 * 1024;
+
+/// Byte offsets of tool_result lines per transcript, rebuilt when the file changes
+#[derive(Default)]
+pub struct ToolOutputIndex {
+    transcripts: Arc<Mutex<HashMap<PathBuf, TranscriptIndex>>>,
+}
+
+struct TranscriptIndex {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// tool_use_id -> offset of the line holding its (last) tool_result
+    offsets: HashMap<String, u64>,
+}
+
+/// One window of a tool's full output. Offsets are byte offsets into the output text,
+/// moved back to the nearest character boundary.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolOutputSlice {
+    pub tool_use_id: String,
+    pub offset: usize,
+    pub content: String,
+    pub total_bytes: usize,
+    /// Where the next slice starts (None once the end is reached)
+    pub next_offset: Option<usize>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Text shown for a tool_result block's content: its text parts joined, or pretty JSON
+pub fn tool_result_text(content: Option<&Value>) -> Option<String> {
+    match content {
+        Some(Value::String(s)) => Some(s.clone()),
+        Some(value @ Value::Array(arr)) => {
+            let texts: Vec<&str> = arr
+                .iter()
+                .filter(|b| b.get("type").and_then(|v| v.as_str()) == Some("text"))
+                .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+                .collect();
+            if texts.is_empty() {
+                serde_json::to_string_pretty(value).ok()
+            } else {
+                Some(texts.join("\n"))
+            }
+        }
+        Some(v) => serde_json::to_string_pretty(v).ok(),
+        None => None,
+    }
+}
+
+/// Largest char boundary at or before `index`
+fn floor_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// The first `max_bytes` of `text` (cut on a char boundary), or None if it already fits
+pub fn preview(text: &str, max_bytes: usize) -> Option<&str> {
+    if text.len() <= max_bytes {
+        return None;
+    }
+    Some(&text[..floor_boundary(text, max_bytes)])
+}
+
+fn slice_output(tool_use_id: &str, text: &str, offset: usize, limit: usize) -> ToolOutputSlice {
+    let start = floor_boundary(text, offset);
+    let mut end = floor_boundary(text, start.saturating_add(limit.min(MAX_SLICE_BYTES)));
+    // Always make progress, even when the limit is smaller than one character
+    if end == start && end < text.len() {
+        end += 1;
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+    }
+    ToolOutputSlice {
+        tool_use_id: tool_use_id.to_string(),
+        offset: start,
+        content: text[start..end].to_string(),
+        total_bytes: text.len(),
+        next_offset: (end < text.len()).then_some(end),
+    }
+}
+
+/// tool_result blocks (carrying a tool_use_id) of a user transcript/stream line
+fn tool_result_blocks(parsed: &mut Value) -> Vec<&mut Value> {
+    if parsed.get("type").and_then(|v| v.as_str()) != Some("user") {
+        return Vec::new();
+    }
+    match parsed.pointer_mut("/message/content") {
+        Some(Value::Array(blocks)) => blocks
+            .iter_mut()
+            .filter(|b| b.get("type").and_then(|v| v.as_str()) == Some("tool_result"))
+            .filter(|b| b.get("tool_use_id").and_then(|v| v.as_str()).is_some())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn build_index(path: &Path) -> Result<TranscriptIndex, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to read session: {}", e))?;
+    let metadata = file.metadata().map_err(|e| format!("Failed to read session: {}", e))?;
+    let mut reader = BufReader::new(file);
+
+    let mut offsets = HashMap::new();
+    let mut offset = 0u64;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("Failed to read session: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        // Cheap substring check before paying for a JSON parse
+        if line.windows(13).any(|w| w == b"\"tool_result\"") {
+            if let Ok(mut parsed) = serde_json::from_slice::<Value>(&line) {
+                for block in tool_result_blocks(&mut parsed) {
+                    if let Some(id) = block.get("tool_use_id").and_then(|v| v.as_str()) {
+                        offsets.insert(id.to_string(), offset);
+                    }
+                }
+            }
+        }
+        offset += read as u64;
+    }
+
+    Ok(TranscriptIndex {
+        modified: metadata.modified().ok(),
+        len: metadata.len(),
+        offsets,
+    })
+}
+
+/// Read the line at `offset` and return the output text of `tool_use_id`'s result in it
+fn read_tool_result(path: &Path, offset: u64, tool_use_id: &str) -> Result<Option<String>, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to read session: {}", e))?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+    let mut line = Vec::new();
+    BufReader::new(file)
+        .read_until(b'\n', &mut line)
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+
+    let mut parsed: Value = match serde_json::from_slice(&line) {
+        Ok(parsed) => parsed,
+        Err(_) => return Ok(None),
+    };
+    Ok(tool_result_blocks(&mut parsed)
+        .into_iter()
+        .rfind(|b| b.get("tool_use_id").and_then(|v| v.as_str()) == Some(tool_use_id))
+        .and_then(|b| tool_result_text(b.get("content"))))
+}
+
+/// Full output of a tool from a transcript, using (and refreshing) the cached line index
+async fn transcript_tool_output(
+    index: &ToolOutputIndex,
+    path: PathBuf,
+    tool_use_id: &str,
+) -> Result<Option<String>, String> {
+    let metadata = tokio::fs::metadata(&path)
+        .await
+        .map_err(|e| format!("Failed to read session: {}", e))?;
+    let (modified, len) = (metadata.modified().ok(), metadata.len());
+
+    let cached = index
+        .transcripts
+        .lock()
+        .await
+        .get(&path)
+        .filter(|entry| entry.modified == modified && entry.len == len)
+        .map(|entry| entry.offsets.get(tool_use_id).copied());
+    let offset = match cached {
+        Some(offset) => offset,
+        None => {
+            let build_path = path.clone();
+            let built = tokio::task::spawn_blocking(move || build_index(&build_path))
+                .await
+                .map_err(|e| format!("Failed to index session: {}", e))??;
+            let offset = built.offsets.get(tool_use_id).copied();
+            index.transcripts.lock().await.insert(path.clone(), built);
+            offset
+        }
+    };
+
+    let offset = match offset {
+        Some(offset) => offset,
+        None => return Ok(None),
+    };
+    let tool_use_id = tool_use_id.to_string();
+    tokio::task::spawn_blocking(move || read_tool_result(&path, offset, &tool_use_id))
+        .await
+        .map_err(|e| format!("Failed to read session: {}", e))?
+}
+
+/// Cut long tool_result contents in a streamed user line down to a preview.
+/// Returns the rewritten line and the full outputs that were cut, or None if nothing was.
+pub fn truncate_stream_line(line: &str, max_bytes: usize) -> Option<(String, Vec<(String, String)>)> {
+    let mut parsed: Value = serde_json::from_str(line).ok()?;
+
+    let mut cut = Vec::new();
+    for block in tool_result_blocks(&mut parsed) {
+        let text = match tool_result_text(block.get("content")) {
+            Some(text) => text,
+            None => continue,
+        };
+        let short = match preview(&text, max_bytes) {
+            Some(short) => short.to_string(),
+            None => continue,
+        };
+        let tool_use_id = block["tool_use_id"].as_str().unwrap_or_default().to_string();
+        block["content"] = Value::String(short);
+        block["output_truncated"] = Value::Bool(true);
+        block["output_total_bytes"] = Value::from(text.len());
+        cut.push((tool_use_id, text));
+    }
+    if cut.is_empty() {
+        return None;
+    }
+
+    // The SDK mirrors the result as tool_use_result; drop the copy rather than ship it whole
+    if let Some(object) = parsed.as_object_mut() {
+        if object.remove("tool_use_result").is_some() {
+            object.insert("tool_use_result_truncated".to_string(), Value::Bool(true));
+        }
+    }
+
+    Some((serde_json::to_string(&parsed).ok()?, cut))
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Fetch a slice of a tool's full output from a session transcript
+#[tauri::command]
+pub async fn get_tool_output(
+    state: State<'_, AppState>,
+    workspace_path: String,
+    session_id: String,
+    tool_use_id: String,
+    offset: usize,
+    limit: usize,
+) -> Result<ToolOutputSlice, String> {
+    if session_id.is_empty() || session_id.contains(['/', '\\']) || session_id.contains("..") {
+        return Err(format!("Invalid session id: {}", session_id));
+    }
+    let path = crate::session_file_path(&workspace_path, &session_id)?;
+    if !path.exists() {
+        return Err(format!("Session not found: {}", session_id));
+    }
+
+    let text = transcript_tool_output(&state.tool_outputs, path, &tool_use_id)
+        .await?
+        .ok_or_else(|| format!("No output recorded for tool {}", tool_use_id))?;
+    Ok(slice_output(&tool_use_id, &text, offset, limit))
+}
+
+/// Fetch a slice of a tool's full output from a running query's buffer
+#[tauri::command]
+pub async fn get_query_tool_output(
+    state: State<'_, AppState>,
+    query_id: String,
+    tool_use_id: String,
+    offset: usize,
+    limit: usize,
+) -> Result<ToolOutputSlice, String> {
+    let queries = state.active_queries.lock().await;
+    let query = queries
+        .get(&query_id)
+        .ok_or_else(|| format!("Query {} is no longer running; load the output from its session", query_id))?;
+    let text = query
+        .tool_outputs
+        .get(&tool_use_id)
+        .ok_or_else(|| format!("No buffered output for tool {}", tool_use_id))?;
+    Ok(slice_output(&tool_use_id, text, offset, limit))
+}
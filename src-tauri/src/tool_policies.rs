@@ -0,0 +1,546 @@
+// mensa - Tool Policies Module
+// Per-workspace allow/deny/ask rules for tool permission prompts, exportable to .claude/settings.json
+
+use crate::{fsutil, stream};
+use crate::store::JsonStore;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+    /// Always prompt, even when an allow rule also matches
+    Ask,
+}
+
+/// One rule for a tool; without a pattern it covers every use of the tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolRule {
+    pub action: PolicyAction,
+    /// Regex over the tool's main input: the Bash command, the file path of Read/Edit/Write,
+    /// the URL of WebFetch, ... (the input JSON for other tools)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+/// The rules for a tool name, or for every tool a `*` wildcard matches (e.g. "mcp__github__*")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolPolicy {
+    pub tool: String,
+    pub rules: Vec<ToolRule>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ToolPoliciesFile {
+    /// Policies keyed by canonical workspace path
+    workspaces: HashMap<String, Vec<ToolPolicy>>,
+}
+
+static POLICIES_FILE: JsonStore<ToolPoliciesFile> = JsonStore::new("tool policies");
+
+/// The rule that decided a permission request
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyDecision {
+    pub action: PolicyAction,
+    /// The policy's tool name or wildcard
+    pub tool: String,
+    pub pattern: Option<String>,
+}
+
+/// A rule `export_tool_policies` couldn't express in Claude Code's permission syntax
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedRule {
+    pub tool: String,
+    pub pattern: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyExport {
+    /// `{ "permissions": { "allow": [...], "deny": [...], "ask": [...] } }`
+    pub settings: Value,
+    pub skipped: Vec<SkippedRule>,
+    /// Where the rules were merged in, when `write` was set
+    pub written_to: Option<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn policies_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("tool-policies.json"))
+}
+
+/// Stable key for a workspace, so "repo" and "repo/" share one set of policies
+fn workspace_key(working_dir: &str) -> String {
+    std::fs::canonicalize(working_dir)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| working_dir.trim_end_matches('/').to_string())
+}
+
+/// Whether a tool name matches a policy's name, where `*` matches any run of characters
+fn tool_matches(pattern: &str, tool: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == tool;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !tool.starts_with(first) || tool.len() < first.len() + last.len() || !tool.ends_with(last) {
+        return false;
+    }
+    let mut rest = &tool[first.len()..tool.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// The input a rule's pattern is matched against
+fn rule_subject(tool: &str, input: &Value) -> String {
+    match stream::tool_subject(tool, input) {
+        Some(subject) => subject.to_string(),
+        None => input.to_string(),
+    }
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))
+}
+
+/// The decision the policies make for a tool use: deny over ask over allow across every
+/// matching rule. None when no rule matches (the prompt goes to the user as usual).
+pub fn evaluate(policies: &[ToolPolicy], tool: &str, input: &Value) -> Option<PolicyDecision> {
+    let subject = rule_subject(tool, input);
+    let mut decision: Option<PolicyDecision> = None;
+    for policy in policies.iter().filter(|p| tool_matches(&p.tool, tool)) {
+        for rule in &policy.rules {
+            let matched = match &rule.pattern {
+                // Patterns are checked when saved; one that no longer compiles matches nothing
+                Some(pattern) => compile(pattern).is_ok_and(|re| re.is_match(&subject)),
+                None => true,
+            };
+            if !matched {
+                continue;
+            }
+            let rank = |action: PolicyAction| match action {
+                PolicyAction::Deny => 2,
+                PolicyAction::Ask => 1,
+                PolicyAction::Allow => 0,
+            };
+            if decision.as_ref().is_none_or(|d| rank(rule.action) > rank(d.action)) {
+                decision = Some(PolicyDecision {
+                    action: rule.action,
+                    tool: policy.tool.clone(),
+                    pattern: rule.pattern.clone(),
+                });
+            }
+        }
+    }
+    decision
+}
+
+/// Literal prefixes a regex like `^npm (test|run lint)` accepts, for Claude Code's
+/// `Bash(prefix:*)` rules; `exact` when it ends in `$`. None for anything beyond literals
+/// and one level of alternation groups.
+fn regex_prefixes(pattern: &str) -> Option<(Vec<String>, bool)> {
+    let body = pattern.strip_prefix('^')?;
+    let (body, exact) = match body.strip_suffix('$') {
+        Some(body) if !body.ends_with('\\') => (body, true),
+        _ => (body, false),
+    };
+    let mut prefixes = vec![String::new()];
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        let alternatives: Vec<String> = match c {
+            '\\' => {
+                let escaped = chars.next()?;
+                if escaped.is_ascii_alphanumeric() {
+                    return None;
+                }
+                vec![escaped.to_string()]
+            }
+            '(' => {
+                if chars.peek() == Some(&'?') {
+                    chars.next();
+                    if chars.next() != Some(':') {
+                        return None;
+                    }
+                }
+                let mut group = String::new();
+                loop {
+                    match chars.next()? {
+                        ')' => break,
+                        '\\' => {
+                            let escaped = chars.next()?;
+                            if escaped.is_ascii_alphanumeric() {
+                                return None;
+                            }
+                            group.push(escaped);
+                        }
+                        c if "()[]{}.*+?^$".contains(c) => return None,
+                        c => group.push(c),
+                    }
+                }
+                group.split('|').map(str::to_string).collect()
+            }
+            c if "|)[]{}.*+?^$".contains(c) => return None,
+            c => vec![c.to_string()],
+        };
+        prefixes = prefixes
+            .iter()
+            .flat_map(|prefix| alternatives.iter().map(move |alt| format!("{}{}", prefix, alt)))
+            .collect();
+    }
+    prefixes.retain(|p| !p.is_empty());
+    (!prefixes.is_empty()).then_some((prefixes, exact))
+}
+
+/// Claude Code permission entries for one rule, or why it can't be expressed
+fn claude_entries(tool: &str, rule: &ToolRule) -> Result<Vec<String>, String> {
+    let tool = match tool.strip_suffix("__*") {
+        // Claude Code names a whole MCP server as "mcp__server"
+        Some(server) if server.starts_with("mcp__") && !server.contains('*') => server.to_string(),
+        _ if tool.contains('*') => return Err("Claude Code rules can't use tool-name wildcards".to_string()),
+        _ => tool.to_string(),
+    };
+    let Some(pattern) = &rule.pattern else {
+        return Ok(vec![tool]);
+    };
+    if tool != "Bash" {
+        return Err("only Bash command patterns can be converted".to_string());
+    }
+    let (prefixes, exact) = regex_prefixes(pattern)
+        .ok_or_else(|| "the pattern isn't an anchored literal prefix (like ^npm (test|run lint))".to_string())?;
+    Ok(prefixes
+        .into_iter()
+        .map(|prefix| match exact {
+            true => format!("Bash({})", prefix),
+            false => format!("Bash({}:*)", prefix.trim_end()),
+        })
+        .collect())
+}
+
+/// The policies as a `.claude/settings.json` permissions block
+fn export_settings(policies: &[ToolPolicy]) -> (Value, Vec<SkippedRule>) {
+    let mut lists: [(&str, PolicyAction, Vec<String>); 3] = [
+        ("allow", PolicyAction::Allow, Vec::new()),
+        ("deny", PolicyAction::Deny, Vec::new()),
+        ("ask", PolicyAction::Ask, Vec::new()),
+    ];
+    let mut skipped = Vec::new();
+    for policy in policies {
+        for rule in &policy.rules {
+            match claude_entries(&policy.tool, rule) {
+                Ok(entries) => {
+                    if let Some((_, _, list)) = lists.iter_mut().find(|(_, action, _)| *action == rule.action) {
+                        for entry in entries {
+                            if !list.contains(&entry) {
+                                list.push(entry);
+                            }
+                        }
+                    }
+                }
+                Err(reason) => skipped.push(SkippedRule {
+                    tool: policy.tool.clone(),
+                    pattern: rule.pattern.clone(),
+                    reason,
+                }),
+            }
+        }
+    }
+    let mut permissions = Map::new();
+    for (name, _, list) in lists {
+        if !list.is_empty() {
+            permissions.insert(name.to_string(), Value::from(list));
+        }
+    }
+    (serde_json::json!({ "permissions": permissions }), skipped)
+}
+
+/// Merge exported permission lists into a workspace's `.claude/settings.json`, keeping
+/// everything already there
+fn merge_into_settings_file(path: &Path, exported: &Value) -> Result<(), String> {
+    let mut settings: Value = match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Value::Object(Map::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let root = settings
+        .as_object_mut()
+        .ok_or_else(|| format!("{} is not a JSON object", path.display()))?;
+    let permissions = root
+        .entry("permissions")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| format!("\"permissions\" in {} is not an object", path.display()))?;
+    for (name, entries) in exported["permissions"].as_object().into_iter().flatten() {
+        let list = permissions
+            .entry(name.clone())
+            .or_insert_with(|| Value::Array(Vec::new()))
+            .as_array_mut()
+            .ok_or_else(|| format!("\"permissions.{}\" in {} is not a list", name, path.display()))?;
+        for entry in entries.as_array().into_iter().flatten() {
+            if !list.contains(entry) {
+                list.push(entry.clone());
+            }
+        }
+    }
+    let mut content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+    content.push('\n');
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fsutil::write_atomic(path, content.as_bytes())
+}
+
+/// A workspace's policies
+pub async fn load_policies(app: &tauri::AppHandle, working_dir: &str) -> Result<Vec<ToolPolicy>, String> {
+    let mut file = POLICIES_FILE.read(app, policies_path(app)?).await?;
+    Ok(file.workspaces.remove(&workspace_key(working_dir)).unwrap_or_default())
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Get a workspace's tool policies
+#[tauri::command]
+pub async fn get_tool_policies(app: tauri::AppHandle, working_dir: String) -> Result<Vec<ToolPolicy>, String> {
+    load_policies(&app, &working_dir).await
+}
+
+/// Replace the rules for one tool name (or wildcard) in a workspace; an empty list removes it.
+/// Returns the workspace's policies.
+#[tauri::command]
+pub async fn set_tool_policy(
+    app: tauri::AppHandle,
+    working_dir: String,
+    tool: String,
+    rules: Vec<ToolRule>,
+) -> Result<Vec<ToolPolicy>, String> {
+    let tool = tool.trim().to_string();
+    if tool.is_empty() {
+        return Err("Tool name cannot be empty".to_string());
+    }
+    let mut rules = rules;
+    for rule in rules.iter_mut() {
+        rule.pattern = rule.pattern.take().filter(|p| !p.trim().is_empty());
+        if let Some(pattern) = &rule.pattern {
+            compile(pattern)?;
+        }
+    }
+
+    let key = workspace_key(&working_dir);
+    let store = POLICIES_FILE.lock(&app, policies_path(&app)?).await;
+    let mut file = store.load().await?;
+    let policies = file.workspaces.entry(key.clone()).or_default();
+    match (policies.iter().position(|p| p.tool == tool), rules.is_empty()) {
+        (Some(at), true) => {
+            policies.remove(at);
+        }
+        (Some(at), false) => policies[at].rules = rules,
+        (None, false) => policies.push(ToolPolicy { tool, rules }),
+        (None, true) => {}
+    }
+    let result = policies.clone();
+    if result.is_empty() {
+        file.workspaces.remove(&key);
+    }
+    store.save(&file).await?;
+    Ok(result)
+}
+
+/// What a workspace's policies decide for a tool use (None: no rule matches, ask the user)
+#[tauri::command]
+pub async fn evaluate_tool_policy(
+    app: tauri::AppHandle,
+    working_dir: String,
+    tool: String,
+    input: Value,
+) -> Result<Option<PolicyDecision>, String> {
+    Ok(evaluate(&load_policies(&app, &working_dir).await?, &tool, &input))
+}
+
+/// Convert a workspace's policies to Claude Code's `.claude/settings.json` permissions, and with
+/// `write` merge them into that file in the workspace
+#[tauri::command]
+pub async fn export_tool_policies(
+    app: tauri::AppHandle,
+    working_dir: String,
+    write: Option<bool>,
+) -> Result<PolicyExport, String> {
+    let policies = load_policies(&app, &working_dir).await?;
+    let (settings, skipped) = export_settings(&policies);
+    let written_to = match write {
+        Some(true) => {
+            let path = Path::new(&working_dir).join(".claude").join("settings.json");
+            let exported = settings.clone();
+            let target = path.clone();
+            tokio::task::spawn_blocking(move || merge_into_settings_file(&target, &exported))
+                .await
+                .map_err(|e| format!("Failed to write settings: {}", e))??;
+            Some(path.to_string_lossy().to_string())
+        }
+        _ => None,
+    };
+    Ok(PolicyExport {
+        settings,
+        skipped,
+        written_to,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn policy(tool: &str, rules: &[(PolicyAction, Option<&str>)]) -> ToolPolicy {
+        ToolPolicy {
+            tool: tool.to_string(),
+            rules: rules
+                .iter()
+                .map(|(action, pattern)| ToolRule {
+                    action: *action,
+                    pattern: pattern.map(str::to_string),
+                })
+                .collect(),
+        }
+    }
+
+    fn action(policies: &[ToolPolicy], tool: &str, input: Value) -> Option<PolicyAction> {
+        evaluate(policies, tool, &input).map(|d| d.action)
+    }
+
+    #[test]
+    fn tool_name_wildcards() {
+        let cases = [
+            ("Bash", "Bash", true),
+            ("Bash", "bash", false),
+            ("Bash", "BashOutput", false),
+            ("*", "anything", true),
+            ("Web*", "WebFetch", true),
+            ("Web*", "WebSearch", true),
+            ("Web*", "Write", false),
+            ("*Edit", "MultiEdit", true),
+            ("*Edit", "Edit", true),
+            ("mcp__github__*", "mcp__github__create_issue", true),
+            ("mcp__github__*", "mcp__github__", true),
+            ("mcp__github__*", "mcp__gitlab__create_issue", false),
+            ("mcp__*__search", "mcp__docs__search", true),
+            ("mcp__*__search", "mcp__docs__search_all", false),
+            ("a*a", "a", false),
+            ("a*b*c", "abc", true),
+            ("a*b*c", "acb", false),
+        ];
+        for (pattern, tool, expected) in cases {
+            assert_eq!(tool_matches(pattern, tool), expected, "{} vs {}", pattern, tool);
+        }
+    }
+
+    #[test]
+    fn patterns_match_the_main_input() {
+        let policies = [
+            policy("Bash", &[(PolicyAction::Allow, Some(r"^(npm|pnpm) (test|run lint)\b"))]),
+            policy("Read", &[(PolicyAction::Deny, Some(r"(^|/)\.env(\.|$)"))]),
+            policy("mcp__db__query", &[(PolicyAction::Ask, Some(r#""sql":"\s*(?i:drop|delete)"#))]),
+        ];
+        assert_eq!(action(&policies, "Bash", json!({ "command": "npm test -- --watch" })), Some(PolicyAction::Allow));
+        assert_eq!(action(&policies, "Bash", json!({ "command": "pnpm run lint" })), Some(PolicyAction::Allow));
+        assert_eq!(action(&policies, "Bash", json!({ "command": "echo ok && npm test" })), None);
+        assert_eq!(action(&policies, "Bash", json!({ "description": "npm test" })), None);
+        assert_eq!(action(&policies, "Read", json!({ "file_path": "/app/.env.local" })), Some(PolicyAction::Deny));
+        assert_eq!(action(&policies, "Read", json!({ "file_path": "/app/src/env.rs" })), None);
+        // Tools without a known subject are matched against their input JSON
+        assert_eq!(action(&policies, "mcp__db__query", json!({ "sql": "DROP TABLE users" })), Some(PolicyAction::Ask));
+        assert_eq!(action(&policies, "mcp__db__query", json!({ "sql": "select 1" })), None);
+    }
+
+    #[test]
+    fn deny_beats_ask_beats_allow_in_any_order() {
+        let allow_all = policy("*", &[(PolicyAction::Allow, None)]);
+        let ask_git = policy("Bash", &[(PolicyAction::Ask, Some("^git "))]);
+        let deny_push = policy("Bash", &[(PolicyAction::Deny, Some("^git push"))]);
+        let orders = [
+            vec![allow_all.clone(), ask_git.clone(), deny_push.clone()],
+            vec![deny_push.clone(), ask_git.clone(), allow_all.clone()],
+            vec![ask_git.clone(), deny_push.clone(), allow_all.clone()],
+        ];
+        for policies in &orders {
+            let decision = evaluate(policies, "Bash", &json!({ "command": "git push --force" })).unwrap();
+            assert_eq!(
+                decision,
+                PolicyDecision {
+                    action: PolicyAction::Deny,
+                    tool: "Bash".to_string(),
+                    pattern: Some("^git push".to_string()),
+                }
+            );
+            assert_eq!(action(policies, "Bash", json!({ "command": "git status" })), Some(PolicyAction::Ask));
+            assert_eq!(action(policies, "Bash", json!({ "command": "ls" })), Some(PolicyAction::Allow));
+            assert_eq!(action(policies, "Read", json!({ "file_path": "a" })), Some(PolicyAction::Allow));
+        }
+
+        // The first of equally strong rules is the one reported
+        let both = [policy("mcp__github__*", &[(PolicyAction::Deny, None)]), policy("mcp__*", &[(PolicyAction::Deny, None)])];
+        assert_eq!(evaluate(&both, "mcp__github__merge", &json!({})).unwrap().tool, "mcp__github__*");
+        assert_eq!(evaluate(&both, "mcp__slack__post", &json!({})).unwrap().tool, "mcp__*");
+    }
+
+    #[test]
+    fn invalid_saved_pattern_matches_nothing() {
+        let policies = [
+            policy("Bash", &[(PolicyAction::Deny, Some("(unclosed"))]),
+            policy("Bash", &[(PolicyAction::Allow, Some("^ls"))]),
+        ];
+        assert_eq!(action(&policies, "Bash", json!({ "command": "ls (unclosed" })), Some(PolicyAction::Allow));
+        assert!(compile("(unclosed").unwrap_err().contains("Invalid pattern '(unclosed'"));
+    }
+
+    #[test]
+    fn export_converts_what_claude_code_can_express() {
+        let policies = [
+            policy("Bash", &[(PolicyAction::Allow, Some("^npm (test|run lint)")), (PolicyAction::Deny, Some(r"^rm -rf /$"))]),
+            policy("Bash", &[(PolicyAction::Allow, Some("npm test"))]),
+            policy("mcp__github__*", &[(PolicyAction::Ask, None)]),
+            policy("Web*", &[(PolicyAction::Allow, None)]),
+            policy("Read", &[(PolicyAction::Deny, Some(r"\.env"))]),
+        ];
+        let (settings, skipped) = export_settings(&policies);
+        assert_eq!(
+            settings,
+            json!({ "permissions": {
+                "allow": ["Bash(npm test:*)", "Bash(npm run lint:*)"],
+                "deny": ["Bash(rm -rf /)"],
+                "ask": ["mcp__github"],
+            }})
+        );
+        let reasons: Vec<(&str, Option<&str>)> = skipped.iter().map(|s| (s.tool.as_str(), s.pattern.as_deref())).collect();
+        assert_eq!(reasons, [("Bash", Some("npm test")), ("Web*", None), ("Read", Some(r"\.env"))]);
+    }
+}
@@ -0,0 +1,627 @@
+// mensa - Transcript Module
+// Parses Claude Code session jsonl transcripts into grouped user/assistant messages
+
+use crate::{cancel, stream, tool_output};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMessage {
+    pub role: String,
+    pub content: String,
+    pub timestamp: String,
+    pub tools: Option<Vec<SessionToolExecution>>,
+    pub blocks: Option<Vec<SessionBlock>>,
+    /// The transcript lines this message was built from, when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_span: Option<Vec<SourceRange>>,
+}
+
+/// A run of consecutive transcript lines: 1-based inclusive line numbers, and byte offsets
+/// from the start of the first line to the end of the last (newline excluded)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRange {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SessionBlock {
+    Text { content: String, order: u64 },
+    Tool {
+        #[serde(rename = "toolId")]
+        tool_id: String,
+        order: u64
+    },
+    Image {
+        #[serde(rename = "mediaType")]
+        media_type: String,
+        data: String,
+        order: u64
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionToolExecution {
+    pub id: String,
+    pub tool: String,
+    pub tool_use_id: Option<String>,
+    pub status: String,
+    pub input: Option<String>,
+    pub output: Option<String>,
+    /// Whether `output` is only a preview (fetch the rest with get_tool_output)
+    pub output_truncated: bool,
+    /// Size of the full output in bytes
+    pub output_total_bytes: Option<usize>,
+    pub started_at: String,
+    pub completed_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Vec<stream::HookEvent>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Cut tool outputs longer than `max_bytes` down to a preview, keeping their full size
+pub fn truncate_tool_outputs(messages: &mut [SessionMessage], max_bytes: usize) {
+    let tools = messages.iter_mut().filter_map(|m| m.tools.as_mut()).flatten();
+    for tool in tools {
+        let cut = match tool.output.as_deref().and_then(|output| tool_output::preview(output, max_bytes)) {
+            Some(preview) => preview.len(),
+            None => continue,
+        };
+        if let Some(output) = tool.output.as_mut() {
+            output.truncate(cut);
+        }
+        tool.output_truncated = true;
+    }
+}
+
+/// Record a hook run on the tool execution it belongs to (dropped if the tool isn't in the transcript)
+fn attach_hook_event(
+    messages: &mut [SessionMessage],
+    tool_index: &HashMap<String, (usize, usize)>,
+    hook: stream::HookEvent,
+) {
+    let tool = hook
+        .tool_use_id
+        .as_ref()
+        .and_then(|id| tool_index.get(id))
+        .and_then(|&(msg_idx, tool_idx)| messages.get_mut(msg_idx)?.tools.as_mut()?.get_mut(tool_idx));
+    if let Some(tool) = tool {
+        tool.hooks.get_or_insert_with(Vec::new).push(hook);
+    }
+}
+
+/// Record that `line` contributed to `message`, extending its last range when they're adjacent
+fn add_source_line(message: &mut SessionMessage, line: SourceRange) {
+    let Some(spans) = message.source_span.as_mut() else {
+        return;
+    };
+    match spans.last_mut() {
+        Some(last) if last.end_line + 1 == line.start_line => {
+            last.end_line = line.end_line;
+            last.end_byte = line.end_byte;
+        }
+        _ => spans.push(line),
+    }
+}
+
+/// Parse a session jsonl transcript into grouped user/assistant messages
+pub fn parse_session_messages(content: &str) -> Result<Vec<SessionMessage>, String> {
+    Ok(parse_session_messages_until(content.as_bytes(), &cancel::CancellationToken::default(), false)?.unwrap_or_default())
+}
+
+/// parse_session_messages that stops early (returning None) once `token` is cancelled.
+/// With `include_source_spans`, each message carries the transcript lines it came from.
+pub fn parse_session_messages_until<R: BufRead>(
+    mut reader: R,
+    token: &cancel::CancellationToken,
+    include_source_spans: bool,
+) -> Result<Option<Vec<SessionMessage>>, String> {
+    let mut messages: Vec<SessionMessage> = Vec::new();
+    let mut tool_index: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut anonymous_tool_counter: u32 = 0;
+    let mut line_start = 0;
+
+    let mut raw_line = Vec::new();
+
+    for line_number in 0.. {
+        raw_line.clear();
+        let read = reader
+            .read_until(b'\n', &mut raw_line)
+            .map_err(|e| format!("Failed to read session: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        let bytes = raw_line.strip_suffix(b"\n").unwrap_or(&raw_line);
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        let source = SourceRange {
+            start_line: line_number + 1,
+            end_line: line_number + 1,
+            start_byte: line_start,
+            end_byte: line_start + bytes.len(),
+        };
+        line_start += read;
+        // A character split by interleaved writes is replaced rather than failing the transcript
+        let line = String::from_utf8_lossy(bytes);
+        let line = line.as_ref();
+
+        if line_number % cancel::CHECK_INTERVAL_LINES == 0 && token.is_cancelled() {
+            return Ok(None);
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let parsed: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        let msg_type = parsed.get("type").and_then(|v| v.as_str()).unwrap_or("");
+
+        // Hook runs are logged as separate entries; attach them to the tool they ran for
+        if let Some(hook) = stream::hook_event_from_transcript(&parsed) {
+            let owner = hook.tool_use_id.as_ref().and_then(|id| tool_index.get(id));
+            if let Some(message) = owner.and_then(|&(msg_idx, _)| messages.get_mut(msg_idx)) {
+                add_source_line(message, source);
+            }
+            attach_hook_event(&mut messages, &tool_index, hook);
+            continue;
+        }
+
+        // Only process user/assistant messages
+        if msg_type != "user" && msg_type != "assistant" {
+            continue;
+        }
+
+        let timestamp = parsed.get("timestamp")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        let message = match parsed.get("message") {
+            Some(m) => m,
+            None => continue,
+        };
+
+        let role = message.get("role")
+            .and_then(|v| v.as_str())
+            .unwrap_or(msg_type)
+            .to_string();
+
+        let content_value = message.get("content");
+        let mut content_texts: Vec<String> = Vec::new();
+        let mut tools: Vec<SessionToolExecution> = Vec::new();
+        // Orders are assigned by order_blocks once messages are grouped
+        let mut blocks: Vec<SessionBlock> = Vec::new();
+        let mut tool_id_mappings: Vec<(String, usize)> = Vec::new();
+
+        match content_value {
+            Some(Value::String(s)) if !s.trim().is_empty() => {
+                content_texts.push(s.clone());
+                blocks.push(SessionBlock::Text { content: s.clone(), order: 0 });
+            }
+            Some(Value::Array(arr)) => {
+                for block in arr {
+                    let block_type = block.get("type").and_then(|v| v.as_str()).unwrap_or("");
+                    match block_type {
+                        "text" => {
+                            if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+                                if !text.trim().is_empty() {
+                                    content_texts.push(text.to_string());
+                                    blocks.push(SessionBlock::Text { content: text.to_string(), order: 0 });
+                                }
+                            }
+                        }
+                        "image" => {
+                            // Handle image blocks with base64 data
+                            if let Some(source) = block.get("source") {
+                                let media_type = source.get("media_type")
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or("image/png")
+                                    .to_string();
+                                if let Some(data) = source.get("data").and_then(|v| v.as_str()) {
+                                    blocks.push(SessionBlock::Image {
+                                        media_type,
+                                        data: data.to_string(),
+                                        order: 0
+                                    });
+                                }
+                            }
+                        }
+                        "tool_use" if msg_type == "assistant" => {
+                            let name = block.get("name")
+                                .and_then(|v| v.as_str())
+                                .unwrap_or("unknown")
+                                .to_string();
+                            let tool_use_id = block.get("id").and_then(|v| v.as_str()).map(|s| s.to_string());
+                            let input_value = block.get("input");
+                            let input = match input_value {
+                                Some(Value::String(s)) => Some(s.clone()),
+                                Some(v) => serde_json::to_string_pretty(v).ok(),
+                                None => None,
+                            };
+
+                            let tool_id = tool_use_id.clone().unwrap_or_else(|| {
+                                anonymous_tool_counter += 1;
+                                format!("tool-{}", anonymous_tool_counter)
+                            });
+
+                            let tool_entry = SessionToolExecution {
+                                id: tool_id.clone(),
+                                tool: name,
+                                tool_use_id: tool_use_id.clone(),
+                                status: "running".to_string(),
+                                input,
+                                output: None,
+                                output_truncated: false,
+                                output_total_bytes: None,
+                                started_at: timestamp.clone(),
+                                completed_at: None,
+                                hooks: None,
+                            };
+
+                            tools.push(tool_entry);
+                            blocks.push(SessionBlock::Tool { tool_id: tool_id.clone(), order: 0 });
+                            if let Some(id) = tool_use_id {
+                                tool_id_mappings.push((id, tools.len() - 1));
+                            }
+                        }
+                        "tool_result" if msg_type == "user" => {
+                            if let Some(tool_use_id) = block.get("tool_use_id").and_then(|v| v.as_str()) {
+                                if let Some((msg_idx, tool_idx)) = tool_index.get(tool_use_id).cloned() {
+                                    if let Some(message) = messages.get_mut(msg_idx) {
+                                        add_source_line(message, source);
+                                        if let Some(message_tools) = message.tools.as_mut() {
+                                            if let Some(tool) = message_tools.get_mut(tool_idx) {
+                                                let output_value = block.get("content");
+                                                let output = tool_output::tool_result_text(output_value);
+
+                                                let is_error = block.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false);
+                                                tool.status = if is_error { "error".to_string() } else { "completed".to_string() };
+                                                tool.output_total_bytes = output.as_ref().map(|o| o.len());
+                                                tool.output = output;
+                                                tool.completed_at = Some(timestamp.clone());
+                                                if let Some(hook) = stream::hook_event_from_tool_result(Some(tool_use_id), output_value.unwrap_or(&Value::Null)) {
+                                                    tool.hooks.get_or_insert_with(Vec::new).push(hook);
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let content_text = content_texts.join("\n");
+        let has_tools = !tools.is_empty();
+        let has_blocks = !blocks.is_empty();
+
+        // Skip empty messages (no text, no tools, no blocks like images)
+        if content_text.trim().is_empty() && !has_tools && !has_blocks {
+            continue;
+        }
+
+        // Group consecutive assistant messages
+        if role == "assistant" && !messages.is_empty() {
+            let last_idx = messages.len() - 1;
+            if messages[last_idx].role == "assistant" {
+                let last = messages
+                    .get_mut(last_idx)
+                    .ok_or_else(|| "Failed to read previous assistant message".to_string())?;
+
+                if !content_text.trim().is_empty() {
+                    if !last.content.is_empty() {
+                        last.content.push('\n');
+                    }
+                    last.content.push_str(&content_text);
+                }
+
+                if has_tools {
+                    let existing_len = last.tools.as_ref().map(|t| t.len()).unwrap_or(0);
+                    if last.tools.is_none() {
+                        last.tools = Some(Vec::new());
+                    }
+                    if let Some(last_tools) = last.tools.as_mut() {
+                        last_tools.extend(tools);
+                    }
+                    for (id, idx) in tool_id_mappings {
+                        tool_index.insert(id, (last_idx, existing_len + idx));
+                    }
+                }
+
+                if has_blocks {
+                    if last.blocks.is_none() {
+                        last.blocks = Some(Vec::new());
+                    }
+                    if let Some(last_blocks) = last.blocks.as_mut() {
+                        last_blocks.extend(blocks);
+                    }
+                }
+
+                last.timestamp = timestamp;
+                add_source_line(last, source);
+                continue;
+            }
+        }
+
+        // Group consecutive user messages
+        if role == "user" && !messages.is_empty() {
+            let last_idx = messages.len() - 1;
+            if messages[last_idx].role == "user" {
+                let last = messages
+                    .get_mut(last_idx)
+                    .ok_or_else(|| "Failed to read previous user message".to_string())?;
+
+                if !content_text.trim().is_empty() {
+                    if !last.content.is_empty() {
+                        last.content.push('\n');
+                    }
+                    last.content.push_str(&content_text);
+                }
+
+                if has_blocks {
+                    if last.blocks.is_none() {
+                        last.blocks = Some(Vec::new());
+                    }
+                    if let Some(last_blocks) = last.blocks.as_mut() {
+                        last_blocks.extend(blocks);
+                    }
+                }
+
+                last.timestamp = timestamp;
+                add_source_line(last, source);
+                continue;
+            }
+        }
+
+        let msg_idx = messages.len();
+        messages.push(SessionMessage {
+            role,
+            content: content_text,
+            timestamp,
+            tools: if has_tools { Some(tools) } else { None },
+            blocks: if blocks.is_empty() { None } else { Some(blocks) },
+            source_span: include_source_spans.then(|| vec![source]),
+        });
+        for (id, idx) in tool_id_mappings {
+            tool_index.insert(id, (msg_idx, idx));
+        }
+    }
+
+    for message in &mut messages {
+        order_blocks(message);
+    }
+    Ok(Some(messages))
+}
+
+/// Number a grouped message's blocks 1..n in transcript order (so each tool follows the text
+/// that introduced it) and drop a text block repeating the one right before it, which some SDK
+/// versions log twice
+fn order_blocks(message: &mut SessionMessage) {
+    let Some(blocks) = message.blocks.as_mut() else {
+        return;
+    };
+    let before = blocks.len();
+    blocks.dedup_by(|next, previous| match (previous, next) {
+        (SessionBlock::Text { content: a, .. }, SessionBlock::Text { content: b, .. }) => a == b,
+        _ => false,
+    });
+    for (index, block) in blocks.iter_mut().enumerate() {
+        let (SessionBlock::Text { order, .. } | SessionBlock::Tool { order, .. } | SessionBlock::Image { order, .. }) = block;
+        *order = index as u64 + 1;
+    }
+    if blocks.len() < before {
+        let texts: Vec<&str> = blocks
+            .iter()
+            .filter_map(|block| match block {
+                SessionBlock::Text { content, .. } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        message.content = texts.join("\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, fixture_path};
+
+    fn parse_with_spans(content: &[u8]) -> Vec<SessionMessage> {
+        parse_session_messages_until(content, &cancel::CancellationToken::default(), true)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn block_kinds(message: &SessionMessage) -> Vec<(&'static str, u64)> {
+        message
+            .blocks
+            .iter()
+            .flatten()
+            .map(|block| match block {
+                SessionBlock::Text { order, .. } => ("text", *order),
+                SessionBlock::Tool { order, .. } => ("tool", *order),
+                SessionBlock::Image { order, .. } => ("image", *order),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn groups_turns_and_attaches_tool_results() {
+        let messages = parse_session_messages(&fixture("sessions/basic.jsonl")).unwrap();
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user", "assistant"]);
+
+        assert_eq!(messages[0].content, "Why does the parser skip blank lines?");
+        // The tool-result-only user record folds into the assistant turn around it
+        let turn = &messages[1];
+        assert_eq!(turn.content, "Let me look at the parser.\nIt skips them on purpose.");
+        assert_eq!(turn.timestamp, "2026-01-05T10:00:05.000Z");
+        assert_eq!(block_kinds(turn), [("text", 1), ("tool", 2), ("text", 3)]);
+
+        let read = &turn.tools.as_ref().unwrap()[0];
+        assert_eq!(read.tool, "Read");
+        assert_eq!(read.status, "completed");
+        assert_eq!(read.output.as_deref(), Some("fn parse() {}"));
+        assert_eq!(read.output_total_bytes, Some(13));
+        assert_eq!(read.completed_at.as_deref(), Some("2026-01-05T10:00:03.000Z"));
+        let hooks = read.hooks.as_ref().unwrap();
+        assert_eq!(hooks.len(), 1);
+        assert_eq!(hooks[0].hook, "PostToolUse");
+        assert_eq!(hooks[0].decision, "ran");
+    }
+
+    #[test]
+    fn keeps_images_and_failed_tools() {
+        let messages = parse_session_messages(&fixture("sessions/basic.jsonl")).unwrap();
+        assert_eq!(block_kinds(&messages[2]), [("text", 1), ("image", 2)]);
+        match &messages[2].blocks.as_ref().unwrap()[1] {
+            SessionBlock::Image { media_type, data, .. } => {
+                assert_eq!(media_type, "image/png");
+                assert_eq!(data, "iVBORw0KGgo=");
+            }
+            other => panic!("expected an image, got {:?}", other),
+        }
+
+        let bash = &messages[3].tools.as_ref().unwrap()[0];
+        assert_eq!(bash.status, "error");
+        assert_eq!(bash.output.as_deref(), Some("exit 1"));
+        assert!(messages[3].content.is_empty());
+    }
+
+    #[test]
+    fn source_spans_cover_every_contributing_line() {
+        let messages = parse_with_spans(fixture("sessions/basic.jsonl").as_bytes());
+        let lines = |m: &SessionMessage| -> Vec<(usize, usize)> {
+            m.source_span.iter().flatten().map(|r| (r.start_line, r.end_line)).collect()
+        };
+        assert_eq!(lines(&messages[0]), [(2, 2)]);
+        // Line 5 is not JSON and line 8 is blank; the hook (6) and tool result (4) count
+        assert_eq!(lines(&messages[1]), [(3, 4), (6, 7)]);
+        assert_eq!(lines(&messages[2]), [(9, 9)]);
+        assert_eq!(lines(&messages[3]), [(10, 11)]);
+    }
+
+    #[test]
+    fn crlf_and_invalid_utf8_keep_file_offsets() {
+        let raw = std::fs::read(fixture_path("sessions/crlf_invalid_utf8.jsonl")).unwrap();
+        let messages = parse_with_spans(&raw);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "café \u{fffd} broken");
+
+        let first_line_end = raw.windows(2).position(|w| w == b"\r\n").unwrap();
+        let first = messages[0].source_span.as_ref().unwrap()[0];
+        assert_eq!((first.start_byte, first.end_byte), (0, first_line_end));
+        let second = messages[1].source_span.as_ref().unwrap()[0];
+        assert_eq!((second.start_byte, second.end_byte), (first_line_end + 2, raw.len()));
+        assert_eq!(&raw[second.start_byte..second.start_byte + 1], b"{");
+    }
+
+    #[test]
+    fn cancelled_parse_returns_none() {
+        let token = cancel::CancellationToken::default();
+        token.cancel();
+        let content = fixture("sessions/basic.jsonl");
+        assert!(parse_session_messages_until(content.as_bytes(), &token, false).unwrap().is_none());
+    }
+
+    #[test]
+    fn truncates_long_tool_outputs_to_a_preview() {
+        let mut messages = parse_session_messages(&fixture("sessions/basic.jsonl")).unwrap();
+        truncate_tool_outputs(&mut messages, 4);
+        let read = &messages[1].tools.as_ref().unwrap()[0];
+        assert!(read.output_truncated);
+        assert!(read.output.as_ref().unwrap().len() <= 4);
+        assert_eq!(read.output_total_bytes, Some(13));
+    }
+
+    fn block_texts(message: &SessionMessage) -> Vec<String> {
+        message
+            .blocks
+            .iter()
+            .flatten()
+            .map(|block| match block {
+                SessionBlock::Text { content, .. } => content.clone(),
+                SessionBlock::Tool { tool_id, .. } => format!("[{}]", tool_id),
+                SessionBlock::Image { .. } => "[image]".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn text_and_tools_alternating_across_records_keep_transcript_order() {
+        let messages = parse_session_messages(&fixture("sessions/interleaved.jsonl")).unwrap();
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["user", "assistant", "user", "assistant"]);
+
+        // Six assistant records and the tool results between them make one turn
+        let turn = &messages[1];
+        assert_eq!(
+            block_texts(turn),
+            [
+                "I'll find the loader first.",
+                "[toolu_01]",
+                "Found it in two places.",
+                "[toolu_02]",
+                "[toolu_03]",
+                "Running the tests.",
+                "[toolu_04]",
+                "Running the tests.",
+                "[toolu_05]",
+                "Done; tests pass.",
+            ]
+        );
+        let orders: Vec<u64> = block_kinds(turn).into_iter().map(|(_, order)| order).collect();
+        assert_eq!(orders, (1..=10).collect::<Vec<u64>>());
+
+        // The repeated record is dropped from the content too; the retry's text, after a tool, is not
+        assert_eq!(
+            turn.content,
+            "I'll find the loader first.\nFound it in two places.\nRunning the tests.\nRunning the tests.\nDone; tests pass."
+        );
+
+        // Results arriving out of order land on their own tools
+        let tools = turn.tools.as_ref().unwrap();
+        let statuses: Vec<(&str, &str)> = tools.iter().map(|t| (t.id.as_str(), t.status.as_str())).collect();
+        assert_eq!(
+            statuses,
+            [("toolu_01", "completed"), ("toolu_02", "completed"), ("toolu_03", "completed"), ("toolu_04", "error"), ("toolu_05", "completed")]
+        );
+        assert_eq!(tools[1].completed_at.as_deref(), Some("2026-02-10T09:00:09.000Z"));
+        assert_eq!(tools[2].completed_at.as_deref(), Some("2026-02-10T09:00:08.000Z"));
+
+        assert_eq!(block_kinds(&messages[3]), [("text", 1)]);
+    }
+
+    #[test]
+    fn ordering_is_the_same_with_source_spans() {
+        let content = fixture("sessions/interleaved.jsonl");
+        let plain = parse_session_messages(&content).unwrap();
+        let spanned = parse_with_spans(content.as_bytes());
+        assert_eq!(block_kinds(&plain[1]), block_kinds(&spanned[1]));
+        // Every line but the second user turn's belongs to the first assistant turn
+        let lines: Vec<(usize, usize)> = spanned[1].source_span.iter().flatten().map(|r| (r.start_line, r.end_line)).collect();
+        assert_eq!(lines, [(2, 15)]);
+    }
+}
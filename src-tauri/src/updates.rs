@@ -0,0 +1,444 @@
+// mensa - Updates Module
+// Checks the release endpoint for a newer mensa, installs it with progress and restarts into it
+
+use crate::cancel::CancellationToken;
+use crate::progress::ProgressReporter;
+use crate::{markdown, proxy, settings, AppState};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, Runtime, State};
+use tauri_plugin_updater::{Update, UpdaterExt};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Longest wait for the update endpoint
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest wait for the whole update download
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Download progress is reported at most once per this many bytes
+const PROGRESS_STEP_BYTES: u64 = 512 * 1024;
+
+/// How long after startup the first background check runs
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(60);
+
+/// How often the background loop wakes to see whether a check is due
+const POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Time between background checks
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Delay before restarting, so the command's reply reaches the window first
+const RESTART_DELAY: Duration = Duration::from_millis(500);
+
+/// The update found by the last check, and whether one is being installed
+#[derive(Default)]
+pub struct UpdateState {
+    pending: Mutex<Option<Update>>,
+    installing: AtomicBool,
+}
+
+/// Result of `check_for_updates`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub current_version: String,
+    /// Newer version offered by the endpoint; None when mensa is up to date
+    pub available_version: Option<String>,
+    /// Publish date as the endpoint announced it (RFC 3339)
+    pub date: Option<String>,
+    /// Release notes as markdown
+    pub notes: Option<String>,
+    /// Release notes rendered to sanitized HTML
+    pub notes_html: Option<String>,
+}
+
+/// Error of the update commands
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum UpdateError {
+    /// This build has no update endpoint or signing key configured
+    NotConfigured { message: String },
+    /// The endpoint or the download couldn't be reached, or answered with an error
+    Network { message: String },
+    /// The download doesn't carry a valid signature from mensa's release key; nothing was installed
+    SignatureMismatch { message: String },
+    /// Queries are running; pass `force` to cancel them and install anyway
+    QueriesActive { count: usize, message: String },
+    Failed { message: String },
+}
+
+impl From<String> for UpdateError {
+    fn from(message: String) -> Self {
+        UpdateError::Failed { message }
+    }
+}
+
+impl From<tauri_plugin_updater::Error> for UpdateError {
+    fn from(error: tauri_plugin_updater::Error) -> Self {
+        use tauri_plugin_updater::Error;
+        let message = error.to_string();
+        match error {
+            Error::EmptyEndpoints => UpdateError::NotConfigured { message },
+            Error::Reqwest(_) | Error::Network(_) | Error::Http(_) | Error::ReleaseNotFound => {
+                UpdateError::Network { message }
+            }
+            Error::Minisign(_) | Error::Base64(_) | Error::SignatureUtf8(_) => UpdateError::SignatureMismatch { message },
+            _ => UpdateError::Failed { message },
+        }
+    }
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateError::NotConfigured { message }
+            | UpdateError::Network { message }
+            | UpdateError::SignatureMismatch { message }
+            | UpdateError::QueriesActive { message, .. }
+            | UpdateError::Failed { message } => f.write_str(message),
+        }
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Whether tauri.conf.json gives the updater a public key to verify downloads with
+fn configured(app: &tauri::AppHandle) -> bool {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|config| config.get("pubkey"))
+        .and_then(|pubkey| pubkey.as_str())
+        .is_some_and(|pubkey| !pubkey.trim().is_empty())
+}
+
+/// Ask the endpoint for a newer release, through the configured proxy
+async fn fetch(app: &tauri::AppHandle) -> Result<Option<Update>, UpdateError> {
+    if !configured(app) {
+        return Err(UpdateError::NotConfigured {
+            message: "This build has no update signing key configured".to_string(),
+        });
+    }
+    let proxy = proxy::resolve(app).await?.and_then(|proxy| proxy.https_url());
+    check_endpoint(app, proxy).await
+}
+
+/// Ask `app`'s update endpoint whether it offers a newer release than the running version
+async fn check_endpoint<R: Runtime>(app: &tauri::AppHandle<R>, proxy: Option<String>) -> Result<Option<Update>, UpdateError> {
+    let mut builder = app.updater_builder().timeout(CHECK_TIMEOUT);
+    if let Some(url) = proxy {
+        let url = url.parse().map_err(|e| format!("Invalid proxy {}: {}", url, e))?;
+        builder = builder.proxy(url);
+    }
+    Ok(builder.build()?.check().await?)
+}
+
+fn info(current_version: String, update: Option<&Update>) -> UpdateInfo {
+    let notes = update.and_then(|u| u.body.clone()).filter(|body| !body.trim().is_empty());
+    UpdateInfo {
+        current_version,
+        available_version: update.map(|u| u.version.clone()),
+        date: update.and_then(|u| u.raw_json.get("pub_date")).and_then(|d| d.as_str()).map(String::from),
+        notes_html: notes.as_deref().map(|n| markdown::render(n, &markdown::MarkdownOptions::default()).html),
+        notes,
+    }
+}
+
+/// Check and remember what was found for `install_update`
+async fn check(app: &tauri::AppHandle) -> Result<UpdateInfo, UpdateError> {
+    let update = fetch(app).await?;
+    let info = info(app.package_info().version.to_string(), update.as_ref());
+    if let Ok(mut pending) = app.state::<AppState>().updates.pending.lock() {
+        *pending = update;
+    }
+    Ok(info)
+}
+
+/// QueriesActive when `count` queries run and the caller didn't `force` the restart
+fn refuse_running(count: usize, force: bool) -> Result<(), UpdateError> {
+    if count == 0 || force {
+        return Ok(());
+    }
+    Err(UpdateError::QueriesActive {
+        count,
+        message: format!("{} running {} would be stopped by the restart", count, if count == 1 { "query" } else { "queries" }),
+    })
+}
+
+/// Refuse while queries run, unless `force`: then cancel every group and query first
+async fn ensure_idle(app: &tauri::AppHandle, state: &AppState, force: bool) -> Result<(), UpdateError> {
+    let count = state.active_queries.lock().await.len();
+    refuse_running(count, force)?;
+    if count == 0 {
+        return Ok(());
+    }
+    crate::query_group::cancel_all(&state.groups);
+    crate::query_cancel::stop_all_queries(app).await;
+    Ok(())
+}
+
+/// Download (reporting progress), verify and install the update, then schedule the restart
+async fn install(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    mut update: Update,
+    force: bool,
+    reporter: &ProgressReporter,
+) -> Result<(), UpdateError> {
+    update.timeout = Some(DOWNLOAD_TIMEOUT);
+    let mut received: u64 = 0;
+    let mut reported: u64 = 0;
+    let bytes = update
+        .download(
+            |chunk, total| {
+                received += chunk as u64;
+                if received - reported >= PROGRESS_STEP_BYTES || Some(received) == total {
+                    reported = received;
+                    reporter.update("downloading", Some(received), total, None);
+                }
+            },
+            || reporter.phase("verifying", None),
+        )
+        .await?;
+
+    // A query may have started while the update downloaded
+    ensure_idle(app, state, force).await?;
+    reporter.phase("installing", Some(format!("Installing {}", update.version)));
+    tokio::task::spawn_blocking(move || update.install(bytes))
+        .await
+        .map_err(|e| format!("Failed to install update: {}", e))??;
+
+    let app = app.clone();
+    state.tasks.spawn("restart after update", |_| async move {
+        tokio::time::sleep(RESTART_DELAY).await;
+        app.request_restart();
+    });
+    Ok(())
+}
+
+/// Check once a day while `autoUpdateCheck` is on, emitting `update-available` for each new
+/// version found. Runs until `token` is cancelled.
+pub async fn run_background_checks(app: tauri::AppHandle, token: CancellationToken) {
+    let mut last_check: Option<Instant> = None;
+    let mut announced: Option<String> = None;
+    let mut wait = FIRST_CHECK_DELAY;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = token.cancelled() => break,
+        }
+        wait = POLL_INTERVAL;
+        if last_check.is_some_and(|at| at.elapsed() < CHECK_INTERVAL) || !configured(&app) {
+            continue;
+        }
+        let state = app.state::<AppState>();
+        let enabled = settings::load(&app, &state.settings)
+            .await
+            .map(|s| s.auto_update_check)
+            .unwrap_or(false);
+        if !enabled {
+            continue;
+        }
+        last_check = Some(Instant::now());
+        match check(&app).await {
+            Ok(UpdateInfo { available_version: Some(version), current_version, date, notes, notes_html }) => {
+                if announced.as_ref() == Some(&version) {
+                    continue;
+                }
+                let _ = app.emit("update-available", serde_json::json!({
+                    "current_version": current_version,
+                    "version": version,
+                    "date": date,
+                    "notes": notes,
+                    "notes_html": notes_html,
+                }));
+                announced = Some(version);
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("[mensa] Update check failed: {}", e),
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Ask the update endpoint whether a newer mensa exists; `install_update` installs what it found
+#[tauri::command]
+pub async fn check_for_updates(app: tauri::AppHandle) -> Result<UpdateInfo, UpdateError> {
+    check(&app).await
+}
+
+/// Install the update found by the last check (checking again if there was none), reporting
+/// `app-update` progress, then restart. Refuses while queries run unless `force`, which
+/// cancels them first.
+#[tauri::command]
+pub async fn install_update(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    force: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<String, UpdateError> {
+    let force = force.unwrap_or(false);
+    ensure_idle(&app, &state, force).await?;
+    if state.updates.installing.swap(true, Ordering::SeqCst) {
+        return Err("An update is already being installed".to_string().into());
+    }
+
+    let pending = state.updates.pending.lock().ok().and_then(|pending| pending.clone());
+    let update = match pending {
+        Some(update) => Ok(Some(update)),
+        None => fetch(&app).await,
+    };
+    let result = match update {
+        Ok(Some(update)) => {
+            let version = update.version.clone();
+            let reporter = ProgressReporter::start(&app, "app-update", operation_id, None, false);
+            let installed = install(&app, &state, update, force, &reporter).await;
+            reporter.settle(installed).map(|_| version)
+        }
+        Ok(None) => Err("mensa is already up to date".to_string().into()),
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        state.updates.installing.store(false, Ordering::SeqCst);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// An update endpoint on localhost answering every request with `status` and `body`
+    async fn endpoint(status: &'static str, body: String) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/latest.json", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    /// A release manifest as the endpoint publishes it
+    fn release(version: &str) -> String {
+        serde_json::json!({
+            "version": version,
+            "notes": "Faster **diffs**",
+            "pub_date": "2026-10-01T12:00:00Z",
+            "url": "https://example.com/mensa.tar.gz",
+            "signature": "c2lnbmF0dXJl",
+        })
+        .to_string()
+    }
+
+    /// A mock app (running version 0.1.0) whose updater asks `url`
+    fn app(url: &str) -> tauri::App<MockRuntime> {
+        let mut context = mock_context(noop_assets());
+        context.config_mut().plugins.0.insert(
+            "updater".to_string(),
+            serde_json::json!({
+                "pubkey": "dW50cnVzdGVkIGNvbW1lbnQ=",
+                "endpoints": [url],
+                "dangerousInsecureTransportProtocol": true,
+            }),
+        );
+        mock_builder()
+            .plugin(tauri_plugin_updater::Builder::new().build())
+            .build(context)
+            .unwrap()
+    }
+
+    async fn check_against(status: &'static str, body: String) -> Result<Option<Update>, UpdateError> {
+        let url = endpoint(status, body).await;
+        let app = app(&url);
+        check_endpoint(app.handle(), None).await
+    }
+
+    #[tokio::test]
+    async fn newer_releases_are_offered() {
+        let update = check_against("200 OK", release("0.2.0")).await.unwrap().unwrap();
+        let info = info("0.1.0".to_string(), Some(&update));
+        assert_eq!(info.available_version.as_deref(), Some("0.2.0"));
+        assert_eq!(info.date.as_deref(), Some("2026-10-01T12:00:00Z"));
+        assert_eq!(info.notes.as_deref(), Some("Faster **diffs**"));
+        assert!(info.notes_html.unwrap().contains("<strong>diffs</strong>"));
+
+        // Pre-releases compare as semver, so a newer one is offered too
+        let update = check_against("200 OK", release("0.2.0-beta.1")).await.unwrap().unwrap();
+        assert_eq!(update.version, "0.2.0-beta.1");
+    }
+
+    #[tokio::test]
+    async fn same_or_older_releases_are_up_to_date() {
+        for version in ["0.1.0", "0.0.9", "0.1.0-rc.1"] {
+            let update = check_against("200 OK", release(version)).await.unwrap();
+            assert!(update.is_none(), "{} offered over 0.1.0", version);
+        }
+        assert!(check_against("204 No Content", String::new()).await.unwrap().is_none());
+        assert_eq!(info("0.1.0".to_string(), None).available_version, None);
+    }
+
+    #[tokio::test]
+    async fn endpoint_failures_map_to_error_kinds() {
+        let failed = check_against("500 Internal Server Error", "oops".to_string()).await;
+        assert!(matches!(failed, Err(UpdateError::Network { .. })), "{:?}", failed.map(|u| u.is_some()));
+
+        let malformed = check_against("200 OK", r#"{"version": "0.2.0"}"#.to_string()).await;
+        assert!(matches!(malformed, Err(UpdateError::Failed { .. })), "{:?}", malformed.map(|u| u.is_some()));
+
+        // Nothing listens on the port once the listener is dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let app = app(&format!("http://127.0.0.1:{}/latest.json", port));
+        let unreachable = check_endpoint(app.handle(), None).await;
+        assert!(matches!(unreachable, Err(UpdateError::Network { .. })), "{:?}", unreachable.map(|u| u.is_some()));
+    }
+
+    #[test]
+    fn running_queries_block_the_restart_unless_forced() {
+        assert!(refuse_running(0, false).is_ok());
+        assert!(refuse_running(3, true).is_ok());
+        match refuse_running(1, false) {
+            Err(UpdateError::QueriesActive { count, message }) => {
+                assert_eq!(count, 1);
+                assert_eq!(message, "1 running query would be stopped by the restart");
+            }
+            other => panic!("expected QueriesActive, got {:?}", other),
+        }
+        match refuse_running(3, false) {
+            Err(UpdateError::QueriesActive { count, message }) => {
+                assert_eq!(count, 3);
+                assert_eq!(message, "3 running queries would be stopped by the restart");
+            }
+            other => panic!("expected QueriesActive, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,377 @@
+// mensa - Usage Analytics Module
+// A local log of finished queries (usage-log.jsonl) and the charts computed from it; nothing leaves the machine
+
+use crate::history::{self, QueryRecord};
+use crate::{settings, AppState};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use tauri::{Manager, State};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Serializes appends and the purge, so a purge never races a record back in
+static USAGE_LOCK: Mutex<()> = Mutex::const_new(());
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// Range used when the caller doesn't give one
+const DEFAULT_RANGE_DAYS: i64 = 30;
+
+/// Most buckets one call returns; longer ranges are cut at their start
+const MAX_BUCKETS: i64 = 2_000;
+
+/// Furthest a UTC offset reaches (UTC+14)
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// What the usage log keeps of a finished query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    pub query_id: String,
+    pub working_dir: String,
+    pub started_at: i64,
+    pub finished_at: i64,
+    /// Terminal reason: completed | failed | cancelled | cost_limit_exceeded
+    pub outcome: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_usd: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Bucketing {
+    #[default]
+    Day,
+    /// Weeks starting on Monday
+    Week,
+}
+
+/// Start and end (unix seconds) of the period to chart; either may be left open
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageBucket {
+    /// Local midnight (unix seconds) the bucket starts at
+    pub start: i64,
+    pub queries: u32,
+    pub completed: u32,
+    pub failed: u32,
+    pub cancelled: u32,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageShare {
+    /// Workspace path or model name
+    pub key: String,
+    pub queries: u32,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageTotals {
+    pub queries: u32,
+    pub cost_usd: f64,
+    pub average_duration_ms: f64,
+    /// Previous form of `average_duration_ms`
+    #[cfg(feature = "legacy_fields")]
+    pub average_duration_secs: f64,
+    /// Fractions of all queries, 0 when there were none
+    pub cancel_rate: f64,
+    pub failure_rate: f64,
+    pub cost_limit_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageAnalytics {
+    pub group_by: Bucketing,
+    /// The period asked for, after defaults
+    pub from: i64,
+    pub to: i64,
+    /// Oldest and newest query in the period; the log may not reach back to `from`
+    pub covered_from: Option<i64>,
+    pub covered_to: Option<i64>,
+    /// False when recording is switched off (the log is then empty)
+    pub recording: bool,
+    /// One per day or week of the period, empty ones included
+    pub buckets: Vec<UsageBucket>,
+    /// Queries started in each local hour of the day, 0-23
+    pub hours: Vec<u32>,
+    /// Most expensive first
+    pub workspaces: Vec<UsageShare>,
+    /// Most used first; queries whose model wasn't seen count under "unknown"
+    pub models: Vec<UsageShare>,
+    pub totals: UsageTotals,
+}
+
+/// Running sums while the log streams past
+#[derive(Default)]
+struct Accumulator {
+    buckets: BTreeMap<i64, UsageBucket>,
+    hours: [u32; 24],
+    workspaces: HashMap<String, (u32, f64)>,
+    models: HashMap<String, (u32, f64)>,
+    totals: UsageTotals,
+    duration_secs: i64,
+    cancelled: u32,
+    failed: u32,
+    cost_limited: u32,
+    covered: Option<(i64, i64)>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+pub fn usage_log_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(history::history_path(app)?.with_file_name("usage-log.jsonl"))
+}
+
+async fn recording_enabled(app: &tauri::AppHandle) -> bool {
+    let state = app.state::<AppState>();
+    settings::load(app, &state.settings)
+        .await
+        .map(|s| s.usage_analytics_enabled)
+        .unwrap_or(false)
+}
+
+/// Log a finished query, unless recording is switched off
+pub async fn record(app: &tauri::AppHandle, record: &QueryRecord) -> Result<(), String> {
+    if !recording_enabled(app).await {
+        return Ok(());
+    }
+    let entry = UsageRecord {
+        query_id: record.query_id.clone(),
+        working_dir: record.working_dir.clone(),
+        started_at: record.started_at,
+        finished_at: record.finished_at,
+        outcome: record.terminal_reason.clone().unwrap_or_else(|| "failed".to_string()),
+        cost_usd: record.cost_usd,
+        model: record.model.clone(),
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|e| format!("Failed to serialize usage record: {}", e))?;
+    line.push('\n');
+
+    let path = usage_log_path(app)?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+    }
+    let _guard = USAGE_LOCK.lock().await;
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .map_err(|e| format!("Failed to open usage log: {}", e))?;
+    file.write_all(line.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to write usage log: {}", e))
+}
+
+/// Delete the usage log
+pub async fn purge(app: &tauri::AppHandle) -> Result<(), String> {
+    let path = usage_log_path(app)?;
+    let _guard = USAGE_LOCK.lock().await;
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("Failed to delete usage log: {}", e)),
+    }
+}
+
+/// Start (unix seconds) of the local day or week containing `secs`
+pub fn bucket_start(secs: i64, group_by: Bucketing, offset_secs: i64) -> i64 {
+    let day = (secs + offset_secs).div_euclid(SECS_PER_DAY);
+    let first_day = match group_by {
+        Bucketing::Day => day,
+        // Day 0 (1970-01-01) was a Thursday, so Mondays are days ≡ 4 (mod 7)
+        Bucketing::Week => day - (day - 4).rem_euclid(7),
+    };
+    first_day * SECS_PER_DAY - offset_secs
+}
+
+/// The bucket after the one starting at `start`
+fn next_bucket(start: i64, group_by: Bucketing) -> i64 {
+    match group_by {
+        Bucketing::Day => start + SECS_PER_DAY,
+        Bucketing::Week => start + 7 * SECS_PER_DAY,
+    }
+}
+
+fn shares(map: HashMap<String, (u32, f64)>, by_cost: bool) -> Vec<UsageShare> {
+    let mut shares: Vec<UsageShare> = map
+        .into_iter()
+        .map(|(key, (queries, cost_usd))| UsageShare { key, queries, cost_usd })
+        .collect();
+    shares.sort_by(|a, b| match by_cost {
+        true => b.cost_usd.total_cmp(&a.cost_usd).then(b.queries.cmp(&a.queries)),
+        false => b.queries.cmp(&a.queries).then(b.cost_usd.total_cmp(&a.cost_usd)),
+    });
+    shares
+}
+
+fn fraction(count: u32, total: u32) -> f64 {
+    match total {
+        0 => 0.0,
+        total => count as f64 / total as f64,
+    }
+}
+
+impl Accumulator {
+    fn add(&mut self, record: &UsageRecord, group_by: Bucketing, offset_secs: i64) {
+        let cost = record.cost_usd.unwrap_or(0.0);
+        let bucket = self
+            .buckets
+            .entry(bucket_start(record.started_at, group_by, offset_secs))
+            .or_default();
+        bucket.queries += 1;
+        bucket.cost_usd += cost;
+        match record.outcome.as_str() {
+            "completed" => bucket.completed += 1,
+            "cancelled" => {
+                bucket.cancelled += 1;
+                self.cancelled += 1;
+            }
+            "cost_limit_exceeded" => self.cost_limited += 1,
+            _ => {
+                bucket.failed += 1;
+                self.failed += 1;
+            }
+        }
+
+        let hour = (record.started_at + offset_secs).rem_euclid(SECS_PER_DAY) / 3600;
+        self.hours[hour as usize] += 1;
+        let workspace = self.workspaces.entry(record.working_dir.clone()).or_default();
+        workspace.0 += 1;
+        workspace.1 += cost;
+        let model = self
+            .models
+            .entry(record.model.clone().unwrap_or_else(|| "unknown".to_string()))
+            .or_default();
+        model.0 += 1;
+        model.1 += cost;
+
+        self.totals.queries += 1;
+        self.totals.cost_usd += cost;
+        self.duration_secs += (record.finished_at - record.started_at).max(0);
+        self.covered = Some(match self.covered {
+            Some((first, last)) => (first.min(record.started_at), last.max(record.started_at)),
+            None => (record.started_at, record.started_at),
+        });
+    }
+
+    fn finish(self, group_by: Bucketing, from: i64, to: i64, offset_secs: i64, recording: bool) -> UsageAnalytics {
+        let mut buckets = Vec::new();
+        let last = bucket_start(to, group_by, offset_secs);
+        let mut start = bucket_start(from, group_by, offset_secs);
+        let mut filled = self.buckets;
+        while start <= last {
+            buckets.push(filled.remove(&start).unwrap_or_else(|| UsageBucket {
+                start,
+                ..Default::default()
+            }));
+            start = next_bucket(start, group_by);
+        }
+
+        let queries = self.totals.queries;
+        let totals = UsageTotals {
+            average_duration_ms: match queries {
+                0 => 0.0,
+                queries => self.duration_secs as f64 * 1000.0 / queries as f64,
+            },
+            #[cfg(feature = "legacy_fields")]
+            average_duration_secs: match queries {
+                0 => 0.0,
+                queries => self.duration_secs as f64 / queries as f64,
+            },
+            cancel_rate: fraction(self.cancelled, queries),
+            failure_rate: fraction(self.failed, queries),
+            cost_limit_rate: fraction(self.cost_limited, queries),
+            ..self.totals
+        };
+        UsageAnalytics {
+            group_by,
+            from,
+            to,
+            covered_from: self.covered.map(|(first, _)| first),
+            covered_to: self.covered.map(|(_, last)| last),
+            recording,
+            buckets,
+            hours: self.hours.to_vec(),
+            workspaces: shares(self.workspaces, true),
+            models: shares(self.models, false),
+            totals,
+        }
+    }
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Queries, cost, durations and outcomes over a period, bucketed by local day or week
+/// (`utcOffsetMinutes` is the caller's zone). Reads the usage log a line at a time.
+#[tauri::command]
+pub async fn get_usage_analytics(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    range: Option<AnalyticsRange>,
+    group_by: Option<Bucketing>,
+    utc_offset_minutes: Option<i32>,
+) -> Result<UsageAnalytics, String> {
+    let group_by = group_by.unwrap_or_default();
+    let offset_minutes = utc_offset_minutes.unwrap_or(0);
+    if offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+        return Err(format!("UTC offset out of range: {} minutes", offset_minutes));
+    }
+    let offset_secs = offset_minutes as i64 * 60;
+    let range = range.unwrap_or_default();
+    let to = range.to.unwrap_or_else(history::now_secs);
+    let from = range.from.unwrap_or(to - DEFAULT_RANGE_DAYS * SECS_PER_DAY);
+    if from > to {
+        return Err("Range starts after it ends".to_string());
+    }
+    let span = match group_by {
+        Bucketing::Day => SECS_PER_DAY,
+        Bucketing::Week => 7 * SECS_PER_DAY,
+    };
+    let from = from.max(to - (MAX_BUCKETS - 1) * span);
+    let recording = settings::load(&app, &state.settings).await?.usage_analytics_enabled;
+
+    let mut sums = Accumulator::default();
+    let path = usage_log_path(&app)?;
+    match tokio::fs::File::open(&path).await {
+        Ok(file) => {
+            let mut lines = tokio::io::BufReader::new(file).lines();
+            while let Some(line) = lines.next_line().await.map_err(|e| format!("Failed to read usage log: {}", e))? {
+                let Ok(record) = serde_json::from_str::<UsageRecord>(&line) else {
+                    continue;
+                };
+                if (from..=to).contains(&record.started_at) {
+                    sums.add(&record, group_by, offset_secs);
+                }
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read usage log: {}", e)),
+    }
+    Ok(sums.finish(group_by, from, to, offset_secs, recording))
+}
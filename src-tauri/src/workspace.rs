@@ -0,0 +1,493 @@
+// mensa - Workspace Module
+// Tracks the workspaces the user selected, their remembered UI state, and .claude scaffolding
+
+use crate::store::JsonStore;
+use crate::{fsutil, project_dir_for_workspace, AppState};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Manager, State};
+use tokio::sync::Mutex;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Lines appended to .gitignore so local agent files stay out of commits
+const AGENT_GITIGNORE_PATTERNS: &[&str] = &[".claude/settings.local.json", "CLAUDE.local.md"];
+
+/// Coalescing window for workspace state writes
+const WORKSPACE_STATE_FLUSH_DELAY: Duration = Duration::from_millis(500);
+
+/// Facts about a project inferred from its manifests
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectFacts {
+    pub language: Option<String>,
+    pub package_manager: Option<String>,
+    pub test_command: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BootstrapOptions {
+    pub claude_md: bool,
+    pub settings: bool,
+    pub gitignore: bool,
+}
+
+impl Default for BootstrapOptions {
+    fn default() -> Self {
+        BootstrapOptions {
+            claude_md: true,
+            settings: true,
+            gitignore: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BootstrapReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub skipped: Vec<String>,
+    pub facts: ProjectFacts,
+}
+
+/// One file per workspace under workspace-state/, sharing a lock
+static WORKSPACE_STATE_FILES: JsonStore<WorkspaceUiState> = JsonStore::new("workspace state");
+
+/// Where the user left off in a workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorkspaceUiState {
+    pub last_session_id: Option<String>,
+    pub last_branch: Option<String>,
+    pub last_pr_url: Option<String>,
+    /// Opaque panel layout blob owned by the frontend
+    pub layout: Option<Value>,
+    /// When workspace_digest last succeeded (unix seconds); the next digest starts here
+    pub last_digest_at: Option<i64>,
+    /// Author of commits made from mensa here, ahead of git config
+    pub commit_identity: Option<crate::identity::CommitIdentity>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleFlags {
+    pub session: bool,
+    pub branch: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceStateView {
+    pub state: WorkspaceUiState,
+    pub stale: StaleFlags,
+}
+
+struct CachedWorkspaceState {
+    state: WorkspaceUiState,
+    flush_scheduled: bool,
+}
+
+/// In-memory copy of each workspace's state; disk writes are coalesced per workspace
+#[derive(Default)]
+pub struct WorkspaceStateCache {
+    entries: Arc<Mutex<HashMap<PathBuf, CachedWorkspaceState>>>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Canonicalize a path and make sure it's a directory
+pub fn canonical_dir(path: &str) -> Result<PathBuf, String> {
+    let canonical = std::fs::canonicalize(path)
+        .map_err(|e| format!("Failed to resolve workspace path '{}': {}", path, e))?;
+    if !canonical.is_dir() {
+        return Err(format!("Path is not a directory: {}", path));
+    }
+    Ok(canonical)
+}
+
+/// Resolve a path and check it is one of the workspaces the user selected
+pub async fn validate_selected_workspace(state: &AppState, working_dir: &str) -> Result<PathBuf, String> {
+    let canonical = canonical_dir(working_dir)?;
+    let selected = state.selected_workspaces.lock().await;
+    if !selected.contains(&canonical) {
+        return Err(format!("Not a selected workspace: {}", working_dir));
+    }
+    Ok(canonical)
+}
+
+/// Infer language, package manager and test command from manifest files
+pub fn detect_project(root: &Path) -> ProjectFacts {
+    let has = |name: &str| root.join(name).exists();
+
+    if has("Cargo.toml") {
+        return ProjectFacts {
+            language: Some("Rust".to_string()),
+            package_manager: Some("cargo".to_string()),
+            test_command: Some("cargo test".to_string()),
+        };
+    }
+
+    if has("package.json") {
+        let package_manager = if has("pnpm-lock.yaml") {
+            "pnpm"
+        } else if has("yarn.lock") {
+            "yarn"
+        } else if has("bun.lockb") || has("bun.lock") {
+            "bun"
+        } else {
+            "npm"
+        };
+        let has_test_script = std::fs::read_to_string(root.join("package.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+            .map(|manifest| manifest.pointer("/scripts/test").is_some())
+            .unwrap_or(false);
+        let language = if has("tsconfig.json") { "TypeScript" } else { "JavaScript" };
+
+        return ProjectFacts {
+            language: Some(language.to_string()),
+            package_manager: Some(package_manager.to_string()),
+            test_command: has_test_script.then(|| format!("{} test", package_manager)),
+        };
+    }
+
+    if has("pyproject.toml") || has("requirements.txt") || has("setup.py") {
+        let package_manager = if has("uv.lock") {
+            "uv"
+        } else if has("poetry.lock") {
+            "poetry"
+        } else {
+            "pip"
+        };
+        return ProjectFacts {
+            language: Some("Python".to_string()),
+            package_manager: Some(package_manager.to_string()),
+            test_command: Some("pytest".to_string()),
+        };
+    }
+
+    if has("go.mod") {
+        return ProjectFacts {
+            language: Some("Go".to_string()),
+            package_manager: Some("go".to_string()),
+            test_command: Some("go test ./...".to_string()),
+        };
+    }
+
+    ProjectFacts::default()
+}
+
+fn claude_md_template(name: &str, facts: &ProjectFacts) -> String {
+    let mut md = format!("# {}\n\n", name);
+    md.push_str("This file gives Claude Code context about this project.\n\n");
+
+    md.push_str("## Project\n\n");
+    match facts.language {
+        Some(ref language) => md.push_str(&format!("- Language: {}\n", language)),
+        None => md.push_str("- Language: (describe the main language and frameworks)\n"),
+    }
+    if let Some(ref package_manager) = facts.package_manager {
+        md.push_str(&format!("- Package manager: {}\n", package_manager));
+    }
+
+    md.push_str("\n## Commands\n\n");
+    match facts.test_command {
+        Some(ref test_command) => md.push_str(&format!("- Test: `{}`\n", test_command)),
+        None => md.push_str("- Test: (add the command that runs the test suite)\n"),
+    }
+
+    md.push_str("\n## Conventions\n\n- (code style, architecture notes, things to avoid)\n");
+    md
+}
+
+fn settings_template(facts: &ProjectFacts) -> String {
+    let mut allow = vec![
+        "Read".to_string(),
+        "Glob".to_string(),
+        "Grep".to_string(),
+        "LS".to_string(),
+        "Bash(git status)".to_string(),
+        "Bash(git diff:*)".to_string(),
+        "Bash(git log:*)".to_string(),
+    ];
+    if let Some(ref test_command) = facts.test_command {
+        allow.push(format!("Bash({}:*)", test_command));
+    }
+
+    let settings = serde_json::json!({
+        "permissions": {
+            "allow": allow,
+            "deny": ["Read(./.env)", "Read(./.env.*)"]
+        }
+    });
+    let mut content = serde_json::to_string_pretty(&settings).unwrap_or_default();
+    content.push('\n');
+    content
+}
+
+/// Append the patterns the workspace's .gitignore lacks under a `# <heading>` comment; returns
+/// the ones added
+pub fn add_gitignore_patterns<S: AsRef<str>>(root: &Path, patterns: &[S], heading: &str) -> Result<Vec<String>, String> {
+    let path = root.join(".gitignore");
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    let mut missing: Vec<String> = Vec::new();
+    for pattern in patterns.iter().map(|p| p.as_ref().trim()) {
+        let listed = existing.lines().any(|line| line.trim() == pattern) || missing.iter().any(|m| m == pattern);
+        if !pattern.is_empty() && !listed {
+            missing.push(pattern.to_string());
+        }
+    }
+    if missing.is_empty() {
+        return Ok(missing);
+    }
+
+    let mut content = existing;
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    content.push_str(&format!("# {}\n", heading));
+    for pattern in &missing {
+        content.push_str(pattern);
+        content.push('\n');
+    }
+    fsutil::write_atomic(&path, content.as_bytes())?;
+    Ok(missing)
+}
+
+/// Write the scaffolding `options` asks for into `root` (see `bootstrap_workspace`)
+pub fn bootstrap(root: &Path, options: &BootstrapOptions) -> Result<BootstrapReport, String> {
+    let facts = detect_project(root);
+    let name = root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Project".to_string());
+    let mut report = BootstrapReport::default();
+
+    if options.claude_md {
+        let created = fsutil::create_new_atomic(&root.join("CLAUDE.md"), claude_md_template(&name, &facts).as_bytes())?;
+        let list = if created { &mut report.created } else { &mut report.skipped };
+        list.push("CLAUDE.md".to_string());
+    }
+
+    if options.settings {
+        let path = root.join(".claude").join("settings.json");
+        let created = fsutil::create_new_atomic(&path, settings_template(&facts).as_bytes())?;
+        let list = if created { &mut report.created } else { &mut report.skipped };
+        list.push(".claude/settings.json".to_string());
+    }
+
+    if options.gitignore {
+        let existed = root.join(".gitignore").exists();
+        let added = add_gitignore_patterns(root, AGENT_GITIGNORE_PATTERNS, "Claude Code local files")?;
+        let list = match (added.is_empty(), existed) {
+            (true, _) => &mut report.skipped,
+            (false, true) => &mut report.updated,
+            (false, false) => &mut report.created,
+        };
+        list.push(".gitignore".to_string());
+    }
+
+    report.facts = facts;
+    Ok(report)
+}
+
+pub fn workspace_state_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not determine app data directory: {}", e))?;
+    Ok(dir.join("workspace-state"))
+}
+
+/// One file per workspace, so windows on different workspaces never write the same file
+fn workspace_state_path(app: &tauri::AppHandle, root: &Path) -> Result<PathBuf, String> {
+    let key = fsutil::sha256_hex(root.to_string_lossy().as_bytes());
+    Ok(workspace_state_dir(app)?.join(format!("{}.json", &key[..32])))
+}
+
+/// Forget cached states that have no pending write, so the next read comes from disk
+pub async fn drop_clean_states(cache: &WorkspaceStateCache) {
+    cache.entries.lock().await.retain(|_, entry| entry.flush_scheduled);
+}
+
+async fn read_workspace_state(app: &tauri::AppHandle, root: &Path) -> Result<WorkspaceUiState, String> {
+    // A corrupt file falls back to its backup, or at worst loses "where was I"; it never blocks opening the workspace
+    WORKSPACE_STATE_FILES.read(app, workspace_state_path(app, root)?).await
+}
+
+/// Cached state of a workspace, read from disk when it isn't cached
+pub async fn load_state(app: &tauri::AppHandle, state: &AppState, root: &Path) -> Result<WorkspaceUiState, String> {
+    let cached = {
+        let entries = state.workspace_states.entries.lock().await;
+        entries.get(root).map(|entry| entry.state.clone())
+    };
+    match cached {
+        Some(ui_state) => Ok(ui_state),
+        None => read_workspace_state(app, root).await,
+    }
+}
+
+/// Merge `patch` into a workspace's cached state and schedule the (coalesced) write
+pub async fn patch_state(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    root: PathBuf,
+    patch: Map<String, Value>,
+) -> Result<WorkspaceUiState, String> {
+    let cache = state.workspace_states.entries.clone();
+    let mut entries = cache.lock().await;
+    if !entries.contains_key(&root) {
+        let loaded = read_workspace_state(app, &root).await?;
+        entries.insert(root.clone(), CachedWorkspaceState {
+            state: loaded,
+            flush_scheduled: false,
+        });
+    }
+    let entry = entries.get_mut(&root).ok_or("Workspace state missing from cache")?;
+
+    let mut merged = serde_json::to_value(&entry.state)
+        .map_err(|e| format!("Failed to serialize workspace state: {}", e))?;
+    if let Value::Object(ref mut current) = merged {
+        current.extend(patch);
+    }
+    entry.state = serde_json::from_value(merged)
+        .map_err(|e| format!("Invalid workspace state: {}", e))?;
+
+    if !entry.flush_scheduled {
+        entry.flush_scheduled = true;
+        schedule_workspace_state_flush(app.clone(), cache.clone(), root);
+    }
+
+    Ok(entry.state.clone())
+}
+
+/// Write the latest cached state once the coalescing window has passed
+fn schedule_workspace_state_flush(app: tauri::AppHandle, cache: Arc<Mutex<HashMap<PathBuf, CachedWorkspaceState>>>, root: PathBuf) {
+    let tasks = app.state::<AppState>().tasks.clone();
+    tasks.spawn(format!("workspace state flush {}", root.display()), |token| async move {
+        // An exit flushes right away instead of losing the pending write
+        tokio::select! {
+            _ = tokio::time::sleep(WORKSPACE_STATE_FLUSH_DELAY) => {}
+            _ = token.cancelled() => {}
+        }
+
+        let snapshot = {
+            let mut entries = cache.lock().await;
+            match entries.get_mut(&root) {
+                Some(entry) => {
+                    entry.flush_scheduled = false;
+                    entry.state.clone()
+                }
+                None => return,
+            }
+        };
+
+        let result = match workspace_state_path(&app, &root) {
+            Ok(path) => WORKSPACE_STATE_FILES.write(&app, path, &snapshot).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("[mensa] {}", e);
+        }
+    });
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Record a workspace the user picked so workspace-modifying commands accept it
+#[tauri::command]
+pub async fn register_workspace(state: State<'_, AppState>, path: String) -> Result<String, String> {
+    let canonical = canonical_dir(&path)?;
+    let display = canonical.to_string_lossy().to_string();
+    state.selected_workspaces.lock().await.insert(canonical);
+    Ok(display)
+}
+
+/// Create starter CLAUDE.md, .claude/settings.json and .gitignore entries (never overwriting)
+#[tauri::command]
+pub async fn bootstrap_workspace(
+    state: State<'_, AppState>,
+    working_dir: String,
+    options: Option<BootstrapOptions>,
+) -> Result<BootstrapReport, String> {
+    let root = validate_selected_workspace(&state, &working_dir).await?;
+    let options = options.unwrap_or_default();
+
+    tokio::task::spawn_blocking(move || bootstrap(&root, &options))
+    .await
+    .map_err(|e| format!("Bootstrap task failed: {}", e))?
+}
+
+/// Add patterns to the workspace's .gitignore (skipping ones already listed); returns those added
+#[tauri::command]
+pub async fn gitignore_add(
+    state: State<'_, AppState>,
+    working_dir: String,
+    patterns: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let root = validate_selected_workspace(&state, &working_dir).await?;
+    tokio::task::spawn_blocking(move || add_gitignore_patterns(&root, &patterns, "Added by mensa"))
+        .await
+        .map_err(|e| format!("Failed to update .gitignore: {}", e))?
+}
+
+/// Get the remembered state for a workspace, flagging references that no longer exist
+#[tauri::command]
+pub async fn get_workspace_state(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+) -> Result<WorkspaceStateView, String> {
+    let root = canonical_dir(&working_dir)?;
+    let ui_state = load_state(&app, &state, &root).await?;
+
+    let session = match ui_state.last_session_id {
+        Some(ref id) => !project_dir_for_workspace(&working_dir)?
+            .join(format!("{}.jsonl", id))
+            .exists(),
+        None => false,
+    };
+    let branch = match ui_state.last_branch {
+        Some(ref name) => git2::Repository::open(&root)
+            .map(|repo| repo.find_branch(name, git2::BranchType::Local).is_err())
+            .unwrap_or(true),
+        None => false,
+    };
+
+    Ok(WorkspaceStateView {
+        state: ui_state,
+        stale: StaleFlags { session, branch },
+    })
+}
+
+/// Merge a partial update into a workspace's state. Keys present in `patch`
+/// replace the stored value (null clears it); the write to disk is coalesced.
+#[tauri::command]
+pub async fn set_workspace_state(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    working_dir: String,
+    patch: Value,
+) -> Result<WorkspaceUiState, String> {
+    let root = canonical_dir(&working_dir)?;
+    let patch = match patch {
+        Value::Object(map) => map,
+        _ => return Err("Workspace state patch must be an object".to_string()),
+    };
+    patch_state(&app, &state, root, patch).await
+}
@@ -0,0 +1,527 @@
+// mensa - Workspace Health Module
+// Finds what makes agents slow in a repository (a bloated object store, slow status, huge
+// untracked directories, awkward paths, big tracked files) and suggests one-click fixes
+
+use crate::{git, workspace};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// The whole analysis stops after this; unfinished sections are reported as timed out
+const TIME_BUDGET: Duration = Duration::from_secs(8);
+
+/// Sections running at once, so a pathological repo isn't hammered by every scan together
+const MAX_CONCURRENT_SECTIONS: usize = 2;
+
+/// Loose objects at which `git gc --auto` would repack (git's default gc.auto)
+const LOOSE_OBJECTS_LIMIT: u64 = 6700;
+
+/// Packs at which `git gc --auto` would consolidate (git's default gc.autoPackLimit)
+const PACKS_LIMIT: u64 = 50;
+
+/// Packed repository sizes worth mentioning, and worth a warning
+const LARGE_REPOSITORY_BYTES: u64 = 1024 * 1024 * 1024;
+const HUGE_REPOSITORY_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// `git status` durations agents feel, and ones that stall them
+const SLOW_STATUS: Duration = Duration::from_secs(2);
+const VERY_SLOW_STATUS: Duration = Duration::from_secs(5);
+
+/// An untracked directory reaching either size is worth ignoring; its walk stops there
+const HUGE_UNTRACKED_BYTES: u64 = 256 * 1024 * 1024;
+const HUGE_UNTRACKED_FILES: u64 = 20_000;
+
+/// Most untracked directories measured
+const MAX_UNTRACKED_DIRS: usize = 100;
+
+/// Absolute path length Windows checkouts fail at (MAX_PATH)
+const LONG_PATH_CHARS: usize = 260;
+
+/// Tracked files above this size count as large
+const LARGE_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Large tracked files from which the finding becomes a warning
+const MANY_LARGE_FILES: usize = 5;
+
+/// Most example paths listed per finding
+const MAX_EXAMPLE_PATHS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// One part of the analysis, run under the shared time budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthSection {
+    /// Object store size, loose objects and packs (`git count-objects`)
+    Repository,
+    /// How long `git status` takes
+    Status,
+    /// Size of the untracked directories status lists
+    Untracked,
+    /// Long and case-conflicting tracked paths
+    Paths,
+    /// Tracked files over 10 MB at HEAD
+    LargeFiles,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthFindingKind {
+    LooseObjects,
+    LargeRepository,
+    SlowStatus,
+    UntrackedDirectory,
+    LongPaths,
+    CaseConflicts,
+    LargeFiles,
+}
+
+/// A fix the UI can offer with one click: `gitGc` runs `git_gc`, `gitignoreAdd` runs
+/// `gitignore_add` with `patterns`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "id", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum HealthSuggestion {
+    GitGc,
+    GitignoreAdd { patterns: Vec<String> },
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthFinding {
+    pub kind: HealthFindingKind,
+    pub severity: HealthSeverity,
+    pub title: String,
+    pub detail: String,
+    /// Examples, relative to the workspace
+    pub paths: Vec<String>,
+    /// Size the finding is about (packed history, an untracked directory, large files)
+    pub size_bytes: Option<u64>,
+    pub suggestion: Option<HealthSuggestion>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceHealth {
+    pub working_dir: String,
+    /// Most severe first
+    pub findings: Vec<HealthFinding>,
+    /// Severity of the worst finding, for the workspace header badge
+    pub worst: Option<HealthSeverity>,
+    /// How long `git status` took, when it finished within the budget
+    pub status_ms: Option<u64>,
+    /// Sections cut off by the time budget; their findings may be missing or partial
+    pub timed_out_sections: Vec<HealthSection>,
+    /// Sections that failed outright
+    pub errors: Vec<String>,
+    pub elapsed_ms: u64,
+}
+
+/// Why a section produced nothing
+enum SectionFailure {
+    TimedOut,
+    Failed(String),
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Run git in `root` without taking optional locks, so an agent's own git calls never wait on it
+async fn git_output(root: &Path, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = tokio::process::Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git {}: {}", args[0], e))?;
+    if !output.status.success() {
+        return Err(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(output.stdout)
+}
+
+/// Run one section once a permit is free, giving up at the deadline
+async fn run_section<T, F>(permits: &Semaphore, deadline: tokio::time::Instant, work: F) -> Result<T, SectionFailure>
+where
+    F: Future<Output = Result<T, String>>,
+{
+    let work = async {
+        let _permit = permits.acquire().await.map_err(|e| e.to_string())?;
+        work.await
+    };
+    match tokio::time::timeout_at(deadline, work).await {
+        Ok(result) => result.map_err(SectionFailure::Failed),
+        Err(_) => Err(SectionFailure::TimedOut),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+fn examples(paths: impl IntoIterator<Item = String>) -> Vec<String> {
+    paths.into_iter().take(MAX_EXAMPLE_PATHS).collect()
+}
+
+/// Loose objects and packs from `git count-objects -v`, and the packed size
+async fn repository_findings(root: &Path) -> Result<Vec<HealthFinding>, String> {
+    let output = git_output(root, &["count-objects", "-v"]).await?;
+    let counts: BTreeMap<String, u64> = String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim().to_string(), value.trim().parse().ok()?))
+        })
+        .collect();
+    let count = |key: &str| counts.get(key).copied().unwrap_or(0);
+    let (loose, packs, packed_bytes) = (count("count"), count("packs"), count("size-pack") * 1024);
+
+    let mut findings = Vec::new();
+    if loose >= LOOSE_OBJECTS_LIMIT || packs >= PACKS_LIMIT {
+        findings.push(HealthFinding {
+            kind: HealthFindingKind::LooseObjects,
+            severity: HealthSeverity::Warning,
+            title: "Repository needs cleanup".to_string(),
+            detail: format!(
+                "{} loose objects and {} packs slow down every git command; git gc packs them together.",
+                loose, packs
+            ),
+            paths: Vec::new(),
+            size_bytes: None,
+            suggestion: Some(HealthSuggestion::GitGc),
+        });
+    }
+    if packed_bytes >= LARGE_REPOSITORY_BYTES {
+        findings.push(HealthFinding {
+            kind: HealthFindingKind::LargeRepository,
+            severity: if packed_bytes >= HUGE_REPOSITORY_BYTES { HealthSeverity::Warning } else { HealthSeverity::Info },
+            title: "Large repository".to_string(),
+            detail: format!("The object store holds {} of packed history.", format_bytes(packed_bytes)),
+            paths: Vec::new(),
+            size_bytes: Some(packed_bytes),
+            suggestion: None,
+        });
+    }
+    Ok(findings)
+}
+
+/// Untracked directories in `git status --porcelain -z` output ("?? dir/"), skipping the
+/// original path that follows a rename or copy
+fn untracked_dirs(output: &[u8]) -> Vec<String> {
+    let mut dirs = Vec::new();
+    let mut entries = output.split(|b| *b == 0).filter(|e| e.len() > 3);
+    while let Some(entry) = entries.next() {
+        let (status, path) = (&entry[..2], String::from_utf8_lossy(&entry[3..]));
+        if matches!(status[0], b'R' | b'C') {
+            entries.next();
+        } else if status == b"??" && path.ends_with('/') {
+            dirs.push(path.to_string());
+        }
+    }
+    dirs
+}
+
+/// Time `git status` as agents run it; also returns the untracked directories it listed
+async fn status_findings(root: &Path) -> Result<(Vec<HealthFinding>, u64, Vec<String>), String> {
+    let started = Instant::now();
+    let output = git_output(root, &["status", "--porcelain", "-z", "--untracked-files=normal"]).await?;
+    let elapsed = started.elapsed();
+
+    let mut findings = Vec::new();
+    if elapsed >= SLOW_STATUS {
+        findings.push(HealthFinding {
+            kind: HealthFindingKind::SlowStatus,
+            severity: if elapsed >= VERY_SLOW_STATUS { HealthSeverity::Critical } else { HealthSeverity::Warning },
+            title: "git status is slow".to_string(),
+            detail: format!(
+                "git status took {:.1}s. Agents run it often, so every step waits on it; large untracked or unignored directories are the usual cause.",
+                elapsed.as_secs_f64()
+            ),
+            paths: Vec::new(),
+            size_bytes: None,
+            suggestion: None,
+        });
+    }
+    Ok((findings, elapsed.as_millis() as u64, untracked_dirs(&output)))
+}
+
+/// Bytes and files under `dir`, stopping at the huge thresholds or the deadline (symlinks
+/// aren't followed); the flag says whether the walk hit the deadline
+fn measure_dir(dir: &Path, deadline: Instant) -> (u64, u64, bool) {
+    let (mut bytes, mut files) = (0u64, 0u64);
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if Instant::now() >= deadline {
+                return (bytes, files, true);
+            }
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                stack.push(entry.path());
+            } else {
+                bytes += metadata.len();
+                files += 1;
+                if bytes >= HUGE_UNTRACKED_BYTES || files >= HUGE_UNTRACKED_FILES {
+                    return (bytes, files, false);
+                }
+            }
+        }
+    }
+    (bytes, files, false)
+}
+
+/// Untracked directories big enough to slow down status and searches, each with the
+/// .gitignore pattern that hides it; the flag says whether the deadline cut the scan short
+fn untracked_findings(root: &Path, dirs: &[String], deadline: Instant) -> (Vec<HealthFinding>, bool) {
+    let mut findings = Vec::new();
+    for dir in dirs.iter().take(MAX_UNTRACKED_DIRS) {
+        let (bytes, files, timed_out) = measure_dir(&root.join(dir), deadline);
+        if bytes >= HUGE_UNTRACKED_BYTES || files >= HUGE_UNTRACKED_FILES {
+            findings.push(HealthFinding {
+                kind: HealthFindingKind::UntrackedDirectory,
+                severity: HealthSeverity::Warning,
+                title: format!("{} isn't ignored", dir),
+                detail: format!(
+                    "This untracked directory holds at least {} in {} files. git status and file searches walk all of it; add it to .gitignore unless it should be committed.",
+                    format_bytes(bytes),
+                    files
+                ),
+                paths: vec![dir.clone()],
+                size_bytes: Some(bytes),
+                suggestion: Some(HealthSuggestion::GitignoreAdd {
+                    patterns: vec![format!("/{}", dir)],
+                }),
+            });
+        }
+        if timed_out {
+            return (findings, true);
+        }
+    }
+    (findings, false)
+}
+
+/// Tracked paths too long for Windows checkouts, and paths differing only in case (which
+/// collide on case-insensitive file systems)
+async fn path_findings(root: &Path) -> Result<Vec<HealthFinding>, String> {
+    let output = git_output(root, &["ls-files", "-z"]).await?;
+    let paths: Vec<String> = output
+        .split(|b| *b == 0)
+        .filter(|p| !p.is_empty())
+        .map(|p| String::from_utf8_lossy(p).to_string())
+        .collect();
+
+    let root_chars = root.to_string_lossy().chars().count() + 1;
+    let long: Vec<String> = paths
+        .iter()
+        .filter(|p| root_chars + p.chars().count() >= LONG_PATH_CHARS)
+        .cloned()
+        .collect();
+
+    // Every path and each of its parent directories, grouped by lowercase form
+    let mut by_folded: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    for path in &paths {
+        let prefixes = path.match_indices('/').map(|(i, _)| &path[..i]);
+        for prefix in prefixes.chain(std::iter::once(path.as_str())) {
+            by_folded.entry(prefix.to_lowercase()).or_default().insert(prefix);
+        }
+    }
+    let conflicts: Vec<String> = by_folded
+        .into_values()
+        .filter(|variants| variants.len() > 1)
+        .map(|variants| variants.into_iter().collect::<Vec<_>>().join(", "))
+        .collect();
+
+    let mut findings = Vec::new();
+    if !long.is_empty() {
+        findings.push(HealthFinding {
+            kind: HealthFindingKind::LongPaths,
+            severity: HealthSeverity::Warning,
+            title: "Very long paths".to_string(),
+            detail: format!(
+                "{} tracked {} reach {} characters in this checkout, which Windows can't create without long path support.",
+                long.len(),
+                if long.len() == 1 { "path would" } else { "paths would" },
+                LONG_PATH_CHARS
+            ),
+            paths: examples(long),
+            size_bytes: None,
+            suggestion: None,
+        });
+    }
+    if !conflicts.is_empty() {
+        findings.push(HealthFinding {
+            kind: HealthFindingKind::CaseConflicts,
+            severity: HealthSeverity::Warning,
+            title: "Paths differing only in case".to_string(),
+            detail: format!(
+                "{} {} only in letter case; on macOS and Windows one overwrites the other, so the checkout always looks modified.",
+                conflicts.len(),
+                if conflicts.len() == 1 { "set of paths differs" } else { "sets of paths differ" }
+            ),
+            paths: examples(conflicts),
+            size_bytes: None,
+            suggestion: None,
+        });
+    }
+    Ok(findings)
+}
+
+/// Tracked files over LARGE_FILE_BYTES at HEAD, from `git ls-tree -l` ("<mode> blob <oid> <size>\t<path>")
+async fn large_file_findings(root: &Path) -> Result<Vec<HealthFinding>, String> {
+    let output = git_output(root, &["ls-tree", "-r", "-l", "-z", "--full-tree", "HEAD"]).await?;
+    let mut large: Vec<(u64, String)> = output
+        .split(|b| *b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (meta, path) = entry.split_once('\t')?;
+            let size: u64 = meta.split_whitespace().nth(3)?.parse().ok()?;
+            (size > LARGE_FILE_BYTES).then(|| (size, path.to_string()))
+        })
+        .collect();
+    if large.is_empty() {
+        return Ok(Vec::new());
+    }
+    large.sort_by_key(|(size, _)| std::cmp::Reverse(*size));
+
+    let total: u64 = large.iter().map(|(size, _)| size).sum();
+    Ok(vec![HealthFinding {
+        kind: HealthFindingKind::LargeFiles,
+        severity: if large.len() >= MANY_LARGE_FILES { HealthSeverity::Warning } else { HealthSeverity::Info },
+        title: format!("{} large tracked {}", large.len(), if large.len() == 1 { "file" } else { "files" }),
+        detail: format!(
+            "Tracked files over {} add up to {}; agents reading or searching them waste time and context.",
+            format_bytes(LARGE_FILE_BYTES),
+            format_bytes(total)
+        ),
+        paths: examples(large.into_iter().map(|(_, path)| path)),
+        size_bytes: Some(total),
+        suggestion: None,
+    }])
+}
+
+/// Run every section, at most MAX_CONCURRENT_SECTIONS at a time and all within TIME_BUDGET
+pub async fn analyze(root: PathBuf, has_head: bool) -> WorkspaceHealth {
+    let started = Instant::now();
+    let deadline = tokio::time::Instant::now() + TIME_BUDGET;
+    let permits = Semaphore::new(MAX_CONCURRENT_SECTIONS);
+
+    let status_then_untracked = async {
+        let status = run_section(&permits, deadline, status_findings(&root)).await;
+        let Ok((_, _, dirs)) = &status else {
+            return (status, None);
+        };
+        // The walk stops a little before the deadline so its partial findings aren't lost
+        let (walk_root, dirs) = (root.clone(), dirs.clone());
+        let walk_deadline = (deadline - Duration::from_millis(200)).into_std();
+        let untracked = run_section(&permits, deadline, async move {
+            tokio::task::spawn_blocking(move || untracked_findings(&walk_root, &dirs, walk_deadline))
+                .await
+                .map_err(|e| format!("Untracked scan failed: {}", e))
+        })
+        .await;
+        (status, Some(untracked))
+    };
+    let large_files = async {
+        match has_head {
+            true => run_section(&permits, deadline, large_file_findings(&root)).await,
+            false => Ok(Vec::new()),
+        }
+    };
+    let (repository, paths, large_files, (status, untracked)) = tokio::join!(
+        run_section(&permits, deadline, repository_findings(&root)),
+        run_section(&permits, deadline, path_findings(&root)),
+        large_files,
+        status_then_untracked,
+    );
+
+    let mut health = WorkspaceHealth {
+        working_dir: root.to_string_lossy().to_string(),
+        findings: Vec::new(),
+        worst: None,
+        status_ms: None,
+        timed_out_sections: Vec::new(),
+        errors: Vec::new(),
+        elapsed_ms: 0,
+    };
+    let status = status.map(|(findings, status_ms, _)| {
+        health.status_ms = Some(status_ms);
+        findings
+    });
+    let untracked = untracked.map(|result| {
+        result.map(|(findings, timed_out)| {
+            if timed_out {
+                health.timed_out_sections.push(HealthSection::Untracked);
+            }
+            findings
+        })
+    });
+    let sections = [
+        (HealthSection::Repository, Some(repository)),
+        (HealthSection::Status, Some(status)),
+        (HealthSection::Untracked, untracked),
+        (HealthSection::Paths, Some(paths)),
+        (HealthSection::LargeFiles, Some(large_files)),
+    ];
+    for (section, result) in sections {
+        match result {
+            Some(Ok(findings)) => health.findings.extend(findings),
+            Some(Err(SectionFailure::TimedOut)) => health.timed_out_sections.push(section),
+            Some(Err(SectionFailure::Failed(e))) => health.errors.push(e),
+            // Not run because status didn't finish
+            None if health.timed_out_sections.contains(&HealthSection::Status) => {
+                health.timed_out_sections.push(section)
+            }
+            None => {}
+        }
+    }
+
+    health.findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    health.worst = health.findings.first().map(|f| f.severity);
+    health.elapsed_ms = started.elapsed().as_millis() as u64;
+    health
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Look for what slows agents down in a workspace's repository. Sections share an 8 second
+/// budget; ones cut off are listed in `timedOutSections` and the rest are still returned.
+#[tauri::command]
+pub async fn analyze_workspace_health(working_dir: String) -> Result<WorkspaceHealth, String> {
+    let root = workspace::canonical_dir(&working_dir)?;
+    let has_head = git::open_repo(&working_dir)?.head().is_ok();
+    Ok(analyze(root, has_head).await)
+}
@@ -0,0 +1,509 @@
+// mensa - Workspace Templates Module
+// Creates a new project from a git URL or a built-in starter: clone or extract, init, bootstrap, install
+
+use crate::git::{self, GitState};
+use crate::progress::ProgressReporter;
+use crate::workspace::{self, BootstrapOptions, BootstrapReport};
+use crate::{script, settings, AppState};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{Manager, State};
+
+// ============================================================================
+// Data Types
+// ============================================================================
+
+/// Dependency installs can download a lot; the default external-command timeout is too short
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(900);
+
+/// Placeholder in built-in template files replaced with the project name
+const NAME_PLACEHOLDER: &str = "{{name}}";
+
+/// A starter embedded in the binary. Files named `gitignore` are written as `.gitignore`
+/// (a real one would apply to this repository).
+struct BuiltinTemplate {
+    name: &'static str,
+    description: &'static str,
+    files: &'static [(&'static str, &'static str)],
+}
+
+const BUILTIN_TEMPLATES: &[BuiltinTemplate] = &[
+    BuiltinTemplate {
+        name: "empty-node",
+        description: "Node.js project with a package.json and an entry point",
+        files: &[
+            ("package.json", include_str!("../workspace-templates/empty-node/package.json")),
+            ("index.js", include_str!("../workspace-templates/empty-node/index.js")),
+            ("gitignore", include_str!("../workspace-templates/empty-node/gitignore")),
+        ],
+    },
+    BuiltinTemplate {
+        name: "empty-python",
+        description: "Python project with a pyproject.toml and a src/ package",
+        files: &[
+            ("pyproject.toml", include_str!("../workspace-templates/empty-python/pyproject.toml")),
+            ("requirements.txt", include_str!("../workspace-templates/empty-python/requirements.txt")),
+            ("src/app/__init__.py", include_str!("../workspace-templates/empty-python/src/app/__init__.py")),
+            ("src/app/main.py", include_str!("../workspace-templates/empty-python/src/app/main.py")),
+            ("gitignore", include_str!("../workspace-templates/empty-python/gitignore")),
+        ],
+    },
+    BuiltinTemplate {
+        name: "empty-rust",
+        description: "Rust binary crate",
+        files: &[
+            ("Cargo.toml", include_str!("../workspace-templates/empty-rust/Cargo.toml")),
+            ("src/main.rs", include_str!("../workspace-templates/empty-rust/src/main.rs")),
+            ("gitignore", include_str!("../workspace-templates/empty-rust/gitignore")),
+        ],
+    },
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceTemplateInfo {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CreateWorkspaceOptions {
+    /// Scaffolding to add; defaults to `bootstrap_workspace`'s
+    pub bootstrap: Option<BootstrapOptions>,
+    /// Run the install command detected from the project's manifest
+    pub install_dependencies: bool,
+    /// Leave whatever was created in place when a step fails
+    pub keep_partial: bool,
+    /// Branch to check out when cloning
+    pub branch: Option<String>,
+    /// Id for `progress` events and `cancel_git_operation`
+    pub operation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedWorkspace {
+    /// Canonical path, already registered as a selected workspace
+    pub path: String,
+    pub cloned: bool,
+    pub initialized_git: bool,
+    pub bootstrap: BootstrapReport,
+    /// The install commands that ran, when `install_dependencies` was set and one was detected
+    pub installed: Vec<String>,
+}
+
+/// A command the install step runs, relative to the project root
+struct InstallStep {
+    program: String,
+    args: Vec<String>,
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Whether a template names a repository to clone rather than a built-in
+fn is_git_url(template: &str) -> bool {
+    template.contains("://") || template.starts_with("git@") || template.ends_with(".git")
+}
+
+/// Package-name-safe version of a directory name: lowercase, `-` separated, never leading with a digit
+fn project_name(destination: &Path) -> String {
+    let raw = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mut name = String::new();
+    for c in raw.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            name.push(c);
+        } else if !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    let name = name.trim_matches('-').to_string();
+    match name.chars().next() {
+        None => "project".to_string(),
+        Some(c) if c.is_ascii_digit() => format!("project-{}", name),
+        Some(_) => name,
+    }
+}
+
+/// Write a built-in template's files into `root`
+fn extract_builtin(template: &BuiltinTemplate, root: &Path) -> Result<(), String> {
+    let name = project_name(root);
+    for (relative, content) in template.files {
+        let relative = if *relative == "gitignore" { ".gitignore" } else { relative };
+        let path = root.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&path, content.replace(NAME_PLACEHOLDER, &name))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Make sure the destination can be created into: missing, or an empty directory.
+/// Returns whether it already existed.
+fn prepare_destination(destination: &Path) -> Result<bool, String> {
+    if !destination.exists() {
+        if !destination.parent().is_some_and(|p| p.is_dir()) {
+            return Err(format!("Parent directory doesn't exist: {}", destination.display()));
+        }
+        std::fs::create_dir(destination)
+            .map_err(|e| format!("Failed to create {}: {}", destination.display(), e))?;
+        return Ok(false);
+    }
+    if !destination.is_dir() {
+        return Err(format!("Path is not a directory: {}", destination.display()));
+    }
+    let mut entries = std::fs::read_dir(destination)
+        .map_err(|e| format!("Failed to read {}: {}", destination.display(), e))?;
+    if entries.next().is_some() {
+        return Err(format!("Destination is not empty: {}", destination.display()));
+    }
+    Ok(true)
+}
+
+/// Undo a failed creation: remove the directory if we made it, otherwise empty it again
+fn clean_up(destination: &Path, existed: bool) {
+    if !existed {
+        let _ = std::fs::remove_dir_all(destination);
+        return;
+    }
+    if let Ok(entries) = std::fs::read_dir(destination) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let _ = if path.is_dir() && !path.is_symlink() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+        }
+    }
+}
+
+/// `git init` the project when it has no repository, then add the bootstrap scaffolding.
+/// Returns whether a repository was created.
+fn init_and_bootstrap(root: &Path, options: &BootstrapOptions) -> Result<(bool, BootstrapReport), String> {
+    let initialized_git = git2::Repository::open(root).is_err();
+    if initialized_git {
+        git2::Repository::init(root).map_err(|e| format!("Failed to initialize git repository: {}", e))?;
+    }
+    let report = workspace::bootstrap(root, options)?;
+    Ok((initialized_git, report))
+}
+
+/// Install commands for the project's package manager; empty when there's nothing to install
+fn install_steps(root: &Path, package_manager: Option<&str>, npm: &str) -> Vec<InstallStep> {
+    let step = |program: &str, args: &[&str]| InstallStep {
+        program: program.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+    };
+    match package_manager {
+        Some("npm") => vec![step(npm, &["install", "--no-audit", "--no-fund"])],
+        Some(manager @ ("pnpm" | "yarn" | "bun")) => vec![step(manager, &["install"])],
+        Some("cargo") => vec![step("cargo", &["fetch"])],
+        Some("go") => vec![step("go", &["mod", "download"])],
+        Some("uv") => vec![step("uv", &["sync"])],
+        Some("poetry") => vec![step("poetry", &["install"])],
+        // Into a project virtualenv, never the system interpreter
+        Some("pip") if root.join("requirements.txt").exists() => {
+            let python = if cfg!(windows) { "python" } else { "python3" };
+            let pip = if cfg!(windows) { ".venv\\Scripts\\pip" } else { ".venv/bin/pip" };
+            vec![
+                step(python, &["-m", "venv", ".venv"]),
+                step(pip, &["install", "-r", "requirements.txt"]),
+            ]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Run one install command, passing its output lines on as progress
+async fn run_install_step(
+    state: &GitState,
+    reporter: &ProgressReporter,
+    root: &Path,
+    step: &InstallStep,
+) -> Result<(), String> {
+    let command = format!("{} {}", step.program, step.args.join(" "));
+    reporter.phase("installing", Some(command.clone()));
+    let sink_reporter = reporter.clone();
+    let sink: git::ProgressSink = Box::new(move |line: &str| sink_reporter.phase("installing", Some(line.to_string())));
+    let program = if step.program.starts_with(".venv") {
+        root.join(&step.program).to_string_lossy().to_string()
+    } else {
+        step.program.clone()
+    };
+    let output = git::run_external_with_progress(
+        state,
+        &program,
+        &step.args,
+        Some(&root.to_string_lossy()),
+        &[],
+        Some(INSTALL_TIMEOUT),
+        Some(reporter.operation_id().to_string()),
+        Some(sink),
+    )
+    .await
+    .map_err(|e| format!("Failed to run {}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to run {}: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Every step after the destination exists; on error the caller cleans up
+async fn populate(
+    app: &tauri::AppHandle,
+    reporter: &ProgressReporter,
+    template: &str,
+    destination: &Path,
+    options: &CreateWorkspaceOptions,
+) -> Result<CreatedWorkspace, String> {
+    let git_state = app.state::<GitState>();
+    let cloned = is_git_url(template);
+
+    if cloned {
+        reporter.phase("cloning", Some(template.to_string()));
+        let destination_arg = destination.to_string_lossy().to_string();
+        let mut args = vec!["clone", "--progress"];
+        if let Some(ref branch) = options.branch {
+            args.extend(["--branch", branch.as_str()]);
+        }
+        args.extend(["--", template, destination_arg.as_str()]);
+        let output = git::run_external_with_progress(
+            &git_state,
+            "git",
+            &args,
+            None,
+            &[],
+            None,
+            Some(reporter.operation_id().to_string()),
+            Some(reporter.git_sink()),
+        )
+        .await
+        .map_err(|e| format!("Failed to clone {}: {}", template, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to clone {}: {}",
+                template,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    } else {
+        let builtin = BUILTIN_TEMPLATES
+            .iter()
+            .find(|t| t.name == template)
+            .ok_or_else(|| format!("Unknown workspace template: {}", template))?;
+        reporter.phase("extracting", Some(builtin.name.to_string()));
+        let root = destination.to_path_buf();
+        tokio::task::spawn_blocking(move || extract_builtin(builtin, &root))
+            .await
+            .map_err(|e| format!("Failed to extract template: {}", e))??;
+    }
+
+    let root = destination.to_path_buf();
+    let bootstrap_options = options.bootstrap.clone().unwrap_or_default();
+    let (initialized_git, bootstrap) = tokio::task::spawn_blocking(move || init_and_bootstrap(&root, &bootstrap_options))
+    .await
+    .map_err(|e| format!("Failed to bootstrap workspace: {}", e))??;
+    reporter.phase("bootstrapped", None);
+
+    let mut installed = Vec::new();
+    if options.install_dependencies {
+        let npm = script::npm_binary(&settings::node_binary(app).await);
+        let steps = install_steps(destination, bootstrap.facts.package_manager.as_deref(), &npm);
+        for step in &steps {
+            run_install_step(&git_state, reporter, destination, step).await?;
+            installed.push(format!("{} {}", step.program, step.args.join(" ")));
+        }
+    }
+
+    let canonical = workspace::canonical_dir(&destination.to_string_lossy())?;
+    Ok(CreatedWorkspace {
+        path: canonical.to_string_lossy().to_string(),
+        cloned,
+        initialized_git,
+        bootstrap,
+        installed,
+    })
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// The built-in starters `create_workspace_from_template` accepts by name
+#[tauri::command]
+pub async fn list_workspace_templates() -> Result<Vec<WorkspaceTemplateInfo>, String> {
+    Ok(BUILTIN_TEMPLATES
+        .iter()
+        .map(|t| WorkspaceTemplateInfo {
+            name: t.name.to_string(),
+            description: t.description.to_string(),
+        })
+        .collect())
+}
+
+/// Create a project at `destination` (missing or empty) from a git URL or a built-in template:
+/// clone or extract, `git init` when there's no repository, add the bootstrap scaffolding and
+/// optionally install dependencies, reporting `progress` throughout. The new workspace is
+/// registered so workspace commands accept it. On failure the destination is removed (or
+/// emptied, if it existed) unless `keep_partial` is set.
+#[tauri::command]
+pub async fn create_workspace_from_template(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    template: String,
+    destination: String,
+    options: Option<CreateWorkspaceOptions>,
+) -> Result<CreatedWorkspace, String> {
+    let options = options.unwrap_or_default();
+    let destination = PathBuf::from(&destination);
+    if !destination.is_absolute() {
+        return Err(format!("Destination must be an absolute path: {}", destination.display()));
+    }
+    let existed = prepare_destination(&destination)?;
+
+    let reporter = ProgressReporter::start(
+        &app,
+        "workspace-create",
+        options.operation_id.clone(),
+        Some(&destination.to_string_lossy()),
+        true,
+    );
+    let result = populate(&app, &reporter, &template, &destination, &options).await;
+    if result.is_err() && !options.keep_partial {
+        let destination = destination.clone();
+        let _ = tokio::task::spawn_blocking(move || clean_up(&destination, existed)).await;
+    }
+    let created = reporter.settle(result)?;
+
+    state
+        .selected_workspaces
+        .lock()
+        .await
+        .insert(PathBuf::from(&created.path));
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit_all, write};
+
+    /// A built-in template created the way `create_workspace_from_template` does, minus the app
+    fn create(template: &BuiltinTemplate, destination: &Path) -> (bool, BootstrapReport) {
+        assert!(!prepare_destination(destination).unwrap());
+        extract_builtin(template, destination).unwrap();
+        let options = BootstrapOptions {
+            gitignore: true,
+            ..Default::default()
+        };
+        init_and_bootstrap(destination, &options).unwrap()
+    }
+
+    /// Files a first build or install of each template creates, which its gitignore covers
+    fn build_output(template: &str) -> &'static [&'static str] {
+        match template {
+            "empty-node" => &["node_modules/left-pad/index.js", ".env"],
+            "empty-python" => &[".venv/bin/python", "src/app/__pycache__/main.cpython-312.pyc", ".env"],
+            "empty-rust" => &["target/debug/app"],
+            other => panic!("no build output listed for {}", other),
+        }
+    }
+
+    fn listed(files: &[git::GitFile]) -> Vec<&str> {
+        let mut paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn every_builtin_template_yields_a_repository_git_status_reads() {
+        for template in BUILTIN_TEMPLATES {
+            let parent = tempfile::tempdir().unwrap();
+            let destination = parent.path().join("My App 2");
+            let (initialized_git, report) = create(template, &destination);
+            assert!(initialized_git, "{}", template.name);
+            assert!(report.created.contains(&"CLAUDE.md".to_string()), "{}: {:?}", template.name, report);
+            assert!(report.facts.language.is_some(), "{}: {:?}", template.name, report.facts);
+
+            let working_dir = destination.to_string_lossy().to_string();
+            let status = git::compute_status(&working_dir, None, git::DEFAULT_RENAME_THRESHOLD).unwrap();
+            // Before the first commit, status still reads the branch HEAD will start
+            assert_ne!(status.branch, "HEAD", "{}", template.name);
+            let untracked = listed(&status.untracked);
+            for (relative, _) in template.files {
+                let relative = if *relative == "gitignore" { ".gitignore" } else { relative };
+                assert!(untracked.contains(&relative), "{}: {} not in {:?}", template.name, relative, untracked);
+            }
+            assert!(untracked.contains(&"CLAUDE.md") && untracked.contains(&".claude/settings.json"));
+            assert!(status.staged.is_empty() && status.modified.is_empty());
+
+            // The name placeholder is filled in with a package-safe name
+            for (relative, _) in template.files {
+                let relative = if *relative == "gitignore" { ".gitignore" } else { relative };
+                let content = std::fs::read_to_string(destination.join(relative)).unwrap();
+                assert!(!content.contains(NAME_PLACEHOLDER), "{}: {}", template.name, relative);
+            }
+
+            // Committed, the tree is clean; its gitignore keeps build output out of status
+            let repo = git2::Repository::open(&destination).unwrap();
+            repo.config().unwrap().set_str("user.name", "Test").unwrap();
+            repo.config().unwrap().set_str("user.email", "test@example.com").unwrap();
+            commit_all(&repo, "Initial commit");
+            for output in build_output(template.name) {
+                write(&destination, output, "");
+            }
+            let status = git::compute_status(&working_dir, None, git::DEFAULT_RENAME_THRESHOLD).unwrap();
+            assert!(status.files.is_empty(), "{}: {:?}", template.name, status.files);
+        }
+    }
+
+    #[test]
+    fn placeholder_names_are_package_safe() {
+        assert_eq!(project_name(Path::new("/work/My App 2")), "my-app-2");
+        assert_eq!(project_name(Path::new("/work/2024 report")), "project-2024-report");
+        assert_eq!(project_name(Path::new("/work/---")), "project");
+
+        let parent = tempfile::tempdir().unwrap();
+        let destination = parent.path().join("My App 2");
+        let node = BUILTIN_TEMPLATES.iter().find(|t| t.name == "empty-node").unwrap();
+        create(node, &destination);
+        let manifest: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(destination.join("package.json")).unwrap()).unwrap();
+        assert_eq!(manifest["name"], "my-app-2");
+    }
+
+    #[test]
+    fn failed_creation_leaves_the_destination_as_it_was() {
+        let parent = tempfile::tempdir().unwrap();
+        let created = parent.path().join("new");
+        assert!(!prepare_destination(&created).unwrap());
+        write(&created, "partial/file.txt", "x");
+        clean_up(&created, false);
+        assert!(!created.exists());
+
+        let existing = parent.path().join("existing");
+        std::fs::create_dir(&existing).unwrap();
+        assert!(prepare_destination(&existing).unwrap());
+        write(&existing, "partial/file.txt", "x");
+        write(&existing, ".git/HEAD", "x");
+        clean_up(&existing, true);
+        assert!(existing.is_dir());
+        assert_eq!(std::fs::read_dir(&existing).unwrap().count(), 0);
+
+        write(&existing, "kept.txt", "x");
+        assert!(prepare_destination(&existing).unwrap_err().contains("not empty"));
+    }
+}
@@ -0,0 +1,5 @@
+# TODO: read the port from the environment
+def main():
+    print(message)  # FIXME: log instead of print
+    ## HACK(dev): monkeypatch until the fix lands
+    return message
@@ -0,0 +1,2 @@
+@echo off
+REM TODO: support paths with spaces
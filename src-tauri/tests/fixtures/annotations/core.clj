@@ -0,0 +1,2 @@
+;; TODO(rich): memoize
+(defn f [x] x) ; HACK: identity for now
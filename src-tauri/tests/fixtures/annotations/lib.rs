@@ -0,0 +1,11 @@
+//! Parser entry points
+// TODO: handle CRLF input
+/// FIXME(ana): the doc example panics
+fn parse() {
+    let label = "TODO: not a comment";
+    let todos = 3; // XXX wrong count after a merge
+    /* HACK: skip the BOM */
+    /*
+     * TODO(li) split this function
+     */
+}
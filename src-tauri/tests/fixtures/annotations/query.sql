@@ -0,0 +1,2 @@
+-- TODO: add an index on user_id
+SELECT id FROM users; -- FIXME(db-team) full scan
@@ -0,0 +1,6 @@
+def main() -> None:
+    print("Hello from {{name}}")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,73 @@
+// mensa - App Data Service
+// Provides frontend wrappers for exporting and importing mensa's stores between machines
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+export type AppDataStore =
+  | 'settings'
+  | 'presets'
+  | 'templates'
+  | 'history'
+  | 'statusFilters'
+  | 'sensitivePatterns'
+  | 'reviewDrafts'
+  | 'workspaceStates'
+  | 'projectMeta';
+
+export interface ExportReport {
+  path: string;
+  bytes: number;
+  stores: Array<{ name: AppDataStore; entries: string[] }>;
+}
+
+/** merge keeps local values on conflict; replace overwrites with the bundle's copy */
+export type ImportMode = 'merge' | 'replace';
+
+export interface ImportReport {
+  formatVersion: number;
+  mode: ImportMode;
+  stores: Array<{
+    store: string;
+    filesWritten: number;
+    conflicts: Array<{ file: string; key: string; resolution: 'keptExisting' | 'replaced' }>;
+    error?: string;
+  }>;
+  /** Workspaces referenced by the imported data that don't exist on this machine */
+  missingWorkspaces: string[];
+}
+
+/**
+ * Bundle the chosen stores (all of them for an empty list) into a zip; secrets are never included
+ */
+export async function exportAppData(destinationPath: string, include: AppDataStore[] = []): Promise<ExportReport> {
+  return invoke<ExportReport>('export_app_data', { destinationPath, include });
+}
+
+/**
+ * Import a bundle made by exportAppData
+ */
+export async function importAppData(sourcePath: string, mode: ImportMode): Promise<ImportReport> {
+  return invoke<ImportReport>('import_app_data', { sourcePath, mode });
+}
+
+/**
+ * Subscribe to finished imports (any window), e.g. to reload presets and settings
+ */
+export async function onAppDataImported(callback: (report: ImportReport) => void): Promise<UnlistenFn> {
+  return listen<ImportReport>('app-data-imported', (event) => callback(event.payload));
+}
+
+/** A store file was unreadable (e.g. cut short by a crash) and was repaired on read */
+export interface StoreRecovered {
+  store: string;
+  path: string;
+  /** backup: the last good version was restored; default: no usable backup, the store was reset */
+  recovered_from: 'backup' | 'default';
+  /** Where the unreadable file was kept, if there was one */
+  corrupt_copy: string | null;
+}
+
+export async function onStoreRecovered(callback: (recovered: StoreRecovered) => void): Promise<UnlistenFn> {
+  return listen<StoreRecovered>('store-recovered', (event) => callback(event.payload));
+}
@@ -0,0 +1,70 @@
+// mensa - Bookmarks Service
+// Provides frontend wrappers for session message bookmarks
+
+import { invoke } from '@tauri-apps/api/core';
+
+export interface MessageBookmark {
+  id: string;
+  sessionId: string;
+  messageIndex: number;
+  note: string;
+  timestamp: string;
+  contentHash: string;
+  createdAt: number;
+  /** Current index of the message, or null when it can no longer be found */
+  resolvedIndex: number | null;
+}
+
+export interface SessionMessagesPage<T = unknown> {
+  messages: T[];
+  offset: number;
+  total: number;
+  bookmarks: MessageBookmark[];
+  /** The last transcript line is still being written */
+  writeInProgress: boolean;
+  /** Broken lines that were skipped; repairSessionFile fixes them */
+  malformedLines: number;
+}
+
+/**
+ * Bookmark a message by its index in load_session_messages
+ */
+export async function addMessageBookmark(
+  workspacePath: string,
+  sessionId: string,
+  messageIndex: number,
+  note: string
+): Promise<MessageBookmark> {
+  return invoke<MessageBookmark>('add_message_bookmark', { workspacePath, sessionId, messageIndex, note });
+}
+
+/**
+ * List a session's bookmarks, re-located against the current transcript
+ */
+export async function listMessageBookmarks(sessionId: string, workspacePath?: string): Promise<MessageBookmark[]> {
+  return invoke<MessageBookmark[]>('list_message_bookmarks', { sessionId, workspacePath: workspacePath ?? null });
+}
+
+export async function removeMessageBookmark(id: string): Promise<boolean> {
+  return invoke<boolean>('remove_message_bookmark', { id });
+}
+
+/**
+ * Load one window of a session's messages along with the bookmarks inside it.
+ * With includeSourceSpans each message gets a sourceSpan (see sessions.ts).
+ */
+export async function loadSessionMessagesPage<T = unknown>(
+  workspacePath: string,
+  sessionId: string,
+  offset: number,
+  limit: number,
+  includeSourceSpans = false
+): Promise<SessionMessagesPage<T>> {
+  return invoke<SessionMessagesPage<T>>('load_session_messages_page', {
+    workspacePath,
+    sessionId,
+    offset,
+    limit,
+    includeSourceSpans,
+  });
+}
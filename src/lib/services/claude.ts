@@ -2,11 +2,13 @@
 // This service handles communication with Claude via the backend
 
 import { invoke } from '@tauri-apps/api/core';
-import { listen, type UnlistenFn } from '@tauri-apps/api/event';
-import type { ContentBlock, SettingSource, SlashCommand, PlanModeQuestion, AllowedPrompt } from '$lib/types';
+import { listen, type EventCallback, type UnlistenFn } from '@tauri-apps/api/event';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
+import type { ContentBlock, SettingSource, SlashCommand, PlanModeQuestion, AllowedPrompt, HookEvent } from '$lib/types';
+import type { SessionHolder } from './sessions';
 
 export interface ClaudeStreamEvent {
-  type: 'text' | 'tool_use' | 'tool_result' | 'error' | 'done' | 'system_init' | 'cancelled' | 'ask_user_question' | 'exit_plan_mode';
+  type: 'text' | 'tool_use' | 'tool_result' | 'error' | 'done' | 'system_init' | 'cancelled' | 'ask_user_question' | 'exit_plan_mode' | 'cost_warning' | 'context_usage' | 'context_warning';
   queryId?: string;
   sessionId?: string;  // Claude backend session ID for resume functionality
   content?: string;
@@ -15,10 +17,18 @@ export interface ClaudeStreamEvent {
     name: string;
     input?: Record<string, unknown> | string;
     result?: string;
+    resultTruncated?: boolean;   // result is a preview; fetch the rest with getQueryToolOutput
+    resultTotalBytes?: number;
   };
   error?: string;
   slashCommands?: SlashCommand[];
-  reason?: string;  // For cancelled events
+  reason?: string;  // For cancelled events ('user_cancelled' | 'cost_limit_exceeded' | 'timed_out')
+  // For cost_warning and cost-limit cancellations
+  costUsd?: number;
+  maxCostUsd?: number;
+  // For context_usage and context_warning
+  contextUsage?: ContextUsage;
+  contextWarning?: ContextWarning;
   // For ask_user_question
   questions?: PlanModeQuestion[];
   toolUseId?: string;
@@ -30,18 +40,128 @@ export interface ClaudeStreamEvent {
 
 export type StreamCallback = (event: ClaudeStreamEvent) => void;
 
+// Error returned by query_claude
+export type QueryError =
+  | { kind: 'sessionWorkspaceMismatch'; recordedCwd: string; workingDir: string }
+  | { kind: 'unknownReferences'; references: ReferenceCheck[] }
+  | { kind: 'nodeTooOld'; found: string; required: string; path: string; alternative: NodeCandidate | null }
+  | { kind: 'lowDiskSpace'; path: string; freeBytes: number; floorBytes: number }
+  | { kind: 'sessionInUse'; sessionId: string; by: SessionHolder; hint: string }
+  | { kind: 'failed'; message: string };
+
+export function asQueryError(e: unknown): QueryError | null {
+  if (e && typeof e === 'object' && 'kind' in e) {
+    return e as QueryError;
+  }
+  return null;
+}
+
+function queryErrorMessage(e: unknown): string {
+  const queryError = asQueryError(e);
+  if (queryError?.kind === 'sessionWorkspaceMismatch') {
+    return `This session was recorded in ${queryError.recordedCwd}, not ${queryError.workingDir}`;
+  }
+  if (queryError?.kind === 'unknownReferences') {
+    return `The prompt references files that don't exist: ${queryError.references.map((r) => r.reference).join(', ')}`;
+  }
+  if (queryError?.kind === 'nodeTooOld') {
+    const instead = queryError.alternative
+      ? ` Use ${queryError.alternative.path} (${queryError.alternative.version}) instead.`
+      : '';
+    return `Node ${queryError.found} at ${queryError.path} is too old; ${queryError.required} or newer is required.${instead}`;
+  }
+  if (queryError?.kind === 'lowDiskSpace') {
+    const mb = (bytes: number) => Math.floor(bytes / (1024 * 1024));
+    return `Only ${mb(queryError.freeBytes)} MB free on the volume of ${queryError.path} (at least ${mb(queryError.floorBytes)} MB needed)`;
+  }
+  if (queryError?.kind === 'sessionInUse') {
+    const by = queryError.by.kind === 'claudeCli'
+      ? `Claude Code (pid ${queryError.by.pid}) in ${queryError.by.cwd}`
+      : `another mensa query (${queryError.by.queryId})`;
+    return `This session's project is in use by ${by}. ${queryError.hint}`;
+  }
+  if (queryError?.kind === 'failed') {
+    return queryError.message;
+  }
+  return e instanceof Error ? e.message : String(e);
+}
+
 // Stream payload from backend with query_id
 interface StreamPayload {
   query_id: string;
   data: string;
+  seq: number;  // position in the query's event sequence (see replayQueryEvents)
+}
+
+export type StderrSeverity = 'debug' | 'info' | 'warning' | 'error';
+
+/** A stderr line (or an error with its stack frames), tagged by the backend's classifier */
+interface StderrPayload extends StreamPayload {
+  severity: StderrSeverity;
 }
 
 // Done payload from backend
 interface DonePayload {
   query_id: string;
   code: number;
+  reason?: 'cost_limit_exceeded' | 'cancelled' | 'cancelled_before_start' | 'predecessor_failed' | 'timed_out';
+  seq: number;  // always the query's highest
+  dropped_events: number;  // context-usage and file-change updates skipped while the window lagged
+}
+
+// Payload of claude-cost-warning and claude-cost-limit
+interface CostPayload {
+  query_id: string;
+  cost_usd: number;
+  max_cost_usd: number;
 }
 
+// Payload of claude-context-usage (at most once a second) and get_query_context_usage
+export interface ContextUsage {
+  query_id: string;
+  model: string | null;
+  /** Tokens the latest assistant message saw plus what it wrote */
+  used_tokens: number;
+  context_limit: number;
+  percent: number;
+  input_tokens: number;
+  output_tokens: number;
+  cache_creation_input_tokens: number;
+  cache_read_input_tokens: number;
+  output_tokens_per_sec: number;
+}
+
+// Payload of claude-context-warning, once per threshold (settings.contextWarningPercents) crossed
+export interface ContextWarning {
+  query_id: string;
+  threshold: number;
+  /** The highest threshold: suggest a fresh session or compacting */
+  critical: boolean;
+  used_tokens: number;
+  context_limit: number;
+  percent: number;
+}
+
+// Optional backend behaviours for a query
+export interface QueryOptions {
+  redactSensitive?: boolean;
+  secretEnv?: string[];
+  maxImageEdge?: number;
+  allowRemap?: boolean;
+  toolOutputPreview?: number;
+  maxCostUsd?: number;          // stop the query once its cost reaches this (default: config maxCostUsd)
+  costWarningFraction?: number; // warn once at this fraction of maxCostUsd (default 0.8)
+  trimImagesOnResume?: boolean; // resume from a copy with large images replaced (see services/context)
+  validateReferences?: boolean; // check file paths in the prompt, warning via onPromptReferenceWarnings
+  strictReferences?: boolean;   // refuse to start (unknownReferences) instead of warning
+  skipDiskCheck?: boolean;      // don't check free disk space before starting or watch it while running
+  forceResume?: boolean;        // resume even while another Claude Code uses the project (sessionInUse)
+}
+
+// 'quick': one turn without tools, stopped after 90s, its session deleted afterwards.
+// Attachments are fine; resumeSession and toolResult are refused.
+export type QueryMode = 'quick';
+
 // Return type for streaming query
 export interface QueryHandle {
   queryId: string;
@@ -57,6 +177,8 @@ interface ClaudeContentBlock {
   tool_use_id?: string;  // for tool_result
   content?: string;      // tool_result content
   is_error?: boolean;
+  output_truncated?: boolean;    // content cut to a preview by the backend
+  output_total_bytes?: number;
 }
 
 interface ClaudeJsonMessage {
@@ -80,6 +202,7 @@ interface ClaudeJsonMessage {
 }
 
 export interface ClaudeQueryConfig {
+  model?: string;
   permissionMode?: string;
   maxTurns?: number;
   mcpServers?: Array<{
@@ -98,6 +221,12 @@ export interface ClaudeQueryConfig {
   enableSkills?: boolean;
 }
 
+// Query events are sent only to the windows following the query (the one that started it,
+// or one that called subscribeQuery), so listen on this window rather than on every target
+function listenQuery<T>(event: string, handler: EventCallback<T>): Promise<UnlistenFn> {
+  return getCurrentWebviewWindow().listen<T>(event, handler);
+}
+
 /**
  * Send a message to Claude CLI and stream the response
  * Returns a handle with the queryId and a cancel function
@@ -108,7 +237,13 @@ export async function queryClaudeStreaming(
   onEvent: StreamCallback,
   config?: ClaudeQueryConfig,
   resumeSession?: string,
-  toolResult?: { tool_use_id: string; content: unknown }
+  toolResult?: { tool_use_id: string; content: unknown },
+  preset?: string,
+  template?: { name: string; vars: Record<string, string> },
+  options?: QueryOptions,
+  sensitive?: boolean,  // keep this prompt out of the prompt history
+  attachmentIds?: string[],  // staged clipboard images from ingestClipboardImage
+  mode?: QueryMode
 ): Promise<QueryHandle> {
   const hasAttachments = typeof prompt !== 'string';
   const promptStr = hasAttachments ? JSON.stringify(prompt) : prompt;
@@ -118,6 +253,11 @@ export async function queryClaudeStreaming(
   let unlistenStream: UnlistenFn | null = null;
   let unlistenDone: UnlistenFn | null = null;
   let unlistenStderr: UnlistenFn | null = null;
+  let unlistenCostWarning: UnlistenFn | null = null;
+  let unlistenCostLimit: UnlistenFn | null = null;
+  let unlistenContextUsage: UnlistenFn | null = null;
+  let unlistenContextWarning: UnlistenFn | null = null;
+  let lastCost: CostPayload | null = null;
 
   // Per-session tool tracking (no longer global)
   const sessionToolUseIdToName = new Map<string, string>();
@@ -138,7 +278,7 @@ export async function queryClaudeStreaming(
 
   try {
     // Listen for streaming data
-    unlistenStream = await listen<StreamPayload>('claude-stream', (event) => {
+    unlistenStream = await listenQuery<StreamPayload>('claude-stream', (event) => {
       const { query_id, data } = event.payload;
 
       // Only process events for this query
@@ -160,31 +300,61 @@ export async function queryClaudeStreaming(
       }
     });
 
-    // Listen for stderr (error messages)
-    unlistenStderr = await listen<StreamPayload>('claude-stderr', (event) => {
-      const { query_id, data } = event.payload;
+    // Listen for stderr (error messages); benign noise (debug/info) stays out of the error shown
+    unlistenStderr = await listenQuery<StderrPayload>('claude-stderr', (event) => {
+      const { query_id, data, severity } = event.payload;
       if (resolvedQueryId && query_id !== resolvedQueryId) return;
 
+      if (severity === 'debug' || severity === 'info') {
+        console.debug('[claude stderr]', query_id, data);
+        return;
+      }
       const current = stderrByQuery.get(query_id) || '';
       stderrByQuery.set(query_id, current + data + '\n');
       console.error('[claude stderr]', query_id, data);
     });
 
+    // Listen for cost ceiling events
+    unlistenCostWarning = await listenQuery<CostPayload>('claude-cost-warning', (event) => {
+      if (resolvedQueryId && event.payload.query_id !== resolvedQueryId) return;
+      emitEvent({ type: 'cost_warning', costUsd: event.payload.cost_usd, maxCostUsd: event.payload.max_cost_usd });
+    });
+    unlistenCostLimit = await listenQuery<CostPayload>('claude-cost-limit', (event) => {
+      if (resolvedQueryId && event.payload.query_id !== resolvedQueryId) return;
+      lastCost = event.payload;
+    });
+
+    // Listen for context window usage
+    unlistenContextUsage = await listenQuery<ContextUsage>('claude-context-usage', (event) => {
+      if (resolvedQueryId && event.payload.query_id !== resolvedQueryId) return;
+      emitEvent({ type: 'context_usage', contextUsage: event.payload });
+    });
+    unlistenContextWarning = await listenQuery<ContextWarning>('claude-context-warning', (event) => {
+      if (resolvedQueryId && event.payload.query_id !== resolvedQueryId) return;
+      emitEvent({ type: 'context_warning', contextWarning: event.payload });
+    });
+
     // Listen for completion
-    unlistenDone = await listen<DonePayload>('claude-done', (event) => {
-      const { query_id, code } = event.payload;
+    unlistenDone = await listenQuery<DonePayload>('claude-done', (event) => {
+      const { query_id, code, reason } = event.payload;
 
       // Only process events for this query
       if (resolvedQueryId && query_id !== resolvedQueryId) return;
 
-      if (code !== 0) {
-        // Filter out debug messages (lines starting with [claude-query])
-        const stderrOutput = stderrByQuery.get(query_id) || '';
-        const errorLines = stderrOutput
-          .split('\n')
-          .filter(line => !line.startsWith('[claude-query]'))
-          .join('\n')
-          .trim();
+      if (reason === 'timed_out') {
+        emitEvent({ type: 'cancelled', reason });
+      } else if (reason === 'cost_limit_exceeded') {
+        emitEvent({
+          type: 'cancelled',
+          reason,
+          costUsd: lastCost?.cost_usd,
+          maxCostUsd: lastCost?.max_cost_usd
+        });
+      } else if (reason === 'cancelled' || reason === 'cancelled_before_start') {
+        emitEvent({ type: 'cancelled', reason: 'user_cancelled' });
+      } else if (code !== 0) {
+        // Debug messages ([claude-query] lines included) were already left out by severity
+        const errorLines = (stderrByQuery.get(query_id) || '').trim();
         const errorMsg = errorLines || `Claude exited with code ${code}`;
         emitEvent({ type: 'error', error: errorMsg });
       }
@@ -194,6 +364,10 @@ export async function queryClaudeStreaming(
       unlistenStream?.();
       unlistenStderr?.();
       unlistenDone?.();
+      unlistenCostWarning?.();
+      unlistenCostLimit?.();
+      unlistenContextUsage?.();
+      unlistenContextWarning?.();
 
       // Clean up session data
       stderrByQuery.delete(query_id);
@@ -207,7 +381,13 @@ export async function queryClaudeStreaming(
       config: config ? JSON.stringify(config) : null,
       resumeSession: resumeSession || null,
       hasAttachments: hasAttachments || null,
-      toolResult: toolResult ? JSON.stringify(toolResult) : null
+      attachmentIds: attachmentIds?.length ? attachmentIds : null,
+      toolResult: toolResult ? JSON.stringify(toolResult) : null,
+      preset: preset || null,
+      template: template || null,
+      options: options || null,
+      sensitive: sensitive || null,
+      mode: mode || null
     });
     console.log('[claude] invoke query_claude returned queryId:', resolvedQueryId);
 
@@ -217,7 +397,9 @@ export async function queryClaudeStreaming(
       cancel: async () => {
         console.log('[claude] Cancelling query:', resolvedQueryId);
         try {
-          await invoke('cancel_query', { queryId: resolvedQueryId });
+          const outcome = await cancelQuery(resolvedQueryId);
+          // Anything the backend found ends with its own claude-done, which cleans up
+          if (outcome.action !== 'nothing') return;
           emitEvent({ type: 'cancelled', reason: 'user_cancelled' });
           emitEvent({ type: 'done' });
         } catch (e) {
@@ -228,10 +410,14 @@ export async function queryClaudeStreaming(
         unlistenStream?.();
         unlistenStderr?.();
         unlistenDone?.();
+        unlistenCostWarning?.();
+        unlistenCostLimit?.();
+        unlistenContextUsage?.();
+        unlistenContextWarning?.();
       }
     };
   } catch (error) {
-    const errorMsg = error instanceof Error ? error.message : String(error);
+    const errorMsg = queryErrorMessage(error);
     onEvent({ type: 'error', error: errorMsg, queryId: resolvedQueryId || undefined });
     onEvent({ type: 'done', queryId: resolvedQueryId || undefined });
 
@@ -239,6 +425,10 @@ export async function queryClaudeStreaming(
     unlistenStream?.();
     unlistenStderr?.();
     unlistenDone?.();
+    unlistenCostWarning?.();
+    unlistenCostLimit?.();
+    unlistenContextUsage?.();
+    unlistenContextWarning?.();
 
     // Return a no-op handle
     return {
@@ -248,6 +438,454 @@ export async function queryClaudeStreaming(
   }
 }
 
+export interface QueryFilesChangedPayload {
+  query_id: string;
+  files: string[];
+}
+
+// Files the agent has touched so far in a query (or the final set once it finished)
+export async function getQueryChangedFiles(queryId: string): Promise<string[]> {
+  return invoke<string[]>('get_query_changed_files', { queryId });
+}
+
+// Latest context window usage of a running query (null before the first assistant message)
+export async function getQueryContextUsage(queryId: string): Promise<ContextUsage | null> {
+  return invoke<ContextUsage | null>('get_query_context_usage', { queryId });
+}
+
+// Subscribe to live changed-file updates for a query
+export async function onQueryFilesChanged(
+  queryId: string,
+  callback: (files: string[]) => void
+): Promise<UnlistenFn> {
+  return listenQuery<QueryFilesChangedPayload>('query-files-changed', (event) => {
+    if (event.payload.query_id === queryId) {
+      callback(event.payload.files);
+    }
+  });
+}
+
+export interface ProcessedAttachment {
+  index: number;
+  mediaType: string;
+  width: number;
+  height: number;
+  originalBytes: number;
+  bytes: number;
+  warnings?: string[];
+}
+
+interface AttachmentsProcessedPayload {
+  query_id: string;
+  attachments: ProcessedAttachment[];
+}
+
+// Subscribe to the resized/re-encoded image details actually sent for a query
+export async function onAttachmentsProcessed(
+  queryId: string,
+  callback: (attachments: ProcessedAttachment[]) => void
+): Promise<UnlistenFn> {
+  return listenQuery<AttachmentsProcessedPayload>('attachments-processed', (event) => {
+    if (event.payload.query_id === queryId) {
+      callback(event.payload.attachments);
+    }
+  });
+}
+
+export interface ReferenceCheck {
+  reference: string;            // as written in the prompt
+  kind: 'mention' | 'path';
+  path: string;                 // the path checked, without a :line suffix
+  exists: boolean;
+  isDir: boolean;
+  canonicalPath?: string | null; // on-disk casing, when it differs from what was written
+  suggestions: string[];
+}
+
+export interface PromptReferenceReport {
+  references: ReferenceCheck[];
+  missing: number;
+}
+
+// Check the file paths a prompt mentions before sending it
+export async function validatePromptReferences(workingDir: string, prompt: string): Promise<PromptReferenceReport> {
+  return invoke<PromptReferenceReport>('validate_prompt_references', { workingDir, prompt });
+}
+
+interface PromptReferenceWarningsPayload {
+  query_id: string;
+  references: ReferenceCheck[];
+}
+
+// Subscribe to the missing references found when a query started with validateReferences
+export async function onPromptReferenceWarnings(
+  queryId: string,
+  callback: (references: ReferenceCheck[]) => void
+): Promise<UnlistenFn> {
+  return listenQuery<PromptReferenceWarningsPayload>('prompt-reference-warnings', (event) => {
+    if (event.payload.query_id === queryId) {
+      callback(event.payload.references);
+    }
+  });
+}
+
+export interface VolumeSpace {
+  path: string;
+  freeBytes: number;
+  totalBytes: number;
+}
+
+export interface DiskSpaceReport {
+  workspace: VolumeSpace;
+  /** Where Claude Code writes session transcripts */
+  claudeDir: VolumeSpace;
+  sameVolume: boolean;
+}
+
+// Free and total bytes of the volumes holding the workspace and ~/.claude
+export async function checkDiskSpace(workingDir: string): Promise<DiskSpaceReport> {
+  return invoke<DiskSpaceReport>('check_disk_space', { workingDir });
+}
+
+export interface LowDiskWarning {
+  query_id: string;
+  path: string;
+  free_bytes: number;
+  total_bytes: number;
+  threshold_bytes: number;
+  /** Below the hard floor (only seen mid-run; a query doesn't start below it) */
+  critical: boolean;
+  /** Seen while the query ran rather than before it started */
+  running: boolean;
+}
+
+// Subscribe to low free space on a query's volumes, before it starts and while it runs
+export async function onLowDiskWarning(queryId: string, callback: (warning: LowDiskWarning) => void): Promise<UnlistenFn> {
+  return listenQuery<LowDiskWarning>('low-disk-warning', (event) => {
+    if (event.payload.query_id === queryId) {
+      callback(event.payload);
+    }
+  });
+}
+
+export interface StagedAttachment {
+  attachmentId: string;
+  mediaType: string;
+  width: number;
+  height: number;
+  bytes: number;
+  thumbnailB64: string;  // PNG preview
+}
+
+export type ClipboardImageError =
+  | { kind: 'noImage' }
+  | { kind: 'unsupportedFormat'; message: string }
+  | { kind: 'failed'; message: string };
+
+// Read the clipboard image in the backend and stage it; send it by passing attachmentId
+// to queryClaudeStreaming. Unsent images expire after an hour.
+export async function ingestClipboardImage(maxEdge?: number): Promise<StagedAttachment> {
+  return invoke<StagedAttachment>('ingest_clipboard_image', { maxEdge: maxEdge ?? null });
+}
+
+// Subscribe to hooks (formatters, linters, guards) running or blocking tools during a query
+export async function onHookEvent(
+  queryId: string,
+  callback: (hook: HookEvent) => void
+): Promise<UnlistenFn> {
+  return listenQuery<HookEvent & { query_id: string }>('claude-hook-event', (event) => {
+    const { query_id, ...hook } = event.payload;
+    if (query_id === queryId) {
+      callback(hook);
+    }
+  });
+}
+
+// One buffered query event as it was emitted (payload includes the same seq)
+export interface ReplayEvent {
+  seq: number;
+  event: string;
+  payload: Record<string, unknown>;
+  /** Unix milliseconds when it was emitted */
+  atMs: number;
+}
+
+export interface ReplayPage {
+  queryId: string;
+  events: ReplayEvent[];
+  /** Events before this seq were evicted; load the session transcript for that prefix */
+  evictedBeforeSeq: number | null;
+  nextSeq: number;
+  finished: boolean;
+}
+
+export interface ReplayBufferInfo {
+  queryId: string;
+  nextSeq: number;
+  finished: boolean;
+}
+
+// Follow a query from this window: its events arrive here from nextSeq on, and the page holds
+// what was buffered before (e.g. a query started from the command line)
+export async function subscribeQuery(queryId: string, fromSeq = 0): Promise<ReplayPage> {
+  return invoke<ReplayPage>('subscribe_query', { queryId, fromSeq });
+}
+
+// Buffered events of a running or recently finished query, from fromSeq on
+export async function replayQueryEvents(queryId: string, fromSeq = 0): Promise<ReplayPage> {
+  return invoke<ReplayPage>('replay_query_events', { queryId, fromSeq });
+}
+
+// Queries whose events can be replayed (running, or finished and not yet acknowledged)
+export async function listReplayBuffers(): Promise<ReplayBufferInfo[]> {
+  return invoke<ReplayBufferInfo[]>('list_replay_buffers');
+}
+
+// Release a finished query's buffer once its state has been rebuilt
+export async function ackQueryEvents(queryId: string): Promise<boolean> {
+  return invoke<boolean>('ack_query_events', { queryId });
+}
+
+// Seq of the last event a query emitted (null before the first), to tell whether any were missed
+export async function getLastSeq(queryId: string): Promise<number | null> {
+  return invoke<number | null>('get_last_seq', { queryId });
+}
+
+export interface ReattachHandle {
+  queryId: string;
+  /** Set when the start of the query was evicted and must come from load_session_messages */
+  evictedBeforeSeq: number | null;
+  detach: () => void;
+}
+
+/**
+ * Rebuild the stream of a query started before the webview reloaded (or in another window,
+ * or from the command line), then follow it live from this window.
+ * Live events are held back until the replay is applied and dropped if already replayed,
+ * so every event is delivered exactly once and in sequence order.
+ */
+export async function reattachQueryStream(queryId: string, onEvent: StreamCallback): Promise<ReattachHandle> {
+  const toolUseIdToName = new Map<string, string>();
+  const emitEvent = (event: ClaudeStreamEvent) => onEvent({ ...event, queryId });
+  let lastSeq = -1;
+  let pending: ReplayEvent[] | null = [];
+  let finished = false;
+  let unlistenStream: UnlistenFn | null = null;
+  let unlistenDone: UnlistenFn | null = null;
+
+  const detach = () => {
+    unlistenStream?.();
+    unlistenDone?.();
+  };
+
+  const apply = (item: ReplayEvent) => {
+    if (item.seq <= lastSeq || finished) return;
+    lastSeq = item.seq;
+    if (item.event === 'claude-stream') {
+      const data = String(item.payload.data ?? '');
+      if (!data.trim()) return;
+      try {
+        handleClaudeMessage(JSON.parse(data) as ClaudeJsonMessage, emitEvent, toolUseIdToName);
+      } catch {
+        emitEvent({ type: 'text', content: data + '\n' });
+      }
+    } else if (item.event === 'claude-done') {
+      finished = true;
+      const payload = item.payload as unknown as DonePayload;
+      if (payload.reason === 'cost_limit_exceeded' || payload.reason === 'timed_out') {
+        emitEvent({ type: 'cancelled', reason: payload.reason });
+      } else if (payload.reason === 'cancelled' || payload.reason === 'cancelled_before_start') {
+        emitEvent({ type: 'cancelled', reason: 'user_cancelled' });
+      } else if (payload.code !== 0) {
+        emitEvent({ type: 'error', error: `Claude exited with code ${payload.code}` });
+      }
+      emitEvent({ type: 'done' });
+      detach();
+      void ackQueryEvents(queryId);
+    }
+  };
+
+  const onLive = (event: string, payload: { query_id: string; seq: number }) => {
+    if (payload.query_id !== queryId) return;
+    const item = { seq: payload.seq, event, payload: payload as unknown as Record<string, unknown> };
+    if (pending) {
+      pending.push(item);
+    } else {
+      apply(item);
+    }
+  };
+
+  unlistenStream = await listenQuery<StreamPayload>('claude-stream', (event) => onLive('claude-stream', event.payload));
+  unlistenDone = await listenQuery<DonePayload>('claude-done', (event) => onLive('claude-done', event.payload));
+
+  let page: ReplayPage;
+  try {
+    page = await subscribeQuery(queryId);
+  } catch (e) {
+    detach();
+    throw e;
+  }
+  page.events.forEach(apply);
+  const held = pending;
+  pending = null;
+  held.sort((a, b) => a.seq - b.seq).forEach(apply);
+
+  return { queryId, evictedBeforeSeq: page.evictedBeforeSeq, detach };
+}
+
+export interface NodeCandidate {
+  path: string;
+  version: string;
+}
+
+/** ok; tooOld: queries are refused; unrecognized: queries run with a warning; missing: couldn't be run */
+export type NodeStatus = 'ok' | 'tooOld' | 'unrecognized' | 'missing';
+
+export interface NodeCheck {
+  path: string;
+  /** As printed by `node --version` */
+  version: string | null;
+  required: string;
+  status: NodeStatus;
+  /** Newest binary in the usual locations that is new enough, when this one isn't */
+  alternative: NodeCandidate | null;
+}
+
+export interface RuntimeHealth {
+  nodeBinary: string;
+  nodeFound: boolean;
+  nodeCheck: NodeCheck;
+  script: { path: string; source: 'resourceDir' | 'executable' | 'devCwd' | 'embedded' } | null;
+  scriptError: string | null;
+  /** The packaged script can't be used where the app is; it runs from app data instead */
+  appLocation: AppLocationIssue | null;
+}
+
+export interface AppLocationIssue {
+  /** translocated: opened where it was downloaded, so macOS runs it from a temporary read-only copy */
+  kind: 'translocated' | 'readOnly';
+  resourceDir: string;
+  message: string;
+}
+
+// Report which node binary and query script (and script source) queries will use
+export async function checkRuntimeHealth(): Promise<RuntimeHealth> {
+  return invoke<RuntimeHealth>('check_runtime_health');
+}
+
+// Show the running app in the file manager, so it can be moved to the Applications folder
+export async function revealAppLocation(): Promise<string> {
+  return invoke<string>('reveal_app_location');
+}
+
+// Run queries with this node binary from now on (saved as nodePath) after checking its version
+export async function setPreferredRuntimePath(path: string): Promise<NodeCheck> {
+  return invoke<NodeCheck>('set_preferred_runtime_path', { path });
+}
+
+// Queue a prompt to auto-send when the query finishes successfully; returns the follow-up's query id
+export async function queueFollowup(queryId: string, prompt: string, config?: ClaudeQueryConfig): Promise<string> {
+  return invoke<string>('queue_followup', {
+    queryId,
+    prompt,
+    config: config ? JSON.stringify(config) : null
+  });
+}
+
+export async function clearFollowup(queryId: string): Promise<boolean> {
+  return invoke<boolean>('clear_followup', { queryId });
+}
+
+// What cancel_query found and did; every cancelled query still gets one claude-done with its reason
+export interface CancelOutcome {
+  queryId: string;
+  found:
+    | { state: 'running' | 'draining' | 'cancelling' | 'notFound' }
+    | { state: 'queuedFollowup'; predecessor: string }
+    | { state: 'queuedInGroup'; groupId: string };
+  action: 'stopped' | 'leftToFinish' | 'alreadyStopping' | 'removedFromQueue' | 'nothing';
+  cancelledFollowup: string | null;
+  /** Left queued by keepFollowup; it resumes the cancelled run's session */
+  keptFollowup: string | null;
+}
+
+// Cancel a running or queued query; its queued follow-up goes with it unless keepFollowup
+export async function cancelQuery(queryId: string, keepFollowup = false): Promise<CancelOutcome> {
+  return invoke<CancelOutcome>('cancel_query', { queryId, keepFollowup });
+}
+
+// One workspace's prompt in a query group
+export interface GroupQuery {
+  workingDir: string;
+  prompt: string;
+  config?: ClaudeQueryConfig;
+}
+
+export interface GroupOptions {
+  /** Run at most this many members at once, on top of settings.maxConcurrentQueries */
+  maxParallel?: number;
+}
+
+export interface GroupStarted {
+  groupId: string;
+  /** In the order the members were given */
+  queryIds: string[];
+}
+
+// One member's outcome in claude-group-done
+export interface GroupResult {
+  query_id: string;
+  working_dir: string;
+  /** "completed" | "failed" | "cancelled" | "cost_limit_exceeded" */
+  exit_reason: string;
+  cost: number | null;
+  /** Why the member couldn't start */
+  error: string | null;
+}
+
+export interface GroupDone {
+  group_id: string;
+  results: GroupResult[];
+}
+
+// Run one prompt per workspace; member events carry group_id, and claude-group-done follows the last one
+export async function queryClaudeMulti(prompts: GroupQuery[], groupOptions?: GroupOptions): Promise<GroupStarted> {
+  return invoke<GroupStarted>('query_claude_multi', {
+    prompts: prompts.map((p) => ({
+      workingDir: p.workingDir,
+      prompt: p.prompt,
+      config: p.config ? JSON.stringify(p.config) : null
+    })),
+    groupOptions
+  });
+}
+
+// Cancel every queued and running member of a group
+export async function cancelGroup(groupId: string): Promise<boolean> {
+  return invoke<boolean>('cancel_group', { groupId });
+}
+
+// Subscribe to a group's members starting (they may queue behind the concurrency limit first)
+export async function onGroupQueryStarted(
+  groupId: string,
+  callback: (queryId: string, workingDir: string) => void
+): Promise<UnlistenFn> {
+  return listen<{ group_id: string; query_id: string; working_dir: string }>('claude-group-query-started', (event) => {
+    if (event.payload.group_id === groupId) {
+      callback(event.payload.query_id, event.payload.working_dir);
+    }
+  });
+}
+
+// Subscribe to a group finishing
+export async function onGroupDone(groupId: string, callback: (done: GroupDone) => void): Promise<UnlistenFn> {
+  return listen<GroupDone>('claude-group-done', (event) => {
+    if (event.payload.group_id === groupId) {
+      callback(event.payload);
+    }
+  });
+}
+
 // Extract slash commands from system init data
 function extractSlashCommands(data: Record<string, unknown>): SlashCommand[] {
   const commands: SlashCommand[] = [];
@@ -455,7 +1093,9 @@ function handleClaudeMessage(
           type: 'tool_result' as const,
           tool: {
             name: toolName || 'unknown',
-            result: resultContent || ''
+            result: resultContent || '',
+            resultTruncated: block.output_truncated,
+            resultTotalBytes: block.output_total_bytes
           }
         };
         console.log('[claude] EMITTING tool_result event:', event);
@@ -0,0 +1,59 @@
+// mensa - Compare Service
+// Provides frontend wrappers for comparing two sessions turn by turn
+
+import { invoke } from '@tauri-apps/api/core';
+
+export interface TokenUsage {
+  inputTokens: number;
+  outputTokens: number;
+  cacheCreationInputTokens: number;
+  cacheReadInputTokens: number;
+}
+
+/** A prompt and the reply to it; messages have the shape load_session_messages returns */
+export interface ComparedTurn<T = unknown> {
+  index: number;
+  prompt: T;
+  response: T | null;
+  toolCalls: number;
+  usage: TokenUsage;
+  costUsd: number;
+  /** From the prompt to the last reply message */
+  durationMs: number | null;
+}
+
+export interface TurnPair<T = unknown> {
+  /** Prompt similarity, 1 when identical after normalization */
+  similarity: number;
+  a: ComparedTurn<T>;
+  b: ComparedTurn<T>;
+}
+
+export interface SessionTotals {
+  turns: number;
+  toolCalls: number;
+  usage: TokenUsage;
+  costUsd: number;
+  /** From the first prompt to the last message */
+  wallTimeMs: number | null;
+}
+
+export interface SessionComparison<T = unknown> {
+  sessionA: string;
+  sessionB: string;
+  pairs: TurnPair<T>[];
+  unmatchedA: ComparedTurn<T>[];
+  unmatchedB: ComparedTurn<T>[];
+  summary: { a: SessionTotals; b: SessionTotals };
+}
+
+/**
+ * Pair two sessions' prompts (fuzzy, in order) with both replies and per-turn stats
+ */
+export async function compareSessions<T = unknown>(
+  workspacePath: string,
+  sessionA: string,
+  sessionB: string
+): Promise<SessionComparison<T>> {
+  return invoke<SessionComparison<T>>('compare_sessions', { workspacePath, sessionA, sessionB });
+}
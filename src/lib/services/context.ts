@@ -0,0 +1,51 @@
+// mensa - Session Context Service
+// Provides frontend wrappers for estimating a session's resume payload and trimmed resumes
+
+import { invoke } from '@tauri-apps/api/core';
+
+export interface HeavyItem {
+  kind: 'image' | 'toolOutput' | 'text';
+  /** 1-based line in the transcript */
+  line: number;
+  role: string;
+  bytes: number;
+  description: string;
+}
+
+export interface SessionContextReport {
+  sessionId: string;
+  transcriptBytes: number;
+  textChars: number;
+  /** Heuristic: textChars / 4 */
+  estimatedTextTokens: number;
+  imageCount: number;
+  imageBytes: number;
+  /** Images a trimmed resume (trimImagesOnResume) would replace */
+  trimmableImageCount: number;
+  trimmableImageBytes: number;
+  toolOutputBytes: number;
+  heaviest: HeavyItem[];
+}
+
+/** A resume that ran from a trimmed companion copy; also on query history records */
+export interface TrimmedResume {
+  originalSessionId: string;
+  trimmedSessionId: string;
+  imagesRemoved: number;
+  bytesSaved: number;
+  createdAt: number;
+}
+
+/**
+ * Estimate what resuming a session sends along, with its heaviest items
+ */
+export async function analyzeSessionContext(workspacePath: string, sessionId: string): Promise<SessionContextReport> {
+  return invoke<SessionContextReport>('analyze_session_context', { workspacePath, sessionId });
+}
+
+/**
+ * The original session of a trimmed companion copy, or null for an ordinary session
+ */
+export async function getTrimmedSessionOrigin(workspacePath: string, sessionId: string): Promise<TrimmedResume | null> {
+  return invoke<TrimmedResume | null>('get_trimmed_session_origin', { workspacePath, sessionId });
+}
@@ -2,6 +2,8 @@
 
 import { readDir } from '@tauri-apps/plugin-fs';
 import { join } from '@tauri-apps/api/path';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import type { MentionItem } from '$lib/types';
 
 // Hidden file/folder patterns to filter out
@@ -81,3 +83,102 @@ export function filterMentionItems(items: MentionItem[], filter: string): Mentio
     item.value.toLowerCase().includes(lowerFilter)
   );
 }
+
+export interface FileMatch {
+  path: string;
+  score: number;
+  /** Char indices in path of the matched characters, for highlighting */
+  positions: number[];
+  status: 'modified' | 'staged' | 'untracked' | null;
+  recent: boolean;
+}
+
+export interface FileMatchResult {
+  matches: FileMatch[];
+  /** The index is still being built; re-query on file-index-ready for complete results */
+  indexing: boolean;
+  indexedFiles: number;
+  truncated: boolean;
+}
+
+/**
+ * Fuzzy-match workspace files (gitignore-aware, ranked in the backend with git status and recency boosts)
+ */
+export async function fuzzyMatchFiles(workingDir: string, query: string, limit?: number): Promise<FileMatchResult> {
+  return invoke<FileMatchResult>('fuzzy_match_files', { workingDir, query, limit });
+}
+
+/**
+ * Have the file index rebuilt on its next use
+ */
+export async function invalidateFileIndex(workingDir: string): Promise<void> {
+  return invoke('invalidate_file_index', { workingDir });
+}
+
+/**
+ * Listen for a workspace's file index finishing a build
+ */
+export async function onFileIndexReady(
+  callback: (payload: { working_dir: string; file_count: number; truncated: boolean }) => void
+): Promise<UnlistenFn> {
+  return listen<{ working_dir: string; file_count: number; truncated: boolean }>('file-index-ready', (event) =>
+    callback(event.payload)
+  );
+}
+
+export interface CodeAnnotation {
+  path: string;
+  line: number;
+  kind: string;
+  author: string | null;
+  text: string;
+}
+
+export interface FileAnnotations {
+  path: string;
+  annotations: CodeAnnotation[];
+}
+
+export interface AnnotationScan {
+  files: FileAnnotations[];
+  totals: Record<string, number>;
+  total: number;
+  filesScanned: number;
+  truncated: boolean;
+  cached: boolean;
+}
+
+/**
+ * Find TODO/FIXME/HACK/XXX comments (or custom kinds) in a workspace, grouped by file.
+ * Pass an operationId to stop a long scan with cancel_operation.
+ */
+export async function scanCodeAnnotations(
+  workingDir: string,
+  options: { kinds?: string[]; includeGlobs?: string[]; operationId?: string } = {}
+): Promise<AnnotationScan> {
+  return invoke<AnnotationScan>('scan_code_annotations', {
+    workingDir,
+    kinds: options.kinds ?? null,
+    includeGlobs: options.includeGlobs ?? null,
+    operationId: options.operationId ?? null
+  });
+}
+
+/**
+ * Listen for annotations found so far while a fresh scan runs
+ */
+export async function onCodeAnnotationsBatch(
+  callback: (payload: { working_dir: string; operation_id: string | null; files: FileAnnotations[] }) => void
+): Promise<UnlistenFn> {
+  return listen<{ working_dir: string; operation_id: string | null; files: FileAnnotations[] }>(
+    'code-annotations-batch',
+    (event) => callback(event.payload)
+  );
+}
+
+/**
+ * Format selected annotations as a prompt asking for them to be resolved
+ */
+export async function annotationsToPrompt(entries: CodeAnnotation[]): Promise<string> {
+  return invoke<string>('annotations_to_prompt', { entries });
+}
@@ -0,0 +1,69 @@
+// mensa - Follow Service
+// Provides frontend wrappers for following a file live while the agent edits it
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+export interface FollowInfo {
+  followId: string;
+  path: string;
+  /** Baseline the first patch applies to; null when missing, binary or too large */
+  content: string | null;
+}
+
+/** Replace oldLen lines from oldStart (0-based, previous content) with lines */
+export interface PatchHunk {
+  oldStart: number;
+  oldLen: number;
+  lines: string[];
+}
+
+export interface FollowHandlers {
+  onPatch: (hunks: PatchHunk[]) => void;
+  /** Full reload (large change, file replaced or recreated); null content means not displayable */
+  onContent: (content: string | null, recreated: boolean) => void;
+  onRemoved: () => void;
+}
+
+/**
+ * Apply hunks from a file-patched event to the previously served content
+ */
+export function applyHunks(content: string, hunks: PatchHunk[]): string {
+  const lines = content.match(/[^\n]*\n|[^\n]+$/g) ?? [];
+  for (const hunk of [...hunks].reverse()) {
+    lines.splice(hunk.oldStart, hunk.oldLen, ...hunk.lines);
+  }
+  return lines.join('');
+}
+
+/**
+ * Start following a file; pass paths from getQueryChangedFiles to follow what the agent touched
+ */
+export async function followFile(
+  workingDir: string,
+  path: string,
+  handlers: FollowHandlers
+): Promise<{ info: FollowInfo; stop: () => Promise<void> }> {
+  const info = await invoke<FollowInfo>('follow_file', { workingDir, path });
+  const isMine = (payload: { follow_id: string }) => payload.follow_id === info.followId;
+
+  const unlisteners: UnlistenFn[] = await Promise.all([
+    listen<{ follow_id: string; hunks: PatchHunk[] }>('file-patched', (event) => {
+      if (isMine(event.payload)) handlers.onPatch(event.payload.hunks);
+    }),
+    listen<{ follow_id: string; content: string | null; recreated: boolean }>('file-content', (event) => {
+      if (isMine(event.payload)) handlers.onContent(event.payload.content, event.payload.recreated);
+    }),
+    listen<{ follow_id: string }>('file-removed', (event) => {
+      if (isMine(event.payload)) handlers.onRemoved();
+    }),
+  ]);
+
+  return {
+    info,
+    stop: async () => {
+      unlisteners.forEach((unlisten) => unlisten());
+      await invoke<boolean>('unfollow_file', { followId: info.followId });
+    },
+  };
+}
@@ -2,71 +2,208 @@
 // Provides frontend wrappers for Tauri git commands
 
 import { invoke } from '@tauri-apps/api/core';
-import type { GitStatus, BranchInfo, GitCommit, PRCreationOptions } from '$lib/types/git';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { GitStatus, BranchInfo, BranchListItem, BranchProtection, CommitResult, DestructionPreview, DiffStats, EffectiveIdentity, GhAuthStatus, GhFeature, GhPreflight, GitCommandError, GitCommit, GitLog, GitLogPage, IdentityMismatch, LineEndingReport, PRCreationOptions, PrContext, RepoCapabilities, RepoInfo, StatusSummary } from '$lib/types/git';
+import { REPO_UNSUPPORTED_PREFIX, SHALLOW_HISTORY_PREFIX } from '$lib/types/git';
 
 /**
- * Get the current git status of the repository
+ * Invoke a git command whose errors are typed: a GitCommandError is rethrown as an Error
+ * carrying its fields, so `e.message` and `asGitCommandError(e)` both work on it
  */
-export async function getGitStatus(workingDir: string): Promise<GitStatus> {
-  return invoke<GitStatus>('git_status', { workingDir });
+export async function invokeGit<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+  try {
+    return await invoke<T>(command, args);
+  } catch (e) {
+    const typed = asGitCommandError(e);
+    throw typed ? Object.assign(new Error(typed.message), typed) : e;
+  }
+}
+
+/**
+ * Get the current git status of the repository. Calls within the minimum refresh interval
+ * get the previous result (with `staleMs` set) unless `force` is set.
+ */
+export async function getGitStatus(
+  workingDir: string,
+  applyFilters = true,
+  renameThreshold?: number,
+  force = false
+): Promise<GitStatus> {
+  return invokeGit<GitStatus>('git_status', { workingDir, applyFilters, renameThreshold, force });
+}
+
+/** Emitted once per status walk whose result differs from the previous one */
+export interface GitStateChanged {
+  working_dir: string;
+  branch: string;
+  ahead: number;
+  behind: number;
+  changed_files: number;
+}
+
+/**
+ * Subscribe to repository status changes found by git_status walks
+ */
+export async function onGitStateChanged(callback: (change: GitStateChanged) => void): Promise<UnlistenFn> {
+  return listen<GitStateChanged>('git-state-changed', (event) => callback(event.payload));
+}
+
+/**
+ * Probe whether the git panel can work with this directory (bare, damaged, unsupported format)
+ */
+export async function getRepoCapabilities(workingDir: string): Promise<RepoCapabilities> {
+  return invoke<RepoCapabilities>('get_repo_capabilities', { workingDir });
+}
+
+/**
+ * Which code host the repository's remote points at, to show only the PR and review actions it supports
+ */
+export async function getRepoInfo(workingDir: string): Promise<RepoInfo> {
+  return invoke<RepoInfo>('get_repo_info', { workingDir });
+}
+
+/**
+ * The reason from a "repository unsupported" error, whether it arrived as a string or typed error
+ */
+export function repoUnsupportedReason(err: unknown): string | null {
+  if (typeof err === 'string') {
+    return err.startsWith(REPO_UNSUPPORTED_PREFIX) ? err.slice(REPO_UNSUPPORTED_PREFIX.length) : null;
+  }
+  const typed = err as GitCommandError | null;
+  return typed && typed.kind === 'repoUnsupported' ? typed.reason : null;
+}
+
+/**
+ * Whether an error (string or typed) means a shallow clone lacks the history needed; offer deepenHistory
+ */
+export function isShallowHistoryError(err: unknown): boolean {
+  if (typeof err === 'string') {
+    return err.startsWith(SHALLOW_HISTORY_PREFIX);
+  }
+  const typed = err as GitCommandError | null;
+  return !!typed && typed.kind === 'shallowHistory';
+}
+
+/**
+ * Get changed-file counts without the file lists (cheaper than getGitStatus)
+ */
+export async function getStatusSummary(workingDir: string): Promise<StatusSummary> {
+  return invokeGit<StatusSummary>('git_status_summary', { workingDir });
+}
+
+/**
+ * Check whether the working tree has any uncommitted change
+ */
+export async function isDirty(workingDir: string): Promise<boolean> {
+  return invokeGit<boolean>('git_is_dirty', { workingDir });
 }
 
 /**
  * Get the diff for a specific file or the entire working tree
  * @param staged - If true, show staged changes; if false, show unstaged changes
+ * @param glob - Treat filePath as a pattern; by default it names exactly one path (brackets, * and ? included)
+ * @param base - Diff from this revision (e.g. "origin/main" or a tag) to the working tree, or to the index when staged
+ * @param stripNotebookOutputs - Diff .ipynb files without cell outputs and execution counts
  */
 export async function getGitDiff(
   workingDir: string,
   filePath?: string,
-  staged: boolean = false
+  staged: boolean = false,
+  rawPath?: string,
+  glob: boolean = false,
+  base?: string,
+  stripNotebookOutputs: boolean = false
 ): Promise<string> {
-  return invoke<string>('git_diff', { workingDir, filePath, staged });
+  return invokeGit<string>('git_diff', { workingDir, filePath, staged, rawPath, glob, base, stripNotebookOutputs });
 }
 
 /**
  * Stage files for commit
+ * @param rawPaths - Base64 raw paths (GitFile.rawB64) for files with non-UTF-8 names
+ * @param glob - Treat paths as patterns instead of literal file names
  */
-export async function stageFiles(workingDir: string, paths: string[]): Promise<boolean> {
-  return invoke<boolean>('git_stage', { workingDir, paths });
+export async function stageFiles(
+  workingDir: string,
+  paths: string[],
+  rawPaths?: string[],
+  glob: boolean = false
+): Promise<boolean> {
+  return invokeGit<boolean>('git_stage', { workingDir, paths, rawPaths, glob });
 }
 
 /**
  * Unstage files
+ * @param rawPaths - Base64 raw paths (GitFile.rawB64) for files with non-UTF-8 names
+ * @param glob - Treat paths as patterns instead of literal file names
  */
-export async function unstageFiles(workingDir: string, paths: string[]): Promise<boolean> {
-  return invoke<boolean>('git_unstage', { workingDir, paths });
+export async function unstageFiles(
+  workingDir: string,
+  paths: string[],
+  rawPaths?: string[],
+  glob: boolean = false
+): Promise<boolean> {
+  return invokeGit<boolean>('git_unstage', { workingDir, paths, rawPaths, glob });
 }
 
 /**
  * Get branch information including ahead/behind counts
  */
 export async function getBranchInfo(workingDir: string): Promise<BranchInfo> {
-  return invoke<BranchInfo>('git_branch_info', { workingDir });
+  return invokeGit<BranchInfo>('git_branch_info', { workingDir });
 }
 
 /**
- * Create a commit with the staged changes
+ * Create a commit with the staged changes, authored by the workspace's commit identity
  * @param paths - Optional specific files to commit (will stage them first)
  */
 export async function createCommit(
   workingDir: string,
   message: string,
   paths?: string[]
-): Promise<string> {
-  return invoke<string>('git_commit', { workingDir, message, paths });
+): Promise<CommitResult> {
+  return invoke<CommitResult>('git_commit', { workingDir, message, paths });
+}
+
+/**
+ * The author a commit would use right now ("committing as X <y>")
+ */
+export async function getCommitIdentity(workingDir: string): Promise<EffectiveIdentity> {
+  return invoke<EffectiveIdentity>('get_commit_identity', { workingDir });
+}
+
+/**
+ * Set this workspace's commit author (pass null for both to fall back to git config);
+ * writeGitConfig also sets user.name and user.email in the repository's local config
+ */
+export async function setCommitIdentity(
+  workingDir: string,
+  name: string | null,
+  email: string | null,
+  writeGitConfig = false
+): Promise<EffectiveIdentity> {
+  return invoke<EffectiveIdentity>('set_commit_identity', { workingDir, name, email, writeGitConfig });
+}
+
+/**
+ * Warn when the commit identity matches none of the repository's recent authors
+ */
+export async function detectIdentityMismatch(workingDir: string): Promise<IdentityMismatch> {
+  return invoke<IdentityMismatch>('detect_identity_mismatch', { workingDir });
 }
 
 /**
  * Push changes to remote
  * @param setUpstream - If true, sets the upstream tracking branch
  * @param branch - Optional specific branch to push
+ * @param opId - Optional operation id, usable with cancelGitOperation
  */
 export async function pushChanges(
   workingDir: string,
   setUpstream: boolean = false,
-  branch?: string
+  branch?: string,
+  opId?: string
 ): Promise<boolean> {
-  return invoke<boolean>('git_push', { workingDir, setUpstream, branch });
+  return invoke<boolean>('git_push', { workingDir, setUpstream, branch, opId });
 }
 
 /**
@@ -76,29 +213,110 @@ export async function getCommitLog(
   workingDir: string,
   limit: number = 50,
   branch?: string
-): Promise<GitCommit[]> {
-  return invoke<GitCommit[]>('git_log', { workingDir, limit, branch });
+): Promise<GitLog> {
+  return invokeGit<GitLog>('git_log', { workingDir, limit, branch });
+}
+
+/**
+ * Commits a page at a time; a walk stays on the commit it started from. Rejects with a PageError.
+ */
+export async function getCommitLogPaged(
+  workingDir: string,
+  cursor?: string,
+  limit?: number,
+  branch?: string
+): Promise<GitLogPage> {
+  return invoke<GitLogPage>('git_log_paged', { workingDir, branch, cursor, limit });
 }
 
 /**
  * Fetch from remote
  */
-export async function fetchRemote(workingDir: string): Promise<boolean> {
-  return invoke<boolean>('git_fetch', { workingDir });
+export async function fetchRemote(workingDir: string, opId?: string, pruneGoneUpstreams = false): Promise<boolean> {
+  return invokeGit<boolean>('git_fetch', { workingDir, opId, pruneGoneUpstreams });
+}
+
+/**
+ * Pack loose objects and consolidate packs (git gc), reported as a "git-gc" operation
+ */
+export async function cleanupRepository(workingDir: string, opId?: string): Promise<boolean> {
+  return invokeGit<boolean>('git_gc', { workingDir, opId });
+}
+
+/**
+ * Track a different remote branch (e.g. "origin/main"), or stop tracking with null
+ */
+export async function setUpstream(workingDir: string, branch: string, upstream: string | null): Promise<boolean> {
+  return invokeGit<boolean>('git_set_upstream', { workingDir, branch, upstream });
+}
+
+/**
+ * Fetch more history into a shallow clone (depth more commits, or all of it when omitted).
+ * Progress arrives as `progress` events (see services/progress). Resolves to whether it's still shallow.
+ */
+export async function deepenHistory(workingDir: string, depth?: number, opId?: string): Promise<boolean> {
+  return invokeGit<boolean>('git_fetch_deepen', { workingDir, depth, opId });
 }
 
 /**
  * Pull from remote
  */
-export async function pullChanges(workingDir: string): Promise<boolean> {
-  return invoke<boolean>('git_pull', { workingDir });
+export async function pullChanges(workingDir: string, opId?: string): Promise<boolean> {
+  return invokeGit<boolean>('git_pull', { workingDir, opId });
+}
+
+/**
+ * Get branch protection rules (defaults to the current branch)
+ */
+export async function getBranchProtection(workingDir: string, branch?: string): Promise<BranchProtection> {
+  return invokeGit<BranchProtection>('get_branch_protection', { workingDir, branch });
+}
+
+/**
+ * Create a branch at HEAD and switch to it, keeping uncommitted changes
+ */
+export async function createBranchAndMoveChanges(workingDir: string, newBranch: string): Promise<string> {
+  return invokeGit<string>('git_create_branch_and_move_changes', { workingDir, newBranch });
+}
+
+/**
+ * Narrow an invoke error to the structured git command error, if it is one
+ */
+export function asGitCommandError(e: unknown): GitCommandError | null {
+  if (e && typeof e === 'object' && 'kind' in e && 'message' in e) {
+    return e as GitCommandError;
+  }
+  return null;
 }
 
 /**
- * Discard changes in a file (restore to HEAD)
+ * Cancel a running push/pull/fetch or PR diff by its operation id
  */
-export async function discardChanges(workingDir: string, filePath: string): Promise<boolean> {
-  return invoke<boolean>('git_discard', { workingDir, filePath });
+export async function cancelGitOperation(opId: string): Promise<boolean> {
+  return invoke<boolean>('cancel_git_operation', { opId });
+}
+
+/**
+ * Discard unstaged changes in a file (restore it from the index). With dryRun nothing is
+ * touched; the discard itself needs the preview's token as previewToken, and refuses to act
+ * on files changed since.
+ */
+export async function discardChanges(
+  workingDir: string,
+  filePath: string,
+  rawPath?: string,
+  glob: boolean = false,
+  dryRun: boolean = false,
+  previewToken?: string
+): Promise<DestructionPreview> {
+  return invokeGit<DestructionPreview>('git_discard', { workingDir, filePath, rawPath, glob, dryRun, previewToken });
+}
+
+/**
+ * Get the effective text/eol attributes and autocrlf config for paths
+ */
+export async function checkAttr(workingDir: string, paths: string[]): Promise<LineEndingReport> {
+  return invokeGit<LineEndingReport>('git_check_attr', { workingDir, paths });
 }
 
 /**
@@ -108,6 +326,20 @@ export async function checkGhCliAvailable(): Promise<boolean> {
   return invoke<boolean>('check_gh_cli_available');
 }
 
+/**
+ * Get gh's accounts and token scopes per host (cached; refresh re-reads them)
+ */
+export async function getGhAuthStatus(refresh = false): Promise<GhAuthStatus> {
+  return invoke<GhAuthStatus>('get_gh_auth_status', { refresh });
+}
+
+/**
+ * Check whether the gh token has the scopes a feature needs
+ */
+export async function preflightGhFeature(feature: GhFeature): Promise<GhPreflight> {
+  return invoke<GhPreflight>('preflight_gh_feature', { feature });
+}
+
 /**
  * Create a pull request using gh CLI
  */
@@ -119,10 +351,61 @@ export async function createPullRequest(
 }
 
 /**
- * Get list of available branches
+ * Get list of available branch names (sorted alphabetically)
  */
 export async function listBranches(workingDir: string): Promise<string[]> {
-  return invoke<string[]>('git_list_branches', { workingDir });
+  return invokeGit<string[]>('git_list_branches', { workingDir, namesOnly: true });
+}
+
+/**
+ * Get local branches with upstream and ahead/behind info
+ * @param sort - 'recent' (by last commit time, default) or 'name'
+ */
+export async function listBranchesDetailed(
+  workingDir: string,
+  sort: 'recent' | 'name' = 'recent'
+): Promise<BranchListItem[]> {
+  return invokeGit<BranchListItem[]>('git_list_branches', { workingDir, namesOnly: false, sort });
+}
+
+/**
+ * Get per-file +/- counts for staged or unstaged changes (no patch text); `base` and
+ * `stripNotebookOutputs` as in getGitDiff
+ */
+export async function getDiffStats(
+  workingDir: string,
+  staged: boolean,
+  applyFilters = true,
+  base?: string,
+  stripNotebookOutputs = false
+): Promise<DiffStats> {
+  return invokeGit<DiffStats>('git_diff_stats', { workingDir, staged, applyFilters, base, stripNotebookOutputs });
+}
+
+/**
+ * Get per-file +/- counts between two commits or branches
+ */
+export async function getDiffStatsRange(
+  workingDir: string,
+  base: string,
+  head: string,
+  applyFilters = true
+): Promise<DiffStats> {
+  return invokeGit<DiffStats>('git_diff_stats_range', { workingDir, base, head, applyFilters });
+}
+
+/**
+ * Get the workspace's display-ignore globs for the status and diff panels
+ */
+export async function getStatusFilters(workingDir: string): Promise<string[]> {
+  return invoke<string[]>('get_status_filters', { workingDir });
+}
+
+/**
+ * Replace the workspace's display-ignore globs (an empty list clears them)
+ */
+export async function setStatusFilters(workingDir: string, globs: string[]): Promise<string[]> {
+  return invoke<string[]>('set_status_filters', { workingDir, globs });
 }
 
 /**
@@ -133,7 +416,7 @@ export async function getDiffBetweenCommits(
   base: string,
   head: string
 ): Promise<string> {
-  return invoke<string>('git_diff_commits', { workingDir, base, head });
+  return invokeGit<string>('git_diff_commits', { workingDir, base, head });
 }
 
 /**
@@ -297,9 +580,8 @@ export async function generatePRDescription(
   if (commits.length > 0) {
     parts.push('\n## Commits\n');
     for (const commit of commits.slice(0, 10)) {
-      const shortHash = commit.hash.substring(0, 7);
       const message = commit.message.split('\n')[0];
-      parts.push(`- \`${shortHash}\` ${message}\n`);
+      parts.push(`- \`${commit.shortHash}\` ${message}\n`);
     }
     if (commits.length > 10) {
       parts.push(`- ... and ${commits.length - 10} more commits\n`);
@@ -312,3 +594,19 @@ export async function generatePRDescription(
 
   return parts.join('');
 }
+
+/**
+ * Everything needed to draft a PR: commits ahead of the base, linked issues,
+ * change summary, PR template and CODEOWNERS reviewers
+ */
+export async function collectPrContext(
+  workingDir: string,
+  base?: string,
+  issuePatterns?: string[]
+): Promise<PrContext> {
+  return invoke<PrContext>('collect_pr_context', {
+    workingDir,
+    base: base ?? null,
+    issuePatterns: issuePatterns ?? null
+  });
+}
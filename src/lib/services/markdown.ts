@@ -0,0 +1,37 @@
+// mensa - Markdown Service
+// Sanitized markdown rendering done by the backend (never render agent markdown with innerHTML directly)
+
+import { invoke } from '@tauri-apps/api/core';
+
+export interface MarkdownOptions {
+  highlightCode?: boolean;
+}
+
+export interface OutlineHeading {
+  level: number;
+  text: string;
+  id: string;
+}
+
+export interface RenderedMarkdown {
+  html: string;
+  outline: OutlineHeading[];
+}
+
+export interface RenderedPlanFile extends RenderedMarkdown {
+  content: string;
+}
+
+/**
+ * Render markdown to sanitized HTML plus a heading outline
+ */
+export async function renderMarkdown(content: string, options?: MarkdownOptions): Promise<RenderedMarkdown> {
+  return invoke<RenderedMarkdown>('render_markdown', { content, options });
+}
+
+/**
+ * Read a plan file with its sanitized HTML and outline
+ */
+export async function readRenderedPlanFile(workspacePath: string, planFilename: string): Promise<RenderedPlanFile> {
+  return invoke<RenderedPlanFile>('read_plan_file', { workspacePath, planFilename, rendered: true });
+}
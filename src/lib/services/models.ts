@@ -0,0 +1,37 @@
+// mensa - Models Service
+// Provides frontend wrappers for the model catalog the backend prices and sizes queries with
+
+import { invoke } from '@tauri-apps/api/core';
+
+/** The last layer that set an entry: built in, the modelCatalogUrl manifest, or models.json in app data */
+export type CatalogSource = 'builtin' | 'remote' | 'user';
+
+export interface ModelInfo {
+  id: string;
+  displayName: string;
+  /** Substrings of model names this entry also stands for; the longest match wins */
+  matches: string[];
+  contextWindow: number;
+  supportsThinking: boolean;
+  /** USD per million tokens */
+  inputPerMtok: number;
+  outputPerMtok: number;
+  cacheWritePerMtok: number;
+  cacheReadPerMtok: number;
+  source: CatalogSource;
+}
+
+export interface ModelList {
+  models: ModelInfo[];
+  /** Override entries or files that were skipped, and why */
+  warnings: string[];
+  /** Unix seconds of the remote manifest in use, if any */
+  remoteFetchedAt: number | null;
+}
+
+/**
+ * The model catalog; a stale remote manifest is refreshed in the background
+ */
+export async function listModels(): Promise<ModelList> {
+  return invoke<ModelList>('list_models');
+}
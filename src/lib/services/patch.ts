@@ -0,0 +1,55 @@
+// mensa - Patch Service
+// Provides frontend wrappers for previewing and applying agent-proposed unified diffs
+
+import { invoke } from '@tauri-apps/api/core';
+import type { DiffStats } from '$lib/types/git';
+
+export type WhitespaceMode = 'exact' | 'ignoreTrailing' | 'ignoreAll';
+
+export interface ApplyPatchOptions {
+  /** Validate and report only; nothing is written */
+  dryRun?: boolean;
+  stage?: boolean;
+  whitespace?: WhitespaceMode;
+}
+
+export interface HunkReport {
+  header: string;
+  status: 'clean' | 'offset' | 'conflict';
+  appliedAt: number | null;
+  offset: number;
+  mismatch: { line: number; expected: string; actual: string | null } | null;
+}
+
+export interface FilePatchReport {
+  path: string;
+  oldPath?: string;
+  status: 'added' | 'modified' | 'deleted' | 'renamed';
+  hunks: HunkReport[];
+  error: string | null;
+}
+
+export interface PatchApplyResult {
+  applied: boolean;
+  dryRun: boolean;
+  staged: boolean;
+  stageError: string | null;
+  files: FilePatchReport[];
+  /** Same shape as getDiffStats, so the changes list component can render it */
+  summary: DiffStats;
+}
+
+/**
+ * Validate a unified diff and apply it to every file, or to none if any hunk conflicts
+ */
+export async function applyPatchText(
+  workingDir: string,
+  patchText: string,
+  options: ApplyPatchOptions = {}
+): Promise<PatchApplyResult> {
+  return invoke<PatchApplyResult>('apply_patch_text', { workingDir, patchText, options });
+}
+
+export async function previewPatch(workingDir: string, patchText: string, whitespace?: WhitespaceMode): Promise<PatchApplyResult> {
+  return applyPatchText(workingDir, patchText, { dryRun: true, whitespace });
+}
@@ -0,0 +1,68 @@
+// mensa - Plans Service
+// Provides frontend wrappers for listing plan files and approving or rejecting a plan written in plan mode
+
+import { invoke } from '@tauri-apps/api/core';
+import type { Page } from '$lib/types';
+
+/** Recorded in the resumed query's history record */
+export interface PlanApproval {
+  planFilename: string;
+  /** SHA-256 of the plan as approved, edits included */
+  planHash: string;
+  /** "Name <email>" of the workspace's commit identity */
+  approvedBy: string;
+  approvedAt: number;
+  edited: boolean;
+  /** Query that wrote the plan */
+  plannedIn: string;
+}
+
+export interface PlanContinuation {
+  /** The resumed query, already started; its events go to this window */
+  queryId: string;
+  sessionId: string;
+  /** The workspace's default permission mode, never "plan" */
+  permissionMode: string;
+  approval: PlanApproval;
+  /** E.g. the session was continued after the plan was written */
+  warnings: string[];
+}
+
+/**
+ * SHA-256 of a plan file as read now, or null when it doesn't exist
+ */
+export async function getPlanFileHash(planFilename: string): Promise<string | null> {
+  return invoke<string | null>('plan_file_hash', { planFilename });
+}
+
+/**
+ * Approve a plan and resume its session in execution mode. Pass the hash from getPlanFileHash
+ * to fail with a conflict if the plan changed after it was shown; `edits` replaces the plan first.
+ */
+export async function approvePlanAndContinue(
+  queryOrSessionId: string,
+  planFilename: string,
+  edits?: string,
+  expectedHash?: string
+): Promise<PlanContinuation> {
+  return invoke<PlanContinuation>('approve_plan_and_continue', { queryOrSessionId, planFilename, edits, expectedHash });
+}
+
+/**
+ * Reject a plan: a rejection note is appended to the plan file and nothing runs
+ */
+export async function rejectPlan(
+  queryOrSessionId: string,
+  planFilename: string,
+  note?: string,
+  expectedHash?: string
+): Promise<void> {
+  return invoke<void>('reject_plan', { queryOrSessionId, planFilename, note, expectedHash });
+}
+
+/**
+ * Plan filenames a page at a time, most recently modified first. Rejects with a PageError.
+ */
+export async function listPlanFilesPaged(cursor?: string, limit?: number): Promise<Page<string>> {
+  return invoke<Page<string>>('list_plan_files_paged', { cursor, limit });
+}
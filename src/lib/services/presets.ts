@@ -0,0 +1,81 @@
+// mensa - Query Presets Service
+// Provides frontend wrappers for saved model/permission presets
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { ClaudeQueryConfig } from './claude';
+
+export type PresetScope = 'builtin' | 'global' | 'workspace';
+
+export interface QueryPreset {
+  name: string;
+  config: Partial<ClaudeQueryConfig>;
+  shortcut?: string;
+  scope: PresetScope;
+  hidden: boolean;
+}
+
+/**
+ * List presets for a workspace (built-ins, global, then workspace overrides)
+ */
+export async function listQueryPresets(workingDir?: string): Promise<QueryPreset[]> {
+  return invoke<QueryPreset[]>('list_query_presets', { workingDir: workingDir ?? null });
+}
+
+/**
+ * Create or replace a global or workspace preset
+ */
+export async function saveQueryPreset(
+  name: string,
+  config: Partial<ClaudeQueryConfig>,
+  scope: Exclude<PresetScope, 'builtin'>,
+  workingDir?: string,
+  shortcut?: string
+): Promise<QueryPreset> {
+  return invoke<QueryPreset>('save_query_preset', {
+    name,
+    config,
+    scope,
+    workingDir: workingDir ?? null,
+    shortcut: shortcut ?? null
+  });
+}
+
+/**
+ * Delete a global or workspace preset
+ */
+export async function deleteQueryPreset(
+  name: string,
+  scope: Exclude<PresetScope, 'builtin'>,
+  workingDir?: string
+): Promise<boolean> {
+  return invoke<boolean>('delete_query_preset', { name, scope, workingDir: workingDir ?? null });
+}
+
+/**
+ * Hide or show a built-in preset
+ */
+export async function setQueryPresetHidden(name: string, hidden: boolean): Promise<boolean> {
+  return invoke<boolean>('set_query_preset_hidden', { name, hidden });
+}
+
+/**
+ * Set the config applied to every query in a workspace (null clears it)
+ */
+export async function setWorkspaceQueryDefaults(
+  workingDir: string,
+  config: Partial<ClaudeQueryConfig> | null
+): Promise<void> {
+  return invoke<void>('set_workspace_query_defaults', { workingDir, config });
+}
+
+/**
+ * Subscribe to preset changes made from any window
+ */
+export async function onPresetsChanged(
+  callback: (workingDir: string | null) => void
+): Promise<UnlistenFn> {
+  return listen<{ working_dir: string | null }>('presets-changed', (event) => {
+    callback(event.payload.working_dir);
+  });
+}
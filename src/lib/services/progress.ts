@@ -0,0 +1,79 @@
+// mensa - Progress Service
+// Provides frontend wrappers for progress of long-running backend operations
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+/** Payload of progress, progress-complete and progress-failed events */
+export interface ProgressEvent {
+  operation_id: string;
+  /** e.g. "git-fetch", "git-push", "pr-diff", "app-data-import", "app-update" */
+  kind: string;
+  phase: string;
+  /** Set with total when the amount of work is known; otherwise the phase is indeterminate */
+  current: number | null;
+  total: number | null;
+  message: string | null;
+}
+
+export interface RunningOperation {
+  operationId: string;
+  kind: string;
+  workingDir: string | null;
+  startedAt: number;
+  /** Whether cancelGitOperation can stop it */
+  cancellable: boolean;
+  last: ProgressEvent;
+}
+
+/** A long-lived backend task (watcher, output reader, query group runner) */
+export interface BackgroundTask {
+  id: number;
+  name: string;
+  startedAt: number;
+  uptimeMs: number;
+  /** False once the task's future ended */
+  alive: boolean;
+  /** Asked to stop and not yet done */
+  cancelled: boolean;
+}
+
+export interface ProgressHandlers {
+  onProgress: (event: ProgressEvent) => void;
+  onComplete?: (event: ProgressEvent) => void;
+  onFailed?: (event: ProgressEvent) => void;
+}
+
+/**
+ * Operations currently reporting progress, oldest first (e.g. to rebuild a progress list after a reload)
+ */
+export async function listRunningOperations(): Promise<RunningOperation[]> {
+  return invoke<RunningOperation[]>('list_running_operations');
+}
+
+/**
+ * Background tasks still running, oldest first (for diagnostics)
+ */
+export async function listBackgroundTasks(): Promise<BackgroundTask[]> {
+  return invoke<BackgroundTask[]>('list_background_tasks');
+}
+
+/**
+ * Listen to progress of every operation; returns a function that stops listening
+ */
+export async function onProgress(handlers: ProgressHandlers): Promise<UnlistenFn> {
+  const unlisteners: UnlistenFn[] = await Promise.all([
+    listen<ProgressEvent>('progress', (event) => handlers.onProgress(event.payload)),
+    listen<ProgressEvent>('progress-complete', (event) => handlers.onComplete?.(event.payload)),
+    listen<ProgressEvent>('progress-failed', (event) => handlers.onFailed?.(event.payload)),
+  ]);
+  return () => unlisteners.forEach((unlisten) => unlisten());
+}
+
+/**
+ * Fraction done (0..1) of a determinate event, or null while indeterminate
+ */
+export function progressFraction(event: ProgressEvent): number | null {
+  if (event.current === null || !event.total) return null;
+  return Math.min(1, event.current / event.total);
+}
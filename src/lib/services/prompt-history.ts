@@ -0,0 +1,57 @@
+// mensa - Prompt History Service
+// Provides frontend wrappers for recalling previously submitted prompts
+
+import { invoke } from '@tauri-apps/api/core';
+import type { Page } from '$lib/types';
+
+export interface PromptEntry {
+  id: string;
+  /** First 2000 characters of the prompt */
+  prompt: string;
+  workspace: string;
+  createdAt: number;
+  queryId: string;
+  /** Terminal reason of the query once it finished: completed | failed | cancelled | cost_limit_exceeded */
+  outcome?: string;
+}
+
+export interface PromptMatch extends PromptEntry {
+  score: number;
+}
+
+/**
+ * Fuzzy-search recorded prompts (best match first, then newest); an empty query lists recent prompts
+ */
+export async function searchPromptHistory(
+  query: string,
+  workspace?: string,
+  limit?: number
+): Promise<PromptMatch[]> {
+  return invoke<PromptMatch[]>('search_prompt_history', { query, workspace, limit });
+}
+
+/**
+ * searchPromptHistory a page at a time, in the same order. Rejects with a PageError.
+ */
+export async function searchPromptHistoryPaged(
+  query: string,
+  workspace?: string,
+  cursor?: string,
+  limit?: number
+): Promise<Page<PromptMatch>> {
+  return invoke<Page<PromptMatch>>('search_prompt_history_paged', { query, workspace, cursor, limit });
+}
+
+/**
+ * Delete one recorded prompt
+ */
+export async function deletePromptHistoryEntry(id: string): Promise<boolean> {
+  return invoke<boolean>('delete_prompt_history_entry', { id });
+}
+
+/**
+ * Delete every recorded prompt
+ */
+export async function clearPromptHistory(): Promise<void> {
+  return invoke<void>('clear_prompt_history');
+}
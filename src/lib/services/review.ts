@@ -2,6 +2,8 @@
 // Handles code review operations using Claude
 
 import { invoke } from '@tauri-apps/api/core';
+import { invokeGit } from './git';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { queryClaudeStreaming, type ClaudeQueryConfig } from './claude';
 import { appConfig } from '$lib/stores/app.svelte';
 import { reviewStore } from '$lib/stores/review.svelte';
@@ -12,9 +14,12 @@ import type {
   ReviewFinding,
   ReviewStats,
   PRInfo,
+  PRListItem,
   ReviewFocus,
   FindingSeverity,
 } from '$lib/types/review';
+import type { ReviewError } from '$lib/types/git';
+import type { Page } from '$lib/types';
 
 // ============================================================================
 // Diff Content Retrieval
@@ -29,7 +34,7 @@ export async function getDiffContent(source: ReviewSource): Promise<string> {
   switch (source.type) {
     case 'local-changes': {
       // Get unstaged changes
-      const diff = await invoke<string>('git_diff', {
+      const diff = await invokeGit<string>('git_diff', {
         workingDir,
         filePath: null,
         staged: false,
@@ -39,7 +44,7 @@ export async function getDiffContent(source: ReviewSource): Promise<string> {
 
     case 'staged': {
       // Get staged changes
-      const diff = await invoke<string>('git_diff', {
+      const diff = await invokeGit<string>('git_diff', {
         workingDir,
         filePath: null,
         staged: true,
@@ -49,7 +54,7 @@ export async function getDiffContent(source: ReviewSource): Promise<string> {
 
     case 'branch': {
       // Get diff between branches
-      const diff = await invoke<string>('git_diff_commits', {
+      const diff = await invokeGit<string>('git_diff_commits', {
         workingDir,
         base: source.baseBranch,
         head: source.headBranch,
@@ -69,7 +74,7 @@ export async function getDiffContent(source: ReviewSource): Promise<string> {
       // Get diff for specific files
       const diffs: string[] = [];
       for (const path of source.paths) {
-        const diff = await invoke<string>('git_diff', {
+        const diff = await invokeGit<string>('git_diff', {
           workingDir,
           filePath: path,
           staged: false,
@@ -88,7 +93,7 @@ export async function getDiffContent(source: ReviewSource): Promise<string> {
 // PR Information
 // ============================================================================
 
-interface GhPRInfo {
+export interface GhPRInfo {
   title: string;
   body: string;
   author: string;
@@ -101,10 +106,29 @@ interface GhPRInfo {
   headRefName: string;
   createdAt: string;
   updatedAt: string;
+  headSha: string;
+  bodyHtml?: string;
+  /** Fields gh sent in an unexpected shape and that fell back to a default */
+  schemaWarnings?: string[];
+  /** Top-level gh fields nothing reads, kept for debugging */
+  extra?: Record<string, unknown>;
 }
 
 /**
- * Fetch PR information from GitHub
+ * A repository's PRs a page at a time, highest number first. The first page is fetched afresh;
+ * later ones reuse that fetch while it is fresh. Rejects with a PageError.
+ */
+export async function listPRsPaged(
+  workingDir: string,
+  state = 'open',
+  cursor?: string,
+  limit?: number
+): Promise<Page<PRListItem>> {
+  return invoke<Page<PRListItem>>('list_prs_paged', { workingDir, state, cursor, limit });
+}
+
+/**
+ * Fetch PR information from GitHub, or a merge request's from GitLab
  */
 export async function fetchPRInfo(prUrl: string): Promise<PRInfo> {
   const ghInfo = await invoke<GhPRInfo>('fetch_pr_info', { prUrl });
@@ -122,6 +146,7 @@ export async function fetchPRInfo(prUrl: string): Promise<PRInfo> {
     headBranch: ghInfo.headRefName,
     createdAt: ghInfo.createdAt,
     updatedAt: ghInfo.updatedAt,
+    schemaWarnings: ghInfo.schemaWarnings,
   };
 }
 
@@ -476,18 +501,177 @@ Please apply this fix to the file. Use the Edit tool to make the change.`;
 // ============================================================================
 
 /**
- * Post review to GitHub PR
+ * Post review to GitHub PR. GitLab and Bitbucket reject it with an `unsupportedProvider`
+ * error, rethrown as an Error carrying its message.
  */
 export async function postReviewToGitHub(
   prUrl: string,
   verdict: 'approve' | 'request-changes' | 'comment',
   summary: string
 ): Promise<void> {
-  await invoke('post_pr_review', {
-    prUrl,
-    verdict,
-    body: summary,
-  });
+  try {
+    await invoke('post_pr_review', {
+      prUrl,
+      verdict,
+      body: summary,
+    });
+  } catch (e) {
+    const typed = e as ReviewError | null;
+    throw new Error(typed && typeof typed === 'object' && 'message' in typed ? typed.message : String(e));
+  }
+}
+
+export interface ReviewDraftComment {
+  path: string;
+  line: number;
+  startLine?: number;
+  side?: 'LEFT' | 'RIGHT';
+  body: string;
+}
+
+export interface ReviewDraft {
+  prUrl: string;
+  verdict: 'approve' | 'request-changes' | 'comment';
+  body: string;
+  comments: ReviewDraftComment[];
+  headSha: string | null;
+  createdAt: number;
+  updatedAt: number;
+  /** The PR head moved since saving; line anchors need revalidating */
+  outdated: boolean;
+}
+
+/**
+ * Save the in-progress review for a PR so it survives restarts. The draft stays anchored to the
+ * head it was first saved against (reported `outdated` once the PR moves) until `acknowledgeHead`.
+ */
+export async function saveReviewDraft(
+  prUrl: string,
+  verdict: ReviewDraft['verdict'],
+  body: string,
+  comments: ReviewDraftComment[] = [],
+  acknowledgeHead = false
+): Promise<ReviewDraft> {
+  return invoke<ReviewDraft>('save_review_draft', { prUrl, verdict, body, comments, acknowledgeHead });
+}
+
+export async function getReviewDraft(prUrl: string): Promise<ReviewDraft | null> {
+  return invoke<ReviewDraft | null>('get_review_draft', { prUrl });
+}
+
+export async function deleteReviewDraft(prUrl: string): Promise<boolean> {
+  return invoke<boolean>('delete_review_draft', { prUrl });
+}
+
+// ============================================================================
+// Review Fixes
+// ============================================================================
+
+export interface ReviewThreadComment {
+  author: string;
+  body: string;
+  createdAt: string;
+}
+
+export interface ReviewThread {
+  id: string;
+  path: string;
+  /** Line in the PR head, or for an outdated thread the line it was left on */
+  line: number | null;
+  isResolved: boolean;
+  isOutdated: boolean;
+  diffHunk: string;
+  comments: ReviewThreadComment[];
+}
+
+export interface ReviewFixPrompt {
+  prompt: string;
+  threads: ReviewThread[];
+  /** Requested ids that are resolved or not on the PR */
+  skipped: string[];
+}
+
+export type ThreadStatus = 'likelyAddressed' | 'fileTouched' | 'untouched';
+
+export interface ThreadMatch {
+  threadId: string;
+  path: string;
+  line: number | null;
+  status: ThreadStatus;
+  /** Short hashes of commits since the query started that changed the thread's file */
+  commits: string[];
+  replied: boolean;
+}
+
+/**
+ * Build a prompt asking to address a PR's unresolved review threads (all of them when
+ * no ids are given)
+ */
+export async function buildReviewFixPrompt(prUrl: string, threadIds: string[] = []): Promise<ReviewFixPrompt> {
+  return invoke<ReviewFixPrompt>('build_review_fix_prompt', { prUrl, threadIds });
+}
+
+/**
+ * Which unresolved threads a finished query likely addressed. With `postReplies`, threads
+ * addressed by a commit get a reply naming it; nothing is resolved.
+ */
+export async function matchThreadsToChanges(
+  prUrl: string,
+  queryId: string,
+  postReplies = false
+): Promise<ThreadMatch[]> {
+  return invoke<ThreadMatch[]>('match_threads_to_changes', { prUrl, queryId, postReplies });
+}
+
+// ============================================================================
+// Review Queue
+// ============================================================================
+
+/** What fetchPRBatch gets besides each PR's info */
+export type PRBatchPart = 'diff' | 'checks' | 'files' | 'comments';
+
+/** One PR of a batch, emitted as soon as it's fetched; parts not asked for are absent or null */
+export interface PRBatchItem {
+  operation_id: string;
+  index: number;
+  /** The number or URL as requested */
+  pr: string;
+  url: string | null;
+  info?: GhPRInfo | null;
+  diff?: string | null;
+  /** gh pr checks rows: name, state, bucket ("pass" | "fail" | "pending" | ...), link, workflow */
+  checks?: { name: string; state: string; bucket: string; link: string; workflow: string }[] | null;
+  files?: { path: string; additions: number; deletions: number }[] | null;
+  comments?: { comments: unknown[]; reviews: unknown[] } | null;
+  error: string | null;
+}
+
+export interface PRBatchSummary {
+  operationId: string;
+  requested: number;
+  succeeded: number;
+  failed: { pr: string; error: string }[];
+  cancelled: boolean;
+  /** GitHub's rate limit was hit; PRs not started by then were skipped */
+  rateLimited: boolean;
+}
+
+/**
+ * Fetch several PRs (numbers on the workspace's remote, or URLs) concurrently; each arrives
+ * through onPRBatchItem, and opening one afterwards reuses what was fetched. Cancel with
+ * cancelGitOperation(opId).
+ */
+export async function fetchPRBatch(
+  workingDir: string,
+  prs: string[],
+  include: PRBatchPart[] = [],
+  opId?: string
+): Promise<PRBatchSummary> {
+  return invoke<PRBatchSummary>('fetch_pr_batch', { workingDir, prs, include, opId });
+}
+
+export async function onPRBatchItem(callback: (item: PRBatchItem) => void): Promise<UnlistenFn> {
+  return listen<PRBatchItem>('pr-batch-item', (event) => callback(event.payload));
 }
 
 /**
@@ -0,0 +1,268 @@
+// mensa - Sessions Service
+// Provides frontend wrappers for Tauri session listing, title, transcript location, migration, permission and search commands
+
+import { invoke } from '@tauri-apps/api/core';
+import type { Page } from '$lib/types';
+
+/** custom: already titled by the user (left alone); date: nothing better than "Session from <date>" */
+export type TitleSource = 'custom' | 'plan' | 'heading' | 'prompt' | 'date';
+
+export interface SessionTitle {
+  sessionId: string;
+  title: string;
+  source: TitleSource;
+  /** Whether the title was written to the sessions index */
+  applied: boolean;
+}
+
+/**
+ * Set a session's title; an empty or null title removes it. False when the index has no such session.
+ */
+export async function renameSession(workspacePath: string, sessionId: string, title: string | null): Promise<boolean> {
+  return invoke<boolean>('rename_session', { workspacePath, sessionId, title });
+}
+
+/**
+ * Title a session from its transcript without a model call, unless it has a custom title already
+ */
+export async function generateSessionTitle(workspacePath: string, sessionId: string): Promise<SessionTitle> {
+  return invoke<SessionTitle>('generate_session_title', { workspacePath, sessionId });
+}
+
+/**
+ * Title every untitled session of a workspace; returns the titles that were set
+ */
+export async function generateMissingTitles(workspacePath: string): Promise<SessionTitle[]> {
+  return invoke<SessionTitle[]>('generate_missing_titles', { workspacePath });
+}
+
+/**
+ * Consecutive transcript lines a message was built from: 1-based inclusive lines, and byte
+ * offsets into the whole jsonl file (end excludes the newline). A message merged from
+ * non-adjacent lines has several.
+ */
+export interface SourceRange {
+  startLine: number;
+  endLine: number;
+  startByte: number;
+  endByte: number;
+}
+
+export interface OpenedLocation {
+  path: string;
+  line: number;
+  /** null when no editor is configured and the file was revealed in the file manager */
+  editor: string | null;
+  /** Whether the editor was told the line */
+  atLine: boolean;
+}
+
+/**
+ * Open a session's jsonl at a line in the preferred editor, or reveal it in the file manager
+ */
+export async function openSessionAt(workspacePath: string, sessionId: string, line: number): Promise<OpenedLocation> {
+  return invoke<OpenedLocation>('open_session_at', { workspacePath, sessionId, line });
+}
+
+export type MigrationMode = 'copy' | 'move';
+
+export interface SessionMigration {
+  sessionId: string;
+  /** Id in the new project; differs from sessionId when the new project already had it */
+  targetSessionId: string;
+  reassigned: boolean;
+  /** Transcript lines whose cwd or sessionId was rewritten */
+  linesRewritten: number;
+  bytes: number;
+  /** Set when this session couldn't be migrated; the others still are */
+  error: string | null;
+}
+
+export interface MigrationReport {
+  oldProjectDir: string;
+  newProjectDir: string;
+  mode: MigrationMode;
+  dryRun: boolean;
+  sessions: SessionMigration[];
+  /** Index entries (with their titles) carried over */
+  indexEntries: number;
+  bookmarks: number;
+  trimmedSessions: number;
+}
+
+/**
+ * Copy or move a workspace's sessions to the project of its new path, so they resume there.
+ * Run with `dryRun` first to see what would happen.
+ */
+export async function migrateWorkspaceSessions(
+  oldPath: string,
+  newPath: string,
+  mode: MigrationMode,
+  dryRun = false
+): Promise<MigrationReport> {
+  return invoke<MigrationReport>('migrate_workspace_sessions', { oldPath, newPath, mode, dryRun });
+}
+
+/** Prefix of errors caused by a path under ~/.claude mensa may not read or write; the rest is the path */
+export const PERMISSION_DENIED_PREFIX = 'Permission denied: ';
+
+/** Error of the session and plan commands (and cancellable session loads) */
+export type SessionError =
+  | { kind: 'permissionDenied'; path: string; suggestedFix: string; message: string }
+  | { kind: 'sessionInUse'; sessionId: string; by: SessionHolder; hint: string }
+  | { kind: 'failed'; message: string };
+
+/** Who else is writing to a session's project */
+export type SessionHolder =
+  | { kind: 'mensa'; pid: number; queryId: string }
+  | { kind: 'claudeCli'; pid: number; cwd: string; command: string };
+
+/**
+ * The path and fix of a permission error, whether it arrived typed or as a string (no fix then)
+ */
+export function permissionDenied(err: unknown): { path: string; suggestedFix: string | null } | null {
+  if (typeof err === 'string') {
+    return err.startsWith(PERMISSION_DENIED_PREFIX)
+      ? { path: err.slice(PERMISSION_DENIED_PREFIX.length), suggestedFix: null }
+      : null;
+  }
+  const typed = err as SessionError | null;
+  return typed && typed.kind === 'permissionDenied' ? { path: typed.path, suggestedFix: typed.suggestedFix } : null;
+}
+
+export interface PermissionIssue {
+  path: string;
+  isDir: boolean;
+  problem: 'unreadable' | 'unwritable';
+  /** Unix only */
+  ownerUid: number | null;
+  /** Owned by another user (root, after a sudo run) */
+  foreignOwner: boolean;
+  /** Octal permission bits, Unix only */
+  mode: string | null;
+  /** Shell command that fixes it; mensa never runs it */
+  suggestedFix: string;
+}
+
+export interface PermissionReport {
+  claudeDir: string;
+  checked: number;
+  issues: PermissionIssue[];
+  truncated: boolean;
+}
+
+/**
+ * Find paths under ~/.claude mensa can't read or write, for the given workspaces (the selected ones by default)
+ */
+export async function checkClaudePermissions(workspaces?: string[]): Promise<PermissionReport> {
+  return invoke<PermissionReport>('check_claude_permissions', { workspaces });
+}
+
+export interface RepairReport {
+  sessionId: string;
+  path: string;
+  /** 1-based lines holding whole entries glued to other writes; the entries were kept */
+  salvagedLines: number[];
+  /** 1-based lines nothing could be recovered from */
+  quarantinedLines: number[];
+  /** Where every changed line was kept as it was */
+  quarantineFile: string | null;
+  /** The unfinished last line was left for its writer */
+  writeInProgress: boolean;
+  changed: boolean;
+}
+
+/**
+ * Split apart or quarantine transcript lines broken by two writers; refuses (sessionInUse) while another
+ * Claude Code uses the project unless forced
+ */
+export async function repairSessionFile(workspacePath: string, sessionId: string, force = false): Promise<RepairReport> {
+  return invoke<RepairReport>('repair_session_file', { workspacePath, sessionId, force });
+}
+
+/** Parsed session cache usage, for the debug panel */
+export interface SessionCacheStats {
+  entries: number;
+  /** Estimated from the cached transcripts' sizes */
+  bytes: number;
+  capacityBytes: number;
+  hits: number;
+  misses: number;
+  /** Hits over all lookups, 0 before the first */
+  hitRate: number;
+}
+
+export async function getSessionCacheStats(): Promise<SessionCacheStats> {
+  return invoke<SessionCacheStats>('get_session_cache_stats');
+}
+
+export interface SessionSearchOptions {
+  /** Also search tool output (file contents, command output); off by default */
+  includeToolResults?: boolean;
+  /** Snippets per session (default 3); matchCount still counts every match */
+  maxSnippetsPerSession?: number;
+  /** Sessions returned, most recently modified first (default 50) */
+  limit?: number;
+}
+
+export interface SearchSnippet {
+  source: 'user' | 'assistant' | 'toolResult';
+  text: string;
+  timestamp: string | null;
+}
+
+export interface SessionSearchHit {
+  sessionId: string;
+  firstPrompt: string | null;
+  created: string | null;
+  modified: string;
+  matchCount: number;
+  snippets: SearchSnippet[];
+}
+
+export interface SessionSearchResults {
+  hits: SessionSearchHit[];
+  scanned: number;
+  /** More sessions matched than the limit allowed */
+  truncated: boolean;
+}
+
+/**
+ * Search the text of every session in a workspace; every word of the query must appear in one
+ * message, in any case
+ */
+export async function searchSessions(
+  workspacePath: string,
+  query: string,
+  options?: SessionSearchOptions
+): Promise<SessionSearchResults> {
+  return invoke<SessionSearchResults>('search_sessions', { workspacePath, query, options: options ?? null });
+}
+
+/** A session as listed by list_sessions */
+export interface SessionEntry {
+  sessionId: string;
+  firstPrompt: string;
+  messageCount: number;
+  created: string;
+  modified: string;
+  customTitle?: string;
+  /** Working directory the session was recorded in, when it differs from the workspace */
+  originalCwd?: string;
+  /** The index was behind the transcript; messageCount and modified come from the file */
+  indexStale: boolean;
+  /** The index lists the session but its transcript is gone, so it can't be opened */
+  missingFile: boolean;
+}
+
+/**
+ * A workspace's sessions a page at a time, most recently modified first. Rejects with a PageError.
+ */
+export async function listSessionsPaged(
+  workspacePath: string,
+  cursor?: string,
+  limit?: number,
+  healIndex = false
+): Promise<Page<SessionEntry>> {
+  return invoke<Page<SessionEntry>>('list_sessions_paged', { workspacePath, healIndex, cursor, limit });
+}
@@ -0,0 +1,201 @@
+// mensa - Settings Service
+// Provides frontend wrappers for the app-wide settings store
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+export interface StderrRule {
+  /** Regex tested against each stderr line */
+  pattern: string;
+  severity: 'debug' | 'info' | 'warning' | 'error';
+}
+
+export interface Settings {
+  notificationsEnabled: boolean;
+  /** Command used to open files externally, e.g. "code" */
+  preferredEditor: string | null;
+  /** Node binary for queries; null searches the usual install locations */
+  nodePath: string | null;
+  /** Oldest node version queries run with (e.g. "20.0.0"); null uses the built-in minimum */
+  minNodeVersion: string | null;
+  trayEnabled: boolean;
+  batchIntervalMs: number;
+  /** Minimum time between git status walks; calls in between reuse the last result */
+  gitStatusMinIntervalMs: number;
+  defaultTimeoutSecs: number;
+  /** Record prompts for recall; turning it off deletes the recorded ones */
+  promptHistoryEnabled: boolean;
+  /** Log finished queries for the usage analytics; turning it off deletes the log */
+  usageAnalyticsEnabled: boolean;
+  /** Check for a newer mensa once a day in the background */
+  autoUpdateCheck: boolean;
+  /** Context window by model-name substring, ahead of the model catalog; null drops a key */
+  contextLimits: Record<string, number>;
+  /** Percentages of the context window at which a running query warns */
+  contextWarningPercents: number[];
+  /** Running queries a query group waits for before starting its next member */
+  maxConcurrentQueries: number;
+  /** Proxy for the agent, git and gh; credentials go through setProxyCredentials */
+  proxy: ProxySettings;
+  /** Commands run, or NDJSON feeds appended to, on query, commit and PR events */
+  integrationHooks: IntegrationHook[];
+  /** Free space (MB) below which a query warns with low-disk-warning, before starting or while running */
+  lowDiskWarningMb: number;
+  /** Free space (MB) below which a query refuses to start (lowDiskSpace) */
+  minFreeDiskMb: number;
+  /** Extra stderr classifications, checked before the built-in rules; unmatched lines are warnings */
+  stderrRules: StderrRule[];
+  /** Accept requests from editor extensions on a local socket; applies at next launch */
+  controlServerEnabled: boolean;
+  /** Memory (MB, estimated from transcript sizes) parsed sessions are kept in; 0 turns the cache off */
+  sessionCacheMb: number;
+  /** http(s) URL of a model catalog manifest layered between the built-in models and models.json */
+  modelCatalogUrl: string | null;
+  /** Mutating commands are refused and queries only plan; change it with setReadOnly */
+  readOnly: boolean;
+  /** Keys from newer app versions are passed through untouched */
+  [key: string]: unknown;
+}
+
+export interface ProxySettings {
+  /** none: leave the environment alone; system: macOS proxy settings, or the environment elsewhere */
+  mode: 'none' | 'manual' | 'system';
+  http: string | null;
+  https: string | null;
+  /** Comma-separated hosts that bypass the proxy */
+  noProxy: string | null;
+}
+
+export type IntegrationEventKind = 'queryCompleted' | 'commitCreated' | 'prOpened';
+
+/** Set exactly one of command and appendToFeed */
+export interface IntegrationHook {
+  eventKinds: IntegrationEventKind[];
+  /** Program and arguments, run without a shell; the event arrives on stdin as JSON */
+  command?: string[] | null;
+  /** Absolute path of an NDJSON file to append events to */
+  appendToFeed?: string | null;
+  /** Default 10, at most 300 */
+  timeoutSecs?: number | null;
+  /** Rotate the feed at this size (default 10 MB) */
+  feedMaxBytes?: number | null;
+}
+
+/** One event as a hook receives it (a feed line, or a command's stdin) */
+export interface IntegrationEvent {
+  version: number;
+  id: string;
+  kind: IntegrationEventKind | 'test';
+  timestamp: number;
+  /** snake_case fields, depending on kind */
+  data: Record<string, unknown>;
+}
+
+export interface HookInvocation {
+  index: number;
+  eventId: string;
+  kind: IntegrationEventKind | 'test';
+  /** null for feeds, and for commands that were killed or never started */
+  exitCode: number | null;
+  timedOut: boolean;
+  durationMs: number;
+  error: string | null;
+  /** The end of the command's stderr */
+  stderr: string;
+}
+
+export type ProxyFailure =
+  | { kind: 'connectRefused'; message: string }
+  | { kind: 'authRequired'; message: string }
+  | { kind: 'tlsInterception'; message: string }
+  | { kind: 'timeout'; message: string }
+  | { kind: 'other'; message: string };
+
+export interface ProxyTestResult {
+  ok: boolean;
+  url: string;
+  /** Proxy used, without credentials; null when the request went direct */
+  proxy: string | null;
+  status: number | null;
+  latencyMs: number | null;
+  failure: ProxyFailure | null;
+}
+
+// Error returned by updateSettings
+export type SettingsError =
+  | { kind: 'invalid'; errors: Array<{ key: string; message: string }> }
+  | { kind: 'failed'; message: string };
+
+export interface SettingsChanged {
+  keys: string[];
+  settings: Settings;
+}
+
+/**
+ * Get the current settings
+ */
+export async function getSettings(): Promise<Settings> {
+  return invoke<Settings>('get_settings');
+}
+
+/**
+ * Apply a partial update; rejects with a SettingsError listing every invalid key
+ */
+export async function updateSettings(patch: Partial<Settings>): Promise<Settings> {
+  return invoke<Settings>('update_settings', { patch });
+}
+
+/**
+ * Subscribe to settings changes made from any window
+ */
+export async function onSettingsChanged(callback: (change: SettingsChanged) => void): Promise<UnlistenFn> {
+  return listen<SettingsChanged>('settings-changed', (event) => callback(event.payload));
+}
+
+/**
+ * HEAD a URL (default: the Anthropic API) through the configured proxy
+ */
+export async function testProxy(url?: string): Promise<ProxyTestResult> {
+  return invoke<ProxyTestResult>('test_proxy', { url: url ?? null });
+}
+
+/**
+ * Keep the proxy's username and password in the keychain; pass null to remove them
+ */
+export async function setProxyCredentials(username: string | null, password?: string): Promise<void> {
+  return invoke('set_proxy_credentials', { username, password: password ?? null });
+}
+
+/**
+ * Send a synthetic test event to the integration hook at index and report how it went
+ */
+export async function testIntegrationHook(index: number): Promise<HookInvocation> {
+  return invoke<HookInvocation>('test_integration_hook', { index });
+}
+
+export interface ReadOnlyStatus {
+  enabled: boolean;
+  /** Commands refused while enabled, to hide their controls */
+  mutatingCommands: string[];
+}
+
+// Rejected by mutating commands while read-only mode is on, and by setReadOnly
+export type ReadOnlyError =
+  | { kind: 'readOnlyMode'; command: string; message: string }
+  | { kind: 'wrongPassphrase'; message: string }
+  | { kind: 'failed'; message: string };
+
+export function isReadOnlyError(error: unknown): error is Extract<ReadOnlyError, { kind: 'readOnlyMode' }> {
+  return typeof error === 'object' && error !== null && (error as { kind?: unknown }).kind === 'readOnlyMode';
+}
+
+export async function getReadOnlyStatus(): Promise<ReadOnlyStatus> {
+  return invoke<ReadOnlyStatus>('get_read_only_status');
+}
+
+/**
+ * Turn read-only mode on (setting the passphrase) or off (with the same passphrase)
+ */
+export async function setReadOnly(enabled: boolean, passphrase: string): Promise<ReadOnlyStatus> {
+  return invoke<ReadOnlyStatus>('set_read_only', { enabled, passphrase });
+}
@@ -0,0 +1,58 @@
+// mensa - Prompt Templates Service
+// Provides frontend wrappers for shared prompt templates with typed variables
+
+import { invoke } from '@tauri-apps/api/core';
+
+export type TemplateScope = 'global' | 'workspace';
+
+export type VariableKind = 'string' | 'branch' | 'file' | 'pr_url';
+
+export interface TemplateVariable {
+  name: string;
+  kind: VariableKind;
+  required?: boolean;
+  default?: string;
+  description?: string;
+}
+
+/**
+ * Body placeholders use {{name}}; write \{{ for a literal {{.
+ * current_branch, default_branch, changed_files and latest_session are filled by the backend.
+ */
+export interface PromptTemplate {
+  name: string;
+  body: string;
+  variables: TemplateVariable[];
+  scope: TemplateScope;
+}
+
+export interface ExpandedPrompt {
+  prompt: string;
+  unresolved: string[];
+}
+
+/**
+ * List templates for a workspace (workspace templates shadow global ones of the same name)
+ */
+export async function listPromptTemplates(workingDir?: string): Promise<PromptTemplate[]> {
+  return invoke<PromptTemplate[]>('list_prompt_templates', { workingDir: workingDir ?? null });
+}
+
+export async function savePromptTemplate(template: PromptTemplate, workingDir?: string): Promise<PromptTemplate> {
+  return invoke<PromptTemplate>('save_prompt_template', { template, workingDir: workingDir ?? null });
+}
+
+export async function deletePromptTemplate(name: string, scope: TemplateScope, workingDir?: string): Promise<boolean> {
+  return invoke<boolean>('delete_prompt_template', { name, scope, workingDir: workingDir ?? null });
+}
+
+/**
+ * Expand a template into the final prompt; fails when a required variable is missing or invalid
+ */
+export async function expandPromptTemplate(
+  workingDir: string,
+  name: string,
+  vars: Record<string, string> = {}
+): Promise<ExpandedPrompt> {
+  return invoke<ExpandedPrompt>('expand_prompt_template', { workingDir, name, vars });
+}
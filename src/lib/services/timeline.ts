@@ -0,0 +1,76 @@
+// mensa - Timeline Service
+// Provides frontend wrappers for the chronological view of what a query did
+
+import { invoke } from '@tauri-apps/api/core';
+import type { HookEvent } from '$lib/types';
+import type { GitCommit } from '$lib/types/git';
+
+export type ToolStatus = 'ok' | 'error' | 'pending';
+
+/** One thing that happened during a run; switch on `kind` */
+export type TimelineEntry =
+  | {
+      kind: 'toolRun';
+      atMs: number;
+      toolUseId: string | null;
+      name: string;
+      /** File, command, pattern or URL the tool worked on */
+      subject: string | null;
+      durationMs: number | null;
+      status: ToolStatus;
+    }
+  | {
+      /** Consecutive runs of one tool, e.g. "Read ×14 in src/" */
+      kind: 'toolRunGroup';
+      atMs: number;
+      name: string;
+      count: number;
+      scope: string | null;
+      label: string;
+      durationMs: number;
+      errors: number;
+    }
+  | { kind: 'filesChanged'; atMs: number; files: string[] }
+  | { kind: 'commit'; atMs: number; commit: GitCommit }
+  | { kind: 'permissionDenied'; atMs: number; tool: string; toolUseId: string | null }
+  | { kind: 'hook'; atMs: number; event: HookEvent }
+  | { kind: 'costCheckpoint'; atMs: number; costUsd: number; maxCostUsd: number | null; reason: 'warning' | 'limit' | 'final' }
+  | { kind: 'warning'; atMs: number; source: 'contextUsage' | 'disk' | 'stderr' | 'references'; message: string };
+
+export interface TimelineTotals {
+  toolRuns: number;
+  toolErrors: number;
+  /** Runs per tool name */
+  tools: Record<string, number>;
+  filesChanged: number;
+  commits: number;
+  permissionDenials: number;
+  hooksRun: number;
+  hooksBlocked: number;
+  warnings: number;
+  costUsd: number | null;
+  durationMs: number | null;
+}
+
+export interface QueryTimeline {
+  queryId: string;
+  /** live: the query's event buffer; session: its transcript; history: the history record alone */
+  source: 'live' | 'session' | 'history';
+  running: boolean;
+  startedAtMs: number | null;
+  finishedAtMs: number | null;
+  entries: TimelineEntry[];
+  /** Entries cut to stay under the cap, after grouping */
+  omitted: number;
+  /** The start of the run was evicted from the event buffer */
+  partial: boolean;
+  /** Counted over everything, before grouping and cutting */
+  totals: TimelineTotals;
+}
+
+/**
+ * Everything a running or finished query did, in time order, with totals for a summary card
+ */
+export async function getQueryTimeline(queryId: string): Promise<QueryTimeline> {
+  return invoke<QueryTimeline>('get_query_timeline', { queryId });
+}
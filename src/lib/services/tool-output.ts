@@ -0,0 +1,39 @@
+// mensa - Tool Output Service
+// Provides frontend wrappers for fetching the full text of tool outputs sent as previews
+
+import { invoke } from '@tauri-apps/api/core';
+
+/** One window of a tool's full output (byte offsets, snapped to character boundaries) */
+export interface ToolOutputSlice {
+  toolUseId: string;
+  offset: number;
+  content: string;
+  totalBytes: number;
+  /** Start of the next slice; null once the end is reached */
+  nextOffset: number | null;
+}
+
+/**
+ * Fetch part of a tool's output from a saved session (for outputTruncated tools)
+ */
+export async function getToolOutput(
+  workspacePath: string,
+  sessionId: string,
+  toolUseId: string,
+  offset: number,
+  limit: number
+): Promise<ToolOutputSlice> {
+  return invoke<ToolOutputSlice>('get_tool_output', { workspacePath, sessionId, toolUseId, offset, limit });
+}
+
+/**
+ * Fetch part of a tool's output from a running query (for truncated tool_result stream events)
+ */
+export async function getQueryToolOutput(
+  queryId: string,
+  toolUseId: string,
+  offset: number,
+  limit: number
+): Promise<ToolOutputSlice> {
+  return invoke<ToolOutputSlice>('get_query_tool_output', { queryId, toolUseId, offset, limit });
+}
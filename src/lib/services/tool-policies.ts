@@ -0,0 +1,68 @@
+// mensa - Tool Policies Service
+// Provides frontend wrappers for per-workspace allow/deny/ask rules on tool permission prompts
+
+import { invoke } from '@tauri-apps/api/core';
+
+export type PolicyAction = 'allow' | 'deny' | 'ask';
+
+export interface ToolRule {
+  action: PolicyAction;
+  /** Regex over the tool's main input (Bash command, file path, URL, ...); none covers every use */
+  pattern?: string;
+}
+
+export interface ToolPolicy {
+  /** Tool name, or a `*` wildcard such as "mcp__github__*" */
+  tool: string;
+  rules: ToolRule[];
+}
+
+/** The rule that decided a tool use; deny wins over ask, ask over allow */
+export interface PolicyDecision {
+  action: PolicyAction;
+  tool: string;
+  pattern: string | null;
+}
+
+export interface SkippedRule {
+  tool: string;
+  pattern: string | null;
+  reason: string;
+}
+
+export interface PolicyExport {
+  /** `{ permissions: { allow, deny, ask } }` in .claude/settings.json form */
+  settings: { permissions: Partial<Record<PolicyAction, string[]>> };
+  /** Rules Claude Code's permission syntax can't express (regexes, tool wildcards) */
+  skipped: SkippedRule[];
+  writtenTo: string | null;
+}
+
+export async function getToolPolicies(workingDir: string): Promise<ToolPolicy[]> {
+  return invoke<ToolPolicy[]>('get_tool_policies', { workingDir });
+}
+
+/**
+ * Replace the rules for one tool in a workspace (an empty list removes them); returns all policies
+ */
+export async function setToolPolicy(workingDir: string, tool: string, rules: ToolRule[]): Promise<ToolPolicy[]> {
+  return invoke<ToolPolicy[]>('set_tool_policy', { workingDir, tool, rules });
+}
+
+/**
+ * What the workspace's policies decide for a tool use; null means no rule matches and the user is asked
+ */
+export async function evaluateToolPolicy(
+  workingDir: string,
+  tool: string,
+  input: Record<string, unknown>
+): Promise<PolicyDecision | null> {
+  return invoke<PolicyDecision | null>('evaluate_tool_policy', { workingDir, tool, input });
+}
+
+/**
+ * Convert the policies to .claude/settings.json permissions; with `write`, merge them into the workspace's file
+ */
+export async function exportToolPolicies(workingDir: string, write = false): Promise<PolicyExport> {
+  return invoke<PolicyExport>('export_tool_policies', { workingDir, write });
+}
@@ -0,0 +1,58 @@
+// mensa - Updates Service
+// Provides frontend wrappers for checking for, and installing, newer mensa releases
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+export interface UpdateInfo {
+  currentVersion: string;
+  /** Newer version on offer; null when mensa is up to date */
+  availableVersion: string | null;
+  /** Publish date (RFC 3339) */
+  date: string | null;
+  /** Release notes as markdown */
+  notes: string | null;
+  /** Release notes rendered to sanitized HTML */
+  notesHtml: string | null;
+}
+
+/** Error of checkForUpdates and installUpdate */
+export type UpdateError =
+  | { kind: 'notConfigured'; message: string }
+  | { kind: 'network'; message: string }
+  /** The download isn't signed by mensa's release key; nothing was installed */
+  | { kind: 'signatureMismatch'; message: string }
+  /** Install again with force to cancel the running queries */
+  | { kind: 'queriesActive'; count: number; message: string }
+  | { kind: 'failed'; message: string };
+
+/** Payload of update-available, emitted by the daily background check */
+export interface UpdateAvailableEvent {
+  current_version: string;
+  version: string;
+  date: string | null;
+  notes: string | null;
+  notes_html: string | null;
+}
+
+/**
+ * Ask the update endpoint whether a newer mensa exists
+ */
+export async function checkForUpdates(): Promise<UpdateInfo> {
+  return invoke<UpdateInfo>('check_for_updates');
+}
+
+/**
+ * Download, verify and install the update, then restart; progress arrives as "app-update"
+ * progress events. Resolves with the installed version shortly before the restart.
+ */
+export async function installUpdate(force = false, operationId?: string): Promise<string> {
+  return invoke<string>('install_update', { force, operationId });
+}
+
+/**
+ * Listen for updates found by the background check; returns a function that stops listening
+ */
+export async function onUpdateAvailable(handler: (update: UpdateAvailableEvent) => void): Promise<UnlistenFn> {
+  return listen<UpdateAvailableEvent>('update-available', (event) => handler(event.payload));
+}
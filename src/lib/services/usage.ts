@@ -0,0 +1,68 @@
+// mensa - Usage Analytics Service
+// Provides frontend wrappers for charts of local query usage (nothing is uploaded)
+
+import { invoke } from '@tauri-apps/api/core';
+
+/** Weeks start on Monday */
+export type Bucketing = 'day' | 'week';
+
+/** Unix seconds; an open end defaults to now, an open start to 30 days before the end */
+export interface AnalyticsRange {
+  from?: number;
+  to?: number;
+}
+
+export interface UsageBucket {
+  /** Local midnight (unix seconds) the bucket starts at */
+  start: number;
+  queries: number;
+  completed: number;
+  failed: number;
+  cancelled: number;
+  costUsd: number;
+}
+
+export interface UsageShare {
+  /** Workspace path or model name */
+  key: string;
+  queries: number;
+  costUsd: number;
+}
+
+export interface UsageTotals {
+  queries: number;
+  costUsd: number;
+  averageDurationMs: number;
+  /** Fractions of all queries, 0 when there were none */
+  cancelRate: number;
+  failureRate: number;
+  costLimitRate: number;
+}
+
+export interface UsageAnalytics {
+  groupBy: Bucketing;
+  from: number;
+  to: number;
+  /** Oldest and newest query in the period; the log may not reach back to `from` */
+  coveredFrom: number | null;
+  coveredTo: number | null;
+  /** False when usageAnalyticsEnabled is off (the log is then empty) */
+  recording: boolean;
+  /** One per day or week of the period, empty ones included */
+  buckets: UsageBucket[];
+  /** Queries started in each local hour of the day, 0-23 */
+  hours: number[];
+  /** Most expensive first */
+  workspaces: UsageShare[];
+  /** Most used first; "unknown" when a run's model wasn't seen */
+  models: UsageShare[];
+  totals: UsageTotals;
+}
+
+/**
+ * Queries, cost, durations and outcomes over a period, bucketed in the local time zone
+ */
+export async function getUsageAnalytics(range?: AnalyticsRange, groupBy?: Bucketing): Promise<UsageAnalytics> {
+  const utcOffsetMinutes = -new Date().getTimezoneOffset();
+  return invoke<UsageAnalytics>('get_usage_analytics', { range, groupBy, utcOffsetMinutes });
+}
@@ -0,0 +1,241 @@
+// mensa - Workspace Service
+// Provides frontend wrappers for Tauri workspace commands
+
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { GitCommit, StatusSummary } from '$lib/types/git';
+import type { PRListItem } from '$lib/types/review';
+
+export interface WorkspaceUiState {
+  lastSessionId?: string | null;
+  lastBranch?: string | null;
+  lastPrUrl?: string | null;
+  layout?: unknown;
+  /** When the last workspace digest succeeded (unix seconds) */
+  lastDigestAt?: number | null;
+  /** Author of commits made from mensa here (set with setCommitIdentity) */
+  commitIdentity?: { name: string; email: string } | null;
+}
+
+export interface WorkspaceStateView {
+  state: WorkspaceUiState;
+  stale: { session: boolean; branch: boolean };
+}
+
+export interface BootstrapOptions {
+  claudeMd?: boolean;
+  settings?: boolean;
+  gitignore?: boolean;
+}
+
+export interface BootstrapReport {
+  created: string[];
+  updated: string[];
+  skipped: string[];
+  facts: {
+    language?: string;
+    packageManager?: string;
+    testCommand?: string;
+  };
+}
+
+/**
+ * Get where the user left off in a workspace; stale flags mark references that no longer exist
+ */
+export async function getWorkspaceState(workingDir: string): Promise<WorkspaceStateView> {
+  return invoke<WorkspaceStateView>('get_workspace_state', { workingDir });
+}
+
+/**
+ * Update part of the remembered workspace state (null clears a field)
+ */
+export async function setWorkspaceState(
+  workingDir: string,
+  patch: Partial<WorkspaceUiState>
+): Promise<WorkspaceUiState> {
+  return invoke<WorkspaceUiState>('set_workspace_state', { workingDir, patch });
+}
+
+/**
+ * Create starter CLAUDE.md, .claude/settings.json and .gitignore entries
+ */
+export async function bootstrapWorkspace(
+  workingDir: string,
+  options?: BootstrapOptions
+): Promise<BootstrapReport> {
+  return invoke<BootstrapReport>('bootstrap_workspace', { workingDir, options });
+}
+
+/**
+ * Add patterns to the workspace's .gitignore, skipping ones already listed; returns those added
+ */
+export async function gitignoreAdd(workingDir: string, patterns: string[]): Promise<string[]> {
+  return invoke<string[]>('gitignore_add', { workingDir, patterns });
+}
+
+export type HealthSeverity = 'info' | 'warning' | 'critical';
+
+export type HealthSection = 'repository' | 'status' | 'untracked' | 'paths' | 'largeFiles';
+
+/** One-click fixes: gitGc maps to cleanupRepository, gitignoreAdd to gitignoreAdd(patterns) */
+export type HealthSuggestion =
+  | { id: 'gitGc' }
+  | { id: 'gitignoreAdd'; patterns: string[] };
+
+export interface HealthFinding {
+  kind:
+    | 'looseObjects'
+    | 'largeRepository'
+    | 'slowStatus'
+    | 'untrackedDirectory'
+    | 'longPaths'
+    | 'caseConflicts'
+    | 'largeFiles';
+  severity: HealthSeverity;
+  title: string;
+  detail: string;
+  /** Examples, relative to the workspace */
+  paths: string[];
+  /** Size the finding is about (packed history, an untracked directory, large files) */
+  sizeBytes: number | null;
+  suggestion: HealthSuggestion | null;
+}
+
+export interface WorkspaceHealth {
+  workingDir: string;
+  /** Most severe first */
+  findings: HealthFinding[];
+  /** Worst severity, for the workspace header badge */
+  worst: HealthSeverity | null;
+  statusMs: number | null;
+  /** Sections cut off by the time budget; their findings may be missing or partial */
+  timedOutSections: HealthSection[];
+  errors: string[];
+  elapsedMs: number;
+}
+
+/**
+ * Look for what slows agents down in a workspace (repository size, status latency, unignored
+ * directories, long or case-conflicting paths, large tracked files); finishes within seconds
+ */
+export async function analyzeWorkspaceHealth(workingDir: string): Promise<WorkspaceHealth> {
+  return invoke<WorkspaceHealth>('analyze_workspace_health', { workingDir });
+}
+
+export interface WorkspaceTemplateInfo {
+  name: string;
+  description: string;
+}
+
+export interface CreateWorkspaceOptions {
+  /** Scaffolding to add; defaults to bootstrapWorkspace's */
+  bootstrap?: BootstrapOptions;
+  /** Run the install command detected from the project's manifest */
+  installDependencies?: boolean;
+  /** Leave whatever was created in place when a step fails */
+  keepPartial?: boolean;
+  /** Branch to check out when cloning */
+  branch?: string;
+  /** Id for progress events and cancelGitOperation */
+  operationId?: string;
+}
+
+export interface CreatedWorkspace {
+  /** Canonical path, already registered as a selected workspace */
+  path: string;
+  cloned: boolean;
+  initializedGit: boolean;
+  bootstrap: BootstrapReport;
+  /** Install commands that ran */
+  installed: string[];
+}
+
+/**
+ * List the built-in starters createWorkspaceFromTemplate accepts by name
+ */
+export async function listWorkspaceTemplates(): Promise<WorkspaceTemplateInfo[]> {
+  return invoke<WorkspaceTemplateInfo[]>('list_workspace_templates');
+}
+
+/**
+ * Create a project from a git URL or built-in template at a missing or empty destination,
+ * ready to open; a failed attempt is cleaned up unless keepPartial is set
+ */
+export async function createWorkspaceFromTemplate(
+  template: string,
+  destination: string,
+  options?: CreateWorkspaceOptions
+): Promise<CreatedWorkspace> {
+  return invoke<CreatedWorkspace>('create_workspace_from_template', { template, destination, options });
+}
+
+/** A digest section: data, or the error (including timeouts) that section hit */
+export interface DigestSection<T> {
+  data: T | null;
+  error: string | null;
+}
+
+export interface DigestSession {
+  sessionId: string;
+  firstPrompt: string;
+  messageCount: number;
+  modifiedAt: number;
+}
+
+export interface WorkspaceDigest {
+  since: number;
+  generatedAt: number;
+  sessions: DigestSection<DigestSession[]>;
+  commits: DigestSection<GitCommit[]>;
+  status: DigestSection<StatusSummary>;
+  pullRequests: DigestSection<PRListItem[]>;
+}
+
+/**
+ * Summarize what changed since `since` (default: the previous digest, or the last day)
+ */
+export async function getWorkspaceDigest(workingDir: string, since?: number): Promise<WorkspaceDigest> {
+  return invoke<WorkspaceDigest>('workspace_digest', { workingDir, since });
+}
+
+export interface LanguageShare {
+  language: string;
+  bytes: number;
+  files: number;
+  /** Of the bytes in recognized languages */
+  percent: number;
+}
+
+export interface WorkspaceOverview {
+  /** Top 5 languages by bytes */
+  languages: LanguageShare[];
+  otherPercent: number;
+  topDirectories: Array<{ path: string; bytes: number; files: number }>;
+  fileCount: number;
+  totalBytes: number;
+  /** Owners of each top-level path; null without CODEOWNERS */
+  ownership: Array<{ path: string; owners: string[] }> | null;
+  owners: Array<{ owner: string; paths: string[] }>;
+  /** null outside a git repository */
+  head: string | null;
+  /** The index was still building or the time budget ran out */
+  partial: boolean;
+  truncated: boolean;
+  cached: boolean;
+}
+
+/**
+ * Languages, largest directories and owners of a workspace, for its header; cached per HEAD
+ */
+export async function getWorkspaceOverview(workingDir: string): Promise<WorkspaceOverview> {
+  return invoke<WorkspaceOverview>('workspace_overview', { workingDir });
+}
+
+/**
+ * Listen for requests (from an editor extension, over the control endpoint) to show a workspace
+ */
+export async function onWorkspaceOpenRequested(
+  callback: (request: { working_dir: string }) => void
+): Promise<UnlistenFn> {
+  return listen<{ working_dir: string }>('workspace-open-requested', (event) => callback(event.payload));
+}
@@ -1,6 +1,7 @@
 // mensa - App State Management (Svelte 5 Runes)
 
 import { browser } from '$app/environment';
+import { invoke } from '@tauri-apps/api/core';
 import type { AppState, AppConfig, Message, ToolExecution, WorkspaceConfig, ClaudeConfig, MCPServerConfig, PermissionMode, Attachment, MessageBlock, SubagentGroup, SettingSource, SlashCommand, Theme } from '$lib/types';
 
 const DEFAULT_CLAUDE_CONFIG: ClaudeConfig = {
@@ -14,6 +15,14 @@ const DEFAULT_CLAUDE_CONFIG: ClaudeConfig = {
   vimMode: false
 };
 
+// Tell the backend which workspace the user picked; workspace-modifying commands only accept these
+function registerWorkspace(ws: WorkspaceConfig | undefined) {
+  if (!ws) return;
+  invoke('register_workspace', { path: ws.path }).catch((e) => {
+    console.error('[mensa] Failed to register workspace:', e);
+  });
+}
+
 // App configuration persisted to localStorage
 function createAppConfig() {
   // Always start with defaults - will hydrate from localStorage on client
@@ -34,6 +43,7 @@ function createAppConfig() {
         const data = JSON.parse(stored);
         onboardingCompleted = data.onboardingCompleted ?? false;
         workspace = data.workspace ?? undefined;
+        registerWorkspace(workspace);
         claude = { ...DEFAULT_CLAUDE_CONFIG, ...data.claude };
         theme = data.theme ?? 'system';
         themeUserSet = data.themeUserSet ?? false;
@@ -69,6 +79,7 @@ function createAppConfig() {
 
     setWorkspace(ws: WorkspaceConfig) {
       workspace = ws;
+      registerWorkspace(ws);
       save();
     },
 
@@ -19,6 +19,7 @@ function createGitStore() {
   let error = $state<string | null>(null);
   let branchInfo = $state<BranchInfo | null>(null);
   let commitLog = $state<GitCommit[]>([]);
+  let commitLogTruncatedByShallowClone = $state(false);
   let diffViewMode = $state<DiffViewMode>('unified');
 
   // Commit dialog state
@@ -47,6 +48,7 @@ function createGitStore() {
     get error() { return error; },
     get branchInfo() { return branchInfo; },
     get commitLog() { return commitLog; },
+    get commitLogTruncatedByShallowClone() { return commitLogTruncatedByShallowClone; },
     get diffViewMode() { return diffViewMode; },
 
     // Commit dialog getters
@@ -105,9 +107,10 @@ function createGitStore() {
     },
 
     /**
-     * Refresh git status from the repository
+     * Refresh git status from the repository. Pass `force` after changing the repository,
+     * since a refresh right after another one otherwise gets the previous status back.
      */
-    async refresh(workingDir: string) {
+    async refresh(workingDir: string, force = false) {
       if (!workingDir) return;
 
       isLoading = true;
@@ -115,7 +118,7 @@ function createGitStore() {
 
       try {
         const [newStatus, newBranchInfo] = await Promise.all([
-          gitService.getGitStatus(workingDir),
+          gitService.getGitStatus(workingDir, true, undefined, force),
           gitService.getBranchInfo(workingDir)
         ]);
 
@@ -145,7 +148,7 @@ function createGitStore() {
       currentDiff = null;
 
       try {
-        const diff = await gitService.getGitDiff(workingDir, file.path, staged);
+        const diff = await gitService.getGitDiff(workingDir, file.path, staged, file.rawB64);
         currentDiff = diff;
       } catch (e) {
         console.error('[gitStore] Failed to get diff:', e);
@@ -168,7 +171,7 @@ function createGitStore() {
     async stageFiles(workingDir: string, paths: string[]) {
       try {
         await gitService.stageFiles(workingDir, paths);
-        await this.refresh(workingDir);
+        await this.refresh(workingDir, true);
       } catch (e) {
         error = e instanceof Error ? e.message : String(e);
         throw e;
@@ -198,7 +201,7 @@ function createGitStore() {
     async unstageFiles(workingDir: string, paths: string[]) {
       try {
         await gitService.unstageFiles(workingDir, paths);
-        await this.refresh(workingDir);
+        await this.refresh(workingDir, true);
       } catch (e) {
         error = e instanceof Error ? e.message : String(e);
         throw e;
@@ -220,8 +223,9 @@ function createGitStore() {
      */
     async discardFile(workingDir: string, filePath: string) {
       try {
-        await gitService.discardChanges(workingDir, filePath);
-        await this.refresh(workingDir);
+        const preview = await gitService.discardChanges(workingDir, filePath, undefined, false, true);
+        await gitService.discardChanges(workingDir, filePath, undefined, false, false, preview.token);
+        await this.refresh(workingDir, true);
 
         // Clear selection if the discarded file was selected
         if (selectedFile?.path === filePath) {
@@ -239,7 +243,7 @@ function createGitStore() {
     async fetch(workingDir: string) {
       try {
         await gitService.fetchRemote(workingDir);
-        await this.refresh(workingDir);
+        await this.refresh(workingDir, true);
       } catch (e) {
         error = e instanceof Error ? e.message : String(e);
         throw e;
@@ -252,7 +256,7 @@ function createGitStore() {
     async pull(workingDir: string) {
       try {
         await gitService.pullChanges(workingDir);
-        await this.refresh(workingDir);
+        await this.refresh(workingDir, true);
       } catch (e) {
         error = e instanceof Error ? e.message : String(e);
         throw e;
@@ -286,10 +290,13 @@ function createGitStore() {
      */
     async loadCommitLog(workingDir: string, limit: number = 50, branch?: string) {
       try {
-        commitLog = await gitService.getCommitLog(workingDir, limit, branch);
+        const log = await gitService.getCommitLog(workingDir, limit, branch);
+        commitLog = log.commits;
+        commitLogTruncatedByShallowClone = log.shallowBoundaryReached;
       } catch (e) {
         console.error('[gitStore] Failed to load commit log:', e);
         commitLog = [];
+        commitLogTruncatedByShallowClone = false;
       }
     },
 
@@ -315,8 +322,8 @@ function createGitStore() {
       isCommitting = true;
 
       try {
-        const hash = await gitService.createCommit(workingDir, message);
-        console.log('[gitStore] Created commit:', hash);
+        const { oid: hash, author } = await gitService.createCommit(workingDir, message);
+        console.log('[gitStore] Created commit:', hash, `as ${author.name} <${author.email}>`);
 
         if (push) {
           // Check if we need to set upstream
@@ -325,13 +332,14 @@ function createGitStore() {
           console.log('[gitStore] Pushed changes');
         }
 
-        await this.refresh(workingDir);
+        await this.refresh(workingDir, true);
         this.closeCommitDialog();
         this.clearClaudeModifiedFiles();
 
         return hash;
       } catch (e) {
-        error = e instanceof Error ? e.message : String(e);
+        const gitError = gitService.asGitCommandError(e);
+        error = gitError ? gitError.message : e instanceof Error ? e.message : String(e);
         throw e;
       } finally {
         isCommitting = false;
@@ -340,6 +348,13 @@ function createGitStore() {
 
     // PR dialog actions
     async openPRDialog(workingDir: string) {
+      // gh can't open merge requests on GitLab or Bitbucket remotes
+      const repoInfo = await gitService.getRepoInfo(workingDir).catch(() => null);
+      if (repoInfo && !repoInfo.supportsPullRequests) {
+        error = `Creating pull requests isn't supported for ${repoInfo.provider === 'gitlab' ? 'GitLab' : 'Bitbucket'} remotes yet.`;
+        return;
+      }
+
       // Check gh CLI availability
       if (ghCliAvailable === null) {
         ghCliAvailable = await gitService.checkGhCliAvailable();
@@ -419,6 +434,7 @@ function createGitStore() {
       error = null;
       branchInfo = null;
       commitLog = [];
+      commitLogTruncatedByShallowClone = false;
       showCommitDialog = false;
       commitMessage = '';
       isCommitting = false;
@@ -1,11 +1,32 @@
 // mensa - Git Types
 
+import type { Page } from './index';
+
 export type GitFileStatus = 'added' | 'modified' | 'deleted' | 'renamed' | 'untracked';
 
 export interface GitFile {
   path: string;
   status: GitFileStatus;
   oldPath?: string;
+  rawB64?: string; // Base64 of the raw path bytes, only set for non-UTF-8 paths
+}
+
+export interface StatusSummary {
+  staged: number;
+  modified: number;
+  untracked: number;
+  deleted: number;
+  conflicted: number;
+}
+
+/** A file's staged and unstaged state together; renames are one row with oldPath */
+export interface GitFileEx {
+  path: string;
+  oldPath?: string;
+  indexStatus: 'added' | 'modified' | 'deleted' | 'renamed' | 'typechange' | null;
+  worktreeStatus: 'untracked' | 'modified' | 'deleted' | 'renamed' | 'typechange' | null;
+  conflicted: boolean;
+  rawB64?: string;
 }
 
 export interface GitStatus {
@@ -17,6 +38,12 @@ export interface GitStatus {
   modified: GitFile[];
   untracked: GitFile[];
   deleted: GitFile[];
+  /** One row per changed file; the four lists above are deprecated */
+  files: GitFileEx[];
+  /** Files hidden by the workspace's status filters */
+  filtered: GitFile[];
+  /** Set when the previous result was reused inside the minimum refresh interval: its age */
+  staleMs?: number;
 }
 
 export interface BranchInfo {
@@ -24,9 +51,124 @@ export interface BranchInfo {
   upstream?: string;
   ahead: number;
   behind: number;
+  /** An upstream is configured but the remote branch was deleted */
+  upstreamGone: boolean;
   recentBranches: string[];
 }
 
+export interface BranchListItem {
+  name: string;
+  isCurrent: boolean;
+  upstream?: string;
+  ahead: number;
+  behind: number;
+  upstreamGone: boolean;
+  lastCommitTime: number; // Unix timestamp in seconds
+  lastCommitSummary: string;
+}
+
+export interface DiffFileStat {
+  path: string;
+  oldPath?: string;
+  status: 'added' | 'modified' | 'deleted' | 'renamed' | 'copied' | 'typechange';
+  additions: number;
+  deletions: number;
+  binary: boolean;
+  /** Only reported for binary files */
+  oldSizeBytes?: number;
+  newSizeBytes?: number;
+  lineEndingsOnly: boolean;
+  /** Marked linguist-generated in .gitattributes */
+  generated: boolean;
+  /** Marked linguist-vendored in .gitattributes */
+  vendored: boolean;
+  /** Marked -diff: shown as binary, stats only */
+  diffSuppressed: boolean;
+  /** The diff=<driver> attribute */
+  diffDriver?: string;
+}
+
+export interface PathAttributes {
+  path: string;
+  text: string;
+  eol: string;
+  crlf: string;
+}
+
+export interface LineEndingReport {
+  coreAutocrlf: string | null;
+  coreEol: string | null;
+  files: PathAttributes[];
+}
+
+export interface DiffStats {
+  files: DiffFileStat[];
+  /** Totals over `files` only */
+  additions: number;
+  deletions: number;
+  /** Files hidden by the workspace's status filters */
+  filtered: DiffFileStat[];
+}
+
+export interface BranchProtection {
+  branch: string;
+  status: 'protected' | 'unprotected' | 'insufficient_scope' | 'unknown';
+  restrictsPushes: boolean;
+  requiredStatusChecks: string[];
+  requiredReviewCount: number;
+  enforceAdmins: boolean;
+  message?: string;
+}
+
+// Structured error returned by the git commands (see invokeGit)
+export type GitCommandError =
+  | { kind: 'protectedBranch'; branch: string; message: string; suggestion: string }
+  | { kind: 'repoUnsupported'; reason: string; message: string }
+  | { kind: 'shallowHistory'; message: string; suggestion: string }
+  | { kind: 'upstreamGone'; branch: string; upstream: string; message: string; suggestion: string }
+  | { kind: 'failed'; message: string };
+
+/** Prefix of every git command error when the repository can't be used */
+export const REPO_UNSUPPORTED_PREFIX = 'Repository unsupported: ';
+
+/** Prefix of errors caused by history missing from a shallow clone */
+export const SHALLOW_HISTORY_PREFIX = 'Shallow history: ';
+
+export interface RepoCapabilities {
+  isRepo: boolean;
+  isBare: boolean;
+  objectFormat: string | null;
+  hasCommits: boolean;
+  supportsStatus: boolean;
+  /** A .git is present but can't be read */
+  damaged: boolean;
+  failureReason: string | null;
+  hint?: string;
+}
+
+export type ForgeProvider = 'github' | 'gitlab' | 'bitbucket' | 'unknown';
+
+/** The code host behind a repository's remote and what mensa can do there */
+export interface RepoInfo {
+  provider: ForgeProvider;
+  /** "origin", or the first remote when there is no origin */
+  remoteName: string | null;
+  remoteUrl: string | null;
+  host: string | null;
+  project: string | null;
+  /** Creating and listing PRs (through gh); off for GitLab and Bitbucket */
+  supportsPullRequests: boolean;
+  /** Loading a PR/MR's info and diff for review */
+  supportsReview: boolean;
+  /** Posting a review back */
+  supportsReviewPosting: boolean;
+}
+
+// Structured error returned by post_pr_review
+export type ReviewError =
+  | { kind: 'unsupportedProvider'; provider: ForgeProvider; operation: string; message: string }
+  | { kind: 'failed'; message: string };
+
 export interface GitCommit {
   hash: string;
   shortHash: string;
@@ -39,6 +181,19 @@ export interface GitCommit {
   deletions: number;
 }
 
+export interface GitLog {
+  commits: GitCommit[];
+  isShallow: boolean;
+  /** The log stopped at a shallow clone's boundary; older history wasn't fetched */
+  shallowBoundaryReached: boolean;
+}
+
+/** A page of git_log_paged, with the shallow-clone flags of GitLog */
+export interface GitLogPage extends Page<GitCommit> {
+  isShallow: boolean;
+  shallowBoundaryReached: boolean;
+}
+
 export interface PRCreationOptions {
   base: string;
   head: string;
@@ -49,6 +204,36 @@ export interface PRCreationOptions {
   labels?: string[];
 }
 
+export interface GhHostAuth {
+  host: string;
+  user: string | null;
+  /** The account gh uses for this host */
+  active: boolean;
+  /** False when gh reports the token as invalid */
+  loggedIn: boolean;
+  /** null when gh can't tell (fine-grained tokens, GH_TOKEN without scope headers) */
+  scopes: string[] | null;
+}
+
+export interface GhAuthStatus {
+  installed: boolean;
+  authenticated: boolean;
+  hosts: GhHostAuth[];
+}
+
+export type GhFeature = 'post_review' | 'merge_pr' | 'read_checks' | 'manage_labels';
+
+export interface GhPreflight {
+  feature: GhFeature;
+  ok: boolean;
+  requiredScopes: string[];
+  missingScopes: string[];
+  /** e.g. `gh auth refresh -h github.com -s repo,workflow` */
+  fixCommand: string | null;
+  /** Why the feature is unavailable, for a tooltip */
+  reason: string | null;
+}
+
 // Diff comment types for inline comments (Phase 4)
 export interface DiffComment {
   id: string;
@@ -64,3 +249,65 @@ export interface DiffComment {
 
 // View mode for diff viewer
 export type DiffViewMode = 'split' | 'unified';
+
+export interface PrContext {
+  branch: string;
+  base: string;
+  headSha: string;
+  baseSha: string;
+  commits: GitCommit[] | null;
+  /** Issue references from the branch name and commit messages; sources are "branch" or short hashes */
+  issues: { reference: string; sources: string[] }[] | null;
+  changes: DiffStats | null;
+  template: { path: string; content: string } | null;
+  reviewers: { owner: string; files: string[] }[] | null;
+  /** Sections that failed; the rest are still filled in */
+  errors: { section: string; message: string }[];
+}
+
+/** Where a commit author comes from: set for the workspace, git config, or mensa's placeholder */
+export type IdentitySource = 'workspace' | 'gitConfig' | 'fallback';
+
+export interface EffectiveIdentity {
+  name: string;
+  email: string;
+  source: IdentitySource;
+}
+
+export interface CommitResult {
+  oid: string;
+  /** Who the commit was authored as */
+  author: EffectiveIdentity;
+}
+
+export interface IdentityMismatch {
+  identity: EffectiveIdentity;
+  /** Authors of the last commits on HEAD, most commits first */
+  recentAuthors: { name: string; email: string; commits: number }[];
+  /** None of the recent commits used the identity's email */
+  mismatch: boolean;
+  message: string | null;
+}
+
+/** A file a destructive command overwrites or removes */
+export interface PreviewFile {
+  path: string;
+  /** Set when `path` isn't valid UTF-8 or NFC; pass back as rawPath */
+  rawPath: string | null;
+  /** Size of the content that is lost (0 for a file already missing) */
+  bytes: number;
+  /** The lost content is stored in git */
+  recoverable: boolean;
+}
+
+/** Exactly what a destructive command does, or with dryRun would do */
+export interface DestructionPreview {
+  filesRestored: PreviewFile[];
+  filesDeleted: PreviewFile[];
+  branchesDeleted: string[];
+  commitsOrphaned: string[];
+  /** Pass back as previewToken to refuse running a plan that changed since */
+  token: string;
+  /** False for a dry run */
+  executed: boolean;
+}
@@ -67,9 +67,21 @@ export interface ToolExecution {
   status: 'running' | 'completed' | 'error';
   input?: string;
   output?: string;
+  outputTruncated?: boolean;  // output is a preview; fetch the rest with getToolOutput
+  outputTotalBytes?: number;
   startedAt: Date;
   completedAt?: Date;
   parentSubagentId?: string;  // If this tool belongs to a subagent
+  hooks?: HookEvent[];        // Hooks that ran for this tool (from the session transcript)
+}
+
+export interface HookEvent {
+  hook: string;               // e.g. "PostToolUse", or "unknown"
+  tool: string | null;
+  toolUseId: string | null;
+  decision: 'started' | 'ran' | 'blocked' | 'error' | 'unknown';
+  output: string | null;
+  raw?: unknown;              // Original payload for unrecognised shapes
 }
 
 export interface SubagentGroup {
@@ -176,6 +188,23 @@ export interface AllowedPrompt {
   prompt: string;
 }
 
+/** One page of a paged list command; pass `nextCursor` back to get the next one */
+export interface Page<T> {
+  items: T[];
+  /** Items in the whole list, when the backend can tell without reading it all */
+  total: number | null;
+  nextCursor: string | null;
+  hasMore: boolean;
+}
+
+/** Error of the paged list commands; on the cursor kinds, start again from the first page */
+export type PageError =
+  | { kind: 'invalidCursor'; message: string }
+  | { kind: 'expiredCursor'; message: string }
+  | { kind: 'cursorMismatch'; message: string }
+  | { kind: 'permissionDenied'; path: string; suggestedFix: string; message: string }
+  | { kind: 'failed'; message: string };
+
 // Re-export session types from the store for convenience
 export type { SessionStatus, SessionState } from '$lib/stores/sessions.svelte';
 
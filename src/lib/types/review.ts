@@ -93,6 +93,8 @@ export interface PRInfo {
   headBranch: string;
   createdAt: string;
   updatedAt: string;
+  /** Fields gh sent in an unexpected shape and that fell back to a default */
+  schemaWarnings?: string[];
 }
 
 export interface PRListItem {
@@ -106,4 +108,8 @@ export interface PRListItem {
   updatedAt: string;
   url: string;
   isDraft: boolean;
+  /** Fields gh sent in an unexpected shape and that fell back to a default */
+  schemaWarnings?: string[];
+  /** Top-level gh fields nothing reads, kept for debugging */
+  extra?: Record<string, unknown>;
 }